@@ -0,0 +1,166 @@
+//! The fields a backend's answer about a compromised guild is known to have.
+//!
+//! [`Response::api_response`](crate::Response::api_response) stays the answer
+//! as it came in, since diffs, schema drift and the result files all need
+//! exactly that; [`GuildInfo`] is read from it for everything that wants to
+//! know what the answer says.
+
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::window::parse_date;
+use crate::Response;
+
+/// Keys each field has been seen under, in the order they're tried
+const NAME: &[&str] = &["name", "guild_name"];
+const ICON: &[&str] = &["icon", "icon_url", "iconURL"];
+const MEMBER_COUNT: &[&str] = &["member_count", "memberCount", "members"];
+const MESSAGE_COUNT: &[&str] = &[
+    "messages",
+    "message_count",
+    "messageCount",
+    "scraped_messages",
+];
+const FIRST_SEEN: &[&str] = &["first_seen", "firstSeen"];
+const LAST_SEEN: &[&str] = &["last_seen", "lastSeen"];
+/// Only sent to requests with an API key
+const SAMPLES: &[&str] = &["message_samples", "messageSamples", "samples"];
+const USERS: &[&str] = &["users", "user_stats", "userStats", "top_users"];
+
+/// One of the archived messages, as an authenticated answer shows it
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct MessageSample {
+    #[serde(default, alias = "channelId", skip_serializing_if = "Option::is_none")]
+    pub channel_id: Option<String>,
+    #[serde(
+        default,
+        alias = "authorId",
+        alias = "author",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub author_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+}
+
+/// How much of one member's activity in the guild was archived, from an
+/// authenticated answer
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct UserStats {
+    #[serde(alias = "user_id", alias = "userId")]
+    pub id: String,
+    #[serde(default, alias = "username", skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(
+        default,
+        alias = "messages",
+        alias = "messageCount",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub message_count: Option<u64>,
+}
+
+/// What an answer says about a guild in the dataset. A field under a name
+/// that isn't known, or with a value that doesn't fit, is kept in `extra`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(from = "Map<String, Value>")]
+pub struct GuildInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The icon's hash or URL, whichever the backend sends
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub member_count: Option<u64>,
+    /// Messages the scraper archived from the guild
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_seen: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<DateTime<Utc>>,
+    /// Some of the archived messages; only sent with an API key
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub message_samples: Vec<MessageSample>,
+    /// The members whose messages were archived; only sent with an API key
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub users: Vec<UserStats>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Takes the first of `keys` that `read` makes sense of out of `fields`
+fn take<T>(
+    fields: &mut Map<String, Value>,
+    keys: &[&str],
+    read: impl Fn(&Value) -> Option<T>,
+) -> Option<T> {
+    let (key, value) = keys
+        .iter()
+        .find_map(|key| Some((*key, read(fields.get(*key)?)?)))?;
+    fields.remove(key);
+    Some(value)
+}
+
+/// An RFC 3339 timestamp, a date, or seconds since the epoch
+fn timestamp(value: &Value) -> Option<DateTime<Utc>> {
+    match value {
+        Value::String(s) => parse_date(s).ok(),
+        Value::Number(n) => DateTime::from_timestamp(n.as_i64()?, 0),
+        _ => None,
+    }
+}
+
+fn string(value: &Value) -> Option<String> {
+    value.as_str().map(str::to_owned)
+}
+
+/// An array whose every item reads as `T`
+fn list<T: DeserializeOwned>(value: &Value) -> Option<Vec<T>> {
+    match value {
+        Value::Array(_) => serde_json::from_value(value.clone()).ok(),
+        _ => None,
+    }
+}
+
+impl From<Map<String, Value>> for GuildInfo {
+    fn from(mut fields: Map<String, Value>) -> Self {
+        Self {
+            name: take(&mut fields, NAME, string),
+            icon: take(&mut fields, ICON, string),
+            member_count: take(&mut fields, MEMBER_COUNT, Value::as_u64),
+            message_count: take(&mut fields, MESSAGE_COUNT, Value::as_u64),
+            first_seen: take(&mut fields, FIRST_SEEN, timestamp),
+            last_seen: take(&mut fields, LAST_SEEN, timestamp),
+            message_samples: take(&mut fields, SAMPLES, list).unwrap_or_default(),
+            users: take(&mut fields, USERS, list).unwrap_or_default(),
+            extra: fields,
+        }
+    }
+}
+
+impl GuildInfo {
+    /// What `api_response` says, if it's an object
+    pub fn of(api_response: &Value) -> Option<Self> {
+        match api_response {
+            Value::Object(fields) => Some(Self::from(fields.clone())),
+            _ => None,
+        }
+    }
+}
+
+impl Response {
+    /// What the answer says about the guild, when it has fields at all
+    pub fn guild_info(&self) -> Option<GuildInfo> {
+        GuildInfo::of(&self.api_response)
+    }
+
+    /// Number of archived messages, if the backend reported one
+    pub fn message_count(&self) -> Option<u64> {
+        self.guild_info()?.message_count
+    }
+}
@@ -0,0 +1,49 @@
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+
+use super::{fetch, parse_json, Backend, BackendFuture};
+use crate::CheckError;
+
+pub const DEFAULT_BASE_URL: &str = "https://kickthespy.pet";
+
+/// kickthespy.pet's bot lookup, which describes the scraper bot present in a
+/// guild and answers 404 when it doesn't know of one
+pub struct KickTheSpy {
+    base_url: String,
+}
+
+impl KickTheSpy {
+    /// `base_url` is the site root, without a trailing slash
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+        }
+    }
+}
+
+impl Default for KickTheSpy {
+    fn default() -> Self {
+        Self::new(DEFAULT_BASE_URL)
+    }
+}
+
+impl Backend for KickTheSpy {
+    fn name(&self) -> &str {
+        "kickthespy.pet"
+    }
+
+    fn url(&self, id: &str) -> String {
+        format!("{}/getBot?id={id}", self.base_url)
+    }
+
+    fn check<'a>(&'a self, client: &'a Client, id: &'a str) -> BackendFuture<'a> {
+        Box::pin(async move {
+            let (status, text) = fetch(client, &self.url(id)).await?;
+            match status {
+                StatusCode::NOT_FOUND => Ok(Value::Bool(false)),
+                status if status.is_success() => parse_json(&text),
+                status => Err(CheckError::HttpStatus(status)),
+            }
+        })
+    }
+}
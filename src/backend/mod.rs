@@ -0,0 +1,169 @@
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use futures_util::future::BoxFuture;
+#[cfg(target_arch = "wasm32")]
+use futures_util::future::LocalBoxFuture;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+#[cfg(not(target_arch = "wasm32"))]
+use reqwest::Response;
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+use tracing::debug;
+
+use crate::schema::Schema;
+use crate::CheckError;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod fixtures;
+mod kickthespy;
+#[cfg(not(target_arch = "wasm32"))]
+mod local;
+#[cfg(not(target_arch = "wasm32"))]
+mod simulated;
+mod spypet;
+mod template;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use fixtures::{Fixtures, FIXTURES};
+pub use kickthespy::KickTheSpy;
+#[cfg(not(target_arch = "wasm32"))]
+pub use local::{LocalDataset, LOCAL};
+#[cfg(not(target_arch = "wasm32"))]
+pub use simulated::{Simulated, SimulationProfile, SIMULATED};
+pub use spypet::SpyPet;
+pub use template::{JsonPath, TemplateError, UrlTemplate};
+
+/// What [`Backend::check`] returns. In the browser, requests are futures of
+/// the page's event loop, which can't be sent to other threads.
+#[cfg(not(target_arch = "wasm32"))]
+pub type BackendFuture<'a> = BoxFuture<'a, Result<Value, CheckError>>;
+#[cfg(target_arch = "wasm32")]
+pub type BackendFuture<'a> = LocalBoxFuture<'a, Result<Value, CheckError>>;
+
+/// A service that can tell whether a guild is in a scraper's dataset
+pub trait Backend: Send + Sync {
+    /// Recorded as the `source` of every result this backend produces
+    fn name(&self) -> &str;
+
+    /// The URL `check` requests for `id`
+    fn url(&self, id: &str) -> String;
+
+    /// The fields of this backend's answers, to notice when they change.
+    /// `None` if they aren't known.
+    fn schema(&self) -> Option<Schema> {
+        None
+    }
+
+    /// Looks up one guild. `Value::Bool(false)` means the guild isn't in the
+    /// dataset; anything else is the service's description of it.
+    fn check<'a>(&'a self, client: &'a Client, id: &'a str) -> BackendFuture<'a>;
+}
+
+/// The error a response is, before its body is read: 429s are
+/// [`CheckError::RateLimited`], challenges [`CheckError::Challenge`] and
+/// other 401s and 403s [`CheckError::Unauthorized`]. Every other status is
+/// left to the backend.
+fn refusal(status: StatusCode, headers: &HeaderMap) -> Option<CheckError> {
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = headers
+            .get(RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse().ok())
+            .map(Duration::from_secs);
+        return Some(CheckError::RateLimited { retry_after });
+    }
+
+    // Cloudflare's way of saying so, usually with a 403
+    let challenge = headers
+        .get("cf-mitigated")
+        .is_some_and(|v| v.as_bytes().eq_ignore_ascii_case(b"challenge"));
+    if challenge {
+        return Some(CheckError::Challenge(status));
+    }
+
+    if matches!(status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) {
+        return Some(CheckError::Unauthorized(status));
+    }
+    None
+}
+
+/// Requests `url`, turning refusals into errors (see [`refusal`]), and
+/// leaving every other status to the backend
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn fetch(client: &Client, url: &str) -> Result<(StatusCode, String), CheckError> {
+    crate::headers::sending();
+    let response = crate::headers::conditional(client.get(url)).send().await?;
+    let status = response.status();
+    tracing::Span::current().record("status", status.as_u16());
+    crate::headers::capture(status, response.headers());
+
+    // the body consumes the response, so headers are kept beforehand
+    let headers = crate::dump::capturing().then(|| response.headers().clone());
+
+    if let Some(err) = refusal(status, response.headers()) {
+        if let Some(headers) = &headers {
+            crate::dump::capture(url, status.as_u16(), headers, "");
+        }
+        return Err(err);
+    }
+
+    let limit = crate::check::BODY_LIMIT
+        .try_with(|limit| *limit)
+        .ok()
+        .flatten();
+    let text = match read_body(response, limit).await {
+        Ok(body) => String::from_utf8_lossy(&body).into_owned(),
+        Err(err) => {
+            if let Some(headers) = &headers {
+                crate::dump::capture(url, status.as_u16(), headers, "");
+            }
+            return Err(err);
+        }
+    };
+    debug!(%status, size=%text.len(), "got response");
+    if let Some(headers) = &headers {
+        crate::dump::capture(url, status.as_u16(), headers, &text);
+    }
+    Ok((status, text))
+}
+
+/// Like the native `fetch`, through the browser's fetch, which leaves
+/// headers, dumps and body limits to the page
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn fetch(client: &Client, url: &str) -> Result<(StatusCode, String), CheckError> {
+    let response = client.get(url).send().await?;
+    let status = response.status();
+    if let Some(err) = refusal(status, response.headers()) {
+        return Err(err);
+    }
+    let text = response.text().await?;
+    debug!(%status, size=%text.len(), "got response");
+    Ok((status, text))
+}
+
+/// Reads the body a chunk at a time, giving up as soon as it's past `limit`
+#[cfg(not(target_arch = "wasm32"))]
+async fn read_body(mut response: Response, limit: Option<u64>) -> Result<Vec<u8>, CheckError> {
+    let limit = limit.unwrap_or(u64::MAX);
+    if response
+        .content_length()
+        .is_some_and(|length| length > limit)
+    {
+        return Err(CheckError::TooLarge { limit });
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        let _ =
+            crate::check::BODY_BYTES.try_with(|bytes| bytes.set(bytes.get() + chunk.len() as u64));
+        if (body.len() + chunk.len()) as u64 > limit {
+            return Err(CheckError::TooLarge { limit });
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+pub(crate) fn parse_json(text: &str) -> Result<Value, CheckError> {
+    serde_json::from_str(text).map_err(|_| CheckError::bad_body(text))
+}
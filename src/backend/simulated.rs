@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use futures_util::future::BoxFuture;
+use reqwest::{Client, StatusCode};
+use serde_json::{json, Value};
+
+use super::Backend;
+use crate::CheckError;
+
+/// The `source` of simulated results, so they can't pass for real ones
+pub const SIMULATED: &str = "simulated";
+
+/// The first made-up guild ID; real IDs this low don't exist
+const FIRST_ID: u64 = 100_000_000_000_000_000;
+
+/// How the stub behaves. Fractions are of all requests, from 0 to 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SimulationProfile {
+    /// Each answer takes between these two long
+    pub latency: (Duration, Duration),
+    /// Of the guilds, how many are in the dataset
+    pub compromised: f64,
+    /// Requests answered with a 429
+    pub rate_limited: f64,
+    /// What the 429s ask to wait
+    pub retry_after: Option<Duration>,
+    /// Requests answered with a 500
+    pub server_errors: f64,
+}
+
+impl SimulationProfile {
+    /// Quick answers, no errors
+    pub fn friendly() -> Self {
+        Self {
+            latency: (Duration::from_millis(50), Duration::from_millis(150)),
+            compromised: 0.05,
+            rate_limited: 0.0,
+            retry_after: None,
+            server_errors: 0.0,
+        }
+    }
+
+    /// Slow answers and a lot of rate limiting, like an API under load
+    pub fn harsh() -> Self {
+        Self {
+            latency: (Duration::from_millis(300), Duration::from_millis(1200)),
+            compromised: 0.05,
+            rate_limited: 0.2,
+            retry_after: Some(Duration::from_secs(5)),
+            server_errors: 0.02,
+        }
+    }
+
+    /// Answers anywhere from instant to slow, and server errors
+    pub fn flaky() -> Self {
+        Self {
+            latency: (Duration::from_millis(10), Duration::from_millis(3000)),
+            compromised: 0.05,
+            rate_limited: 0.02,
+            retry_after: Some(Duration::from_secs(1)),
+            server_errors: 0.1,
+        }
+    }
+}
+
+/// A stand-in for the API that makes its answers up, for trying out
+/// pacing and output without sending anything anywhere. Answers follow
+/// `profile`; whether a guild is compromised depends only on its ID, so it
+/// stays the same between checks.
+pub struct Simulated {
+    profile: SimulationProfile,
+    requests: AtomicU64,
+}
+
+impl Simulated {
+    pub fn new(profile: SimulationProfile) -> Self {
+        Self {
+            profile,
+            requests: AtomicU64::new(0),
+        }
+    }
+
+    /// `n` made-up guilds to check against it, id → name. The names say
+    /// they're simulated.
+    pub fn guilds(n: usize) -> BTreeMap<String, String> {
+        (1..=n as u64)
+            .map(|i| {
+                let id = (FIRST_ID + i).to_string();
+                (id, format!("Simulated server {i}"))
+            })
+            .collect()
+    }
+}
+
+/// A well-mixed number from 0 to 1 for `seed` (splitmix64)
+fn roll(seed: u64) -> f64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+impl Backend for Simulated {
+    fn name(&self) -> &str {
+        SIMULATED
+    }
+
+    fn url(&self, id: &str) -> String {
+        format!("simulated://servers/{id}")
+    }
+
+    fn check<'a>(
+        &'a self,
+        _client: &'a Client,
+        id: &'a str,
+    ) -> BoxFuture<'a, Result<Value, CheckError>> {
+        Box::pin(async move {
+            let guild: u64 = id.parse().unwrap_or_default();
+            let request = self.requests.fetch_add(1, Ordering::Relaxed);
+            let seed = guild ^ request.wrapping_mul(0x2545_f491_4f6c_dd1d);
+
+            let (min, max) = self.profile.latency;
+            let latency = min + max.saturating_sub(min).mul_f64(roll(seed));
+            tokio::time::sleep(latency).await;
+
+            let outcome = roll(seed.rotate_left(17));
+            let (status, answer) = if outcome < self.profile.rate_limited {
+                (StatusCode::TOO_MANY_REQUESTS, None)
+            } else if outcome < self.profile.rate_limited + self.profile.server_errors {
+                (StatusCode::INTERNAL_SERVER_ERROR, None)
+            } else if roll(guild) < self.profile.compromised {
+                let messages = (roll(guild.rotate_left(29)) * 5000.0) as u64 + 1;
+                let answer = json!({ "name": "Simulated server", "messages": messages });
+                (StatusCode::OK, Some(answer))
+            } else {
+                (StatusCode::OK, Some(Value::Bool(false)))
+            };
+            tracing::Span::current().record("status", status.as_u16());
+
+            let Some(answer) = answer else {
+                return Err(match status {
+                    StatusCode::TOO_MANY_REQUESTS => CheckError::RateLimited {
+                        retry_after: self.profile.retry_after,
+                    },
+                    status => CheckError::HttpStatus(status),
+                });
+            };
+            let size = answer.to_string().len() as u64;
+            let _ = crate::check::BODY_BYTES.try_with(|bytes| bytes.set(bytes.get() + size));
+            Ok(answer)
+        })
+    }
+}
@@ -0,0 +1,71 @@
+use reqwest::Client;
+
+use super::{fetch, parse_json, Backend, BackendFuture};
+use crate::schema::Schema;
+use crate::{CheckError, Kind};
+
+pub const DEFAULT_BASE_URL: &str = "https://api.spy.pet";
+
+/// spy.pet's `/servers/{id}` endpoint, or `/users/{id}` or
+/// `/channels/{id}`, which answer `false` for IDs it doesn't know about
+pub struct SpyPet {
+    base_url: String,
+    kind: Kind,
+}
+
+impl SpyPet {
+    /// `base_url` is the API root, without a trailing slash
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+            kind: Kind::Guild,
+        }
+    }
+
+    /// Looks up `kind` instead of servers
+    pub fn with_kind(self, kind: Kind) -> Self {
+        Self { kind, ..self }
+    }
+}
+
+impl Default for SpyPet {
+    fn default() -> Self {
+        Self::new(DEFAULT_BASE_URL)
+    }
+}
+
+impl Backend for SpyPet {
+    fn name(&self) -> &str {
+        "spy.pet"
+    }
+
+    fn url(&self, id: &str) -> String {
+        match self.kind {
+            Kind::Guild => format!("{}/servers/{id}", self.base_url),
+            Kind::User => format!("{}/users/{id}", self.base_url),
+            Kind::Channel => format!("{}/channels/{id}", self.base_url),
+        }
+    }
+
+    fn schema(&self) -> Option<Schema> {
+        // what user and channel answers carry isn't known yet
+        if self.kind != Kind::Guild {
+            return None;
+        }
+        Some(Schema {
+            required: &["name", "messages"],
+            // the last two only with an API key
+            optional: &["id", "icon", "message_samples", "users"],
+        })
+    }
+
+    fn check<'a>(&'a self, client: &'a Client, id: &'a str) -> BackendFuture<'a> {
+        Box::pin(async move {
+            let (status, text) = fetch(client, &self.url(id)).await?;
+            if !status.is_success() {
+                return Err(CheckError::HttpStatus(status));
+            }
+            parse_json(&text)
+        })
+    }
+}
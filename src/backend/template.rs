@@ -0,0 +1,153 @@
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use reqwest::Client;
+use serde_json::Value;
+use thiserror::Error;
+
+use super::{fetch, parse_json, Backend, BackendFuture};
+use crate::CheckError;
+
+#[derive(Error, Debug)]
+pub enum TemplateError {
+    #[error("url template must contain an {{id}} placeholder")]
+    MissingPlaceholder,
+
+    #[error("invalid expression at {0:?}")]
+    BadExpression(String),
+}
+
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// A path into a JSON document, written either jq-style (`.guild.tracked`,
+/// `.bots[0]`) or as a JSON pointer (`/guild/tracked`)
+pub struct JsonPath(Vec<Segment>);
+
+impl JsonPath {
+    pub fn parse(expr: &str) -> Result<Self, TemplateError> {
+        let expr = expr.trim();
+        if expr.starts_with('/') {
+            return Ok(Self(
+                expr.split('/')
+                    .skip(1)
+                    .map(|part| {
+                        let part = part.replace("~1", "/").replace("~0", "~");
+                        match part.parse() {
+                            Ok(i) => Segment::Index(i),
+                            Err(_) => Segment::Key(part),
+                        }
+                    })
+                    .collect(),
+            ));
+        }
+
+        let mut segments = Vec::new();
+        let mut rest = expr.strip_prefix('$').unwrap_or(expr);
+        if rest == "." {
+            return Ok(Self(segments));
+        }
+        while !rest.is_empty() {
+            let bad = || TemplateError::BadExpression(rest.to_owned());
+            if let Some(after) = rest.strip_prefix('.') {
+                let end = after.find(['.', '[']).unwrap_or(after.len());
+                if end == 0 {
+                    return Err(bad());
+                }
+                segments.push(Segment::Key(after[..end].to_owned()));
+                rest = &after[end..];
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let end = after.find(']').ok_or_else(bad)?;
+                let inner = &after[..end];
+                let segment = match inner.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+                    Some(key) => Segment::Key(key.to_owned()),
+                    None => Segment::Index(inner.parse().map_err(|_| bad())?),
+                };
+                segments.push(segment);
+                rest = &after[end + 1..];
+            } else {
+                return Err(bad());
+            }
+        }
+        Ok(Self(segments))
+    }
+
+    /// The value at this path, or `Value::Null` if it doesn't exist
+    pub fn select<'a>(&self, value: &'a Value) -> &'a Value {
+        let mut current = value;
+        for segment in &self.0 {
+            let next = match segment {
+                Segment::Key(key) => current.get(key),
+                Segment::Index(i) => current.get(i),
+            };
+            match next {
+                Some(v) => current = v,
+                None => return &Value::Null,
+            }
+        }
+        current
+    }
+}
+
+/// Like jq, only `null` and `false` are falsy
+fn truthy(value: &Value) -> bool {
+    !matches!(value, Value::Null | Value::Bool(false))
+}
+
+/// An ad hoc backend: requests a URL built from a template and decides
+/// whether the guild is compromised by evaluating an expression on the body
+pub struct UrlTemplate {
+    template: String,
+    matcher: Option<JsonPath>,
+}
+
+impl UrlTemplate {
+    /// `template` must contain `{id}`, which is replaced with the URL-encoded
+    /// guild ID. Without `matcher`, the whole body is tested for truthiness.
+    pub fn new(template: impl Into<String>, matcher: Option<&str>) -> Result<Self, TemplateError> {
+        let template = template.into();
+        Self::validate(&template)?;
+        Ok(Self {
+            template,
+            matcher: matcher.map(JsonPath::parse).transpose()?,
+        })
+    }
+
+    pub fn validate(template: &str) -> Result<(), TemplateError> {
+        if template.contains("{id}") {
+            Ok(())
+        } else {
+            Err(TemplateError::MissingPlaceholder)
+        }
+    }
+}
+
+impl Backend for UrlTemplate {
+    fn name(&self) -> &str {
+        &self.template
+    }
+
+    fn url(&self, id: &str) -> String {
+        let id = utf8_percent_encode(id, NON_ALPHANUMERIC).to_string();
+        self.template.replace("{id}", &id)
+    }
+
+    fn check<'a>(&'a self, client: &'a Client, id: &'a str) -> BackendFuture<'a> {
+        Box::pin(async move {
+            let (status, text) = fetch(client, &self.url(id)).await?;
+            if !status.is_success() {
+                return Err(CheckError::HttpStatus(status));
+            }
+            let body = parse_json(&text)?;
+            let selected = match &self.matcher {
+                Some(matcher) => matcher.select(&body),
+                None => &body,
+            };
+            if truthy(selected) {
+                Ok(body)
+            } else {
+                Ok(Value::Bool(false))
+            }
+        })
+    }
+}
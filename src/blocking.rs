@@ -0,0 +1,35 @@
+//! A synchronous wrapper around [`check_guilds`](crate::check_guilds), for
+//! callers that don't run an async runtime of their own.
+//!
+//! ```no_run
+//! use std::collections::BTreeMap;
+//!
+//! use spy_pet_checker::{blocking, CheckOptions};
+//!
+//! let guilds = BTreeMap::from([("1234567890123456789".to_owned(), "My server".to_owned())]);
+//! let report = blocking::check_guilds(guilds, &CheckOptions::default()).unwrap();
+//! for result in &report.results {
+//!     println!("{}: {}", result.guild_name, result.api_response);
+//! }
+//! ```
+
+use std::io;
+
+use crate::{CheckOptions, RunReport};
+
+/// Runs [`crate::check_guilds`] to completion on a private current-thread
+/// runtime.
+///
+/// # Panics
+///
+/// Panics when called from within an async runtime, like
+/// `reqwest::blocking` does.
+pub fn check_guilds(
+    guilds: impl IntoIterator<Item = (String, String)>,
+    options: &CheckOptions,
+) -> io::Result<RunReport> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    Ok(runtime.block_on(crate::check_guilds(guilds, options)))
+}
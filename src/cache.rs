@@ -0,0 +1,232 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, warn};
+
+/// Bumped whenever the file layout changes; older files are ignored
+const VERSION: u32 = 1;
+
+/// A backend's last answer about a guild
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CacheEntry {
+    pub api_response: Value,
+    pub checked_at: DateTime<Utc>,
+    /// What to ask the backend whether the answer changed with, once it's
+    /// no longer fresh
+    #[serde(default, flatten)]
+    pub validators: Validators,
+}
+
+/// The `ETag` and `Last-Modified` headers an answer came with
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Validators {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct CacheFile {
+    version: u32,
+    /// Backend name → guild ID → answer
+    entries: Entries,
+}
+
+/// A cached answer, as listed by [`ResultCache::entries`]
+#[derive(Clone, Debug)]
+pub struct CachedAnswer {
+    pub source: String,
+    pub guild_id: String,
+    pub entry: CacheEntry,
+    /// Size of the answer as stored, in bytes
+    pub size: u64,
+}
+
+impl CachedAnswer {
+    pub fn age(&self) -> Duration {
+        (Utc::now() - self.entry.checked_at)
+            .to_std()
+            .unwrap_or_default()
+    }
+}
+
+/// Answers from earlier runs, so guilds checked recently don't have to be
+/// asked about again.
+///
+/// Several processes can use the same file: saving only adds the answers
+/// this one got, to whatever is on disk by then, so entries removed in the
+/// meantime stay removed unless they were checked again.
+pub struct ResultCache {
+    path: PathBuf,
+    ttl: Duration,
+    file: Mutex<CacheFile>,
+    /// Answers got since loading, to be saved
+    new: Mutex<Entries>,
+}
+
+type Entries = BTreeMap<String, BTreeMap<String, CacheEntry>>;
+
+impl ResultCache {
+    /// Loads the cache at `path`, whose entries are fresh for `ttl`. A
+    /// missing, unreadable or outdated file is an empty cache.
+    pub fn load(path: impl Into<PathBuf>, ttl: Duration) -> Self {
+        let path = path.into();
+        let file = match read(&path) {
+            Ok(Some(file)) if file.version == VERSION => file,
+            Ok(Some(file)) => {
+                debug!(
+                    version = file.version,
+                    "ignoring cache from another version"
+                );
+                CacheFile::default()
+            }
+            Ok(None) => CacheFile::default(),
+            Err(err) => {
+                warn!(%err, "ignoring unreadable cache {}", path.display());
+                CacheFile::default()
+            }
+        };
+        Self {
+            path,
+            ttl,
+            file: Mutex::new(file),
+            new: Mutex::default(),
+        }
+    }
+
+    /// The cached answer of `source` about `id`, if it's fresh
+    pub fn get(&self, source: &str, id: &str) -> Option<CacheEntry> {
+        let file = self.file.lock().expect("cache lock poisoned");
+        let entry = file.entries.get(source)?.get(id)?;
+        let age = (Utc::now() - entry.checked_at).to_std().unwrap_or_default();
+        (age < self.ttl).then(|| entry.clone())
+    }
+
+    /// The cached answer of `source` about `id` whatever its age, if it
+    /// came with validators to ask whether it changed
+    pub fn stale(&self, source: &str, id: &str) -> Option<CacheEntry> {
+        let file = self.file.lock().expect("cache lock poisoned");
+        let entry = file.entries.get(source)?.get(id)?;
+        (!entry.validators.is_empty()).then(|| entry.clone())
+    }
+
+    pub fn insert(&self, source: &str, id: &str, entry: CacheEntry) {
+        let mut file = self.file.lock().expect("cache lock poisoned");
+        file.entries
+            .entry(source.to_owned())
+            .or_default()
+            .insert(id.to_owned(), entry.clone());
+        let mut new = self.new.lock().expect("cache lock poisoned");
+        new.entry(source.to_owned())
+            .or_default()
+            .insert(id.to_owned(), entry);
+    }
+
+    /// Adds the answers got since loading to the file on disk, replacing it
+    /// atomically
+    pub fn save(&self) -> io::Result<()> {
+        let new = std::mem::take(&mut *self.new.lock().expect("cache lock poisoned"));
+        update(&self.path, |entries| {
+            for (source, answers) in new {
+                entries.entry(source).or_default().extend(answers);
+            }
+        })
+    }
+
+    /// Every answer in the cache at `path`, whatever its age, by backend and
+    /// then guild ID
+    pub fn entries(path: &Path) -> io::Result<Vec<CachedAnswer>> {
+        let entries = match read(path)? {
+            Some(file) if file.version == VERSION => file.entries,
+            _ => Entries::new(),
+        };
+        let mut answers = Vec::new();
+        for (source, guilds) in entries {
+            for (guild_id, entry) in guilds {
+                let size = serde_json::to_vec(&entry.api_response)?.len() as u64;
+                answers.push(CachedAnswer {
+                    source: source.clone(),
+                    guild_id,
+                    entry,
+                    size,
+                });
+            }
+        }
+        Ok(answers)
+    }
+
+    /// Removes every backend's answer about `id` from the cache at `path`.
+    /// Returns how many there were.
+    pub fn remove(path: &Path, id: &str) -> io::Result<usize> {
+        let mut removed = 0;
+        update(path, |entries| {
+            for guilds in entries.values_mut() {
+                removed += guilds.remove(id).is_some() as usize;
+            }
+            entries.retain(|_, guilds| !guilds.is_empty());
+        })?;
+        Ok(removed)
+    }
+
+    /// Removes the answers older than `older_than` from the cache at
+    /// `path`, or all of them. Returns how many there were.
+    pub fn clear(path: &Path, older_than: Option<Duration>) -> io::Result<usize> {
+        let mut removed = 0;
+        update(path, |entries| {
+            let now = Utc::now();
+            for guilds in entries.values_mut() {
+                let before = guilds.len();
+                guilds.retain(|_, entry| {
+                    let age = (now - entry.checked_at).to_std().unwrap_or_default();
+                    older_than.is_some_and(|older_than| age <= older_than)
+                });
+                removed += before - guilds.len();
+            }
+            entries.retain(|_, guilds| !guilds.is_empty());
+        })?;
+        Ok(removed)
+    }
+}
+
+/// Changes the cache file at `path` with `f`, holding a lock on it so other
+/// processes changing it at the same time wait their turn, and replaces it
+/// atomically so readers never see half of it
+fn update(path: &Path, f: impl FnOnce(&mut Entries)) -> io::Result<()> {
+    let lock = File::create(path.with_extension("json.lock"))?;
+    lock.lock()?;
+    let mut file = match read(path) {
+        Ok(Some(file)) if file.version == VERSION => file,
+        Ok(_) => CacheFile::default(),
+        Err(err) => {
+            warn!(%err, "replacing unreadable cache {}", path.display());
+            CacheFile::default()
+        }
+    };
+    f(&mut file.entries);
+    file.version = VERSION;
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_vec(&file)?)?;
+    std::fs::rename(tmp, path)
+}
+
+fn read(path: &Path) -> io::Result<Option<CacheFile>> {
+    match std::fs::read(path) {
+        Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
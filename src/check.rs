@@ -0,0 +1,1130 @@
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context as TaskContext, Poll};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use futures_util::{Stream, StreamExt};
+use reqwest::header::HeaderMap;
+use reqwest::{Certificate, Client, StatusCode};
+use serde_json::Value;
+use tokio::sync::Semaphore;
+use tokio::task::{self, JoinSet};
+use tokio_util::sync::CancellationToken;
+use tracing::instrument::Instrument;
+use tracing::{debug, error, field, info, info_span, warn, Span};
+
+use crate::backend::{Backend, SpyPet};
+use crate::cache::{CacheEntry, ResultCache};
+use crate::client::{build_client, build_keyless_client, ApiKey};
+use crate::deep::DeepScan;
+use crate::dns::DohResolver;
+use crate::dump::ResponseDump;
+use crate::error::CheckError;
+use crate::headers::{self, CAPTURED};
+use crate::members::MemberScanner;
+use crate::pacing::{AdaptiveConcurrency, Batches, Spacing};
+use crate::proxy::Proxy;
+use crate::report::ApiKeyStatus;
+use crate::result::{
+    classify, CheckResult, FailedCheck, Kind, RequestStats, Response, Status, Unparseable,
+    Verification,
+};
+use crate::retry::RetryPolicy;
+use crate::schema::DriftCheck;
+use crate::tor::CircuitRotation;
+use crate::verify::Verifier;
+use crate::warnings::{Condition, Warnings};
+use crate::web::{Confidence, Fallback, WebFallback, WEB_FALLBACK};
+use crate::{Performance, RunReport, SchemaDrift};
+
+#[derive(Clone)]
+pub struct CheckOptions {
+    /// Maximum number of requests in flight at once
+    pub concurrency: usize,
+    /// Shared with other runs to cap the requests in flight across all of
+    /// them; takes precedence over `concurrency`
+    pub limiter: Option<Arc<Semaphore>>,
+    /// Lowers the concurrency while the API is struggling, on top of the
+    /// concurrency limit
+    pub adaptive: Option<Arc<AdaptiveConcurrency>>,
+    /// Services to ask about each guild; every guild is checked against all
+    /// of them
+    pub backends: Vec<Arc<dyn Backend>>,
+    /// Deadline for each request, including reading the body
+    pub timeout: Option<Duration>,
+    /// Tries failed requests again. The waits in between keep the check's
+    /// place under the concurrency limit, so a struggling API gets fewer
+    /// requests meanwhile.
+    pub retry: RetryPolicy,
+    /// Once every guild has been checked, checks the ones that failed for
+    /// a reason worth retrying again, this many times, each pass at half the
+    /// concurrency of the one before. Only what still fails after the last
+    /// pass is yielded as failed.
+    pub retry_passes: u32,
+    /// Extra root certificates to trust
+    pub ca_certs: Vec<Certificate>,
+    /// Accept invalid TLS certificates and hostnames
+    pub insecure: bool,
+    /// Host names to connect to the given addresses, without looking them up
+    pub resolve: Vec<(String, IpAddr)>,
+    /// Looks up every other host name; the system resolver does otherwise
+    pub doh: Option<DohResolver>,
+    /// Sends every request through this proxy. Without one, the proxies in
+    /// `HTTP_PROXY` and `HTTPS_PROXY` are used.
+    pub proxy: Option<Proxy>,
+    /// Asks Tor for new circuits as the requests go out, for a `proxy`
+    /// that's Tor. Only connections opened after a rotation use the new
+    /// circuit, so it works best with `pool_size` 0.
+    pub tor: Option<Arc<CircuitRotation>>,
+    /// Sent with every request to the backends, and nothing else
+    pub api_key: Option<ApiKey>,
+    /// Extra headers for every request to the backends, like the API key
+    pub headers: HeaderMap,
+    /// Sent with every request instead of [`USER_AGENT`](crate::USER_AGENT)
+    pub user_agent: Option<String>,
+    /// Sends the requests to the backends instead of a client built from the
+    /// options above, which aren't applied to it
+    pub client: Option<Client>,
+    /// Like `client`, for requests to anything but the backends, like
+    /// Discord. It mustn't send the API key.
+    pub keyless_client: Option<Client>,
+    /// Idle connections kept open to each host for later requests; reqwest's
+    /// default, no limit, if `None`
+    pub pool_size: Option<usize>,
+    /// Stops the run when cancelled: checks that haven't finished are dropped
+    pub cancel: CancellationToken,
+    /// Winds the run down when cancelled: checks still waiting to send their
+    /// request are dropped, the ones in flight are left to finish
+    pub drain: CancellationToken,
+    /// Answers guilds checked recently from here, and stores new answers in it
+    pub cache: Option<Arc<ResultCache>>,
+    /// Keeps the raw body of every response
+    pub dump: Option<Arc<ResponseDump>>,
+    /// Asks for more about every compromised guild
+    pub deep_scan: Option<Arc<DeepScan>>,
+    /// Reads the website about guilds the API failed to answer about
+    pub web_fallback: Option<Arc<WebFallback>>,
+    /// Asks again about every guild found compromised, to mark whether the
+    /// second answer agrees
+    pub verify: Option<Arc<Verifier>>,
+    /// Reads every guild's member list, looking for known scraper bots. Runs
+    /// once per guild, outside the concurrency limit, whatever the backends
+    /// answer.
+    pub member_scan: Option<Arc<MemberScanner>>,
+    /// Sends requests in batches with cooldowns in between, on top of the
+    /// concurrency limit
+    pub batches: Option<Arc<Batches>>,
+    /// Keeps the requests a delay apart, retries included, on top of the
+    /// concurrency limit
+    pub spacing: Option<Arc<Spacing>>,
+    /// Keep the interesting headers of each answer in its result. They're
+    /// logged at debug level either way.
+    pub keep_headers: bool,
+    /// The most bytes of a response body read before the check fails with
+    /// [`CheckError::TooLarge`]. Unlimited if `None`.
+    pub max_response_size: Option<u64>,
+    /// What the IDs checked are. The backends have to be asking about the
+    /// same kind; results are only marked with it.
+    pub kind: Kind,
+    /// Where answers that can only be worked around are reported. When
+    /// strict, the guild they came up for fails instead.
+    pub warnings: Arc<Warnings>,
+}
+
+impl Default for CheckOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 1,
+            limiter: None,
+            adaptive: None,
+            backends: vec![Arc::new(SpyPet::default())],
+            timeout: None,
+            retry: RetryPolicy::NONE,
+            retry_passes: 0,
+            ca_certs: Vec::new(),
+            insecure: false,
+            resolve: Vec::new(),
+            doh: None,
+            proxy: None,
+            tor: None,
+            api_key: None,
+            headers: HeaderMap::new(),
+            user_agent: None,
+            client: None,
+            keyless_client: None,
+            pool_size: None,
+            cancel: CancellationToken::new(),
+            drain: CancellationToken::new(),
+            cache: None,
+            dump: None,
+            deep_scan: None,
+            web_fallback: None,
+            verify: None,
+            member_scan: None,
+            batches: None,
+            spacing: None,
+            keep_headers: false,
+            max_response_size: None,
+            kind: Kind::Guild,
+            warnings: Arc::default(),
+        }
+    }
+}
+
+tokio::task_local! {
+    /// Response body bytes read by the check running in this task
+    pub(crate) static BODY_BYTES: Cell<u64>;
+
+    /// The most body bytes the check running in this task reads
+    pub(crate) static BODY_LIMIT: Option<u64>;
+}
+
+/// Where one check spent its time
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Timing {
+    /// Waiting for a slot under the concurrency limit
+    pub queued: Duration,
+    /// From sending the request to having read the whole response
+    pub request: Duration,
+    /// Body bytes received; only counted for the built-in backends
+    pub bytes: u64,
+    /// The lowest remaining rate limit quota the responses reported, if they
+    /// said
+    pub rate_limit_remaining: Option<u64>,
+    /// Requests sent, retries included
+    pub attempts: u32,
+}
+
+/// How many checks are spawned ahead of the ones running, at least
+const SPAWN_AHEAD: usize = 256;
+
+/// Guilds added to a [`CheckStream`] that don't have a task yet
+struct Queued {
+    guilds: VecDeque<(String, String)>,
+    options: Arc<CheckOptions>,
+    sema: Arc<Semaphore>,
+    pass: Pass,
+}
+
+/// Which retry pass checks are part of
+#[derive(Default)]
+struct Pass {
+    /// 0 for the first time through
+    number: u32,
+    /// The pass's own concurrency limit, on top of the usual one
+    limit: Option<Arc<Semaphore>>,
+    /// How the check went in the pass before
+    previous: Option<FailedCheck>,
+}
+
+struct Pending {
+    span: Span,
+    guild_id: String,
+    guild_name: String,
+    source: String,
+    /// Which of the options' backends is asked, and with what
+    backend: usize,
+    options: Arc<CheckOptions>,
+    /// The retry pass, whose previous failure is yielded if the check
+    /// doesn't finish
+    pass: Pass,
+}
+
+/// A failed check waiting for the next retry pass
+struct Retry {
+    failed: FailedCheck,
+    backend: usize,
+    options: Arc<CheckOptions>,
+}
+
+/// Results of a run, yielded in completion order.
+///
+/// Dropping the stream aborts every check that hasn't finished yet.
+pub struct CheckStream {
+    /// Cached answers have no timing
+    join_set: JoinSet<Option<(CheckResult, Option<Timing>)>>,
+    /// What each task is checking, to say which guild a panic came from
+    pending: HashMap<task::Id, Pending>,
+    /// Spawned a window at a time as results come in
+    queued: VecDeque<Queued>,
+    started: Instant,
+    timings: Vec<Timing>,
+    drift: DriftCheck,
+    warnings: Arc<Warnings>,
+    /// Failed checks held back for the next retry pass
+    retries: Vec<Retry>,
+    /// Failed checks whose retry pass was cancelled, yielded as they were
+    given_up: VecDeque<FailedCheck>,
+    /// The pass running, 0 for the first time through
+    pass: u32,
+    /// Whether the end of the stream has been reached and reported
+    finished: bool,
+}
+
+impl CheckStream {
+    /// Latency and throughput of the checks yielded so far
+    pub fn performance(&self) -> Performance {
+        Performance::compute(&self.timings, self.started.elapsed())
+    }
+
+    /// How the answers so far differed from what the backends used to send
+    pub fn schema_drift(&self) -> Vec<SchemaDrift> {
+        self.drift.drift()
+    }
+
+    fn warn_drift(&self) {
+        for drift in self.drift.drift() {
+            if !drift.unknown_fields.is_empty() {
+                let fields: Vec<&str> = drift.unknown_fields.iter().map(String::as_str).collect();
+                self.warnings.warn(
+                    Condition::SchemaDrift,
+                    format!(
+                        "{} now returns fields {} that this version doesn't understand, an update may be needed",
+                        drift.source,
+                        fields.join(", ")
+                    ),
+                );
+            }
+            if !drift.missing_fields.is_empty() {
+                let fields: Vec<&str> = drift.missing_fields.iter().map(String::as_str).collect();
+                self.warnings.warn(
+                    Condition::SchemaDrift,
+                    format!(
+                        "{} no longer always returns fields {}, an update may be needed",
+                        drift.source,
+                        fields.join(", ")
+                    ),
+                );
+            }
+        }
+    }
+}
+
+impl Stream for CheckStream {
+    type Item = CheckResult;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            self.top_up();
+            if let Some(failed) = self.given_up.pop_front() {
+                return Poll::Ready(Some(Err(failed)));
+            }
+            return match self.join_set.poll_join_next_with_id(cx) {
+                Poll::Ready(Some(Ok((id, Some((result, timing)))))) => {
+                    let pending = self
+                        .pending
+                        .remove(&id)
+                        .expect("every task is tracked until it finishes");
+                    self.timings.extend(timing);
+                    match result {
+                        Ok(response) => {
+                            self.drift.record(&response);
+                            Poll::Ready(Some(Ok(response)))
+                        }
+                        Err(failed) => match self.hold(failed, &pending) {
+                            Some(failed) => Poll::Ready(Some(Err(failed))),
+                            None => continue,
+                        },
+                    }
+                }
+                // cancelled through the token
+                Poll::Ready(Some(Ok((id, None)))) => {
+                    let previous = self.pending.remove(&id).and_then(|p| p.pass.previous);
+                    match previous {
+                        Some(failed) => Poll::Ready(Some(Err(failed))),
+                        None => continue,
+                    }
+                }
+                Poll::Ready(Some(Err(err))) if err.is_panic() => {
+                    let pending = self
+                        .pending
+                        .remove(&err.id())
+                        .expect("every task is tracked until it finishes");
+                    let message = panic_message(err.into_panic());
+                    pending.span.in_scope(|| {
+                        pending.span.record("outcome", "panic");
+                        error!(%message, "check panicked");
+                    });
+                    Poll::Ready(Some(Err(FailedCheck::new(
+                        pending.guild_id,
+                        pending.guild_name,
+                        pending.source,
+                        1,
+                        CheckError::Panic(message),
+                    ))))
+                }
+                // aborted checks have nothing to report
+                Poll::Ready(Some(Err(err))) => {
+                    self.pending.remove(&err.id());
+                    continue;
+                }
+                Poll::Ready(None) => {
+                    if self.start_pass() {
+                        continue;
+                    }
+                    // a pass that can't start leaves them failed
+                    if let Some(retry) = self.retries.pop() {
+                        return Poll::Ready(Some(Err(retry.failed)));
+                    }
+                    if !self.finished {
+                        self.finished = true;
+                        self.warn_drift();
+                    }
+                    Poll::Ready(None)
+                }
+                Poll::Pending => Poll::Pending,
+            };
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let queued: usize = self
+            .queued
+            .iter()
+            .map(|queued| queued.guilds.len() * queued.options.backends.len())
+            .sum();
+        let held = self.retries.len() + self.given_up.len();
+        (
+            self.join_set.len(),
+            Some(self.join_set.len() + queued + held),
+        )
+    }
+}
+
+/// A check that fails after all, because of a `condition` not allowed when
+/// strict
+fn strict_failure(
+    id: String,
+    name: String,
+    source: String,
+    condition: Condition,
+    message: String,
+) -> FailedCheck {
+    FailedCheck::new(
+        id,
+        name,
+        source,
+        1,
+        CheckError::Strict { condition, message },
+    )
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(message) => (*message).to_owned(),
+            Err(_) => "unknown panic payload".to_owned(),
+        },
+    }
+}
+
+/// Checks every guild in `guilds` (id → name), yielding each result as soon
+/// as its request finishes. Tasks are spawned a window at a time as results
+/// come in, so the first request goes out right away however many there are.
+///
+/// Must be called from within a tokio runtime.
+pub fn check_stream(
+    guilds: impl IntoIterator<Item = (String, String)>,
+    options: &CheckOptions,
+) -> CheckStream {
+    let mut stream = CheckStream {
+        join_set: JoinSet::new(),
+        pending: HashMap::new(),
+        queued: VecDeque::new(),
+        started: Instant::now(),
+        timings: Vec::new(),
+        drift: DriftCheck::new([]),
+        warnings: Arc::clone(&options.warnings),
+        retries: Vec::new(),
+        given_up: VecDeque::new(),
+        pass: 0,
+        finished: false,
+    };
+    stream.add(guilds, options);
+    stream
+}
+
+impl CheckStream {
+    /// Checks more guilds in the same run, with options of their own, e.g.
+    /// users next to servers. They share the concurrency limit only if both
+    /// options have the same `limiter`.
+    pub fn add(
+        &mut self,
+        guilds: impl IntoIterator<Item = (String, String)>,
+        options: &CheckOptions,
+    ) {
+        self.drift.extend(
+            options
+                .backends
+                .iter()
+                .filter_map(|b| Some((b.name().to_owned(), b.schema()?))),
+        );
+        let sema = match &options.limiter {
+            Some(limiter) => Arc::clone(limiter),
+            None => Arc::new(Semaphore::new(options.concurrency)),
+        };
+        // one client of each for every check, so connections are reused. If
+        // one can't be built, each check fails saying why.
+        let mut options = options.clone();
+        if options.client.is_none() {
+            options.client = build_client(&options).ok();
+        }
+        if options.keyless_client.is_none() {
+            options.keyless_client = build_keyless_client(&options).ok();
+        }
+        let options = Arc::new(options);
+        self.queued.push_back(Queued {
+            guilds: guilds.into_iter().collect(),
+            options,
+            sema,
+            pass: Pass::default(),
+        });
+        self.top_up();
+    }
+
+    /// Holds `failed` back for the next retry pass if one is left and the
+    /// error is worth retrying, or gives it back
+    fn hold(&mut self, failed: FailedCheck, pending: &Pending) -> Option<FailedCheck> {
+        let retryable = failed.error.as_ref().is_some_and(CheckError::is_retryable);
+        let options = &pending.options;
+        if !retryable
+            || pending.pass.number >= options.retry_passes
+            || options.cancel.is_cancelled()
+        {
+            return Some(failed);
+        }
+        self.retries.push(Retry {
+            failed,
+            backend: pending.backend,
+            options: Arc::clone(options),
+        });
+        None
+    }
+
+    /// Queues the checks held back for another try, at half the concurrency
+    /// of the pass before. False if there are none, or the run is winding
+    /// down.
+    fn start_pass(&mut self) -> bool {
+        let Some(first) = self.retries.first() else {
+            return false;
+        };
+        if first.options.cancel.is_cancelled() || first.options.drain.is_cancelled() {
+            return false;
+        }
+        self.pass += 1;
+        let concurrency = (first.options.concurrency / 2).max(1);
+        info!(
+            pass = self.pass,
+            failed = self.retries.len(),
+            concurrency,
+            "checking the failed guilds again"
+        );
+        let limit = Arc::new(Semaphore::new(concurrency));
+        for retry in std::mem::take(&mut self.retries) {
+            let (id, name) = (
+                retry.failed.guild_id.clone(),
+                retry.failed.guild_name.clone(),
+            );
+            // only the backend that failed is asked again
+            let mut options = (*retry.options).clone();
+            options.backends = vec![Arc::clone(&retry.options.backends[retry.backend])];
+            options.concurrency = concurrency;
+            if retry.backend != 0 {
+                options.member_scan = None;
+            }
+            let sema = match &options.limiter {
+                Some(limiter) => Arc::clone(limiter),
+                None => Arc::new(Semaphore::new(concurrency)),
+            };
+            self.queued.push_back(Queued {
+                guilds: VecDeque::from([(id, name)]),
+                options: Arc::new(options),
+                sema,
+                pass: Pass {
+                    number: self.pass,
+                    limit: Some(Arc::clone(&limit)),
+                    previous: Some(retry.failed),
+                },
+            });
+        }
+        self.top_up();
+        true
+    }
+
+    /// Spawns queued checks until enough are in flight to keep every slot
+    /// busy, so a big run doesn't start a task for each guild up front
+    fn top_up(&mut self) {
+        while let Some(queued) = self.queued.front_mut() {
+            let ahead = SPAWN_AHEAD.max(queued.options.concurrency * 2);
+            if queued.options.cancel.is_cancelled() {
+                let previous = queued.pass.previous.take();
+                self.given_up.extend(previous);
+                self.queued.pop_front();
+                continue;
+            }
+            if self.join_set.len() >= ahead {
+                break;
+            }
+            let Some((id, name)) = queued.guilds.pop_front() else {
+                self.queued.pop_front();
+                continue;
+            };
+            let (options, sema) = (Arc::clone(&queued.options), Arc::clone(&queued.sema));
+            let pass = Pass {
+                number: queued.pass.number,
+                limit: queued.pass.limit.clone(),
+                previous: queued.pass.previous.take(),
+            };
+            self.spawn(id, name, &options, &sema, pass);
+        }
+    }
+
+    /// Starts the checks of one guild, one for each backend
+    fn spawn(
+        &mut self,
+        id: String,
+        name: String,
+        options: &Arc<CheckOptions>,
+        sema: &Arc<Semaphore>,
+        mut pass: Pass,
+    ) {
+        for (i, backend) in options.backends.iter().enumerate() {
+            // with several backends, the first one's result gets the scan
+            let scan_members = i == 0 && options.kind == Kind::Guild;
+            // `name` would clash with the span name in the JSON logs
+            let span = info_span!(
+                "check",
+                %id,
+                guild = %name,
+                backend = backend.name(),
+                outcome = field::Empty,
+                status = field::Empty,
+                attempts = field::Empty,
+                verification = field::Empty,
+            );
+            let sema = Arc::clone(sema);
+            let limit = pass.limit.clone();
+            let backend = Arc::clone(backend);
+            let options = Arc::clone(options);
+            let task = Pending {
+                span: span.clone(),
+                guild_id: id.clone(),
+                guild_name: name.clone(),
+                source: backend.name().to_owned(),
+                backend: i,
+                options: Arc::clone(&options),
+                pass: Pass {
+                    number: pass.number,
+                    limit: None,
+                    previous: pass.previous.take(),
+                },
+            };
+            let (id, name) = (id.clone(), name.clone());
+            let handle = self.join_set.spawn(
+                async move {
+                    let check = async {
+                        let (mut result, timing) =
+                            check_or_cached(id.clone(), name, &*backend, &options, &sema, limit)
+                                .await?;
+                        if let (Some(scanner), true, Ok(response)) =
+                            (&options.member_scan, scan_members, &mut result)
+                        {
+                            // Discord mustn't see the API key
+                            let scan = match build_keyless_client(&options) {
+                                Ok(client) => scanner.scan_logged(&client, &id).await,
+                                Err(err) => Err(err.into()),
+                            };
+                            match scan {
+                                Ok(scan) => response.member_scan = Some(scan),
+                                Err(err) => {
+                                    let message = format!(
+                                        "{} (ID: {id}): member scan failed: {err}",
+                                        response.guild_name
+                                    );
+                                    if !options.warnings.warn(Condition::ScanFailed, &message) {
+                                        result = Err(strict_failure(
+                                            response.guild_id.clone(),
+                                            response.guild_name.clone(),
+                                            response.source.clone(),
+                                            Condition::ScanFailed,
+                                            message,
+                                        ));
+                                    }
+                                }
+                            }
+                        }
+                        Some((result, timing))
+                    };
+
+                    tokio::select! {
+                        biased;
+                        _ = options.cancel.cancelled() => {
+                            debug!("cancelled");
+                            None
+                        }
+                        result = check => result,
+                    }
+                }
+                .instrument(span),
+            );
+            self.pending.insert(handle.id(), task);
+        }
+    }
+}
+
+/// One check, waiting for a slot under the concurrency limit unless the
+/// cache has the answer. `None` if the run winds down before the slot comes.
+async fn check_or_cached(
+    id: String,
+    name: String,
+    backend: &dyn Backend,
+    options: &CheckOptions,
+    sema: &Semaphore,
+    pass_limit: Option<Arc<Semaphore>>,
+) -> Option<(CheckResult, Option<Timing>)> {
+    if let Some(response) = cached(&id, &name, backend, options) {
+        return Some((Ok(response), None));
+    }
+
+    let wait = async {
+        // cooldowns aren't waiting for the concurrency limit, so they're not
+        // counted as queued
+        if let Some(batches) = &options.batches {
+            batches.start().await;
+        }
+        let queued_at = Instant::now();
+        let pass = match pass_limit {
+            Some(limit) => Some(
+                limit
+                    .acquire_owned()
+                    .await
+                    .expect("semaphore is never closed"),
+            ),
+            None => None,
+        };
+        let adaptive = match &options.adaptive {
+            Some(adaptive) => Some(adaptive.acquire().await),
+            None => None,
+        };
+        let ticket = sema.acquire().await.expect("semaphore is never closed");
+        ((pass, adaptive), ticket, queued_at.elapsed())
+    };
+    let (permits, ticket, queued) = tokio::select! {
+        biased;
+        _ = options.drain.cancelled() => {
+            debug!("run winding down, not sent");
+            return None;
+        }
+        slot = wait => slot,
+    };
+
+    let started = Instant::now();
+    let check = async {
+        let result = check_guild(id, name, backend, options, ticket).await;
+        let remaining = CAPTURED.with(|captured| captured.borrow().min_remaining);
+        (result, BODY_BYTES.with(Cell::get), remaining)
+    };
+    let check = CAPTURED.scope(RefCell::default(), check);
+    let check = BODY_LIMIT.scope(options.max_response_size, check);
+    let (result, bytes, rate_limit_remaining) = BODY_BYTES.scope(Cell::new(0), check).await;
+    drop(permits);
+    if let Some(batches) = &options.batches {
+        batches.finish();
+    }
+    let attempts = match &result {
+        Ok(response) => response.request.map_or(1, |request| request.attempts),
+        Err(failed) => failed.attempts,
+    };
+    let timing = Timing {
+        queued,
+        request: started.elapsed(),
+        bytes,
+        rate_limit_remaining,
+        attempts,
+    };
+    let result = match (&options.verify, result) {
+        // a page read instead of the API isn't worth asking the API again
+        (Some(verifier), Ok(mut response))
+            if response.is_compromised() && response.fallback.is_none() =>
+        {
+            let verify = verify(verifier, &response.guild_id, backend, options, sema);
+            let verify = BODY_LIMIT.scope(options.max_response_size, verify);
+            response.verification = Some(CAPTURED.scope(RefCell::default(), verify).await);
+            Ok(response)
+        }
+        (_, result) => result,
+    };
+    Some((result, Some(timing)))
+}
+
+/// Asks `backend` about the compromised guild `id` again once the
+/// verifier's delay is up, retrying as usual
+async fn verify(
+    verifier: &Verifier,
+    id: &str,
+    backend: &dyn Backend,
+    options: &CheckOptions,
+    sema: &Semaphore,
+) -> Verification {
+    let wait = async {
+        tokio::time::sleep(verifier.delay()).await;
+        if let Some(batches) = &options.batches {
+            batches.start().await;
+        }
+        sema.acquire().await.expect("semaphore is never closed")
+    };
+    let ticket = tokio::select! {
+        biased;
+        _ = options.drain.cancelled() => {
+            debug!("run winding down, not verified");
+            return Verification::Unverified;
+        }
+        ticket = wait => ticket,
+    };
+    info!("verifying");
+    let client = match verifier.client() {
+        Some(client) => Ok(client.clone()),
+        None => build_client(options),
+    };
+    let (result, _) = match client {
+        Ok(client) => request(&client, id, backend, options).await,
+        Err(err) => (Err(err.into()), 1),
+    };
+    drop(ticket);
+    if let Some(batches) = &options.batches {
+        batches.finish();
+    }
+    let verification = match result.map(|answer| classify(&answer)) {
+        Ok(Status::Compromised) => Verification::Confirmed,
+        Ok(status) => {
+            warn!(%status, "compromised at first, not when asked again");
+            Verification::Flaky
+        }
+        Err(err) => {
+            warn!(%err, "couldn't verify");
+            Verification::Unverified
+        }
+    };
+    Span::current().record("verification", verification.as_str());
+    verification
+}
+
+/// Checks every guild and collects the results. If `options.cancel` or
+/// `options.drain` fires midway, the report holds whatever finished before
+/// that.
+pub async fn check_guilds(
+    guilds: impl IntoIterator<Item = (String, String)>,
+    options: &CheckOptions,
+) -> RunReport {
+    let mut results = check_stream(guilds, options);
+    let mut report = RunReport::default();
+
+    while let Some(result) = results.next().await {
+        match result {
+            Ok(v) => {
+                report.results.push(v);
+            }
+            Err(failed) => {
+                report.failed.push(failed);
+            }
+        };
+    }
+
+    report.cancelled = options.cancel.is_cancelled() || options.drain.is_cancelled();
+    report.performance = Some(results.performance());
+    report.schema_drift = results.schema_drift();
+    report.api_key = options.api_key.is_some().then_some(ApiKeyStatus::Set);
+    report
+}
+
+fn cached(id: &str, name: &str, backend: &dyn Backend, options: &CheckOptions) -> Option<Response> {
+    let entry = options.cache.as_ref()?.get(backend.name(), id)?;
+    Span::current().record("outcome", "cached");
+    debug!(checked_at = %entry.checked_at, "answered from cache");
+    let mut response = Response {
+        guild_id: id.to_owned(),
+        guild_name: name.to_owned(),
+        source: backend.name().to_owned(),
+        kind: options.kind,
+        checked_at: Some(entry.checked_at),
+        labels: Vec::new(),
+        classification: Some(classify(&entry.api_response)),
+        api_response: entry.api_response,
+        unparseable: None,
+        details: None,
+        member_scan: None,
+        headers: None,
+        window: None,
+        from_cache: true,
+        fallback: None,
+        request: None,
+        verification: None,
+        severity: None,
+    };
+    response.severity = response.severity();
+    Some(response)
+}
+
+async fn check_guild(
+    id: String,
+    name: String,
+    backend: &dyn Backend,
+    options: &CheckOptions,
+    ticket: tokio::sync::SemaphorePermit<'_>,
+) -> CheckResult {
+    let span = Span::current();
+    // an answer that's no longer fresh is asked about again only if it changed
+    let stale = options
+        .cache
+        .as_ref()
+        .and_then(|cache| cache.stale(backend.name(), &id));
+    headers::set_conditional(stale.as_ref().map(|entry| entry.validators.clone()));
+    let sent = Instant::now();
+    let (client, result, attempts) = match build_client(options) {
+        Ok(client) => {
+            let (result, attempts) = request(&client, &id, backend, options).await;
+            (Some(client), result, attempts)
+        }
+        Err(err) => (None, Err(err.into()), 1),
+    };
+    headers::set_conditional(None);
+    let duration = sent.elapsed();
+    let http_status = headers::last_request().and_then(|last| last.status);
+    let mut validators = headers::validators();
+    let result = match (result, stale) {
+        (Err(CheckError::HttpStatus(StatusCode::NOT_MODIFIED)), Some(entry)) => {
+            debug!(checked_at = %entry.checked_at, "unchanged since the cached answer");
+            if validators.is_empty() {
+                validators = entry.validators;
+            }
+            Ok(entry.api_response)
+        }
+        (result, _) => result,
+    };
+    let (result, fallback) = match (result, &options.web_fallback) {
+        (Err(err), Some(web)) if web.applies(backend.name(), options.kind, &err) => {
+            fall_back(web, options, &id, err).await
+        }
+        (result, _) => (result, None),
+    };
+    let source = match fallback {
+        Some(_) => WEB_FALLBACK,
+        None => backend.name(),
+    };
+    // before deep scan requests replace them
+    let headers = match options.keep_headers {
+        true => CAPTURED
+            .try_with(|captured| captured.borrow().headers.clone())
+            .ok()
+            .filter(|headers| !headers.is_empty()),
+        false => None,
+    };
+
+    let (api_response, unparseable, status) = match result {
+        Ok(api_response) => {
+            let status = classify(&api_response);
+            span.record("outcome", status.as_str());
+            match status {
+                Status::Clean => info!("not found"),
+                Status::Compromised => info!("found"),
+                _ => {
+                    let message = format!(
+                        "{name} (ID: {id}): answer doesn't say whether it's in the dataset: {api_response}"
+                    );
+                    if !options.warnings.warn(Condition::Indeterminate, &message) {
+                        let source = source.to_owned();
+                        return Err(strict_failure(
+                            id,
+                            name,
+                            source,
+                            Condition::Indeterminate,
+                            message,
+                        ));
+                    }
+                }
+            }
+            (api_response, None, status)
+        }
+        Err(CheckError::BadBody { snippet }) => {
+            let body = Unparseable::new(snippet);
+            span.record("outcome", "unparseable");
+            let message = format!(
+                "{name} (ID: {id}): response isn't JSON ({:?}): {:?}",
+                body.body, body.snippet
+            );
+            if !options.warnings.warn(Condition::UnparseableBody, &message) {
+                let err = CheckError::Strict {
+                    condition: Condition::UnparseableBody,
+                    message,
+                };
+                return Err(FailedCheck::new(id, name, source.to_owned(), attempts, err));
+            }
+            (Value::Null, Some(body), Status::Unparseable)
+        }
+        Err(err) => {
+            span.record("outcome", err.kind().as_str());
+            error!(%err, "check failed");
+            return Err(FailedCheck::new(id, name, source.to_owned(), attempts, err));
+        }
+    };
+
+    // still holding the ticket, so follow-ups count toward the limit
+    let details = match (&options.deep_scan, client, status, &fallback) {
+        // the API is failing, and the follow-ups go to it
+        (Some(deep), Some(client), Status::Compromised, None) => {
+            info!("deep scanning");
+            match deep.scan(&client, &id).await {
+                Ok(details) => Some(details),
+                Err(err) => {
+                    let message = format!(
+                        "{name} (ID: {id}): deep scan failed, keeping the shallow result: {err}"
+                    );
+                    if !options.warnings.warn(Condition::ScanFailed, &message) {
+                        let source = source.to_owned();
+                        return Err(strict_failure(
+                            id,
+                            name,
+                            source,
+                            Condition::ScanFailed,
+                            message,
+                        ));
+                    }
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+    drop(ticket);
+
+    let checked_at = Utc::now();
+    if let (Some(cache), None, None) = (&options.cache, &unparseable, &fallback) {
+        let entry = CacheEntry {
+            api_response: api_response.clone(),
+            checked_at,
+            validators,
+        };
+        cache.insert(backend.name(), &id, entry);
+    }
+
+    let mut response = Response {
+        guild_id: id,
+        guild_name: name,
+        source: source.to_owned(),
+        kind: options.kind,
+        checked_at: Some(checked_at),
+        labels: Vec::new(),
+        api_response,
+        unparseable,
+        details,
+        member_scan: None,
+        headers,
+        classification: Some(status),
+        window: None,
+        from_cache: false,
+        fallback,
+        request: Some(RequestStats {
+            duration_ms: duration.as_secs_f64() * 1000.0,
+            http_status: http_status.map(|status| status.as_u16()),
+            attempts,
+        }),
+        verification: None,
+        severity: None,
+    };
+    response.severity = response.severity();
+    Ok(response)
+}
+
+/// Asks `backend` about `id`, trying again as `options.retry` allows. Every
+/// try takes a place in a batch and waits its turn under the spacing.
+/// Returns the last try's result and the number of tries.
+async fn request(
+    client: &Client,
+    id: &str,
+    backend: &dyn Backend,
+    options: &CheckOptions,
+) -> (Result<Value, CheckError>, u32) {
+    let span = Span::current();
+    let mut attempts = 1;
+    info!("requesting");
+    loop {
+        span.record("attempts", attempts);
+        if let Some(spacing) = &options.spacing {
+            spacing.wait().await;
+        }
+        let check = backend.check(client, id);
+        let result = match &options.dump {
+            Some(dump) => dump.record(backend.name(), id, check).await,
+            None => check.await,
+        };
+        if let Some(adaptive) = &options.adaptive {
+            adaptive.record(result.as_ref().is_err_and(is_overload));
+        }
+        if let Some(tor) = &options.tor {
+            let rate_limited = matches!(result, Err(CheckError::RateLimited { .. }));
+            tor.record(rate_limited).await;
+        }
+        let wait = match &result {
+            Err(err) => options.retry.delay(attempts, err),
+            Ok(_) => None,
+        };
+        let (Some(wait), Err(err)) = (wait, &result) else {
+            return (result, attempts);
+        };
+        warn!(%err, attempts, wait = %humantime::format_duration(wait), "request failed, trying again");
+        if let Some(batches) = &options.batches {
+            batches.finish();
+        }
+        tokio::time::sleep(wait).await;
+        if let Some(batches) = &options.batches {
+            batches.start().await;
+        }
+        attempts += 1;
+    }
+}
+
+/// Whether `err` says the API has more requests than it can take
+fn is_overload(err: &CheckError) -> bool {
+    match err {
+        CheckError::RateLimited { .. } => true,
+        CheckError::HttpStatus(status) => status.is_server_error(),
+        _ => false,
+    }
+}
+
+/// Reads the website about `id` after the API failed with `err`. If that
+/// fails too, the API's error stands.
+async fn fall_back(
+    web: &WebFallback,
+    options: &CheckOptions,
+    id: &str,
+    err: CheckError,
+) -> (Result<Value, CheckError>, Option<Fallback>) {
+    let url = web.url(id);
+    info!(%err, %url, "api failed, reading the web page instead");
+    // the website mustn't see the API key
+    let page = match build_keyless_client(options) {
+        Ok(client) => web.check(&client, id).await,
+        Err(err) => Err(err.into()),
+    };
+    match page {
+        Ok(answer) => {
+            let fallback = Fallback {
+                confidence: Confidence::Low,
+                url,
+                api_error: err.to_string(),
+            };
+            (Ok(answer), Some(fallback))
+        }
+        Err(web_err) => {
+            warn!(%web_err, "web fallback failed too");
+            (Err(err), None)
+        }
+    }
+}
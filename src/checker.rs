@@ -0,0 +1,117 @@
+//! A long-lived handle for running checks from another program, like a
+//! moderation bot, instead of shelling out to the binary.
+//!
+//! ```no_run
+//! use spy_pet_checker::Checker;
+//!
+//! # async fn run() {
+//! let checker = Checker::new().concurrency(4);
+//! match checker.check("1234567890123456789", "My server").await {
+//!     Some(Ok(response)) if response.is_compromised() => {
+//!         println!("{} is in the dataset", response.guild_name)
+//!     }
+//!     Some(Ok(_)) => println!("not in the dataset"),
+//!     Some(Err(failed)) => eprintln!("check failed: {}", failed.message),
+//!     None => eprintln!("cancelled"),
+//! }
+//! # }
+//! ```
+
+use std::sync::Arc;
+
+use futures_util::StreamExt;
+use reqwest::Client;
+use tokio::sync::Semaphore;
+
+use crate::backend::{Backend, SpyPet};
+use crate::{
+    build_client, build_keyless_client, check_guilds, check_stream, CheckOptions, CheckResult,
+    CheckStream, RunReport,
+};
+
+/// Runs checks with the same options every time. The concurrency limit is
+/// shared by everything a `Checker` (or a clone of it) runs, however many
+/// calls are in flight.
+#[derive(Clone)]
+pub struct Checker {
+    options: CheckOptions,
+}
+
+impl Checker {
+    /// Asks spy.pet, one request at a time
+    pub fn new() -> Self {
+        Self::with_options(CheckOptions::default())
+    }
+
+    /// Starts from `options`, for the settings without a method of their own
+    pub fn with_options(mut options: CheckOptions) -> Self {
+        if options.limiter.is_none() {
+            options.limiter = Some(Arc::new(Semaphore::new(options.concurrency)));
+        }
+        // built once here rather than for every call
+        if options.client.is_none() {
+            options.client = build_client(&options).ok();
+        }
+        if options.keyless_client.is_none() {
+            options.keyless_client = build_keyless_client(&options).ok();
+        }
+        Self { options }
+    }
+
+    /// Maximum number of requests in flight at once
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.options.concurrency = concurrency;
+        self.options.limiter = Some(Arc::new(Semaphore::new(concurrency)));
+        self
+    }
+
+    /// Asks a spy.pet-compatible API at `base_url` instead, e.g. a mirror
+    pub fn endpoint(self, base_url: impl Into<String>) -> Self {
+        let kind = self.options.kind;
+        self.backend(SpyPet::new(base_url).with_kind(kind))
+    }
+
+    /// Asks `backend` instead
+    pub fn backend(mut self, backend: impl Backend + 'static) -> Self {
+        self.options.backends = vec![Arc::new(backend)];
+        self
+    }
+
+    /// Sends the requests with `client`, e.g. to share its connection pool
+    /// with the rest of a program
+    pub fn client(mut self, client: Client) -> Self {
+        self.options.client = Some(client);
+        self
+    }
+
+    pub fn options(&self) -> &CheckOptions {
+        &self.options
+    }
+
+    /// Checks one guild. With several backends, the first one answers.
+    /// `None` if `options().cancel` fired first.
+    pub async fn check(&self, id: &str, name: &str) -> Option<CheckResult> {
+        let mut options = self.options.clone();
+        options.backends.truncate(1);
+        check_stream([(id.to_owned(), name.to_owned())], &options)
+            .next()
+            .await
+    }
+
+    /// Checks every guild in `guilds` (id → name) and collects the results
+    pub async fn check_all(&self, guilds: impl IntoIterator<Item = (String, String)>) -> RunReport {
+        check_guilds(guilds, &self.options).await
+    }
+
+    /// Checks every guild in `guilds` (id → name), yielding each result as
+    /// soon as its request finishes
+    pub fn stream(&self, guilds: impl IntoIterator<Item = (String, String)>) -> CheckStream {
+        check_stream(guilds, &self.options)
+    }
+}
+
+impl Default for Checker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
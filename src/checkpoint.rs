@@ -0,0 +1,133 @@
+//! Progress of a run, saved as it goes, so an interrupted run can pick up
+//! where it stopped instead of starting over.
+//!
+//! A checkpoint is JSON Lines, one result per line, appended and flushed as
+//! each check finishes: a crash loses at most the line being written. Failed
+//! checks aren't saved, so a resumed run tries them again.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use tracing::warn;
+
+use crate::{Kind, Response};
+
+/// Saves the results of a run to `path` as they come in
+pub struct Checkpoint {
+    path: PathBuf,
+    file: BufWriter<File>,
+}
+
+impl Checkpoint {
+    /// Starts a checkpoint at `path`. `resume` keeps what's already there,
+    /// otherwise it's started over.
+    pub fn create(path: impl Into<PathBuf>, resume: bool) -> io::Result<Self> {
+        let path = path.into();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(resume)
+            .read(resume)
+            .write(true)
+            .truncate(!resume)
+            .open(&path)?;
+        // a crash can leave the last line cut short, which the next mustn't
+        // be glued onto
+        if resume && file.metadata()?.len() > 0 {
+            let mut last = [0];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last != *b"\n" {
+                file.write_all(b"\n")?;
+            }
+        }
+        Ok(Self {
+            path,
+            file: BufWriter::new(file),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn record(&mut self, response: &Response) -> io::Result<()> {
+        serde_json::to_writer(&mut self.file, response)?;
+        self.file.write_all(b"\n")?;
+        self.file.flush()
+    }
+
+    /// Deletes the checkpoint, once the run it's for is done
+    pub fn remove(self) -> io::Result<()> {
+        drop(self.file);
+        std::fs::remove_file(&self.path)
+    }
+
+    /// The results saved at `path`, none if there's no checkpoint there. A
+    /// line that doesn't parse, like one cut short by a crash, is skipped.
+    pub fn load(path: &Path) -> io::Result<Vec<Response>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        let mut results = Vec::new();
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(response) => results.push(response),
+                Err(err) => {
+                    warn!(%err, line = number + 1, path = %path.display(), "skipping damaged checkpoint line")
+                }
+            }
+        }
+        Ok(results)
+    }
+}
+
+/// Names the checkpoint of a run over `ids` against `backends`, so a run
+/// over other inputs doesn't pick it up. Stable across versions and
+/// platforms, unlike the standard library's hasher.
+pub fn fingerprint<'a>(
+    kind: &str,
+    backends: impl IntoIterator<Item = &'a str>,
+    ids: impl IntoIterator<Item = &'a str>,
+) -> String {
+    // FNV-1a
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut feed = |part: &str| {
+        for byte in part.bytes().chain([0]) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    };
+    feed(kind);
+    let backends: BTreeSet<&str> = backends.into_iter().collect();
+    backends.iter().for_each(|b| feed(b));
+    let ids: BTreeSet<&str> = ids.into_iter().collect();
+    ids.iter().for_each(|id| feed(id));
+    format!("{hash:016x}")
+}
+
+/// The saved results of the IDs every one of their backends answered
+/// about, `backends` of them for each kind. Those are done; the rest, along
+/// with their partial results, are checked again.
+pub fn finished(saved: Vec<Response>, backends: impl Fn(Kind) -> usize) -> Vec<Response> {
+    let mut by_id: BTreeMap<(Kind, String), Vec<Response>> = BTreeMap::new();
+    for response in saved {
+        let key = (response.kind, response.guild_id.clone());
+        let answers = by_id.entry(key).or_default();
+        // the last answer from a backend wins
+        answers.retain(|r| r.source != response.source);
+        answers.push(response);
+    }
+    by_id
+        .into_iter()
+        .filter(|((kind, _), answers)| answers.len() >= backends(*kind))
+        .flat_map(|(_, answers)| answers)
+        .collect()
+}
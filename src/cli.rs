@@ -0,0 +1,2773 @@
+use std::fmt;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use spy_pet_checker::backend::{
+    Backend, JsonPath, KickTheSpy, SimulationProfile, SpyPet, TemplateError, UrlTemplate,
+};
+use spy_pet_checker::dataset::DEFAULT_LIST_URL;
+use spy_pet_checker::discord;
+use spy_pet_checker::i18n::Lang;
+use spy_pet_checker::members::KNOWN_BOTS_URL;
+use spy_pet_checker::output::{self, Formatter};
+use spy_pet_checker::search::DEFAULT_SEARCH_URL;
+use spy_pet_checker::severity::Level;
+use spy_pet_checker::web;
+use spy_pet_checker::window::{parse_date, WindowMode};
+use spy_pet_checker::Kind;
+use spy_pet_checker::{index, mail, notify};
+
+#[derive(ValueEnum, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[clap(help = "Simple output in human readable format")]
+    Plain,
+
+    #[clap(help = "Complete output in json format")]
+    Json,
+
+    #[clap(help = "A result per line of JSON, written as soon as it comes in")]
+    Ndjson,
+
+    #[clap(help = "A YAML mapping with the results, for config-driven tooling")]
+    Yaml,
+
+    #[clap(help = "A TOML document with a [[results]] table per result")]
+    Toml,
+
+    #[clap(help = "One row per server, with the answer's fields as columns, for spreadsheets")]
+    Csv,
+
+    #[clap(help = "A report to paste into a Discord message or a GitHub issue")]
+    Markdown,
+
+    #[clap(help = "A standalone report page, with a table that sorts by any column")]
+    Html,
+
+    #[clap(help = "A JUnit XML report with a test case per server, for CI")]
+    Junit,
+}
+
+/// What the formats that can be adjusted are adjusted by
+#[derive(Clone, Copy, Default)]
+pub struct FormatOptions {
+    pub group_by: Option<GroupBy>,
+    /// JSON on a single line
+    pub json_compact: bool,
+    /// List clean servers in the plain output too
+    pub include_clean: bool,
+    /// List what's known of the compromised servers in the plain output
+    pub details: bool,
+    /// Color the plain output
+    pub color: bool,
+    pub lang: Lang,
+}
+
+impl Format {
+    pub fn formatter(&self) -> Box<dyn Formatter> {
+        self.adjusted_formatter(FormatOptions::default())
+    }
+
+    pub fn adjusted_formatter(&self, options: FormatOptions) -> Box<dyn Formatter> {
+        let group_by = options.group_by.map(GroupBy::into_output);
+        match self {
+            Format::Plain => Box::new(output::Plain {
+                group_by,
+                include_clean: options.include_clean,
+                details: options.details,
+                color: options.color,
+                lang: options.lang,
+            }),
+            Format::Json => Box::new(output::Json {
+                group_by,
+                compact: options.json_compact,
+            }),
+            Format::Ndjson => Box::new(output::Ndjson),
+            Format::Yaml => Box::new(output::Yaml),
+            Format::Toml => Box::new(output::Toml),
+            Format::Csv => Box::new(output::Csv::default()),
+            Format::Markdown => Box::new(output::Markdown { group_by }),
+            Format::Html => Box::new(output::Html { group_by }),
+            Format::Junit => Box::new(output::Junit),
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NotifyOn {
+    #[clap(help = "After every run")]
+    Always,
+
+    #[clap(help = "When any server is compromised")]
+    Compromised,
+
+    #[clap(
+        help = "When a server is compromised that the previous run or watch cycle didn't report"
+    )]
+    NewCompromised,
+
+    #[clap(help = "When any check failed")]
+    Errors,
+
+    Never,
+}
+
+#[derive(ValueEnum, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifyMethod {
+    Post,
+    Put,
+    Patch,
+}
+
+impl NotifyMethod {
+    pub fn into_method(self) -> reqwest::Method {
+        match self {
+            NotifyMethod::Post => reqwest::Method::POST,
+            NotifyMethod::Put => reqwest::Method::PUT,
+            NotifyMethod::Patch => reqwest::Method::PATCH,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailFormat {
+    Plain,
+    Html,
+}
+
+#[derive(ValueEnum, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndexFormat {
+    #[default]
+    #[clap(help = "Go by the file extension, or else by what the index looks like")]
+    Auto,
+
+    #[clap(help = "A JSON object of IDs and names")]
+    Json,
+
+    #[clap(help = "An ID per line, optionally followed by a tab and the name")]
+    Text,
+
+    #[clap(help = "id,name rows, or id and name columns under a header")]
+    Csv,
+
+    #[clap(help = "A flat mapping of IDs and names")]
+    Yaml,
+
+    #[clap(help = "A table of IDs and names")]
+    Toml,
+}
+
+impl IndexFormat {
+    /// The format asked for, or `None` to detect it
+    pub fn known(self) -> Option<index::IndexFormat> {
+        match self {
+            IndexFormat::Auto => None,
+            IndexFormat::Json => Some(index::IndexFormat::Json),
+            IndexFormat::Text => Some(index::IndexFormat::Text),
+            IndexFormat::Csv => Some(index::IndexFormat::Csv),
+            IndexFormat::Yaml => Some(index::IndexFormat::Yaml),
+            IndexFormat::Toml => Some(index::IndexFormat::Toml),
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    #[clap(help = "TLS from the start, usually on port 465")]
+    Tls,
+
+    #[value(name = "starttls")]
+    #[clap(help = "STARTTLS after connecting, usually on port 587")]
+    StartTls,
+
+    #[clap(help = "Neither, for a relay on the same machine (port 25)")]
+    None,
+}
+
+impl SmtpSecurity {
+    pub fn into_security(self) -> mail::Security {
+        match self {
+            SmtpSecurity::Tls => mail::Security::Tls,
+            SmtpSecurity::StartTls => mail::Security::StartTls,
+            SmtpSecurity::None => mail::Security::None,
+        }
+    }
+}
+
+impl NotifyOn {
+    pub fn into_notify(self) -> notify::NotifyOn {
+        match self {
+            NotifyOn::Always => notify::NotifyOn::Always,
+            NotifyOn::Compromised => notify::NotifyOn::Compromised,
+            NotifyOn::NewCompromised => notify::NotifyOn::NewCompromised,
+            NotifyOn::Errors => notify::NotifyOn::Errors,
+            NotifyOn::Never => notify::NotifyOn::Never,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SimulateProfile {
+    #[clap(help = "Quick answers, no errors")]
+    Friendly,
+
+    #[clap(help = "Slow answers, lots of 429s and some 500s")]
+    Harsh,
+
+    #[clap(help = "Very uneven latency and frequent 500s")]
+    Flaky,
+}
+
+impl SimulateProfile {
+    pub fn into_profile(self) -> SimulationProfile {
+        match self {
+            SimulateProfile::Friendly => SimulationProfile::friendly(),
+            SimulateProfile::Harsh => SimulationProfile::harsh(),
+            SimulateProfile::Flaky => SimulationProfile::flaky(),
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckKind {
+    #[clap(help = "Server IDs")]
+    Servers,
+
+    #[clap(help = "User IDs, looked up in spy.pet's user dataset")]
+    Users,
+
+    #[clap(help = "Channel IDs, looked up to see whether their messages were scraped")]
+    Channels,
+}
+
+impl CheckKind {
+    pub fn into_kind(self) -> Kind {
+        match self {
+            CheckKind::Servers => Kind::Guild,
+            CheckKind::Users => Kind::User,
+            CheckKind::Channels => Kind::Channel,
+        }
+    }
+
+    /// What's being checked, for messages
+    pub fn plural(self) -> &'static str {
+        match self {
+            CheckKind::Servers => "servers",
+            CheckKind::Users => "users",
+            CheckKind::Channels => "channels",
+        }
+    }
+
+    /// [`CheckKind::plural`], or the singular for one of them
+    pub fn noun(self, count: usize) -> &'static str {
+        match (self, count) {
+            (CheckKind::Servers, 1) => "server",
+            (CheckKind::Users, 1) => "user",
+            (CheckKind::Channels, 1) => "channel",
+            (kind, _) => kind.plural(),
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SinceMode {
+    #[clap(help = "Leave compromised servers outside the window out of the results")]
+    Filter,
+
+    #[clap(help = "Keep them, marked as outside the window")]
+    Annotate,
+}
+
+impl SinceMode {
+    pub fn into_window_mode(self) -> WindowMode {
+        match self {
+            SinceMode::Filter => WindowMode::Filter,
+            SinceMode::Annotate => WindowMode::Annotate,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SeverityLevel {
+    #[clap(help = "Score below 25")]
+    Low,
+
+    #[clap(help = "Score of 25 or more")]
+    Medium,
+
+    #[clap(help = "Score of 50 or more")]
+    High,
+
+    #[clap(help = "Score of 75 or more")]
+    Critical,
+}
+
+impl SeverityLevel {
+    pub fn into_level(self) -> Level {
+        match self {
+            SeverityLevel::Low => Level::Low,
+            SeverityLevel::Medium => Level::Medium,
+            SeverityLevel::High => Level::High,
+            SeverityLevel::Critical => Level::Critical,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    #[clap(help = "The backend that answered")]
+    Source,
+
+    #[clap(help = "The accounts or files results were merged from")]
+    Label,
+
+    #[clap(help = "Clean, compromised or unparseable")]
+    Status,
+
+    #[clap(help = "Servers or users")]
+    Kind,
+}
+
+impl GroupBy {
+    fn into_output(self) -> output::GroupBy {
+        match self {
+            GroupBy::Source => output::GroupBy::Source,
+            GroupBy::Label => output::GroupBy::Label,
+            GroupBy::Status => output::GroupBy::Status,
+            GroupBy::Kind => output::GroupBy::Kind,
+        }
+    }
+}
+
+/// How `--output` is written to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputMode {
+    /// Emptied first, and created if it doesn't exist
+    #[default]
+    Replace,
+    /// `--append`
+    Append,
+    /// `--atomic`: written next to it, then renamed over it
+    Atomic,
+}
+
+#[derive(ValueEnum, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    #[clap(help = "Server ID, oldest server first")]
+    Id,
+
+    #[clap(help = "Server name, ignoring case")]
+    Name,
+
+    #[clap(help = "Compromised first, then indeterminate and unparseable, then clean")]
+    Status,
+
+    #[clap(help = "Most archived messages first")]
+    Messages,
+
+    #[clap(help = "Worst exposure first, by severity score")]
+    Severity,
+}
+
+impl SortBy {
+    pub fn into_output(self) -> output::SortBy {
+        match self {
+            SortBy::Id => output::SortBy::Id,
+            SortBy::Name => output::SortBy::Name,
+            SortBy::Status => output::SortBy::Status,
+            SortBy::Messages => output::SortBy::Messages,
+            SortBy::Severity => output::SortBy::Severity,
+        }
+    }
+}
+
+/// Output formats for commands that print a summary rather than results
+#[derive(ValueEnum, Clone)]
+pub enum SummaryFormat {
+    #[clap(help = "Human readable")]
+    Plain,
+
+    #[clap(help = "Machine readable")]
+    Json,
+}
+
+/// Shells `completions` writes a script for
+#[derive(ValueEnum, Clone, Copy)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+#[derive(ValueEnum, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendChoice {
+    #[clap(name = "spypet", help = "spy.pet's server lookup")]
+    #[serde(rename = "spypet")]
+    SpyPet,
+
+    #[clap(name = "kickthespy", help = "kickthespy.pet's scraper bot lookup")]
+    #[serde(rename = "kickthespy")]
+    KickTheSpy,
+
+    #[clap(help = "Every backend above, one result per backend")]
+    All,
+}
+
+impl BackendChoice {
+    pub fn backends(&self) -> Vec<Arc<dyn Backend>> {
+        match self {
+            BackendChoice::SpyPet => vec![Arc::new(SpyPet::default())],
+            BackendChoice::KickTheSpy => vec![Arc::new(KickTheSpy::default())],
+            BackendChoice::All => {
+                vec![Arc::new(SpyPet::default()), Arc::new(KickTheSpy::default())]
+            }
+        }
+    }
+
+    /// The backends of every one of `choices`, each once
+    pub fn backends_of(choices: &[BackendChoice]) -> Vec<Arc<dyn Backend>> {
+        let mut backends: Vec<Arc<dyn Backend>> = Vec::new();
+        for backend in choices.iter().flat_map(BackendChoice::backends) {
+            if !backends.iter().any(|b| b.name() == backend.name()) {
+                backends.push(backend);
+            }
+        }
+        backends
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RuntimeChoice {
+    #[clap(help = "current-thread with --concurrency 1, multi-thread otherwise")]
+    Auto,
+
+    #[clap(help = "Run everything on the main thread")]
+    CurrentThread,
+
+    #[clap(help = "One worker thread per CPU core")]
+    MultiThread,
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    #[clap(help = "Human readable")]
+    Text,
+
+    #[clap(help = "One JSON object per event")]
+    Json,
+}
+
+/// How often `--log-file` starts a new file
+#[derive(ValueEnum, Clone, Copy)]
+pub enum LogRotation {
+    Hourly,
+    Daily,
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FailFast {
+    #[clap(help = "Stop at the first compromised server")]
+    Compromised,
+
+    #[clap(help = "Stop at the first failed check")]
+    Error,
+
+    #[clap(help = "Stop at whichever comes first")]
+    Any,
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FailOn {
+    #[clap(help = "Exit with 2 if any server is compromised")]
+    Found,
+
+    #[clap(help = "Exit with 1 if any check failed")]
+    Error,
+
+    #[clap(help = "Both")]
+    Any,
+}
+
+impl FailOn {
+    pub fn found(self) -> bool {
+        matches!(self, FailOn::Found | FailOn::Any)
+    }
+
+    pub fn error(self) -> bool {
+        matches!(self, FailOn::Error | FailOn::Any)
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+    #[clap(help = "When writing to a terminal, unless NO_COLOR is set")]
+    Auto,
+
+    Always,
+
+    Never,
+}
+
+impl ColorMode {
+    /// Whether to color what's written to `stream`
+    pub fn enabled(self, stream: &impl IsTerminal) -> bool {
+        match self {
+            ColorMode::Auto => stream.is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressFormat {
+    #[clap(help = "Newline-delimited JSON events on stderr")]
+    Json,
+
+    #[clap(help = "A progress bar on stderr, under the logs")]
+    Bar,
+
+    #[clap(help = "Just the logs")]
+    Logs,
+}
+
+#[derive(Parser)]
+#[command(about = "Check if any of the servers you are in is present in spy.pet's database")]
+pub struct Cli {
+    #[command(flatten)]
+    pub global: GlobalArgs,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// `check`'s arguments, for when no subcommand is given
+    #[command(flatten)]
+    pub check: CheckArgs,
+}
+
+#[derive(Args)]
+pub struct GlobalArgs {
+    #[arg(
+        long,
+        global = true,
+        env = "SPY_PET_LOG_FORMAT",
+        default_value = "text",
+        help = "Format of the logs on stderr"
+    )]
+    pub log_format: LogFormat,
+
+    #[arg(
+        long,
+        global = true,
+        env = "SPY_PET_LOG_FILE",
+        help = "Also append logs to this file"
+    )]
+    pub log_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        global = true,
+        env = "SPY_PET_LOG_FILE_LEVEL",
+        default_value = "debug",
+        help = "Most verbose level written to --log-file"
+    )]
+    pub log_file_level: tracing::Level,
+
+    #[arg(
+        long,
+        global = true,
+        env = "SPY_PET_LOG_ROTATE",
+        requires = "log_file",
+        help = "Start a new --log-file every hour or day, named after it with the date appended"
+    )]
+    pub log_rotate: Option<LogRotation>,
+
+    #[arg(
+        long,
+        global = true,
+        env = "SPY_PET_LOG_KEEP",
+        requires = "log_rotate",
+        help = "Delete all but this many of the newest rotated log files"
+    )]
+    pub log_keep: Option<usize>,
+
+    #[arg(
+        long,
+        global = true,
+        env = "SPY_PET_COLOR",
+        default_value = "auto",
+        help = "Color the logs and the plain output"
+    )]
+    pub color: ColorMode,
+
+    #[arg(
+        long,
+        global = true,
+        env = "SPY_PET_LANG",
+        help = "Language of the plain output [default: the system's]",
+        long_help = "Language of the plain output: en, de, es, fr, pt or ru, or a locale like pt_BR.UTF-8. Without it, the system's locale decides, and English is used when there's no translation for it. Logs and the other formats are always in English"
+    )]
+    pub lang: Option<Lang>,
+
+    #[arg(
+        short,
+        long,
+        global = true,
+        action = clap::ArgAction::Count,
+        conflicts_with = "quiet",
+        help = "Log more on stderr: -v for debug, -vv for trace",
+        long_help = "Log more on stderr: -v for debug, -vv for trace. Dependencies stay at info. Either this or --quiet replaces RUST_LOG"
+    )]
+    pub verbose: u8,
+
+    #[arg(
+        short,
+        long,
+        global = true,
+        action = clap::ArgAction::Count,
+        help = "Log less on stderr: -q for warnings and errors, -qq for just errors"
+    )]
+    pub quiet: u8,
+
+    #[cfg(feature = "otel")]
+    #[arg(
+        long,
+        global = true,
+        env = "SPY_PET_OTLP_ENDPOINT",
+        help = "Export traces to this OTLP/HTTP collector, e.g. http://localhost:4318",
+        long_help = "Export traces to this OTLP/HTTP collector, e.g. http://localhost:4318. Defaults to OTEL_EXPORTER_OTLP_TRACES_ENDPOINT or OTEL_EXPORTER_OTLP_ENDPOINT when they're set"
+    )]
+    pub otlp_endpoint: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        env = "SPY_PET_CONFIG",
+        help = "Path to config file",
+        long_help = "Path to config file [default: ./spy-pet-checker.toml if there is one, else ~/.config/spy-pet-checker/config.toml]"
+    )]
+    pub config: Option<PathBuf>,
+
+    #[arg(
+        long,
+        global = true,
+        env = "SPY_PET_STATE_DIR",
+        help = "Directory for the cache, checkpoints and run history",
+        long_help = "Directory for the cache, checkpoints and run history [default: ~/.local/state/spy-pet-checker]"
+    )]
+    pub state_dir: Option<PathBuf>,
+
+    #[arg(
+        long,
+        global = true,
+        env = "SPY_PET_NO_STATE",
+        conflicts_with = "state_dir",
+        help = "Don't persist anything to disk besides the output"
+    )]
+    pub no_state: bool,
+
+    // accepted without self-update too, where there's no check to skip, so
+    // the same command line works on every build
+    #[arg(
+        long,
+        global = true,
+        env = "SPY_PET_NO_UPDATE_CHECK",
+        help = "Don't check for new releases in the background"
+    )]
+    pub no_update_check: bool,
+}
+
+impl GlobalArgs {
+    /// The filter `--verbose` or `--quiet` ask for on stderr, if either was
+    /// given
+    pub fn log_filter(&self) -> Option<String> {
+        let level = match (self.verbose, self.quiet) {
+            (0, 0) => return None,
+            (0, 1) => "warn",
+            (0, _) => "error",
+            (1, _) => "debug",
+            (_, _) => "trace",
+        };
+        Some(match self.verbose {
+            0 => level.to_owned(),
+            _ => format!("info,spy_pet_checker={level}"),
+        })
+    }
+
+    /// The language of the plain output, `--lang` or the system's
+    pub fn lang(&self) -> Lang {
+        self.lang.unwrap_or_else(Lang::detect)
+    }
+}
+
+// parsed once at startup, so the size of `check`'s arguments doesn't matter
+#[allow(clippy::large_enum_variant)]
+#[derive(ValueEnum, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenType {
+    #[clap(help = "A user account's token")]
+    User,
+
+    #[clap(help = "A bot's token")]
+    Bot,
+}
+
+impl TokenType {
+    pub fn into_token_type(self) -> discord::TokenType {
+        match self {
+            TokenType::User => discord::TokenType::User,
+            TokenType::Bot => discord::TokenType::Bot,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    #[command(about = "Check the servers in an index against spy.pet (default)")]
+    Check(Box<CheckArgs>),
+
+    #[command(about = "Check user IDs against spy.pet, like check --kind users")]
+    CheckUsers(Box<CheckArgs>),
+
+    #[command(about = "Write an index of your servers, from Discord or an export")]
+    FetchGuilds(FetchGuildsArgs),
+
+    #[command(about = "Check the servers a Discord bot is in and post each one its result")]
+    Bot(Box<BotArgs>),
+
+    #[command(about = "List and reopen previous runs")]
+    History(HistoryArgs),
+
+    #[command(about = "Inspect and prune the result cache")]
+    Cache(CacheArgs),
+
+    #[command(about = "Combine several result files into one report")]
+    Merge(MergeArgs),
+
+    #[command(about = "Summarize a result file without querying the API")]
+    Stats(StatsArgs),
+
+    #[command(about = "Look for problems in an index without querying the API")]
+    Validate(ValidateArgs),
+
+    #[command(about = "Show how results changed between two result files")]
+    Diff(DiffArgs),
+
+    #[command(about = "Ask each backend about one server to see whether it's answering")]
+    Healthcheck(Box<HealthcheckArgs>),
+
+    #[command(
+        about = "Look for known scraper bots in the member lists of the servers a bot is in"
+    )]
+    ScanBots(Box<ScanBotsArgs>),
+
+    #[command(about = "Download a dataset's whole server list, to check against offline")]
+    Dump(Box<DumpArgs>),
+
+    #[command(about = "Render a result file in another format")]
+    Report(ReportArgs),
+
+    #[cfg(feature = "sign")]
+    #[command(about = "Check that a report signed with --sign wasn't changed")]
+    Verify(VerifyArgs),
+
+    #[cfg(feature = "serve")]
+    #[command(about = "Answer checks over HTTP")]
+    Serve(Box<ServeArgs>),
+
+    #[cfg(feature = "self-update")]
+    #[command(about = "Replace this executable with the latest release")]
+    SelfUpdate(Box<SelfUpdateArgs>),
+
+    #[command(about = "Authorize listing your servers through Discord in the browser")]
+    Auth(AuthArgs),
+
+    #[cfg(feature = "keyring")]
+    #[command(about = "Store the Discord token or the API key in the system keyring")]
+    Login(LoginArgs),
+
+    #[cfg(feature = "keyring")]
+    #[command(about = "Remove the Discord token or the API key from the system keyring")]
+    Logout(LogoutArgs),
+
+    #[command(about = "Manage the Discord token or the API key")]
+    Token(TokenArgs),
+
+    #[command(about = "Print a tab completion script for a shell")]
+    Completions(CompletionsArgs),
+
+    #[command(hide = true, about = "Print the man page, for packaging")]
+    Mangen,
+}
+
+#[derive(Args)]
+pub struct TokenArgs {
+    #[arg(
+        long,
+        global = true,
+        help = "Manage the API key for the spy.pet API instead of the Discord token"
+    )]
+    pub api_key: bool,
+
+    #[command(subcommand)]
+    pub action: TokenAction,
+}
+
+#[derive(Args)]
+pub struct AuthArgs {
+    #[arg(
+        long,
+        env = "SPY_PET_OAUTH_CLIENT_ID",
+        value_name = "ID",
+        required_unless_present = "forget",
+        help = "The client ID of the Discord application to authorize"
+    )]
+    pub client_id: Option<String>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_OAUTH_CLIENT_SECRET_FILE",
+        value_name = "PATH",
+        help = "Read the application's client secret from this file, or prompt for it with -",
+        long_help = "Read the application's client secret from this file, or prompt for it with -. SPY_PET_OAUTH_CLIENT_SECRET is read otherwise; without either, the application must be a public client"
+    )]
+    pub client_secret_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        default_value_t = 53134,
+        help = "Listen for the browser coming back on this port of 127.0.0.1",
+        long_help = "Listen for the browser coming back on this port of 127.0.0.1. The application needs http://127.0.0.1:<PORT>/callback among its redirects"
+    )]
+    pub port: u16,
+
+    #[arg(long, help = "Print the link to open instead of opening the browser")]
+    pub no_browser: bool,
+
+    #[arg(
+        long,
+        value_parser = humantime::parse_duration,
+        default_value = "5m",
+        help = "How long to wait for the authorization"
+    )]
+    pub timeout: Duration,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["client_secret_file", "no_browser"],
+        help = "Revoke the stored token and remove it"
+    )]
+    pub forget: bool,
+}
+
+#[cfg(feature = "keyring")]
+#[derive(Args)]
+pub struct LoginArgs {
+    #[arg(
+        long,
+        help = "Store the API key for the spy.pet API instead of the Discord token"
+    )]
+    pub api_key: bool,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Take it from this file instead of prompting, to move it out of the file",
+        long_help = "Take it from this file instead of prompting for it, to move a token kept in a file into the keyring. The file can be deleted afterwards"
+    )]
+    pub from_file: Option<PathBuf>,
+}
+
+#[cfg(feature = "keyring")]
+#[derive(Args)]
+pub struct LogoutArgs {
+    #[arg(long, help = "Remove the API key instead of the Discord token")]
+    pub api_key: bool,
+
+    #[arg(long, conflicts_with = "api_key", help = "Remove both")]
+    pub all: bool,
+}
+
+#[derive(Subcommand)]
+pub enum TokenAction {
+    #[cfg(feature = "keyring")]
+    #[command(about = "Prompt for a token and store it in the system keyring")]
+    Set,
+
+    #[cfg(feature = "keyring")]
+    #[command(about = "Remove the token from the system keyring")]
+    Clear,
+
+    #[command(about = "Show where the token would be read from")]
+    Status {
+        #[arg(
+            long,
+            visible_alias = "api-key-file",
+            help = "Read the token from this file, or prompt for it with -"
+        )]
+        token_file: Option<PathBuf>,
+    },
+}
+
+#[cfg(feature = "self-update")]
+#[derive(Args)]
+pub struct SelfUpdateArgs {
+    #[arg(long, help = "Only report whether an update is available")]
+    pub check: bool,
+
+    #[command(flatten)]
+    pub request: RequestArgs,
+}
+
+#[cfg(feature = "serve")]
+#[derive(Args)]
+pub struct ServeArgs {
+    #[arg(
+        long,
+        env = "SPY_PET_LISTEN",
+        default_value = "127.0.0.1:8080",
+        help = "Address to listen on"
+    )]
+    pub listen: std::net::SocketAddr,
+
+    #[arg(
+        long,
+        env = "SPY_PET_MAX_INBOUND",
+        default_value_t = 16,
+        help = "Max number of HTTP requests handled at once"
+    )]
+    pub max_inbound: usize,
+
+    #[arg(
+        long,
+        env = "SPY_PET_MAX_GUILDS",
+        default_value_t = 1000,
+        help = "Max number of servers in one POST /check"
+    )]
+    pub max_guilds: usize,
+
+    #[arg(
+        long,
+        env = "SPY_PET_MAX_RUNS",
+        default_value_t = 4,
+        help = "Max number of POST /check runs going at once, background ones included"
+    )]
+    pub max_runs: usize,
+
+    #[arg(
+        long,
+        env = "SPY_PET_CACHE_TTL",
+        value_parser = humantime::parse_duration,
+        help = "Answer servers checked within this long (e.g. 24h) from the result cache"
+    )]
+    pub cache_ttl: Option<Duration>,
+
+    #[cfg(feature = "metrics")]
+    #[arg(
+        long,
+        env = "SPY_PET_METRICS_LISTEN",
+        help = "Serve Prometheus metrics on this address"
+    )]
+    pub metrics_listen: Option<std::net::SocketAddr>,
+
+    #[command(flatten)]
+    pub request: RequestArgs,
+}
+
+#[derive(Args)]
+pub struct HealthcheckArgs {
+    #[arg(
+        long,
+        value_name = "ID",
+        default_value = PROBE_ID,
+        help = "The server to ask about; any answer but an error counts as up"
+    )]
+    pub probe_id: String,
+
+    #[arg(long, help = "Print the result of each backend as a line of JSON")]
+    pub json: bool,
+
+    #[command(flatten)]
+    pub check: CheckArgs,
+}
+
+#[derive(Args)]
+pub struct ScanBotsArgs {
+    #[arg(
+        long,
+        help = "Download the latest list of known scraper bots instead of scanning",
+        long_help = "Download the latest list of known scraper bots into the state directory instead of scanning. Later scans, and --scan-members, use it next to the list bundled with this version"
+    )]
+    pub update_list: bool,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        default_value = KNOWN_BOTS_URL,
+        requires = "update_list",
+        help = "Where --update-list downloads the list from"
+    )]
+    pub list_url: String,
+
+    #[arg(long, help = "Print the scan of each server as a line of JSON")]
+    pub json: bool,
+
+    #[command(flatten)]
+    pub check: CheckArgs,
+}
+
+#[derive(Args)]
+pub struct DumpArgs {
+    #[arg(
+        value_name = "FILE",
+        help = "Where to save the dataset, for check --local-dataset"
+    )]
+    pub path: PathBuf,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        default_value = DEFAULT_LIST_URL,
+        help = "The server list to download, with {page} for the page number",
+        long_help = "The server list to download, with {page} for the page number: pages are requested from 1 until one is empty or 404. A page is an array of IDs or of objects with an id, or an object keyed by ID"
+    )]
+    pub list_url: String,
+
+    #[arg(
+        long,
+        default_value_t = 10_000,
+        help = "Stop after this many pages, in case the list never ends"
+    )]
+    pub max_pages: u32,
+
+    #[command(flatten)]
+    pub check: CheckArgs,
+}
+
+/// Discord Developers, which every backend should know about
+pub const PROBE_ID: &str = "613425648685547541";
+
+#[derive(Args)]
+pub struct BotArgs {
+    #[arg(
+        long = "channel",
+        env = "SPY_PET_BOT_CHANNEL",
+        value_name = "GUILD=CHANNEL",
+        value_delimiter = ',',
+        value_parser = parse_channel,
+        help = "Post the server's result in this channel (repeatable)"
+    )]
+    pub channels: Vec<(String, String)>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_BOT_DM_OWNER",
+        help = "DM the owner of each server without a --channel its result"
+    )]
+    pub dm_owner: bool,
+
+    #[arg(
+        long,
+        env = "SPY_PET_BOT_POST_CLEAN",
+        help = "Also post to the servers that aren't compromised"
+    )]
+    pub post_clean: bool,
+
+    #[cfg(feature = "serve")]
+    #[arg(
+        long,
+        env = "SPY_PET_BOT_INTERACTIONS_LISTEN",
+        requires = "public_key",
+        help = "Answer /spy-pet-check on this address, as the bot's interactions endpoint",
+        long_help = "Register the /spy-pet-check command and answer it on this address, which has to be reachable as the application's Interactions Endpoint URL (with the /interactions path). Whoever can manage a server can then check it on demand and get the result back only they can see. This keeps running until interrupted, after posting the results of the first check if there's a --channel or --dm-owner"
+    )]
+    pub interactions_listen: Option<std::net::SocketAddr>,
+
+    #[cfg(feature = "serve")]
+    #[arg(
+        long,
+        env = "SPY_PET_BOT_PUBLIC_KEY",
+        value_parser = parse_public_key,
+        help = "The application's public key, to check interactions are from Discord"
+    )]
+    pub public_key: Option<[u8; 32]>,
+
+    #[command(flatten)]
+    pub check: CheckArgs,
+}
+
+#[derive(Args)]
+pub struct CompletionsArgs {
+    #[arg(help = "Shell to write the script for")]
+    pub shell: Shell,
+}
+
+#[derive(Args)]
+pub struct StatsArgs {
+    #[arg(help = "Result file, as JSON or newline-delimited JSON")]
+    pub file: PathBuf,
+
+    #[arg(short, long, default_value = "plain", help = "output format")]
+    pub format: SummaryFormat,
+
+    #[arg(
+        short,
+        long,
+        default_value_t = 10,
+        help = "Number of most exposed servers to list"
+    )]
+    pub top: usize,
+
+    #[arg(short, long, help = "Output to file instead of stdout")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct ValidateArgs {
+    #[arg(
+        short,
+        long,
+        value_name = "PATH",
+        default_value = "index.json",
+        help = "Index file or directory of index files to look at (repeatable)"
+    )]
+    pub index_path: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Read the index as id → name, without checking which side holds the IDs"
+    )]
+    pub no_autodetect: bool,
+
+    #[arg(long, value_enum, default_value_t = IndexFormat::Auto, help = "How the index is written")]
+    pub index_format: IndexFormat,
+
+    #[arg(
+        long,
+        help = "Print the indexes merged and cleaned up, as one JSON index",
+        long_help = "Print the indexes merged and cleaned up, as one JSON index of IDs to names, to standard output. The problems found go to standard error instead"
+    )]
+    pub normalize: bool,
+}
+
+#[derive(Args)]
+#[command(group(
+    clap::ArgGroup::new("source")
+        .required(true)
+        .args(["from_discord", "from_data_package", "from_dce"])
+))]
+pub struct FetchGuildsArgs {
+    #[arg(
+        long,
+        help = "List the servers your account is in through Discord's API"
+    )]
+    pub from_discord: bool,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "List the servers in a Discord data package (package.zip or its extracted directory)"
+    )]
+    pub from_data_package: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "List the servers of DiscordChatExporter JSON exports (a file, a directory or a glob)"
+    )]
+    pub from_dce: Option<String>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_TOKEN_FILE",
+        help = "Read the Discord token for --from-discord from this file, or prompt for it with -"
+    )]
+    pub token_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_TOKEN_TYPE",
+        value_enum,
+        default_value = "user",
+        help = "Whether the Discord token is a user's or a bot's"
+    )]
+    pub token_type: TokenType,
+
+    #[arg(short, long, help = "Write the index to this file instead of stdout")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct DiffArgs {
+    #[arg(help = "The earlier result file, as JSON or newline-delimited JSON")]
+    pub old: PathBuf,
+
+    #[arg(help = "The later result file")]
+    pub new: PathBuf,
+
+    #[arg(short, long, default_value = "plain", help = "output format")]
+    pub format: Format,
+
+    #[arg(short, long, help = "Output to file instead of stdout")]
+    pub output: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FIELD",
+        help = "Don't report changes to this field (repeatable)",
+        long_help = "Don't report changes to this field (repeatable). A name like last_seen matches the field at any depth, a JSON pointer like /guild/last_seen only that one"
+    )]
+    pub ignore_field: Vec<String>,
+}
+
+#[cfg(feature = "sign")]
+#[derive(Args)]
+pub struct VerifyArgs {
+    #[arg(help = "The signed report")]
+    pub report: PathBuf,
+
+    #[arg(
+        long,
+        env = "SPY_PET_VERIFY_KEY",
+        help = "The public key it should be signed with, in PEM"
+    )]
+    pub key: PathBuf,
+
+    #[arg(
+        long,
+        help = "The detached signature, if it isn't next to the report with .sig added"
+    )]
+    pub signature: Option<PathBuf>,
+
+    #[arg(
+        short,
+        long,
+        help = "Write the report that was signed to this file, e.g. to read a JSON one"
+    )]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct ReportArgs {
+    #[arg(help = "Result file, as JSON or newline-delimited JSON")]
+    pub file: PathBuf,
+
+    #[arg(short, long, default_value = "plain", help = "output format")]
+    pub format: Format,
+
+    #[arg(
+        long,
+        help = "List results under a heading per group, with subtotals",
+        long_help = "List results under a heading per group, with subtotals. With the JSON format, the output also has a summary of each group"
+    )]
+    pub group_by: Option<GroupBy>,
+
+    #[arg(
+        long,
+        help = "Write the results through this template instead of --format"
+    )]
+    pub template: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Write the results in this order instead of the order the checks finished in",
+        long_help = "Write the results in this order instead of the order the checks finished in, so the output of two runs can be diffed. Ties are broken by server ID. The checks that failed are listed by server ID"
+    )]
+    pub sort_by: Option<SortBy>,
+
+    #[arg(long, help = "Write JSON on a single line instead of pretty-printed")]
+    pub json_compact: bool,
+
+    #[arg(
+        long,
+        conflicts_with = "include_clean",
+        help = "Only write the compromised servers, and the checks that failed"
+    )]
+    pub only_compromised: bool,
+
+    #[arg(long, help = "List the clean servers in the plain output too")]
+    pub include_clean: bool,
+
+    #[arg(
+        long,
+        help = "List what the dataset has on each compromised server in the plain output"
+    )]
+    pub details: bool,
+
+    #[arg(short, long, help = "Output to file instead of stdout")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct MergeArgs {
+    #[arg(
+        required = true,
+        help = "Result files, as JSON or newline-delimited JSON"
+    )]
+    pub files: Vec<PathBuf>,
+
+    #[arg(short, long, default_value = "json", help = "output format")]
+    pub format: Format,
+
+    #[arg(short, long, help = "Output to file instead of stdout")]
+    pub output: Option<PathBuf>,
+
+    #[arg(long, help = "Skip files that can't be read instead of failing")]
+    pub skip_invalid: bool,
+}
+
+#[derive(Args)]
+pub struct HistoryArgs {
+    #[command(subcommand)]
+    pub action: Option<HistoryAction>,
+}
+
+#[derive(Subcommand)]
+pub enum HistoryAction {
+    #[command(about = "List previous runs (default)")]
+    List {
+        #[arg(
+            long,
+            value_parser = parse_date,
+            help = "Only runs started from this date on (RFC 3339 or YYYY-MM-DD)"
+        )]
+        since: Option<DateTime<Utc>>,
+
+        #[arg(
+            long,
+            value_parser = parse_date,
+            help = "Only runs started before this date (RFC 3339 or YYYY-MM-DD)"
+        )]
+        until: Option<DateTime<Utc>>,
+    },
+
+    #[command(about = "Render a stored run without querying the API again")]
+    Show {
+        run_id: String,
+
+        #[arg(short, long, default_value = "plain", help = "output format")]
+        format: Format,
+
+        #[arg(short, long, help = "Output to file instead of stdout")]
+        output: Option<PathBuf>,
+    },
+
+    #[command(about = "Show how results changed between two stored runs")]
+    Diff {
+        #[arg(help = "The earlier run")]
+        old: String,
+
+        #[arg(help = "The later run")]
+        new: String,
+
+        #[arg(short, long, default_value = "plain", help = "output format")]
+        format: Format,
+
+        #[arg(short, long, help = "Output to file instead of stdout")]
+        output: Option<PathBuf>,
+
+        #[arg(
+            long,
+            value_name = "FIELD",
+            help = "Don't report changes to this field (repeatable)",
+            long_help = "Don't report changes to this field (repeatable). A name like last_seen matches the field at any depth, a JSON pointer like /guild/last_seen only that one"
+        )]
+        ignore_field: Vec<String>,
+    },
+
+    #[command(about = "Show what every stored run found about a server or user")]
+    Server {
+        id: String,
+
+        #[arg(long, help = "Only list the runs where its status changed")]
+        changes: bool,
+    },
+
+    #[command(about = "Delete all but the most recent runs")]
+    Prune {
+        #[arg(long, help = "Number of runs to keep")]
+        keep: usize,
+    },
+}
+
+#[derive(Args)]
+pub struct CacheArgs {
+    #[command(subcommand)]
+    pub action: Option<CacheAction>,
+}
+
+#[derive(Subcommand)]
+pub enum CacheAction {
+    #[command(about = "List cached answers and the state directory's disk usage (default)")]
+    Ls {
+        #[arg(
+            long,
+            env = "SPY_PET_CACHE_TTL",
+            value_parser = humantime::parse_duration,
+            help = "Count answers younger than this as fresh, instead of the configured --cache-ttl"
+        )]
+        cache_ttl: Option<Duration>,
+    },
+
+    #[command(about = "Print a cached answer")]
+    Show {
+        guild_id: String,
+
+        #[arg(long, help = "The backend whose answer to print, if several have one")]
+        source: Option<String>,
+    },
+
+    #[command(about = "Remove the cached answers about a server")]
+    Rm { guild_id: String },
+
+    #[command(about = "Remove cached answers")]
+    Clear {
+        #[arg(
+            long,
+            value_parser = humantime::parse_duration,
+            help = "Only remove answers older than this (e.g. 7d)"
+        )]
+        older_than: Option<Duration>,
+
+        #[arg(short, long, help = "Don't ask first; needed without a terminal")]
+        yes: bool,
+    },
+}
+
+#[derive(Args)]
+pub struct CheckArgs {
+    #[command(flatten)]
+    pub request: RequestArgs,
+
+    #[arg(
+        short,
+        long,
+        env = "SPY_PET_INDEX_PATH",
+        value_name = "[LABEL=]PATH",
+        default_value = "index.json",
+        help = "Path to index.json containing server names and IDs (repeatable)",
+        long_help = "Path to index.json containing server names and IDs (repeatable), or - for standard input. An index that isn't a JSON object is read as plain text, an ID per line with an optional name after a tab. With a label, e.g. alice=alice.json, the results of the servers in it are marked with the label, and the report counts each label's separately"
+    )]
+    pub index_path: Vec<IndexPath>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_NO_AUTODETECT",
+        help = "Read the index as id → name, without checking which side holds the IDs"
+    )]
+    pub no_autodetect: bool,
+
+    #[arg(
+        long,
+        env = "SPY_PET_INDEX_FORMAT",
+        value_enum,
+        default_value_t = IndexFormat::Auto,
+        help = "How the index is written"
+    )]
+    pub index_format: IndexFormat,
+
+    #[arg(
+        long,
+        env = "SPY_PET_FROM_DCE",
+        value_name = "PATH",
+        help = "Check the servers of DiscordChatExporter JSON exports instead of index.json",
+        long_help = "Check the servers of DiscordChatExporter JSON exports instead of index.json. Takes a file, a directory (searched recursively for .json files) or a glob"
+    )]
+    pub from_dce: Option<String>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_FROM_DATA_PACKAGE",
+        value_name = "PATH",
+        conflicts_with = "from_dce",
+        help = "Check the servers in a Discord data package instead of index.json",
+        long_help = "Check the servers in a Discord data package instead of index.json. Takes the package.zip Discord sends, or the directory it was extracted to"
+    )]
+    pub from_data_package: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_INCLUDE_USERS",
+        requires = "from_data_package",
+        help = "Also check the friends and DM contacts in --from-data-package"
+    )]
+    pub include_users: bool,
+
+    #[arg(
+        long,
+        env = "SPY_PET_FROM_DISCORD",
+        conflicts_with_all = ["from_dce", "from_data_package"],
+        help = "Check the servers the Discord token's account is in instead of index.json",
+        long_help = "Check the servers the Discord token's account is in instead of index.json, listing them through Discord's API. The token is read from --token-file, SPY_PET_DISCORD_TOKEN, DISCORD_TOKEN or the keyring"
+    )]
+    pub from_discord: bool,
+
+    #[arg(
+        long,
+        env = "SPY_PET_TOKEN_TYPE",
+        default_value = "user",
+        help = "Whether the Discord token for --from-discord is a user's or a bot's"
+    )]
+    pub token_type: TokenType,
+
+    #[arg(
+        long,
+        env = "SPY_PET_IDS",
+        value_name = "ID",
+        value_delimiter = ',',
+        conflicts_with_all = ["from_dce", "from_data_package", "from_discord", "simulate"],
+        help = "Check these IDs instead of an index (repeatable)"
+    )]
+    pub ids: Vec<String>,
+
+    #[arg(
+        long = "channels",
+        env = "SPY_PET_CHANNELS",
+        value_name = "ID",
+        value_delimiter = ',',
+        conflicts_with_all = ["ids", "kind", "from_dce", "from_data_package", "from_discord", "simulate", "invites"],
+        help = "Check these channel IDs, like --kind channels --ids (repeatable)"
+    )]
+    pub channel_ids: Vec<String>,
+
+    #[arg(
+        long = "invite",
+        env = "SPY_PET_INVITE",
+        value_name = "URL",
+        value_delimiter = ',',
+        value_parser = parse_invite,
+        conflicts_with_all = ["ids", "from_dce", "from_data_package", "from_discord", "simulate"],
+        help = "Check the server this invite link is for (repeatable)",
+        long_help = "Check the server this invite link (discord.gg/<code> or discord.com/invite/<code>) is for, looked up through Discord's public invite API (repeatable). The index is still checked too if --index-path is given; an index can also list invite links in place of IDs"
+    )]
+    pub invites: Vec<String>,
+
+    #[arg(
+        long,
+        visible_alias = "include-id",
+        env = "SPY_PET_ONLY",
+        value_name = "ID",
+        value_delimiter = ',',
+        help = "Only check these IDs of the index (repeatable)"
+    )]
+    pub only: Vec<String>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_EXCLUDE_ID",
+        value_name = "ID",
+        value_delimiter = ',',
+        help = "Don't check these IDs of the index (repeatable)"
+    )]
+    pub exclude_id: Vec<String>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_NAME_FILTER",
+        value_name = "REGEX",
+        value_parser = parse_regex,
+        help = "Only check the servers whose name matches this regex",
+        long_help = "Only check the servers whose name matches this regex, anywhere in the name unless it's anchored with ^ and $. (?i) at the start ignores case"
+    )]
+    pub name_filter: Option<String>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_SHARD",
+        value_name = "I/N",
+        help = "Only check the I-th of N shards the servers are split into",
+        long_help = "Only check the I-th of N shards the servers are split into, counting from 1, to split a big run between machines: each one runs with the same servers and its own --shard, and `merge` combines their JSON results. Which shard a server is in depends only on its ID"
+    )]
+    pub shard: Option<Shard>,
+
+    #[cfg(feature = "pick")]
+    #[arg(
+        long,
+        conflicts_with_all = ["watch", "print_config", "dry_run"],
+        help = "Pick the servers to check from a searchable list first"
+    )]
+    pub pick: bool,
+
+    #[cfg(feature = "pick")]
+    #[arg(
+        long,
+        value_name = "PATH",
+        requires = "pick",
+        help = "Save the picked servers as an index, to run on them again with --index-path"
+    )]
+    pub pick_save: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_KIND",
+        default_value = "servers",
+        help = "What the IDs in the index are"
+    )]
+    pub kind: CheckKind,
+
+    #[arg(
+        short,
+        long,
+        env = "SPY_PET_FORMAT",
+        default_value = "plain",
+        help = "output format (repeatable, each with its --output)",
+        long_help = "output format. Repeatable, to write the same run several ways: each --format goes with the --output in the same position, e.g. --format json -o results.json --format plain writes JSON to results.json and plain text to stdout. An --output past the last --format is written in the last format"
+    )]
+    pub format: Vec<Format>,
+
+    #[arg(
+        short,
+        long,
+        env = "SPY_PET_OUTPUT",
+        help = "Output to file instead of stdout (repeatable, see --format)"
+    )]
+    pub output: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_APPEND",
+        conflicts_with = "atomic",
+        help = "Add to the end of --output instead of replacing it",
+        long_help = "Add to the end of --output instead of replacing it, e.g. to keep newline-delimited JSON from every run in one file"
+    )]
+    pub append: bool,
+
+    #[arg(
+        long,
+        env = "SPY_PET_ATOMIC",
+        help = "Write --output to a temporary file and only put it in place once complete",
+        long_help = "Write --output to a temporary file next to it and only rename it over --output once the report is complete, so a run that crashes or is killed halfway leaves the previous report as it was"
+    )]
+    pub atomic: bool,
+
+    #[arg(
+        long,
+        env = "SPY_PET_ENCRYPT",
+        value_name = "RECIPIENT",
+        conflicts_with_all = ["append", "watch"],
+        help = "Encrypt the report with age to this recipient, or to a passphrase (repeatable)",
+        long_help = "Encrypt the report with age, so only the holders of the keys can read it: an age1... public key, repeatable for several, or `passphrase` for one read from --passphrase-file or SPY_PET_PASSPHRASE. Works with every format and with each --output; decrypt with age -d. Nothing is written unencrypted if the recipients or passphrase are wrong, and a terminal isn't written to at all"
+    )]
+    pub encrypt: Vec<String>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_PASSPHRASE_FILE",
+        value_name = "PATH",
+        help = "Read the --encrypt passphrase from this file, or `-` to prompt"
+    )]
+    pub passphrase_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_SIGN",
+        value_name = "KEY",
+        conflicts_with_all = ["append", "watch"],
+        help = "Sign the report with this Ed25519 private key, to check with verify",
+        long_help = "Sign the report with this Ed25519 private key in PEM, e.g. from openssl genpkey -algorithm ed25519, so whoever it's shared with can check with verify and the public key that it wasn't changed. JSON reports are written inside a JWS, other formats get a detached signature in the --output file name with .sig added"
+    )]
+    pub sign: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_GROUP_BY",
+        help = "List results under a heading per group, with subtotals",
+        long_help = "List results under a heading per group, with subtotals. With the JSON format, the output also has a summary of each group"
+    )]
+    pub group_by: Option<GroupBy>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_TEMPLATE",
+        conflicts_with = "watch",
+        help = "Write the results through this template instead of --format",
+        long_help = "Write the results through this template instead of --format. Templates use a Tera-style syntax: {{ guild.guild_name }} prints a value, {% for guild in compromised %}...{% endfor %} and {% if %}...{% else %}...{% endif %} control what's printed. See the README for the values and filters there are"
+    )]
+    pub template: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_SORT_BY",
+        help = "Write the results in this order instead of the order the checks finished in",
+        long_help = "Write the results in this order instead of the order the checks finished in, so the output of two runs can be diffed. Ties are broken by server ID. The checks that failed are listed by server ID"
+    )]
+    pub sort_by: Option<SortBy>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_JSON_COMPACT",
+        help = "Write JSON on a single line instead of pretty-printed"
+    )]
+    pub json_compact: bool,
+
+    #[arg(
+        long,
+        env = "SPY_PET_ONLY_COMPROMISED",
+        conflicts_with = "include_clean",
+        help = "Only write the compromised servers, and the checks that failed",
+        long_help = "Only write the compromised servers, and the checks that failed, leaving out the results that came back clean or undecided. The history still records every result"
+    )]
+    pub only_compromised: bool,
+
+    #[arg(
+        long,
+        env = "SPY_PET_INCLUDE_CLEAN",
+        help = "List the clean servers in the plain output too",
+        long_help = "List the clean servers in the plain output too, for a complete record of what was checked. The other formats always have them"
+    )]
+    pub include_clean: bool,
+
+    #[arg(
+        long,
+        env = "SPY_PET_DETAILS",
+        help = "List what the dataset has on each compromised server in the plain output",
+        long_help = "List what the dataset has on each compromised server in the plain output: its name there, how many messages were archived, its member count and when it was first and last seen, as far as the answer says"
+    )]
+    pub details: bool,
+
+    #[arg(
+        long,
+        env = "SPY_PET_SINCE",
+        value_parser = parse_date,
+        help = "Only count dataset activity from this date on (RFC 3339 or YYYY-MM-DD)",
+        long_help = "Only count dataset activity from this date on (RFC 3339 or YYYY-MM-DD), going by the first and last seen times in the answers"
+    )]
+    pub since: Option<DateTime<Utc>>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_UNTIL",
+        value_parser = parse_date,
+        help = "Only count dataset activity up to this date (RFC 3339 or YYYY-MM-DD)"
+    )]
+    pub until: Option<DateTime<Utc>>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_SINCE_MODE",
+        default_value = "annotate",
+        help = "What to do with compromised servers outside --since/--until"
+    )]
+    pub since_mode: SinceMode,
+
+    #[arg(
+        long,
+        env = "SPY_PET_WATCH",
+        value_parser = humantime::parse_duration,
+        help = "Re-check on this interval (e.g. 12h) and only report changes",
+        long_help = "Re-check on this interval (e.g. 12h) and only report changes. The index is re-read every cycle, and SIGHUP starts the next one right away"
+    )]
+    pub watch: Option<Duration>,
+
+    #[cfg(feature = "metrics")]
+    #[arg(
+        long,
+        env = "SPY_PET_METRICS_LISTEN",
+        help = "Serve Prometheus metrics on this address in watch mode"
+    )]
+    pub metrics_listen: Option<std::net::SocketAddr>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_PROGRESS_FORMAT",
+        help = "How to report progress on stderr",
+        long_help = "How to report progress on stderr. Defaults to a progress bar when stderr is a terminal and the logs are text, and to just the logs otherwise"
+    )]
+    pub progress_format: Option<ProgressFormat>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_PROGRESS_EVENTS",
+        value_name = "PATH",
+        num_args = 0..=1,
+        help = "Write every step of the run as a JSON line, on stderr or to this file or named pipe",
+        long_help = "Write every step of the run as a line of JSON, for programs following it: the start, each server queued, each request started and finished, each result and error, and the summary. Without a path they replace the logs on stderr, like --progress-format json; with one they go to that file or named pipe, and stderr is left as it is"
+    )]
+    pub progress_events: Option<Option<PathBuf>>,
+
+    #[cfg(feature = "tui")]
+    #[arg(
+        long,
+        conflicts_with_all = ["progress_format", "progress_events", "watch", "print_config", "dry_run"],
+        help = "Follow the run on an interactive screen; the report is written when it's closed"
+    )]
+    pub tui: bool,
+
+    #[arg(
+        long,
+        env = "SPY_PET_MAX_ERRORS",
+        help = "Stop the run once this many checks have failed"
+    )]
+    pub max_errors: Option<usize>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_MAX_ERROR_RATE",
+        value_parser = parse_percentage,
+        help = "Stop the run once more than this percentage of checks have failed",
+        long_help = "Stop the run once more than this percentage of checks have failed (e.g. 50). Only applies after the first 20 checks"
+    )]
+    pub max_error_rate: Option<f64>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_FAIL_FAST",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "any",
+        help = "Stop at the first compromised server or failed check"
+    )]
+    pub fail_fast: Option<FailFast>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_FAIL_ON",
+        help = "Exit non-zero when the run found compromised servers or had failed checks",
+        long_help = "Exit non-zero once the run is done if it found compromised servers (with 2) or had failed checks (with 1), for using it as a CI gate. Unlike --fail-fast, every server is still checked"
+    )]
+    pub fail_on: Option<FailOn>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_RESUME",
+        conflicts_with = "watch",
+        help = "Pick up an interrupted run where it stopped",
+        long_help = "Pick up an interrupted run where it stopped: the servers it finished are taken from its checkpoint in the state directory instead of being checked again. Only a run over the same servers and backends is picked up"
+    )]
+    pub resume: bool,
+
+    #[arg(
+        long,
+        env = "SPY_PET_BASELINE",
+        value_name = "FILE",
+        conflicts_with = "watch",
+        help = "Leave out the servers this result file found compromised; \"previous\" for the last run in the history",
+        long_help = "Leave out of the output the servers already found compromised in this result file (JSON or newline-delimited JSON), so only new findings are reported. \"previous\" takes the last run in the history instead; name a file called that ./previous. The run is still recorded in full"
+    )]
+    pub baseline: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_STRICT",
+        help = "Fail on anything that would only be warned about",
+        long_help = "Fail on anything that would only be warned about and worked around: a flipped index, duplicate index keys, unreadable inputs, options that have no effect, and so on. A server whose answer can't be made sense of fails its check, and the run exits non-zero listing every such condition"
+    )]
+    pub strict: bool,
+
+    #[arg(
+        long,
+        env = "SPY_PET_CACHE_TTL",
+        value_parser = humantime::parse_duration,
+        help = "Answer servers checked within this long (e.g. 24h) from the result cache"
+    )]
+    pub cache_ttl: Option<Duration>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_NO_CACHE",
+        visible_alias = "force",
+        help = "Ask the backends about every server, ignoring --cache-ttl"
+    )]
+    pub no_cache: bool,
+
+    #[arg(
+        long,
+        env = "SPY_PET_DUMP_DIR",
+        help = "Save every raw response body, with its status and headers, in this directory"
+    )]
+    pub dump_dir: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_DUMP_OVERWRITE",
+        requires = "dump_dir",
+        help = "Replace dumps left in --dump-dir by earlier runs"
+    )]
+    pub dump_overwrite: bool,
+
+    #[arg(
+        long,
+        env = "SPY_PET_DEEP_SCAN",
+        help = "Ask --deep-endpoint about every compromised server, and add the answers to its result"
+    )]
+    pub deep_scan: bool,
+
+    #[arg(
+        long,
+        env = "SPY_PET_DEEP_ENDPOINT",
+        value_delimiter = ',',
+        value_parser = parse_url_template,
+        help = "URL template for --deep-scan (repeatable)",
+        long_help = "URL template for --deep-scan (repeatable). {id} is replaced with the server ID; with {page}, pages 1, 2, ... are requested until one is empty or 404"
+    )]
+    pub deep_endpoint: Vec<String>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_FALLBACK_WEB",
+        value_name = "URL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = web::DEFAULT_WEB_URL,
+        value_parser = parse_url_template,
+        help = "Read a server's page on the website when the API fails with a server error or a challenge",
+        long_help = "Read a server's page on the website when the API fails with a server error or a challenge. Results read off a page have the source web-fallback and low confidence. The page's URL template defaults to https://spy.pet/servers/{id}"
+    )]
+    pub fallback_web: Option<String>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_VERIFY",
+        help = "Ask again about every compromised server, marking it confirmed or flaky",
+        long_help = "Ask again about every compromised server once --verify-delay is up, marking it confirmed if the second answer agrees, flaky if it doesn't and unverified if there's no second answer. Results read off the website with --fallback-web aren't asked about again"
+    )]
+    pub verify: bool,
+
+    #[arg(
+        long,
+        env = "SPY_PET_VERIFY_DELAY",
+        value_parser = humantime::parse_duration,
+        default_value = "30s",
+        help = "How long after the first answer --verify asks again (e.g. 2m)"
+    )]
+    pub verify_delay: Duration,
+
+    #[arg(
+        long,
+        env = "SPY_PET_VERIFY_PROXY",
+        value_name = "URL",
+        requires = "verify",
+        help = "Ask again through this proxy instead of the first answer's route",
+        long_help = "Ask again through this proxy instead of the first answer's route, so a hiccup on that route isn't repeated. Takes the same URLs as --proxy, with no password"
+    )]
+    pub verify_proxy: Option<String>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_BATCH_SIZE",
+        requires = "cooldown",
+        help = "Send requests in batches of this many, resting for --cooldown in between"
+    )]
+    pub batch_size: Option<usize>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_COOLDOWN",
+        value_parser = humantime::parse_duration,
+        requires = "batch_size",
+        help = "How long to rest between --batch-size batches (e.g. 5m)"
+    )]
+    pub cooldown: Option<Duration>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_INCLUDE_HEADERS",
+        help = "Add the rate limit, tracing and content type headers of each answer to its result"
+    )]
+    pub include_headers: bool,
+
+    #[arg(
+        long,
+        env = "SPY_PET_MAX_RESPONSE_SIZE",
+        value_name = "BYTES",
+        value_parser = parse_size,
+        default_value = "16M",
+        help = "Fail a check whose answer is bigger than this (e.g. 512K, 0 for no limit)"
+    )]
+    pub max_response_size: u64,
+
+    #[arg(
+        long,
+        env = "SPY_PET_PREFILTER",
+        value_name = "URL_OR_PATH",
+        help = "Only check the servers in this listing of tracked IDs, marking the rest as not in the listing",
+        long_help = "Only check the servers in this listing of tracked IDs, marking the rest as not in the listing. Takes a URL or a file with a JSON array or object of IDs, or one ID per line. Downloaded listings are reused for a day (--no-cache fetches it again)"
+    )]
+    pub prefilter: Option<String>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_VERIFY_ALL",
+        help = "Check every server even with --prefilter"
+    )]
+    pub verify_all: bool,
+
+    #[arg(
+        long,
+        env = "SPY_PET_MIN_SHARED",
+        value_name = "N",
+        help = "Only report servers listed in at least N labelled indexes"
+    )]
+    pub min_shared: Option<usize>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_MIN_SEVERITY",
+        value_name = "LEVEL",
+        help = "Leave compromised servers below this severity out of the results",
+        long_help = "Leave compromised servers below this severity out of the results. The severity is scored from 0 to 100 out of the archived messages, how recently the server was seen and its member count; see the README for the scoring"
+    )]
+    pub min_severity: Option<SeverityLevel>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_SCAN_MEMBERS",
+        help = "Look for known scraper bots in every server's member list, with a Discord bot token",
+        long_help = "Look for known scraper bots in every server's member list, with a Discord bot token. The bot needs to be in the server and have the server members intent; servers it can't read are skipped"
+    )]
+    pub scan_members: bool,
+
+    #[arg(
+        long,
+        env = "SPY_PET_BOT_LIST",
+        value_delimiter = ',',
+        help = "More scraper bot user IDs for --scan-members, one per line with an optional label (repeatable)"
+    )]
+    pub bot_list: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_TOKEN_FILE",
+        help = "Read the Discord token for --from-discord or --scan-members from this file, or prompt for it with -"
+    )]
+    pub token_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_NOTIFY_ON",
+        help = "When a run is worth notifying about; recorded with each run"
+    )]
+    pub notify_on: Option<NotifyOn>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_DISCORD_WEBHOOK",
+        hide_env_values = true,
+        help = "Post compromised servers to this Discord webhook",
+        long_help = "Post compromised servers to this Discord webhook, an embed for each. Without --notify-on, it's posted to when a run finds any, or in watch mode when a cycle finds new ones; with it, when the condition is met"
+    )]
+    pub discord_webhook: Option<String>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_DISCORD_WEBHOOK_FILE",
+        conflicts_with = "discord_webhook",
+        help = "Read --discord-webhook's URL from this file, or prompt for it with -"
+    )]
+    pub discord_webhook_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_NOTIFY_URL",
+        hide_env_values = true,
+        group = "notify_target",
+        help = "Send the run's summary and compromised servers as JSON to this URL",
+        long_help = "Send the run's summary and compromised servers as JSON to this URL, e.g. a Slack or ntfy endpoint. It's sent when the Discord webhook would be"
+    )]
+    pub notify_url: Option<String>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_NOTIFY_URL_FILE",
+        group = "notify_target",
+        help = "Read --notify-url's URL from this file, or prompt for it with -"
+    )]
+    pub notify_url_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_NOTIFY_METHOD",
+        default_value = "post",
+        requires = "notify_target",
+        help = "How to send to --notify-url"
+    )]
+    pub notify_method: NotifyMethod,
+
+    #[arg(
+        long = "notify-header",
+        env = "SPY_PET_NOTIFY_HEADER",
+        hide_env_values = true,
+        value_name = "NAME: VALUE",
+        requires = "notify_target",
+        help = "Send this header to --notify-url (repeatable), e.g. its authorization"
+    )]
+    pub notify_header: Vec<String>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_NOTIFY_HEADER_FILE",
+        requires = "notify_target",
+        help = "Send the headers in this file to --notify-url as well, one NAME: VALUE per line"
+    )]
+    pub notify_header_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_NOTIFY_RETRIES",
+        default_value_t = 2,
+        requires = "notify_target",
+        help = "Times to try --notify-url again after a network error, 5xx or 429"
+    )]
+    pub notify_retries: u32,
+
+    #[arg(
+        long,
+        env = "SPY_PET_DESKTOP_NOTIFY",
+        help = "Pop up a desktop notification when the webhooks would be sent to",
+        long_help = "Pop up a desktop notification, with the run's summary and the servers found, when the webhooks would be sent to. Uses notify-send on Linux and the BSDs, osascript on macOS and PowerShell on Windows"
+    )]
+    pub desktop_notify: bool,
+
+    #[arg(
+        long,
+        env = "SPY_PET_EMAIL_TO",
+        value_name = "ADDRESS",
+        value_delimiter = ',',
+        help = "Email the report to this address after the run (repeatable)",
+        long_help = "Email the report to this address after the run (repeatable). Needs --smtp-server and --email-from, which are best kept in the config file. Without --notify-on it's sent after every run; with it, when the condition is met"
+    )]
+    pub email_to: Vec<String>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_EMAIL_FROM",
+        value_name = "ADDRESS",
+        help = "Send --email-to's reports from this address"
+    )]
+    pub email_from: Option<String>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_EMAIL_FORMAT",
+        default_value = "plain",
+        help = "How to render the emailed report"
+    )]
+    pub email_format: EmailFormat,
+
+    #[arg(
+        long,
+        env = "SPY_PET_SMTP_SERVER",
+        value_name = "HOST[:PORT]",
+        help = "The SMTP server to send --email-to through"
+    )]
+    pub smtp_server: Option<String>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_SMTP_SECURITY",
+        default_value = "starttls",
+        help = "How to secure the connection to --smtp-server"
+    )]
+    pub smtp_security: SmtpSecurity,
+
+    #[arg(
+        long,
+        env = "SPY_PET_SMTP_USERNAME",
+        help = "Log in to --smtp-server as this user",
+        long_help = "Log in to --smtp-server as this user, with the password from --smtp-password-file or SPY_PET_SMTP_PASSWORD. With --smtp-security none, only to a server on this machine"
+    )]
+    pub smtp_username: Option<String>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_SMTP_PASSWORD_FILE",
+        help = "Read the SMTP password from this file, or prompt for it with -"
+    )]
+    pub smtp_password_file: Option<PathBuf>,
+
+    #[arg(long, help = "Print the effective configuration and exit")]
+    pub print_config: bool,
+
+    #[arg(
+        long,
+        help = "Load the index and show what a run would request, without sending anything"
+    )]
+    pub dry_run: bool,
+
+    #[arg(
+        long,
+        requires = "dry_run",
+        help = "With --dry-run, list every URL that would be requested instead of the first few"
+    )]
+    pub all_urls: bool,
+
+    #[arg(
+        long,
+        env = "SPY_PET_SIMULATE",
+        value_name = "N",
+        conflicts_with_all = ["from_dce", "from_data_package", "url_template", "backend", "prefilter", "scan_members", "deep_scan", "watch"],
+        help = "Check N made-up servers against a built-in stub instead of the API",
+        long_help = "Check N made-up servers against a built-in stub instead of the API, to try out --concurrency, --batch-size and the like. Nothing is sent anywhere, results are marked as simulated and no state is kept"
+    )]
+    pub simulate: Option<usize>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_SIMULATE_PROFILE",
+        default_value = "friendly",
+        requires = "simulate",
+        help = "How the --simulate stub answers"
+    )]
+    pub simulate_profile: SimulateProfile,
+
+    #[arg(
+        long,
+        env = "SPY_PET_SIMULATE_LATENCY",
+        value_parser = humantime::parse_duration,
+        requires = "simulate",
+        help = "Typical latency of the --simulate stub, instead of the profile's; answers take half to 1.5 times this"
+    )]
+    pub simulate_latency: Option<Duration>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_SIMULATE_COMPROMISED",
+        value_parser = parse_percentage,
+        requires = "simulate",
+        help = "Percentage of made-up servers that are compromised, instead of the profile's"
+    )]
+    pub simulate_compromised: Option<f64>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_OFFLINE",
+        conflicts_with_all = ["from_discord", "invites", "scan_members", "deep_scan", "fallback_web", "discord_webhook", "discord_webhook_file", "notify_url", "notify_url_file", "email_to", "url_template", "backend"],
+        help = "Don't send anything over the network: answer from --fixtures, --local-dataset or --simulate",
+        long_help = "Don't send anything over the network: answer from --fixtures, --local-dataset or --simulate, skip the update check and use a downloaded --prefilter listing only if it's cached"
+    )]
+    pub offline: bool,
+
+    #[arg(
+        long,
+        env = "SPY_PET_FIXTURES",
+        value_name = "DIR",
+        requires = "offline",
+        conflicts_with = "simulate",
+        help = "Answer each server from DIR/<id>.json instead of the API",
+        long_help = "Answer each server from DIR/<id>.json instead of the API, a file holding the body the API would send. Servers without a file aren't in the dataset. Results have \"fixtures\" as their source"
+    )]
+    pub fixtures: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_LOCAL_DATASET",
+        value_name = "FILE",
+        conflicts_with_all = ["simulate", "fixtures", "url_template", "backend"],
+        help = "Check against a server list saved by dump instead of asking the API",
+        long_help = "Check against a server list saved by dump instead of asking the API about each server. Results have \"local\" as their source and are as recent as the dump"
+    )]
+    pub local_dataset: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "NAME",
+        conflicts_with = "dry_run",
+        help = "Look for servers named like this in the dataset instead of checking",
+        long_help = "Look for servers named like this in the dataset instead of checking, for servers whose ID you don't know: lists the closest names with their IDs and how alike they are. Can be given more than once"
+    )]
+    pub search: Vec<String>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_SEARCH_URL",
+        value_name = "URL",
+        default_value = DEFAULT_SEARCH_URL,
+        help = "The search for --search and name-only index entries, with {query} for the name"
+    )]
+    pub search_url: String,
+
+    #[arg(
+        short,
+        long,
+        env = "SPY_PET_YES",
+        help = "Don't ask before starting long or aggressive runs",
+        long_help = "Don't ask before starting long or aggressive runs. Runs without a terminal on stdin never ask"
+    )]
+    pub yes: bool,
+}
+
+impl CheckArgs {
+    /// `check`'s arguments as clap fills them in with none given, for the
+    /// commands that run checks without taking all of `check`'s flags
+    #[cfg(any(feature = "serve", feature = "self-update"))]
+    pub fn defaults() -> clap::error::Result<Self> {
+        let command = Self::augment_args(clap::Command::new("check").no_binary_name(true));
+        let matches = command.try_get_matches_from(std::iter::empty::<String>())?;
+        <Self as clap::FromArgMatches>::from_arg_matches(&matches)
+    }
+
+    /// `--progress-format`, or what it defaults to on this terminal. Events
+    /// on stderr take it over like `json` does.
+    pub fn progress(&self, global: &GlobalArgs) -> ProgressFormat {
+        #[cfg(feature = "tui")]
+        if self.tui {
+            return ProgressFormat::Logs;
+        }
+        if self.progress_events == Some(None) {
+            return ProgressFormat::Json;
+        }
+        match self.progress_format {
+            Some(format) => format,
+            None if matches!(global.log_format, LogFormat::Text)
+                && std::io::stderr().is_terminal() =>
+            {
+                ProgressFormat::Bar
+            }
+            None => ProgressFormat::Logs,
+        }
+    }
+}
+
+/// How to query the backends, shared by every command that makes requests
+#[derive(Args)]
+pub struct RequestArgs {
+    #[arg(
+        short,
+        long,
+        env = "SPY_PET_CONCURRENCY",
+        default_value_t = 1,
+        help = "Max number of concurrent requests",
+        long_help = "Maximum number of concurrent requests. settings this higher than 1 may get you ratelimited"
+    )]
+    pub concurrency: usize,
+
+    #[arg(
+        long,
+        env = "SPY_PET_ADAPTIVE",
+        help = "Lower the concurrency while the API answers 429 or 5xx",
+        long_help = "Lower the concurrency while the API answers 429 or 5xx, halving it each time, and raise it back up to --concurrency one step at a time while the answers are healthy"
+    )]
+    pub adaptive: bool,
+
+    #[arg(
+        long,
+        env = "SPY_PET_RETRIES",
+        default_value_t = 3,
+        help = "Times to try again after a network error, timeout, 429 or 5xx",
+        long_help = "Times to try a request again after a network error, timeout, 429 or 5xx, before counting it as failed. 0 tries once"
+    )]
+    pub retries: u32,
+
+    #[arg(
+        long,
+        env = "SPY_PET_BACKOFF",
+        value_parser = humantime::parse_duration,
+        default_value = "1s",
+        help = "Wait before the first retry, doubled for each one after it",
+        long_help = "Wait before the first retry, doubled for each one after it, up to a minute. A 429 is retried after its Retry-After instead, or not at all if that's over a minute"
+    )]
+    pub backoff: Duration,
+
+    #[arg(
+        long,
+        env = "SPY_PET_RETRY_PASSES",
+        default_value_t = 1,
+        help = "Times to go over the servers that failed again once the rest are done",
+        long_help = "Times to go over the servers that failed with a network error, timeout, 429 or 5xx again once every other one has been checked, each pass at half the concurrency of the one before. Only the ones still failing after the last pass count as failed. 0 leaves them failed"
+    )]
+    pub retry_passes: u32,
+
+    #[arg(
+        long,
+        env = "SPY_PET_POOL_SIZE",
+        help = "Idle connections kept open to each host between requests",
+        long_help = "Idle connections kept open to each host between requests, for the next ones to reuse. Unlimited by default; 0 opens a new connection for every request"
+    )]
+    pub pool_size: Option<usize>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_TIMEOUT",
+        value_parser = humantime::parse_duration,
+        default_value = "30s",
+        help = "Give up on a request that hasn't finished after this long",
+        long_help = "Give up on a request that hasn't finished after this long, from connecting to reading the whole body. A request that timed out is retried like a network error. 0 waits forever"
+    )]
+    pub timeout: Duration,
+
+    #[arg(
+        long,
+        env = "SPY_PET_DELAY",
+        value_parser = humantime::parse_duration,
+        help = "Wait at least this long between starting two requests",
+        long_help = "Wait at least this long between starting two requests, retries included, whatever the concurrency"
+    )]
+    pub delay: Option<Duration>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_JITTER",
+        value_parser = humantime::parse_duration,
+        help = "Add a random wait of up to this long to each --delay"
+    )]
+    pub jitter: Option<Duration>,
+
+    #[arg(
+        short,
+        long,
+        env = "SPY_PET_BACKEND",
+        default_value = "spypet",
+        value_delimiter = ',',
+        help = "Service to check the servers against (repeatable)",
+        long_help = "Service to check the servers against (repeatable). With several, every server is checked against each of them, and each result says which one it came from"
+    )]
+    pub backend: Vec<BackendChoice>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_URL_TEMPLATE",
+        value_parser = parse_url_template,
+        help = "Check against a custom API instead of --backend",
+        long_help = "Check against a custom API instead of --backend. {id} is replaced with the server ID, e.g. \"https://example.com/api/guild/{id}\""
+    )]
+    pub url_template: Option<String>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_MATCH_COMPROMISED",
+        requires = "url_template",
+        value_parser = parse_match_expression,
+        help = "Path into the --url-template response that is truthy for compromised servers",
+        long_help = "Path into the --url-template response that is truthy (not null or false) for compromised servers, jq-style (.guild.tracked) or as a JSON pointer (/guild/tracked). Defaults to the whole response"
+    )]
+    pub match_compromised: Option<String>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_CA_CERT",
+        value_delimiter = ',',
+        help = "Additional PEM root certificate to trust (repeatable)"
+    )]
+    pub ca_cert: Vec<PathBuf>,
+
+    #[arg(long, env = "SPY_PET_INSECURE", help = "Don't verify TLS certificates")]
+    pub insecure: bool,
+
+    #[arg(
+        long,
+        env = "SPY_PET_DOH",
+        value_name = "URL",
+        help = "Look up host names with this DNS-over-HTTPS server",
+        long_help = "Look up host names with this DNS-over-HTTPS server (RFC 8484), e.g. https://1.1.1.1/dns-query, instead of the system resolver. The server's own name is looked up the usual way, so give it as an IP address or with --resolve to avoid any plaintext query"
+    )]
+    pub doh: Option<String>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_PROXY",
+        value_name = "URL",
+        help = "Send every request through this proxy: http://, https://, socks5:// or socks5h://",
+        long_help = "Send every request through this proxy: http://, https://, socks5:// or socks5h:// (the proxy looks up host names). A user name can be in the URL, the password comes from --proxy-password-file or SPY_PET_PROXY_PASSWORD. Without it, HTTP_PROXY, HTTPS_PROXY and ALL_PROXY are used, and NO_PROXY is honoured either way"
+    )]
+    pub proxy: Option<String>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_PROXY_PASSWORD_FILE",
+        requires = "proxy",
+        help = "Read the --proxy password from this file, or prompt for it with -"
+    )]
+    pub proxy_password_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_TOR",
+        conflicts_with = "proxy",
+        help = "Send every request through Tor's SOCKS port",
+        long_help = "Send every request through Tor's SOCKS port (--tor-socks), with host names looked up by Tor, so the API never sees your IP address"
+    )]
+    pub tor: bool,
+
+    #[arg(
+        long,
+        env = "SPY_PET_TOR_SOCKS",
+        value_name = "HOST:PORT",
+        default_value = spy_pet_checker::tor::TOR_SOCKS,
+        help = "Where Tor's SOCKS port is"
+    )]
+    pub tor_socks: String,
+
+    #[arg(
+        long,
+        env = "SPY_PET_TOR_CONTROL",
+        value_name = "HOST:PORT",
+        default_value = spy_pet_checker::tor::TOR_CONTROL,
+        help = "Where Tor's control port is, for --new-circuit-every and --new-circuit-on-429"
+    )]
+    pub tor_control: String,
+
+    #[arg(
+        long,
+        env = "SPY_PET_TOR_PASSWORD_FILE",
+        help = "Read the control port password from this file, or prompt for it with -",
+        long_help = "Read the control port password from this file, or prompt for it with -, for a Tor with HashedControlPassword. Otherwise it comes from SPY_PET_TOR_PASSWORD; without one, Tor's cookie is used"
+    )]
+    pub tor_password_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_NEW_CIRCUIT_EVERY",
+        value_name = "N",
+        requires = "tor",
+        help = "Ask Tor for a new circuit every N requests"
+    )]
+    pub new_circuit_every: Option<u32>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_NEW_CIRCUIT_ON_429",
+        requires = "tor",
+        help = "Ask Tor for a new circuit whenever the API answers 429"
+    )]
+    pub new_circuit_on_429: bool,
+
+    #[arg(
+        long,
+        env = "SPY_PET_RESOLVE",
+        value_name = "HOST:IP",
+        value_delimiter = ',',
+        help = "Connect to HOST at IP without looking it up (repeatable)"
+    )]
+    pub resolve: Vec<String>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_API_KEY_FILE",
+        help = "Read the API key from this file, or prompt for it with -",
+        long_help = "Read the API key from this file, or prompt for it with -. Otherwise it comes from SPY_PET_API_KEY or the system keyring (`spy-pet-checker token --api-key set`); it's never taken on the command line"
+    )]
+    pub api_key_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_API_KEY_HEADER",
+        default_value = "Authorization",
+        help = "Header to send the API key in; Authorization gets it as a bearer token"
+    )]
+    pub api_key_header: String,
+
+    #[arg(
+        long,
+        env = "SPY_PET_USER_AGENT",
+        help = "User-Agent to send, instead of spy-pet-checker/<version>"
+    )]
+    pub user_agent: Option<String>,
+
+    #[arg(
+        long = "header",
+        env = "SPY_PET_HEADER",
+        value_name = "NAME: VALUE",
+        help = "Send this header with every request to the API (repeatable)",
+        long_help = "Send this header with every request to the API (repeatable), e.g. an identification header or a cookie a mirror wants. Like the API key, it isn't sent anywhere else, such as Discord or the DoH server"
+    )]
+    pub header: Vec<String>,
+
+    #[arg(
+        long,
+        env = "SPY_PET_RUNTIME",
+        default_value = "auto",
+        help = "Async runtime flavor"
+    )]
+    pub runtime: RuntimeChoice,
+}
+
+/// An `--index-path`, optionally labelled: `alice=alice.json`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct IndexPath {
+    pub label: Option<String>,
+    pub path: PathBuf,
+}
+
+impl FromStr for IndexPath {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // a label can't hold a path separator, so `./a=b.json` is a path
+        let (label, path) = match s.split_once('=') {
+            Some((label, path)) if !label.contains(['/', '\\']) => (Some(label), path),
+            _ => (None, s),
+        };
+        if label.is_some_and(str::is_empty) {
+            return Err(format!(
+                "{s:?} has an empty label, leave out the = for none"
+            ));
+        }
+        if path.is_empty() {
+            return Err(format!("{s:?} has no path"));
+        }
+        Ok(Self {
+            label: label.map(str::to_owned),
+            path: PathBuf::from(path),
+        })
+    }
+}
+
+impl TryFrom<String> for IndexPath {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// A `--shard`: the `number`th of `count`, from 1
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Shard {
+    pub number: u32,
+    pub count: u32,
+}
+
+impl Shard {
+    /// Whether the guild `id` is checked by this shard
+    pub fn contains(self, id: &str) -> bool {
+        index::shard_of(id, self.count) + 1 == self.number
+    }
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (number, count) = s
+            .split_once('/')
+            .ok_or_else(|| format!("{s:?} isn't I/N, like 1/4"))?;
+        let parse = |n: &str| {
+            n.trim()
+                .parse::<u32>()
+                .map_err(|err| format!("{s:?}: {err}"))
+        };
+        let (number, count) = (parse(number)?, parse(count)?);
+        if number == 0 || number > count {
+            return Err(format!("{s:?}: shards go from 1 to {count}"));
+        }
+        Ok(Self { number, count })
+    }
+}
+
+impl TryFrom<String> for Shard {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Shard> for String {
+    fn from(shard: Shard) -> Self {
+        shard.to_string()
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.number, self.count)
+    }
+}
+
+impl From<IndexPath> for String {
+    fn from(index: IndexPath) -> Self {
+        index.to_string()
+    }
+}
+
+impl fmt::Display for IndexPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(label) = &self.label {
+            write!(f, "{label}=")?;
+        }
+        write!(f, "{}", self.path.display())
+    }
+}
+
+/// A `--channel`: two snowflakes, `GUILD=CHANNEL`
+fn parse_channel(s: &str) -> Result<(String, String), String> {
+    let snowflake = |id: &str| !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit());
+    match s.split_once('=') {
+        Some((guild, channel)) if snowflake(guild) && snowflake(channel) => {
+            Ok((guild.to_owned(), channel.to_owned()))
+        }
+        _ => Err("write it as GUILD=CHANNEL, with two IDs".to_owned()),
+    }
+}
+
+fn parse_invite(s: &str) -> Result<String, String> {
+    match spy_pet_checker::discord::invite_code(s) {
+        Some(_) => Ok(s.to_owned()),
+        None => Err("expected a discord.gg/<code> or discord.com/invite/<code> link".to_owned()),
+    }
+}
+
+#[cfg(feature = "serve")]
+fn parse_public_key(s: &str) -> Result<[u8; 32], String> {
+    crate::commands::interactions::decode_hex(s)
+        .and_then(|key| key.try_into().ok())
+        .ok_or_else(|| "expected the 64 hex digits of an Ed25519 public key".to_owned())
+}
+
+fn parse_url_template(s: &str) -> Result<String, TemplateError> {
+    UrlTemplate::validate(s)?;
+    Ok(s.to_owned())
+}
+
+fn parse_regex(s: &str) -> Result<String, regex::Error> {
+    Regex::new(s)?;
+    Ok(s.to_owned())
+}
+
+fn parse_percentage(s: &str) -> Result<f64, String> {
+    let value: f64 = s
+        .trim_end_matches('%')
+        .parse()
+        .map_err(|err| format!("{err}"))?;
+    if (0.0..=100.0).contains(&value) {
+        Ok(value)
+    } else {
+        Err("must be between 0 and 100".to_owned())
+    }
+}
+
+/// Bytes, or kibibytes, mebibytes or gibibytes with a K, M or G after them
+fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (digits, shift) = match s.char_indices().last() {
+        Some((at, 'k' | 'K')) => (&s[..at], 10),
+        Some((at, 'm' | 'M')) => (&s[..at], 20),
+        Some((at, 'g' | 'G')) => (&s[..at], 30),
+        _ => (s, 0),
+    };
+    let value: u64 = digits.trim().parse().map_err(|err| format!("{err}"))?;
+    value
+        .checked_mul(1 << shift)
+        .ok_or_else(|| "too big".to_owned())
+}
+
+fn parse_match_expression(s: &str) -> Result<String, TemplateError> {
+    JsonPath::parse(s)?;
+    Ok(s.to_owned())
+}
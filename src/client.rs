@@ -0,0 +1,154 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::header::{HeaderName, HeaderValue, AUTHORIZATION};
+use reqwest::{Client, ClientBuilder};
+use thiserror::Error;
+
+use crate::secret::Secret;
+use crate::CheckOptions;
+
+#[cfg(all(feature = "rustls", feature = "native-tls"))]
+compile_error!("features `rustls` and `native-tls` are mutually exclusive");
+
+#[cfg(not(any(feature = "rustls", feature = "native-tls")))]
+compile_error!("one of the features `rustls` or `native-tls` must be enabled");
+
+/// Name of the TLS stack this build was compiled with
+pub const TLS_BACKEND: &str = if cfg!(feature = "rustls") {
+    "rustls"
+} else {
+    "native-tls"
+};
+
+/// Sent as the User-Agent unless another one is set
+pub const USER_AGENT: &str = concat!("spy-pet-checker/", env!("CARGO_PKG_VERSION"));
+
+/// Interval of TCP keepalive probes on idle connections
+const TCP_KEEPALIVE: Duration = Duration::from_secs(60);
+
+#[cfg(feature = "rustls")]
+fn tls_builder() -> ClientBuilder {
+    Client::builder().use_rustls_tls()
+}
+
+#[cfg(all(feature = "native-tls", not(feature = "rustls")))]
+fn tls_builder() -> ClientBuilder {
+    Client::builder().use_native_tls()
+}
+
+#[derive(Error, Debug)]
+pub enum ApiKeyError {
+    #[error("{0:?} isn't a valid header name")]
+    BadHeader(String),
+
+    #[error("the API key can't be sent in a header, it has characters headers can't hold")]
+    BadKey,
+}
+
+#[derive(Error, Debug)]
+pub enum HeaderError {
+    #[error("{0:?} isn't a header, write it as \"Name: value\"")]
+    Format(String),
+
+    #[error("{0:?} isn't a valid header name")]
+    Name(String),
+
+    #[error("the value of {0} has characters headers can't hold")]
+    Value(String),
+}
+
+/// Parses a `Name: value` header. Its value is marked sensitive, since it
+/// may well be a cookie.
+pub fn parse_header(header: &str) -> Result<(HeaderName, HeaderValue), HeaderError> {
+    let (name, value) = header
+        .split_once(':')
+        .ok_or_else(|| HeaderError::Format(header.to_owned()))?;
+    let name = name.trim();
+    let name = HeaderName::try_from(name).map_err(|_| HeaderError::Name(name.to_owned()))?;
+    let mut value =
+        HeaderValue::try_from(value.trim()).map_err(|_| HeaderError::Value(name.to_string()))?;
+    value.set_sensitive(true);
+    Ok((name, value))
+}
+
+/// A key for the API, sent with every request to it. Sent in `Authorization`
+/// it's a bearer token, in any other header as is.
+#[derive(Clone, Debug)]
+pub struct ApiKey {
+    header: HeaderName,
+    /// Marked sensitive, so it's left out of reqwest's debug output
+    value: HeaderValue,
+}
+
+impl ApiKey {
+    pub fn new(header: &str, key: &Secret<String>) -> Result<Self, ApiKeyError> {
+        let header =
+            HeaderName::try_from(header).map_err(|_| ApiKeyError::BadHeader(header.to_owned()))?;
+        let value = match header == AUTHORIZATION {
+            true => format!("Bearer {}", key.expose()),
+            false => key.expose().clone(),
+        };
+        let mut value = HeaderValue::try_from(value).map_err(|_| ApiKeyError::BadKey)?;
+        value.set_sensitive(true);
+        Ok(Self { header, value })
+    }
+
+    pub fn header(&self) -> &HeaderName {
+        &self.header
+    }
+}
+
+/// Builds the HTTP client used for checks, according to `options`. It sends
+/// the extra headers and the API key, if there is one. A client given in
+/// `options` is used instead.
+pub fn build_client(options: &CheckOptions) -> reqwest::Result<Client> {
+    if let Some(client) = &options.client {
+        return Ok(client.clone());
+    }
+    let mut headers = options.headers.clone();
+    if let Some(key) = &options.api_key {
+        headers.insert(key.header.clone(), key.value.clone());
+    }
+    builder(options).default_headers(headers).build()
+}
+
+/// Like [`build_client`], but without the API key or the extra headers, for
+/// requests to anything but the API: other services mustn't see them. A
+/// keyless client given in `options` is used instead.
+pub fn build_keyless_client(options: &CheckOptions) -> reqwest::Result<Client> {
+    if let Some(client) = &options.keyless_client {
+        return Ok(client.clone());
+    }
+    builder(options).build()
+}
+
+fn builder(options: &CheckOptions) -> ClientBuilder {
+    let user_agent = options.user_agent.as_deref().unwrap_or(USER_AGENT);
+    let mut builder = tls_builder().user_agent(user_agent);
+    for cert in &options.ca_certs {
+        builder = builder.add_root_certificate(cert.clone());
+    }
+    if options.insecure {
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    for (host, ip) in &options.resolve {
+        // the port of the URL is used either way
+        builder = builder.resolve(host, SocketAddr::new(*ip, 0));
+    }
+    if let Some(proxy) = &options.proxy {
+        builder = builder.proxy(proxy.reqwest());
+    }
+    if let Some(doh) = &options.doh {
+        builder = builder.dns_resolver(Arc::new(doh.clone()));
+    }
+    if let Some(timeout) = options.timeout {
+        builder = builder.timeout(timeout);
+    }
+    if let Some(size) = options.pool_size {
+        builder = builder.pool_max_idle_per_host(size);
+    }
+    // keeps idle pooled connections from being dropped by NATs and proxies
+    builder.tcp_keepalive(TCP_KEEPALIVE)
+}
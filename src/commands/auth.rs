@@ -0,0 +1,124 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use color_eyre::eyre::{self, bail, Context};
+use reqwest::Client;
+use spy_pet_checker::oauth::{self, OAuthApp, OAuthToken, RedirectListener};
+use spy_pet_checker::state::StateDir;
+use spy_pet_checker::warnings::Warnings;
+use spy_pet_checker::{build_keyless_client, CheckOptions};
+use tracing::{debug, warn};
+
+use crate::cli::{AuthArgs, GlobalArgs};
+use crate::config::{resolve_state_dir, FileConfig};
+use crate::credentials;
+
+pub fn run(global: GlobalArgs, args: AuthArgs) -> eyre::Result<()> {
+    let file = FileConfig::load(global.config.as_deref())?;
+    file.warn_unknown(&Warnings::default());
+    let Some(state_dir) = resolve_state_dir(&global, &file) else {
+        bail!("the token can't be kept with --no-state");
+    };
+    let path = StateDir::new(state_dir)
+        .oauth_token_path()
+        .context("couldn't open the state directory")?;
+    let client = build_keyless_client(&CheckOptions::default())?;
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+
+    if args.forget {
+        return runtime.block_on(forget(&client, &path));
+    }
+    let client_id = args.client_id.clone().expect("required without --forget");
+    let client_secret = credentials::read_secret(
+        "client secret",
+        args.client_secret_file.as_deref(),
+        credentials::OAUTH_CLIENT_SECRET_ENV,
+    )?
+    .map(|(secret, _)| secret);
+    let app = OAuthApp::new(client_id, client_secret);
+    let token = runtime.block_on(authorize(&client, &app, &args))?;
+    token
+        .save(&path)
+        .with_context(|| format!("couldn't save the token to {}", path.display()))?;
+    println!(
+        "Authorized; --from-discord lists your servers with it from now on, until `spy-pet-checker auth --forget`"
+    );
+    Ok(())
+}
+
+/// Sends the browser to Discord and waits for it to come back with a code,
+/// which is traded for a token
+async fn authorize(client: &Client, app: &OAuthApp, args: &AuthArgs) -> eyre::Result<OAuthToken> {
+    let listener = RedirectListener::bind(args.port).await?;
+    let redirect_uri = listener.redirect_uri();
+    let state = oauth::random_state();
+    let url = app.authorize_url(&redirect_uri, &state);
+    let opened = !args.no_browser
+        && open_browser(&url)
+            .inspect_err(|err| debug!(%err, "couldn't open the browser"))
+            .is_ok();
+    match opened {
+        true => {
+            println!("Opened the browser to authorize listing your servers. If it didn't, open:")
+        }
+        false => println!("Open this link to authorize listing your servers:"),
+    }
+    println!("{url}");
+
+    let code = tokio::time::timeout(args.timeout, listener.code(&state))
+        .await
+        .map_err(|_| {
+            eyre::eyre!(
+                "not authorized within {}",
+                humantime::format_duration(args.timeout)
+            )
+        })??;
+    let token = app
+        .exchange(client, &code, &redirect_uri)
+        .await
+        .context("couldn't get a token from Discord")?;
+    Ok(token)
+}
+
+/// `auth --forget`: revokes the stored token and removes it. The file goes
+/// even if Discord can't be told.
+async fn forget(client: &Client, path: &Path) -> eyre::Result<()> {
+    let Some(token) = OAuthToken::load(path)? else {
+        println!("No token was stored");
+        return Ok(());
+    };
+    if let Err(err) = OAuthApp::of(&token).revoke(client, &token).await {
+        warn!(%err, "couldn't revoke the token, it stays valid until it expires");
+    }
+    std::fs::remove_file(path).with_context(|| format!("couldn't remove {}", path.display()))?;
+    println!("Token removed");
+    Ok(())
+}
+
+/// Opens `url` with the platform's handler for links
+fn open_browser(url: &str) -> std::io::Result<()> {
+    #[cfg(target_os = "macos")]
+    let mut command = Command::new("open");
+    // not through cmd's start, which would take the &s for its own
+    #[cfg(windows)]
+    let mut command = {
+        let mut command = Command::new("rundll32");
+        command.arg("url.dll,FileProtocolHandler");
+        command
+    };
+    #[cfg(not(any(target_os = "macos", windows)))]
+    let mut command = Command::new("xdg-open");
+    let status = command
+        .arg(url)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(std::io::Error::other(format!(
+            "the opener failed ({status})"
+        ))),
+    }
+}
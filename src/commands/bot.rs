@@ -0,0 +1,165 @@
+//! `bot`: checks the servers a Discord bot is in, and posts each one its
+//! result in a channel of its own or its owner's DMs.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use chrono::Utc;
+use clap::ArgMatches;
+use color_eyre::eyre::{self, Context};
+use spy_pet_checker::discord::Bot;
+use spy_pet_checker::output::Formatter;
+use spy_pet_checker::secret::Secret;
+use spy_pet_checker::warnings::Condition;
+use spy_pet_checker::{build_keyless_client, check_guilds, ErrorKind, Response};
+use tracing::{debug, info, warn};
+
+use crate::cli::{BotArgs, Format, GlobalArgs, TokenType};
+use crate::commands::check::{load_guilds, record_run, unauthorized, write_outputs};
+use crate::commands::{formatter, open_report};
+use crate::config::{Config, FileConfig};
+
+pub fn run(global: GlobalArgs, args: BotArgs, matches: &ArgMatches) -> eyre::Result<()> {
+    let BotArgs {
+        channels,
+        dm_owner,
+        post_clean,
+        #[cfg(feature = "serve")]
+        interactions_listen,
+        #[cfg(feature = "serve")]
+        public_key,
+        check,
+    } = args;
+    let posting = !channels.is_empty() || dm_owner;
+    #[cfg(feature = "serve")]
+    let answering = interactions_listen.is_some();
+    #[cfg(not(feature = "serve"))]
+    let answering = false;
+    if !posting && !answering {
+        eyre::bail!("bot needs a --channel or --dm-owner to post anywhere");
+    }
+    let file = FileConfig::load(global.config.as_deref())?;
+    let mut config = Config::resolve(check, &global, matches, file);
+    // the bot's own servers, whatever the config file says
+    config.from_discord = true;
+    config.token_type = TokenType::Bot;
+    if config.watch.is_some() {
+        config.warnings.warn(
+            Condition::IgnoredOption,
+            "--watch has no effect with bot, run it on a schedule instead",
+        );
+    }
+    let config = Arc::new(config);
+    let channels: BTreeMap<String, String> = channels.into_iter().collect();
+    let formatter = formatter(
+        &config.format,
+        config.format_options(&global),
+        config.template.as_deref(),
+    )?;
+
+    let runtime = config
+        .build_runtime()
+        .context("couldn't start async runtime")?;
+    runtime.block_on(async {
+        if posting {
+            post_results(&config, &global, formatter, &channels, dm_owner, post_clean).await?;
+        }
+        #[cfg(feature = "serve")]
+        if let (Some(addr), Some(public_key)) = (interactions_listen, public_key) {
+            let options = config.check_options()?;
+            let client = build_keyless_client(&options)?;
+            let bot = Bot::new(token(&config)?);
+            super::interactions::serve(bot, client, addr, public_key, options).await?;
+        }
+        Ok(())
+    })
+}
+
+/// The token the servers were listed with
+fn token(config: &Config) -> eyre::Result<Secret<String>> {
+    let (token, _) = config
+        .discord_token()?
+        .ok_or_else(|| eyre::eyre!("bot needs the bot's token"))?;
+    Ok(token)
+}
+
+/// Checks every server the bot is in and posts each one its result
+async fn post_results(
+    config: &Config,
+    global: &GlobalArgs,
+    mut formatter: Box<dyn Formatter>,
+    channels: &BTreeMap<String, String>,
+    dm_owner: bool,
+    post_clean: bool,
+) -> eyre::Result<()> {
+    let started_at = Utc::now();
+    let (guilds, _) = load_guilds(config, false).await?;
+    config.warnings.check()?;
+    let index_size = guilds.len();
+    let options = config.check_options()?;
+    let report = check_guilds(guilds, &options).await;
+    if let Some(refused) = report
+        .failed
+        .iter()
+        .find(|failed| failed.kind == ErrorKind::Unauthorized)
+    {
+        eyre::bail!(unauthorized(&refused.message, options.api_key.is_some()));
+    }
+
+    let bot = Bot::new(token(config)?);
+    let client = build_keyless_client(&options)?;
+    let mut posted = 0;
+    for result in one_per_guild(&report.results) {
+        if !result.is_compromised() && !post_clean {
+            continue;
+        }
+        let guild = &result.guild_id;
+        let channel = match channels.get(guild) {
+            Some(channel) => channel.clone(),
+            None if dm_owner => match bot.owner_dm(&client, guild).await {
+                Ok(channel) => channel,
+                Err(err) => {
+                    warn!(guild, %err, "couldn't open a DM with the server's owner");
+                    continue;
+                }
+            },
+            None => {
+                debug!(guild, "no --channel for the server");
+                continue;
+            }
+        };
+        match bot.post(&client, &channel, result).await {
+            Ok(()) => posted += 1,
+            Err(err) => warn!(guild, channel, %err, "couldn't post the result"),
+        }
+    }
+    info!(posted, "posted the results");
+
+    let json = matches!(config.format, Format::Json) && config.template.is_none();
+    let mut writer = open_report(config, config.output.as_deref(), json)?;
+    formatter
+        .write_results(&mut writer, &report)
+        .context("couldn't write to output")?;
+    writer.commit()?;
+    write_outputs(config, global, &report)?;
+    record_run(config, started_at, index_size, report);
+    config.warnings.check()?;
+    Ok(())
+}
+
+/// With several backends, the compromised result of each guild if there is
+/// one, so a server isn't posted to twice
+fn one_per_guild(results: &[Response]) -> impl Iterator<Item = &Response> {
+    let mut guilds: BTreeMap<&str, &Response> = BTreeMap::new();
+    for result in results {
+        guilds
+            .entry(&result.guild_id)
+            .and_modify(|kept| {
+                if !kept.is_compromised() && result.is_compromised() {
+                    *kept = result;
+                }
+            })
+            .or_insert(result);
+    }
+    guilds.into_values()
+}
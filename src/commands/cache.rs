@@ -0,0 +1,157 @@
+use std::io::{IsTerminal, Write};
+use std::time::Duration;
+
+use color_eyre::eyre::{self, bail, Context};
+use spy_pet_checker::cache::{CachedAnswer, ResultCache};
+use spy_pet_checker::state::StateDir;
+use spy_pet_checker::warnings::Warnings;
+
+use crate::cli::{CacheAction, CacheArgs, GlobalArgs};
+use crate::config::{resolve_state_dir, FileConfig};
+
+pub fn run(global: GlobalArgs, args: CacheArgs) -> eyre::Result<()> {
+    let file = FileConfig::load(global.config.as_deref())?;
+    file.warn_unknown(&Warnings::default());
+    let Some(state_dir) = resolve_state_dir(&global, &file) else {
+        bail!("the result cache is unavailable with --no-state");
+    };
+    let state = StateDir::new(state_dir);
+    let path = state
+        .result_cache_path()
+        .context("couldn't open the result cache")?;
+    let entries = || ResultCache::entries(&path).context("couldn't read the result cache");
+
+    match args.action.unwrap_or(CacheAction::Ls { cache_ttl: None }) {
+        CacheAction::Ls { cache_ttl } => {
+            let ttl = cache_ttl.or(file.cache_ttl());
+            let answers = entries()?;
+            if answers.is_empty() {
+                println!("No cached answers");
+            } else {
+                println!(
+                    "{:<12} {:<20} {:>6} {:>10} {:>5}",
+                    "SOURCE", "SERVER", "AGE", "SIZE", "FRESH"
+                );
+                for answer in &answers {
+                    let fresh = match ttl {
+                        Some(ttl) if answer.age() < ttl => "yes",
+                        Some(_) => "no",
+                        None => "-",
+                    };
+                    println!(
+                        "{:<12} {:<20} {:>6} {:>10} {:>5}",
+                        answer.source,
+                        answer.guild_id,
+                        age(answer.age()),
+                        bytes(answer.size),
+                        fresh
+                    );
+                }
+                let size: u64 = answers.iter().map(|a| a.size).sum();
+                println!("{} answer(s), {}", answers.len(), bytes(size));
+                if ttl.is_none() {
+                    println!("No --cache-ttl configured, so none of them are used");
+                }
+            }
+
+            let usage = state
+                .disk_usage()
+                .context("couldn't measure the state directory")?;
+            let total: u64 = usage.values().sum();
+            println!();
+            println!(
+                "State directory {}: {}",
+                state.root().display(),
+                bytes(total)
+            );
+            for (name, size) in usage {
+                println!("  {name:<20} {:>10}", bytes(size));
+            }
+        }
+        CacheAction::Show { guild_id, source } => {
+            let answers: Vec<CachedAnswer> = entries()?
+                .into_iter()
+                .filter(|a| a.guild_id == guild_id)
+                .filter(|a| source.as_ref().is_none_or(|source| a.source == *source))
+                .collect();
+            let answer = match answers.as_slice() {
+                [] => bail!("no cached answer about {guild_id}"),
+                [answer] => answer,
+                answers => {
+                    let sources: Vec<&str> = answers.iter().map(|a| a.source.as_str()).collect();
+                    bail!(
+                        "several backends have an answer about {guild_id}, pick one with --source: {}",
+                        sources.join(", ")
+                    );
+                }
+            };
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&answer.entry.api_response)?
+            );
+        }
+        CacheAction::Rm { guild_id } => {
+            let removed = ResultCache::remove(&path, &guild_id)
+                .context("couldn't update the result cache")?;
+            println!("Removed {removed} answer(s)");
+        }
+        CacheAction::Clear { older_than, yes } => {
+            if !yes {
+                let count = entries()?
+                    .iter()
+                    .filter(|a| older_than.is_none_or(|older_than| a.age() > older_than))
+                    .count();
+                if count == 0 {
+                    println!("Removed 0 answer(s)");
+                    return Ok(());
+                }
+                if !confirm(count, older_than)? {
+                    bail!("cancelled, nothing was removed");
+                }
+            }
+            let removed = ResultCache::clear(&path, older_than)
+                .context("couldn't update the result cache")?;
+            println!("Removed {removed} answer(s)");
+        }
+    }
+
+    Ok(())
+}
+
+/// Asks before clearing `count` answers. Without a terminal to ask on, it
+/// refuses.
+fn confirm(count: usize, older_than: Option<Duration>) -> eyre::Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        bail!("clearing the cache needs --yes when not run from a terminal");
+    }
+    match older_than {
+        Some(older_than) => eprint!(
+            "Remove {count} cached answer(s) older than {}? [y/N] ",
+            humantime::format_duration(older_than)
+        ),
+        None => eprint!("Remove all {count} cached answer(s)? [y/N] "),
+    }
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// `age` in its largest whole unit, e.g. `3h`
+fn age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3600 => format!("{}m", secs / 60),
+        3600..86400 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
+fn bytes(n: u64) -> String {
+    match n {
+        0..1024 => format!("{n} B"),
+        1024..1048576 => format!("{:.1} KiB", n as f64 / 1024.0),
+        _ => format!("{:.1} MiB", n as f64 / 1048576.0),
+    }
+}
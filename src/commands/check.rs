@@ -0,0 +1,2053 @@
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io::{BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use clap::{ArgMatches, ValueEnum};
+use color_eyre::eyre::{self, Context};
+use futures_util::StreamExt;
+use regex::Regex;
+use spy_pet_checker::backend::Simulated;
+use spy_pet_checker::checkpoint::{self, Checkpoint};
+use spy_pet_checker::discord::{invite_code, InviteResolver, Webhook};
+use spy_pet_checker::history::{History, RunRecord};
+use spy_pet_checker::index::{self, IndexFormat, OrientError, Orientation};
+use spy_pet_checker::mail::{Mail, Smtp};
+use spy_pet_checker::merge::parse_results;
+use spy_pet_checker::notify::{Notification, NotifyOn, Payload, RunSummary};
+use spy_pet_checker::output::{sort_run, JsonDocument, JsonLines};
+use spy_pet_checker::prefilter::{Listing, PrefilterReport};
+use spy_pet_checker::search::Candidate;
+use spy_pet_checker::warnings::{Condition, Warnings};
+use spy_pet_checker::window::{InWindow, WindowMode, WindowReport};
+use spy_pet_checker::{
+    build_client, build_keyless_client, check_stream, CancellationToken, CheckError, CheckOptions,
+    ErrorKind, Response, RunReport, Semaphore,
+};
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::{debug, error, info, info_span, warn, Instrument};
+
+use crate::cli::{
+    CheckArgs, CheckKind, EmailFormat, FailFast, FailOn, Format, FormatOptions, GlobalArgs,
+    LogFormat, OutputMode, ProgressFormat, SeverityLevel,
+};
+use crate::commands::{check_report, formatter, open_report, shutdown_signal, watch, Exit, Output};
+use crate::config::{Config, FileConfig};
+use crate::credentials;
+#[cfg(unix)]
+use crate::progress::dump_on_sigusr1;
+use crate::progress::{EventSink, JsonProgress, ProgressBar, RunStatus, Update};
+use crate::{dce, package};
+
+/// Reads the index, and the invite links and names it lists instead of IDs. `-` is
+/// standard input, and without a `format` it's worked out from the path and
+/// the contents. Unless `no_autodetect`, a map written name → id is turned
+/// around.
+pub async fn load_index(
+    path: &Path,
+    format: Option<IndexFormat>,
+    no_autodetect: bool,
+    warnings: &Warnings,
+) -> eyre::Result<(BTreeMap<String, String>, Vec<String>, Vec<String>)> {
+    let stdin = path == Path::new("-");
+    let streamed = !stdin
+        && format
+            .or_else(|| IndexFormat::from_extension(path))
+            .is_some_and(|format| format == IndexFormat::Json);
+    // JSON files are parsed as they're read, so a huge one isn't held twice
+    let (string, format, parsed) = match (stdin, streamed) {
+        (true, _) => {
+            let mut string = String::new();
+            tokio::io::stdin()
+                .read_to_string(&mut string)
+                .await
+                .context("couldn't read the index from standard input")?;
+            let format = format.unwrap_or_else(|| IndexFormat::detect(path, &string));
+            let parsed = index::parse_as(format, &string);
+            (Some(string), format, parsed)
+        }
+        (false, true) => {
+            let file = std::fs::File::open(path)
+                .with_context(|| format!("couldn't read file {}", path.display()))?;
+            let parsed = tokio::task::spawn_blocking(move || {
+                index::parse_from(std::io::BufReader::new(file))
+            })
+            .await?;
+            (None, IndexFormat::Json, parsed.map_err(Into::into))
+        }
+        (false, false) => {
+            let string = tokio::fs::read_to_string(path)
+                .await
+                .with_context(|| format!("couldn't read file {}", path.display()))?;
+            let format = format.unwrap_or_else(|| IndexFormat::detect(path, &string));
+            let parsed = index::parse_as(format, &string);
+            (Some(string), format, parsed)
+        }
+    };
+    debug!(?format, "read index {}", path.display());
+    let (index, duplicates) =
+        parsed.with_context(|| format!("couldn't parse index file {}", path.display()))?;
+    for key in duplicates {
+        warnings.warn(
+            Condition::DuplicateKey,
+            format!(
+                "{} has {key:?} more than once, only the last one counts",
+                path.display()
+            ),
+        );
+    }
+    let mut index = index::normalize(index);
+    let invites = index::take_invites(&mut index)
+        .into_iter()
+        .map(|(link, _)| link)
+        .collect();
+    let names = index::take_names(&mut index);
+    // a list has the IDs first by definition
+    if !no_autodetect && format.is_map() {
+        index = match index::orient(index.clone()) {
+            Ok((index, orientation)) => {
+                if orientation == Orientation::NameToId {
+                    warnings.warn(
+                        Condition::FlippedIndex,
+                        format!(
+                            "{} maps server names to IDs instead of IDs to names, reading it the other way round",
+                            path.display()
+                        ),
+                    );
+                }
+                index
+            }
+            // some entries are broken, which the findings below point out
+            Err(OrientError::NoIds) => index::mostly_ids(index),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("couldn't read index file {}", path.display()))
+            }
+        };
+    }
+    preflight(path, string.as_deref(), &mut index, warnings)?;
+    Ok((index, invites, names))
+}
+
+/// Stops at entries that can't be checked, before anything is sent, and
+/// names the ones without a name by their ID. Without the `text` of the
+/// index, it's read again to say which lines are wrong.
+fn preflight(
+    path: &Path,
+    text: Option<&str>,
+    index: &mut BTreeMap<String, String>,
+    warnings: &Warnings,
+) -> eyre::Result<()> {
+    let mut findings = index::validate(text.unwrap_or_default(), index, &[]);
+    let broken = findings.iter().any(|finding| finding.problem.is_error());
+    if let (None, true) = (text, broken) {
+        let text = std::fs::read_to_string(path).unwrap_or_default();
+        findings = index::validate(&text, index, &[]);
+    }
+    let errors: Vec<String> = findings
+        .iter()
+        .filter(|finding| finding.problem.is_error())
+        .map(|finding| format!("\n  {finding}"))
+        .collect();
+    if !errors.is_empty() {
+        eyre::bail!(
+            "index file {} has entries that can't be checked:{}",
+            path.display(),
+            errors.concat()
+        );
+    }
+    for (id, name) in index.iter_mut().filter(|(_, name)| name.is_empty()) {
+        warnings.warn(
+            Condition::EmptyName,
+            format!("{id} in {} has no name, going by its ID", path.display()),
+        );
+        name.clone_from(id);
+    }
+    Ok(())
+}
+
+/// Writes `report` to the `--format`/`--output` pairs after the first
+pub fn write_outputs(config: &Config, global: &GlobalArgs, report: &RunReport) -> eyre::Result<()> {
+    for extra in &config.outputs {
+        let options = FormatOptions {
+            color: extra.output.is_none() && global.color.enabled(&std::io::stdout()),
+            ..config.format_options(global)
+        };
+        let json = matches!(extra.format, Format::Json);
+        let mut writer = open_report(config, extra.output.as_deref(), json)?;
+        formatter(&extra.format, options, None)?
+            .write_results(&mut writer, report)
+            .context("couldn't write to output")?;
+        writer.commit()?;
+    }
+    Ok(())
+}
+
+/// The labels of labelled `--index-path`s, by the IDs they list
+pub type Labels = BTreeMap<String, Vec<String>>;
+
+/// Marks `response` with the labels of the indexes that listed it
+pub fn add_labels(response: &mut Response, labels: &Labels) {
+    if let Some(labels) = labels.get(&response.guild_id) {
+        response.labels.clone_from(labels);
+    }
+}
+
+/// The guilds to check: from `--from-dce` exports or `--from-data-package`
+/// if given, or the indexes. Narrowed down to `--only`, if given, without
+/// the `--exclude-id`s and to the names `--name-filter` matches, then to the
+/// `--shard`. `offline` skips the invite links rather than look them up.
+pub async fn load_guilds(
+    config: &Config,
+    offline: bool,
+) -> eyre::Result<(BTreeMap<String, String>, Labels)> {
+    let (mut guilds, labels) = load_all_guilds(config, offline).await?;
+    if !config.only.is_empty() {
+        for id in config.only.iter().filter(|id| !guilds.contains_key(*id)) {
+            config.warnings.warn(
+                Condition::MissingOnly,
+                format!("--only {id} isn't in the index, skipping it"),
+            );
+        }
+        guilds.retain(|id, _| config.only.contains(id));
+    }
+    guilds.retain(|id, _| !config.exclude_id.contains(id));
+    if let Some(pattern) = &config.name_filter {
+        let pattern = Regex::new(pattern).context("invalid --name-filter")?;
+        let before = guilds.len();
+        guilds.retain(|_, name| pattern.is_match(name));
+        debug!(
+            matched = guilds.len(),
+            "--name-filter left out {} servers",
+            before - guilds.len()
+        );
+    }
+    if let Some(shard) = config.shard {
+        let before = guilds.len();
+        guilds.retain(|id, _| shard.contains(id));
+        info!(
+            servers = guilds.len(),
+            "shard {shard} of the {before} servers"
+        );
+    }
+    let labels = labels
+        .into_iter()
+        .filter(|(id, _)| guilds.contains_key(id))
+        .collect();
+    Ok((guilds, labels))
+}
+
+async fn load_all_guilds(
+    config: &Config,
+    offline: bool,
+) -> eyre::Result<(BTreeMap<String, String>, Labels)> {
+    let listed =
+        config.from_dce.is_some() || config.from_data_package.is_some() || config.from_discord;
+    match config.kind {
+        CheckKind::Users if listed => eyre::bail!("--from-dce, --from-data-package and --from-discord list servers, give the user IDs in an index or use --include-users"),
+        CheckKind::Channels if listed => eyre::bail!("--from-dce, --from-data-package and --from-discord list servers, give the channel IDs in an index or with --channels"),
+        _ => {}
+    }
+    if !config.ids.is_empty() {
+        if let Some(id) = config.ids.iter().find(|id| !index::is_snowflake(id)) {
+            eyre::bail!("--ids {id} isn't a Discord ID");
+        }
+        // there's no name to go by
+        let guilds = config
+            .ids
+            .iter()
+            .map(|id| (id.clone(), id.clone()))
+            .collect();
+        return Ok((guilds, Labels::new()));
+    }
+    if let Some(pattern) = &config.from_dce {
+        let (pattern, warnings) = (pattern.clone(), Arc::clone(&config.warnings));
+        let guilds =
+            tokio::task::spawn_blocking(move || dce::load_guilds(&pattern, &warnings)).await??;
+        return Ok((guilds, Labels::new()));
+    }
+    if let Some(n) = config.simulate {
+        return Ok((Simulated::guilds(n), Labels::new()));
+    }
+    if let Some(root) = &config.from_data_package {
+        let root = root.clone();
+        let guilds = tokio::task::spawn_blocking(move || package::load_guilds(&root)).await??;
+        return Ok((guilds, Labels::new()));
+    }
+    if config.from_discord {
+        let client = build_keyless_client(&config.check_options()?)?;
+        let lister = config.guild_lister(&client).await?;
+        let guilds = lister
+            .guilds(&client)
+            .await
+            .context("couldn't list the servers through Discord's API")?;
+        info!(servers = guilds.len(), "listed the servers from Discord");
+        return Ok((guilds, Labels::new()));
+    }
+
+    // a guild in several indexes is checked once, with all their labels and
+    // the name the first one gave it
+    let mut guilds: BTreeMap<String, String> = BTreeMap::new();
+    let mut sources: BTreeMap<String, String> = BTreeMap::new();
+    let mut labels = Labels::new();
+    let mut add = |id: String, name: String, label: Option<&String>, source: &str| {
+        if let Some(label) = label {
+            let labels = labels.entry(id.clone()).or_default();
+            if !labels.contains(label) {
+                labels.push(label.clone());
+            }
+        }
+        match guilds.entry(id) {
+            Entry::Vacant(entry) => {
+                sources.insert(entry.key().clone(), source.to_owned());
+                entry.insert(name);
+            }
+            // an ID standing in for the name isn't a conflict
+            Entry::Occupied(mut entry) if entry.get() == entry.key() => {
+                sources.insert(entry.key().clone(), source.to_owned());
+                entry.insert(name);
+            }
+            Entry::Occupied(entry) if *entry.get() != name && name != *entry.key() => {
+                let (id, kept) = (entry.key(), entry.get());
+                let first = &sources[id];
+                config.warnings.warn(
+                    Condition::DuplicateKey,
+                    format!(
+                        "{id} is {kept:?} in {first} but {name:?} in {source}, going by the first"
+                    ),
+                );
+            }
+            Entry::Occupied(_) => {}
+        }
+    };
+    let mut names = Vec::new();
+    let mut invites: Vec<(String, Option<&String>)> = config
+        .invites
+        .iter()
+        .map(|link| (link.clone(), None))
+        .collect();
+    for index in &config.index_path {
+        for path in index_files(&index.path)? {
+            let (listed, linked, unknown) = load_index(
+                &path,
+                config.index_format.known(),
+                config.no_autodetect,
+                &config.warnings,
+            )
+            .await?;
+            let source = path.display().to_string();
+            for (id, name) in listed {
+                add(id, name, index.label.as_ref(), &source);
+            }
+            invites.extend(linked.into_iter().map(|link| (link, index.label.as_ref())));
+            names.extend(unknown);
+        }
+    }
+    if offline {
+        for (link, _) in invites.drain(..) {
+            let message = format!("invite {link} can't be looked up offline, skipping it");
+            config.warnings.warn(Condition::SkippedInput, message);
+        }
+        for name in names.drain(..) {
+            let message =
+                format!("{name:?} has no ID and can't be searched for offline, skipping it");
+            config.warnings.warn(Condition::SkippedInput, message);
+        }
+    }
+    if !names.is_empty() {
+        let search = config.search()?;
+        let client = build_client(&config.check_options()?)?;
+        for name in names {
+            let message = match search.find(&client, &name).await {
+                Ok(candidates) if candidates.is_empty() => {
+                    format!("{name:?} has no ID and the search found no server by that name, skipping it")
+                }
+                Ok(candidates) => format!(
+                    "{name:?} has no ID, skipping it; the search found {}. Put the right one's ID in the index to check it",
+                    candidates.iter().map(describe_candidate).collect::<Vec<_>>().join(", ")
+                ),
+                Err(err) => format!("{name:?} has no ID and couldn't be searched for, skipping it: {err}"),
+            };
+            config.warnings.warn(Condition::SkippedInput, message);
+        }
+    }
+    if !invites.is_empty() {
+        let resolver = InviteResolver::default();
+        let client = build_keyless_client(&config.check_options()?)?;
+        for (link, label) in invites {
+            if let Some((id, name)) = resolve_invite(config, &resolver, &client, &link).await {
+                add(id, name, label, &link);
+            }
+        }
+    }
+    Ok((guilds, labels))
+}
+
+/// The index files `path` stands for: the ones in it, by name, if it's a
+/// directory, or else just itself
+pub(crate) fn index_files(path: &Path) -> eyre::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_owned()]);
+    }
+    let entries = std::fs::read_dir(path)
+        .with_context(|| format!("couldn't read directory {}", path.display()))?;
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let hidden = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+        if !hidden
+            && path.is_file()
+            && extension.is_some_and(|extension| index::EXTENSIONS.contains(&extension.as_str()))
+        {
+            files.push(path);
+        }
+    }
+    if files.is_empty() {
+        eyre::bail!("there are no index files in {}", path.display());
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn describe_candidate(candidate: &Candidate) -> String {
+    format!(
+        "{} (ID: {}, {:.0}% alike)",
+        candidate.name,
+        candidate.id,
+        candidate.score * 100.0
+    )
+}
+
+/// `--search`: the servers in the dataset named like each of `queries`
+fn print_search(config: &Config, queries: &[String]) -> eyre::Result<()> {
+    let search = config.search()?;
+    let client = build_client(&config.check_options()?)?;
+    let runtime = config
+        .build_runtime()
+        .context("couldn't start async runtime")?;
+    let mut failed = 0;
+    for query in queries {
+        match runtime.block_on(search.find(&client, query)) {
+            Ok(candidates) => {
+                println!("{query:?}: {} candidates", candidates.len());
+                for candidate in &candidates {
+                    println!("  {}", describe_candidate(candidate));
+                }
+            }
+            Err(err) => {
+                failed += 1;
+                warn!(query, %err, "couldn't search");
+            }
+        }
+    }
+    if failed > 0 {
+        eyre::bail!("{failed} of the searches failed");
+    }
+    Ok(())
+}
+
+/// The ID and name of the server `link` invites to, or `None` with a warning
+/// if that can't be found out
+async fn resolve_invite(
+    config: &Config,
+    resolver: &InviteResolver,
+    client: &reqwest::Client,
+    link: &str,
+) -> Option<(String, String)> {
+    let code = invite_code(link).expect("only invite links are collected");
+    let message = match resolver.resolve(client, code).await {
+        Ok(Some((id, name))) => {
+            debug!(link, id, "resolved invite");
+            return Some((id, name));
+        }
+        Ok(None) => format!("invite {link} is expired or isn't for a server, skipping it"),
+        Err(err) => format!("couldn't look up invite {link}, skipping it: {err}"),
+    };
+    config.warnings.warn(Condition::SkippedInput, message);
+    None
+}
+
+/// The users to check next to the guilds, with `--include-users`
+pub async fn load_users(config: &Config) -> eyre::Result<Option<BTreeMap<String, String>>> {
+    let (Some(root), true) = (&config.from_data_package, config.include_users) else {
+        return Ok(None);
+    };
+    let (root, warnings) = (root.clone(), Arc::clone(&config.warnings));
+    let users =
+        tokio::task::spawn_blocking(move || package::load_users(&root, &warnings)).await??;
+    Ok(Some(users))
+}
+
+/// How long a downloaded `--prefilter` listing is used before it's fetched
+/// again
+const LISTING_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Listings and dumps older than this are warned about
+pub(crate) const STALE_LISTING: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// The `--prefilter` listing, unless `--verify-all` is given. Downloaded
+/// listings are kept in the state directory and reused for [`LISTING_TTL`],
+/// or for longer when downloading a new one fails. `offline` gives `None`
+/// rather than download one.
+pub async fn load_prefilter(config: &Config, offline: bool) -> eyre::Result<Option<Listing>> {
+    let Some(source) = config.prefilter.as_ref().filter(|_| !config.verify_all) else {
+        return Ok(None);
+    };
+    if config.kind != CheckKind::Servers {
+        eyre::bail!(
+            "--prefilter listings are of servers, it doesn't work with --kind {}",
+            config.kind.plural()
+        );
+    }
+    let listing = if source.starts_with("http://") || source.starts_with("https://") {
+        let path = config
+            .state()
+            .map(|state| state.prefilter_path())
+            .transpose();
+        let path = path.unwrap_or_else(|err| {
+            warn!(%err, "couldn't open the listing cache");
+            None
+        });
+        let cached = path.as_deref().and_then(|path| match Listing::load(path) {
+            Ok(listing) => listing.filter(|l| l.source == *source),
+            Err(err) => {
+                warn!(%err, "ignoring unreadable listing cache {}", path.display());
+                None
+            }
+        });
+        match cached {
+            Some(cached) if !config.no_cache && cached.age() < LISTING_TTL => {
+                debug!("using the cached listing");
+                cached
+            }
+            _ if offline => return Ok(None),
+            cached => {
+                let client = build_keyless_client(&config.check_options()?)?;
+                match (Listing::fetch(&client, source).await, cached) {
+                    (Ok(listing), _) => {
+                        if let Some(path) = &path {
+                            if let Err(err) = listing.save(path) {
+                                warn!(%err, "couldn't cache the listing");
+                            }
+                        }
+                        listing
+                    }
+                    (Err(err), Some(cached)) => {
+                        config.warnings.warn(
+                            Condition::Degraded,
+                            format!("{err}, using the one from {} ago", age(&cached)),
+                        );
+                        cached
+                    }
+                    (Err(err), None) => {
+                        return Err(err)
+                            .with_context(|| format!("couldn't load --prefilter {source}"))
+                    }
+                }
+            }
+        }
+    } else {
+        Listing::read(Path::new(source))
+            .with_context(|| format!("couldn't load --prefilter {source}"))?
+    };
+    if listing.age() > STALE_LISTING {
+        config.warnings.warn(
+            Condition::Degraded,
+            format!(
+                "the --prefilter listing is {} old, servers added to the dataset since aren't in it",
+                age(&listing)
+            ),
+        );
+    }
+    info!(
+        ids = listing.len(),
+        "prefilter listing from {}, {} old",
+        listing.source,
+        age(&listing)
+    );
+    Ok(Some(listing))
+}
+
+/// How old a listing is, to the minute
+fn age(listing: &Listing) -> humantime::FormattedDuration {
+    humantime::format_duration(Duration::from_secs(listing.age().as_secs() / 60 * 60))
+}
+
+/// `--max-error-rate` only kicks in after this many checks, so one early
+/// failure doesn't end the run
+const MIN_ERROR_SAMPLE: usize = 20;
+
+/// Which error threshold a run exceeded, if any
+fn error_threshold(config: &Config, done: usize, errors: usize) -> Option<String> {
+    if let Some(max) = config.max_errors {
+        if errors >= max {
+            return Some(format!("--max-errors {max}"));
+        }
+    }
+    if let Some(max) = config.max_error_rate {
+        if done >= MIN_ERROR_SAMPLE && errors as f64 / done as f64 * 100.0 > max {
+            return Some(format!("--max-error-rate {max}"));
+        }
+    }
+    None
+}
+
+/// Why a run ended before checking everything
+enum Stop {
+    /// Names the `--max-errors` or `--max-error-rate` threshold
+    TooManyErrors(String),
+    /// `--fail-fast` found this guild compromised
+    Compromised(String),
+    /// `--fail-fast` saw the check of this guild fail
+    Failed(String),
+}
+
+/// The exit `--fail-on` asks for, with the same codes as `--fail-fast`
+fn fail_on(on: FailOn, kind: CheckKind, found: usize, errors: usize) -> Option<Exit> {
+    if on.found() && found > 0 {
+        return Some(Exit {
+            code: 2,
+            message: format!("found {found} compromised {}", kind.noun(found)),
+        });
+    }
+    if on.error() && errors > 0 {
+        let checks = if errors == 1 { "check" } else { "checks" };
+        return Some(Exit {
+            code: 1,
+            message: format!("{errors} {checks} failed"),
+        });
+    }
+    None
+}
+
+impl Stop {
+    /// `--fail-fast` exits with 2 when the answer is "compromised", so that
+    /// CI can tell it apart from the run going wrong
+    fn into_exit(self, errors: usize) -> Exit {
+        let (code, message) = match self {
+            Stop::TooManyErrors(threshold) => {
+                (1, format!("stopped after {errors} errors ({threshold})"))
+            }
+            Stop::Compromised(guild) => (2, format!("stopped at {guild}, which is compromised")),
+            Stop::Failed(guild) => (1, format!("stopped at {guild}, which couldn't be checked")),
+        };
+        Exit {
+            code,
+            message: format!("{message}, results are partial"),
+        }
+    }
+}
+
+/// What to tell the user when the API refuses the checks
+pub fn unauthorized(message: &str, key_sent: bool) -> String {
+    let what = match key_sent {
+        true => "check the API key",
+        false => "it needs an API key",
+    };
+    format!(
+        "{message}; {what}, given with --api-key-file, {} or `spy-pet-checker token --api-key set`",
+        credentials::API_KEY_ENV
+    )
+}
+
+/// Starts the run's checkpoint. With `--resume`, the servers and users an
+/// earlier run over the same inputs finished are taken out of `guilds` and
+/// `users`, and their saved results returned. Trouble with the checkpoint
+/// doesn't stop the run, it just isn't saved.
+fn open_checkpoint(
+    config: &Config,
+    options: &CheckOptions,
+    guilds: &mut BTreeMap<String, String>,
+    users: &mut Option<BTreeMap<String, String>>,
+) -> (Option<Checkpoint>, Vec<Response>) {
+    let Some(state) = config.state() else {
+        if config.resume {
+            config.warnings.warn(
+                Condition::IgnoredOption,
+                "--resume needs the state directory, which --no-state and --simulate turn off",
+            );
+        }
+        return (None, Vec::new());
+    };
+    let ids = guilds.keys().chain(users.iter().flat_map(BTreeMap::keys));
+    let fingerprint = checkpoint::fingerprint(
+        options.kind.as_str(),
+        options.backends.iter().map(|b| b.name()),
+        ids.map(String::as_str),
+    );
+    let path = match state.checkpoint_path(&fingerprint) {
+        Ok(path) => path,
+        Err(err) => {
+            warn!(%err, "couldn't open the checkpoint directory, progress won't be saved");
+            return (None, Vec::new());
+        }
+    };
+
+    let mut resumed = Vec::new();
+    if config.resume {
+        let saved = Checkpoint::load(&path).unwrap_or_else(|err| {
+            warn!(%err, path = %path.display(), "couldn't read the checkpoint");
+            Vec::new()
+        });
+        let guild_kind = options.kind;
+        resumed = checkpoint::finished(saved, |kind| match kind == guild_kind {
+            true => options.backends.len(),
+            false => 1,
+        });
+        let mut done = BTreeSet::new();
+        for response in &resumed {
+            let removed = match response.kind == guild_kind {
+                true => guilds.remove(&response.guild_id),
+                false => users.as_mut().and_then(|u| u.remove(&response.guild_id)),
+            };
+            if removed.is_some() || done.contains(&response.guild_id) {
+                done.insert(response.guild_id.clone());
+            }
+        }
+        resumed.retain(|response| done.contains(&response.guild_id));
+        match done.len() {
+            0 => info!("no checkpoint to resume for these inputs, checking everything"),
+            n => info!(
+                "resuming: {n} already checked, {} to go",
+                guilds.len() + users.as_ref().map_or(0, BTreeMap::len)
+            ),
+        }
+    }
+    match Checkpoint::create(&path, config.resume) {
+        Ok(checkpoint) => (Some(checkpoint), resumed),
+        Err(err) => {
+            warn!(%err, path = %path.display(), "couldn't start the checkpoint, progress won't be saved");
+            (None, resumed)
+        }
+    }
+}
+
+/// Where `--format json` and `ndjson` write results as they come in
+enum Stream {
+    Document(JsonDocument<BufWriter<Output>>),
+    Lines(JsonLines<BufWriter<Output>>),
+}
+
+impl Stream {
+    fn push(&mut self, result: &Response) -> std::io::Result<()> {
+        match self {
+            Stream::Document(document) => document.push(result),
+            Stream::Lines(lines) => lines.push(result),
+        }
+    }
+
+    /// Ends the output, which is then up to the caller to commit
+    fn finish(self, run: &RunReport) -> std::io::Result<Output> {
+        let w = match self {
+            Stream::Document(document) => document.finish(run)?,
+            Stream::Lines(lines) => lines.finish()?,
+        };
+        w.into_inner().map_err(std::io::IntoInnerError::into_error)
+    }
+}
+
+/// How long the requests in flight get to finish once the run is interrupted
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
+
+/// On Ctrl-C or SIGTERM, stops sending requests and waits for the ones in
+/// flight, up to [`SHUTDOWN_GRACE`] or until the signal comes again
+async fn wind_down_on_signal(drain: CancellationToken, cancel: CancellationToken) {
+    shutdown_signal().await;
+    warn!("interrupted, finishing the requests in flight, interrupt again to stop right away");
+    drain.cancel();
+    tokio::select! {
+        _ = shutdown_signal() => warn!("interrupted again, stopping"),
+        _ = tokio::time::sleep(SHUTDOWN_GRACE) => {
+            warn!("requests in flight didn't finish in time, stopping");
+        }
+    }
+    cancel.cancel();
+}
+
+/// Runs the checks. Also returns why the run stopped early, if it did.
+///
+/// With `stream`, results are written to it as they come in, and only the
+/// ones needed after the run (compromised ones, or all of them for the
+/// history) stay in the report. With `updates`, every result is sent there
+/// too, and with `events` written there as a progress event. `unlisted` are
+/// the results of guilds `--prefilter` left out. Results are marked with
+/// their `labels`. Findings `known` already had aren't written to `stream`
+/// and don't stop the run, and neither are clean or undecided results with
+/// `--only-compromised`.
+#[allow(clippy::too_many_arguments)]
+async fn process(
+    config: Arc<Config>,
+    mut guilds: BTreeMap<String, String>,
+    labels: Labels,
+    mut users: Option<BTreeMap<String, String>>,
+    mut unlisted: Vec<Response>,
+    progress: ProgressFormat,
+    stream: &mut Option<Stream>,
+    updates: Option<UnboundedSender<Update>>,
+    events: Option<Arc<EventSink>>,
+    known: Option<&Known>,
+) -> eyre::Result<(RunReport, Option<Stop>)> {
+    let mut options = config.check_options()?;
+    if let Some(events) = &events {
+        options.backends = events.report_requests(options.backends);
+    }
+    tokio::spawn(wind_down_on_signal(
+        options.drain.clone(),
+        options.cancel.clone(),
+    ));
+
+    // owning the limiter lets the status dump count requests in flight
+    let limiter = Arc::new(Semaphore::new(config.concurrency));
+    options.limiter = Some(Arc::clone(&limiter));
+    options.cache = config.result_cache();
+    options.dump = config.response_dump(options.backends.len())?;
+    options.member_scan = config.member_scanner()?;
+    options.batches = config.batches()?;
+    let user_options = config.user_options(&options)?;
+    let (mut checkpoint, resumed) = open_checkpoint(&config, &options, &mut guilds, &mut users);
+    let total = guilds.len() * options.backends.len()
+        + users.as_ref().map_or(0, BTreeMap::len)
+        + resumed.len();
+    let status = Arc::new(RunStatus::new(
+        total,
+        config.concurrency,
+        Arc::clone(&limiter),
+        options.batches.clone(),
+    ));
+    // a view that went away doesn't stop the run
+    let send = |update| {
+        if let Some(updates) = &updates {
+            let _ = updates.send(update);
+        }
+    };
+    send(Update::Started {
+        total,
+        concurrency: config.concurrency,
+        limiter,
+        batches: options.batches.clone(),
+        cancel: options.cancel.clone(),
+    });
+    #[cfg(unix)]
+    let dump = tokio::spawn(dump_on_sigusr1(Arc::clone(&status)));
+
+    let mut json = events.map(|events| JsonProgress::start(events, total));
+    if let Some(json) = &json {
+        let queued = guilds.iter().chain(users.iter().flatten());
+        for (id, name) in queued {
+            json.queued(id, name);
+        }
+    }
+    let bar = (progress == ProgressFormat::Bar).then(|| ProgressBar::start(Arc::clone(&status)));
+    // `--include-users` users aren't in the indexes
+    let guild_kind = options.kind;
+    let mut results = check_stream(guilds, &options);
+    if let (Some(users), Some(user_options)) = (users, &user_options) {
+        results.add(users, user_options);
+    }
+    let mut report = RunReport::default();
+    let mut stop = None;
+    let mut window = config.window.map(|window| WindowReport {
+        window,
+        mode: config.since_mode.into_window_mode(),
+        outside: 0,
+    });
+    let keep_clean = stream.is_none() || config.state().is_some() || !config.outputs.is_empty();
+    let fail_on_compromised = matches!(
+        config.fail_fast,
+        Some(FailFast::Compromised | FailFast::Any)
+    );
+    let fail_on_error = matches!(config.fail_fast, Some(FailFast::Error | FailFast::Any));
+    for response in &mut unlisted {
+        add_labels(response, &labels);
+    }
+    let min_shared = config.min_shared.unwrap_or(0);
+    let min_severity = config.min_severity.map(SeverityLevel::into_level);
+    unlisted.retain(|response| response.labels.len() >= min_shared);
+    for response in unlisted {
+        if let (Some(stream), false) = (stream.as_mut(), config.only_compromised) {
+            stream.push(&response).context("couldn't write to output")?;
+        }
+        if keep_clean {
+            report.results.push(response);
+        }
+    }
+    // the results saved by the run being resumed come first, as if they had
+    // just come in
+    let mut resumed = resumed.into_iter();
+    loop {
+        let (result, saved) = match resumed.next() {
+            Some(response) => (Ok(response), true),
+            None => match results.next().await {
+                Some(result) => (result, false),
+                None => break,
+            },
+        };
+        status.record(result.is_ok());
+        let mut stop_here = None;
+        match result {
+            Ok(mut response) => {
+                if response.kind == guild_kind {
+                    add_labels(&mut response, &labels);
+                }
+                if !saved {
+                    if let Some(Err(err)) = checkpoint.as_mut().map(|c| c.record(&response)) {
+                        warn!(%err, "couldn't write to the checkpoint, progress won't be saved");
+                        checkpoint = None;
+                    }
+                }
+                if let Some(json) = &mut json {
+                    json.result(&response);
+                }
+                if let Some(bar) = &bar {
+                    bar.result(&response);
+                }
+                if response.kind == guild_kind && response.labels.len() < min_shared {
+                    debug!(id = %response.guild_id, "in fewer than --min-shared indexes, leaving it out");
+                    continue;
+                }
+                if let (Some(window), true) = (&mut window, response.is_compromised()) {
+                    let in_window = window.window.check(&response.api_response);
+                    response.window = Some(in_window);
+                    if in_window == InWindow::Outside {
+                        window.outside += 1;
+                        if window.mode == WindowMode::Filter {
+                            debug!(id = %response.guild_id, "outside the window, leaving it out");
+                            continue;
+                        }
+                    }
+                }
+                if let (Some(min), Some(severity)) = (min_severity, response.severity()) {
+                    if severity.level < min {
+                        debug!(id = %response.guild_id, %severity, "below --min-severity, leaving it out");
+                        continue;
+                    }
+                }
+                let known = known.is_some_and(|known| known.has(&response));
+                let shown = !known && (response.is_compromised() || !config.only_compromised);
+                if let (Some(stream), true) = (stream.as_mut(), shown) {
+                    stream.push(&response).context("couldn't write to output")?;
+                }
+                if updates.is_some() {
+                    send(Update::Result(Box::new(response.clone())));
+                }
+                let outside = response.window == Some(InWindow::Outside);
+                if fail_on_compromised && response.is_compromised() && !outside && !known {
+                    let guild = format!("{} (ID: {})", response.guild_name, response.guild_id);
+                    stop_here = Some(Stop::Compromised(guild));
+                }
+                if keep_clean || response.is_compromised() {
+                    report.results.push(response);
+                }
+            }
+            Err(failed) => {
+                // every other check would be refused the same way, so one
+                // error says it all
+                if failed.kind == ErrorKind::Unauthorized {
+                    options.cancel.cancel();
+                    #[cfg(unix)]
+                    dump.abort();
+                    let message = unauthorized(&failed.message, options.api_key.is_some());
+                    // what was streamed so far is closed off, not left cut
+                    // short, and marked as partial
+                    if let Some(stream) = stream.take() {
+                        report.cancelled = true;
+                        report.failed.push(failed);
+                        stream
+                            .finish(&report)
+                            .context("couldn't write to output")?
+                            .commit()?;
+                    }
+                    eyre::bail!(message);
+                }
+                if let Some(json) = &mut json {
+                    json.error(&failed);
+                }
+                if let Some(bar) = &bar {
+                    bar.error();
+                }
+                if updates.is_some() {
+                    send(Update::Failed {
+                        guild_id: failed.guild_id.clone(),
+                        guild_name: failed.guild_name.clone(),
+                        source: failed.source.clone(),
+                        message: failed.message.clone(),
+                    });
+                }
+                if fail_on_error {
+                    let guild = format!("{} (ID: {})", failed.guild_name, failed.guild_id);
+                    stop_here = Some(Stop::Failed(guild));
+                }
+                report.failed.push(failed);
+            }
+        }
+        if stop.is_some() {
+            continue;
+        }
+        let done = status.done();
+        if let Some(threshold) = error_threshold(&config, done, report.failed.len()) {
+            error!("too many errors, stopping ({threshold})");
+            stop_here = Some(Stop::TooManyErrors(threshold));
+        }
+        if stop_here.is_some() {
+            // stragglers are aborted mid-request, not waited for
+            options.cancel.cancel();
+            stop = stop_here;
+        }
+    }
+    report.cancelled = options.cancel.is_cancelled() || options.drain.is_cancelled();
+    report.performance = Some(results.performance());
+    report.schema_drift = results.schema_drift();
+    report.window = window;
+    if let Some(json) = json {
+        json.finish(&report);
+    }
+    drop(bar);
+    #[cfg(unix)]
+    dump.abort();
+    if let Some(adaptive) = options.adaptive.as_ref().filter(|a| a.lowest() < a.max()) {
+        info!(
+            "--adaptive lowered the concurrency to {} at most, it ended at {} of {}",
+            adaptive.lowest(),
+            adaptive.current(),
+            adaptive.max()
+        );
+    }
+    if let Some(cache) = &options.cache {
+        if let Err(err) = cache.save() {
+            warn!(%err, "couldn't save the result cache");
+        }
+    }
+    if let Some(dump) = options.dump.as_ref().filter(|d| d.kept() > 0) {
+        warn!(
+            "kept {} dumps from earlier runs, pass --dump-overwrite to replace them",
+            dump.kept()
+        );
+    }
+
+    if report.cancelled && stop.is_none() {
+        warn!("run was interrupted, results are partial");
+    }
+    if let Some(checkpoint) = checkpoint {
+        if report.cancelled {
+            info!(
+                "progress saved in {}, run again with --resume to pick up from here",
+                checkpoint.path().display()
+            );
+        } else if let Err(err) = checkpoint.remove() {
+            warn!(%err, "couldn't remove the finished run's checkpoint");
+        }
+    }
+
+    Ok((report, stop))
+}
+
+pub fn run(global: GlobalArgs, args: CheckArgs, matches: &ArgMatches) -> eyre::Result<()> {
+    run_kind(global, args, matches, None)
+}
+
+/// `check-users`: a check with `--kind users`, whatever the config file says
+pub fn run_users(global: GlobalArgs, args: CheckArgs, matches: &ArgMatches) -> eyre::Result<()> {
+    run_kind(global, args, matches, Some(CheckKind::Users))
+}
+
+fn run_kind(
+    global: GlobalArgs,
+    args: CheckArgs,
+    matches: &ArgMatches,
+    kind: Option<CheckKind>,
+) -> eyre::Result<()> {
+    let print_config = args.print_config;
+    let dry_run = args.dry_run;
+    let search = args.search.clone();
+    let all_urls = args.all_urls;
+    let yes = args.yes;
+    let progress = args.progress(&global);
+    let progress_events = args.progress_events.clone();
+    #[cfg(feature = "tui")]
+    let tui = args.tui;
+    #[cfg(not(feature = "tui"))]
+    let tui = false;
+    #[cfg(feature = "pick")]
+    let (pick, pick_save) = (args.pick, args.pick_save.clone());
+    let file = FileConfig::load(global.config.as_deref())?;
+    let mut config = Config::resolve(args, &global, matches, file);
+    if let Some(kind) = kind {
+        config.kind = kind;
+    }
+    config.read_notify_files()?;
+    let config = Arc::new(config);
+
+    if print_config {
+        // shows whether there is a key, but doesn't stop to prompt for one
+        if config.api_key_file.as_deref() != Some(Path::new("-")) {
+            config.api_key()?;
+        }
+        print!("{}", toml::to_string(&*config)?);
+        return Ok(());
+    }
+
+    if config.min_shared.is_some() && config.index_path.iter().all(|i| i.label.is_none()) {
+        eyre::bail!("--min-shared counts the labels of --index-path label=path, give at least one");
+    }
+    let to_stdout = std::iter::once(&config.output)
+        .chain(config.outputs.iter().map(|extra| &extra.output))
+        .filter(|output| output.is_none())
+        .count();
+    if to_stdout > 1 {
+        eyre::bail!("only one --format can go to stdout, give the others an --output");
+    }
+
+    // a bad --notify-header would otherwise only show once the run is over
+    config.callback()?;
+    // and --encrypt and --sign, which would otherwise only be read then
+    check_report(&config)?;
+
+    if dry_run {
+        return print_plan(&config, all_urls);
+    }
+    if !search.is_empty() {
+        return print_search(&config, &search);
+    }
+
+    let ignored = |message| config.warnings.warn(Condition::IgnoredOption, message);
+
+    if let Some(interval) = config.watch {
+        if config.max_errors.is_some() || config.max_error_rate.is_some() {
+            ignored("--max-errors and --max-error-rate have no effect with --watch");
+        }
+        if config.fail_fast.is_some() {
+            ignored("--fail-fast has no effect with --watch");
+        }
+        if config.fail_on.is_some() {
+            ignored("--fail-on has no effect with --watch");
+        }
+        if config.template.is_some() {
+            ignored("--template has no effect with --watch");
+        }
+        if config.sort_by.is_some() {
+            ignored("--sort-by has no effect with --watch");
+        }
+        if config.only_compromised || config.include_clean || config.details {
+            ignored(
+                "--only-compromised, --include-clean and --details have no effect with --watch",
+            );
+        }
+        if config.cache_ttl.is_some() {
+            ignored("--cache-ttl has no effect with --watch");
+        }
+        if config.window.is_some() {
+            ignored("--since and --until have no effect with --watch");
+        }
+        if config.include_users {
+            ignored("--include-users has no effect with --watch");
+        }
+        if config.prefilter.is_some() {
+            ignored("--prefilter has no effect with --watch");
+        }
+        if config.min_shared.is_some() {
+            ignored("--min-shared has no effect with --watch");
+        }
+        if !config.email_to.is_empty() {
+            ignored("--email-to has no effect with --watch");
+        }
+        if !config.outputs.is_empty() {
+            ignored("--watch only writes the first --format and --output");
+        }
+        if config.output_mode == OutputMode::Atomic {
+            ignored("--atomic has no effect with --watch, which appends to --output");
+        }
+        config.warnings.check()?;
+        return watch::run(config, interval);
+    }
+
+    // read now, so a password prompt comes before the run and not after it
+    let smtp = config.smtp()?;
+
+    #[cfg(feature = "metrics")]
+    if config.metrics_listen.is_some() {
+        ignored("--metrics-listen has no effect without --watch");
+    }
+
+    let runtime = config
+        .build_runtime()
+        .context("couldn't start async runtime")?;
+    let (guilds, labels) = runtime.block_on(load_guilds(&config, config.offline))?;
+    if config.simulate.is_some() {
+        warn!(
+            "--simulate: checking {} made-up servers against a built-in stub, none of the results are real",
+            guilds.len()
+        );
+    }
+    // with --strict, nothing is checked from inputs that had to be worked
+    // around
+    config.warnings.check()?;
+    #[cfg(feature = "pick")]
+    let guilds = match pick {
+        true => {
+            let Some(picked) = crate::pick::pick(guilds, config.kind.plural())? else {
+                return Ok(());
+            };
+            if picked.is_empty() {
+                info!("nothing picked, not checking anything");
+                return Ok(());
+            }
+            if let Some(path) = &pick_save {
+                crate::pick::save(path, &picked)?;
+            }
+            picked
+        }
+        false => guilds,
+    };
+    let users = runtime.block_on(load_users(&config))?;
+    let user_count = users.as_ref().map_or(0, BTreeMap::len);
+    let index_size = guilds.len() + user_count;
+    let listing = runtime.block_on(load_prefilter(&config, config.offline))?;
+    let (guilds, unlisted) = match &listing {
+        Some(listing) => listing.split(guilds),
+        None => (guilds, BTreeMap::new()),
+    };
+    let prefilter = listing.as_ref().map(|listing| PrefilterReport {
+        source: listing.source.clone(),
+        fetched_at: listing.fetched_at,
+        listed: guilds.len(),
+        unlisted: unlisted.len(),
+    });
+    let unlisted: Vec<Response> = match &listing {
+        Some(listing) => unlisted
+            .into_iter()
+            .map(|(id, name)| listing.unlisted(id, name))
+            .collect(),
+        None => Vec::new(),
+    };
+    let known = Known::load(&config)?;
+    // a broken template should fail before the run, not after it
+    let mut formatter = formatter(
+        &config.format,
+        config.format_options(&global),
+        config.template.as_deref(),
+    )?;
+    // JSON reports are signed inside, others next to them
+    let json = matches!(config.format, Format::Json) && config.template.is_none();
+    config.warnings.check()?;
+    if !yes && !confirm(&config, guilds.len(), user_count)? {
+        return Ok(());
+    }
+
+    let start = Instant::now();
+    let started_at = Utc::now();
+    #[cfg(feature = "self-update")]
+    let update_check = match config.state() {
+        // an unasked-for request to GitHub could give a proxied run away
+        Some(state)
+            if !global.no_update_check
+                && !config.offline
+                && !config.tor
+                && config.proxy.is_none() =>
+        {
+            let options = config.check_options()?;
+            Some(runtime.spawn(async move { crate::update::newer_version(&state, options).await }))
+        }
+        _ => None,
+    };
+    let (report, stop) = runtime.block_on(async {
+        // JSON is written as results come in, so big runs don't pile up
+        let writer = || -> eyre::Result<BufWriter<Output>> {
+            Ok(BufWriter::new(open_report(
+                &config,
+                config.output.as_deref(),
+                json,
+            )?))
+        };
+        let mut stream = match config.format {
+            // sorting needs every result too
+            _ if config.template.is_some() || config.sort_by.is_some() => None,
+            // the group summary needs every result
+            // and the TUI needs the terminal to itself
+            Format::Json if config.group_by.is_none() && !tui => Some(Stream::Document(
+                JsonDocument::new(writer()?, Some(started_at)).compact(config.json_compact),
+            )),
+            Format::Ndjson if !tui => Some(Stream::Lines(JsonLines::new(writer()?))),
+            Format::Json
+            | Format::Ndjson
+            | Format::Yaml
+            | Format::Toml
+            | Format::Plain
+            | Format::Csv
+            | Format::Markdown
+            | Format::Html
+            | Format::Junit => None,
+        };
+        let events = match &progress_events {
+            Some(Some(path)) => Some(
+                EventSink::open(path)
+                    .with_context(|| format!("couldn't open {}", path.display()))?,
+            ),
+            Some(None) => Some(EventSink::stderr(true)),
+            None if progress == ProgressFormat::Json => Some(EventSink::stderr(false)),
+            None => None,
+        };
+        let span = info_span!("run", guilds = index_size);
+        #[cfg(feature = "tui")]
+        let (updates, screen) = match tui {
+            true => {
+                let (updates, screen) = start_tui()?;
+                (Some(updates), Some(screen))
+            }
+            false => (None, None),
+        };
+        #[cfg(not(feature = "tui"))]
+        let updates = None;
+        let (mut report, stop) = process(
+            Arc::clone(&config),
+            guilds,
+            labels,
+            users,
+            unlisted,
+            progress,
+            &mut stream,
+            updates,
+            events,
+            known.as_ref(),
+        )
+        .instrument(span)
+        .await?;
+        report.prefilter = prefilter;
+        report.started_at = Some(started_at);
+        // the report is written once the screen is closed
+        #[cfg(feature = "tui")]
+        if let Some(screen) = screen {
+            screen.await?.context("couldn't draw the TUI")?;
+        }
+        let streamed = stream.is_some();
+        if let Some(stream) = stream {
+            stream
+                .finish(&report)
+                .context("couldn't write to output")?
+                .commit()?;
+        }
+        if !streamed || !config.outputs.is_empty() {
+            // the known findings are put back for the history, as are the
+            // results --only-compromised leaves out
+            let (hidden, shown) = std::mem::take(&mut report.results)
+                .into_iter()
+                .partition(|r| {
+                    known.as_ref().is_some_and(|known| known.has(r))
+                        || (config.only_compromised && !r.is_compromised())
+                });
+            report.results = shown;
+            if let Some(by) = config.sort_by {
+                sort_run(&mut report, by.into_output());
+            }
+            if !streamed {
+                let mut writer = open_report(&config, config.output.as_deref(), json)?;
+                formatter
+                    .write_results(&mut writer, &report)
+                    .context("couldn't write to output")?;
+                writer.commit()?;
+            }
+            write_outputs(&config, &global, &report)?;
+            report.results.extend(hidden);
+        }
+        if let Some(known) = &known {
+            match report.results.iter().filter(|r| known.has(r)).count() {
+                0 => {}
+                n => info!("left out {n} compromised servers the baseline already had"),
+            }
+        }
+        Ok::<_, eyre::Report>((report, stop))
+    })?;
+    info!("processing took {:?}", start.elapsed());
+
+    // plain text on stderr would break the JSON log stream
+    match (global.log_format, progress) {
+        // the finished event already has the count
+        (_, ProgressFormat::Json) => {}
+        (LogFormat::Text, _) => {
+            // the plain output on stdout already lists the failed checks
+            let listed = std::iter::once((&config.format, &config.output))
+                .chain(
+                    config
+                        .outputs
+                        .iter()
+                        .map(|extra| (&extra.format, &extra.output)),
+                )
+                .any(|(format, output)| {
+                    matches!(format, Format::Plain) && output.is_none() && config.template.is_none()
+                });
+            print_summary(&report, listed);
+        }
+        (LogFormat::Json, _) => {
+            let perf = report.performance.clone().unwrap_or_default();
+            let latency = perf.latency.as_ref();
+            info!(
+                errors = report.failed.len(),
+                requests = perf.requests,
+                requests_per_second = perf.requests_per_second,
+                bytes_received = perf.bytes_received,
+                latency_p50_ms = latency.map(|l| l.p50_ms),
+                latency_p95_ms = latency.map(|l| l.p95_ms),
+                latency_max_ms = latency.map(|l| l.max_ms),
+                queued_ms = perf.queued_ms,
+                requesting_ms = perf.requesting_ms,
+                min_rate_limit_remaining = perf.min_rate_limit_remaining,
+                "run finished"
+            )
+        }
+    }
+
+    let errors = report.failed.len();
+    // what the output shows: the baseline's findings and those outside the
+    // window aren't news
+    let mut report = report;
+    report.notification = notification(&config, &report, None);
+    let found: Vec<&Response> = report
+        .compromised()
+        .filter(|r| r.window != Some(InWindow::Outside))
+        .filter(|r| !known.as_ref().is_some_and(|known| known.has(r)))
+        .collect();
+    let summary = summary("compromised", found.len(), index_size, errors);
+    let payload = Payload {
+        summary: &summary,
+        checked: index_size,
+        errors,
+        findings: &found,
+        notification: report.notification,
+    };
+    runtime.block_on(notify(&config, &payload));
+    let found = found.len();
+    if let Some(smtp) = &smtp {
+        if report.notification.is_none_or(|n| n.fired) {
+            let email = email_report(
+                &config,
+                &global,
+                smtp,
+                &mut report,
+                known.as_ref(),
+                &summary,
+            );
+            runtime.block_on(email);
+        }
+    }
+    record_run(&config, started_at, index_size, report);
+    if let Some(stop) = stop {
+        return Err(stop.into_exit(errors).into());
+    }
+    if let Some(exit) = config
+        .fail_on
+        .and_then(|on| fail_on(on, config.kind, found, errors))
+    {
+        return Err(exit.into());
+    }
+    config.warnings.check()?;
+
+    #[cfg(feature = "self-update")]
+    if let (Some(handle), false) = (update_check, progress == ProgressFormat::Json) {
+        // don't hold up the exit for a slow release feed
+        let wait = runtime.block_on(async {
+            tokio::time::timeout(std::time::Duration::from_secs(1), handle).await
+        });
+        if let Ok(Ok(Some(version))) = wait {
+            match global.log_format {
+                LogFormat::Text => eprintln!("spy-pet-checker {version} is available, run `spy-pet-checker self-update` to install it"),
+                LogFormat::Json => info!(%version, "update available"),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Puts up the `--tui` screen on a thread of its own. It follows the run
+/// through the returned channel and stays up after it, until it's closed.
+#[cfg(feature = "tui")]
+fn start_tui() -> eyre::Result<(
+    UnboundedSender<Update>,
+    tokio::task::JoinHandle<std::io::Result<()>>,
+)> {
+    if !std::io::stdout().is_terminal() {
+        eyre::bail!("--tui needs stdout to be a terminal, pass --output instead of redirecting");
+    }
+    // the run may log before the screen is up
+    crate::tui::capture_logs();
+    let (updates, receiver) = tokio::sync::mpsc::unbounded_channel();
+    let runtime = tokio::runtime::Handle::current();
+    let screen = tokio::task::spawn_blocking(move || crate::tui::run(receiver, runtime));
+    Ok((updates, screen))
+}
+
+/// How many example URLs `--dry-run` shows
+const PLAN_URLS: usize = 5;
+
+/// `--dry-run`: goes through everything a run does before the first request
+/// and prints what would happen instead, with every URL if `all_urls`
+fn print_plan(config: &Config, all_urls: bool) -> eyre::Result<()> {
+    let options = config.check_options()?;
+    let runtime = config
+        .build_runtime()
+        .context("couldn't start async runtime")?;
+    let (guilds, labels) = match config.from_discord {
+        // listing them is a request to Discord
+        true => (BTreeMap::new(), Labels::new()),
+        false => runtime.block_on(load_guilds(config, true))?,
+    };
+    let users = runtime.block_on(load_users(config))?;
+    let user_count = users.as_ref().map_or(0, BTreeMap::len);
+    let listing = runtime.block_on(load_prefilter(config, true))?;
+    let index_size = guilds.len();
+    let guilds = match &listing {
+        Some(listing) => listing.split(guilds).0,
+        None => guilds,
+    };
+    let total = guilds.len() * options.backends.len() + user_count;
+
+    match (&config.from_dce, &config.from_data_package) {
+        _ if config.simulate.is_some() => {
+            println!("{} made-up servers (--simulate)", index_size)
+        }
+        (Some(pattern), _) => println!("{} servers from the exports in {pattern}", index_size),
+        (None, Some(root)) => println!(
+            "{} servers from the data package in {}",
+            index_size,
+            root.display()
+        ),
+        (None, None) if config.from_discord => {
+            println!("The servers Discord lists when the run starts (not in a dry run)")
+        }
+        (None, None) if !config.ids.is_empty() => {
+            println!("{} {} from --ids", index_size, config.kind.plural())
+        }
+        (None, None) => {
+            let indexes: Vec<String> = config.index_path.iter().map(|i| i.to_string()).collect();
+            println!(
+                "{} {} from {}",
+                index_size,
+                config.kind.plural(),
+                indexes.join(", ")
+            );
+        }
+    }
+    let mut per_label: BTreeMap<&str, usize> = BTreeMap::new();
+    for label in labels.values().flatten() {
+        *per_label.entry(label).or_default() += 1;
+    }
+    if !per_label.is_empty() {
+        let counts: Vec<String> = per_label
+            .iter()
+            .map(|(label, count)| format!("{label} {count}"))
+            .collect();
+        println!("Labels: {}", counts.join(", "));
+    }
+    if let Some(shard) = config.shard {
+        println!("Shard: {shard} (the servers counted above are its share)");
+    }
+    if users.is_some() {
+        println!("{user_count} users from its friends list and DMs");
+    }
+    match (&config.prefilter, &listing) {
+        (Some(_), _) if config.verify_all => {}
+        (Some(_), Some(listing)) => println!(
+            "Prefilter: {} of {index_size} servers in the listing from {} ({} old), only those are checked",
+            guilds.len(),
+            listing.source,
+            age(listing)
+        ),
+        (Some(source), None) => {
+            println!("Prefilter: {source} would be downloaded first, and only the servers in it checked")
+        }
+        (None, _) => {}
+    }
+    let names: Vec<&str> = options.backends.iter().map(|b| b.name()).collect();
+    println!("Backends: {}", names.join(", "));
+    if let Some(scanner) = config.member_scanner()? {
+        println!(
+            "Member lists: scanned for {} known scraper bots, {} more requests at least",
+            scanner.bots().len(),
+            guilds.len()
+        );
+    }
+    let shown = match all_urls {
+        true => total,
+        false => PLAN_URLS,
+    };
+    match total > shown {
+        true => println!("{total} requests, for example:"),
+        false => println!("{total} requests:"),
+    }
+    let urls = guilds
+        .keys()
+        .flat_map(|id| options.backends.iter().map(move |b| b.url(id)));
+    for url in urls.take(shown) {
+        println!("  GET {url}");
+    }
+    if total > shown {
+        println!("  ... and {} more (--all-urls lists them)", total - shown);
+    }
+
+    println!("Concurrency: {}", config.concurrency);
+    if let Some(spacing) = config.spacing() {
+        println!(
+            "Spacing: {} between requests, plus up to {} at random",
+            humantime::format_duration(spacing.delay()),
+            humantime::format_duration(spacing.jitter())
+        );
+    }
+    if let Some(batches) = config.batches()? {
+        println!(
+            "Batches: {} requests, then {} of rest",
+            batches.size(),
+            humantime::format_duration(batches.cooldown())
+        );
+    }
+    match (&options.proxy, &options.tor) {
+        (_, Some(_)) => println!("Proxy: Tor, at {}", config.tor_socks),
+        (Some(proxy), None) => println!("Proxy: {proxy}"),
+        (None, None) => println!("Proxy: none"),
+    }
+    if let Some(doh) = &config.doh {
+        println!("DNS: over HTTPS, from {doh}");
+    }
+    if config.insecure {
+        println!("TLS: certificates aren't verified (--insecure)");
+    } else if !config.ca_cert.is_empty() {
+        println!(
+            "TLS: {} extra CA certificates trusted",
+            config.ca_cert.len()
+        );
+    }
+    let mode = match config.output_mode {
+        OutputMode::Replace => "",
+        OutputMode::Append => ", appended to",
+        OutputMode::Atomic => ", replaced once the run is done",
+    };
+    let outputs = std::iter::once((&config.format, &config.output)).chain(
+        config
+            .outputs
+            .iter()
+            .map(|extra| (&extra.format, &extra.output)),
+    );
+    for (format, output) in outputs {
+        let format = format.to_possible_value().expect("no format is hidden");
+        let format = format.get_name();
+        match output {
+            Some(path) => println!("Output: {format} to {}{mode}", path.display()),
+            None => println!("Output: {format} to stdout"),
+        }
+    }
+    match config.state() {
+        Some(state) => println!("History: {}", state.root().display()),
+        None => println!("History: not recorded"),
+    }
+    println!("Estimated duration: {}", estimate(config, total));
+    Ok(())
+}
+
+/// Request latency estimates go by when there's no previous run
+const ASSUMED_LATENCY: Duration = Duration::from_millis(500);
+
+/// How long a run is expected to take
+struct Estimate {
+    duration: Duration,
+    /// Whether it's from the last run's latency rather than [`ASSUMED_LATENCY`]
+    measured: bool,
+}
+
+impl fmt::Display for Estimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", humantime::format_duration(self.duration))?;
+        match self.measured {
+            true => write!(f, " (at the last run's median latency)"),
+            false => write!(
+                f,
+                " (assuming {} a request, no previous run to go by)",
+                humantime::format_duration(ASSUMED_LATENCY)
+            ),
+        }
+    }
+}
+
+fn estimate(config: &Config, total: usize) -> Estimate {
+    let (latency, measured) = match last_latency(config) {
+        Some(latency) => (latency, true),
+        None => (ASSUMED_LATENCY, false),
+    };
+    let rounds = total.div_ceil(config.concurrency.max(1)) as u32;
+    let cooldowns = match (config.batch_size, config.cooldown) {
+        (Some(size), Some(cooldown)) => {
+            cooldown * total.div_ceil(size.max(1)).saturating_sub(1) as u32
+        }
+        _ => Duration::ZERO,
+    };
+    Estimate {
+        duration: Duration::from_secs((latency * rounds + cooldowns).as_secs()),
+        measured,
+    }
+}
+
+/// Runs expected to take longer than this ask before starting
+const CONFIRM_DURATION: Duration = Duration::from_secs(30 * 60);
+
+/// Above this concurrency getting rate limited is all but certain, so the
+/// run asks before starting
+const AGGRESSIVE_CONCURRENCY: usize = 8;
+
+/// Shows the plan and asks whether to go ahead, if the run is long or
+/// aggressive. Without a terminal to ask on, it goes ahead.
+fn confirm(config: &Config, guilds: usize, users: usize) -> eyre::Result<bool> {
+    // the stub and the fixtures don't mind
+    if !std::io::stdin().is_terminal() || config.simulate.is_some() || config.offline {
+        return Ok(true);
+    }
+    let backends: Vec<String> = config
+        .check_options()?
+        .backends
+        .iter()
+        .map(|b| b.name().to_owned())
+        .collect();
+    let total = guilds * backends.len() + users;
+    let estimate = estimate(config, total);
+    let aggressive = config.concurrency > AGGRESSIVE_CONCURRENCY;
+    if !aggressive && estimate.duration <= CONFIRM_DURATION {
+        return Ok(true);
+    }
+
+    match users {
+        0 => eprintln!(
+            "About to check {guilds} {} against {} ({total} requests)",
+            config.kind.plural(),
+            backends.join(", ")
+        ),
+        users => eprintln!(
+            "About to check {guilds} servers against {} and {users} users ({total} requests)",
+            backends.join(", ")
+        ),
+    }
+    match aggressive {
+        true => eprintln!(
+            "Concurrency: {}, which is likely to get rate limited",
+            config.concurrency
+        ),
+        false => eprintln!("Concurrency: {}", config.concurrency),
+    }
+    eprintln!("Estimated duration: {estimate}");
+    eprint!("Start the run? [y/N] ");
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Median request latency of the most recent run in the history
+fn last_latency(config: &Config) -> Option<Duration> {
+    let history = History::new(config.state()?);
+    let ids = history.run_ids().ok()?;
+    let latency = ids.iter().rev().find_map(|id| {
+        let record = history.load(id).ok()?;
+        Some(record.report.performance?.latency?.p50_ms)
+    })?;
+    Some(Duration::from_secs_f64(latency / 1000.0))
+}
+
+/// Checks `--notify-on` against a finished run. `new_compromised` is worked
+/// out from the last recorded run if not given.
+pub fn notification(
+    config: &Config,
+    report: &RunReport,
+    new_compromised: Option<usize>,
+) -> Option<Notification> {
+    let on = config.notify_on?.into_notify();
+    let new_compromised = match new_compromised {
+        Some(n) => n,
+        None if on == NotifyOn::NewCompromised => {
+            let previous = previous_compromised(config).unwrap_or_default();
+            report
+                .compromised()
+                .filter(|r| !previous.contains(&(r.guild_id.clone(), r.source.clone())))
+                .count()
+        }
+        None => 0,
+    };
+    let summary = RunSummary {
+        compromised: report.compromised().count(),
+        new_compromised,
+        errors: report.failed.len(),
+    };
+    let notification = Notification::evaluate(on, &summary);
+    debug!(?notification.on, notification.fired, "notification condition");
+    Some(notification)
+}
+
+/// What a notification leads with, e.g. `2 compromised servers in a check of
+/// 120`
+pub fn summary(what: &str, found: usize, checked: usize, errors: usize) -> String {
+    let mut summary = match found {
+        0 => format!("No {what} servers in a check of {checked}"),
+        1 => format!("1 {what} server in a check of {checked}"),
+        n => format!("{n} {what} servers in a check of {checked}"),
+    };
+    match errors {
+        0 => {}
+        1 => summary.push_str(", 1 check failed"),
+        n => summary.push_str(&format!(", {n} checks failed")),
+    }
+    summary
+}
+
+/// The most servers a desktop notification lists
+const DESKTOP_FINDINGS: usize = 5;
+
+/// The summary, and the first few servers found
+fn desktop_body(payload: &Payload<'_>) -> String {
+    let mut body = payload.summary.to_owned();
+    for found in payload.findings.iter().take(DESKTOP_FINDINGS) {
+        body.push_str(&format!("\n{} ({})", found.guild_name, found.guild_id));
+    }
+    if payload.findings.len() > DESKTOP_FINDINGS {
+        let more = payload.findings.len() - DESKTOP_FINDINGS;
+        body.push_str(&format!("\n…and {more} more"));
+    }
+    body
+}
+
+/// Tells `--discord-webhook`, `--notify-url` and `--desktop-notify`, if
+/// they're given, about a run: when `--notify-on` fires, or without it when
+/// anything was found. Failing to is only warned about.
+pub async fn notify(config: &Config, payload: &Payload<'_>) {
+    let fired = match payload.notification {
+        Some(notification) => notification.fired,
+        None => !payload.findings.is_empty(),
+    };
+    if !fired {
+        return;
+    }
+    if config.desktop_notify {
+        if let Err(err) = crate::desktop::show("spy-pet-checker", &desktop_body(payload)) {
+            warn!("couldn't show a desktop notification: {err:#}");
+        }
+    }
+    if config.discord_webhook.is_none() && config.notify_url.is_none() {
+        return;
+    }
+    let client = match config
+        .check_options()
+        .and_then(|options| Ok(build_keyless_client(&options)?))
+    {
+        Ok(client) => client,
+        Err(err) => return warn!("couldn't set up the notification client: {err:#}"),
+    };
+    let findings = payload.findings.len();
+    if let Some(url) = &config.discord_webhook {
+        match Webhook::new(url.clone()).post(&client, payload).await {
+            Ok(()) => info!(findings, "posted to the Discord webhook"),
+            Err(CheckError::Network(err)) => warn!(%err, "couldn't reach the Discord webhook"),
+            Err(err) => warn!(%err, "couldn't post to the Discord webhook"),
+        }
+    }
+    match config.callback() {
+        Ok(Some(callback)) => match callback.send(&client, payload).await {
+            Ok(()) => info!(findings, "sent to --notify-url"),
+            Err(err) => warn!(%err, "couldn't send to --notify-url"),
+        },
+        Ok(None) => {}
+        Err(err) => warn!("{err:#}"),
+    }
+}
+
+/// Emails the report as `--email-format` renders it, with what the output
+/// leaves out left out. Failing to is only warned about.
+async fn email_report(
+    config: &Config,
+    global: &GlobalArgs,
+    smtp: &Smtp,
+    report: &mut RunReport,
+    known: Option<&Known>,
+    summary: &str,
+) {
+    let (hidden, shown) = std::mem::take(&mut report.results)
+        .into_iter()
+        .partition(|r| {
+            known.is_some_and(|known| known.has(r))
+                || (config.only_compromised && !r.is_compromised())
+        });
+    report.results = shown;
+    if let Some(by) = config.sort_by {
+        sort_run(report, by.into_output());
+    }
+    let (format, html) = match config.email_format {
+        EmailFormat::Plain => (Format::Plain, false),
+        EmailFormat::Html => (Format::Html, true),
+    };
+    let options = FormatOptions {
+        color: false,
+        ..config.format_options(global)
+    };
+    let mut body = Vec::new();
+    let rendered = format
+        .adjusted_formatter(options)
+        .write_results(&mut body, report);
+    report.results.extend(hidden);
+    if let Err(err) = rendered {
+        return warn!(%err, "couldn't render the report to email");
+    }
+    let mail = Mail {
+        from: config.email_from.clone().unwrap_or_default(),
+        to: config.email_to.clone(),
+        subject: format!("spy-pet-checker: {summary}"),
+        body: String::from_utf8_lossy(&body).into_owned(),
+        html,
+    };
+    match smtp.send(&mail).await {
+        Ok(()) => info!(to = mail.to.len(), "emailed the report"),
+        Err(err) => warn!(%err, "couldn't email the report"),
+    }
+}
+
+/// The last recorded run
+fn last_run(config: &Config) -> Option<RunRecord> {
+    let history = History::new(config.state()?);
+    let last = history.run_ids().ok()?.pop()?;
+    history.load(&last).ok()
+}
+
+/// Guilds (id, source) the last recorded run found compromised
+fn previous_compromised(config: &Config) -> Option<BTreeSet<(String, String)>> {
+    let compromised = last_run(config)?
+        .report
+        .compromised()
+        .map(|r| (r.guild_id.clone(), r.source.clone()))
+        .collect();
+    Some(compromised)
+}
+
+/// Servers `--baseline` already found compromised, left out of the output
+struct Known(BTreeSet<String>);
+
+impl Known {
+    fn load(config: &Config) -> eyre::Result<Option<Self>> {
+        let Some(path) = &config.baseline else {
+            return Ok(None);
+        };
+        let results = match path.as_os_str() == "previous" {
+            true => match last_run(config) {
+                Some(record) => record.report.results,
+                None => {
+                    config.warnings.warn(
+                        Condition::IgnoredOption,
+                        "--baseline previous: no run in the history to compare with, reporting everything",
+                    );
+                    return Ok(None);
+                }
+            },
+            false => {
+                let text = std::fs::read_to_string(path)
+                    .with_context(|| format!("couldn't read {}", path.display()))?;
+                parse_results(&text)
+                    .with_context(|| format!("couldn't parse {}", path.display()))?
+            }
+        };
+        let known: BTreeSet<String> = results
+            .into_iter()
+            .filter(Response::is_compromised)
+            .map(|r| r.guild_id)
+            .collect();
+        debug!(known = known.len(), "loaded the baseline");
+        Ok(Some(Self(known)))
+    }
+
+    /// Whether `response` is a finding the baseline already had
+    fn has(&self, response: &Response) -> bool {
+        response.is_compromised() && self.0.contains(&response.guild_id)
+    }
+}
+
+/// How many failed checks the summary lists one by one
+const SUMMARY_FAILURES: usize = 10;
+
+/// The end of run summary on stderr. Failed checks are listed one by one
+/// unless they already were, in the output.
+fn print_summary(report: &RunReport, listed: bool) {
+    let mut kinds: BTreeMap<ErrorKind, usize> = BTreeMap::new();
+    for failed in &report.failed {
+        *kinds.entry(failed.kind).or_default() += 1;
+    }
+    let kinds: Vec<String> = kinds
+        .iter()
+        .map(|(kind, count)| format!("{count} {kind}"))
+        .collect();
+    match report.failed.len() {
+        0 => eprintln!("Errors: 0"),
+        n => eprintln!("Errors: {n} ({})", kinds.join(", ")),
+    }
+    let shown = match listed {
+        true => 0,
+        false => SUMMARY_FAILURES,
+    };
+    for failed in report.failed.iter().take(shown) {
+        let tries = match failed.attempts {
+            0 | 1 => String::new(),
+            n => format!(" (after {n} tries)"),
+        };
+        eprintln!(
+            "  {} (ID: {}) on {}: {}{tries}",
+            failed.guild_name, failed.guild_id, failed.source, failed.message
+        );
+    }
+    if !listed && report.failed.len() > SUMMARY_FAILURES {
+        eprintln!("  ... and {} more", report.failed.len() - SUMMARY_FAILURES);
+    }
+    let Some(perf) = &report.performance else {
+        return;
+    };
+    if let Some(latency) = &perf.latency {
+        eprintln!(
+            "Latency: p50 {:.0}ms, p95 {:.0}ms, max {:.0}ms",
+            latency.p50_ms, latency.p95_ms, latency.max_ms
+        );
+    }
+    eprintln!(
+        "Throughput: {:.1} requests/s, {:.1} KiB received",
+        perf.requests_per_second,
+        perf.bytes_received as f64 / 1024.0
+    );
+    if perf.retries > 0 {
+        eprintln!(
+            "Retries: {} ({:.1}% of the requests sent)",
+            perf.retries,
+            perf.retries as f64 / (perf.requests as u64 + perf.retries) as f64 * 100.0
+        );
+    }
+    let busy = perf.queued_ms + perf.requesting_ms;
+    if busy > 0.0 {
+        eprintln!(
+            "Time waiting for the concurrency limit: {:.0}% (the rest on the network)",
+            perf.queued_ms / busy * 100.0
+        );
+    }
+    if let Some(remaining) = perf.min_rate_limit_remaining {
+        eprintln!("Minimum remaining quota observed: {remaining}");
+    }
+}
+
+/// Saves the run to the history, unless the state directory is disabled
+pub fn record_run(
+    config: &Config,
+    started_at: DateTime<Utc>,
+    index_size: usize,
+    report: RunReport,
+) {
+    if let Some(state) = config.state() {
+        let mut record = RunRecord::new(started_at, index_size, report);
+        match History::new(state).save(&mut record) {
+            Ok(path) => debug!(path=%path.display(), "recorded run {}", record.run_id),
+            Err(err) => warn!(%err, "couldn't record run in history"),
+        }
+    }
+}
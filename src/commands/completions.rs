@@ -0,0 +1,546 @@
+//! Tab completion scripts, written from the command line definition itself so
+//! they cover every subcommand and flag of this build.
+
+use std::io::{self, Write};
+
+use clap::{Arg, ArgAction, Command, CommandFactory, ValueHint};
+use color_eyre::eyre;
+
+use crate::cli::{Cli, CompletionsArgs, Shell};
+
+const BIN: &str = "spy-pet-checker";
+
+/// What an option's value can be completed with
+enum Value {
+    /// It's a switch, there's no value
+    None,
+    Choices(Vec<String>),
+    Path,
+    Any,
+}
+
+/// An option as the scripts need it
+struct Opt {
+    longs: Vec<String>,
+    short: Option<char>,
+    help: String,
+    value: Value,
+    repeatable: bool,
+}
+
+/// A command and the subcommands on the way to it, e.g. `["history", "show"]`
+struct Node<'a> {
+    path: Vec<&'a str>,
+    command: &'a Command,
+}
+
+impl Node<'_> {
+    /// Its name in shell function names, e.g. `spy_pet_checker__history__show`
+    fn function(&self) -> String {
+        let mut name = BIN.replace('-', "_");
+        for part in &self.path {
+            name.push_str("__");
+            name.push_str(&part.replace('-', "_"));
+        }
+        name
+    }
+
+    fn subcommands(&self) -> Vec<(&str, String)> {
+        self.command
+            .get_subcommands()
+            .filter(|sub| !sub.is_hide_set())
+            .map(|sub| (sub.get_name(), about(sub)))
+            .collect()
+    }
+
+    /// What its positional arguments can be completed with, going by the
+    /// first one
+    fn positional(&self) -> Value {
+        self.command
+            .get_positionals()
+            .next()
+            .map_or(Value::None, value)
+    }
+
+    fn options(&self) -> Vec<Opt> {
+        self.command
+            .get_arguments()
+            .filter(|arg| !arg.is_positional() && !arg.is_hide_set())
+            .map(|arg| Opt {
+                longs: arg
+                    .get_long_and_visible_aliases()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(str::to_owned)
+                    .collect(),
+                short: arg.get_short(),
+                help: help(arg),
+                value: value(arg),
+                repeatable: matches!(arg.get_action(), ArgAction::Append | ArgAction::Count),
+            })
+            .collect()
+    }
+}
+
+fn about(command: &Command) -> String {
+    command
+        .get_about()
+        .map(|about| about.to_string())
+        .unwrap_or_default()
+}
+
+fn help(arg: &Arg) -> String {
+    arg.get_help()
+        .map(|help| help.to_string())
+        .unwrap_or_default()
+}
+
+fn value(arg: &Arg) -> Value {
+    if !arg.get_action().takes_values() {
+        return Value::None;
+    }
+    let choices: Vec<String> = arg
+        .get_possible_values()
+        .into_iter()
+        .filter(|value| !value.is_hide_set())
+        .map(|value| value.get_name().to_owned())
+        .collect();
+    if !choices.is_empty() {
+        return Value::Choices(choices);
+    }
+    match arg.get_value_hint() {
+        ValueHint::AnyPath | ValueHint::FilePath | ValueHint::DirPath => Value::Path,
+        _ => Value::Any,
+    }
+}
+
+/// Every command, the top level first and each before its subcommands
+fn nodes(command: &Command) -> Vec<Node<'_>> {
+    fn walk<'a>(node: Node<'a>, nodes: &mut Vec<Node<'a>>) {
+        let subcommands: Vec<&Command> = node
+            .command
+            .get_subcommands()
+            .filter(|sub| !sub.is_hide_set())
+            .collect();
+        let path = node.path.clone();
+        // `help`'s subcommands are the same as the top level's
+        let help = path.last() == Some(&"help");
+        nodes.push(node);
+        for sub in subcommands.into_iter().filter(|_| !help) {
+            let mut path = path.clone();
+            path.push(sub.get_name());
+            walk(Node { path, command: sub }, nodes);
+        }
+    }
+    let mut nodes = Vec::new();
+    walk(
+        Node {
+            path: Vec::new(),
+            command,
+        },
+        &mut nodes,
+    );
+    nodes
+}
+
+/// `text` in single quotes, escaped the way `shell` wants
+fn quote(text: &str, shell: Shell) -> String {
+    match shell {
+        Shell::Fish => format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'")),
+        Shell::Powershell => format!("'{}'", text.replace('\'', "''")),
+        Shell::Bash | Shell::Zsh => format!("'{}'", text.replace('\'', r"'\''")),
+    }
+}
+
+fn bash(w: &mut dyn Write, nodes: &[Node]) -> io::Result<()> {
+    let function = nodes[0].function();
+    writeln!(w, "_{function}() {{")?;
+    writeln!(w, "    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\"")?;
+    writeln!(w, "    local prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"")?;
+    writeln!(w, "    local cmd=\"{function}\" i")?;
+    writeln!(w, "    for ((i = 1; i < COMP_CWORD; i++)); do")?;
+    writeln!(w, "        case \"$cmd,${{COMP_WORDS[i]}}\" in")?;
+    for node in nodes {
+        for (name, _) in node.subcommands() {
+            writeln!(
+                w,
+                "            {},{name}) cmd=\"{}__{}\" ;;",
+                node.function(),
+                node.function(),
+                name.replace('-', "_")
+            )?;
+        }
+    }
+    writeln!(w, "        esac")?;
+    writeln!(w, "    done")?;
+    writeln!(w, "    case \"$cmd\" in")?;
+    for node in nodes {
+        let options = node.options();
+        writeln!(w, "        {})", node.function())?;
+        writeln!(w, "            case \"$prev\" in")?;
+        for opt in options
+            .iter()
+            .filter(|opt| !matches!(opt.value, Value::None))
+        {
+            let mut names: Vec<String> = opt.longs.iter().map(|long| format!("--{long}")).collect();
+            names.extend(opt.short.map(|short| format!("-{short}")));
+            let reply = match &opt.value {
+                Value::Choices(choices) => {
+                    format!(
+                        "COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+                        choices.join(" ")
+                    )
+                }
+                Value::Path => "COMPREPLY=($(compgen -f -- \"$cur\"))".to_owned(),
+                _ => "COMPREPLY=()".to_owned(),
+            };
+            writeln!(w, "                {}) {reply}; return ;;", names.join("|"))?;
+        }
+        writeln!(w, "            esac")?;
+        let mut flags: Vec<String> = Vec::new();
+        for opt in &options {
+            flags.extend(opt.longs.iter().map(|long| format!("--{long}")));
+            flags.extend(opt.short.map(|short| format!("-{short}")));
+        }
+        let mut words: Vec<String> = node
+            .subcommands()
+            .into_iter()
+            .map(|(name, _)| name.to_owned())
+            .collect();
+        let positional = node.positional();
+        if let Value::Choices(choices) = &positional {
+            words.extend(choices.iter().cloned());
+        }
+        writeln!(w, "            if [[ \"$cur\" == -* ]]; then")?;
+        writeln!(
+            w,
+            "                COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+            flags.join(" ")
+        )?;
+        writeln!(w, "            else")?;
+        writeln!(
+            w,
+            "                COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+            words.join(" ")
+        )?;
+        if let Value::Path = positional {
+            writeln!(w, "                COMPREPLY+=($(compgen -f -- \"$cur\"))")?;
+        }
+        writeln!(w, "            fi")?;
+        writeln!(w, "            ;;")?;
+    }
+    writeln!(w, "    esac")?;
+    writeln!(w, "}}")?;
+    writeln!(w)?;
+    writeln!(w, "complete -F _{function} -o bashdefault -o default {BIN}")
+}
+
+/// An `_arguments` spec for `opt`, one per name
+fn zsh_specs(opt: &Opt) -> Vec<String> {
+    // brackets and colons mean something in a spec
+    let help = opt
+        .help
+        .replace('\\', "\\\\")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:");
+    let action = match &opt.value {
+        Value::None => String::new(),
+        Value::Choices(choices) => format!(":value:({})", choices.join(" ")),
+        Value::Path => ":file:_files".to_owned(),
+        Value::Any => ":value:".to_owned(),
+    };
+    let takes_value = !matches!(opt.value, Value::None);
+    let repeat = if opt.repeatable { "*" } else { "" };
+    let mut names: Vec<String> = opt
+        .longs
+        .iter()
+        .map(|long| format!("--{long}{}", if takes_value { "=" } else { "" }))
+        .collect();
+    names.extend(
+        opt.short
+            .map(|short| format!("-{short}{}", if takes_value { "+" } else { "" })),
+    );
+    names
+        .into_iter()
+        .map(|name| quote(&format!("{repeat}{name}[{help}]{action}"), Shell::Zsh))
+        .collect()
+}
+
+fn zsh(w: &mut dyn Write, nodes: &[Node]) -> io::Result<()> {
+    writeln!(w, "#compdef {BIN}")?;
+    for node in nodes {
+        let subcommands = node.subcommands();
+        writeln!(w)?;
+        writeln!(w, "_{}() {{", node.function())?;
+        let mut specs: Vec<String> = node.options().iter().flat_map(zsh_specs).collect();
+        if subcommands.is_empty() {
+            match node.positional() {
+                Value::Choices(choices) => specs.push(quote(
+                    &format!("1:value:({})", choices.join(" ")),
+                    Shell::Zsh,
+                )),
+                Value::Path => specs.push("'*:file:_files'".to_owned()),
+                Value::None | Value::Any => {}
+            }
+        } else {
+            writeln!(w, "    local line state")?;
+            specs.push("'1: :->commands'".to_owned());
+            specs.push("'*:: :->arguments'".to_owned());
+        }
+        let context = if subcommands.is_empty() { "" } else { " -C" };
+        write!(w, "    _arguments{context} -s")?;
+        for spec in &specs {
+            write!(w, " \\\n        {spec}")?;
+        }
+        writeln!(w)?;
+        if subcommands.is_empty() {
+            writeln!(w, "}}")?;
+            continue;
+        }
+        writeln!(w, "    case $state in")?;
+        writeln!(w, "        commands)")?;
+        writeln!(w, "            local -a commands")?;
+        writeln!(w, "            commands=(")?;
+        for (name, about) in &subcommands {
+            let about = about.replace(':', "\\:");
+            writeln!(
+                w,
+                "                {}",
+                quote(&format!("{name}:{about}"), Shell::Zsh)
+            )?;
+        }
+        writeln!(w, "            )")?;
+        writeln!(w, "            _describe -t commands command commands")?;
+        writeln!(w, "            ;;")?;
+        writeln!(w, "        arguments)")?;
+        writeln!(w, "            case $line[1] in")?;
+        for (name, _) in &subcommands {
+            writeln!(
+                w,
+                "                {name}) _{}__{} ;;",
+                node.function(),
+                name.replace('-', "_")
+            )?;
+        }
+        writeln!(w, "            esac")?;
+        writeln!(w, "            ;;")?;
+        writeln!(w, "    esac")?;
+        writeln!(w, "}}")?;
+    }
+    writeln!(w)?;
+    writeln!(w, "_{} \"$@\"", nodes[0].function())
+}
+
+/// The condition for completing at `node`: its subcommands were given, and
+/// none of its own yet
+fn fish_condition(node: &Node) -> String {
+    let mut conditions: Vec<String> = Vec::new();
+    if node.path.is_empty() {
+        conditions.push("__fish_use_subcommand".to_owned());
+    }
+    conditions.extend(
+        node.path
+            .iter()
+            .map(|part| format!("__fish_seen_subcommand_from {part}")),
+    );
+    let names: Vec<&str> = node
+        .subcommands()
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    if !node.path.is_empty() && !names.is_empty() {
+        conditions.push(format!(
+            "not __fish_seen_subcommand_from {}",
+            names.join(" ")
+        ));
+    }
+    conditions.join("; and ")
+}
+
+fn fish(w: &mut dyn Write, nodes: &[Node]) -> io::Result<()> {
+    writeln!(w, "complete -c {BIN} -f")?;
+    for node in nodes {
+        let condition = quote(&fish_condition(node), Shell::Fish);
+        for (name, about) in node.subcommands() {
+            writeln!(
+                w,
+                "complete -c {BIN} -n {condition} -a {name} -d {}",
+                quote(&about, Shell::Fish)
+            )?;
+        }
+        match node.positional() {
+            Value::Choices(choices) => writeln!(
+                w,
+                "complete -c {BIN} -n {condition} -a {}",
+                quote(&choices.join(" "), Shell::Fish)
+            )?,
+            Value::Path => writeln!(w, "complete -c {BIN} -n {condition} -F")?,
+            Value::None | Value::Any => {}
+        }
+        for opt in node.options() {
+            let mut line = format!("complete -c {BIN} -n {condition}");
+            for long in &opt.longs {
+                line.push_str(&format!(" -l {long}"));
+            }
+            if let Some(short) = opt.short {
+                line.push_str(&format!(" -s {short}"));
+            }
+            match &opt.value {
+                Value::None => {}
+                Value::Choices(choices) => line.push_str(&format!(
+                    " -r -f -a {}",
+                    quote(&choices.join(" "), Shell::Fish)
+                )),
+                Value::Path => line.push_str(" -r -F"),
+                Value::Any => line.push_str(" -r"),
+            }
+            if !opt.help.is_empty() {
+                line.push_str(&format!(" -d {}", quote(&opt.help, Shell::Fish)));
+            }
+            writeln!(w, "{line}")?;
+        }
+    }
+    Ok(())
+}
+
+fn powershell(w: &mut dyn Write, nodes: &[Node]) -> io::Result<()> {
+    writeln!(w, "using namespace System.Management.Automation")?;
+    writeln!(w, "using namespace System.Management.Automation.Language")?;
+    writeln!(w)?;
+    writeln!(
+        w,
+        "Register-ArgumentCompleter -Native -CommandName '{BIN}' -ScriptBlock {{"
+    )?;
+    writeln!(
+        w,
+        "    param($wordToComplete, $commandAst, $cursorPosition)"
+    )?;
+    writeln!(w, "    $elements = $commandAst.CommandElements")?;
+    writeln!(w, "    $command = @(")?;
+    writeln!(w, "        '{BIN}'")?;
+    writeln!(w, "        for ($i = 1; $i -lt $elements.Count; $i++) {{")?;
+    writeln!(w, "            $element = $elements[$i]")?;
+    writeln!(
+        w,
+        "            if ($element -isnot [StringConstantExpressionAst] -or"
+    )?;
+    writeln!(
+        w,
+        "                $element.StringConstantType -ne [StringConstantType]::BareWord -or"
+    )?;
+    writeln!(
+        w,
+        "                $element.Value.StartsWith('-') -or $element.Value -eq $wordToComplete) {{"
+    )?;
+    writeln!(w, "                break")?;
+    writeln!(w, "            }}")?;
+    writeln!(w, "            $element.Value")?;
+    writeln!(w, "        }}) -join ';'")?;
+    // the values of the option before the word, if it has a fixed set
+    writeln!(
+        w,
+        "    $last = $elements.Count - $(if ($wordToComplete) {{ 2 }} else {{ 1 }})"
+    )?;
+    writeln!(
+        w,
+        "    $values = @(switch (\"$command;$($elements[$last].Extent.Text)\") {{"
+    )?;
+    for node in nodes {
+        let mut key = vec![BIN];
+        key.extend(&node.path);
+        for opt in node.options() {
+            let Value::Choices(choices) = &opt.value else {
+                continue;
+            };
+            let choices: Vec<String> = choices
+                .iter()
+                .map(|choice| quote(choice, Shell::Powershell))
+                .collect();
+            let mut names: Vec<String> = opt.longs.iter().map(|long| format!("--{long}")).collect();
+            names.extend(opt.short.map(|short| format!("-{short}")));
+            for name in names {
+                let case = quote(&format!("{};{name}", key.join(";")), Shell::Powershell);
+                writeln!(w, "        {case} {{ {} }}", choices.join(", "))?;
+            }
+        }
+    }
+    writeln!(w, "    }})")?;
+    writeln!(w, "    if ($values) {{")?;
+    writeln!(
+        w,
+        "        return $values.Where{{ $_ -like \"$wordToComplete*\" }} | ForEach-Object {{"
+    )?;
+    writeln!(
+        w,
+        "            [CompletionResult]::new($_, $_, [CompletionResultType]::ParameterValue, $_)"
+    )?;
+    writeln!(w, "        }}")?;
+    writeln!(w, "    }}")?;
+    writeln!(w, "    $completions = @(switch ($command) {{")?;
+    for node in nodes {
+        let mut key = vec![BIN];
+        key.extend(&node.path);
+        writeln!(w, "        {} {{", quote(&key.join(";"), Shell::Powershell))?;
+        for opt in node.options() {
+            let help = quote(
+                if opt.help.is_empty() { " " } else { &opt.help },
+                Shell::Powershell,
+            );
+            let mut names: Vec<String> = opt.longs.iter().map(|long| format!("--{long}")).collect();
+            names.extend(opt.short.map(|short| format!("-{short}")));
+            for name in names {
+                let name = quote(&name, Shell::Powershell);
+                writeln!(
+                    w,
+                    "            [CompletionResult]::new({name}, {name}, [CompletionResultType]::ParameterName, {help})"
+                )?;
+            }
+        }
+        for (name, about) in node.subcommands() {
+            let about = quote(
+                if about.is_empty() { " " } else { &about },
+                Shell::Powershell,
+            );
+            let name = quote(name, Shell::Powershell);
+            writeln!(
+                w,
+                "            [CompletionResult]::new({name}, {name}, [CompletionResultType]::ParameterValue, {about})"
+            )?;
+        }
+        if let Value::Choices(choices) = node.positional() {
+            for choice in choices {
+                let choice = quote(&choice, Shell::Powershell);
+                writeln!(
+                    w,
+                    "            [CompletionResult]::new({choice}, {choice}, [CompletionResultType]::ParameterValue, {choice})"
+                )?;
+            }
+        }
+        writeln!(w, "            break")?;
+        writeln!(w, "        }}")?;
+    }
+    writeln!(w, "    }})")?;
+    writeln!(
+        w,
+        "    $completions.Where{{ $_.CompletionText -like \"$wordToComplete*\" }} |"
+    )?;
+    writeln!(w, "        Sort-Object -Property ListItemText")?;
+    writeln!(w, "}}")
+}
+
+pub fn run(args: CompletionsArgs) -> eyre::Result<()> {
+    let mut command = Cli::command();
+    // copies the global flags into every subcommand
+    command.build();
+    let nodes = nodes(&command);
+    let mut out = io::stdout().lock();
+    match args.shell {
+        Shell::Bash => bash(&mut out, &nodes)?,
+        Shell::Zsh => zsh(&mut out, &nodes)?,
+        Shell::Fish => fish(&mut out, &nodes)?,
+        Shell::Powershell => powershell(&mut out, &nodes)?,
+    }
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,227 @@
+use std::io::{self, Write};
+
+use color_eyre::eyre::{self, Context};
+use serde_json::json;
+use spy_pet_checker::diff::{diff_runs, Change, FieldChange, GuildDiff, Ignore};
+use spy_pet_checker::merge::parse_results;
+use spy_pet_checker::output::{
+    escape_html, escape_markdown, to_toml, to_yaml, write_csv_row, write_html_page,
+    write_testsuites, Outcome, TestCase, TestSuite,
+};
+use spy_pet_checker::{RunReport, Status};
+
+use crate::cli::{DiffArgs, Format};
+use crate::commands::open_output;
+
+pub fn run(args: DiffArgs) -> eyre::Result<()> {
+    let load = |path: &std::path::Path| -> eyre::Result<RunReport> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("couldn't read {}", path.display()))?;
+        let results =
+            parse_results(&text).with_context(|| format!("couldn't parse {}", path.display()))?;
+        Ok(RunReport {
+            results,
+            ..Default::default()
+        })
+    };
+    let (old, new) = (load(&args.old)?, load(&args.new)?);
+    let diffs = diff_runs(&old, &new, &Ignore::new(args.ignore_field));
+    let mut writer = open_output(args.output.as_deref())?;
+    write_diffs(&mut writer, &args.format, &diffs).context("couldn't write to output")
+}
+
+/// Writes `diffs` as a list of changes, or as JSON
+pub fn write_diffs(w: &mut dyn Write, format: &Format, diffs: &[GuildDiff]) -> io::Result<()> {
+    match format {
+        Format::Plain => write_plain(w, diffs),
+        Format::Json => serde_json::to_writer_pretty(&mut *w, diffs)
+            .map_err(io::Error::from)
+            .and_then(|()| writeln!(w)),
+        Format::Ndjson => diffs.iter().try_for_each(|diff| {
+            serde_json::to_writer(&mut *w, diff)?;
+            writeln!(w)
+        }),
+        Format::Yaml => {
+            let diffs = serde_json::to_value(diffs)?;
+            w.write_all(to_yaml(&json!({ "diffs": diffs })).as_bytes())
+        }
+        Format::Toml => {
+            let diffs = serde_json::to_value(diffs)?;
+            w.write_all(to_toml(&json!({ "diffs": diffs }))?.as_bytes())
+        }
+        Format::Csv => write_csv(w, diffs),
+        Format::Markdown => write_markdown(w, diffs),
+        Format::Html => {
+            let mut plain = Vec::new();
+            write_plain(&mut plain, diffs)?;
+            let plain = String::from_utf8_lossy(&plain);
+            let body = format!("<pre>{}</pre>\n", escape_html(&plain));
+            write_html_page(w, "Changes between two runs", &body)
+        }
+        Format::Junit => write_junit(w, diffs),
+    }
+}
+
+/// A test case per server and backend that changed, which fails when it's
+/// newly compromised
+fn write_junit(w: &mut dyn Write, diffs: &[GuildDiff]) -> io::Result<()> {
+    let cases = diffs
+        .iter()
+        .map(|diff| {
+            let mut plain = Vec::new();
+            write_diff(&mut plain, diff)?;
+            let details = String::from_utf8_lossy(&plain).into_owned();
+            let outcome = match diff.change {
+                Change::NewlyCompromised => Outcome::Failed {
+                    message: "newly compromised".to_owned(),
+                    details,
+                },
+                Change::NewlyClean | Change::Changed => Outcome::Passed,
+            };
+            Ok(TestCase {
+                classname: diff.source.clone(),
+                name: format!("{} (ID: {})", diff.guild_name, diff.guild_id),
+                outcome,
+            })
+        })
+        .collect::<io::Result<_>>()?;
+    let suite = TestSuite {
+        name: "diff".to_owned(),
+        cases,
+    };
+    write_testsuites(w, "spy-pet-checker diff", &[suite], None)
+}
+
+fn write_markdown(w: &mut dyn Write, diffs: &[GuildDiff]) -> io::Result<()> {
+    if diffs.is_empty() {
+        return writeln!(w, "No changes");
+    }
+    for (change, title) in SECTIONS {
+        let diffs: Vec<&GuildDiff> = diffs.iter().filter(|d| d.change == change).collect();
+        if diffs.is_empty() {
+            continue;
+        }
+        writeln!(w, "## {title} ({})", diffs.len())?;
+        writeln!(w)?;
+        for diff in diffs {
+            write!(
+                w,
+                "- **{}** (`{}`) on {}",
+                escape_markdown(&diff.guild_name),
+                diff.guild_id,
+                escape_markdown(&diff.source)
+            )?;
+            if diff.from != diff.to {
+                write!(w, ": {} → {}", status(diff.from), status(diff.to))?;
+            }
+            writeln!(w)?;
+            for change in &diff.changes {
+                let path = match change.path() {
+                    "" => "(whole response)",
+                    path => path,
+                };
+                let change = match change {
+                    FieldChange::Added { value, .. } => format!("added `{value}`"),
+                    FieldChange::Removed { value, .. } => format!("removed `{value}`"),
+                    FieldChange::Changed { from, to, .. } => format!("`{from}` → `{to}`"),
+                    FieldChange::Length { from, to, .. } => format!("{from} → {to} items"),
+                };
+                writeln!(w, "  - {}: {change}", escape_markdown(path))?;
+            }
+        }
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+/// A row per server and backend, with how many fields of the answer changed
+fn write_csv(w: &mut dyn Write, diffs: &[GuildDiff]) -> io::Result<()> {
+    write_csv_row(
+        w,
+        &[
+            "guild_id",
+            "guild_name",
+            "source",
+            "change",
+            "from",
+            "to",
+            "fields_changed",
+        ],
+    )?;
+    for diff in diffs {
+        let change = serde_json::to_value(diff.change)?;
+        write_csv_row(
+            w,
+            &[
+                diff.guild_id.as_str(),
+                &diff.guild_name,
+                &diff.source,
+                change.as_str().unwrap_or_default(),
+                status(diff.from),
+                status(diff.to),
+                &diff.changes.len().to_string(),
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+/// How diffs are grouped, in order
+const SECTIONS: [(Change, &str); 3] = [
+    (Change::NewlyCompromised, "Newly compromised"),
+    (Change::NewlyClean, "Newly clean"),
+    (Change::Changed, "Changed"),
+];
+
+fn status(status: Option<Status>) -> &'static str {
+    status.map_or("not checked", Status::as_str)
+}
+
+fn write_plain(w: &mut dyn Write, diffs: &[GuildDiff]) -> io::Result<()> {
+    if diffs.is_empty() {
+        return writeln!(w, "No changes");
+    }
+    let mut first = true;
+    for (change, title) in SECTIONS {
+        let diffs: Vec<&GuildDiff> = diffs.iter().filter(|d| d.change == change).collect();
+        if diffs.is_empty() {
+            continue;
+        }
+        if !first {
+            writeln!(w)?;
+        }
+        first = false;
+        writeln!(w, "{title} ({}):", diffs.len())?;
+        for diff in diffs {
+            write_diff(w, diff)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_diff(w: &mut dyn Write, diff: &GuildDiff) -> io::Result<()> {
+    write!(
+        w,
+        "  {} (ID: {}) on {}",
+        diff.guild_name, diff.guild_id, diff.source
+    )?;
+    if diff.from != diff.to {
+        write!(w, ": {} -> {}", status(diff.from), status(diff.to))?;
+    }
+    writeln!(w)?;
+    for change in &diff.changes {
+        let path = match change.path() {
+            "" => "(whole response)",
+            path => path,
+        };
+        match change {
+            FieldChange::Added { value, .. } => writeln!(w, "    + {path}: {value}")?,
+            FieldChange::Removed { value, .. } => writeln!(w, "    - {path}: {value}")?,
+            FieldChange::Changed { from, to, .. } => writeln!(w, "    ~ {path}: {from} -> {to}")?,
+            FieldChange::Length { from, to, .. } => {
+                writeln!(w, "    # {path}: {from} -> {to} items")?
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,39 @@
+//! `dump`: downloads a dataset's whole server list into a file, which
+//! `check --local-dataset` then matches indexes against without a request
+//! per server.
+
+use clap::ArgMatches;
+use color_eyre::eyre::{self, Context};
+use spy_pet_checker::build_client;
+use spy_pet_checker::dataset::Dataset;
+
+use crate::cli::{DumpArgs, GlobalArgs};
+use crate::config::{Config, FileConfig};
+
+pub fn run(global: GlobalArgs, args: DumpArgs, matches: &ArgMatches) -> eyre::Result<()> {
+    let file = FileConfig::load(global.config.as_deref())?;
+    let config = Config::resolve(args.check, &global, matches, file);
+    if config.offline {
+        eyre::bail!("dump downloads the list, it doesn't work with --offline");
+    }
+    // the list is the API's, so it goes with the key
+    let client = build_client(&config.check_options()?)?;
+    let runtime = config
+        .build_runtime()
+        .context("couldn't start async runtime")?;
+    let dataset = runtime
+        .block_on(Dataset::download(&client, &args.list_url, args.max_pages))
+        .with_context(|| format!("couldn't download {}", args.list_url))?;
+    if dataset.is_empty() {
+        eyre::bail!("{} has no servers in it, nothing was saved", args.list_url);
+    }
+    dataset
+        .save(&args.path)
+        .with_context(|| format!("couldn't write {}", args.path.display()))?;
+    println!(
+        "{} servers, saved to {}",
+        dataset.len(),
+        args.path.display()
+    );
+    Ok(())
+}
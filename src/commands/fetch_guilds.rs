@@ -0,0 +1,72 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use color_eyre::eyre::{self, bail, Context};
+use spy_pet_checker::discord::{GuildLister, TokenType};
+use spy_pet_checker::oauth;
+use spy_pet_checker::state::StateDir;
+use spy_pet_checker::warnings::Warnings;
+use spy_pet_checker::{build_keyless_client, CheckOptions};
+use tracing::info;
+
+use crate::cli::{FetchGuildsArgs, GlobalArgs};
+use crate::config::{resolve_state_dir, FileConfig};
+use crate::credentials;
+use crate::{dce, package};
+
+pub fn run(global: GlobalArgs, args: FetchGuildsArgs) -> eyre::Result<()> {
+    let warnings = Warnings::default();
+    let guilds = if let Some(root) = &args.from_data_package {
+        package::load_guilds(root)?
+    } else if let Some(pattern) = &args.from_dce {
+        dce::load_guilds(pattern, &warnings)?
+    } else {
+        from_discord(&global, &args)?
+    };
+    info!(servers = guilds.len(), "listed the servers");
+
+    let mut json = serde_json::to_string_pretty(&guilds)?;
+    json.push('\n');
+    match &args.output {
+        Some(path) => std::fs::write(path, json)
+            .with_context(|| format!("couldn't write {}", path.display()))?,
+        None => std::io::stdout()
+            .write_all(json.as_bytes())
+            .context("couldn't write to output")?,
+    }
+    Ok(())
+}
+
+fn from_discord(
+    global: &GlobalArgs,
+    args: &FetchGuildsArgs,
+) -> eyre::Result<BTreeMap<String, String>> {
+    let client = build_keyless_client(&CheckOptions::default())?;
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let lister = match credentials::discord_token(args.token_file.as_deref())? {
+        Some((token, _)) => GuildLister::new(token, args.token_type.into_token_type()),
+        None => {
+            let file = FileConfig::load(global.config.as_deref())?;
+            let path = match resolve_state_dir(global, &file) {
+                Some(root) => Some(StateDir::new(root).oauth_token_path()?),
+                None => None,
+            };
+            let token = match &path {
+                Some(path) => runtime.block_on(oauth::stored_access_token(&client, path))?,
+                None => None,
+            };
+            let Some(token) = token else {
+                bail!(
+                    "--from-discord needs `spy-pet-checker auth` or a Discord token, from --token-file, {} or `spy-pet-checker token set`",
+                    credentials::DISCORD_TOKEN_ENV
+                );
+            };
+            GuildLister::new(token, TokenType::Bearer)
+        }
+    };
+    runtime
+        .block_on(lister.guilds(&client))
+        .context("couldn't list the servers through Discord's API")
+}
@@ -0,0 +1,77 @@
+//! `healthcheck`: asks each backend about one server, so monitoring can tell
+//! the API being down apart from a run going wrong.
+
+use clap::ArgMatches;
+use color_eyre::eyre::{self, Context};
+use futures_util::future::join_all;
+use spy_pet_checker::health::{self, Health, Probe};
+
+use crate::cli::{GlobalArgs, HealthcheckArgs};
+use crate::commands::Exit;
+use crate::config::{Config, FileConfig};
+
+/// The exit code for a backend in this state. 1 and 2 are taken by the
+/// run and its findings.
+fn exit_code(health: Health) -> u8 {
+    match health {
+        Health::Up => 0,
+        Health::Down => 3,
+        Health::RateLimited => 4,
+        Health::Refused => 5,
+        Health::Unexpected => 6,
+    }
+}
+
+fn describe(probe: &Probe) -> String {
+    let health = match probe.health {
+        Health::Up => "up",
+        Health::Down => "down",
+        Health::RateLimited => "rate limited",
+        Health::Refused => "refusing requests",
+        Health::Unexpected => "answering unexpectedly",
+    };
+    let mut line = format!("{}: {health}, {:.0}ms", probe.source, probe.latency_ms);
+    if let Some(status) = probe.status {
+        line.push_str(&format!(" (HTTP {status})"));
+    }
+    if let Some(error) = &probe.error {
+        line.push_str(&format!(": {error}"));
+    }
+    for (name, value) in &probe.headers {
+        if name.contains("ratelimit") || name == "retry-after" {
+            line.push_str(&format!("\n  {name}: {value}"));
+        }
+    }
+    line
+}
+
+pub fn run(global: GlobalArgs, args: HealthcheckArgs, matches: &ArgMatches) -> eyre::Result<()> {
+    let file = FileConfig::load(global.config.as_deref())?;
+    let config = Config::resolve(args.check, &global, matches, file);
+    let options = config.check_options()?;
+    let runtime = config
+        .build_runtime()
+        .context("couldn't start async runtime")?;
+    let probes = runtime.block_on(join_all(
+        options
+            .backends
+            .iter()
+            .map(|backend| health::probe(&**backend, &options, &args.probe_id)),
+    ));
+
+    for probe in &probes {
+        match args.json {
+            true => println!("{}", serde_json::to_string(probe)?),
+            false => println!("{}", describe(probe)),
+        }
+    }
+    // the first backend that isn't up decides
+    match probes.iter().find(|probe| probe.health != Health::Up) {
+        Some(probe) => Err(Exit {
+            code: exit_code(probe.health),
+            message: format!("{} isn't healthy", probe.source),
+        }
+        .into()),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::{HashMap, HashSet};
+
+use color_eyre::eyre::{self, bail, Context};
+use spy_pet_checker::diff::{diff_runs, Ignore};
+use spy_pet_checker::history::History;
+use spy_pet_checker::warnings::Warnings;
+use spy_pet_checker::Status;
+
+use crate::cli::{GlobalArgs, HistoryAction, HistoryArgs};
+use crate::commands::diff::write_diffs;
+use crate::commands::open_output;
+use crate::config::{resolve_state_dir, FileConfig};
+
+pub fn run(global: GlobalArgs, args: HistoryArgs) -> eyre::Result<()> {
+    let file = FileConfig::load(global.config.as_deref())?;
+    file.warn_unknown(&Warnings::default());
+    let Some(state_dir) = resolve_state_dir(&global, &file) else {
+        bail!("run history is unavailable with --no-state");
+    };
+    let history = History::new(spy_pet_checker::state::StateDir::new(state_dir));
+
+    let list = HistoryAction::List {
+        since: None,
+        until: None,
+    };
+    match args.action.unwrap_or(list) {
+        HistoryAction::List { since, until } => {
+            let ids = history.run_ids().context("couldn't list runs")?;
+            if ids.is_empty() {
+                println!("No runs recorded yet");
+                return Ok(());
+            }
+
+            println!(
+                "{:<22} {:<20} {:>8} {:>12} {:>7}",
+                "RUN", "DATE", "SERVERS", "COMPROMISED", "ERRORS"
+            );
+            for id in ids {
+                match history.load(&id) {
+                    Ok(record)
+                        if since.is_some_and(|since| record.started_at < since)
+                            || until.is_some_and(|until| record.started_at >= until) => {}
+                    Ok(record) => println!(
+                        "{:<22} {:<20} {:>8} {:>12} {:>7}",
+                        record.run_id,
+                        record.started_at.format("%Y-%m-%d %H:%M:%S"),
+                        record.index_size,
+                        record.report.compromised().count(),
+                        record.report.failed.len(),
+                    ),
+                    Err(err) => println!("{id:<22} (unreadable: {err})"),
+                }
+            }
+        }
+        HistoryAction::Show {
+            run_id,
+            format,
+            output,
+        } => {
+            let record = history
+                .load(&run_id)
+                .with_context(|| format!("couldn't load run {run_id}"))?;
+            let mut writer = open_output(output.as_deref())?;
+            format
+                .formatter()
+                .write_results(&mut writer, &record.report)
+                .context("couldn't write to output")?;
+        }
+        HistoryAction::Diff {
+            old,
+            new,
+            format,
+            output,
+            ignore_field,
+        } => {
+            let load = |run_id: &str| {
+                history
+                    .load(run_id)
+                    .with_context(|| format!("couldn't load run {run_id}"))
+            };
+            let (old, new) = (load(&old)?, load(&new)?);
+            let diffs = diff_runs(&old.report, &new.report, &Ignore::new(ignore_field));
+            let mut writer = open_output(output.as_deref())?;
+            write_diffs(&mut writer, &format, &diffs).context("couldn't write to output")?;
+        }
+        HistoryAction::Server { id, changes } => {
+            let sightings = history.sightings(&id).context("couldn't read the runs")?;
+            if sightings.is_empty() {
+                println!("No stored run checked {id}");
+                return Ok(());
+            }
+
+            println!("{:<22} {:<20} {:<14} SOURCE", "RUN", "DATE", "STATUS");
+            let mut last: HashMap<&str, Status> = HashMap::new();
+            for sighting in &sightings {
+                let status = match &sighting.outcome {
+                    Ok(status) => {
+                        let changed = last.insert(&sighting.source, *status) != Some(*status);
+                        if changes && !changed {
+                            continue;
+                        }
+                        status.to_string()
+                    }
+                    // a failed check doesn't say anything new
+                    Err(_) if changes => continue,
+                    Err(message) => format!("error: {message}"),
+                };
+                println!(
+                    "{:<22} {:<20} {:<14} {}",
+                    sighting.run_id,
+                    sighting.started_at.format("%Y-%m-%d %H:%M:%S"),
+                    status,
+                    sighting.source,
+                );
+            }
+
+            let runs: HashSet<&str> = sightings.iter().map(|s| s.run_id.as_str()).collect();
+            match sightings
+                .iter()
+                .find(|s| matches!(s.outcome, Ok(Status::Compromised)))
+            {
+                Some(first) => println!(
+                    "First found compromised in run {} on {}",
+                    first.run_id,
+                    first.started_at.format("%Y-%m-%d")
+                ),
+                None => println!("Not found compromised in {} run(s)", runs.len()),
+            }
+        }
+        HistoryAction::Prune { keep } => {
+            let removed = history.prune(keep).context("couldn't prune runs")?;
+            println!("Removed {} run(s)", removed.len());
+        }
+    }
+
+    Ok(())
+}
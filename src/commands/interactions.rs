@@ -0,0 +1,171 @@
+//! The bot's interactions endpoint, which answers `/spy-pet-check` with the
+//! result for the server it's used in.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use axum::{Json, Router};
+use color_eyre::eyre::{self, Context};
+use futures_util::StreamExt;
+use reqwest::Client;
+use ring::signature::{UnparsedPublicKey, ED25519};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use spy_pet_checker::discord::{result_message, Bot, SLASH_COMMAND};
+use spy_pet_checker::{check_stream, CheckOptions};
+use tracing::{info, warn};
+
+/// Only the one who used the command sees the answer
+const EPHEMERAL: u64 = 1 << 6;
+
+struct Endpoint {
+    bot: Bot,
+    client: Client,
+    application_id: String,
+    public_key: [u8; 32],
+    options: CheckOptions,
+}
+
+#[derive(Deserialize)]
+struct Interaction {
+    #[serde(rename = "type")]
+    kind: u8,
+    token: String,
+    guild_id: Option<String>,
+    data: Option<CommandData>,
+}
+
+#[derive(Deserialize)]
+struct CommandData {
+    name: String,
+}
+
+pub(crate) fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Whether Discord signed `body`, as it does every interaction it sends
+fn signed(public_key: &[u8; 32], headers: &HeaderMap, body: &[u8]) -> bool {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    let (Some(signature), Some(timestamp)) = (
+        header("x-signature-ed25519").and_then(decode_hex),
+        header("x-signature-timestamp"),
+    ) else {
+        return false;
+    };
+    let mut message = timestamp.as_bytes().to_vec();
+    message.extend_from_slice(body);
+    UnparsedPublicKey::new(&ED25519, public_key)
+        .verify(&message, &signature)
+        .is_ok()
+}
+
+async fn interaction(
+    State(endpoint): State<Arc<Endpoint>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<Value>, StatusCode> {
+    if !signed(&endpoint.public_key, &headers, &body) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    let interaction: Interaction =
+        serde_json::from_slice(&body).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let command = interaction.data.as_ref().map(|data| data.name.as_str());
+    match (interaction.kind, command) {
+        // the check Discord makes of the endpoint
+        (1, _) => Ok(Json(json!({ "type": 1 }))),
+        (2, Some(SLASH_COMMAND)) => {
+            let Some(guild_id) = interaction.guild_id else {
+                return Ok(Json(json!({
+                    "type": 4,
+                    "data": { "content": "This only works in a server.", "flags": EPHEMERAL },
+                })));
+            };
+            tokio::spawn(answer(endpoint, guild_id, interaction.token));
+            // a check can take longer than the 3 seconds Discord waits
+            Ok(Json(json!({ "type": 5, "data": { "flags": EPHEMERAL } })))
+        }
+        _ => Err(StatusCode::BAD_REQUEST),
+    }
+}
+
+async fn answer(endpoint: Arc<Endpoint>, guild_id: String, token: String) {
+    let Endpoint {
+        bot,
+        client,
+        application_id,
+        options,
+        ..
+    } = &*endpoint;
+    let name = match bot.guild_name(client, &guild_id).await {
+        Ok(name) => name,
+        Err(err) => {
+            warn!(guild = guild_id, %err, "couldn't look up the server's name");
+            String::new()
+        }
+    };
+    info!(guild = guild_id, "checking for /{SLASH_COMMAND}");
+    let mut results = check_stream([(guild_id.clone(), name)], options);
+    let message = match results.next().await {
+        Some(Ok(result)) => result_message(&result),
+        Some(Err(failed)) => json!({
+            "content": format!("Couldn't check the server: {}", failed.message),
+        }),
+        None => json!({ "content": "The checker is shutting down, try again later." }),
+    };
+    if let Err(err) = bot.answer(client, application_id, &token, &message).await {
+        warn!(guild = guild_id, %err, "couldn't answer /{SLASH_COMMAND}");
+    }
+}
+
+/// Registers the command, then answers it on `addr` until interrupted
+pub async fn serve(
+    bot: Bot,
+    client: Client,
+    addr: SocketAddr,
+    public_key: [u8; 32],
+    options: CheckOptions,
+) -> eyre::Result<()> {
+    let application_id = bot
+        .application_id(&client)
+        .await
+        .context("couldn't look up the bot's application")?;
+    bot.register_command(&client, &application_id)
+        .await
+        .with_context(|| format!("couldn't register /{SLASH_COMMAND}"))?;
+    let cancel = options.cancel.clone();
+    let endpoint = Endpoint {
+        bot,
+        client,
+        application_id,
+        public_key,
+        options,
+    };
+    let app = Router::new()
+        .route("/interactions", post(interaction))
+        .with_state(Arc::new(endpoint));
+
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("couldn't listen on {addr}"))?;
+    info!("answering /{SLASH_COMMAND} on {addr}");
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                warn!("interrupted, shutting down");
+            }
+            cancel.cancel();
+        })
+        .await
+        .context("interactions endpoint failed")
+}
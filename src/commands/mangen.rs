@@ -0,0 +1,185 @@
+//! The spy-pet-checker(1) man page, written from the command line definition
+//! itself so it documents every flag of this build.
+
+use std::io::{self, Write};
+
+use clap::{Arg, Command, CommandFactory};
+use color_eyre::eyre;
+
+use crate::cli::Cli;
+
+const BIN: &str = "spy-pet-checker";
+
+/// `text` safe in roff: backslashes and hyphens escaped, and no line taken
+/// for a request
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\e")
+        .replace('-', "\\-")
+        .lines()
+        .map(|line| match line.starts_with(['.', '\'']) {
+            true => format!("\\&{line}"),
+            false => line.to_owned(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn bold(text: &str) -> String {
+    format!("\\fB{}\\fR", escape(text))
+}
+
+fn italic(text: &str) -> String {
+    format!("\\fI{}\\fR", escape(text))
+}
+
+/// The option's names as they're written, e.g. `-o, --output=PATH`
+fn names(arg: &Arg) -> String {
+    let mut names: Vec<String> = Vec::new();
+    names.extend(arg.get_short().map(|short| bold(&format!("-{short}"))));
+    for long in arg.get_long_and_visible_aliases().unwrap_or_default() {
+        names.push(bold(&format!("--{long}")));
+    }
+    let mut names = names.join(", ");
+    if arg.get_action().takes_values() {
+        let value = match arg.get_value_names() {
+            Some([name, ..]) => name.to_string(),
+            _ => arg.get_id().as_str().to_uppercase(),
+        };
+        names.push_str(&format!("={}", italic(&value)));
+    }
+    names
+}
+
+/// What `--help` says about `arg`, with its defaults, values and variable
+fn description(arg: &Arg) -> String {
+    let help = arg
+        .get_long_help()
+        .or(arg.get_help())
+        .map(|help| help.to_string())
+        .unwrap_or_default();
+    let mut notes: Vec<String> = Vec::new();
+    let defaults: Vec<String> = arg
+        .get_default_values()
+        .iter()
+        .map(|value| value.to_string_lossy().into_owned())
+        .collect();
+    if !defaults.is_empty() && arg.get_action().takes_values() {
+        notes.push(format!("default: {}", defaults.join(", ")));
+    }
+    let values: Vec<String> = arg
+        .get_possible_values()
+        .into_iter()
+        .filter(|value| !value.is_hide_set())
+        .map(|value| value.get_name().to_owned())
+        .collect();
+    if !values.is_empty() && arg.get_action().takes_values() {
+        notes.push(format!("possible values: {}", values.join(", ")));
+    }
+    if let Some(env) = arg.get_env() {
+        notes.push(format!("env: {}", env.to_string_lossy()));
+    }
+    let mut description = escape(&help);
+    if !notes.is_empty() {
+        let notes: Vec<String> = notes.iter().map(|note| format!("[{note}]")).collect();
+        description.push_str(&format!("\n.br\n{}", escape(&notes.join(" "))));
+    }
+    description
+}
+
+fn write_options<'a>(w: &mut dyn Write, args: impl Iterator<Item = &'a Arg>) -> io::Result<()> {
+    for arg in args.filter(|arg| !arg.is_hide_set()) {
+        writeln!(w, ".TP")?;
+        if arg.is_positional() {
+            let name = match arg.get_value_names() {
+                Some([name, ..]) => name.to_string(),
+                _ => arg.get_id().as_str().to_uppercase(),
+            };
+            writeln!(w, "{}", italic(&name))?;
+        } else {
+            writeln!(w, "{}", names(arg))?;
+        }
+        writeln!(w, "{}", description(arg))?;
+    }
+    Ok(())
+}
+
+fn about(command: &Command) -> String {
+    command
+        .get_long_about()
+        .or(command.get_about())
+        .map(|about| about.to_string())
+        .unwrap_or_default()
+}
+
+/// A subsection for `command` and each of its subcommands, with their own
+/// options; the top level's global ones, `globals`, are in OPTIONS
+fn write_commands(
+    w: &mut dyn Write,
+    globals: &[&str],
+    path: &[&str],
+    command: &Command,
+) -> io::Result<()> {
+    for sub in command.get_subcommands() {
+        if sub.is_hide_set() || sub.get_name() == "help" {
+            continue;
+        }
+        let mut path = path.to_vec();
+        path.push(sub.get_name());
+        writeln!(w, ".SS \"{}\"", escape(&path.join(" ")))?;
+        writeln!(w, "{}", escape(&about(sub)))?;
+        let own = sub
+            .get_arguments()
+            .filter(|arg| !globals.contains(&arg.get_id().as_str()));
+        write_options(w, own)?;
+        write_commands(w, globals, &path, sub)?;
+    }
+    Ok(())
+}
+
+fn write_page(w: &mut dyn Write, command: &Command) -> io::Result<()> {
+    let version = env!("CARGO_PKG_VERSION");
+    writeln!(
+        w,
+        ".TH {} 1 \"\" \"{} {version}\"",
+        escape(&BIN.to_uppercase()),
+        escape(BIN)
+    )?;
+    writeln!(w, ".SH NAME")?;
+    writeln!(w, "{} \\- {}", escape(BIN), escape(&about(command)))?;
+    writeln!(w, ".SH SYNOPSIS")?;
+    writeln!(
+        w,
+        "{} [{}] [{}] [{}]",
+        bold(BIN),
+        italic("OPTIONS"),
+        italic("COMMAND"),
+        italic("ARGS")
+    )?;
+    writeln!(w, ".SH DESCRIPTION")?;
+    writeln!(
+        w,
+        "Without a command, runs {} with the options given to it.",
+        bold("check")
+    )?;
+    writeln!(w, ".SH OPTIONS")?;
+    writeln!(w, "These apply to every command.")?;
+    let globals: Vec<&Arg> = command
+        .get_arguments()
+        .filter(|arg| arg.is_global_set())
+        .collect();
+    write_options(w, globals.iter().copied())?;
+    let globals: Vec<&str> = globals.iter().map(|arg| arg.get_id().as_str()).collect();
+    writeln!(w, ".SH COMMANDS")?;
+    write_commands(w, &globals, &[], command)?;
+    writeln!(w, ".SH VERSION")?;
+    writeln!(w, "v{}", escape(version))
+}
+
+pub fn run() -> eyre::Result<()> {
+    let mut command = Cli::command();
+    command.build();
+    let mut out = io::stdout().lock();
+    write_page(&mut out, &command)?;
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use color_eyre::eyre::{self, bail, Context};
+use spy_pet_checker::merge::{merge, parse_failed, parse_results, still_failed};
+use spy_pet_checker::RunReport;
+use tracing::{error, info};
+
+use crate::cli::MergeArgs;
+use crate::commands::open_output;
+
+pub fn run(args: MergeArgs) -> eyre::Result<()> {
+    let mut inputs = Vec::new();
+    let mut failed = Vec::new();
+    let mut invalid = 0;
+
+    for path in &args.files {
+        let parsed = std::fs::read_to_string(path)
+            .map_err(eyre::Report::from)
+            .and_then(|text| {
+                let results = parse_results(&text)?;
+                Ok((results, parse_failed(&text)))
+            });
+        match parsed {
+            Ok((results, errors)) => {
+                info!(path=%path.display(), "read {} results", results.len());
+                inputs.push((path.display().to_string(), results));
+                failed.extend(errors);
+            }
+            Err(err) if args.skip_invalid => {
+                error!(path=%path.display(), %err, "skipping invalid input");
+                invalid += 1;
+            }
+            Err(err) => {
+                return Err(err).with_context(|| {
+                    format!(
+                        "couldn't read {} (use --skip-invalid to ignore it)",
+                        path.display()
+                    )
+                })
+            }
+        }
+    }
+
+    if inputs.is_empty() {
+        bail!("no valid inputs to merge");
+    }
+
+    let (results, conflicts) = merge(inputs);
+    // a check that failed in one input but was answered in another is done
+    let failed = still_failed(failed, &results);
+    let report = RunReport {
+        results,
+        failed,
+        ..Default::default()
+    };
+
+    let mut writer = open_output(args.output.as_deref())?;
+    args.format
+        .formatter()
+        .write_results(&mut writer, &report)
+        .context("couldn't write to output")?;
+
+    if invalid > 0 {
+        eprintln!("Skipped inputs: {invalid}");
+    }
+    if !report.failed.is_empty() {
+        eprintln!("Failed checks: {}", report.failed.len());
+    }
+    if !conflicts.is_empty() {
+        eprintln!("Conflicts: {}", conflicts.len());
+    }
+
+    Ok(())
+}
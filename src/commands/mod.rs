@@ -0,0 +1,356 @@
+use std::fs::{File, OpenOptions};
+#[cfg(feature = "encrypt")]
+use std::io::IsTerminal;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "sign")]
+use std::sync::Arc;
+
+use color_eyre::eyre::{self, Context};
+#[cfg(feature = "encrypt")]
+use spy_pet_checker::encrypt::Sealer;
+use spy_pet_checker::output::{Formatter, OutputTemplate, Templated};
+#[cfg(feature = "sign")]
+use spy_pet_checker::sign::SigningKey;
+use tracing::warn;
+
+use crate::cli::{Format, FormatOptions, OutputMode};
+use crate::config::Config;
+
+pub mod auth;
+pub mod bot;
+pub mod cache;
+pub mod check;
+pub mod completions;
+pub mod diff;
+pub mod dump;
+pub mod fetch_guilds;
+pub mod healthcheck;
+pub mod history;
+#[cfg(feature = "serve")]
+pub mod interactions;
+pub mod mangen;
+pub mod merge;
+pub mod report;
+pub mod scan_bots;
+#[cfg(feature = "self-update")]
+pub mod self_update;
+#[cfg(feature = "serve")]
+pub mod serve;
+pub mod stats;
+pub mod token;
+pub mod validate;
+#[cfg(feature = "sign")]
+pub mod verify;
+pub mod watch;
+
+/// Ends the process with `code` instead of the usual 1, once `main` has
+/// cleaned up
+#[derive(Debug, thiserror::Error)]
+#[error("{message}")]
+pub struct Exit {
+    pub code: u8,
+    pub message: String,
+}
+
+/// Resolves on Ctrl-C, or SIGTERM where there is one
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut term) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = term.recv() => {}
+                }
+                return;
+            }
+            Err(err) => warn!(%err, "couldn't listen for SIGTERM"),
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Where a report goes: stdout or a file. With [`OutputMode::Atomic`] the
+/// file is a temporary one next to the real one, which [`Output::commit`]
+/// renames over it; dropped without that, it's removed again. With a
+/// [`Signer`] the report is held until it's complete and signed, and with a
+/// [`Sealer`] everything is encrypted on the way.
+pub struct Output {
+    file: Option<File>,
+    /// The temporary file and the one it replaces
+    rename: Option<(PathBuf, PathBuf)>,
+    #[cfg(feature = "sign")]
+    signer: Option<Signer>,
+    #[cfg(feature = "encrypt")]
+    sealer: Option<Sealer>,
+}
+
+/// `--sign`: the key, and where the signature goes
+#[cfg(feature = "sign")]
+struct Signer {
+    key: Arc<SigningKey>,
+    /// `None` when the report is JSON, which the signature wraps instead
+    signature: Option<PathBuf>,
+    report: Vec<u8>,
+}
+
+impl Output {
+    #[cfg(feature = "encrypt")]
+    fn write_through(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        match &mut self.file {
+            Some(file) => file.write_all(buf),
+            None => std::io::stdout().write_all(buf),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        #[cfg(feature = "sign")]
+        if let Some(signer) = &mut self.signer {
+            signer.report.extend_from_slice(buf);
+            return Ok(buf.len());
+        }
+        #[cfg(feature = "encrypt")]
+        if let Some(sealer) = &mut self.sealer {
+            let sealed = sealer.update(buf).map_err(std::io::Error::other)?;
+            self.write_through(&sealed)?;
+            return Ok(buf.len());
+        }
+        match &mut self.file {
+            Some(file) => file.write(buf),
+            None => std::io::stdout().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.file {
+            Some(file) => file.flush(),
+            None => std::io::stdout().flush(),
+        }
+    }
+}
+
+impl Output {
+    /// Finishes the report, putting it in place if it was written aside
+    pub fn commit(mut self) -> eyre::Result<()> {
+        #[cfg(feature = "sign")]
+        let signature = match self.signer.take() {
+            Some(Signer {
+                key,
+                signature: Some(path),
+                report,
+            }) => {
+                self.write_all(&report)
+                    .context("couldn't write to output")?;
+                Some((path, key.detached(&report)))
+            }
+            Some(Signer { key, report, .. }) => {
+                writeln!(self, "{}", key.embedded(&report)).context("couldn't write to output")?;
+                None
+            }
+            None => None,
+        };
+        #[cfg(feature = "encrypt")]
+        if let Some(sealer) = self.sealer.take() {
+            let last = sealer.finish().context("couldn't encrypt the report")?;
+            self.write_through(&last)
+                .context("couldn't write to output")?;
+        }
+        self.flush().context("couldn't write to output")?;
+        if let (Some(file), Some((temp, path))) = (&self.file, self.rename.take()) {
+            file.sync_all()
+                .with_context(|| format!("couldn't write {}", temp.display()))?;
+            std::fs::rename(&temp, &path).with_context(|| {
+                format!("couldn't move {} to {}", temp.display(), path.display())
+            })?;
+        }
+        #[cfg(feature = "sign")]
+        if let Some((path, signature)) = signature {
+            std::fs::write(&path, format!("{signature}\n"))
+                .with_context(|| format!("couldn't write {}", path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Output {
+    fn drop(&mut self) {
+        if let Some((temp, _)) = self.rename.take() {
+            let _ = std::fs::remove_file(temp);
+        }
+    }
+}
+
+/// Where the detached signature of `report` goes: next to it, with `.sig`
+/// added to the name
+#[cfg(feature = "sign")]
+pub fn signature_path(report: &Path) -> PathBuf {
+    let mut path = report.as_os_str().to_owned();
+    path.push(".sig");
+    PathBuf::from(path)
+}
+
+/// Fails if `--encrypt` or `--sign` is set but the report couldn't be
+/// encrypted or signed, so a run can find out before it starts rather than
+/// once it's done
+pub fn check_report(config: &Config) -> eyre::Result<()> {
+    if config.output_mode == OutputMode::Append {
+        if !config.encrypt.is_empty() {
+            eyre::bail!("--encrypt can't add to the end of a file, drop --append");
+        }
+        if config.sign.is_some() {
+            eyre::bail!("--sign can't add to the end of a file, drop --append");
+        }
+    }
+    if !config.encrypt.is_empty() {
+        #[cfg(not(feature = "encrypt"))]
+        eyre::bail!("--encrypt needs a build with the encrypt feature");
+        #[cfg(feature = "encrypt")]
+        config.recipients()?;
+    }
+    if config.sign.is_some() {
+        #[cfg(not(feature = "sign"))]
+        eyre::bail!("--sign needs a build with the sign feature");
+        #[cfg(feature = "sign")]
+        config.signing_key()?;
+    }
+    Ok(())
+}
+
+/// Opens `path` for the report of a check, the way `--append`, `--atomic`,
+/// `--sign` and `--encrypt` say. Those are checked before anything is
+/// opened, so a bad recipient or key leaves an earlier report in place. A
+/// `json` report is signed inside, any other next to it.
+pub fn open_report(config: &Config, path: Option<&Path>, json: bool) -> eyre::Result<Output> {
+    check_report(config)?;
+    #[cfg(feature = "sign")]
+    let signer = match config.signing_key()? {
+        Some(key) => {
+            let signature = match (json, path) {
+                (true, _) => None,
+                (false, Some(path)) => Some(signature_path(path)),
+                (false, None) => eyre::bail!(
+                    "--sign writes the signature next to the report, give that an --output"
+                ),
+            };
+            Some(Signer {
+                key,
+                signature,
+                report: Vec::new(),
+            })
+        }
+        None => None,
+    };
+    #[cfg(not(feature = "sign"))]
+    let _ = json;
+
+    #[cfg(feature = "encrypt")]
+    let output = match config.recipients()? {
+        Some(recipients) => {
+            if path.is_none() && std::io::stdout().is_terminal() {
+                eyre::bail!("--encrypt won't write to a terminal, give an --output or redirect it");
+            }
+            let (sealer, header) =
+                Sealer::new(&recipients).context("couldn't encrypt the report")?;
+            let mut output = open_output_as(path, config.output_mode)?;
+            output
+                .write_through(&header)
+                .context("couldn't write to output")?;
+            output.sealer = Some(sealer);
+            output
+        }
+        None => open_output_as(path, config.output_mode)?,
+    };
+    #[cfg(not(feature = "encrypt"))]
+    let output = open_output_as(path, config.output_mode)?;
+
+    #[cfg(feature = "sign")]
+    let mut output = output;
+    #[cfg(feature = "sign")]
+    {
+        output.signer = signer;
+    }
+    Ok(output)
+}
+
+/// Opens `path` for writing the report the way `mode` says, or stdout if
+/// `None`
+pub fn open_output_as(path: Option<&Path>, mode: OutputMode) -> eyre::Result<Output> {
+    let Some(path) = path else {
+        return Ok(Output {
+            file: None,
+            rename: None,
+            #[cfg(feature = "sign")]
+            signer: None,
+            #[cfg(feature = "encrypt")]
+            sealer: None,
+        });
+    };
+    let open = |path: &Path, options: &mut OpenOptions| {
+        options
+            .open(path)
+            .with_context(|| format!("couldn't open {}", path.display()))
+    };
+    let (file, rename) = match mode {
+        OutputMode::Replace => (
+            open(
+                path,
+                OpenOptions::new().write(true).create(true).truncate(true),
+            )?,
+            None,
+        ),
+        OutputMode::Append => (
+            open(path, OpenOptions::new().append(true).create(true))?,
+            None,
+        ),
+        OutputMode::Atomic => {
+            // in the same directory, since a rename can't cross filesystems
+            let name = path
+                .file_name()
+                .unwrap_or(path.as_os_str())
+                .to_string_lossy();
+            let temp = path.with_file_name(format!(".{name}.{}.tmp", std::process::id()));
+            let file = open(&temp, OpenOptions::new().write(true).create_new(true))?;
+            (file, Some((temp, path.to_owned())))
+        }
+    };
+    Ok(Output {
+        file: Some(file),
+        rename,
+        #[cfg(feature = "sign")]
+        signer: None,
+        #[cfg(feature = "encrypt")]
+        sealer: None,
+    })
+}
+
+/// Opens `path` for writing the report, creating it if needed, or stdout if
+/// `None`
+pub fn open_output(path: Option<&Path>) -> eyre::Result<Output> {
+    open_output_as(path, OutputMode::Replace)
+}
+
+/// Opens `path` for appending, creating it if needed, or stdout if `None`
+pub fn append_output(path: Option<&Path>) -> eyre::Result<Output> {
+    open_output_as(path, OutputMode::Append)
+}
+
+/// The formatter for `format`, or for the template at `template` when set
+pub fn formatter(
+    format: &Format,
+    options: FormatOptions,
+    template: Option<&Path>,
+) -> eyre::Result<Box<dyn Formatter>> {
+    let Some(path) = template else {
+        return Ok(format.adjusted_formatter(options));
+    };
+    let source = std::fs::read_to_string(path)
+        .with_context(|| format!("couldn't read template {}", path.display()))?;
+    let template = OutputTemplate::parse(&source)
+        .with_context(|| format!("invalid template {}", path.display()))?;
+    Ok(Box::new(Templated { template }))
+}
@@ -0,0 +1,38 @@
+use color_eyre::eyre::{self, Context};
+use spy_pet_checker::merge::parse_results;
+use spy_pet_checker::output::sort_run;
+use spy_pet_checker::RunReport;
+
+use crate::cli::{FormatOptions, GlobalArgs, ReportArgs};
+use crate::commands::{formatter, open_output};
+
+pub fn run(global: GlobalArgs, args: ReportArgs) -> eyre::Result<()> {
+    let text = std::fs::read_to_string(&args.file)
+        .with_context(|| format!("couldn't read {}", args.file.display()))?;
+    let mut results =
+        parse_results(&text).with_context(|| format!("couldn't parse {}", args.file.display()))?;
+    if args.only_compromised {
+        results.retain(|result| result.is_compromised());
+    }
+    let options = FormatOptions {
+        group_by: args.group_by,
+        json_compact: args.json_compact,
+        include_clean: args.include_clean,
+        details: args.details,
+        color: args.output.is_none() && global.color.enabled(&std::io::stdout()),
+        lang: global.lang(),
+    };
+    let mut formatter = formatter(&args.format, options, args.template.as_deref())?;
+    let mut report = RunReport {
+        results,
+        ..Default::default()
+    };
+    if let Some(by) = args.sort_by {
+        sort_run(&mut report, by.into_output());
+    }
+
+    let mut writer = open_output(args.output.as_deref())?;
+    formatter
+        .write_results(&mut writer, &report)
+        .context("couldn't write to output")
+}
@@ -0,0 +1,123 @@
+//! `scan-bots`: reads the member lists of the servers a bot is in, looking
+//! for known scraper bots in them right now rather than past scraping in a
+//! dataset.
+
+use clap::ArgMatches;
+use color_eyre::eyre::{self, Context};
+use futures_util::stream::{self, StreamExt};
+use serde::Serialize;
+use spy_pet_checker::build_keyless_client;
+use spy_pet_checker::members::{BotList, MemberScan};
+use tracing::{info, warn};
+
+use crate::cli::{GlobalArgs, ScanBotsArgs, TokenType};
+use crate::commands::check::load_guilds;
+use crate::commands::Exit;
+use crate::config::{Config, FileConfig};
+
+/// A line of `--json`
+#[derive(Serialize)]
+struct Line<'a> {
+    guild_id: &'a str,
+    guild_name: &'a str,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    scan: Option<&'a MemberScan>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+pub fn run(global: GlobalArgs, args: ScanBotsArgs, matches: &ArgMatches) -> eyre::Result<()> {
+    let file = FileConfig::load(global.config.as_deref())?;
+    let mut config = Config::resolve(args.check, &global, matches, file);
+    let runtime = config
+        .build_runtime()
+        .context("couldn't start async runtime")?;
+    if args.update_list {
+        let Some(state) = config.state() else {
+            eyre::bail!(
+                "--update-list keeps the list in the state directory, which --no-state turns off"
+            );
+        };
+        let path = state.known_bots_path()?;
+        let client = build_keyless_client(&config.check_options()?)?;
+        let bots = runtime.block_on(BotList::update(&client, &args.list_url, &path))?;
+        println!(
+            "{} known scraper bots, saved to {}",
+            bots.len(),
+            path.display()
+        );
+        return Ok(());
+    }
+
+    // the servers the bot is in, unless given
+    config.from_discord = config.ids.is_empty();
+    config.token_type = TokenType::Bot;
+    config.scan_members = true;
+    let scanner = config.member_scanner()?.expect("--scan-members is on");
+    let client = build_keyless_client(&config.check_options()?)?;
+    let (guilds, _) = runtime.block_on(load_guilds(&config, false))?;
+    config.warnings.check()?;
+    info!(servers = guilds.len(), "scanning member lists");
+
+    let mut scans: Vec<_> = runtime.block_on(
+        stream::iter(guilds)
+            .map(|(id, name)| async {
+                let scan = scanner.scan(&client, &id).await;
+                (id, name, scan)
+            })
+            .buffer_unordered(config.concurrency.max(1))
+            .collect(),
+    );
+    scans.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let (mut hosting, mut skipped, mut failed) = (0, 0, 0);
+    for (id, name, scan) in &scans {
+        match scan {
+            Ok(MemberScan::Scanned { bots, .. }) if !bots.is_empty() => {
+                hosting += 1;
+                if !args.json {
+                    let bots: Vec<String> = bots
+                        .iter()
+                        .map(|bot| format!("{} ({})", bot.label, bot.id))
+                        .collect();
+                    println!("{name} (ID: {id}) hosts a scraper: {}", bots.join(", "));
+                }
+            }
+            Ok(MemberScan::Scanned { .. }) => {}
+            Ok(MemberScan::Skipped { reason }) => {
+                skipped += 1;
+                warn!(id, "{name} wasn't scanned: {reason}");
+            }
+            Err(err) => {
+                failed += 1;
+                warn!(id, %err, "couldn't scan {name}");
+            }
+        }
+        if args.json {
+            let line = Line {
+                guild_id: id,
+                guild_name: name,
+                scan: scan.as_ref().ok(),
+                error: scan.as_ref().err().map(ToString::to_string),
+            };
+            println!("{}", serde_json::to_string(&line)?);
+        }
+    }
+    eprintln!(
+        "Scanned {} of {} servers: {hosting} host a known scraper bot",
+        scans.len() - skipped - failed,
+        scans.len()
+    );
+
+    if hosting > 0 {
+        return Err(Exit {
+            code: 2,
+            message: format!("{hosting} servers host a known scraper bot"),
+        }
+        .into());
+    }
+    if failed > 0 {
+        eyre::bail!("couldn't scan {failed} servers");
+    }
+    Ok(())
+}
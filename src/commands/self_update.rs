@@ -0,0 +1,41 @@
+use clap::ArgMatches;
+use color_eyre::eyre::{self, Context};
+
+use crate::cli::{CheckArgs, GlobalArgs, SelfUpdateArgs};
+use crate::config::{Config, FileConfig};
+use crate::update::{self, current_version};
+
+pub fn run(global: GlobalArgs, args: SelfUpdateArgs, matches: &ArgMatches) -> eyre::Result<()> {
+    let file = FileConfig::load(global.config.as_deref())?;
+    let check = CheckArgs {
+        request: args.request,
+        ..CheckArgs::defaults().context("invalid check setting")?
+    };
+    let config = Config::resolve(check, &global, matches, file);
+    let options = config.check_options()?;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("couldn't start async runtime")?;
+
+    runtime.block_on(async {
+        let client = update::client(&options)?;
+        let release = update::latest_release(&client).await?;
+        let latest = release.version()?;
+        let current = current_version();
+
+        if latest <= current {
+            println!("spy-pet-checker {current} is up to date");
+            return Ok(());
+        }
+        if args.check {
+            println!("spy-pet-checker {latest} is available (running {current})");
+            return Ok(());
+        }
+
+        update::install(&client, &release).await?;
+        println!("Updated spy-pet-checker from {current} to {latest}");
+        Ok(())
+    })
+}
@@ -0,0 +1,291 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chrono::Utc;
+use clap::ArgMatches;
+use color_eyre::eyre::{self, Context};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use spy_pet_checker::index;
+use spy_pet_checker::{check_guilds, check_stream, CheckOptions, Response, RunReport, Semaphore};
+use tokio::sync::OwnedSemaphorePermit;
+use tower::limit::ConcurrencyLimitLayer;
+use tracing::{info, warn};
+
+use crate::cli::{CheckArgs, GlobalArgs, ServeArgs};
+use crate::config::{Config, FileConfig};
+
+/// Finished runs kept for `GET /results/{run_id}`, oldest dropped first
+const KEPT_RUNS: usize = 100;
+
+type ApiError = (StatusCode, Json<Value>);
+
+fn api_error(status: StatusCode, error: &str) -> ApiError {
+    (status, Json(json!({ "error": error })))
+}
+
+struct Server {
+    options: CheckOptions,
+    max_guilds: usize,
+    /// One permit per run that may be going, so `?wait=false` can't pile
+    /// up background runs
+    run_slots: Arc<Semaphore>,
+    runs: Mutex<Runs>,
+}
+
+/// Runs by ID: `None` while they're going, their report once finished
+#[derive(Default)]
+struct Runs {
+    finished: VecDeque<String>,
+    reports: HashMap<String, Option<Arc<RunReport>>>,
+}
+
+impl Server {
+    fn start(self: &Arc<Self>, permit: OwnedSemaphorePermit) -> Started {
+        let now = Utc::now().format("%Y%m%dT%H%M%SZ");
+        // 128 bits from the system's secure randomness, so a run can't be
+        // looked up by anyone it wasn't handed to
+        let mut random = [0u8; 16];
+        getrandom::getrandom(&mut random).expect("the system has no randomness");
+        let run_id = format!("{now}-{:032x}", u128::from_be_bytes(random));
+        let mut runs = self.runs.lock().unwrap();
+        runs.reports.insert(run_id.clone(), None);
+        Started {
+            server: Arc::clone(self),
+            run_id,
+            _permit: permit,
+        }
+    }
+}
+
+/// A run that's going. Dropping it before [`Started::finish`], as when a
+/// waiting client hangs up, forgets the run instead of leaving it running
+/// forever.
+struct Started {
+    server: Arc<Server>,
+    run_id: String,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Started {
+    fn finish(&self, report: RunReport) -> Arc<RunReport> {
+        let report = Arc::new(report);
+        let mut runs = self.server.runs.lock().unwrap();
+        runs.reports
+            .insert(self.run_id.clone(), Some(Arc::clone(&report)));
+        runs.finished.push_back(self.run_id.clone());
+        if runs.finished.len() > KEPT_RUNS {
+            if let Some(oldest) = runs.finished.pop_front() {
+                runs.reports.remove(&oldest);
+            }
+        }
+        report
+    }
+}
+
+impl Drop for Started {
+    fn drop(&mut self) {
+        let mut runs = self.server.runs.lock().unwrap();
+        if let Some(None) = runs.reports.get(&self.run_id) {
+            runs.reports.remove(&self.run_id);
+        }
+    }
+}
+
+/// A run as the API returns it
+#[derive(Serialize)]
+struct Run<'a> {
+    run_id: &'a str,
+    status: &'static str,
+    #[serde(flatten)]
+    report: Option<&'a RunReport>,
+}
+
+impl Run<'_> {
+    fn json(&self) -> Json<Value> {
+        Json(serde_json::to_value(self).expect("reports serialize"))
+    }
+}
+
+/// What to check: a list of IDs, or an `index.json`-style object
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Guilds {
+    Ids(Vec<String>),
+    Index(BTreeMap<String, String>),
+}
+
+#[derive(Deserialize)]
+struct CheckQuery {
+    /// Answer once the run is done, instead of right away with its ID
+    #[serde(default = "wait_default")]
+    wait: bool,
+}
+
+fn wait_default() -> bool {
+    true
+}
+
+/// Whether `id` is a snowflake, as the CLI has its `--ids`
+fn is_id(id: &str) -> bool {
+    index::is_snowflake(id) && id.parse::<u64>().is_ok()
+}
+
+async fn healthz() -> &'static str {
+    "ok"
+}
+
+async fn check_one(
+    State(server): State<Arc<Server>>,
+    Path(id): Path<String>,
+) -> Result<Json<Response>, ApiError> {
+    if !is_id(&id) {
+        return Err(api_error(StatusCode::BAD_REQUEST, "not a Discord ID"));
+    }
+    let mut results = check_stream([(id.clone(), id)], &server.options);
+    match results.next().await {
+        Some(Ok(response)) => Ok(Json(response)),
+        Some(Err(failed)) => Err((
+            StatusCode::BAD_GATEWAY,
+            Json(json!({ "error": failed.message, "kind": failed.kind })),
+        )),
+        None => Err(api_error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "server is shutting down",
+        )),
+    }
+}
+
+async fn check_many(
+    State(server): State<Arc<Server>>,
+    Query(query): Query<CheckQuery>,
+    Json(guilds): Json<Guilds>,
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let guilds: BTreeMap<String, String> = match guilds {
+        Guilds::Ids(ids) => ids.into_iter().map(|id| (id.clone(), id)).collect(),
+        Guilds::Index(index) => index,
+    };
+    if let Some(id) = guilds.keys().find(|id| !is_id(id)) {
+        let error = format!("{id} isn't a Discord ID");
+        return Err(api_error(StatusCode::BAD_REQUEST, &error));
+    }
+    if guilds.len() > server.max_guilds {
+        let error = format!(
+            "at most {} servers can be checked at once",
+            server.max_guilds
+        );
+        return Err(api_error(StatusCode::PAYLOAD_TOO_LARGE, &error));
+    }
+    let Ok(permit) = Arc::clone(&server.run_slots).try_acquire_owned() else {
+        return Err(api_error(
+            StatusCode::TOO_MANY_REQUESTS,
+            "too many runs going, try again later",
+        ));
+    };
+    let started = server.start(permit);
+    if !query.wait {
+        let run_id = started.run_id.clone();
+        tokio::spawn(async move {
+            let report = check_guilds(guilds, &started.server.options).await;
+            started.finish(report);
+        });
+        let run = Run {
+            run_id: &run_id,
+            status: "running",
+            report: None,
+        };
+        return Ok((StatusCode::ACCEPTED, run.json()));
+    }
+    let report = check_guilds(guilds, &server.options).await;
+    let report = started.finish(report);
+    let run = Run {
+        run_id: &started.run_id,
+        status: "done",
+        report: Some(&report),
+    };
+    Ok((StatusCode::OK, run.json()))
+}
+
+async fn results(
+    State(server): State<Arc<Server>>,
+    Path(run_id): Path<String>,
+) -> Result<Json<Value>, ApiError> {
+    let report = server.runs.lock().unwrap().reports.get(&run_id).cloned();
+    let (status, report) = match &report {
+        Some(Some(report)) => ("done", Some(&**report)),
+        Some(None) => ("running", None),
+        None => return Err(api_error(StatusCode::NOT_FOUND, "no such run")),
+    };
+    let run = Run {
+        run_id: &run_id,
+        status,
+        report,
+    };
+    Ok(run.json())
+}
+
+pub fn run(global: GlobalArgs, args: ServeArgs, matches: &ArgMatches) -> eyre::Result<()> {
+    let file = FileConfig::load(global.config.as_deref())?;
+    let check = CheckArgs {
+        request: args.request,
+        cache_ttl: args.cache_ttl,
+        #[cfg(feature = "metrics")]
+        metrics_listen: args.metrics_listen,
+        ..CheckArgs::defaults().context("invalid check setting")?
+    };
+    let config = Config::resolve(check, &global, matches, file);
+
+    let mut options = config.check_options()?;
+    // every caller shares one budget of upstream requests
+    options.limiter = Some(Arc::new(Semaphore::new(config.concurrency)));
+    options.cache = config.result_cache();
+    let cancel = options.cancel.clone();
+    #[cfg(feature = "metrics")]
+    let metrics = args.metrics_listen.map(|addr| {
+        let metrics = crate::metrics::Metrics::new();
+        options.backends = metrics.instrument(std::mem::take(&mut options.backends));
+        (addr, metrics)
+    });
+
+    let app = Router::new()
+        .route("/healthz", get(healthz))
+        .route("/check/:guild_id", get(check_one))
+        .route("/check", post(check_many))
+        .route("/results/:run_id", get(results))
+        .layer(ConcurrencyLimitLayer::new(args.max_inbound))
+        .with_state(Arc::new(Server {
+            options,
+            max_guilds: args.max_guilds,
+            run_slots: Arc::new(Semaphore::new(args.max_runs)),
+            runs: Mutex::default(),
+        }));
+
+    let runtime = config
+        .build_runtime()
+        .context("couldn't start async runtime")?;
+    runtime.block_on(async {
+        #[cfg(feature = "metrics")]
+        if let Some((addr, metrics)) = metrics {
+            crate::metrics::serve(addr, metrics, cancel.clone()).await?;
+        }
+        let listener = tokio::net::TcpListener::bind(args.listen)
+            .await
+            .with_context(|| format!("couldn't listen on {}", args.listen))?;
+        info!("listening on {}", args.listen);
+
+        axum::serve(listener, app)
+            .with_graceful_shutdown(async move {
+                if tokio::signal::ctrl_c().await.is_ok() {
+                    warn!("interrupted, shutting down");
+                }
+                cancel.cancel();
+            })
+            .await
+            .context("server failed")
+    })
+}
@@ -0,0 +1,70 @@
+use std::io::Write;
+
+use color_eyre::eyre::{self, Context};
+use spy_pet_checker::merge::parse_results;
+use spy_pet_checker::stats::Stats;
+
+use crate::cli::{StatsArgs, SummaryFormat};
+use crate::commands::open_output;
+
+fn write_plain(w: &mut dyn Write, stats: &Stats) -> std::io::Result<()> {
+    writeln!(w, "Servers:     {}", stats.total)?;
+    writeln!(w, "Compromised: {}", stats.compromised)?;
+    writeln!(w, "Clean:       {}", stats.clean)?;
+
+    if stats.by_source.len() > 1 {
+        writeln!(w, "\nBy source:")?;
+        for (source, count) in &stats.by_source {
+            writeln!(w, "  {source}: {count}")?;
+        }
+    }
+
+    if let Some(messages) = &stats.messages {
+        writeln!(w, "\nArchived messages in compromised servers:")?;
+        writeln!(
+            w,
+            "  total {}, min {}, median {}, max {}",
+            messages.total, messages.min, messages.median, messages.max
+        )?;
+        for (bucket, count) in &messages.buckets {
+            writeln!(w, "  >= {bucket:<10} {count}")?;
+        }
+    }
+
+    if !stats.most_exposed.is_empty() {
+        writeln!(w, "\nMost exposed:")?;
+        for exposure in &stats.most_exposed {
+            writeln!(
+                w,
+                "  {:>10}  {} (ID: {})",
+                exposure.messages, exposure.guild_name, exposure.guild_id
+            )?;
+        }
+    }
+
+    if !stats.notes.is_empty() {
+        writeln!(w, "\nNot available:")?;
+        for note in &stats.notes {
+            writeln!(w, "  {note}")?;
+        }
+    }
+    Ok(())
+}
+
+pub fn run(args: StatsArgs) -> eyre::Result<()> {
+    let text = std::fs::read_to_string(&args.file)
+        .with_context(|| format!("couldn't read {}", args.file.display()))?;
+    let results =
+        parse_results(&text).with_context(|| format!("couldn't parse {}", args.file.display()))?;
+    let stats = Stats::compute(&results, args.top);
+
+    let mut writer = open_output(args.output.as_deref())?;
+    match args.format {
+        SummaryFormat::Plain => write_plain(&mut writer, &stats),
+        SummaryFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, &stats)?;
+            writeln!(writer)
+        }
+    }
+    .context("couldn't write to output")
+}
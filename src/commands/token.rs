@@ -0,0 +1,90 @@
+#[cfg(feature = "keyring")]
+use std::path::Path;
+
+use color_eyre::eyre;
+
+#[cfg(feature = "keyring")]
+use crate::cli::{LoginArgs, LogoutArgs};
+use crate::cli::{TokenAction, TokenArgs};
+use crate::credentials::{self, Credential};
+
+pub fn run(args: TokenArgs) -> eyre::Result<()> {
+    let credential = if args.api_key {
+        Credential::ApiKey
+    } else {
+        Credential::DiscordToken
+    };
+    let name = credential.name();
+    match args.action {
+        #[cfg(feature = "keyring")]
+        TokenAction::Set => {
+            let (secret, _) =
+                credentials::read_secret(name, Some(Path::new("-")), credential.env())?
+                    .expect("prompting always yields a value");
+            credentials::store(credential, &secret)?;
+            println!("{name} stored in the system keyring");
+        }
+        #[cfg(feature = "keyring")]
+        TokenAction::Clear => {
+            if credentials::clear(credential)? {
+                println!("{name} removed from the system keyring");
+            } else {
+                println!("No {name} was stored");
+            }
+        }
+        TokenAction::Status { token_file } => {
+            match credentials::load(credential, token_file.as_deref())? {
+                Some((_, source)) => println!("Using the {name} from the {source}"),
+                None => println!("No {name} configured"),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `login`: stores the token or key in the keyring, from a prompt or a file,
+/// and reads it back to make sure the keyring kept it
+#[cfg(feature = "keyring")]
+pub fn login(args: LoginArgs) -> eyre::Result<()> {
+    let credential = match args.api_key {
+        true => Credential::ApiKey,
+        false => Credential::DiscordToken,
+    };
+    let name = credential.name();
+    let file = args.from_file.as_deref().unwrap_or(Path::new("-"));
+    let (secret, _) = credentials::read_secret(name, Some(file), credential.env())?
+        .expect("a file or prompt always yields a value");
+    credentials::store(credential, &secret)?;
+    if credentials::stored(credential)?.as_ref() != Some(&secret) {
+        eyre::bail!("the system keyring didn't keep the {name}");
+    }
+    println!("{name} stored in the system keyring");
+    if let Some(path) = &args.from_file {
+        println!("{} can be deleted now", path.display());
+    }
+    for var in credentials::set_in_env(credential) {
+        println!("{var} is set and is used instead until it's unset");
+    }
+    Ok(())
+}
+
+/// `logout`: removes the token, the key or both from the keyring
+#[cfg(feature = "keyring")]
+pub fn logout(args: LogoutArgs) -> eyre::Result<()> {
+    let credentials = match (args.all, args.api_key) {
+        (true, _) => vec![Credential::DiscordToken, Credential::ApiKey],
+        (false, true) => vec![Credential::ApiKey],
+        (false, false) => vec![Credential::DiscordToken],
+    };
+    for credential in credentials {
+        let name = credential.name();
+        match credentials::clear(credential)? {
+            true => println!("{name} removed from the system keyring"),
+            false => println!("No {name} was stored"),
+        }
+        for var in credentials::set_in_env(credential) {
+            println!("{var} is still set, unset it to stop using the {name}");
+        }
+    }
+    Ok(())
+}
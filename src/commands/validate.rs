@@ -0,0 +1,159 @@
+//! `validate`: reads indexes the way `check` would and reports what's wrong
+//! with them, without sending anything.
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use color_eyre::eyre::{self, Context};
+use spy_pet_checker::index::{self, IndexFormat, OrientError, Orientation};
+
+use crate::cli::ValidateArgs;
+use crate::commands::check::index_files;
+use crate::commands::Exit;
+
+#[derive(Default)]
+struct Tally {
+    errors: usize,
+    warnings: usize,
+}
+
+fn read(path: &Path) -> eyre::Result<String> {
+    let mut text = String::new();
+    match path == Path::new("-") {
+        true => std::io::stdin()
+            .read_to_string(&mut text)
+            .context("couldn't read the index from standard input")?,
+        false => std::fs::File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut text))
+            .with_context(|| format!("couldn't read file {}", path.display()))?,
+    };
+    Ok(text)
+}
+
+/// Reports the problems of the index at `path` to `w` and adds its entries
+/// to `merged`
+fn validate_file(
+    w: &mut dyn Write,
+    path: &Path,
+    args: &ValidateArgs,
+    merged: &mut BTreeMap<String, String>,
+    tally: &mut Tally,
+) -> eyre::Result<()> {
+    let shown = path.display();
+    let text = read(path)?;
+    let format = args
+        .index_format
+        .known()
+        .unwrap_or_else(|| IndexFormat::detect(path, &text));
+    let (index, duplicates) = match index::parse_as(format, &text) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            writeln!(w, "{shown}: error: {err}")?;
+            tally.errors += 1;
+            return Ok(());
+        }
+    };
+    let mut index = index::normalize(index);
+    let invites = index::take_invites(&mut index);
+    let names = index::take_names(&mut index);
+    if !args.no_autodetect && format.is_map() {
+        index = match index::orient(index.clone()) {
+            Ok((index, orientation)) => {
+                if orientation == Orientation::NameToId {
+                    writeln!(
+                        w,
+                        "{shown}: warning: maps server names to IDs, check reads it the other way round"
+                    )?;
+                    tally.warnings += 1;
+                }
+                index
+            }
+            Err(OrientError::NoIds) => index::mostly_ids(index),
+            Err(err) => {
+                writeln!(w, "{shown}: error: {err}")?;
+                tally.errors += 1;
+                return Ok(());
+            }
+        };
+    }
+
+    for finding in index::validate(&text, &index, &duplicates) {
+        let level = match finding.problem.is_error() {
+            true => {
+                tally.errors += 1;
+                "error"
+            }
+            false => {
+                tally.warnings += 1;
+                "warning"
+            }
+        };
+        let at = match finding.line {
+            Some(line) => format!("{shown}:{line}"),
+            None => shown.to_string(),
+        };
+        writeln!(w, "{at}: {level}: {:?} {}", finding.key, finding.problem)?;
+    }
+    writeln!(
+        w,
+        "{shown}: {} servers, {} invite links and {} names to search for, read as {}",
+        index.len(),
+        invites.len(),
+        names.len(),
+        format!("{format:?}").to_lowercase()
+    )?;
+
+    for (id, name) in index {
+        if !index::is_snowflake(&id) {
+            continue;
+        }
+        let name = match name.is_empty() {
+            true => id.clone(),
+            false => name,
+        };
+        merged.entry(id).or_insert(name);
+    }
+    // resolved by check, which is the part that needs the network
+    for (link, name) in invites {
+        merged.entry(link).or_insert(name);
+    }
+    for name in names {
+        merged
+            .entry(format!("{} {name}", index::NAME_ONLY))
+            .or_default();
+    }
+    Ok(())
+}
+
+pub fn run(args: ValidateArgs) -> eyre::Result<()> {
+    let mut stdout = std::io::stdout();
+    let mut stderr = std::io::stderr();
+    let w: &mut dyn Write = match args.normalize {
+        true => &mut stderr,
+        false => &mut stdout,
+    };
+    let mut merged = BTreeMap::new();
+    let mut tally = Tally::default();
+    for path in &args.index_path {
+        for path in index_files(path)? {
+            validate_file(w, &path, &args, &mut merged, &mut tally)?;
+        }
+    }
+    writeln!(w, "{} errors, {} warnings", tally.errors, tally.warnings)?;
+    if args.normalize {
+        let mut json = serde_json::to_string_pretty(&merged)?;
+        json.push('\n');
+        std::io::stdout()
+            .write_all(json.as_bytes())
+            .context("couldn't write the index")?;
+    }
+    if tally.errors > 0 {
+        return Err(Exit {
+            code: 1,
+            message: format!("the indexes have {} errors", tally.errors),
+        }
+        .into());
+    }
+    Ok(())
+}
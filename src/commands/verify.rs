@@ -0,0 +1,53 @@
+//! `verify`: checks a report from `--sign` against the public key, with the
+//! signature next to it or, for JSON, around it.
+
+use std::io::Write;
+
+use color_eyre::eyre::{self, Context};
+use spy_pet_checker::sign::{SignError, VerifyingKey};
+
+use crate::cli::VerifyArgs;
+use crate::commands::{open_output, signature_path};
+
+pub fn run(args: VerifyArgs) -> eyre::Result<()> {
+    let key = VerifyingKey::read(&args.key).context("invalid --key")?;
+    let read = |path: &std::path::Path| {
+        std::fs::read(path).with_context(|| format!("couldn't read {}", path.display()))
+    };
+    let contents = read(&args.report)?;
+    let detached = args
+        .signature
+        .clone()
+        .or_else(|| Some(signature_path(&args.report)).filter(|path| path.exists()));
+    let report = match &detached {
+        Some(path) => {
+            let signature = String::from_utf8_lossy(&read(path)?).into_owned();
+            key.verify_detached(&contents, &signature)
+                .with_context(|| format!("{} doesn't verify", args.report.display()))?;
+            contents
+        }
+        None => match key.verify_embedded(&String::from_utf8_lossy(&contents)) {
+            Err(SignError::Malformed) => eyre::bail!(
+                "{} isn't signed inside, and there's no {} next to it",
+                args.report.display(),
+                signature_path(&args.report).display()
+            ),
+            report => {
+                report.with_context(|| format!("{} doesn't verify", args.report.display()))?
+            }
+        },
+    };
+    eprintln!(
+        "{}: good signature from key {}",
+        args.report.display(),
+        key.key_id()
+    );
+    if let Some(path) = &args.output {
+        let mut writer = open_output(Some(path))?;
+        writer
+            .write_all(&report)
+            .context("couldn't write to output")?;
+        writer.commit()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,202 @@
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use color_eyre::eyre::{self, Context};
+use spy_pet_checker::notify::Payload;
+use spy_pet_checker::warnings::Condition;
+use spy_pet_checker::watch::{Change, WatchState};
+use spy_pet_checker::{check_guilds, CancellationToken, ErrorKind, Response};
+use tokio::sync::Notify;
+use tracing::{error, info, warn};
+
+use crate::commands::check::{
+    add_labels, load_guilds, notification, notify, record_run, summary, unauthorized,
+};
+use crate::commands::{append_output, shutdown_signal};
+use crate::config::Config;
+#[cfg(feature = "metrics")]
+use crate::metrics::Metrics;
+
+pub fn run(config: Arc<Config>, interval: Duration) -> eyre::Result<()> {
+    if config
+        .index_path
+        .iter()
+        .any(|index| index.path == Path::new("-"))
+    {
+        eyre::bail!("--watch reads the index again every time, give it a file instead of -");
+    }
+    let runtime = config
+        .build_runtime()
+        .context("couldn't start async runtime")?;
+    runtime.block_on(watch(config, interval))
+}
+
+/// Starts the next cycle early on SIGHUP, as after editing the index
+#[cfg(unix)]
+async fn wake_on_hangup(wake: Arc<Notify>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(err) => return warn!(%err, "couldn't listen for SIGHUP"),
+    };
+    while hangup.recv().await.is_some() {
+        wake.notify_one();
+    }
+}
+
+async fn watch(config: Arc<Config>, interval: Duration) -> eyre::Result<()> {
+    let shutdown = CancellationToken::new();
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            shutdown_signal().await;
+            warn!("shutting down");
+            shutdown.cancel();
+        }
+    });
+
+    let state_path = match config.state() {
+        Some(state) => Some(
+            state
+                .watch_state_path()
+                .context("couldn't prepare the state directory")?,
+        ),
+        None => {
+            warn!("without a state directory, a restart will report known changes again");
+            None
+        }
+    };
+    let mut state = match &state_path {
+        Some(path) => WatchState::load(path)
+            .with_context(|| format!("couldn't read watch state {}", path.display()))?,
+        None => WatchState::default(),
+    };
+
+    let wake = Arc::new(Notify::new());
+    #[cfg(unix)]
+    tokio::spawn(wake_on_hangup(Arc::clone(&wake)));
+
+    let mut options = config.check_options()?;
+    options.cancel = shutdown.clone();
+    // every cycle would keep the first cycle's dumps otherwise
+    if config.dump_dir.is_some() && !config.dump_overwrite {
+        config.warnings.warn(
+            Condition::IgnoredOption,
+            "--dump-dir only keeps the first cycle's responses without --dump-overwrite",
+        );
+    }
+    options.dump = config.response_dump(options.backends.len())?;
+    options.member_scan = config.member_scanner()?;
+    options.batches = config.batches()?;
+    #[cfg(feature = "metrics")]
+    let metrics = match config.metrics_listen {
+        Some(addr) => {
+            let metrics = Metrics::new();
+            crate::metrics::serve(addr, Arc::clone(&metrics), shutdown.clone()).await?;
+            options.backends = metrics.instrument(options.backends);
+            metrics.set_compromised(state.compromised_guilds());
+            Some(metrics)
+        }
+        None => None,
+    };
+    let mut formatter = config.format.formatter();
+    if !config.encrypt.is_empty() || config.sign.is_some() {
+        eyre::bail!(
+            "--encrypt and --sign can't be used with --watch, which adds to the report every round"
+        );
+    }
+    let mut writer = append_output(config.output.as_deref())?;
+
+    loop {
+        let start = Instant::now();
+        let started_at = Utc::now();
+
+        // re-read every cycle so edits to the index are picked up
+        match load_guilds(&config, config.offline).await {
+            Ok((guilds, labels)) => {
+                config.warnings.check()?;
+                let index_size = guilds.len();
+                let mut report = check_guilds(guilds.clone(), &options).await;
+                // the next cycle would be refused just the same
+                if let Some(refused) = report
+                    .failed
+                    .iter()
+                    .find(|failed| failed.kind == ErrorKind::Unauthorized)
+                {
+                    let key_sent = options.api_key.is_some();
+                    eyre::bail!(unauthorized(&refused.message, key_sent));
+                }
+                for response in &mut report.results {
+                    add_labels(response, &labels);
+                }
+                let changes = state.update(report.results.clone(), &guilds);
+                let new_compromised = changes
+                    .iter()
+                    .filter(|c| matches!(c, Change::Compromised { .. }))
+                    .count();
+                report.notification = notification(&config, &report, Some(new_compromised));
+                let new: Vec<&Response> = report
+                    .results
+                    .iter()
+                    .filter(|r| {
+                        changes.iter().any(|c| {
+                            matches!(c, Change::Compromised { guild_id, source, .. }
+                                if *guild_id == r.guild_id && *source == r.source)
+                        })
+                    })
+                    .collect();
+                let errors = report.failed.len();
+                let summary = summary("newly compromised", new.len(), index_size, errors);
+                let payload = Payload {
+                    summary: &summary,
+                    checked: index_size,
+                    errors,
+                    findings: &new,
+                    notification: report.notification,
+                };
+                notify(&config, &payload).await;
+                info!(
+                    changes = changes.len(),
+                    errors = report.failed.len(),
+                    "cycle took {:?}",
+                    start.elapsed()
+                );
+                #[cfg(feature = "metrics")]
+                if let Some(metrics) = &metrics {
+                    metrics.set_compromised(state.compromised_guilds());
+                    metrics.record_found(new_compromised);
+                    metrics.finish_cycle(Utc::now(), start.elapsed());
+                }
+
+                formatter
+                    .write_changes(&mut writer, &changes)
+                    .and_then(|()| writer.flush())
+                    .context("couldn't write to output")?;
+                if let Some(path) = &state_path {
+                    if let Err(err) = state.save(path) {
+                        warn!(%err, "couldn't save watch state");
+                    }
+                }
+                record_run(&config, started_at, index_size, report);
+                config.warnings.check()?;
+            }
+            Err(err) => error!("skipping cycle: {err:#}"),
+        }
+
+        if shutdown.is_cancelled() {
+            break;
+        }
+        info!("next check in {}", humantime::format_duration(interval));
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = tokio::time::sleep(interval) => {}
+            _ = wake.notified() => info!("checking now"),
+        }
+    }
+
+    Ok(())
+}
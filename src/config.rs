@@ -0,0 +1,1597 @@
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+#[cfg(feature = "metrics")]
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use color_eyre::eyre::{self, Context};
+use directories::ProjectDirs;
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::{Certificate, Url};
+use serde::{Deserialize, Serialize};
+use spy_pet_checker::backend::{
+    Backend, Fixtures, LocalDataset, Simulated, SimulationProfile, SpyPet, UrlTemplate,
+};
+use spy_pet_checker::cache::ResultCache;
+use spy_pet_checker::dataset::Dataset;
+use spy_pet_checker::deep::DeepScan;
+use spy_pet_checker::discord::{self, GuildLister};
+use spy_pet_checker::dns::DohResolver;
+use spy_pet_checker::dump::ResponseDump;
+#[cfg(feature = "encrypt")]
+use spy_pet_checker::encrypt::Recipient;
+use spy_pet_checker::mail::{self, Smtp};
+use spy_pet_checker::members::{BotList, MemberScanner};
+use spy_pet_checker::notify::Callback;
+use spy_pet_checker::oauth;
+use spy_pet_checker::pacing::{AdaptiveConcurrency, Batches, Spacing};
+use spy_pet_checker::proxy::Proxy;
+use spy_pet_checker::retry::RetryPolicy;
+use spy_pet_checker::search::Search;
+use spy_pet_checker::secret::Secret;
+#[cfg(feature = "sign")]
+use spy_pet_checker::sign::SigningKey;
+use spy_pet_checker::state::StateDir;
+use spy_pet_checker::tor::{CircuitRotation, TorControl};
+use spy_pet_checker::verify::Verifier;
+use spy_pet_checker::warnings::{Condition, Warnings};
+use spy_pet_checker::web::WebFallback;
+use spy_pet_checker::window::Window;
+use spy_pet_checker::{
+    build_client, build_keyless_client, parse_header, ApiKey, ApiKeyStatus, CheckOptions, Kind,
+};
+use tokio::runtime::{self, Runtime};
+use tracing::{debug, info, warn};
+
+use crate::cli::{
+    BackendChoice, CheckArgs, CheckKind, EmailFormat, FailFast, FailOn, Format, FormatOptions,
+    GlobalArgs, GroupBy, IndexFormat, IndexPath, NotifyMethod, NotifyOn, OutputMode, RuntimeChoice,
+    SeverityLevel, Shard, SinceMode, SmtpSecurity, SortBy, TokenType,
+};
+use crate::commands::check::STALE_LISTING;
+#[cfg(feature = "encrypt")]
+use crate::credentials::PASSPHRASE_ENV;
+use crate::credentials::{
+    self, Credential, SecretSource, DISCORD_WEBHOOK_ENV, NOTIFY_URL_ENV, PROXY_PASSWORD_ENV,
+    SMTP_PASSWORD_ENV, TOR_PASSWORD_ENV,
+};
+
+/// `index_path` in the config file: one path, or a list of them
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum IndexPaths {
+    One(IndexPath),
+    Many(Vec<IndexPath>),
+}
+
+impl IndexPaths {
+    fn into_vec(self) -> Vec<IndexPath> {
+        match self {
+            IndexPaths::One(index) => vec![index],
+            IndexPaths::Many(indexes) => indexes,
+        }
+    }
+}
+
+/// `backend` in the config file: one backend, or a list of them
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Backends {
+    One(BackendChoice),
+    Many(Vec<BackendChoice>),
+}
+
+impl Backends {
+    fn into_vec(self) -> Vec<BackendChoice> {
+        match self {
+            Backends::One(backend) => vec![backend],
+            Backends::Many(backends) => backends,
+        }
+    }
+}
+
+/// Options as read from the config file. Every field is optional; anything
+/// missing falls back to the CLI default.
+#[derive(Deserialize, Default)]
+pub struct FileConfig {
+    concurrency: Option<usize>,
+    adaptive: Option<bool>,
+    retries: Option<u32>,
+    #[serde(default, with = "humantime_serde")]
+    backoff: Option<Duration>,
+    retry_passes: Option<u32>,
+    pool_size: Option<usize>,
+    #[serde(default, with = "humantime_serde")]
+    timeout: Option<Duration>,
+    #[serde(default, with = "humantime_serde")]
+    delay: Option<Duration>,
+    #[serde(default, with = "humantime_serde")]
+    jitter: Option<Duration>,
+    index_path: Option<IndexPaths>,
+    no_autodetect: Option<bool>,
+    index_format: Option<IndexFormat>,
+    from_dce: Option<String>,
+    from_data_package: Option<PathBuf>,
+    include_users: Option<bool>,
+    from_discord: Option<bool>,
+    token_type: Option<TokenType>,
+    ids: Option<Vec<String>>,
+    invites: Option<Vec<String>>,
+    only: Option<Vec<String>>,
+    exclude_id: Option<Vec<String>>,
+    name_filter: Option<String>,
+    shard: Option<Shard>,
+    kind: Option<CheckKind>,
+    format: Option<Format>,
+    backend: Option<Backends>,
+    url_template: Option<String>,
+    match_compromised: Option<String>,
+    ca_cert: Option<Vec<PathBuf>>,
+    insecure: Option<bool>,
+    doh: Option<String>,
+    proxy: Option<String>,
+    proxy_password_file: Option<PathBuf>,
+    tor: Option<bool>,
+    tor_socks: Option<String>,
+    tor_control: Option<String>,
+    tor_password_file: Option<PathBuf>,
+    new_circuit_every: Option<u32>,
+    new_circuit_on_429: Option<bool>,
+    resolve: Option<Vec<String>>,
+    api_key_file: Option<PathBuf>,
+    api_key_header: Option<String>,
+    user_agent: Option<String>,
+    header: Option<Vec<String>>,
+    runtime: Option<RuntimeChoice>,
+    state_dir: Option<PathBuf>,
+    output: Option<PathBuf>,
+    append: Option<bool>,
+    atomic: Option<bool>,
+    encrypt: Option<Vec<String>>,
+    passphrase_file: Option<PathBuf>,
+    sign: Option<PathBuf>,
+    group_by: Option<GroupBy>,
+    template: Option<PathBuf>,
+    sort_by: Option<SortBy>,
+    json_compact: Option<bool>,
+    only_compromised: Option<bool>,
+    include_clean: Option<bool>,
+    details: Option<bool>,
+    #[serde(default, with = "humantime_serde")]
+    watch: Option<Duration>,
+    #[cfg(feature = "metrics")]
+    metrics_listen: Option<SocketAddr>,
+    max_errors: Option<usize>,
+    max_error_rate: Option<f64>,
+    fail_fast: Option<FailFast>,
+    fail_on: Option<FailOn>,
+    strict: Option<bool>,
+    #[serde(default, with = "humantime_serde")]
+    cache_ttl: Option<Duration>,
+    dump_dir: Option<PathBuf>,
+    dump_overwrite: Option<bool>,
+    deep_scan: Option<bool>,
+    deep_endpoint: Option<Vec<String>>,
+    fallback_web: Option<String>,
+    verify: Option<bool>,
+    #[serde(default, with = "humantime_serde")]
+    verify_delay: Option<Duration>,
+    verify_proxy: Option<String>,
+    batch_size: Option<usize>,
+    #[serde(default, with = "humantime_serde")]
+    cooldown: Option<Duration>,
+    include_headers: Option<bool>,
+    max_response_size: Option<u64>,
+    prefilter: Option<String>,
+    verify_all: Option<bool>,
+    local_dataset: Option<PathBuf>,
+    search_url: Option<String>,
+    min_shared: Option<usize>,
+    min_severity: Option<SeverityLevel>,
+    scan_members: Option<bool>,
+    bot_list: Option<Vec<PathBuf>>,
+    token_file: Option<PathBuf>,
+    notify_on: Option<NotifyOn>,
+    discord_webhook: Option<String>,
+    discord_webhook_file: Option<PathBuf>,
+    notify_url: Option<String>,
+    notify_url_file: Option<PathBuf>,
+    notify_method: Option<NotifyMethod>,
+    notify_header: Option<Vec<String>>,
+    notify_header_file: Option<PathBuf>,
+    notify_retries: Option<u32>,
+    desktop_notify: Option<bool>,
+    email_to: Option<Vec<String>>,
+    email_from: Option<String>,
+    email_format: Option<EmailFormat>,
+    smtp_server: Option<String>,
+    smtp_security: Option<SmtpSecurity>,
+    smtp_username: Option<String>,
+    smtp_password_file: Option<PathBuf>,
+
+    #[serde(flatten)]
+    unknown: BTreeMap<String, toml::Value>,
+    /// Where it was read from
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+/// The effective configuration after merging CLI flags, environment and the
+/// config file
+#[derive(Serialize)]
+pub struct Config {
+    pub concurrency: usize,
+    /// Lower the concurrency while the API is struggling
+    pub adaptive: bool,
+    pub retries: u32,
+    #[serde(with = "humantime_serde")]
+    pub backoff: Duration,
+    /// Passes over the failed checks once the rest are done
+    pub retry_passes: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool_size: Option<usize>,
+    /// Deadline for each request; none if zero
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+    /// Least time between the starts of two requests
+    #[serde(with = "humantime_serde", skip_serializing_if = "Option::is_none")]
+    pub delay: Option<Duration>,
+    /// Random extra time added to each delay, up to this much
+    #[serde(with = "humantime_serde", skip_serializing_if = "Option::is_none")]
+    pub jitter: Option<Duration>,
+    pub index_path: Vec<IndexPath>,
+    /// Don't turn around indexes written name → id
+    pub no_autodetect: bool,
+    /// How the indexes are written, unless it's worked out for each
+    pub index_format: IndexFormat,
+    /// DiscordChatExporter exports to read instead of the index
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_dce: Option<String>,
+    /// Discord data package to read instead of the index
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_data_package: Option<PathBuf>,
+    /// Check the users in the data package too
+    pub include_users: bool,
+    /// List the guilds through Discord's API instead of reading the index
+    pub from_discord: bool,
+    pub token_type: TokenType,
+    /// `--ids`: the IDs to check, instead of loading any
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ids: Vec<String>,
+    /// `--invite`: invite links to check the servers of, next to the index
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub invites: Vec<String>,
+    /// `--only`: the IDs to check, out of all those loaded
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub only: Vec<String>,
+    /// `--exclude-id`: the IDs not to check, out of all those loaded
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub exclude_id: Vec<String>,
+    /// `--name-filter`: a regex the names of the guilds to check match
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name_filter: Option<String>,
+    /// `--shard`: the part of the guilds this run checks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shard: Option<Shard>,
+    pub kind: CheckKind,
+    pub format: Format,
+    /// `--backend`: the services asked about every guild
+    pub backend: Vec<BackendChoice>,
+    pub url_template: Option<String>,
+    pub match_compromised: Option<String>,
+    pub ca_cert: Vec<PathBuf>,
+    pub insecure: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub doh: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy_password_file: Option<PathBuf>,
+    pub tor: bool,
+    pub tor_socks: String,
+    pub tor_control: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tor_password_file: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_circuit_every: Option<u32>,
+    pub new_circuit_on_429: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub resolve: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key_file: Option<PathBuf>,
+    pub api_key_header: String,
+    /// The API key once read, shown only as whether there is one
+    #[serde(serialize_with = "api_key_status", skip_serializing_if = "no_api_key")]
+    pub api_key: LoadedKey,
+    /// The Discord token once read
+    #[serde(skip)]
+    pub discord_token: LoadedKey,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    /// Shown without their values, which may be cookies
+    #[serde(serialize_with = "header_names", skip_serializing_if = "Vec::is_empty")]
+    pub header: Vec<String>,
+    pub runtime: RuntimeChoice,
+    /// `None` when persistence is disabled with `--no-state`
+    pub state_dir: Option<PathBuf>,
+    pub output: Option<PathBuf>,
+    /// The `--format`/`--output` pairs after the first, which is `format`
+    /// and `output`. Written from the report once the run is done.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<ExtraOutput>,
+    /// `--append` or `--atomic`
+    pub output_mode: OutputMode,
+    /// `--encrypt`: age recipients, or `passphrase`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub encrypt: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub passphrase_file: Option<PathBuf>,
+    /// The recipients once the passphrase is read, so it's only asked once
+    #[cfg(feature = "encrypt")]
+    #[serde(skip)]
+    pub recipients: OnceLock<Arc<[Recipient]>>,
+    /// `--sign`: the private key to sign the report with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sign: Option<PathBuf>,
+    #[cfg(feature = "sign")]
+    #[serde(skip)]
+    pub signing_key: OnceLock<Arc<SigningKey>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group_by: Option<GroupBy>,
+    /// `--template`: written through instead of `format`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<PathBuf>,
+    /// `--sort-by`: the order results are written in, instead of the order
+    /// the checks finished in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort_by: Option<SortBy>,
+    pub json_compact: bool,
+    /// Leave everything but the compromised servers out of the output
+    pub only_compromised: bool,
+    /// List the clean servers in the plain output
+    pub include_clean: bool,
+    /// List what the answers say under the compromised servers in the plain
+    /// output
+    pub details: bool,
+    /// `--since` and `--until`, if either was given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window: Option<Window>,
+    pub since_mode: SinceMode,
+    /// Interval between checks in watch mode
+    #[serde(with = "humantime_serde", skip_serializing_if = "Option::is_none")]
+    pub watch: Option<Duration>,
+    #[cfg(feature = "metrics")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metrics_listen: Option<SocketAddr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_errors: Option<usize>,
+    /// Percentage of failed checks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_error_rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fail_fast: Option<FailFast>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fail_on: Option<FailOn>,
+    /// Take the servers an interrupted run finished from its checkpoint
+    pub resume: bool,
+    /// `--baseline`: the results whose compromised servers aren't news
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub baseline: Option<PathBuf>,
+    pub strict: bool,
+    /// `--simulate`: made-up servers to check against the stub
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub simulate: Option<usize>,
+    /// How the stub answers, with the overrides applied
+    #[serde(skip)]
+    pub simulation: SimulationProfile,
+    pub offline: bool,
+    /// `--fixtures`: the directory of answers to check against instead
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fixtures: Option<PathBuf>,
+    /// `--local-dataset`: the dump to check against instead
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub local_dataset: Option<PathBuf>,
+    /// The dump once read, which can be large
+    #[serde(skip)]
+    pub dataset: OnceLock<Arc<Dataset>>,
+    /// Where `--search` and name-only index entries are searched for
+    pub search_url: String,
+    /// Where warn-and-continue conditions are reported, and kept with
+    /// `--strict`
+    #[serde(skip)]
+    pub warnings: Arc<Warnings>,
+    /// How long cached results stay fresh
+    #[serde(with = "humantime_serde", skip_serializing_if = "Option::is_none")]
+    pub cache_ttl: Option<Duration>,
+    #[serde(skip)]
+    pub no_cache: bool,
+    /// Where raw responses are saved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dump_dir: Option<PathBuf>,
+    pub dump_overwrite: bool,
+    pub deep_scan: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub deep_endpoint: Vec<String>,
+    /// The web page read when the API fails
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback_web: Option<String>,
+    pub verify: bool,
+    /// How long after the first answer a compromised server is asked about
+    /// again
+    #[serde(with = "humantime_serde")]
+    pub verify_delay: Duration,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verify_proxy: Option<String>,
+    /// Requests in each batch, between cooldowns
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub batch_size: Option<usize>,
+    #[serde(with = "humantime_serde", skip_serializing_if = "Option::is_none")]
+    pub cooldown: Option<Duration>,
+    pub include_headers: bool,
+    /// Most bytes read of an answer, 0 for no limit
+    pub max_response_size: u64,
+    /// Listing of tracked IDs to check against first
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prefilter: Option<String>,
+    pub verify_all: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_shared: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_severity: Option<SeverityLevel>,
+    pub scan_members: bool,
+    /// Bot lists added to the bundled one
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub bot_list: Vec<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_file: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notify_on: Option<NotifyOn>,
+    /// The URL holds the webhook's token
+    #[serde(serialize_with = "redacted", skip_serializing_if = "Option::is_none")]
+    pub discord_webhook: Option<Secret<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub discord_webhook_file: Option<PathBuf>,
+    /// Like the webhook's, the URL may hold a token
+    #[serde(serialize_with = "redacted", skip_serializing_if = "Option::is_none")]
+    pub notify_url: Option<Secret<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notify_url_file: Option<PathBuf>,
+    pub notify_method: NotifyMethod,
+    #[serde(serialize_with = "header_names", skip_serializing_if = "Vec::is_empty")]
+    pub notify_header: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notify_header_file: Option<PathBuf>,
+    pub notify_retries: u32,
+    pub desktop_notify: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub email_to: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_from: Option<String>,
+    pub email_format: EmailFormat,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smtp_server: Option<String>,
+    pub smtp_security: SmtpSecurity,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smtp_username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smtp_password_file: Option<PathBuf>,
+}
+
+type LoadedKey = OnceLock<Option<(Secret<String>, SecretSource)>>;
+
+fn no_api_key(key: &LoadedKey) -> bool {
+    !matches!(key.get(), Some(Some(_)))
+}
+
+fn api_key_status<S: serde::Serializer>(_: &LoadedKey, serializer: S) -> Result<S::Ok, S::Error> {
+    ApiKeyStatus::Set.serialize(serializer)
+}
+
+fn redacted<S: serde::Serializer>(
+    _: &Option<Secret<String>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str("[redacted]")
+}
+
+fn header_names<S: serde::Serializer>(
+    headers: &[String],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let names = headers.iter().map(|header| match header.split_once(':') {
+        Some((name, _)) => format!("{}: [redacted]", name.trim()),
+        None => header.clone(),
+    });
+    serializer.collect_seq(names)
+}
+
+/// Config file in the working directory, ahead of the user's
+const LOCAL_CONFIG: &str = "spy-pet-checker.toml";
+
+/// Where the config file is read from without `--config`: `LOCAL_CONFIG` if
+/// the working directory has one, the user's config directory otherwise
+pub fn default_config_path() -> Option<PathBuf> {
+    let local = Path::new(LOCAL_CONFIG);
+    if local.is_file() {
+        return Some(local.to_owned());
+    }
+    ProjectDirs::from("", "", "spy-pet-checker").map(|dirs| dirs.config_dir().join("config.toml"))
+}
+
+impl FileConfig {
+    /// Loads `path`, or the default location if `None`. A missing file is only
+    /// an error when the path was given explicitly.
+    pub fn load(path: Option<&Path>) -> eyre::Result<Self> {
+        let (path, explicit) = match path {
+            Some(path) => (path.to_owned(), true),
+            None => match default_config_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+
+        let string = match std::fs::read_to_string(&path) {
+            Ok(string) => string,
+            Err(err) if !explicit && err.kind() == std::io::ErrorKind::NotFound => {
+                debug!(path=%path.display(), "no config file");
+                return Ok(Self::default());
+            }
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("couldn't read config file {}", path.display()))
+            }
+        };
+
+        let mut config: Self = toml::from_str(&string)
+            .with_context(|| format!("couldn't parse config file {}", path.display()))?;
+        config.path = Some(path);
+        Ok(config)
+    }
+
+    /// Reports the keys this version doesn't know, which are ignored
+    pub fn warn_unknown(&self, warnings: &Warnings) {
+        if let (Some(path), false) = (&self.path, self.unknown.is_empty()) {
+            let keys: Vec<&str> = self.unknown.keys().map(String::as_str).collect();
+            warnings.warn(
+                Condition::UnknownConfigKey,
+                format!(
+                    "unknown keys in config file {}: {}",
+                    path.display(),
+                    keys.join(", ")
+                ),
+            );
+        }
+    }
+
+    /// The configured `--cache-ttl`
+    pub fn cache_ttl(&self) -> Option<Duration> {
+        self.cache_ttl
+    }
+}
+
+/// The state directory from `--state-dir`, the config file or the platform
+/// default, or `None` with `--no-state`
+pub fn resolve_state_dir(global: &GlobalArgs, file: &FileConfig) -> Option<PathBuf> {
+    if global.no_state {
+        return None;
+    }
+    global
+        .state_dir
+        .clone()
+        .or_else(|| file.state_dir.clone())
+        .or_else(|| StateDir::default_location().map(|dir| dir.root().to_owned()))
+}
+
+/// A secret given either as a value or as a file holding it: the command
+/// line's, in whichever form, over the config file's
+fn value_or_file<T>(
+    cli: (Option<T>, Option<PathBuf>),
+    file: (Option<T>, Option<PathBuf>),
+) -> (Option<T>, Option<PathBuf>) {
+    match cli {
+        (None, None) => file,
+        cli => cli,
+    }
+}
+
+/// Takes the CLI value unless clap only filled in its default, in which case
+/// the config file wins. Arguments the command doesn't define count as
+/// defaults.
+fn pick<T>(matches: &ArgMatches, id: &str, cli: T, file: Option<T>) -> T {
+    let source = match matches.try_contains_id(id) {
+        Ok(_) => matches.value_source(id),
+        Err(_) => None,
+    };
+    match (source, file) {
+        (Some(ValueSource::DefaultValue) | None, Some(file)) => file,
+        _ => cli,
+    }
+}
+
+/// A `--format` after the first, and where it goes: its `--output`, or
+/// stdout if it has none
+#[derive(Clone, Serialize)]
+pub struct ExtraOutput {
+    pub format: Format,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<PathBuf>,
+}
+
+/// Each format with the output in the same position: stdout for a format
+/// past the last output, the last format for an output past the last format
+fn output_pairs(formats: Vec<Format>, outputs: Vec<PathBuf>) -> Vec<ExtraOutput> {
+    let last = formats.last().cloned().unwrap_or(Format::Plain);
+    let pairs = formats.len().max(outputs.len());
+    let mut formats = formats.into_iter();
+    let mut outputs = outputs.into_iter();
+    (0..pairs)
+        .map(|_| ExtraOutput {
+            format: formats.next().unwrap_or(last.clone()),
+            output: outputs.next(),
+        })
+        .collect()
+}
+
+impl Config {
+    /// Library options for running checks with this configuration
+    pub fn check_options(&self) -> eyre::Result<CheckOptions> {
+        let mut options = CheckOptions {
+            concurrency: self.concurrency,
+            adaptive: self
+                .adaptive
+                .then(|| AdaptiveConcurrency::new(self.concurrency)),
+            retry: RetryPolicy::new(self.retries, self.backoff),
+            retry_passes: self.retry_passes,
+            pool_size: self.pool_size,
+            timeout: (!self.timeout.is_zero()).then_some(self.timeout),
+            spacing: self.spacing(),
+            backends: self.backends()?,
+            ca_certs: self.ca_certs()?,
+            insecure: self.insecure,
+            proxy: self.proxy()?,
+            headers: self.headers()?,
+            user_agent: self.user_agent.clone(),
+            tor: self.circuit_rotation()?,
+            resolve: self.resolve_overrides()?,
+            deep_scan: self.deep_scan()?,
+            web_fallback: self.web_fallback()?,
+            keep_headers: self.include_headers,
+            max_response_size: Some(self.max_response_size).filter(|&size| size > 0),
+            kind: self.kind.into_kind(),
+            warnings: Arc::clone(&self.warnings),
+            ..Default::default()
+        };
+        // a pooled connection would keep its old circuit after a rotation
+        if options.tor.is_some() && options.pool_size.is_none() {
+            options.pool_size = Some(0);
+        }
+        options.doh = self.doh_resolver(&options)?;
+        // after the resolver, whose client must not send the key to the DoH
+        // server
+        options.api_key = self.api_key()?;
+        // after the key and the resolver, which a --verify-proxy client
+        // needs as well
+        options.verify = self.verifier(&options)?;
+        Ok(options)
+    }
+
+    fn deep_scan(&self) -> eyre::Result<Option<Arc<DeepScan>>> {
+        if !self.deep_scan {
+            return Ok(None);
+        }
+        if self.deep_endpoint.is_empty() {
+            eyre::bail!("--deep-scan needs at least one --deep-endpoint to ask");
+        }
+        let deep = DeepScan::new(self.deep_endpoint.clone()).context("invalid deep_endpoint")?;
+        Ok(Some(Arc::new(deep)))
+    }
+
+    fn web_fallback(&self) -> eyre::Result<Option<Arc<WebFallback>>> {
+        let Some(template) = &self.fallback_web else {
+            return Ok(None);
+        };
+        let web = WebFallback::new(template.clone()).context("invalid fallback_web")?;
+        Ok(Some(Arc::new(web)))
+    }
+
+    fn verifier(&self, options: &CheckOptions) -> eyre::Result<Option<Arc<Verifier>>> {
+        if !self.verify {
+            return Ok(None);
+        }
+        let mut verifier = Verifier::new(self.verify_delay);
+        if let Some(url) = &self.verify_proxy {
+            let proxy = Proxy::new(url, None).context("invalid --verify-proxy")?;
+            let client = build_client(&CheckOptions {
+                proxy: Some(proxy),
+                ..options.clone()
+            })
+            .context("couldn't set up the --verify-proxy client")?;
+            verifier = verifier.via(client);
+        }
+        Ok(Some(Arc::new(verifier)))
+    }
+
+    /// The `--batch-size` and `--cooldown` pacing, if both are set
+    pub fn batches(&self) -> eyre::Result<Option<Arc<Batches>>> {
+        match (self.batch_size, self.cooldown) {
+            (Some(0), _) => eyre::bail!("the batch size has to be at least 1"),
+            (Some(size), Some(cooldown)) => Ok(Some(Batches::new(size, cooldown))),
+            (None, None) => Ok(None),
+            (Some(_), None) | (None, Some(_)) => {
+                eyre::bail!("batch_size and cooldown only work together, set both")
+            }
+        }
+    }
+
+    /// Reads `--discord-webhook-file`, `--notify-url-file` and
+    /// `--notify-header-file` into the settings they stand in for, so the
+    /// secrets stay off the command line
+    pub fn read_notify_files(&mut self) -> eyre::Result<()> {
+        if let Some(path) = &self.discord_webhook_file {
+            let url = credentials::read_secret("Discord webhook", Some(path), DISCORD_WEBHOOK_ENV)?;
+            self.discord_webhook = url.map(|(url, _)| url);
+        }
+        if let Some(path) = &self.notify_url_file {
+            let url = credentials::read_secret("notify URL", Some(path), NOTIFY_URL_ENV)?;
+            self.notify_url = url.map(|(url, _)| url);
+        }
+        if let Some(path) = &self.notify_header_file {
+            let headers = std::fs::read_to_string(path).with_context(|| {
+                format!("couldn't read --notify-header-file {}", path.display())
+            })?;
+            let headers = headers
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty());
+            self.notify_header.extend(headers.map(str::to_owned));
+        }
+        Ok(())
+    }
+
+    /// Where `--notify-url` is sent, with its `--notify-header`s checked
+    pub fn callback(&self) -> eyre::Result<Option<Callback>> {
+        let Some(url) = &self.notify_url else {
+            return Ok(None);
+        };
+        let mut headers = HeaderMap::new();
+        for header in &self.notify_header {
+            let (name, value) = parse_header(header).context("invalid --notify-header")?;
+            headers.append(name, value);
+        }
+        let callback = Callback::new(url.clone())
+            .with_method(self.notify_method.into_method())
+            .with_headers(headers)
+            .with_retry(RetryPolicy::new(self.notify_retries, self.backoff));
+        Ok(Some(callback))
+    }
+
+    /// Who `--encrypt` encrypts the report to, `None` without it. The
+    /// passphrase is read the first time, so `--passphrase-file -` only
+    /// prompts once for all the outputs.
+    #[cfg(feature = "encrypt")]
+    pub fn recipients(&self) -> eyre::Result<Option<Arc<[Recipient]>>> {
+        if self.encrypt.is_empty() {
+            return Ok(None);
+        }
+        if let Some(recipients) = self.recipients.get() {
+            return Ok(Some(recipients.clone()));
+        }
+        let recipients = self
+            .encrypt
+            .iter()
+            .map(|recipient| match recipient.as_str() {
+                "passphrase" => {
+                    let passphrase = credentials::read_passphrase(
+                        "passphrase",
+                        self.passphrase_file.as_deref(),
+                        PASSPHRASE_ENV,
+                    )?;
+                    let Some((passphrase, _)) = passphrase else {
+                        eyre::bail!(
+                            "--encrypt passphrase needs one in --passphrase-file or {PASSPHRASE_ENV}"
+                        );
+                    };
+                    Ok(Recipient::passphrase(passphrase))
+                }
+                recipient => Recipient::parse(recipient).context("invalid --encrypt"),
+            })
+            .collect::<eyre::Result<Arc<[_]>>>()?;
+        if recipients.len() > 1 && self.encrypt.iter().any(|r| r == "passphrase") {
+            eyre::bail!("--encrypt passphrase can't be combined with other recipients");
+        }
+        Ok(Some(self.recipients.get_or_init(|| recipients).clone()))
+    }
+
+    /// The `--sign` key, read once for all the outputs
+    #[cfg(feature = "sign")]
+    pub fn signing_key(&self) -> eyre::Result<Option<Arc<SigningKey>>> {
+        let Some(path) = &self.sign else {
+            return Ok(None);
+        };
+        if let Some(key) = self.signing_key.get() {
+            return Ok(Some(key.clone()));
+        }
+        let key = SigningKey::read(path).context("invalid --sign")?;
+        debug!(key = key.key_id(), "signing the report");
+        Ok(Some(self.signing_key.get_or_init(|| Arc::new(key)).clone()))
+    }
+
+    /// The `--smtp-server` to send `--email-to` through, if there are any
+    /// addresses, with the password read when there's a `--smtp-username`
+    pub fn smtp(&self) -> eyre::Result<Option<Smtp>> {
+        if self.email_to.is_empty() {
+            return Ok(None);
+        }
+        let Some(server) = &self.smtp_server else {
+            eyre::bail!("--email-to needs an --smtp-server to send through");
+        };
+        let Some(from) = &self.email_from else {
+            eyre::bail!("--email-to needs an --email-from address");
+        };
+        for address in self.email_to.iter().chain([from]) {
+            mail::check_address(address)?;
+        }
+        let (host, port) = match server.rsplit_once(':') {
+            Some((host, port)) if !host.contains(':') || host.starts_with('[') => {
+                let port = port.parse().context("invalid --smtp-server port")?;
+                (
+                    host.trim_start_matches('[').trim_end_matches(']'),
+                    Some(port),
+                )
+            }
+            _ => (server.as_str(), None),
+        };
+        let mut smtp = Smtp::new(host, port, self.smtp_security.into_security());
+        if let Some(username) = &self.smtp_username {
+            // the password would cross the network in the clear
+            let loopback =
+                host == "localhost" || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback());
+            if matches!(self.smtp_security, SmtpSecurity::None) && !loopback {
+                eyre::bail!(
+                    "--smtp-username with --smtp-security none would send the password unencrypted; use tls or starttls, or a relay on this machine"
+                );
+            }
+            let password = credentials::read_secret(
+                "SMTP password",
+                self.smtp_password_file.as_deref(),
+                SMTP_PASSWORD_ENV,
+            )?;
+            let Some((password, _)) = password else {
+                eyre::bail!(
+                    "--smtp-username needs a password in --smtp-password-file or {SMTP_PASSWORD_ENV}"
+                );
+            };
+            smtp = smtp.with_login(username.clone(), password);
+        }
+        Ok(Some(smtp))
+    }
+
+    /// The `--header`s, checked, along with the `--user-agent`
+    pub fn headers(&self) -> eyre::Result<HeaderMap> {
+        if let Some(agent) = &self.user_agent {
+            HeaderValue::try_from(agent).context("invalid --user-agent")?;
+        }
+        let mut headers = HeaderMap::new();
+        for header in &self.header {
+            let (name, value) = parse_header(header).context("invalid --header")?;
+            headers.append(name, value);
+        }
+        Ok(headers)
+    }
+
+    /// The `--delay` and `--jitter` spacing, if either is set
+    pub fn spacing(&self) -> Option<Arc<Spacing>> {
+        if self.delay.is_none() && self.jitter.is_none() {
+            return None;
+        }
+        let delay = self.delay.unwrap_or_default();
+        Some(Spacing::new(delay, self.jitter.unwrap_or_default()))
+    }
+
+    /// The API key from `--api-key-file`, the environment or the keyring.
+    /// Read once, so `--api-key-file -` only prompts once.
+    pub fn api_key(&self) -> eyre::Result<Option<ApiKey>> {
+        let found = match self.api_key.get() {
+            Some(found) => found,
+            None => {
+                let found = credentials::load(Credential::ApiKey, self.api_key_file.as_deref())?;
+                self.api_key.get_or_init(|| found)
+            }
+        };
+        let Some((key, source)) = found else {
+            return Ok(None);
+        };
+        debug!(%source, header = self.api_key_header, "sending the API key");
+        let key = ApiKey::new(&self.api_key_header, key)
+            .with_context(|| format!("can't send the API key from the {source}"))?;
+        Ok(Some(key))
+    }
+
+    /// The Discord token, read the first time it's needed, so `--token-file -`
+    /// only prompts once
+    pub fn discord_token(&self) -> eyre::Result<Option<(Secret<String>, SecretSource)>> {
+        let found = match self.discord_token.get() {
+            Some(found) => found,
+            None => {
+                let found = credentials::discord_token(self.token_file.as_deref())?;
+                self.discord_token.get_or_init(|| found)
+            }
+        };
+        Ok(found.clone())
+    }
+
+    /// What `--from-discord` lists the guilds with. Reads the token, so it
+    /// prompts for it with `--token-file -`; without one, the token `auth`
+    /// got is used, refreshed first if it has expired.
+    pub async fn guild_lister(&self, client: &reqwest::Client) -> eyre::Result<GuildLister> {
+        if let Some((token, source)) = self.discord_token()? {
+            debug!(%source, token_type = self.token_type.into_token_type().as_str(), "listing guilds");
+            return Ok(GuildLister::new(token, self.token_type.into_token_type()));
+        }
+        let stored = match self.state() {
+            Some(state) => {
+                let path = state.oauth_token_path()?;
+                oauth::stored_access_token(client, &path).await?
+            }
+            None => None,
+        };
+        let Some(token) = stored else {
+            eyre::bail!(
+                "--from-discord needs `spy-pet-checker auth` or a Discord token, from --token-file, {} or `spy-pet-checker token set`",
+                credentials::DISCORD_TOKEN_ENV
+            );
+        };
+        debug!(source = "auth", "listing guilds with an OAuth2 token");
+        Ok(GuildLister::new(token, discord::TokenType::Bearer))
+    }
+
+    /// What `--scan-members` scans with. Reads the token, so it prompts for
+    /// it with `--token-file -`.
+    pub fn member_scanner(&self) -> eyre::Result<Option<Arc<MemberScanner>>> {
+        if !self.scan_members {
+            if !self.bot_list.is_empty() {
+                self.warnings.warn(
+                    Condition::IgnoredOption,
+                    "--bot-list has no effect without --scan-members",
+                );
+            }
+            return Ok(None);
+        }
+        if self.kind != CheckKind::Servers {
+            eyre::bail!(
+                "--scan-members looks in servers' member lists, it doesn't work with --kind {}",
+                self.kind.plural()
+            );
+        }
+        let Some((token, source)) = self.discord_token()? else {
+            eyre::bail!(
+                "--scan-members needs a Discord bot token, from --token-file, {} or `spy-pet-checker token set`",
+                credentials::DISCORD_TOKEN_ENV
+            );
+        };
+        debug!(%source, "scanning member lists");
+        let bots = self.bot_list()?;
+        if bots.is_empty() {
+            self.warnings.warn(
+                Condition::Degraded,
+                "the bot list is empty, --scan-members will only count members; add bots with --bot-list",
+            );
+        }
+        Ok(Some(Arc::new(MemberScanner::new(token, bots))))
+    }
+
+    /// The bundled bot list, updated with the one `scan-bots --update-list`
+    /// downloaded and then the `--bot-list` files
+    fn bot_list(&self) -> eyre::Result<BotList> {
+        let mut bots = BotList::bundled();
+        let updated = self
+            .state()
+            .map(|state| state.known_bots_path())
+            .transpose()?
+            .filter(|path| path.exists());
+        for path in updated.iter().chain(&self.bot_list) {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("couldn't read bot list {}", path.display()))?;
+            let list = BotList::parse(&text)
+                .with_context(|| format!("invalid bot list {}", path.display()))?;
+            bots.extend(list);
+        }
+        Ok(bots)
+    }
+
+    /// Options for the users checked next to the servers with
+    /// `--include-users`, sharing everything else with `options`
+    pub fn user_options(&self, options: &CheckOptions) -> eyre::Result<Option<CheckOptions>> {
+        if !self.include_users {
+            return Ok(None);
+        }
+        if self.url_template.is_some() {
+            eyre::bail!("--include-users only works with the spypet backend, not --url-template");
+        }
+        Ok(Some(CheckOptions {
+            backends: vec![Arc::new(SpyPet::default().with_kind(Kind::User))],
+            kind: Kind::User,
+            // deep scan endpoints are for servers
+            deep_scan: None,
+            member_scan: None,
+            ..options.clone()
+        }))
+    }
+
+    pub fn backends(&self) -> eyre::Result<Vec<Arc<dyn Backend>>> {
+        if self.simulate.is_some() {
+            if self.kind != CheckKind::Servers {
+                eyre::bail!(
+                    "--simulate makes up servers, it doesn't work with --kind {}",
+                    self.kind.plural()
+                );
+            }
+            return Ok(vec![Arc::new(Simulated::new(self.simulation))]);
+        }
+        if let Some(dir) = &self.fixtures {
+            if !dir.is_dir() {
+                eyre::bail!("--fixtures {} isn't a directory", dir.display());
+            }
+            return Ok(vec![Arc::new(Fixtures::new(dir))]);
+        }
+        if self.local_dataset.is_some() {
+            if self.kind != CheckKind::Servers {
+                eyre::bail!(
+                    "a dump is a list of servers, --local-dataset doesn't work with --kind {}",
+                    self.kind.plural()
+                );
+            }
+            return Ok(vec![Arc::new(LocalDataset::new(self.dataset()?))]);
+        }
+        if self.offline {
+            eyre::bail!("--offline needs --fixtures, --local-dataset or --simulate to answer from");
+        }
+        match &self.url_template {
+            Some(template) => {
+                let backend = UrlTemplate::new(template, self.match_compromised.as_deref())
+                    .context("invalid url_template")?;
+                Ok(vec![Arc::new(backend)])
+            }
+            None if self.kind != CheckKind::Servers => {
+                // the other backends only look up servers, so `all` is spy.pet
+                if self
+                    .backend
+                    .iter()
+                    .all(|b| matches!(b, BackendChoice::KickTheSpy))
+                {
+                    eyre::bail!("kickthespy.pet only looks up servers, use --backend spypet")
+                }
+                Ok(vec![Arc::new(
+                    SpyPet::default().with_kind(self.kind.into_kind()),
+                )])
+            }
+            None => Ok(BackendChoice::backends_of(&self.backend)),
+        }
+    }
+
+    /// What `format` is adjusted by. The output is colored by `--color`
+    /// when it goes to stdout.
+    pub fn format_options(&self, global: &GlobalArgs) -> FormatOptions {
+        FormatOptions {
+            color: self.output.is_none() && global.color.enabled(&std::io::stdout()),
+            group_by: self.group_by,
+            json_compact: self.json_compact,
+            include_clean: self.include_clean,
+            details: self.details,
+            lang: global.lang(),
+        }
+    }
+
+    /// The state directory, or `None` if persistence is disabled
+    pub fn state(&self) -> Option<StateDir> {
+        self.state_dir.as_ref().map(StateDir::new)
+    }
+
+    /// The result cache, if `--cache-ttl` is set and there's a state
+    /// directory to keep it in
+    pub fn result_cache(&self) -> Option<Arc<ResultCache>> {
+        let ttl = self.cache_ttl.filter(|_| !self.no_cache)?;
+        let Some(state) = self.state() else {
+            // simulated and fixture runs turn the state off themselves
+            let message = match (&self.simulate, &self.fixtures) {
+                (Some(_), _) => "--cache-ttl has no effect with --simulate",
+                (_, Some(_)) => "--cache-ttl has no effect with --fixtures",
+                (None, None) => "--cache-ttl has no effect with --no-state",
+            };
+            self.warnings.warn(Condition::IgnoredOption, message);
+            return None;
+        };
+        match state.result_cache_path() {
+            Ok(path) => Some(Arc::new(ResultCache::load(path, ttl))),
+            Err(err) => {
+                warn!(%err, "couldn't open the result cache");
+                None
+            }
+        }
+    }
+
+    /// The `--search-url` to find servers by name with
+    pub fn search(&self) -> eyre::Result<Search> {
+        Search::new(&self.search_url).ok_or_else(|| {
+            eyre::eyre!("--search-url needs a {{query}} placeholder to put the name in")
+        })
+    }
+
+    /// The `--local-dataset` dump, read the first time it's needed
+    fn dataset(&self) -> eyre::Result<Arc<Dataset>> {
+        if let Some(dataset) = self.dataset.get() {
+            return Ok(Arc::clone(dataset));
+        }
+        let path = self.local_dataset.as_ref().expect("--local-dataset is set");
+        let dataset = Dataset::read(path)
+            .with_context(|| format!("couldn't read --local-dataset {}", path.display()))?;
+        let age =
+            humantime::format_duration(Duration::from_secs(dataset.age().as_secs() / 60 * 60));
+        if dataset.age() > STALE_LISTING {
+            self.warnings.warn(
+                Condition::Degraded,
+                format!("the --local-dataset dump is {age} old, servers added to the dataset since aren't in it"),
+            );
+        }
+        info!(
+            servers = dataset.len(),
+            "local dataset from {}, {age} old", dataset.source
+        );
+        Ok(Arc::clone(self.dataset.get_or_init(|| Arc::new(dataset))))
+    }
+
+    /// Where to save raw responses for `--dump-dir`, creating the directory
+    pub fn response_dump(&self, backends: usize) -> eyre::Result<Option<Arc<ResponseDump>>> {
+        let Some(dir) = &self.dump_dir else {
+            return Ok(None);
+        };
+        let dump = ResponseDump::new(dir, backends, self.dump_overwrite)
+            .with_context(|| format!("couldn't create dump directory {}", dir.display()))?;
+        Ok(Some(Arc::new(dump)))
+    }
+
+    pub fn build_runtime(&self) -> std::io::Result<Runtime> {
+        let current_thread = match self.runtime {
+            RuntimeChoice::Auto => self.concurrency <= 1,
+            RuntimeChoice::CurrentThread => true,
+            RuntimeChoice::MultiThread => false,
+        };
+        let mut builder = if current_thread {
+            debug!("using current-thread runtime");
+            runtime::Builder::new_current_thread()
+        } else {
+            debug!("using multi-thread runtime");
+            runtime::Builder::new_multi_thread()
+        };
+        builder.enable_all().build()
+    }
+
+    pub fn ca_certs(&self) -> eyre::Result<Vec<Certificate>> {
+        self.ca_cert
+            .iter()
+            .map(|path| {
+                let pem = std::fs::read(path)
+                    .with_context(|| format!("couldn't read certificate {}", path.display()))?;
+                Certificate::from_pem(&pem)
+                    .with_context(|| format!("couldn't parse certificate {}", path.display()))
+            })
+            .collect()
+    }
+
+    /// `--resolve` as host names and the addresses they're pinned to
+    pub fn resolve_overrides(&self) -> eyre::Result<Vec<(String, IpAddr)>> {
+        self.resolve
+            .iter()
+            .map(|entry| {
+                let (host, ip) = entry
+                    .split_once(':')
+                    .filter(|(host, _)| !host.is_empty())
+                    .ok_or_else(|| eyre::eyre!("--resolve takes HOST:IP, not {entry:?}"))?;
+                let ip = ip.trim_start_matches('[').trim_end_matches(']');
+                let ip = ip
+                    .parse()
+                    .with_context(|| format!("{ip:?} in --resolve {entry} isn't an IP address"))?;
+                Ok((host.to_ascii_lowercase(), ip))
+            })
+            .collect()
+    }
+
+    /// The `--proxy`, or a SOCKS proxy from the environment. The password is
+    /// never taken from the URL here, only from `--proxy-password-file` or
+    /// `SPY_PET_PROXY_PASSWORD`.
+    pub fn proxy(&self) -> eyre::Result<Option<Proxy>> {
+        if self.tor {
+            if self.proxy.is_some() {
+                eyre::bail!("--tor and --proxy can't both be set");
+            }
+            let url = format!("socks5h://{}", self.tor_socks);
+            let proxy = Proxy::new(&url, None).context("invalid --tor-socks")?;
+            return Ok(Some(proxy));
+        }
+        let Some(url) = &self.proxy else {
+            return Proxy::socks_from_env().context("invalid proxy in the environment");
+        };
+        if Url::parse(url).is_ok_and(|url| url.password().is_some()) {
+            eyre::bail!(
+                "--proxy has a password in it; give it in --proxy-password-file or {PROXY_PASSWORD_ENV} instead"
+            );
+        }
+        let password = credentials::read_secret(
+            "proxy password",
+            self.proxy_password_file.as_deref(),
+            PROXY_PASSWORD_ENV,
+        )?;
+        let proxy = Proxy::new(url, password.as_ref().map(|(password, _)| password))
+            .context("invalid --proxy")?;
+        debug!(%proxy, "sending requests through a proxy");
+        Ok(Some(proxy))
+    }
+
+    /// Tor circuit rotation, if `--new-circuit-every` or
+    /// `--new-circuit-on-429` asks for it
+    pub fn circuit_rotation(&self) -> eyre::Result<Option<Arc<CircuitRotation>>> {
+        if !self.tor || (self.new_circuit_every.is_none() && !self.new_circuit_on_429) {
+            return Ok(None);
+        }
+        if self.new_circuit_every == Some(0) {
+            eyre::bail!("--new-circuit-every has to be at least 1");
+        }
+        let password = credentials::read_secret(
+            "Tor control password",
+            self.tor_password_file.as_deref(),
+            TOR_PASSWORD_ENV,
+        )?;
+        let control = TorControl::new(&self.tor_control, password.map(|(password, _)| password));
+        Ok(Some(CircuitRotation::new(
+            control,
+            self.new_circuit_every,
+            self.new_circuit_on_429,
+        )))
+    }
+
+    /// The `--doh` resolver. Its own requests go out like the checks', with
+    /// the same certificates and `--resolve` overrides.
+    pub fn doh_resolver(&self, options: &CheckOptions) -> eyre::Result<Option<DohResolver>> {
+        let Some(server) = &self.doh else {
+            return Ok(None);
+        };
+        let server = Url::parse(server).with_context(|| format!("--doh {server} isn't a URL"))?;
+        match server.scheme() {
+            "https" => {}
+            "http" => {
+                self.warnings.warn(
+                    Condition::Degraded,
+                    format!("--doh {server} isn't HTTPS, so lookups aren't private"),
+                );
+            }
+            scheme => eyre::bail!("--doh needs an https URL, not {scheme}"),
+        }
+        if server
+            .domain()
+            .is_some_and(|host| !options.resolve.iter().any(|(pinned, _)| pinned == host))
+        {
+            info!("{server} will be looked up with the system resolver; give it as an IP address or with --resolve to avoid that");
+        }
+        let client = build_keyless_client(options).context("couldn't set up the --doh client")?;
+        Ok(Some(DohResolver::new(server, client)))
+    }
+
+    pub fn resolve(
+        args: CheckArgs,
+        global: &GlobalArgs,
+        matches: &ArgMatches,
+        file: FileConfig,
+    ) -> Self {
+        // simulated and fixture results stay out of the history and caches
+        let state_dir = match args.simulate.is_some() || args.fixtures.is_some() {
+            true => None,
+            false => resolve_state_dir(global, &file),
+        };
+        let mut simulation = args.simulate_profile.into_profile();
+        if let Some(latency) = args.simulate_latency {
+            simulation.latency = (latency / 2, latency * 3 / 2);
+        }
+        if let Some(compromised) = args.simulate_compromised {
+            simulation.compromised = compromised / 100.0;
+        }
+        let strict = pick(matches, "strict", args.strict, file.strict);
+        let warnings = Arc::new(Warnings::new(strict));
+        file.warn_unknown(&warnings);
+        let request = args.request;
+        let (discord_webhook, discord_webhook_file) = value_or_file(
+            (args.discord_webhook, args.discord_webhook_file),
+            (file.discord_webhook, file.discord_webhook_file),
+        );
+        let (notify_url, notify_url_file) = value_or_file(
+            (args.notify_url, args.notify_url_file),
+            (file.notify_url, file.notify_url_file),
+        );
+        let formats = pick(matches, "format", args.format, file.format.map(|f| vec![f]));
+        let outputs = match args.output.is_empty() {
+            true => file.output.into_iter().collect(),
+            false => args.output,
+        };
+        let mut outputs = output_pairs(formats, outputs).into_iter();
+        let ExtraOutput { format, output } = outputs.next().unwrap_or(ExtraOutput {
+            format: Format::Plain,
+            output: None,
+        });
+        let include_users = args.include_users || file.include_users.unwrap_or(false);
+        // `--channels` is `--kind channels --ids`
+        let channels = !args.channel_ids.is_empty();
+        let index_given = file.index_path.is_some()
+            || (matches.try_contains_id("index_path").is_ok()
+                && matches.value_source("index_path") != Some(ValueSource::DefaultValue));
+        let mut index_path = pick(
+            matches,
+            "index_path",
+            args.index_path,
+            file.index_path.map(IndexPaths::into_vec),
+        );
+        let invites = pick(matches, "invites", args.invites, file.invites);
+        // only the invites, unless an index is asked for as well
+        if !invites.is_empty() && !index_given {
+            index_path.clear();
+        }
+        let labelled = index_path.iter().any(|index| index.label.is_some());
+
+        Self {
+            concurrency: pick(
+                matches,
+                "concurrency",
+                request.concurrency,
+                file.concurrency,
+            ),
+            adaptive: pick(matches, "adaptive", request.adaptive, file.adaptive),
+            retries: pick(matches, "retries", request.retries, file.retries),
+            backoff: pick(matches, "backoff", request.backoff, file.backoff),
+            retry_passes: pick(
+                matches,
+                "retry_passes",
+                request.retry_passes,
+                file.retry_passes,
+            ),
+            pool_size: request.pool_size.or(file.pool_size),
+            timeout: pick(matches, "timeout", request.timeout, file.timeout),
+            delay: request.delay.or(file.delay),
+            jitter: request.jitter.or(file.jitter),
+            index_path,
+            index_format: pick(
+                matches,
+                "index_format",
+                args.index_format,
+                file.index_format,
+            ),
+            no_autodetect: pick(
+                matches,
+                "no_autodetect",
+                args.no_autodetect,
+                file.no_autodetect,
+            ),
+            from_dce: args.from_dce.or(file.from_dce),
+            from_data_package: args.from_data_package.or(file.from_data_package),
+            include_users,
+            from_discord: pick(
+                matches,
+                "from_discord",
+                args.from_discord,
+                file.from_discord,
+            ),
+            token_type: pick(matches, "token_type", args.token_type, file.token_type),
+            ids: match channels {
+                true => args.channel_ids,
+                false => pick(matches, "ids", args.ids, file.ids),
+            },
+            invites,
+            only: pick(matches, "only", args.only, file.only),
+            exclude_id: pick(matches, "exclude_id", args.exclude_id, file.exclude_id),
+            name_filter: args.name_filter.or(file.name_filter),
+            shard: args.shard.or(file.shard),
+            kind: match channels {
+                true => CheckKind::Channels,
+                false => pick(matches, "kind", args.kind, file.kind),
+            },
+            format,
+            backend: pick(
+                matches,
+                "backend",
+                request.backend,
+                file.backend.map(Backends::into_vec),
+            ),
+            url_template: request.url_template.or(file.url_template),
+            match_compromised: request.match_compromised.or(file.match_compromised),
+            ca_cert: pick(matches, "ca_cert", request.ca_cert, file.ca_cert),
+            insecure: pick(matches, "insecure", request.insecure, file.insecure),
+            doh: request.doh.or(file.doh),
+            proxy: request.proxy.or(file.proxy),
+            proxy_password_file: request.proxy_password_file.or(file.proxy_password_file),
+            tor: pick(matches, "tor", request.tor, file.tor),
+            tor_socks: pick(matches, "tor_socks", request.tor_socks, file.tor_socks),
+            tor_control: pick(
+                matches,
+                "tor_control",
+                request.tor_control,
+                file.tor_control,
+            ),
+            tor_password_file: request.tor_password_file.or(file.tor_password_file),
+            new_circuit_every: request.new_circuit_every.or(file.new_circuit_every),
+            new_circuit_on_429: pick(
+                matches,
+                "new_circuit_on_429",
+                request.new_circuit_on_429,
+                file.new_circuit_on_429,
+            ),
+            resolve: pick(matches, "resolve", request.resolve, file.resolve),
+            api_key_file: request.api_key_file.or(file.api_key_file),
+            api_key_header: pick(
+                matches,
+                "api_key_header",
+                request.api_key_header,
+                file.api_key_header,
+            ),
+            api_key: OnceLock::new(),
+            discord_token: OnceLock::new(),
+            user_agent: request.user_agent.or(file.user_agent),
+            header: match request.header.is_empty() {
+                true => file.header.unwrap_or_default(),
+                false => request.header,
+            },
+            runtime: pick(matches, "runtime", request.runtime, file.runtime),
+            state_dir,
+            output,
+            outputs: outputs.collect(),
+            output_mode: match (
+                args.append || file.append.unwrap_or(false),
+                args.atomic || file.atomic.unwrap_or(false),
+            ) {
+                (_, true) => OutputMode::Atomic,
+                (true, false) => OutputMode::Append,
+                (false, false) => OutputMode::Replace,
+            },
+            encrypt: match args.encrypt.is_empty() {
+                true => file.encrypt.unwrap_or_default(),
+                false => args.encrypt,
+            },
+            passphrase_file: args.passphrase_file.or(file.passphrase_file),
+            #[cfg(feature = "encrypt")]
+            recipients: OnceLock::new(),
+            sign: args.sign.or(file.sign),
+            #[cfg(feature = "sign")]
+            signing_key: OnceLock::new(),
+            // reports with users in them are sectioned by kind, and those of
+            // labelled indexes by label, unless asked otherwise
+            group_by: args
+                .group_by
+                .or(file.group_by)
+                .or(include_users.then_some(GroupBy::Kind))
+                .or(labelled.then_some(GroupBy::Label)),
+            template: args.template.or(file.template),
+            sort_by: args.sort_by.or(file.sort_by),
+            json_compact: args.json_compact || file.json_compact.unwrap_or(false),
+            only_compromised: args.only_compromised || file.only_compromised.unwrap_or(false),
+            include_clean: args.include_clean || file.include_clean.unwrap_or(false),
+            details: args.details || file.details.unwrap_or(false),
+            window: (args.since.is_some() || args.until.is_some()).then_some(Window {
+                since: args.since,
+                until: args.until,
+            }),
+            since_mode: args.since_mode,
+            watch: args.watch.or(file.watch),
+            #[cfg(feature = "metrics")]
+            metrics_listen: args.metrics_listen.or(file.metrics_listen),
+            max_errors: args.max_errors.or(file.max_errors),
+            max_error_rate: args.max_error_rate.or(file.max_error_rate),
+            fail_fast: args.fail_fast.or(file.fail_fast),
+            fail_on: args.fail_on.or(file.fail_on),
+            resume: args.resume,
+            baseline: args.baseline,
+            strict,
+            warnings,
+            simulate: args.simulate,
+            simulation,
+            offline: args.offline,
+            fixtures: args.fixtures,
+            local_dataset: args.local_dataset.or(file.local_dataset),
+            dataset: OnceLock::new(),
+            search_url: pick(matches, "search_url", args.search_url, file.search_url),
+            cache_ttl: args.cache_ttl.or(file.cache_ttl),
+            no_cache: args.no_cache,
+            dump_dir: args.dump_dir.or(file.dump_dir),
+            dump_overwrite: args.dump_overwrite || file.dump_overwrite.unwrap_or(false),
+            deep_scan: pick(matches, "deep_scan", args.deep_scan, file.deep_scan),
+            batch_size: args.batch_size.or(file.batch_size),
+            cooldown: args.cooldown.or(file.cooldown),
+            include_headers: pick(
+                matches,
+                "include_headers",
+                args.include_headers,
+                file.include_headers,
+            ),
+            max_response_size: pick(
+                matches,
+                "max_response_size",
+                args.max_response_size,
+                file.max_response_size,
+            ),
+            prefilter: args.prefilter.or(file.prefilter),
+            verify_all: pick(matches, "verify_all", args.verify_all, file.verify_all),
+            min_shared: args.min_shared.or(file.min_shared),
+            min_severity: args.min_severity.or(file.min_severity),
+            scan_members: pick(
+                matches,
+                "scan_members",
+                args.scan_members,
+                file.scan_members,
+            ),
+            bot_list: pick(matches, "bot_list", args.bot_list, file.bot_list),
+            token_file: args.token_file.or(file.token_file),
+            notify_on: args.notify_on.or(file.notify_on),
+            discord_webhook: discord_webhook.map(Secret::new),
+            discord_webhook_file,
+            notify_url: notify_url.map(Secret::new),
+            notify_url_file,
+            notify_method: pick(
+                matches,
+                "notify_method",
+                args.notify_method,
+                file.notify_method,
+            ),
+            notify_header: match args.notify_header.is_empty() {
+                true => file.notify_header.unwrap_or_default(),
+                false => args.notify_header,
+            },
+            notify_header_file: args.notify_header_file.or(file.notify_header_file),
+            notify_retries: pick(
+                matches,
+                "notify_retries",
+                args.notify_retries,
+                file.notify_retries,
+            ),
+            desktop_notify: pick(
+                matches,
+                "desktop_notify",
+                args.desktop_notify,
+                file.desktop_notify,
+            ),
+            email_to: match args.email_to.is_empty() {
+                true => file.email_to.unwrap_or_default(),
+                false => args.email_to,
+            },
+            email_from: args.email_from.or(file.email_from),
+            email_format: pick(
+                matches,
+                "email_format",
+                args.email_format,
+                file.email_format,
+            ),
+            smtp_server: args.smtp_server.or(file.smtp_server),
+            smtp_security: pick(
+                matches,
+                "smtp_security",
+                args.smtp_security,
+                file.smtp_security,
+            ),
+            smtp_username: args.smtp_username.or(file.smtp_username),
+            smtp_password_file: args.smtp_password_file.or(file.smtp_password_file),
+            deep_endpoint: pick(
+                matches,
+                "deep_endpoint",
+                args.deep_endpoint,
+                file.deep_endpoint,
+            ),
+            fallback_web: args.fallback_web.or(file.fallback_web),
+            verify: pick(matches, "verify", args.verify, file.verify),
+            verify_delay: pick(
+                matches,
+                "verify_delay",
+                args.verify_delay,
+                file.verify_delay,
+            ),
+            verify_proxy: args.verify_proxy.or(file.verify_proxy),
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{self, bail, Context};
+use spy_pet_checker::secret::Secret;
+use tracing::debug;
+
+#[cfg(feature = "keyring")]
+const KEYRING_SERVICE: &str = "spy-pet-checker";
+#[cfg(feature = "keyring")]
+const DISCORD_TOKEN_ENTRY: &str = "discord-token";
+#[cfg(feature = "keyring")]
+const API_KEY_ENTRY: &str = "api-key";
+
+pub const DISCORD_TOKEN_ENV: &str = "SPY_PET_DISCORD_TOKEN";
+/// The name other Discord tools read the token from, tried after ours
+const DISCORD_TOKEN_COMMON_ENV: &str = "DISCORD_TOKEN";
+pub const API_KEY_ENV: &str = "SPY_PET_API_KEY";
+pub const PROXY_PASSWORD_ENV: &str = "SPY_PET_PROXY_PASSWORD";
+pub const TOR_PASSWORD_ENV: &str = "SPY_PET_TOR_PASSWORD";
+pub const SMTP_PASSWORD_ENV: &str = "SPY_PET_SMTP_PASSWORD";
+pub const DISCORD_WEBHOOK_ENV: &str = "SPY_PET_DISCORD_WEBHOOK";
+pub const NOTIFY_URL_ENV: &str = "SPY_PET_NOTIFY_URL";
+pub const OAUTH_CLIENT_SECRET_ENV: &str = "SPY_PET_OAUTH_CLIENT_SECRET";
+#[cfg(feature = "encrypt")]
+pub const PASSPHRASE_ENV: &str = "SPY_PET_PASSPHRASE";
+
+/// The secrets kept in the keyring
+#[derive(Clone, Copy)]
+pub enum Credential {
+    DiscordToken,
+    ApiKey,
+}
+
+impl Credential {
+    pub fn name(self) -> &'static str {
+        match self {
+            Credential::DiscordToken => "Discord token",
+            Credential::ApiKey => "API key",
+        }
+    }
+
+    pub fn env(self) -> &'static str {
+        match self {
+            Credential::DiscordToken => DISCORD_TOKEN_ENV,
+            Credential::ApiKey => API_KEY_ENV,
+        }
+    }
+
+    /// The flag that reads it from a file
+    #[cfg(feature = "keyring")]
+    fn file_flag(self) -> &'static str {
+        match self {
+            Credential::DiscordToken => "--token-file",
+            Credential::ApiKey => "--api-key-file",
+        }
+    }
+
+    #[cfg(feature = "keyring")]
+    fn entry(self) -> eyre::Result<keyring::Entry> {
+        let name = match self {
+            Credential::DiscordToken => DISCORD_TOKEN_ENTRY,
+            Credential::ApiKey => API_KEY_ENTRY,
+        };
+        keyring::Entry::new(KEYRING_SERVICE, name).map_err(|err| keyring_error(err, self))
+    }
+}
+
+/// Where a secret was found
+#[derive(Clone)]
+pub enum SecretSource {
+    File(PathBuf),
+    Prompt,
+    Env(&'static str),
+    #[cfg(feature = "keyring")]
+    Keyring,
+}
+
+impl fmt::Display for SecretSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretSource::File(path) => write!(f, "file {}", path.display()),
+            SecretSource::Prompt => write!(f, "terminal prompt"),
+            SecretSource::Env(var) => write!(f, "environment variable {var}"),
+            #[cfg(feature = "keyring")]
+            SecretSource::Keyring => write!(f, "system keyring"),
+        }
+    }
+}
+
+/// Reads a secret as given, from `file` (`-` prompts) or the `env` variable
+fn read_raw(
+    what: &str,
+    file: Option<&Path>,
+    env: &'static str,
+) -> eyre::Result<Option<(String, SecretSource)>> {
+    Ok(Some(match file {
+        Some(path) if path == Path::new("-") => (
+            rpassword::prompt_password(format!("{what}: "))
+                .with_context(|| format!("couldn't read the {what} from the terminal"))?,
+            SecretSource::Prompt,
+        ),
+        Some(path) => (
+            std::fs::read_to_string(path)
+                .with_context(|| format!("couldn't read {what} file {}", path.display()))?,
+            SecretSource::File(path.to_owned()),
+        ),
+        None => match std::env::var(env) {
+            Ok(value) => (value, SecretSource::Env(env)),
+            Err(_) => return Ok(None),
+        },
+    }))
+}
+
+/// Reads a secret the way every credential flag takes one: from `file`
+/// (`-` prompts on the terminal without echo), or else from the `env`
+/// variable. Secrets are never accepted as plain command line values.
+pub fn read_secret(
+    what: &str,
+    file: Option<&Path>,
+    env: &'static str,
+) -> eyre::Result<Option<(Secret<String>, SecretSource)>> {
+    let Some((value, source)) = read_raw(what, file, env)? else {
+        return Ok(None);
+    };
+    let value = value.trim();
+    if value.is_empty() {
+        bail!("{what} from {source} is empty");
+    }
+    if value.contains(char::is_whitespace) {
+        bail!("{what} from {source} contains whitespace");
+    }
+    let secret = Secret::new(value.to_owned());
+    debug!(%source, value = ?secret, "loaded {what}");
+    Ok(Some((secret, source)))
+}
+
+/// Like [`read_secret`], for a passphrase, which may have spaces in it: only
+/// the line break at the end of a file is dropped
+#[cfg(feature = "encrypt")]
+pub fn read_passphrase(
+    what: &str,
+    file: Option<&Path>,
+    env: &'static str,
+) -> eyre::Result<Option<(Secret<String>, SecretSource)>> {
+    let Some((value, source)) = read_raw(what, file, env)? else {
+        return Ok(None);
+    };
+    let value = value.trim_end_matches(['\n', '\r']);
+    if value.is_empty() {
+        bail!("{what} from {source} is empty");
+    }
+    let secret = Secret::new(value.to_owned());
+    debug!(%source, value = ?secret, "loaded {what}");
+    Ok(Some((secret, source)))
+}
+
+/// Explains the errors that mean there is no keyring to talk to, e.g. on a
+/// headless machine without a Secret Service
+#[cfg(feature = "keyring")]
+fn keyring_error(err: keyring::Error, credential: Credential) -> eyre::Report {
+    match err {
+        keyring::Error::NoStorageAccess(_) | keyring::Error::PlatformFailure(_) => eyre::eyre!(err)
+            .wrap_err(format!(
+                "no usable system keyring; use {} or {} instead",
+                credential.file_flag(),
+                credential.env()
+            )),
+        err => eyre::eyre!(err).wrap_err("keyring error"),
+    }
+}
+
+/// `credential` from `file`, the environment or the keyring, in that order.
+/// The Discord token is also read from `DISCORD_TOKEN`.
+pub fn load(
+    credential: Credential,
+    file: Option<&Path>,
+) -> eyre::Result<Option<(Secret<String>, SecretSource)>> {
+    if let Some(found) = read_secret(credential.name(), file, credential.env())? {
+        return Ok(Some(found));
+    }
+    if let Credential::DiscordToken = credential {
+        if let Some(found) = read_secret(credential.name(), None, DISCORD_TOKEN_COMMON_ENV)? {
+            return Ok(Some(found));
+        }
+    }
+
+    #[cfg(feature = "keyring")]
+    if let Some(secret) = stored(credential)? {
+        return Ok(Some((secret, SecretSource::Keyring)));
+    }
+
+    Ok(None)
+}
+
+/// The environment variables that are set for `credential`, and so are
+/// read before the keyring
+#[cfg(feature = "keyring")]
+pub fn set_in_env(credential: Credential) -> Vec<&'static str> {
+    let names: &[&'static str] = match credential {
+        Credential::DiscordToken => &[DISCORD_TOKEN_ENV, DISCORD_TOKEN_COMMON_ENV],
+        Credential::ApiKey => &[API_KEY_ENV],
+    };
+    names
+        .iter()
+        .copied()
+        .filter(|name| std::env::var_os(name).is_some())
+        .collect()
+}
+
+/// The Discord token from `token_file`, the environment or the keyring, in
+/// that order
+pub fn discord_token(
+    token_file: Option<&Path>,
+) -> eyre::Result<Option<(Secret<String>, SecretSource)>> {
+    load(Credential::DiscordToken, token_file)
+}
+
+/// The secret in the keyring, if one is stored
+#[cfg(feature = "keyring")]
+pub fn stored(credential: Credential) -> eyre::Result<Option<Secret<String>>> {
+    match credential.entry()?.get_password() {
+        Ok(secret) => Ok(Some(Secret::new(secret))),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(err) => Err(keyring_error(err, credential)),
+    }
+}
+
+#[cfg(feature = "keyring")]
+pub fn store(credential: Credential, secret: &Secret<String>) -> eyre::Result<()> {
+    credential
+        .entry()?
+        .set_password(secret.expose())
+        .map_err(|err| keyring_error(err, credential))
+}
+
+/// Removes the stored secret, returning whether there was one
+#[cfg(feature = "keyring")]
+pub fn clear(credential: Credential) -> eyre::Result<bool> {
+    match credential.entry()?.delete_credential() {
+        Ok(()) => Ok(true),
+        Err(keyring::Error::NoEntry) => Ok(false),
+        Err(err) => Err(keyring_error(err, credential)),
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use tracing::{debug, warn};
+
+use crate::backend::{fetch, parse_json};
+use crate::CheckError;
+
+/// Bumped whenever the file layout changes
+const VERSION: u32 = 1;
+
+/// Where `dump` reads spy.pet's server list from, a page at a time
+pub const DEFAULT_LIST_URL: &str = "https://api.spy.pet/servers?page={page}";
+
+#[derive(Error, Debug)]
+pub enum DatasetError {
+    #[error("couldn't fetch page {page}: {source}")]
+    Fetch {
+        page: u32,
+        #[source]
+        source: CheckError,
+    },
+
+    #[error("page {page} has an entry without an ID: {entry}")]
+    NoId { page: u32, entry: String },
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error("not a dataset file: {0}")]
+    Invalid(#[from] serde_json::Error),
+
+    #[error("the dataset file is from another version (layout {0}), run dump again")]
+    Outdated(u32),
+}
+
+/// A copy of the whole server list of a dataset, from `dump`, to check
+/// indexes against without a request per server. Each server has the
+/// answer the list had for it, or `true` if the list only had its ID.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Dataset {
+    version: u32,
+    /// The URL it was downloaded from
+    pub source: String,
+    pub fetched_at: DateTime<Utc>,
+    servers: BTreeMap<String, Value>,
+}
+
+fn id(value: &Value) -> Option<String> {
+    match value {
+        Value::String(id) => Some(id.clone()),
+        Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+impl Dataset {
+    /// Downloads the list from `url`, requesting `{page}` from 1 until a page
+    /// comes back empty, `null`, `false` or 404, or has nothing new, and at
+    /// most `max_pages` of them. A URL without `{page}` is one page.
+    ///
+    /// A page is an array of IDs or of objects with an `id`, or an object
+    /// keyed by ID.
+    pub async fn download(
+        client: &Client,
+        url: &str,
+        max_pages: u32,
+    ) -> Result<Self, DatasetError> {
+        let mut servers = BTreeMap::new();
+        let paged = url.contains("{page}");
+        for page in 1..=max_pages {
+            let (status, text) = fetch(client, &url.replace("{page}", &page.to_string()))
+                .await
+                .map_err(|source| DatasetError::Fetch { page, source })?;
+            if status == StatusCode::NOT_FOUND && paged {
+                break;
+            }
+            if !status.is_success() {
+                let source = CheckError::HttpStatus(status);
+                return Err(DatasetError::Fetch { page, source });
+            }
+            let entries =
+                match parse_json(&text).map_err(|source| DatasetError::Fetch { page, source })? {
+                    Value::Array(items) => items
+                        .into_iter()
+                        .map(|item| {
+                            let id = match &item {
+                                Value::Object(fields) => fields.get("id").and_then(id),
+                                item => id(item),
+                            };
+                            match (id, item) {
+                                (Some(id), item @ Value::Object(_)) => Ok((id, item)),
+                                (Some(id), _) => Ok((id, Value::Bool(true))),
+                                (None, item) => Err(DatasetError::NoId {
+                                    page,
+                                    entry: item.to_string(),
+                                }),
+                            }
+                        })
+                        .collect::<Result<Vec<_>, _>>()?,
+                    Value::Object(fields) => fields.into_iter().collect(),
+                    _ => Vec::new(),
+                };
+            let before = servers.len();
+            servers.extend(entries);
+            debug!(page, servers = servers.len(), "read a page of the list");
+            // an API that ignores the page number answers the same page again
+            if !paged || servers.len() == before {
+                break;
+            }
+            if page == max_pages {
+                warn!(max_pages, "stopped at the page limit, the list may go on");
+            }
+        }
+        Ok(Self {
+            version: VERSION,
+            source: url.to_owned(),
+            fetched_at: Utc::now(),
+            servers,
+        })
+    }
+
+    pub fn read(path: &Path) -> Result<Self, DatasetError> {
+        let dataset: Self = serde_json::from_slice(&std::fs::read(path)?)?;
+        match dataset.version {
+            VERSION => Ok(dataset),
+            version => Err(DatasetError::Outdated(version)),
+        }
+    }
+
+    /// Writes the dataset to `path`, replacing the file atomically
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(self)?)?;
+        std::fs::rename(tmp, path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.servers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.servers.is_empty()
+    }
+
+    /// What the list says about `id`, if it's in it
+    pub fn get(&self, id: &str) -> Option<&Value> {
+        self.servers.get(id)
+    }
+
+    pub fn age(&self) -> Duration {
+        (Utc::now() - self.fetched_at).to_std().unwrap_or_default()
+    }
+}
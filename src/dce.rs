@@ -0,0 +1,131 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{self, bail, Context};
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, Visitor};
+use serde::Deserialize;
+use spy_pet_checker::warnings::{Condition, Warnings};
+use tracing::debug;
+
+/// The part of a DiscordChatExporter JSON export we care about
+#[derive(Deserialize)]
+struct Guild {
+    id: String,
+    name: String,
+}
+
+/// DCE puts the guild first, so parsing stops there instead of going
+/// through the messages. serde_json insists on reading a map to its end once
+/// the visitor returns, so the visitor bails out with this error after
+/// storing the guild.
+const STOP: &str = "spy-pet-checker: found guild";
+
+struct GuildSeed<'a>(&'a mut Option<Guild>);
+
+impl<'de> DeserializeSeed<'de> for GuildSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for GuildSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a DiscordChatExporter export")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "guild" {
+                *self.0 = Some(map.next_value()?);
+                return Err(de::Error::custom(STOP));
+            }
+            map.next_value::<IgnoredAny>()?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the guild of one export, without loading the rest of the file
+fn read_guild(path: &Path) -> eyre::Result<Guild> {
+    let file = File::open(path).context("couldn't open file")?;
+    let mut guild = None;
+    let mut deserializer = serde_json::Deserializer::from_reader(BufReader::new(file));
+    match GuildSeed(&mut guild).deserialize(&mut deserializer) {
+        Err(_) if guild.is_some() => {}
+        Err(err) => return Err(err).context("not a JSON object"),
+        Ok(()) => {}
+    }
+    guild.ok_or_else(|| eyre::eyre!("no guild in file"))
+}
+
+/// Every `.json` file under `dir`
+fn json_files(dir: &Path, files: &mut Vec<PathBuf>) -> eyre::Result<()> {
+    let entries = std::fs::read_dir(dir)
+        .with_context(|| format!("couldn't read directory {}", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            json_files(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "json") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn expand(pattern: &str) -> eyre::Result<Vec<PathBuf>> {
+    let path = Path::new(pattern);
+    let mut files = Vec::new();
+    if path.is_dir() {
+        json_files(path, &mut files)?;
+    } else if path.exists() {
+        files.push(path.to_owned());
+    } else {
+        for entry in glob::glob(pattern).with_context(|| format!("invalid pattern {pattern}"))? {
+            files.push(entry?);
+        }
+        if files.is_empty() {
+            bail!("{pattern} doesn't exist or match any file");
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The guilds (id → name) of the DiscordChatExporter exports in `pattern`,
+/// which is a file, a directory or a glob. Files that aren't exports are
+/// reported to `warnings` and skipped.
+pub fn load_guilds(pattern: &str, warnings: &Warnings) -> eyre::Result<BTreeMap<String, String>> {
+    let files = expand(pattern)?;
+    let mut guilds = BTreeMap::new();
+    for path in &files {
+        match read_guild(path) {
+            // DM exports carry a placeholder guild
+            Ok(guild) if guild.id == "0" => debug!(path = %path.display(), "skipping DM export"),
+            Ok(guild) => {
+                guilds.insert(guild.id, guild.name);
+            }
+            Err(err) => {
+                warnings.warn(
+                    Condition::SkippedInput,
+                    format!(
+                        "skipping {}, not a DiscordChatExporter export: {err:#}",
+                        path.display()
+                    ),
+                );
+            }
+        }
+    }
+    debug!(files = files.len(), guilds = guilds.len(), "read exports");
+    if guilds.is_empty() {
+        bail!("no guilds found in the exports in {pattern}");
+    }
+    Ok(guilds)
+}
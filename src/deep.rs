@@ -0,0 +1,91 @@
+use std::collections::BTreeMap;
+
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::debug;
+
+use crate::backend::{fetch, parse_json, TemplateError, UrlTemplate};
+use crate::CheckError;
+
+/// Pages fetched from one endpoint at most, so an API that never runs out
+/// can't keep a check going forever
+pub const MAX_PAGES: u32 = 50;
+
+/// More about a compromised guild, from follow-up requests
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Details {
+    /// What each deep scan endpoint answered, keyed by its template. Paged
+    /// endpoints are collected into one array.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub deep: BTreeMap<String, Value>,
+}
+
+/// Follow-up requests made for guilds found compromised.
+///
+/// Each endpoint is a URL template with `{id}`, and optionally `{page}`: paged
+/// endpoints are requested from page 1 until one comes back empty, `null`,
+/// `false` or 404.
+pub struct DeepScan {
+    endpoints: Vec<String>,
+}
+
+impl DeepScan {
+    pub fn new(endpoints: Vec<String>) -> Result<Self, TemplateError> {
+        for endpoint in &endpoints {
+            UrlTemplate::validate(endpoint)?;
+        }
+        Ok(Self { endpoints })
+    }
+
+    pub fn endpoints(&self) -> &[String] {
+        &self.endpoints
+    }
+
+    pub(crate) async fn scan(&self, client: &Client, id: &str) -> Result<Details, CheckError> {
+        let id = utf8_percent_encode(id, NON_ALPHANUMERIC).to_string();
+        let mut details = Details::default();
+        for endpoint in &self.endpoints {
+            let url = endpoint.replace("{id}", &id);
+            let value = if url.contains("{page}") {
+                pages(client, &url).await?
+            } else {
+                page(client, &url).await?.unwrap_or(Value::Null)
+            };
+            details.deep.insert(endpoint.clone(), value);
+        }
+        Ok(details)
+    }
+}
+
+/// One page, or `None` if there's nothing there
+async fn page(client: &Client, url: &str) -> Result<Option<Value>, CheckError> {
+    let (status, text) = fetch(client, url).await?;
+    if status == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    if !status.is_success() {
+        return Err(CheckError::HttpStatus(status));
+    }
+    match parse_json(&text)? {
+        Value::Null | Value::Bool(false) => Ok(None),
+        Value::Array(items) if items.is_empty() => Ok(None),
+        value => Ok(Some(value)),
+    }
+}
+
+async fn pages(client: &Client, url: &str) -> Result<Value, CheckError> {
+    let mut items = Vec::new();
+    for n in 1..=MAX_PAGES {
+        let Some(value) = page(client, &url.replace("{page}", &n.to_string())).await? else {
+            debug!(pages = n - 1, "reached the last page");
+            break;
+        };
+        match value {
+            Value::Array(page) => items.extend(page),
+            value => items.push(value),
+        }
+    }
+    Ok(Value::Array(items))
+}
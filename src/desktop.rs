@@ -0,0 +1,62 @@
+//! Native desktop notifications, through what each platform already has:
+//! `notify-send` on Linux and the BSDs, `osascript` on macOS and a PowerShell
+//! toast on Windows.
+
+use std::process::Command;
+
+use color_eyre::eyre::{self, Context};
+
+/// The scripts read the text from the environment, so nothing in a guild name
+/// has to be escaped for them
+#[cfg(target_os = "macos")]
+fn command(title: &str, body: &str) -> Command {
+    let mut command = Command::new("osascript");
+    command
+        .args([
+            "-e",
+            r#"display notification (system attribute "SPY_PET_BODY") with title (system attribute "SPY_PET_TITLE")"#,
+        ])
+        .env("SPY_PET_TITLE", title)
+        .env("SPY_PET_BODY", body);
+    command
+}
+
+#[cfg(windows)]
+fn command(title: &str, body: &str) -> Command {
+    // with PowerShell's own app ID, since toasts from an unregistered one
+    // aren't shown
+    const SCRIPT: &str = r#"
+[Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime] > $null
+$template = [Windows.UI.Notifications.ToastNotificationManager]::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::ToastText02)
+$text = $template.GetElementsByTagName('text')
+$text.Item(0).AppendChild($template.CreateTextNode($env:SPY_PET_TITLE)) > $null
+$text.Item(1).AppendChild($template.CreateTextNode($env:SPY_PET_BODY)) > $null
+$toast = [Windows.UI.Notifications.ToastNotification]::new($template)
+[Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier('{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\WindowsPowerShell\v1.0\powershell.exe').Show($toast)
+"#;
+    let mut command = Command::new("powershell");
+    command
+        .args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT])
+        .env("SPY_PET_TITLE", title)
+        .env("SPY_PET_BODY", body);
+    command
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn command(title: &str, body: &str) -> Command {
+    let mut command = Command::new("notify-send");
+    command.args(["--app-name=spy-pet-checker", "--", title, body]);
+    command
+}
+
+/// Pops up a notification with `title` and `body`
+pub fn show(title: &str, body: &str) -> eyre::Result<()> {
+    let output = command(title, body)
+        .output()
+        .context("couldn't run the notifier")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        eyre::bail!("the notifier failed ({}): {}", output.status, stderr.trim());
+    }
+    Ok(())
+}
@@ -0,0 +1,208 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{Response, RunReport, Status};
+
+/// One difference between two JSON documents. Paths are JSON pointers.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum FieldChange {
+    Added {
+        path: String,
+        value: Value,
+    },
+    Removed {
+        path: String,
+        value: Value,
+    },
+    Changed {
+        path: String,
+        from: Value,
+        to: Value,
+    },
+    /// An array grew or shrank; the elements involved are listed separately
+    Length {
+        path: String,
+        from: usize,
+        to: usize,
+    },
+}
+
+impl FieldChange {
+    pub fn path(&self) -> &str {
+        match self {
+            FieldChange::Added { path, .. }
+            | FieldChange::Removed { path, .. }
+            | FieldChange::Changed { path, .. }
+            | FieldChange::Length { path, .. } => path,
+        }
+    }
+}
+
+/// Fields left out of a diff: a bare name (`last_seen`) matches that key at
+/// any depth, a JSON pointer (`/guild/last_seen`) only that exact field
+#[derive(Clone, Debug, Default)]
+pub struct Ignore(Vec<String>);
+
+impl Ignore {
+    pub fn new(fields: impl IntoIterator<Item = String>) -> Self {
+        Self(fields.into_iter().collect())
+    }
+
+    fn matches(&self, path: &str, key: &str) -> bool {
+        self.0
+            .iter()
+            .any(|field| field == path || (!field.starts_with('/') && field == key))
+    }
+}
+
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// The differences from `old` to `new`. Objects are compared key by key and
+/// arrays element by element.
+pub fn diff_values(old: &Value, new: &Value, ignore: &Ignore) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    diff_at("", old, new, ignore, &mut changes);
+    changes
+}
+
+fn diff_at(path: &str, old: &Value, new: &Value, ignore: &Ignore, out: &mut Vec<FieldChange>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+            for key in keys {
+                let path = format!("{path}/{}", escape(key));
+                if ignore.matches(&path, key) {
+                    continue;
+                }
+                match (old.get(key), new.get(key)) {
+                    (Some(old), Some(new)) => diff_at(&path, old, new, ignore, out),
+                    (Some(value), None) => out.push(FieldChange::Removed {
+                        path,
+                        value: value.clone(),
+                    }),
+                    (None, Some(value)) => out.push(FieldChange::Added {
+                        path,
+                        value: value.clone(),
+                    }),
+                    (None, None) => unreachable!("key comes from one of the objects"),
+                }
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            if old.len() != new.len() {
+                out.push(FieldChange::Length {
+                    path: path.to_owned(),
+                    from: old.len(),
+                    to: new.len(),
+                });
+            }
+            for i in 0..old.len().max(new.len()) {
+                let path = format!("{path}/{i}");
+                match (old.get(i), new.get(i)) {
+                    (Some(old), Some(new)) => diff_at(&path, old, new, ignore, out),
+                    (Some(value), None) => out.push(FieldChange::Removed {
+                        path,
+                        value: value.clone(),
+                    }),
+                    (None, Some(value)) => out.push(FieldChange::Added {
+                        path,
+                        value: value.clone(),
+                    }),
+                    (None, None) => unreachable!("index is below one of the lengths"),
+                }
+            }
+        }
+        (old, new) if old != new => out.push(FieldChange::Changed {
+            path: path.to_owned(),
+            from: old.clone(),
+            to: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+/// What a [`GuildDiff`] amounts to, for sorting through them
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    /// Compromised now, and wasn't before or wasn't checked
+    NewlyCompromised,
+    /// Was compromised, clean now
+    NewlyClean,
+    /// Anything else: an answer that changed, a server checked only once
+    #[default]
+    Changed,
+}
+
+impl Change {
+    pub fn of(from: Option<Status>, to: Option<Status>) -> Self {
+        match (from, to) {
+            (from, Some(Status::Compromised)) if from != Some(Status::Compromised) => {
+                Self::NewlyCompromised
+            }
+            (Some(Status::Compromised), Some(Status::Clean)) => Self::NewlyClean,
+            _ => Self::Changed,
+        }
+    }
+}
+
+/// How one guild's result differs between two runs
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct GuildDiff {
+    pub guild_id: String,
+    pub guild_name: String,
+    pub source: String,
+    #[serde(default)]
+    pub change: Change,
+    /// `None` if the run didn't have a result for the guild
+    pub from: Option<Status>,
+    pub to: Option<Status>,
+    /// Differences in `api_response`, when both runs have one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<FieldChange>,
+}
+
+/// Every guild whose result changed between `old` and `new`, by ID and
+/// backend
+pub fn diff_runs(old: &RunReport, new: &RunReport, ignore: &Ignore) -> Vec<GuildDiff> {
+    fn index(report: &RunReport) -> BTreeMap<(&str, &str), &Response> {
+        report
+            .results
+            .iter()
+            .map(|r| ((r.guild_id.as_str(), r.source.as_str()), r))
+            .collect()
+    }
+    let (old, new) = (index(old), index(new));
+    let keys: BTreeSet<&(&str, &str)> = old.keys().chain(new.keys()).collect();
+
+    let mut diffs = Vec::new();
+    for key in keys {
+        let (before, after) = (old.get(key).copied(), new.get(key).copied());
+        let changes = match (before, after) {
+            (Some(before), Some(after)) => {
+                diff_values(&before.api_response, &after.api_response, ignore)
+            }
+            _ => Vec::new(),
+        };
+        let (from, to) = (before.map(Response::status), after.map(Response::status));
+        if from == to && changes.is_empty() {
+            continue;
+        }
+        let latest = after.or(before).expect("key comes from one of the runs");
+        diffs.push(GuildDiff {
+            guild_id: latest.guild_id.clone(),
+            guild_name: latest.guild_name.clone(),
+            source: latest.source.clone(),
+            change: Change::of(from, to),
+            from,
+            to,
+            changes,
+        });
+    }
+    diffs
+}
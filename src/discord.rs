@@ -0,0 +1,519 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, AUTHORIZATION, CONTENT_TYPE, RETRY_AFTER};
+use reqwest::{Client, Method, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::debug;
+
+use crate::notify::Payload;
+use crate::secret::Secret;
+use crate::{CheckError, Response};
+
+/// Discord's API
+pub const DISCORD_API: &str = "https://discord.com/api/v10";
+
+/// The most guilds Discord lists in one page
+const GUILD_PAGE_SIZE: usize = 200;
+
+/// 429s in a row on one request before giving up on it
+const MAX_RATE_LIMITS: u32 = 5;
+
+/// The slash command the bot answers
+pub const SLASH_COMMAND: &str = "spy-pet-check";
+
+/// Whose token it is, which decides how it's sent
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TokenType {
+    /// A user account's token, sent as it is
+    #[default]
+    User,
+    /// A bot's token, sent after `Bot `
+    Bot,
+    /// An OAuth2 access token from `auth`, sent after `Bearer `
+    Bearer,
+}
+
+impl TokenType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TokenType::User => "user",
+            TokenType::Bot => "bot",
+            TokenType::Bearer => "bearer",
+        }
+    }
+
+    fn authorization(self, token: &Secret<String>) -> String {
+        match self {
+            TokenType::User => token.expose().clone(),
+            TokenType::Bot => format!("Bot {}", token.expose()),
+            TokenType::Bearer => format!("Bearer {}", token.expose()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RateLimited {
+    /// Seconds, with a fractional part
+    retry_after: f64,
+}
+
+/// Requests `url` from Discord's API, waiting out rate limits: a 429 is tried
+/// again after the wait it asks for, and when the bucket is used up the next
+/// request waits for it to refill. Every other status is left to the caller.
+pub(crate) async fn get(
+    client: &Client,
+    url: &str,
+    query: &[(&str, &str)],
+    authorization: &str,
+) -> Result<(StatusCode, String), CheckError> {
+    request(client, Method::GET, url, query, None, Some(authorization)).await
+}
+
+/// Like [`get`], with any method, a JSON `body` and no token for the public
+/// endpoints
+async fn request(
+    client: &Client,
+    method: Method,
+    url: &str,
+    query: &[(&str, &str)],
+    body: Option<&Value>,
+    authorization: Option<&str>,
+) -> Result<(StatusCode, String), CheckError> {
+    let mut rate_limits = 0;
+    loop {
+        let mut request = client.request(method.clone(), url).query(query);
+        if let Some(authorization) = authorization {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+        if let Some(body) = body {
+            request = request
+                .header(CONTENT_TYPE, "application/json")
+                .body(body.to_string());
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let text = response.text().await?;
+        debug!(%status, size = text.len(), "got Discord response");
+
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            if rate_limits == MAX_RATE_LIMITS {
+                return Err(CheckError::RateLimited {
+                    retry_after: header_secs(&headers, RETRY_AFTER.as_str()),
+                });
+            }
+            rate_limits += 1;
+            let wait = rate_limit_wait(&headers, &text);
+            debug!(?wait, "rate limited by Discord, waiting");
+            tokio::time::sleep(wait).await;
+            continue;
+        }
+
+        // the bucket is used up, wait for it to refill before the next request
+        if headers
+            .get("x-ratelimit-remaining")
+            .is_some_and(|remaining| remaining == "0")
+        {
+            if let Some(wait) = header_secs(&headers, "x-ratelimit-reset-after") {
+                debug!(?wait, "rate limit bucket empty, waiting");
+                tokio::time::sleep(wait).await;
+            }
+        }
+        return Ok((status, text));
+    }
+}
+
+/// How long a 429 asks to wait, from its body or else its `Retry-After`
+fn rate_limit_wait(headers: &HeaderMap, text: &str) -> Duration {
+    serde_json::from_str::<RateLimited>(text)
+        .ok()
+        .and_then(|body| Duration::try_from_secs_f64(body.retry_after).ok())
+        .or_else(|| header_secs(headers, RETRY_AFTER.as_str()))
+        .unwrap_or(Duration::from_secs(1))
+}
+
+/// A header holding seconds, possibly fractional
+fn header_secs(headers: &HeaderMap, name: &str) -> Option<Duration> {
+    let secs: f64 = headers.get(name)?.to_str().ok()?.trim().parse().ok()?;
+    Duration::try_from_secs_f64(secs).ok()
+}
+
+#[derive(Deserialize)]
+struct PartialGuild {
+    id: String,
+    name: String,
+}
+
+/// The code of a Discord invite link: `discord.gg/<code>` or
+/// `discord.com/invite/<code>`, with or without the scheme
+pub fn invite_code(link: &str) -> Option<&str> {
+    let link = link.trim();
+    let rest = link
+        .strip_prefix("https://")
+        .or_else(|| link.strip_prefix("http://"))
+        .unwrap_or(link);
+    let rest = rest.strip_prefix("www.").unwrap_or(rest);
+    let code = [
+        "discord.gg/",
+        "discord.com/invite/",
+        "discordapp.com/invite/",
+    ]
+    .iter()
+    .find_map(|prefix| rest.strip_prefix(prefix))?;
+    let code = code.split(['/', '?', '#']).next()?;
+    let valid = |b: u8| b.is_ascii_alphanumeric() || b == b'-';
+    (!code.is_empty() && code.bytes().all(valid)).then_some(code)
+}
+
+#[derive(Deserialize)]
+struct Invite {
+    guild: Option<PartialGuild>,
+}
+
+/// Finds the guilds invite links are for, through Discord's public invite
+/// API, which needs no token
+pub struct InviteResolver {
+    api: String,
+}
+
+impl Default for InviteResolver {
+    fn default() -> Self {
+        Self {
+            api: DISCORD_API.to_owned(),
+        }
+    }
+}
+
+impl InviteResolver {
+    /// Talks to another API base URL instead of Discord's
+    pub fn with_api(mut self, api: impl Into<String>) -> Self {
+        self.api = api.into();
+        self
+    }
+
+    /// The ID and name of the guild the invite `code` is for, or `None` if
+    /// it's expired, made up, or for a group DM
+    pub async fn resolve(
+        &self,
+        client: &Client,
+        code: &str,
+    ) -> Result<Option<(String, String)>, CheckError> {
+        let url = format!("{}/invites/{code}", self.api);
+        let (status, text) = request(client, Method::GET, &url, &[], None, None).await?;
+        match status {
+            _ if status.is_success() => {
+                let invite: Invite =
+                    serde_json::from_str(&text).map_err(|_| CheckError::bad_body(&text))?;
+                Ok(invite.guild.map(|guild| (guild.id, guild.name)))
+            }
+            StatusCode::NOT_FOUND => Ok(None),
+            _ => Err(CheckError::HttpStatus(status)),
+        }
+    }
+}
+
+/// The most embeds Discord takes in one message
+const MAX_EMBEDS: usize = 10;
+
+/// Discord's red, for the embeds of compromised guilds
+const COMPROMISED_COLOR: u32 = 0xED4245;
+
+/// Posts compromised guilds to a Discord webhook, an embed for each
+pub struct Webhook {
+    url: Secret<String>,
+}
+
+impl Webhook {
+    pub fn new(url: Secret<String>) -> Self {
+        Self { url }
+    }
+
+    /// Posts the payload's summary, and an embed for each of its findings in
+    /// as many messages as they take
+    pub async fn post(&self, client: &Client, payload: &Payload<'_>) -> Result<(), CheckError> {
+        let mut messages: Vec<&[&Response]> = payload.findings.chunks(MAX_EMBEDS).collect();
+        if messages.is_empty() {
+            messages.push(&[]);
+        }
+        for (i, guilds) in messages.into_iter().enumerate() {
+            let embeds: Vec<Value> = guilds.iter().map(|guild| embed(guild)).collect();
+            let mut body = json!({
+                "embeds": embeds,
+                // guild names mustn't ping anyone
+                "allowed_mentions": { "parse": [] },
+            });
+            if i == 0 {
+                body["content"] = payload.summary.into();
+            }
+            self.send(client, &body.to_string()).await?;
+        }
+        Ok(())
+    }
+
+    async fn send(&self, client: &Client, body: &str) -> Result<(), CheckError> {
+        let mut rate_limits = 0;
+        loop {
+            let response = client
+                .post(self.url.expose())
+                .header(CONTENT_TYPE, "application/json")
+                .body(body.to_owned())
+                .send()
+                .await
+                // the URL is the webhook's secret
+                .map_err(|err| err.without_url())?;
+            let status = response.status();
+            if status == StatusCode::TOO_MANY_REQUESTS && rate_limits < MAX_RATE_LIMITS {
+                rate_limits += 1;
+                let headers = response.headers().clone();
+                let text = response.text().await.map_err(|err| err.without_url())?;
+                let wait = rate_limit_wait(&headers, &text);
+                debug!(?wait, "webhook rate limited, waiting");
+                tokio::time::sleep(wait).await;
+                continue;
+            }
+            if !status.is_success() {
+                return Err(CheckError::HttpStatus(status));
+            }
+            return Ok(());
+        }
+    }
+}
+
+/// A bot posting results in Discord: in a guild's channel or its owner's DMs,
+/// or as the answer to [`SLASH_COMMAND`]
+pub struct Bot {
+    token: Secret<String>,
+    api: String,
+}
+
+#[derive(Deserialize)]
+struct Guild {
+    #[serde(default)]
+    name: String,
+    owner_id: String,
+}
+
+/// Anything with a snowflake: a channel, an application
+#[derive(Deserialize)]
+struct Object {
+    id: String,
+}
+
+impl Bot {
+    pub fn new(token: Secret<String>) -> Self {
+        Self {
+            token,
+            api: DISCORD_API.to_owned(),
+        }
+    }
+
+    /// Talks to another API base URL instead of Discord's
+    pub fn with_api(mut self, api: impl Into<String>) -> Self {
+        self.api = api.into();
+        self
+    }
+
+    async fn call<T: serde::de::DeserializeOwned>(
+        &self,
+        client: &Client,
+        method: Method,
+        path: &str,
+        body: Option<&Value>,
+    ) -> Result<T, CheckError> {
+        let url = format!("{}{path}", self.api);
+        let authorization = TokenType::Bot.authorization(&self.token);
+        let (status, text) = request(client, method, &url, &[], body, Some(&authorization)).await?;
+        match status {
+            _ if status.is_success() => {
+                serde_json::from_str(&text).map_err(|_| CheckError::bad_body(&text))
+            }
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                Err(CheckError::Unauthorized(status))
+            }
+            _ => Err(CheckError::HttpStatus(status)),
+        }
+    }
+
+    /// The channel of the DMs with the owner of `guild_id`
+    pub async fn owner_dm(&self, client: &Client, guild_id: &str) -> Result<String, CheckError> {
+        let path = format!("/guilds/{guild_id}");
+        let guild: Guild = self.call(client, Method::GET, &path, None).await?;
+        let body = json!({ "recipient_id": guild.owner_id });
+        let path = "/users/@me/channels";
+        let channel: Object = self.call(client, Method::POST, path, Some(&body)).await?;
+        Ok(channel.id)
+    }
+
+    /// The name of `guild_id`
+    pub async fn guild_name(&self, client: &Client, guild_id: &str) -> Result<String, CheckError> {
+        let path = format!("/guilds/{guild_id}");
+        let guild: Guild = self.call(client, Method::GET, &path, None).await?;
+        Ok(guild.name)
+    }
+
+    /// The ID of the bot's application, which its commands belong to
+    pub async fn application_id(&self, client: &Client) -> Result<String, CheckError> {
+        let application: Object = self
+            .call(client, Method::GET, "/applications/@me", None)
+            .await?;
+        Ok(application.id)
+    }
+
+    /// Registers [`SLASH_COMMAND`], or updates it if it's there already. Only
+    /// members who can manage the server see it, unless a server lets others.
+    pub async fn register_command(
+        &self,
+        client: &Client,
+        application_id: &str,
+    ) -> Result<(), CheckError> {
+        let body = json!({
+            "name": SLASH_COMMAND,
+            "type": 1,
+            "description": "Check this server against the spy.pet dataset",
+            "dm_permission": false,
+            // Manage Server
+            "default_member_permissions": "32",
+        });
+        let path = format!("/applications/{application_id}/commands");
+        let _: Value = self.call(client, Method::POST, &path, Some(&body)).await?;
+        Ok(())
+    }
+
+    /// Sets the answer to a deferred interaction to `message`
+    pub async fn answer(
+        &self,
+        client: &Client,
+        application_id: &str,
+        interaction_token: &str,
+        message: &Value,
+    ) -> Result<(), CheckError> {
+        let path = format!("/webhooks/{application_id}/{interaction_token}/messages/@original");
+        let _: Value = self
+            .call(client, Method::PATCH, &path, Some(message))
+            .await?;
+        Ok(())
+    }
+
+    /// Posts `result` in `channel_id`, with an embed if it's compromised
+    pub async fn post(
+        &self,
+        client: &Client,
+        channel_id: &str,
+        result: &Response,
+    ) -> Result<(), CheckError> {
+        let path = format!("/channels/{channel_id}/messages");
+        let body = result_message(result);
+        let _: Value = self.call(client, Method::POST, &path, Some(&body)).await?;
+        Ok(())
+    }
+}
+
+/// A message telling a guild its result, with an embed if it's compromised
+pub fn result_message(result: &Response) -> Value {
+    let name = match result.guild_name.is_empty() {
+        true => &result.guild_id,
+        false => &result.guild_name,
+    };
+    match result.is_compromised() {
+        true => json!({
+            "content": format!("{name} is in the spy.pet dataset."),
+            "embeds": [embed(result)],
+            "allowed_mentions": { "parse": [] },
+        }),
+        false => json!({
+            "content": format!("{name} wasn't found in the spy.pet dataset."),
+            "allowed_mentions": { "parse": [] },
+        }),
+    }
+}
+
+fn embed(guild: &Response) -> Value {
+    let info = guild.guild_info().unwrap_or_default();
+    let title = match (&info.name, guild.guild_name.is_empty()) {
+        (_, false) => guild.guild_name.clone(),
+        (Some(name), true) => name.clone(),
+        (None, true) => guild.guild_id.clone(),
+    };
+    let mut fields = vec![json!({ "name": "ID", "value": guild.guild_id, "inline": true })];
+    if let Some(count) = info.message_count {
+        fields.push(
+            json!({ "name": "Archived messages", "value": count.to_string(), "inline": true }),
+        );
+    }
+    if let Some(count) = info.member_count {
+        fields.push(json!({ "name": "Members", "value": count.to_string(), "inline": true }));
+    }
+    // shown as a date in the reader's own time zone
+    let dates = [
+        ("First seen", info.first_seen),
+        ("Last seen", info.last_seen),
+    ];
+    for (name, date) in dates {
+        if let Some(date) = date {
+            let value = format!("<t:{}:D>", date.timestamp());
+            fields.push(json!({ "name": name, "value": value, "inline": true }));
+        }
+    }
+    json!({
+        "title": title,
+        "color": COMPROMISED_COLOR,
+        "fields": fields,
+        "footer": { "text": guild.source },
+    })
+}
+
+/// Lists the guilds an account is in, through Discord's API, to check them
+/// without an index
+pub struct GuildLister {
+    token: Secret<String>,
+    token_type: TokenType,
+    api: String,
+}
+
+impl GuildLister {
+    pub fn new(token: Secret<String>, token_type: TokenType) -> Self {
+        Self {
+            token,
+            token_type,
+            api: DISCORD_API.to_owned(),
+        }
+    }
+
+    /// Talks to another API base URL instead of Discord's
+    pub fn with_api(mut self, api: impl Into<String>) -> Self {
+        self.api = api.into();
+        self
+    }
+
+    /// Every guild the token's account is in, id → name, one page at a time
+    pub async fn guilds(&self, client: &Client) -> Result<BTreeMap<String, String>, CheckError> {
+        let url = format!("{}/users/@me/guilds", self.api);
+        let authorization = self.token_type.authorization(&self.token);
+        let limit = GUILD_PAGE_SIZE.to_string();
+        let mut guilds = BTreeMap::new();
+        let mut after = String::from("0");
+        loop {
+            let query = [("limit", limit.as_str()), ("after", after.as_str())];
+            let (status, text) = get(client, &url, &query, &authorization).await?;
+            if !status.is_success() {
+                return Err(CheckError::HttpStatus(status));
+            }
+            let page: Vec<PartialGuild> =
+                serde_json::from_str(&text).map_err(|_| CheckError::bad_body(&text))?;
+            debug!(after, guilds = page.len(), "got guild page");
+            // pages are in ID order, so the last one is where the next starts
+            let next = match page.last() {
+                Some(last) if page.len() >= GUILD_PAGE_SIZE => Some(last.id.clone()),
+                _ => None,
+            };
+            guilds.extend(page.into_iter().map(|guild| (guild.id, guild.name)));
+            match next {
+                Some(next) => after = next,
+                None => break,
+            }
+        }
+        Ok(guilds)
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::error::Error as _;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use reqwest::{Client, Url};
+use thiserror::Error;
+use tracing::debug;
+
+const DNS_MESSAGE: &str = "application/dns-message";
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+
+/// Answers are kept at least this long, whatever their TTL, so every check
+/// doesn't cost a lookup
+const MIN_TTL: Duration = Duration::from_secs(60);
+
+/// Addresses looked up, and until when they're good
+type Cached = (Vec<IpAddr>, Instant);
+
+/// A lookup through a DNS-over-HTTPS server that failed
+#[derive(Error, Debug)]
+#[error("couldn't resolve {name} with DNS-over-HTTPS server {server}: {reason}")]
+pub struct DohError {
+    pub name: String,
+    pub server: String,
+    pub reason: String,
+}
+
+/// Resolves host names by asking a DNS-over-HTTPS server (RFC 8484), so no
+/// plaintext DNS queries leave the machine for them.
+///
+/// The server's own host name is looked up the usual way, unless it's given
+/// as an IP address or with a `--resolve` override.
+///
+/// Clones share their cache of answers.
+#[derive(Clone)]
+pub struct DohResolver {
+    server: Url,
+    client: Client,
+    cache: Arc<Mutex<HashMap<String, Cached>>>,
+}
+
+impl DohResolver {
+    /// `client` is used to talk to `server`; it must not use this resolver
+    pub fn new(server: Url, client: Client) -> Self {
+        Self {
+            server,
+            client,
+            cache: Arc::default(),
+        }
+    }
+
+    pub fn server(&self) -> &Url {
+        &self.server
+    }
+
+    pub async fn lookup(&self, name: &str) -> Result<Vec<IpAddr>, DohError> {
+        if let Some((addrs, expires)) = self.cache.lock().expect("dns cache poisoned").get(name) {
+            if *expires > Instant::now() {
+                return Ok(addrs.clone());
+            }
+        }
+        let error = |reason: String| DohError {
+            name: name.to_owned(),
+            server: self.server.to_string(),
+            reason,
+        };
+
+        let mut addrs = Vec::new();
+        let mut ttl = None;
+        for qtype in [TYPE_A, TYPE_AAAA] {
+            let response = self
+                .client
+                .post(self.server.clone())
+                .header(CONTENT_TYPE, DNS_MESSAGE)
+                .header(ACCEPT, DNS_MESSAGE)
+                .body(query(name, qtype).map_err(error)?)
+                .send()
+                .await
+                .map_err(|err| error(describe(&err)))?;
+            let status = response.status();
+            if !status.is_success() {
+                return Err(error(format!("server answered {status}")));
+            }
+            let body = response
+                .bytes()
+                .await
+                .map_err(|err| error(describe(&err)))?;
+            let answer = parse(&body).map_err(error)?;
+            addrs.extend(answer.addrs);
+            ttl = ttl.min(answer.ttl).or(answer.ttl);
+        }
+        if addrs.is_empty() {
+            return Err(error("no A or AAAA records".to_owned()));
+        }
+        debug!(name, ?addrs, "resolved through DNS-over-HTTPS");
+        let ttl = Duration::from_secs(ttl.unwrap_or(0).into()).max(MIN_TTL);
+        self.cache
+            .lock()
+            .expect("dns cache poisoned")
+            .insert(name.to_owned(), (addrs.clone(), Instant::now() + ttl));
+        Ok(addrs)
+    }
+}
+
+impl Resolve for DohResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolver = self.clone();
+        Box::pin(async move {
+            let addrs = resolver.lookup(name.as_str()).await?;
+            let addrs: Addrs = Box::new(addrs.into_iter().map(|ip| SocketAddr::new(ip, 0)));
+            Ok(addrs)
+        })
+    }
+}
+
+/// A failed request to the server, with its causes, which say more than
+/// reqwest's own message
+fn describe(err: &reqwest::Error) -> String {
+    let mut message = if err.is_timeout() {
+        "request timed out".to_owned()
+    } else {
+        "couldn't contact it".to_owned()
+    };
+    let mut source = err.source();
+    while let Some(cause) = source {
+        // hyper repeats the cause in some messages
+        let cause_message = cause.to_string();
+        if !message.ends_with(&cause_message) {
+            message = format!("{message}: {cause_message}");
+        }
+        source = cause.source();
+    }
+    message
+}
+
+/// A DNS query for `name`, in wire format
+fn query(name: &str, qtype: u16) -> Result<Vec<u8>, String> {
+    // ID 0 as RFC 8484 recommends, recursion desired, one question
+    let mut message = vec![0, 0, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(format!("{name:?} isn't a valid host name"));
+        }
+        message.push(label.len() as u8);
+        message.extend_from_slice(label.as_bytes());
+    }
+    message.push(0);
+    message.extend_from_slice(&qtype.to_be_bytes());
+    message.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(message)
+}
+
+struct Answer {
+    addrs: Vec<IpAddr>,
+    /// The lowest TTL of the address records
+    ttl: Option<u32>,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, n: usize) -> Result<&[u8], String> {
+        let end = self.pos + n;
+        let slice = self
+            .bytes
+            .get(self.pos..end)
+            .ok_or_else(|| "truncated DNS answer".to_owned())?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Skips a name, which may end in a compression pointer
+    fn skip_name(&mut self) -> Result<(), String> {
+        loop {
+            let len = self.take(1)?[0];
+            match len {
+                0 => return Ok(()),
+                len if len & 0xc0 == 0xc0 => {
+                    self.take(1)?;
+                    return Ok(());
+                }
+                len => {
+                    self.take(len.into())?;
+                }
+            }
+        }
+    }
+}
+
+/// The addresses in a DNS response in wire format
+fn parse(bytes: &[u8]) -> Result<Answer, String> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let header = reader.take(12)?;
+    let rcode = header[3] & 0x0f;
+    let questions = u16::from_be_bytes([header[4], header[5]]);
+    let answers = u16::from_be_bytes([header[6], header[7]]);
+    match rcode {
+        0 => {}
+        3 => return Err("no such domain".to_owned()),
+        2 => return Err("server failure".to_owned()),
+        5 => return Err("query refused".to_owned()),
+        rcode => return Err(format!("error code {rcode}")),
+    }
+    for _ in 0..questions {
+        reader.skip_name()?;
+        reader.take(4)?;
+    }
+    let mut answer = Answer {
+        addrs: Vec::new(),
+        ttl: None,
+    };
+    for _ in 0..answers {
+        reader.skip_name()?;
+        let rtype = reader.u16()?;
+        let class = reader.u16()?;
+        let ttl = reader.u32()?;
+        let len = reader.u16()?;
+        let data = reader.take(len.into())?;
+        let addr = match (rtype, class, data.len()) {
+            (TYPE_A, CLASS_IN, 4) => IpAddr::V4(Ipv4Addr::new(data[0], data[1], data[2], data[3])),
+            (TYPE_AAAA, CLASS_IN, 16) => {
+                let octets: [u8; 16] = data.try_into().expect("length was checked");
+                IpAddr::V6(Ipv6Addr::from(octets))
+            }
+            // CNAMEs, which the server already followed
+            _ => continue,
+        };
+        answer.addrs.push(addr);
+        answer.ttl = Some(answer.ttl.map_or(ttl, |min| min.min(ttl)));
+    }
+    Ok(answer)
+}
@@ -0,0 +1,194 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::future::Future;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use chrono::{DateTime, Utc};
+use reqwest::header::HeaderMap;
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+/// Headers worth keeping next to a dumped body
+const HEADERS: &[&str] = &[
+    "content-type",
+    "date",
+    "etag",
+    "last-modified",
+    "retry-after",
+    "server",
+    "cf-cache-status",
+    "cf-ray",
+];
+
+/// What was sent along with a dumped body, in `<guild_id>.meta.json`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DumpMeta {
+    pub guild_id: String,
+    /// Name of the backend that was asked
+    pub source: String,
+    pub url: String,
+    pub status: u16,
+    /// The headers of interest that the response had, lowercased
+    pub headers: BTreeMap<String, String>,
+    pub fetched_at: DateTime<Utc>,
+    /// The body's file name, next to this one
+    pub body_file: String,
+}
+
+/// A response as it came off the wire
+pub(crate) struct Exchange {
+    url: String,
+    status: u16,
+    headers: BTreeMap<String, String>,
+    body: String,
+}
+
+tokio::task_local! {
+    /// The last response received by the check running in this task, when
+    /// it's being dumped
+    static EXCHANGE: RefCell<Option<Exchange>>;
+}
+
+/// Whether the check running in this task is being dumped, so responses are
+/// worth capturing
+pub(crate) fn capturing() -> bool {
+    EXCHANGE.try_with(|_| ()).is_ok()
+}
+
+pub(crate) fn capture(url: &str, status: u16, headers: &HeaderMap, body: &str) {
+    let _ = EXCHANGE.try_with(|exchange| {
+        let headers = HEADERS
+            .iter()
+            .filter_map(|name| {
+                let value = headers.get(*name)?.to_str().ok()?;
+                Some(((*name).to_owned(), value.to_owned()))
+            })
+            .collect();
+        *exchange.borrow_mut() = Some(Exchange {
+            url: url.to_owned(),
+            status,
+            headers,
+            body: body.to_owned(),
+        });
+    });
+}
+
+/// Writes each raw response body to a directory, for archiving and for
+/// looking into answers that didn't parse.
+///
+/// With one backend, bodies go to `<dir>/<guild_id>.json`, or `.txt` when
+/// they aren't JSON, next to a `<guild_id>.meta.json` [`DumpMeta`]. With
+/// several, each backend gets a subdirectory named after it. Only the
+/// built-in backends and [`UrlTemplate`](crate::backend::UrlTemplate) are
+/// captured.
+pub struct ResponseDump {
+    dir: PathBuf,
+    per_backend: bool,
+    overwrite: bool,
+    kept: AtomicUsize,
+}
+
+impl ResponseDump {
+    /// Creates `dir` if it's missing. Existing dumps are left alone unless
+    /// `overwrite` is set.
+    pub fn new(dir: impl Into<PathBuf>, backends: usize, overwrite: bool) -> io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            per_backend: backends > 1,
+            overwrite,
+            kept: AtomicUsize::new(0),
+        })
+    }
+
+    /// How many responses weren't written because a dump was already there
+    pub fn kept(&self) -> usize {
+        self.kept.load(Ordering::Relaxed)
+    }
+
+    /// Runs `check`, then dumps the response it received, if any
+    pub(crate) async fn record<F: Future>(&self, source: &str, id: &str, check: F) -> F::Output {
+        let (output, exchange) = EXCHANGE
+            .scope(RefCell::new(None), async {
+                let output = check.await;
+                (output, EXCHANGE.with(RefCell::take))
+            })
+            .await;
+        if let Some(exchange) = exchange {
+            if let Err(err) = self.write(source, id, exchange) {
+                warn!(%err, "couldn't dump response");
+            }
+        }
+        output
+    }
+
+    fn write(&self, source: &str, id: &str, exchange: Exchange) -> io::Result<()> {
+        let dir = if self.per_backend {
+            let dir = self.dir.join(sanitize(source));
+            std::fs::create_dir_all(&dir)?;
+            dir
+        } else {
+            self.dir.clone()
+        };
+
+        let is_json = serde_json::from_str::<IgnoredAny>(&exchange.body).is_ok();
+        let body_file = format!("{id}.{}", if is_json { "json" } else { "txt" });
+        let meta = DumpMeta {
+            guild_id: id.to_owned(),
+            source: source.to_owned(),
+            url: exchange.url,
+            status: exchange.status,
+            headers: exchange.headers,
+            fetched_at: Utc::now(),
+            body_file: body_file.clone(),
+        };
+
+        let meta_path = dir.join(format!("{id}.meta.json"));
+        if !self.overwrite && meta_path.exists() {
+            debug!(path = %meta_path.display(), "keeping existing dump");
+            self.kept.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+        // a body of the other kind from an earlier run would be stale
+        let other = format!("{id}.{}", if is_json { "txt" } else { "json" });
+        match std::fs::remove_file(dir.join(other)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+        write_file(
+            &dir.join(&body_file),
+            exchange.body.as_bytes(),
+            self.overwrite,
+        )?;
+        write_file(
+            &meta_path,
+            &serde_json::to_vec_pretty(&meta)?,
+            self.overwrite,
+        )
+    }
+}
+
+fn write_file(path: &Path, contents: &[u8], overwrite: bool) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .create_new(!overwrite)
+        .truncate(true)
+        .open(path)?;
+    file.write_all(contents)
+}
+
+/// Makes a backend name (which can be a URL template) usable as a file name
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
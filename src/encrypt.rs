@@ -0,0 +1,374 @@
+//! Encrypting reports at rest in the [age](https://age-encryption.org/v1)
+//! format, so `age -d` (or rage) decrypts them: to X25519 recipients
+//! (`age1...`), or with a passphrase through scrypt.
+
+use std::num::NonZeroU32;
+
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305};
+use ring::agreement::{self, EphemeralPrivateKey, UnparsedPublicKey, X25519};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::{hkdf, hmac, pbkdf2};
+use thiserror::Error;
+
+use crate::secret::Secret;
+
+/// The scrypt work factor (log2 of N) age itself uses
+pub const DEFAULT_WORK_FACTOR: u8 = 18;
+
+const VERSION_LINE: &str = "age-encryption.org/v1";
+
+/// Plaintext per payload chunk
+const CHUNK: usize = 64 * 1024;
+
+#[derive(Error, Debug)]
+pub enum EncryptError {
+    #[error("{0:?} isn't an age recipient, expected age1 followed by the key")]
+    BadRecipient(String),
+
+    #[error("a passphrase can't be combined with other recipients")]
+    PassphraseNotAlone,
+
+    #[error("there's nobody to encrypt to")]
+    NoRecipients,
+
+    #[error("the passphrase is empty")]
+    EmptyPassphrase,
+
+    #[error("couldn't encrypt")]
+    Crypto,
+}
+
+impl From<ring::error::Unspecified> for EncryptError {
+    fn from(_: ring::error::Unspecified) -> Self {
+        EncryptError::Crypto
+    }
+}
+
+/// Who can decrypt a report
+pub enum Recipient {
+    /// An age X25519 public key
+    X25519([u8; 32]),
+    /// Anyone with the passphrase; `work_factor` is log2 of scrypt's N
+    Passphrase {
+        passphrase: Secret<String>,
+        work_factor: u8,
+    },
+}
+
+const BECH32: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+fn polymod(values: impl Iterator<Item = u8>) -> u32 {
+    const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+    let mut check = 1u32;
+    for value in values {
+        let top = check >> 25;
+        check = ((check & 0x1ffffff) << 5) ^ u32::from(value);
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                check ^= generator;
+            }
+        }
+    }
+    check
+}
+
+/// The human-readable part and the data of a Bech32 string, checked against
+/// its checksum, with the data turned from five bits a character into bytes
+pub fn bech32_decode(s: &str) -> Option<(String, Vec<u8>)> {
+    let lower = s.to_ascii_lowercase();
+    if s != lower && s != s.to_ascii_uppercase() {
+        return None;
+    }
+    let (hrp, data) = lower.rsplit_once('1')?;
+    if hrp.is_empty() || data.len() < 6 {
+        return None;
+    }
+    let values: Vec<u8> = data
+        .bytes()
+        .map(|c| BECH32.iter().position(|&b| b == c).map(|at| at as u8))
+        .collect::<Option<_>>()?;
+    let expanded = hrp
+        .bytes()
+        .map(|b| b >> 5)
+        .chain([0])
+        .chain(hrp.bytes().map(|b| b & 31));
+    if polymod(expanded.chain(values.iter().copied())) != 1 {
+        return None;
+    }
+    // five bits at a time into bytes, with only zero padding left over
+    let (mut bytes, mut acc, mut bits) = (Vec::new(), 0u32, 0);
+    for value in &values[..values.len() - 6] {
+        acc = (acc << 5) | u32::from(*value);
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((acc >> bits) as u8);
+        }
+    }
+    if bits >= 5 || acc & ((1 << bits) - 1) != 0 {
+        return None;
+    }
+    Some((hrp.to_owned(), bytes))
+}
+
+/// The key in a Bech32 `age1...` string, checksum and all
+fn decode_recipient(s: &str) -> Option<[u8; 32]> {
+    match bech32_decode(s)? {
+        (hrp, key) if hrp == "age" => key.try_into().ok(),
+        _ => None,
+    }
+}
+
+impl Recipient {
+    /// An `age1...` public key
+    pub fn parse(s: &str) -> Result<Self, EncryptError> {
+        decode_recipient(s.trim())
+            .map(Recipient::X25519)
+            .ok_or_else(|| EncryptError::BadRecipient(s.to_owned()))
+    }
+
+    pub fn passphrase(passphrase: Secret<String>) -> Self {
+        Recipient::Passphrase {
+            passphrase,
+            work_factor: DEFAULT_WORK_FACTOR,
+        }
+    }
+}
+
+struct Len(usize);
+
+impl hkdf::KeyType for Len {
+    fn len(&self) -> usize {
+        self.0
+    }
+}
+
+fn hkdf(salt: &[u8], ikm: &[u8], info: &[u8]) -> Result<[u8; 32], EncryptError> {
+    let mut key = [0; 32];
+    hkdf::Salt::new(hkdf::HKDF_SHA256, salt)
+        .extract(ikm)
+        .expand(&[info], Len(32))?
+        .fill(&mut key)?;
+    Ok(key)
+}
+
+fn seal(key: &[u8; 32], nonce: [u8; 12], plaintext: &[u8]) -> Result<Vec<u8>, EncryptError> {
+    let key = LessSafeKey::new(UnboundKey::new(&CHACHA20_POLY1305, key)?);
+    let mut sealed = plaintext.to_vec();
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::empty(),
+        &mut sealed,
+    )?;
+    Ok(sealed)
+}
+
+fn salsa20_8(block: &mut [u32; 16]) {
+    let mut x = *block;
+    let quarter = |x: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize| {
+        x[b] ^= x[a].wrapping_add(x[d]).rotate_left(7);
+        x[c] ^= x[b].wrapping_add(x[a]).rotate_left(9);
+        x[d] ^= x[c].wrapping_add(x[b]).rotate_left(13);
+        x[a] ^= x[d].wrapping_add(x[c]).rotate_left(18);
+    };
+    for _ in 0..4 {
+        quarter(&mut x, 0, 4, 8, 12);
+        quarter(&mut x, 5, 9, 13, 1);
+        quarter(&mut x, 10, 14, 2, 6);
+        quarter(&mut x, 15, 3, 7, 11);
+        quarter(&mut x, 0, 1, 2, 3);
+        quarter(&mut x, 5, 6, 7, 4);
+        quarter(&mut x, 10, 11, 8, 9);
+        quarter(&mut x, 15, 12, 13, 14);
+    }
+    for (word, mixed) in block.iter_mut().zip(x) {
+        *word = word.wrapping_add(mixed);
+    }
+}
+
+/// scrypt's BlockMix over `2r` blocks of 16 words
+fn block_mix(input: &[u32], output: &mut [u32]) {
+    let blocks = input.len() / 16;
+    let mut x: [u32; 16] = input[input.len() - 16..].try_into().expect("16 words");
+    for i in 0..blocks {
+        for (word, other) in x.iter_mut().zip(&input[i * 16..i * 16 + 16]) {
+            *word ^= other;
+        }
+        salsa20_8(&mut x);
+        // even blocks to the first half, odd ones to the second
+        let at = (i / 2 + (i % 2) * blocks / 2) * 16;
+        output[at..at + 16].copy_from_slice(&x);
+    }
+}
+
+/// scrypt with r = 8 and p = 1, the parameters age uses, deriving a 32 byte
+/// key. `work_factor` is log2 of N.
+pub fn scrypt(passphrase: &[u8], salt: &[u8], work_factor: u8) -> [u8; 32] {
+    const R: usize = 8;
+    let n = 1usize << work_factor;
+    let one = NonZeroU32::new(1).expect("1 isn't 0");
+    let mut b = [0u8; 128 * R];
+    pbkdf2::derive(pbkdf2::PBKDF2_HMAC_SHA256, one, salt, passphrase, &mut b);
+
+    let words = 32 * R;
+    let mut x: Vec<u32> = b
+        .chunks_exact(4)
+        .map(|w| u32::from_le_bytes(w.try_into().expect("4 bytes")))
+        .collect();
+    let mut v = vec![0u32; n * words];
+    let mut scratch = vec![0u32; words];
+    for i in 0..n {
+        v[i * words..(i + 1) * words].copy_from_slice(&x);
+        block_mix(&x, &mut scratch);
+        std::mem::swap(&mut x, &mut scratch);
+    }
+    for _ in 0..n {
+        let j = x[words - 16] as usize & (n - 1);
+        for (word, other) in x.iter_mut().zip(&v[j * words..(j + 1) * words]) {
+            *word ^= other;
+        }
+        block_mix(&x, &mut scratch);
+        std::mem::swap(&mut x, &mut scratch);
+    }
+    for (bytes, word) in b.chunks_exact_mut(4).zip(&x) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+
+    let mut key = [0; 32];
+    pbkdf2::derive(pbkdf2::PBKDF2_HMAC_SHA256, one, &b, passphrase, &mut key);
+    key
+}
+
+/// A stanza of the header: its type and arguments, and its body
+fn stanza(args: &[String], body: &[u8]) -> String {
+    let mut stanza = format!("-> {}\n", args.join(" "));
+    let encoded = STANDARD_NO_PAD.encode(body);
+    // lines of 64 columns, the last one shorter even if that leaves it empty
+    for line in encoded.as_bytes().chunks(64) {
+        stanza.push_str(std::str::from_utf8(line).expect("base64 is ASCII"));
+        stanza.push('\n');
+    }
+    if encoded.len().is_multiple_of(64) {
+        stanza.push('\n');
+    }
+    stanza
+}
+
+/// Encrypts a stream a chunk at a time: [`Sealer::new`] gives the header,
+/// [`Sealer::update`] the chunks that are complete so far, and
+/// [`Sealer::finish`] the last one.
+pub struct Sealer {
+    key: LessSafeKey,
+    counter: u64,
+    buffer: Vec<u8>,
+}
+
+impl Sealer {
+    /// A sealer for `recipients`, and the header to write before anything
+    /// else
+    pub fn new(recipients: &[Recipient]) -> Result<(Self, Vec<u8>), EncryptError> {
+        if recipients.is_empty() {
+            return Err(EncryptError::NoRecipients);
+        }
+        let passphrases = recipients
+            .iter()
+            .filter(|r| matches!(r, Recipient::Passphrase { .. }))
+            .count();
+        if passphrases > 0 && recipients.len() > 1 {
+            return Err(EncryptError::PassphraseNotAlone);
+        }
+        let rng = SystemRandom::new();
+        let mut file_key = [0u8; 16];
+        rng.fill(&mut file_key)?;
+
+        let mut header = format!("{VERSION_LINE}\n");
+        for recipient in recipients {
+            match recipient {
+                Recipient::X25519(public) => {
+                    let ephemeral = EphemeralPrivateKey::generate(&X25519, &rng)?;
+                    let share = ephemeral.compute_public_key()?;
+                    let peer = UnparsedPublicKey::new(&X25519, public);
+                    // ring refuses an all-zero shared secret, as age must
+                    let shared =
+                        agreement::agree_ephemeral(ephemeral, &peer, |shared| shared.to_vec())?;
+                    let salt = [share.as_ref(), public.as_slice()].concat();
+                    let key = hkdf(&salt, &shared, b"age-encryption.org/v1/X25519")?;
+                    let args = ["X25519".to_owned(), STANDARD_NO_PAD.encode(share.as_ref())];
+                    header.push_str(&stanza(&args, &seal(&key, [0; 12], &file_key)?));
+                }
+                Recipient::Passphrase {
+                    passphrase,
+                    work_factor,
+                } => {
+                    if passphrase.expose().is_empty() {
+                        return Err(EncryptError::EmptyPassphrase);
+                    }
+                    let mut salt = [0u8; 16];
+                    rng.fill(&mut salt)?;
+                    let label = [b"age-encryption.org/v1/scrypt".as_slice(), &salt].concat();
+                    let key = scrypt(passphrase.expose().as_bytes(), &label, *work_factor);
+                    let args = [
+                        "scrypt".to_owned(),
+                        STANDARD_NO_PAD.encode(salt),
+                        work_factor.to_string(),
+                    ];
+                    header.push_str(&stanza(&args, &seal(&key, [0; 12], &file_key)?));
+                }
+            }
+        }
+        header.push_str("---");
+        let mac_key = hkdf(&[], &file_key, b"header")?;
+        let mac = hmac::sign(
+            &hmac::Key::new(hmac::HMAC_SHA256, &mac_key),
+            header.as_bytes(),
+        );
+        header.push_str(&format!(" {}\n", STANDARD_NO_PAD.encode(mac.as_ref())));
+
+        let mut nonce = [0u8; 16];
+        rng.fill(&mut nonce)?;
+        let payload_key = hkdf(&nonce, &file_key, b"payload")?;
+        let mut start = header.into_bytes();
+        start.extend_from_slice(&nonce);
+        let sealer = Self {
+            key: LessSafeKey::new(UnboundKey::new(&CHACHA20_POLY1305, &payload_key)?),
+            counter: 0,
+            buffer: Vec::new(),
+        };
+        Ok((sealer, start))
+    }
+
+    fn chunk(&mut self, plaintext: &[u8], last: bool) -> Result<Vec<u8>, EncryptError> {
+        let mut nonce = [0u8; 12];
+        nonce[3..11].copy_from_slice(&self.counter.to_be_bytes());
+        nonce[11] = u8::from(last);
+        self.counter += 1;
+        let mut sealed = plaintext.to_vec();
+        self.key.seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::empty(),
+            &mut sealed,
+        )?;
+        Ok(sealed)
+    }
+
+    /// Takes `data`, and gives back the chunks it completes. A full chunk is
+    /// held back until more comes, since the last one is marked as such.
+    pub fn update(&mut self, data: &[u8]) -> Result<Vec<u8>, EncryptError> {
+        self.buffer.extend_from_slice(data);
+        let mut sealed = Vec::new();
+        while self.buffer.len() > CHUNK {
+            let rest = self.buffer.split_off(CHUNK);
+            let chunk = std::mem::replace(&mut self.buffer, rest);
+            sealed.extend(self.chunk(&chunk, false)?);
+        }
+        Ok(sealed)
+    }
+
+    /// The last chunk
+    pub fn finish(mut self) -> Result<Vec<u8>, EncryptError> {
+        let rest = std::mem::take(&mut self.buffer);
+        self.chunk(&rest, true)
+    }
+}
@@ -0,0 +1,221 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::dns::DohError;
+use crate::warnings::Condition;
+
+/// Maximum length of the body excerpt kept in [`CheckError::BadBody`]
+pub const SNIPPET_LEN: usize = 200;
+
+#[derive(Error, Debug)]
+pub enum CheckError {
+    #[error("couldn't contact api: {0}")]
+    Network(#[source] reqwest::Error),
+
+    #[error("request timed out")]
+    Timeout,
+
+    /// The api's host name couldn't be looked up; `resolver` says who was
+    /// asked
+    #[error("couldn't resolve {host} with {resolver}: {reason}")]
+    Resolve {
+        host: String,
+        resolver: String,
+        reason: String,
+    },
+
+    #[error("rate limited by api")]
+    RateLimited { retry_after: Option<Duration> },
+
+    #[error("api returned error: {0}")]
+    HttpStatus(StatusCode),
+
+    /// 401 or 403: the API wants an API key, or a different one
+    #[error("api refused the request ({0}), the API key is missing or wrong")]
+    Unauthorized(StatusCode),
+
+    /// A bot check, e.g. Cloudflare's, in front of the API
+    #[error("api answered with a challenge page ({0})")]
+    Challenge(StatusCode),
+
+    #[error("couldn't parse api response: {snippet:?}")]
+    BadBody { snippet: String },
+
+    /// The body went past `--max-response-size`, and was cut off there
+    #[error("api response is over the limit of {limit} bytes")]
+    TooLarge { limit: u64 },
+
+    /// `--fixtures` has a file for the guild, but it can't be read
+    #[error("couldn't read fixture {path}: {source}")]
+    Fixture {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// The answer could only be worked around, which isn't allowed when
+    /// strict
+    #[error("{message} (not allowed with --strict)")]
+    Strict {
+        condition: Condition,
+        message: String,
+    },
+
+    /// A bug: the check panicked. The message is the panic payload.
+    #[error("check panicked: {0}")]
+    Panic(String),
+}
+
+impl CheckError {
+    /// Whether trying the same request again could plausibly succeed
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            CheckError::Network(_)
+            | CheckError::Timeout
+            | CheckError::Resolve { .. }
+            | CheckError::RateLimited { .. } => true,
+            CheckError::HttpStatus(status) => status.is_server_error(),
+            CheckError::Unauthorized(_)
+            | CheckError::Challenge(_)
+            | CheckError::BadBody { .. }
+            | CheckError::TooLarge { .. }
+            | CheckError::Fixture { .. }
+            | CheckError::Strict { .. }
+            | CheckError::Panic(_) => false,
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            CheckError::Network(_) => ErrorKind::Network,
+            CheckError::Timeout => ErrorKind::Timeout,
+            CheckError::Resolve { .. } => ErrorKind::Dns,
+            CheckError::RateLimited { .. } => ErrorKind::RateLimited,
+            CheckError::HttpStatus(_) => ErrorKind::HttpStatus,
+            CheckError::Unauthorized(_) => ErrorKind::Unauthorized,
+            CheckError::Challenge(_) => ErrorKind::Challenge,
+            CheckError::BadBody { .. } => ErrorKind::BadBody,
+            CheckError::TooLarge { .. } => ErrorKind::TooLarge,
+            CheckError::Fixture { .. } => ErrorKind::Fixture,
+            CheckError::Strict { .. } => ErrorKind::Strict,
+            CheckError::Panic(_) => ErrorKind::Panic,
+        }
+    }
+
+    /// The HTTP status the API answered with, for errors that come from one
+    pub fn http_status(&self) -> Option<StatusCode> {
+        match self {
+            CheckError::RateLimited { .. } => Some(StatusCode::TOO_MANY_REQUESTS),
+            CheckError::HttpStatus(status)
+            | CheckError::Unauthorized(status)
+            | CheckError::Challenge(status) => Some(*status),
+            CheckError::Network(_)
+            | CheckError::Timeout
+            | CheckError::Resolve { .. }
+            | CheckError::BadBody { .. }
+            | CheckError::TooLarge { .. }
+            | CheckError::Fixture { .. }
+            | CheckError::Strict { .. }
+            | CheckError::Panic(_) => None,
+        }
+    }
+
+    pub(crate) fn bad_body(text: &str) -> Self {
+        let mut end = text.len().min(SNIPPET_LEN);
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        CheckError::BadBody {
+            snippet: text[..end].to_owned(),
+        }
+    }
+}
+
+/// What went wrong in a check, without the details; stays meaningful after
+/// the error has been written to disk
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    Network,
+    Timeout,
+    Dns,
+    RateLimited,
+    HttpStatus,
+    Unauthorized,
+    Challenge,
+    BadBody,
+    TooLarge,
+    Fixture,
+    Strict,
+    Panic,
+}
+
+impl ErrorKind {
+    /// Short, stable label used in error reports and summaries
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorKind::Network => "network",
+            ErrorKind::Timeout => "timeout",
+            ErrorKind::Dns => "dns",
+            ErrorKind::RateLimited => "rate_limited",
+            ErrorKind::HttpStatus => "http_status",
+            ErrorKind::Unauthorized => "unauthorized",
+            ErrorKind::Challenge => "challenge",
+            ErrorKind::BadBody => "bad_body",
+            ErrorKind::TooLarge => "too_large",
+            ErrorKind::Fixture => "fixture",
+            ErrorKind::Strict => "strict",
+            ErrorKind::Panic => "panic",
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<reqwest::Error> for CheckError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            CheckError::Timeout
+        } else if let Some(resolve) = resolve_error(&err) {
+            resolve
+        } else {
+            CheckError::Network(err)
+        }
+    }
+}
+
+/// The failed lookup somewhere in `err`'s sources, if that's what it was
+fn resolve_error(err: &reqwest::Error) -> Option<CheckError> {
+    let sources = || std::iter::successors(err.source(), |cause| (*cause).source());
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(doh) = sources().find_map(|cause| cause.downcast_ref::<DohError>()) {
+        return Some(CheckError::Resolve {
+            host: doh.name.clone(),
+            resolver: format!("DNS-over-HTTPS server {}", doh.server),
+            reason: doh.reason.clone(),
+        });
+    }
+    // what hyper calls a failure of the system resolver
+    let cause = sources().find(|cause| cause.to_string().starts_with("dns error"))?;
+    Some(CheckError::Resolve {
+        host: err
+            .url()
+            .and_then(|url| url.host_str())
+            .unwrap_or("the api's host")
+            .to_owned(),
+        resolver: "the system resolver".to_owned(),
+        reason: cause
+            .source()
+            .map_or_else(|| cause.to_string(), |c| c.to_string()),
+    })
+}
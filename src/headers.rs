@@ -0,0 +1,139 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use reqwest::header::{
+    HeaderMap, HeaderName, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use reqwest::{RequestBuilder, StatusCode};
+use tracing::debug;
+
+use crate::cache::Validators;
+
+/// Headers that say something about the API's limits or help trace a request
+const INTERESTING: &[&str] = &[
+    "content-type",
+    "cf-ray",
+    "x-request-id",
+    "retry-after",
+    "x-ratelimit-limit",
+    "x-ratelimit-remaining",
+    "x-ratelimit-reset",
+    "ratelimit-limit",
+    "ratelimit-remaining",
+    "ratelimit-reset",
+];
+
+const REMAINING: &[&str] = &["x-ratelimit-remaining", "ratelimit-remaining"];
+
+/// What the responses to the check running in this task said
+#[derive(Default)]
+pub(crate) struct Captured {
+    /// The interesting headers of the last response
+    pub headers: BTreeMap<String, String>,
+    /// The lowest remaining rate limit quota any of its responses reported
+    pub min_remaining: Option<u64>,
+    /// What [`last_request`] reads
+    pub last: LastRequest,
+    /// The validators of the last response
+    pub validators: Validators,
+    /// Validators of a cached answer, sent along with the requests until
+    /// they're taken away
+    pub conditional: Option<Validators>,
+}
+
+/// The latest request of the check running in this task
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LastRequest {
+    /// How many requests the check has sent, this one included; above 1,
+    /// this one was a retry
+    pub number: u32,
+    /// `None` if no response came
+    pub status: Option<StatusCode>,
+}
+
+/// What the check running in this task last sent to a backend, for
+/// instrumenting [`Backend`](crate::backend::Backend)s. `None` outside of a
+/// check.
+pub fn last_request() -> Option<LastRequest> {
+    CAPTURED.try_with(|captured| captured.borrow().last).ok()
+}
+
+/// Counts a request going out for the check running in this task
+pub(crate) fn sending() {
+    let _ = CAPTURED.try_with(|captured| {
+        let last = &mut captured.borrow_mut().last;
+        last.number += 1;
+        last.status = None;
+    });
+}
+
+/// Makes the requests of the check running in this task conditional on
+/// the answer having changed since `validators` were given, or not
+pub(crate) fn set_conditional(validators: Option<Validators>) {
+    let _ = CAPTURED.try_with(|captured| captured.borrow_mut().conditional = validators);
+}
+
+/// Adds the headers that make `request` conditional, if it's for a check
+/// that has validators to send
+pub(crate) fn conditional(request: RequestBuilder) -> RequestBuilder {
+    let validators = CAPTURED
+        .try_with(|captured| captured.borrow().conditional.clone())
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+    let request = match validators.etag {
+        Some(etag) => request.header(IF_NONE_MATCH, etag),
+        None => request,
+    };
+    match validators.last_modified {
+        Some(last_modified) => request.header(IF_MODIFIED_SINCE, last_modified),
+        None => request,
+    }
+}
+
+/// The validators of the last response of the check running in this task
+pub(crate) fn validators() -> Validators {
+    CAPTURED
+        .try_with(|captured| captured.borrow().validators.clone())
+        .unwrap_or_default()
+}
+
+tokio::task_local! {
+    pub(crate) static CAPTURED: RefCell<Captured>;
+}
+
+/// Keeps the status and interesting headers of a response for the check
+/// running in this task, and logs the headers
+pub(crate) fn capture(status: StatusCode, headers: &HeaderMap) {
+    let interesting: BTreeMap<String, String> = INTERESTING
+        .iter()
+        .filter_map(|name| {
+            let value = headers.get(*name)?.to_str().ok()?;
+            Some(((*name).to_owned(), value.to_owned()))
+        })
+        .collect();
+    let remaining = REMAINING
+        .iter()
+        .find_map(|name| interesting.get(*name)?.trim().parse::<u64>().ok());
+    debug!(headers = ?interesting, "response headers");
+    let _ = CAPTURED.try_with(|captured| {
+        let mut captured = captured.borrow_mut();
+        captured.last.status = Some(status);
+        if let Some(remaining) = remaining {
+            captured.min_remaining = Some(
+                captured
+                    .min_remaining
+                    .map_or(remaining, |min| min.min(remaining)),
+            );
+        }
+        captured.headers = interesting;
+        captured.validators = Validators {
+            etag: validator(headers, ETAG),
+            last_modified: validator(headers, LAST_MODIFIED),
+        };
+    });
+}
+
+fn validator(headers: &HeaderMap, name: HeaderName) -> Option<String> {
+    Some(headers.get(name)?.to_str().ok()?.to_owned())
+}
@@ -0,0 +1,84 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+use serde::Serialize;
+
+use crate::backend::Backend;
+use crate::headers::CAPTURED;
+use crate::{build_client, CheckError, CheckOptions};
+
+/// What a probe found out about a backend
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Health {
+    /// It answered the way it answers checks
+    Up,
+    /// It couldn't be reached, timed out or answered with a 5xx
+    Down,
+    RateLimited,
+    /// It wants an API key, a different one, or a browser
+    Refused,
+    /// It answered, but not with anything a check could use
+    Unexpected,
+}
+
+impl Health {
+    pub fn of(error: Option<&CheckError>) -> Self {
+        match error {
+            None => Health::Up,
+            Some(CheckError::Network(_) | CheckError::Timeout | CheckError::Resolve { .. }) => {
+                Health::Down
+            }
+            Some(CheckError::HttpStatus(status)) if status.is_server_error() => Health::Down,
+            Some(CheckError::RateLimited { .. }) => Health::RateLimited,
+            Some(CheckError::Unauthorized(_) | CheckError::Challenge(_)) => Health::Refused,
+            Some(_) => Health::Unexpected,
+        }
+    }
+}
+
+/// One request to a backend and how it went
+#[derive(Serialize, Debug)]
+pub struct Probe {
+    pub source: String,
+    pub url: String,
+    pub health: Health,
+    /// Until the answer was read, or the request failed
+    pub latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// The rate limit and tracing headers of the answer
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Asks `backend` about `id` once, without retrying, to see whether it's
+/// answering
+pub async fn probe(backend: &dyn Backend, options: &CheckOptions, id: &str) -> Probe {
+    let request = async {
+        let started = Instant::now();
+        let result = match build_client(options) {
+            Ok(client) => backend.check(&client, id).await.map(drop),
+            Err(err) => Err(err.into()),
+        };
+        let latency = started.elapsed();
+        let (status, headers) = CAPTURED.with(|captured| {
+            let captured = captured.borrow();
+            (captured.last.status, captured.headers.clone())
+        });
+        (result.err(), latency, status, headers)
+    };
+    let (error, latency, status, headers) = CAPTURED.scope(RefCell::default(), request).await;
+    Probe {
+        source: backend.name().to_owned(),
+        url: backend.url(id),
+        health: Health::of(error.as_ref()),
+        latency_ms: latency.as_secs_f64() * 1000.0,
+        status: status.map(|status| status.as_u16()),
+        headers,
+        error: error.map(|err| err.to_string()),
+    }
+}
@@ -0,0 +1,144 @@
+use std::io;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::state::StateDir;
+use crate::{RunReport, Status};
+
+/// A finished run as kept in the state directory's history
+#[derive(Serialize, Deserialize)]
+pub struct RunRecord {
+    pub run_id: String,
+    pub started_at: DateTime<Utc>,
+    /// Number of guilds in the index
+    pub index_size: usize,
+    pub report: RunReport,
+}
+
+impl RunRecord {
+    pub fn new(started_at: DateTime<Utc>, index_size: usize, report: RunReport) -> Self {
+        Self {
+            run_id: started_at.format("%Y%m%dT%H%M%SZ").to_string(),
+            started_at,
+            index_size,
+            report,
+        }
+    }
+}
+
+/// What one backend said about a guild in a stored run
+#[derive(Clone, Debug)]
+pub struct Sighting {
+    pub run_id: String,
+    pub started_at: DateTime<Utc>,
+    pub source: String,
+    /// The failed check's message, if it failed
+    pub outcome: Result<Status, String>,
+}
+
+/// Stored runs, oldest first
+pub struct History {
+    state: StateDir,
+}
+
+impl History {
+    pub fn new(state: StateDir) -> Self {
+        Self { state }
+    }
+
+    /// Stores `record`, renaming it if a run with the same ID already exists
+    pub fn save(&self, record: &mut RunRecord) -> io::Result<PathBuf> {
+        let base = record.run_id.clone();
+        let mut path = self.state.run_path(&record.run_id)?;
+        let mut n = 1;
+        while path.exists() {
+            record.run_id = format!("{base}-{n}");
+            path = self.state.run_path(&record.run_id)?;
+            n += 1;
+        }
+
+        let json = serde_json::to_vec(record)?;
+        std::fs::write(&path, json)?;
+        Ok(path)
+    }
+
+    pub fn load(&self, run_id: &str) -> io::Result<RunRecord> {
+        let bytes = std::fs::read(self.state.run_path(run_id)?)?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    /// IDs of every stored run, oldest first
+    pub fn run_ids(&self) -> io::Result<Vec<String>> {
+        let mut ids: Vec<String> = std::fs::read_dir(self.state.history_dir()?)?
+            .filter_map(|entry| {
+                let name = entry.ok()?.file_name().into_string().ok()?;
+                name.strip_suffix(".json").map(str::to_owned)
+            })
+            .collect();
+        // IDs start with the timestamp, so this is chronological
+        ids.sort();
+        Ok(ids)
+    }
+
+    pub fn remove(&self, run_id: &str) -> io::Result<()> {
+        std::fs::remove_file(self.state.run_path(run_id)?)
+    }
+
+    /// Deletes all but the `keep` most recent runs, returning the removed IDs
+    pub fn prune(&self, keep: usize) -> io::Result<Vec<String>> {
+        let ids = self.run_ids()?;
+        let remove = ids.len().saturating_sub(keep);
+        let removed: Vec<String> = ids.into_iter().take(remove).collect();
+        for id in &removed {
+            self.remove(id)?;
+        }
+        Ok(removed)
+    }
+
+    /// Every stored run that started in `since..until`, oldest first. Runs
+    /// that can't be read are skipped with a warning.
+    pub fn runs(
+        &self,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+    ) -> io::Result<Vec<RunRecord>> {
+        let mut runs = Vec::new();
+        for id in self.run_ids()? {
+            match self.load(&id) {
+                Ok(record)
+                    if since.is_none_or(|since| record.started_at >= since)
+                        && until.is_none_or(|until| record.started_at < until) =>
+                {
+                    runs.push(record)
+                }
+                Ok(_) => {}
+                Err(err) => warn!(%err, "skipping unreadable run {id}"),
+            }
+        }
+        Ok(runs)
+    }
+
+    /// What the stored runs found about the guild or user `id`, oldest first
+    pub fn sightings(&self, id: &str) -> io::Result<Vec<Sighting>> {
+        let mut sightings = Vec::new();
+        for record in self.runs(None, None)? {
+            let sighting = |source: &str, outcome| Sighting {
+                run_id: record.run_id.clone(),
+                started_at: record.started_at,
+                source: source.to_owned(),
+                outcome,
+            };
+            let report = &record.report;
+            for response in report.results.iter().filter(|r| r.guild_id == id) {
+                sightings.push(sighting(&response.source, Ok(response.status())));
+            }
+            for failed in report.failed.iter().filter(|f| f.guild_id == id) {
+                sightings.push(sighting(&failed.source, Err(failed.message.clone())));
+            }
+        }
+        Ok(sightings)
+    }
+}
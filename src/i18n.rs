@@ -0,0 +1,134 @@
+//! The languages the plain output is written in, as Fluent bundles built
+//! into the binary. Logs and the machine-readable formats stay English.
+
+use std::fmt;
+use std::str::FromStr;
+
+use fluent_bundle::{FluentArgs, FluentBundle, FluentResource, FluentValue};
+use unic_langid::LanguageIdentifier;
+
+/// A language the plain output can be written in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Lang {
+    #[default]
+    En,
+    De,
+    Es,
+    Fr,
+    Pt,
+    Ru,
+}
+
+impl Lang {
+    pub const ALL: [Lang; 6] = [Lang::En, Lang::De, Lang::Es, Lang::Fr, Lang::Pt, Lang::Ru];
+
+    /// The language of the system's locale, or English if there's none for
+    /// it
+    pub fn detect() -> Self {
+        sys_locale::get_locale()
+            .and_then(|locale| locale.parse().ok())
+            .unwrap_or_default()
+    }
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Lang::En => "en",
+            Lang::De => "de",
+            Lang::Es => "es",
+            Lang::Fr => "fr",
+            Lang::Pt => "pt",
+            Lang::Ru => "ru",
+        }
+    }
+
+    fn source(self) -> &'static str {
+        match self {
+            Lang::En => include_str!("locales/en.ftl"),
+            Lang::De => include_str!("locales/de.ftl"),
+            Lang::Es => include_str!("locales/es.ftl"),
+            Lang::Fr => include_str!("locales/fr.ftl"),
+            Lang::Pt => include_str!("locales/pt.ftl"),
+            Lang::Ru => include_str!("locales/ru.ftl"),
+        }
+    }
+}
+
+/// Takes a language code or a whole locale, like `de`, `pt-BR` or
+/// `fr_FR.UTF-8`, by its language
+impl FromStr for Lang {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let language = s
+            .split(['-', '_', '.', '@'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        Lang::ALL
+            .into_iter()
+            .find(|lang| lang.code() == language)
+            .ok_or_else(|| {
+                let codes: Vec<&str> = Lang::ALL.iter().map(|lang| lang.code()).collect();
+                format!("no translation for {s:?}, there are {}", codes.join(", "))
+            })
+    }
+}
+
+impl fmt::Display for Lang {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+fn bundle(lang: Lang) -> FluentBundle<FluentResource> {
+    let id: LanguageIdentifier = lang.code().parse().expect("a valid language code");
+    let resource = FluentResource::try_new(lang.source().to_owned())
+        .unwrap_or_else(|(_, errors)| panic!("the {lang} messages don't parse: {errors:?}"));
+    let mut bundle = FluentBundle::new(vec![id]);
+    // the marks that keep right-to-left names apart only garble a terminal
+    bundle.set_use_isolating(false);
+    bundle
+        .add_resource(resource)
+        .expect("message ids are unique");
+    bundle
+}
+
+/// The messages of one language, and the English ones for any it's missing
+pub(crate) struct Messages {
+    bundle: FluentBundle<FluentResource>,
+    fallback: Option<FluentBundle<FluentResource>>,
+}
+
+impl Messages {
+    pub fn new(lang: Lang) -> Self {
+        Self {
+            bundle: bundle(lang),
+            fallback: (lang != Lang::En).then(|| bundle(Lang::En)),
+        }
+    }
+
+    pub fn get(&self, id: &str) -> String {
+        self.format(id, None)
+    }
+
+    pub fn with<'a>(
+        &self,
+        id: &str,
+        args: impl IntoIterator<Item = (&'a str, FluentValue<'a>)>,
+    ) -> String {
+        let args: FluentArgs = args.into_iter().collect();
+        self.format(id, Some(&args))
+    }
+
+    fn format(&self, id: &str, args: Option<&FluentArgs>) -> String {
+        let (bundle, message) = std::iter::once(&self.bundle)
+            .chain(&self.fallback)
+            .find_map(|bundle| Some((bundle, bundle.get_message(id)?)))
+            .unwrap_or_else(|| panic!("no message {id:?}"));
+        let pattern = message.value().expect("messages have a value");
+        let mut errors = Vec::new();
+        bundle
+            .format_pattern(pattern, args, &mut errors)
+            .into_owned()
+    }
+}
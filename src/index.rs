@@ -0,0 +1,630 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::{fmt, io};
+
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess};
+use thiserror::Error;
+
+/// Whether `s` looks like a Discord ID: a snowflake, 17 to 20 digits
+pub fn is_snowflake(s: &str) -> bool {
+    (17..=20).contains(&s.len()) && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Which of `count` shards `id` falls in, from 0. The same on every machine
+/// and every version, so runs splitting an index between them agree on it.
+pub fn shard_of(id: &str, count: u32) -> u32 {
+    // FNV-1a, which unlike the std hasher is stable
+    let hash = id.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    (hash % u64::from(count.max(1))) as u32
+}
+
+/// Takes the entries with an invite link on either side out of `index`, as
+/// (link, the other side) pairs, to be looked up instead of read as they are
+pub fn take_invites(index: &mut BTreeMap<String, String>) -> Vec<(String, String)> {
+    let is_invite = |s: &str| crate::discord::invite_code(s).is_some();
+    let mut invites = Vec::new();
+    index.retain(|key, value| match (is_invite(key), is_invite(value)) {
+        (true, _) => {
+            invites.push((key.clone(), value.clone()));
+            false
+        }
+        (false, true) => {
+            invites.push((value.clone(), key.clone()));
+            false
+        }
+        (false, false) => true,
+    });
+    invites
+}
+
+/// What an entry's key starts with when the index only knows the server's
+/// name: `? Art Club` in a text index, or `"?Art Club": ""` in a map
+pub const NAME_ONLY: char = '?';
+
+/// Takes the name-only entries out of `index`, as the names to search for
+pub fn take_names(index: &mut BTreeMap<String, String>) -> Vec<String> {
+    let mut names = Vec::new();
+    index.retain(|key, value| {
+        let Some(name) = key.strip_prefix(NAME_ONLY) else {
+            return true;
+        };
+        let name = match name.trim() {
+            "" => value.trim(),
+            name => name,
+        };
+        if !name.is_empty() {
+            names.push(name.to_owned());
+        }
+        false
+    });
+    names
+}
+
+/// Which way round an index was written
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Orientation {
+    /// `{"<id>": "<name>"}`, as expected
+    IdToName,
+    /// `{"<name>": "<id>"}`
+    NameToId,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum OrientError {
+    #[error(
+        "both the keys and the values of the index look like server IDs, expected {{\"<server id>\": \"<server name>\"}}"
+    )]
+    Ambiguous,
+
+    #[error(
+        "neither the keys nor the values of the index are all server IDs, expected {{\"<server id>\": \"<server name>\"}}"
+    )]
+    NoIds,
+}
+
+/// Works out which side of `index` holds the IDs and returns it as id →
+/// name. An empty index is taken as it is.
+pub fn orient(
+    index: BTreeMap<String, String>,
+) -> Result<(BTreeMap<String, String>, Orientation), OrientError> {
+    if index.is_empty() {
+        return Ok((index, Orientation::IdToName));
+    }
+    let keys = index.keys().all(|k| is_snowflake(k));
+    let values = index.values().all(|v| is_snowflake(v));
+    match (keys, values) {
+        (true, false) => Ok((index, Orientation::IdToName)),
+        (false, true) => {
+            let flipped = index.into_iter().map(|(name, id)| (id, name)).collect();
+            Ok((flipped, Orientation::NameToId))
+        }
+        (true, true) => Err(OrientError::Ambiguous),
+        (false, false) => Err(OrientError::NoIds),
+    }
+}
+
+/// Hands each entry of an object to its closure as it's read, in the order
+/// written, duplicate keys and all
+struct EachEntry<F>(F);
+
+impl<'de, F: FnMut(String, String)> DeserializeSeed<'de> for EachEntry<F> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, F: FnMut(String, String)> de::Visitor<'de> for EachEntry<F> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an object of strings")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<(), A::Error> {
+        while let Some((key, value)) = map.next_entry()? {
+            (self.0)(key, value);
+        }
+        Ok(())
+    }
+}
+
+/// The file extensions of indexes, which the files of a directory of them
+/// are picked by
+pub const EXTENSIONS: &[&str] = &["json", "txt", "tsv", "csv", "yaml", "yml", "toml"];
+
+/// How an index is written
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexFormat {
+    /// A JSON object of strings
+    Json,
+    /// An ID per line, optionally followed by the name
+    Text,
+    /// `id,name` rows, with or without a header naming the columns
+    Csv,
+    /// A flat mapping of strings
+    Yaml,
+    /// A table of strings
+    Toml,
+}
+
+impl IndexFormat {
+    /// The format `path`'s extension says, if it's one of [`EXTENSIONS`]
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref()? {
+            "json" => Some(IndexFormat::Json),
+            "txt" | "tsv" => Some(IndexFormat::Text),
+            "csv" => Some(IndexFormat::Csv),
+            "yaml" | "yml" => Some(IndexFormat::Yaml),
+            "toml" => Some(IndexFormat::Toml),
+            _ => None,
+        }
+    }
+
+    /// The format `path`'s extension says, or else the one `text` looks like
+    pub fn detect(path: &Path, text: &str) -> Self {
+        if let Some(format) = Self::from_extension(path) {
+            return format;
+        }
+        if is_json(text) {
+            return IndexFormat::Json;
+        }
+        let first = text
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#'));
+        let Some(first) = first else {
+            return IndexFormat::Text;
+        };
+        let key = first.split_whitespace().next().unwrap_or_default();
+        // an ID or a header's column name, not the name after a text ID
+        let first_field = first.split(',').next().unwrap_or_default().trim();
+        let toml = first.split_once('=').is_some_and(|(key, value)| {
+            let key = key.trim();
+            (key.starts_with(['"', '\'']) || !key.contains(' '))
+                && value.trim_start().starts_with(['"', '\''])
+        });
+        if first == "---" || key.ends_with(':') || (first.starts_with(['"', '\'']) && !toml) {
+            IndexFormat::Yaml
+        } else if toml {
+            IndexFormat::Toml
+        } else if first.contains(',') && !first.contains('\t') && !first_field.contains(' ') {
+            IndexFormat::Csv
+        } else {
+            IndexFormat::Text
+        }
+    }
+
+    /// Whether it's a map, which can be written either way round
+    pub fn is_map(self) -> bool {
+        matches!(
+            self,
+            IndexFormat::Json | IndexFormat::Yaml | IndexFormat::Toml
+        )
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ParseError {
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    Toml(#[from] toml::de::Error),
+
+    #[error(transparent)]
+    Line(#[from] LineError),
+}
+
+/// Parses an index written as `format`. Also returns the keys written more
+/// than once, of which the last one counts.
+pub fn parse_as(
+    format: IndexFormat,
+    text: &str,
+) -> Result<(BTreeMap<String, String>, Vec<String>), ParseError> {
+    Ok(match format {
+        IndexFormat::Json => parse(text)?,
+        IndexFormat::Text => parse_lines(text)?,
+        IndexFormat::Csv => parse_csv(text)?,
+        IndexFormat::Yaml => parse_yaml(text)?,
+        // TOML doesn't allow a key twice
+        IndexFormat::Toml => (toml::from_str(text)?, Vec::new()),
+    })
+}
+
+/// A line of a text, CSV or YAML index that couldn't be read
+#[derive(Error, Debug, PartialEq, Eq)]
+#[error("line {line}: {reason}: {text:?}")]
+pub struct LineError {
+    pub line: usize,
+    pub reason: &'static str,
+    pub text: String,
+}
+
+impl LineError {
+    fn new(line: usize, reason: &'static str, text: &str) -> Self {
+        Self {
+            line,
+            reason,
+            text: text.to_owned(),
+        }
+    }
+}
+
+/// Whether `text` is written as a JSON index rather than a plain text one
+pub fn is_json(text: &str) -> bool {
+    text.trim_start_matches('\u{feff}')
+        .trim_start()
+        .starts_with('{')
+}
+
+fn is_id(s: &str) -> bool {
+    is_snowflake(s) || crate::discord::invite_code(s).is_some()
+}
+
+const NOT_AN_ID: &str = "expected a server ID or invite link first";
+
+/// Adds `key`, noting it in `duplicates` if it was already there
+fn insert(
+    index: &mut BTreeMap<String, String>,
+    duplicates: &mut Vec<String>,
+    key: String,
+    value: String,
+) {
+    if index.insert(key.clone(), value).is_some() && !duplicates.contains(&key) {
+        duplicates.push(key);
+    }
+}
+
+/// Parses a plain text index: an ID (or invite link) per line, optionally
+/// followed by a tab or space and the name, or a [`NAME_ONLY`] line. Blank
+/// lines and `#` comments are skipped, and an ID without a name goes by the
+/// ID. Like [`parse`], also returns the IDs listed more than once.
+pub fn parse_lines(text: &str) -> Result<(BTreeMap<String, String>, Vec<String>), LineError> {
+    let mut index = BTreeMap::new();
+    let mut duplicates = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with(NAME_ONLY) {
+            insert(&mut index, &mut duplicates, line.to_owned(), String::new());
+            continue;
+        }
+        let (id, name) = match line.split_once(['\t', ' ']) {
+            Some((id, name)) => (id, name.trim()),
+            None => (line, line),
+        };
+        if !is_id(id) {
+            return Err(LineError::new(n + 1, NOT_AN_ID, line));
+        }
+        insert(&mut index, &mut duplicates, id.to_owned(), name.to_owned());
+    }
+    Ok((index, duplicates))
+}
+
+/// The records of `text` as CSV, with the line each starts on. Fields can be
+/// quoted as RFC 4180 has it, with commas, doubled quotes and line breaks in
+/// them. Blank lines are skipped.
+fn csv_records(text: &str) -> Result<Vec<(usize, Vec<String>)>, LineError> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let (mut line, mut start) = (1, 1);
+    let mut quoted = false;
+    let mut chars = text.trim_start_matches('\u{feff}').chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => match chars.peek() {
+                Some('"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                _ => quoted = false,
+            },
+            '"' if field.is_empty() => quoted = true,
+            '\n' if quoted => {
+                field.push(c);
+                line += 1;
+            }
+            ',' if !quoted => record.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push((start, std::mem::take(&mut record)));
+                line += 1;
+                start = line;
+            }
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(LineError::new(start, "a quoted field isn't closed", &field));
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push((start, record));
+    }
+    records.retain(|(_, record)| !matches!(record.as_slice(), [field] if field.trim().is_empty()));
+    Ok(records)
+}
+
+/// Parses a CSV index: `id,name` rows, or any columns under a header with an
+/// `id` (or `guild_id`, `server_id`) and a `name` (or `guild_name`,
+/// `server_name`) column. Like [`parse`], also returns the IDs listed more
+/// than once.
+pub fn parse_csv(text: &str) -> Result<(BTreeMap<String, String>, Vec<String>), LineError> {
+    let records = csv_records(text)?;
+    let mut rows = records.iter().peekable();
+    let (mut id_column, mut name_column) = (0, Some(1));
+    if let Some((line, header)) = rows.peek() {
+        if !header.first().is_some_and(|field| is_id(field.trim())) {
+            let column = |names: &[&str]| {
+                header
+                    .iter()
+                    .position(|field| names.contains(&field.trim().to_ascii_lowercase().as_str()))
+            };
+            id_column = column(&["id", "guild_id", "server_id"]).ok_or_else(|| {
+                LineError::new(
+                    *line,
+                    "expected an id column or a server ID first",
+                    &header.join(","),
+                )
+            })?;
+            name_column = column(&["name", "guild_name", "server_name"]);
+            rows.next();
+        }
+    }
+
+    let mut index = BTreeMap::new();
+    let mut duplicates = Vec::new();
+    for (line, record) in rows {
+        let field = |column: usize| record.get(column).map(|field| field.trim());
+        let id = field(id_column).unwrap_or_default();
+        if !is_id(id) {
+            return Err(LineError::new(*line, NOT_AN_ID, &record.join(",")));
+        }
+        let name = name_column
+            .and_then(field)
+            .filter(|name| !name.is_empty())
+            .unwrap_or(id);
+        insert(&mut index, &mut duplicates, id.to_owned(), name.to_owned());
+    }
+    Ok((index, duplicates))
+}
+
+/// A YAML scalar at the start of `s`, and what follows it. A plain key ends
+/// at its `:`, a plain value at a ` #` comment.
+fn yaml_scalar(s: &str, key: bool) -> Option<(String, &str)> {
+    let mut chars = s.char_indices().peekable();
+    match chars.next()?.1 {
+        '"' => {
+            let mut scalar = String::new();
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '"' => return Some((scalar, &s[i + 1..])),
+                    '\\' => scalar.push(match chars.next()?.1 {
+                        'n' => '\n',
+                        't' => '\t',
+                        '0' => '\0',
+                        escaped => escaped,
+                    }),
+                    c => scalar.push(c),
+                }
+            }
+            None
+        }
+        '\'' => {
+            let mut scalar = String::new();
+            while let Some((i, c)) = chars.next() {
+                match (c, chars.peek()) {
+                    ('\'', Some((_, '\''))) => {
+                        chars.next();
+                        scalar.push('\'');
+                    }
+                    ('\'', _) => return Some((scalar, &s[i + 1..])),
+                    (c, _) => scalar.push(c),
+                }
+            }
+            None
+        }
+        _ if key => {
+            let bytes = s.as_bytes();
+            let end = (0..bytes.len()).find(|&i| {
+                bytes[i] == b':' && bytes.get(i + 1).is_none_or(u8::is_ascii_whitespace)
+            })?;
+            Some((s[..end].trim_end().to_owned(), &s[end..]))
+        }
+        _ => {
+            let end = s.find(" #").unwrap_or(s.len());
+            Some((s[..end].trim_end().to_owned(), &s[end..]))
+        }
+    }
+}
+
+/// Parses a YAML index. Only a flat mapping is read: `key: value` lines of
+/// plain or quoted strings, and comments. Like [`parse`], also returns the
+/// keys written more than once.
+pub fn parse_yaml(text: &str) -> Result<(BTreeMap<String, String>, Vec<String>), LineError> {
+    const FLAT: &str = "only a flat mapping of IDs and names is read";
+    const ENTRY: &str = "expected key: value";
+
+    let mut index = BTreeMap::new();
+    let mut duplicates = Vec::new();
+    for (n, line) in text.trim_start_matches('\u{feff}').lines().enumerate() {
+        let line = line.trim_end();
+        let content = line.trim_start();
+        if content.is_empty() || content.starts_with('#') || matches!(content, "---" | "...") {
+            continue;
+        }
+        let error = |reason| LineError::new(n + 1, reason, content);
+        if content.len() != line.len() || content.starts_with(['-', '[', '{']) {
+            return Err(error(FLAT));
+        }
+        let (key, rest) = yaml_scalar(content, true).ok_or_else(|| error(ENTRY))?;
+        let rest = rest
+            .trim_start()
+            .strip_prefix(':')
+            .ok_or_else(|| error(ENTRY))?;
+        let rest = rest.trim_start();
+        if rest.is_empty() || rest.starts_with(['#', '[', '{', '|', '>']) {
+            // a nested mapping or list, or a block of text
+            return Err(error(FLAT));
+        }
+        let (value, rest) = yaml_scalar(rest, false).ok_or_else(|| error(ENTRY))?;
+        let rest = rest.trim_start();
+        if !rest.is_empty() && !rest.starts_with('#') {
+            return Err(error(ENTRY));
+        }
+        insert(&mut index, &mut duplicates, key, value);
+    }
+    Ok((index, duplicates))
+}
+
+/// Parses an index, a JSON object of strings. Also returns the keys written
+/// more than once, of which the last one counts.
+pub fn parse(json: &str) -> serde_json::Result<(BTreeMap<String, String>, Vec<String>)> {
+    let mut index = BTreeMap::new();
+    let mut duplicates = Vec::new();
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    EachEntry(|key, value| insert(&mut index, &mut duplicates, key, value))
+        .deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok((index, duplicates))
+}
+
+/// Reads a JSON index from `reader` as it's parsed, handing each entry to
+/// `each` without ever holding the whole text, for indexes too big to
+/// read in one go
+pub fn parse_reader(
+    reader: impl io::Read,
+    each: impl FnMut(String, String),
+) -> serde_json::Result<()> {
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    EachEntry(each).deserialize(&mut deserializer)?;
+    deserializer.end()
+}
+
+/// [`parse`], reading from `reader` as it goes
+pub fn parse_from(
+    reader: impl io::Read,
+) -> serde_json::Result<(BTreeMap<String, String>, Vec<String>)> {
+    let mut index = BTreeMap::new();
+    let mut duplicates = Vec::new();
+    parse_reader(reader, |key, value| {
+        insert(&mut index, &mut duplicates, key, value)
+    })?;
+    Ok((index, duplicates))
+}
+
+/// Something wrong with an index entry
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Problem {
+    /// The key isn't a Discord ID, so there's nothing to check
+    NotAnId,
+    /// The key is written more than once, and the last one counts
+    Duplicate,
+    /// The entry has no name, so it goes by its ID
+    EmptyName,
+}
+
+impl Problem {
+    /// Whether the index can't be checked as it is
+    pub fn is_error(self) -> bool {
+        self == Problem::NotAnId
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Problem::NotAnId => "isn't a Discord ID",
+            Problem::Duplicate => "is listed more than once",
+            Problem::EmptyName => "has no name",
+        })
+    }
+}
+
+/// A problem with the entry `key`, on `line` if it could be found
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub line: Option<usize>,
+    pub key: String,
+    pub problem: Problem,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "line {line}: ")?;
+        }
+        write!(f, "{:?} {}", self.key, self.problem)
+    }
+}
+
+/// `index` with the whitespace around its keys and values, always a slip,
+/// trimmed off
+pub fn normalize(index: BTreeMap<String, String>) -> BTreeMap<String, String> {
+    index
+        .into_iter()
+        .map(|(key, value)| (key.trim().to_owned(), value.trim().to_owned()))
+        .collect()
+}
+
+/// `index` as id → name going by whichever side has more IDs, for an index
+/// [`orient`] can't make out because some entries are broken
+pub fn mostly_ids(index: BTreeMap<String, String>) -> BTreeMap<String, String> {
+    let keys = index.keys().filter(|key| is_snowflake(key)).count();
+    let values = index.values().filter(|value| is_snowflake(value)).count();
+    match keys >= values {
+        true => index,
+        false => index.into_iter().map(|(name, id)| (id, name)).collect(),
+    }
+}
+
+/// The line `needle` is first on in `text`, counting from 1
+fn line_of(text: &str, needle: &str) -> Option<usize> {
+    if needle.is_empty() {
+        return None;
+    }
+    let at = text.find(needle)?;
+    Some(text[..at].matches('\n').count() + 1)
+}
+
+/// What's wrong with the entries of `index`, read as id → name from `text`,
+/// with the keys [`parse`] found more than once. In line order.
+pub fn validate(
+    text: &str,
+    index: &BTreeMap<String, String>,
+    duplicates: &[String],
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut find = |key: &str, problem| {
+        findings.push(Finding {
+            line: line_of(text, key),
+            key: key.to_owned(),
+            problem,
+        })
+    };
+    for (key, name) in index {
+        if !is_snowflake(key) {
+            find(key, Problem::NotAnId);
+        }
+        if name.is_empty() {
+            find(key, Problem::EmptyName);
+        }
+    }
+    for key in duplicates {
+        find(key, Problem::Duplicate);
+    }
+    findings.sort_by_key(|finding| (finding.line.is_none(), finding.line));
+    findings
+}
@@ -0,0 +1,110 @@
+//! The library behind spy-pet-checker. Built for `wasm32-unknown-unknown`
+//! with the `wasm` feature, it has only the parts that need neither tokio's
+//! runtime nor the file system, and the `wasm` module on top to run checks
+//! from a web page.
+
+pub mod answer;
+pub mod backend;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cache;
+#[cfg(not(target_arch = "wasm32"))]
+mod check;
+#[cfg(not(target_arch = "wasm32"))]
+mod checker;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod checkpoint;
+#[cfg(not(target_arch = "wasm32"))]
+mod client;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod dataset;
+pub mod deep;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod diff;
+pub mod discord;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod dns;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod dump;
+#[cfg(feature = "encrypt")]
+pub mod encrypt;
+mod error;
+#[cfg(not(target_arch = "wasm32"))]
+mod headers;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod health;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod history;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod i18n;
+pub mod index;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod mail;
+pub mod members;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod merge;
+pub mod notify;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod oauth;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod output;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod pacing;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod prefilter;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod proxy;
+#[cfg(not(target_arch = "wasm32"))]
+mod report;
+mod result;
+pub mod retry;
+mod schema;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod search;
+pub mod secret;
+pub mod severity;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod shared;
+#[cfg(feature = "sign")]
+pub mod sign;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod state;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod stats;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tor;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod verify;
+pub mod warnings;
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub mod wasm;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod watch;
+pub mod web;
+pub mod window;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use check::{check_guilds, check_stream, CheckOptions, CheckStream, Timing};
+#[cfg(not(target_arch = "wasm32"))]
+pub use checker::Checker;
+#[cfg(not(target_arch = "wasm32"))]
+pub use client::{
+    build_client, build_keyless_client, parse_header, ApiKey, ApiKeyError, HeaderError,
+    TLS_BACKEND, USER_AGENT,
+};
+pub use error::{CheckError, ErrorKind};
+#[cfg(not(target_arch = "wasm32"))]
+pub use headers::{last_request, LastRequest};
+#[cfg(not(target_arch = "wasm32"))]
+pub use report::{ApiKeyStatus, Latency, Performance, RunReport};
+pub use result::{
+    classify, BodyKind, CheckResult, FailedCheck, Kind, RequestStats, Response, Status,
+    Unparseable, Verification,
+};
+pub use schema::{Schema, SchemaDrift};
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use tokio::sync::Semaphore;
+#[cfg(not(target_arch = "wasm32"))]
+pub use tokio_util::sync::CancellationToken;
@@ -0,0 +1,57 @@
+subject-guild = { $name } (ID: { $id })
+subject-user = Nutzer { $name } (ID: { $id })
+subject-channel = Kanal { $name } (ID: { $id })
+
+guild-compromised = ist betroffen: seine Nachrichten wurden archiviert!
+user-compromised = ist im Datensatz
+channel-compromised = ist betroffen: seine Nachrichten wurden archiviert!
+guild-clean = ist nicht betroffen
+not-in-dataset = ist nicht im Datensatz
+unchecked = konnte nicht geprüft werden
+unchecked-answer = { $source } antwortete { $answer }
+
+note-outside-window = (außerhalb des Zeitraums)
+note-no-timestamps = (keine Zeitangaben im Datensatz)
+note-fallback = (von der Webseite abgelesen, unsicher)
+note-confirmed = (bei erneuter Anfrage bestätigt)
+note-flaky = (unbeständig, bei erneuter Anfrage nicht kompromittiert)
+note-unverified = (nicht überprüft, keine zweite Antwort)
+note-severity = (Schweregrad: { $level }, { $score }/100)
+severity-low = niedrig
+severity-medium = mittel
+severity-high = hoch
+severity-critical = kritisch
+note-unlisted = (nicht in der Liste, nicht einzeln geprüft)
+
+detail-name = Name im Datensatz: { $name }
+detail-messages = archivierte Nachrichten: { $count }
+detail-members = Mitglieder: { $count }
+detail-first-seen = zuerst gesehen: { $date }
+detail-last-seen = zuletzt gesehen: { $date }
+detail-archived-members = archivierte Mitglieder: { $count }
+detail-archived-members-top = archivierte Mitglieder: { $count }, die meisten Nachrichten von { $top }
+detail-sample = Beispiel: { $author } in { $channel }: { $content }
+detail-sample-no-channel = Beispiel: { $author }: { $content }
+detail-someone = jemand
+detail-more-samples = und { $count } weitere Beispiele
+
+scraper-bots = { $name } (ID: { $id }) hat bekannte Scraper-Bots unter seinen Mitgliedern: { $bots }
+members-not-scanned = Die Mitgliederlisten von { $count } Servern wurden nicht durchsucht, der Bot ist nicht auf ihnen oder sieht ihre Mitglieder nicht
+
+simulated-run = SIMULIERTER LAUF: diese Ergebnisse hat --simulate erfunden, keines davon ist echt
+run-stopped = Lauf vorzeitig beendet, nicht jeder Server wurde geprüft
+nothing-matched = Keine Server gefunden, vielleicht bist du nicht im Datensatz
+group-summary = { $group }: { $total } geprüft, { $compromised } betroffen
+overall-summary = Insgesamt: { $total } geprüft, { $compromised } betroffen
+shared-heading = Betroffene Server, danach, wie viele Labels sie teilen:
+shared-guild = { $name } (ID: { $id }): { $count } von { $total } Labels ({ $labels })
+prefilter-unlisted = { $count } Server nicht in der Liste (nicht einzeln geprüft), die Liste von { $source } ist { $age } alt
+failed-heading = { $count } Server { $count ->
+    [one] konnte
+   *[other] konnten
+} nicht geprüft werden:
+failed-on = bei { $source }: { $message }
+
+change-compromised = { $name } (ID: { $id }) ist jetzt betroffen!
+change-removed = { $name } (ID: { $id }) ist nicht mehr im Datensatz
+change-messages-grew = Die archivierten Nachrichten von { $name } (ID: { $id }) sind von { $from } auf { $to } gestiegen
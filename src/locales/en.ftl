@@ -0,0 +1,60 @@
+# The plain output. Every message here is needed; the other languages fall
+# back to these for any they leave out.
+
+subject-guild = { $name } (ID: { $id })
+subject-user = User { $name } (ID: { $id })
+subject-channel = Channel { $name } (ID: { $id })
+
+guild-compromised = is compromised!
+user-compromised = appears in the dataset
+channel-compromised = had its messages scraped!
+guild-clean = is clean
+not-in-dataset = isn't in the dataset
+unchecked = couldn't be checked
+unchecked-answer = { $source } answered { $answer }
+
+note-outside-window = (outside window)
+note-no-timestamps = (no dataset timestamps)
+note-fallback = (read off the web page, low confidence)
+note-confirmed = (confirmed when asked again)
+note-flaky = (flaky, not compromised when asked again)
+note-unverified = (not verified, no second answer)
+note-severity = (severity: { $level }, { $score }/100)
+severity-low = low
+severity-medium = medium
+severity-high = high
+severity-critical = critical
+note-unlisted = (not in the listing, not individually verified)
+
+detail-name = name in the dataset: { $name }
+detail-messages = messages archived: { $count }
+detail-members = members: { $count }
+detail-first-seen = first seen: { $date }
+detail-last-seen = last seen: { $date }
+detail-archived-members = members archived: { $count }
+detail-archived-members-top = members archived: { $count }, most messages from { $top }
+detail-sample = sample: { $author } in { $channel }: { $content }
+detail-sample-no-channel = sample: { $author }: { $content }
+detail-someone = someone
+detail-more-samples = and { $count } more samples
+
+scraper-bots = { $name } (ID: { $id }) has known scraper bots among its members: { $bots }
+members-not-scanned = { $count } servers' member lists weren't scanned, the bot isn't in them or can't see their members
+
+simulated-run = SIMULATED RUN: these results were made up by --simulate, none of them are real
+run-stopped = Run stopped early, not every server was checked
+nothing-matched = No servers matched, you may not be in the dataset
+group-summary = { $group }: { $total } checked, { $compromised } compromised
+overall-summary = Overall: { $total } checked, { $compromised } compromised
+shared-heading = Compromised servers, by how many labels share them:
+shared-guild = { $name } (ID: { $id }): { $count } of { $total } labels ({ $labels })
+prefilter-unlisted = { $count } servers not in listing (not individually verified), the listing from { $source } is { $age } old
+failed-heading = { $count } { $count ->
+    [one] server
+   *[other] servers
+} couldn't be checked:
+failed-on = on { $source }: { $message }
+
+change-compromised = { $name } (ID: { $id }) is now compromised!
+change-removed = { $name } (ID: { $id }) is no longer in the dataset
+change-messages-grew = { $name } (ID: { $id }) archived messages grew from { $from } to { $to }
@@ -0,0 +1,57 @@
+subject-guild = { $name } (ID: { $id })
+subject-user = Usuario { $name } (ID: { $id })
+subject-channel = Canal { $name } (ID: { $id })
+
+guild-compromised = está afectado: ¡sus mensajes fueron archivados!
+user-compromised = aparece en el conjunto de datos
+channel-compromised = está afectado: ¡sus mensajes fueron archivados!
+guild-clean = no está afectado
+not-in-dataset = no está en el conjunto de datos
+unchecked = no se pudo comprobar
+unchecked-answer = { $source } respondió { $answer }
+
+note-outside-window = (fuera del periodo)
+note-no-timestamps = (sin fechas en el conjunto de datos)
+note-fallback = (leído de la página web, poco fiable)
+note-confirmed = (confirmado al volver a preguntar)
+note-flaky = (inestable, no comprometido al volver a preguntar)
+note-unverified = (sin verificar, no hubo segunda respuesta)
+note-severity = (gravedad: { $level }, { $score }/100)
+severity-low = baja
+severity-medium = media
+severity-high = alta
+severity-critical = crítica
+note-unlisted = (no está en la lista, no comprobado individualmente)
+
+detail-name = nombre en el conjunto de datos: { $name }
+detail-messages = mensajes archivados: { $count }
+detail-members = miembros: { $count }
+detail-first-seen = visto por primera vez: { $date }
+detail-last-seen = visto por última vez: { $date }
+detail-archived-members = miembros archivados: { $count }
+detail-archived-members-top = miembros archivados: { $count }, la mayoría de mensajes de { $top }
+detail-sample = muestra: { $author } en { $channel }: { $content }
+detail-sample-no-channel = muestra: { $author }: { $content }
+detail-someone = alguien
+detail-more-samples = y { $count } muestras más
+
+scraper-bots = { $name } (ID: { $id }) tiene bots recolectores conocidos entre sus miembros: { $bots }
+members-not-scanned = No se revisaron las listas de miembros de { $count } servidores, el bot no está en ellos o no puede ver a sus miembros
+
+simulated-run = EJECUCIÓN SIMULADA: --simulate inventó estos resultados, ninguno es real
+run-stopped = La ejecución se detuvo antes de tiempo, no se comprobaron todos los servidores
+nothing-matched = Ningún servidor coincidió, puede que no estés en el conjunto de datos
+group-summary = { $group }: { $total } comprobados, { $compromised } afectados
+overall-summary = En total: { $total } comprobados, { $compromised } afectados
+shared-heading = Servidores afectados, según cuántas etiquetas los comparten:
+shared-guild = { $name } (ID: { $id }): { $count } de { $total } etiquetas ({ $labels })
+prefilter-unlisted = { $count } servidores no están en la lista (no comprobados individualmente), la lista de { $source } tiene { $age }
+failed-heading = { $count ->
+    [one] No se pudo comprobar { $count } servidor:
+   *[other] No se pudieron comprobar { $count } servidores:
+}
+failed-on = en { $source }: { $message }
+
+change-compromised = { $name } (ID: { $id }) ahora está afectado
+change-removed = { $name } (ID: { $id }) ya no está en el conjunto de datos
+change-messages-grew = Los mensajes archivados de { $name } (ID: { $id }) pasaron de { $from } a { $to }
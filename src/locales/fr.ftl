@@ -0,0 +1,57 @@
+subject-guild = { $name } (ID : { $id })
+subject-user = Utilisateur { $name } (ID : { $id })
+subject-channel = Salon { $name } (ID : { $id })
+
+guild-compromised = est touché : ses messages ont été archivés !
+user-compromised = figure dans le jeu de données
+channel-compromised = est touché : ses messages ont été archivés !
+guild-clean = n'est pas touché
+not-in-dataset = ne figure pas dans le jeu de données
+unchecked = n'a pas pu être vérifié
+unchecked-answer = { $source } a répondu { $answer }
+
+note-outside-window = (hors de la période)
+note-no-timestamps = (pas de dates dans le jeu de données)
+note-fallback = (lu sur la page web, peu fiable)
+note-confirmed = (confirmé en redemandant)
+note-flaky = (instable, pas compromis en redemandant)
+note-unverified = (non vérifié, pas de seconde réponse)
+note-severity = (gravité : { $level }, { $score }/100)
+severity-low = faible
+severity-medium = moyenne
+severity-high = élevée
+severity-critical = critique
+note-unlisted = (absent de la liste, pas vérifié individuellement)
+
+detail-name = nom dans le jeu de données : { $name }
+detail-messages = messages archivés : { $count }
+detail-members = membres : { $count }
+detail-first-seen = vu pour la première fois : { $date }
+detail-last-seen = vu pour la dernière fois : { $date }
+detail-archived-members = membres archivés : { $count }
+detail-archived-members-top = membres archivés : { $count }, le plus de messages de { $top }
+detail-sample = extrait : { $author } dans { $channel } : { $content }
+detail-sample-no-channel = extrait : { $author } : { $content }
+detail-someone = quelqu'un
+detail-more-samples = et { $count } extraits de plus
+
+scraper-bots = { $name } (ID : { $id }) compte des bots aspirateurs connus parmi ses membres : { $bots }
+members-not-scanned = Les listes de membres de { $count } serveurs n'ont pas été parcourues, le bot n'y est pas ou ne voit pas leurs membres
+
+simulated-run = EXÉCUTION SIMULÉE : ces résultats ont été inventés par --simulate, aucun n'est réel
+run-stopped = Exécution arrêtée avant la fin, tous les serveurs n'ont pas été vérifiés
+nothing-matched = Aucun serveur trouvé, vous n'êtes peut-être pas dans le jeu de données
+group-summary = { $group } : { $total } vérifiés, { $compromised } touchés
+overall-summary = Au total : { $total } vérifiés, { $compromised } touchés
+shared-heading = Serveurs touchés, selon le nombre de labels qui les partagent :
+shared-guild = { $name } (ID : { $id }) : { $count } labels sur { $total } ({ $labels })
+prefilter-unlisted = { $count } serveurs absents de la liste (pas vérifiés individuellement), la liste de { $source } date de { $age }
+failed-heading = { $count ->
+    [one] { $count } serveur n'a pas pu être vérifié :
+   *[other] { $count } serveurs n'ont pas pu être vérifiés :
+}
+failed-on = sur { $source } : { $message }
+
+change-compromised = { $name } (ID : { $id }) est maintenant touché !
+change-removed = { $name } (ID : { $id }) ne figure plus dans le jeu de données
+change-messages-grew = Les messages archivés de { $name } (ID : { $id }) sont passés de { $from } à { $to }
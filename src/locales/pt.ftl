@@ -0,0 +1,57 @@
+subject-guild = { $name } (ID: { $id })
+subject-user = Usuário { $name } (ID: { $id })
+subject-channel = Canal { $name } (ID: { $id })
+
+guild-compromised = foi afetado: suas mensagens foram arquivadas!
+user-compromised = aparece no conjunto de dados
+channel-compromised = foi afetado: suas mensagens foram arquivadas!
+guild-clean = não foi afetado
+not-in-dataset = não está no conjunto de dados
+unchecked = não pôde ser verificado
+unchecked-answer = { $source } respondeu { $answer }
+
+note-outside-window = (fora do período)
+note-no-timestamps = (sem datas no conjunto de dados)
+note-fallback = (lido da página web, pouco confiável)
+note-confirmed = (confirmado ao perguntar de novo)
+note-flaky = (instável, não comprometido ao perguntar de novo)
+note-unverified = (não verificado, sem segunda resposta)
+note-severity = (gravidade: { $level }, { $score }/100)
+severity-low = baixa
+severity-medium = média
+severity-high = alta
+severity-critical = crítica
+note-unlisted = (fora da lista, não verificado individualmente)
+
+detail-name = nome no conjunto de dados: { $name }
+detail-messages = mensagens arquivadas: { $count }
+detail-members = membros: { $count }
+detail-first-seen = visto pela primeira vez: { $date }
+detail-last-seen = visto pela última vez: { $date }
+detail-archived-members = membros arquivados: { $count }
+detail-archived-members-top = membros arquivados: { $count }, mais mensagens de { $top }
+detail-sample = amostra: { $author } em { $channel }: { $content }
+detail-sample-no-channel = amostra: { $author }: { $content }
+detail-someone = alguém
+detail-more-samples = e mais { $count } amostras
+
+scraper-bots = { $name } (ID: { $id }) tem bots coletores conhecidos entre seus membros: { $bots }
+members-not-scanned = As listas de membros de { $count } servidores não foram verificadas, o bot não está neles ou não vê seus membros
+
+simulated-run = EXECUÇÃO SIMULADA: estes resultados foram inventados por --simulate, nenhum é real
+run-stopped = A execução parou antes do fim, nem todos os servidores foram verificados
+nothing-matched = Nenhum servidor encontrado, talvez você não esteja no conjunto de dados
+group-summary = { $group }: { $total } verificados, { $compromised } afetados
+overall-summary = No total: { $total } verificados, { $compromised } afetados
+shared-heading = Servidores afetados, por quantos rótulos os compartilham:
+shared-guild = { $name } (ID: { $id }): { $count } de { $total } rótulos ({ $labels })
+prefilter-unlisted = { $count } servidores fora da lista (não verificados individualmente), a lista de { $source } tem { $age }
+failed-heading = { $count ->
+    [one] { $count } servidor não pôde ser verificado:
+   *[other] { $count } servidores não puderam ser verificados:
+}
+failed-on = em { $source }: { $message }
+
+change-compromised = { $name } (ID: { $id }) agora foi afetado!
+change-removed = { $name } (ID: { $id }) não está mais no conjunto de dados
+change-messages-grew = As mensagens arquivadas de { $name } (ID: { $id }) passaram de { $from } para { $to }
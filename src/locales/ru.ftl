@@ -0,0 +1,69 @@
+subject-guild = { $name } (ID: { $id })
+subject-user = Пользователь { $name } (ID: { $id })
+subject-channel = Канал { $name } (ID: { $id })
+
+guild-compromised = затронут: его сообщения были заархивированы!
+user-compromised = есть в базе данных
+channel-compromised = затронут: его сообщения были заархивированы!
+guild-clean = не затронут
+not-in-dataset = нет в базе данных
+unchecked = не удалось проверить
+unchecked-answer = { $source } ответил { $answer }
+
+note-outside-window = (вне периода)
+note-no-timestamps = (в базе данных нет дат)
+note-fallback = (прочитано с веб-страницы, ненадёжно)
+note-confirmed = (подтверждено повторным запросом)
+note-flaky = (нестабильно, при повторном запросе не скомпрометирован)
+note-unverified = (не проверено, второго ответа нет)
+note-severity = (серьёзность: { $level }, { $score }/100)
+severity-low = низкая
+severity-medium = средняя
+severity-high = высокая
+severity-critical = критическая
+note-unlisted = (нет в списке, не проверен отдельно)
+
+detail-name = название в базе данных: { $name }
+detail-messages = заархивировано сообщений: { $count }
+detail-members = участников: { $count }
+detail-first-seen = впервые замечен: { $date }
+detail-last-seen = в последний раз замечен: { $date }
+detail-archived-members = заархивировано участников: { $count }
+detail-archived-members-top = заархивировано участников: { $count }, больше всего сообщений от { $top }
+detail-sample = пример: { $author } в { $channel }: { $content }
+detail-sample-no-channel = пример: { $author }: { $content }
+detail-someone = кто-то
+detail-more-samples = и ещё { $count } { $count ->
+    [one] пример
+    [few] примера
+   *[many] примеров
+}
+
+scraper-bots = Среди участников { $name } (ID: { $id }) есть известные боты-сборщики: { $bots }
+members-not-scanned = Списки участников { $count } { $count ->
+    [one] сервера
+   *[other] серверов
+} не проверены: бота там нет или он не видит участников
+
+simulated-run = СИМУЛЯЦИЯ: эти результаты придуманы --simulate, ни один из них не настоящий
+run-stopped = Проверка остановлена досрочно, проверены не все серверы
+nothing-matched = Ни один сервер не найден, возможно, вас нет в базе данных
+group-summary = { $group }: проверено { $total }, затронуто { $compromised }
+overall-summary = Всего: проверено { $total }, затронуто { $compromised }
+shared-heading = Затронутые серверы по числу меток, которые их разделяют:
+shared-guild = { $name } (ID: { $id }): { $count } из { $total } меток ({ $labels })
+prefilter-unlisted = { $count } { $count ->
+    [one] сервер не в списке
+    [few] сервера не в списке
+   *[many] серверов не в списке
+} (не проверены отдельно), списку от { $source } уже { $age }
+failed-heading = Не удалось проверить { $count } { $count ->
+    [one] сервер
+    [few] сервера
+   *[many] серверов
+}:
+failed-on = на { $source }: { $message }
+
+change-compromised = { $name } (ID: { $id }) теперь затронут!
+change-removed = { $name } (ID: { $id }) больше нет в базе данных
+change-messages-grew = Число заархивированных сообщений { $name } (ID: { $id }) выросло с { $from } до { $to }
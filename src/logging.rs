@@ -0,0 +1,179 @@
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+
+use chrono::Utc;
+use color_eyre::eyre::{self, Context};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::field::RecordFields;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::format::{DefaultFields, Writer};
+use tracing_subscriber::fmt::{FormatFields, MakeWriter};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer, Registry};
+
+use crate::cli::{GlobalArgs, LogFormat, LogRotation};
+use crate::progress::StderrWriter;
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Span fields are formatted once per field formatter type and then shared
+/// between layers, so a plain layer next to a colored one needs a formatter
+/// of its own to keep escape codes out of the file
+struct PlainFields(DefaultFields);
+
+impl<'writer> FormatFields<'writer> for PlainFields {
+    fn format_fields<R: RecordFields>(&self, writer: Writer<'writer>, fields: R) -> fmt::Result {
+        self.0.format_fields(writer, fields)
+    }
+}
+
+fn fmt_layer<W>(format: LogFormat, writer: W, ansi: bool) -> BoxedLayer
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi);
+    match format {
+        LogFormat::Text if ansi => layer.boxed(),
+        LogFormat::Text => layer.fmt_fields(PlainFields(DefaultFields::new())).boxed(),
+        LogFormat::Json => layer
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(false)
+            .boxed(),
+    }
+}
+
+/// Flushes buffered logs and traces when dropped, so it has to live until
+/// exit
+pub struct LogGuard {
+    _file: Option<WorkerGuard>,
+    #[cfg(feature = "otel")]
+    tracer: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+impl Drop for LogGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        if let Some(tracer) = self.tracer.take() {
+            // failures were already reported by the exporter
+            let _ = tracer.shutdown();
+        }
+    }
+}
+
+/// What the terminal is used for
+pub enum Terminal {
+    Logs,
+    /// Progress events on stderr: logs only go to the file then
+    Progress,
+    /// `--tui`: logs go to its log pane while it's up
+    #[cfg(feature = "tui")]
+    Tui,
+}
+
+/// A log file at `path` with the date appended, a new one every `rotation`,
+/// keeping the `keep` newest
+fn rolling(
+    path: &Path,
+    rotation: LogRotation,
+    keep: Option<usize>,
+) -> eyre::Result<Box<dyn Write + Send>> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = path
+        .file_name()
+        .ok_or_else(|| eyre::eyre!("log file {} has no file name", path.display()))?;
+    let mut builder = RollingFileAppender::builder()
+        .rotation(match rotation {
+            LogRotation::Hourly => Rotation::HOURLY,
+            LogRotation::Daily => Rotation::DAILY,
+        })
+        .filename_prefix(name.to_string_lossy());
+    if let Some(keep) = keep {
+        builder = builder.max_log_files(keep.max(1));
+    }
+    let appender = builder
+        .build(dir)
+        .with_context(|| format!("couldn't open log file {}", path.display()))?;
+    Ok(Box::new(appender))
+}
+
+/// Sets up logging to the terminal and, with `--log-file`, to a file, and
+/// trace export with `--otlp-endpoint`.
+pub fn init(global: &GlobalArgs, terminal: Terminal) -> eyre::Result<LogGuard> {
+    let filter = || match global.log_filter() {
+        Some(filter) => EnvFilter::new(filter),
+        None => EnvFilter::builder()
+            .with_default_directive(LevelFilter::INFO.into())
+            .from_env_lossy(),
+    };
+    let ansi = global.color.enabled(&std::io::stderr());
+    let terminal = match terminal {
+        Terminal::Logs => fmt_layer(global.log_format, || StderrWriter, ansi).with_filter(filter()),
+        Terminal::Progress => {
+            fmt_layer(global.log_format, std::io::stderr, ansi).with_filter(EnvFilter::new("off"))
+        }
+        #[cfg(feature = "tui")]
+        Terminal::Tui => {
+            fmt_layer(global.log_format, || crate::tui::LogWriter, false).with_filter(filter())
+        }
+    };
+    let mut layers = vec![terminal.boxed()];
+
+    let mut file_guard = None;
+    if let Some(path) = &global.log_file {
+        let mut file = match global.log_rotate {
+            Some(rotation) => rolling(path, rotation, global.log_keep)?,
+            None => Box::new(
+                std::fs::OpenOptions::new()
+                    .append(true)
+                    .create(true)
+                    .open(path)
+                    .with_context(|| format!("couldn't open log file {}", path.display()))?,
+            ),
+        };
+        let args: Vec<String> = std::env::args().collect();
+        writeln!(
+            file,
+            "# run {} started: {}",
+            Utc::now().format("%Y%m%dT%H%M%SZ"),
+            args.join(" ")
+        )
+        .with_context(|| format!("couldn't write to log file {}", path.display()))?;
+
+        let (writer, guard) = tracing_appender::non_blocking(file);
+        // dependencies are only interesting when something goes wrong
+        let filter = EnvFilter::new(format!("warn,spy_pet_checker={}", global.log_file_level));
+        layers.push(
+            fmt_layer(global.log_format, writer, false)
+                .with_filter(filter)
+                .boxed(),
+        );
+        file_guard = Some(guard);
+    }
+
+    #[cfg(feature = "otel")]
+    let tracer = match crate::telemetry::endpoint(global.otlp_endpoint.as_deref()) {
+        Some(endpoint) => {
+            let (layer, provider) = crate::telemetry::layer(&endpoint)?;
+            layers.push(layer);
+            Some(provider)
+        }
+        None => None,
+    };
+
+    tracing_subscriber::registry().with(layers).init();
+    Ok(LogGuard {
+        _file: file_guard,
+        #[cfg(feature = "otel")]
+        tracer,
+    })
+}
@@ -0,0 +1,310 @@
+//! Sending reports by email, over SMTP.
+
+use std::fmt;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tracing::debug;
+
+use crate::secret::Secret;
+
+#[derive(Error, Debug)]
+pub enum MailError {
+    #[error("couldn't talk to the SMTP server at {addr}: {source}")]
+    Connect {
+        addr: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("couldn't set up TLS with the SMTP server: {0}")]
+    Tls(String),
+
+    #[error("the SMTP server answered {command} with {reply:?}")]
+    Refused { command: String, reply: String },
+
+    #[error("{0:?} isn't an email address")]
+    Address(String),
+}
+
+/// How the connection to the SMTP server is secured
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Security {
+    /// TLS from the start, usually on port 465
+    Tls,
+    /// `STARTTLS` after connecting, usually on port 587
+    #[default]
+    StartTls,
+    /// Neither, for a relay on the same machine
+    None,
+}
+
+impl Security {
+    pub fn default_port(self) -> u16 {
+        match self {
+            Security::Tls => 465,
+            Security::StartTls => 587,
+            Security::None => 25,
+        }
+    }
+}
+
+/// One message: a report, in plain text or HTML
+#[derive(Clone, Debug)]
+pub struct Mail {
+    pub from: String,
+    pub to: Vec<String>,
+    pub subject: String,
+    pub body: String,
+    pub html: bool,
+}
+
+/// `address`, if it's one that can go in a header and an SMTP command
+pub fn check_address(address: &str) -> Result<(), MailError> {
+    let fine = address.contains('@')
+        && !address
+            .chars()
+            .any(|c| c.is_control() || c.is_whitespace() || matches!(c, '<' | '>' | ','));
+    match fine {
+        true => Ok(()),
+        false => Err(MailError::Address(address.to_owned())),
+    }
+}
+
+/// `text` as a header value, encoded if it isn't ASCII
+fn header_text(text: &str) -> String {
+    match text.is_ascii() {
+        true => text.to_owned(),
+        false => format!("=?UTF-8?B?{}?=", STANDARD.encode(text)),
+    }
+}
+
+impl Mail {
+    /// The message as it's sent after `DATA`. The body is base64, so no line
+    /// is too long or starts with a dot.
+    fn message(&self) -> String {
+        let mut message = String::new();
+        let mut header = |name: &str, value: &str| {
+            message.push_str(&format!("{name}: {value}\r\n"));
+        };
+        header("From", &self.from);
+        header("To", &self.to.join(", "));
+        header("Subject", &header_text(&self.subject));
+        header("Date", &Utc::now().to_rfc2822());
+        let id = format!("<{:016x}@spy-pet-checker>", fastrand::u64(..));
+        header("Message-ID", &id);
+        header("MIME-Version", "1.0");
+        let kind = match self.html {
+            true => "html",
+            false => "plain",
+        };
+        header("Content-Type", &format!("text/{kind}; charset=utf-8"));
+        header("Content-Transfer-Encoding", "base64");
+        message.push_str("\r\n");
+        let body = STANDARD.encode(&self.body);
+        for line in body.as_bytes().chunks(76) {
+            message.push_str(std::str::from_utf8(line).expect("base64 is ASCII"));
+            message.push_str("\r\n");
+        }
+        message
+    }
+}
+
+trait Io: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Io for T {}
+
+type Stream = BufReader<Box<dyn Io>>;
+
+/// An SMTP server to send through
+pub struct Smtp {
+    host: String,
+    port: u16,
+    security: Security,
+    login: Option<(String, Secret<String>)>,
+}
+
+impl fmt::Display for Smtp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.host, self.port)
+    }
+}
+
+impl Smtp {
+    /// The server at `host`, on `port` or else the usual one for `security`
+    pub fn new(host: impl Into<String>, port: Option<u16>, security: Security) -> Self {
+        Self {
+            host: host.into(),
+            port: port.unwrap_or(security.default_port()),
+            security,
+            login: None,
+        }
+    }
+
+    /// Logs in with `AUTH PLAIN`
+    pub fn with_login(mut self, username: String, password: Secret<String>) -> Self {
+        self.login = Some((username, password));
+        self
+    }
+
+    pub async fn send(&self, mail: &Mail) -> Result<(), MailError> {
+        let connect = |source| MailError::Connect {
+            addr: self.to_string(),
+            source,
+        };
+        let tcp = TcpStream::connect((self.host.as_str(), self.port))
+            .await
+            .map_err(connect)?;
+        let mut stream: Stream = match self.security {
+            Security::Tls => BufReader::new(tls(Box::new(tcp), &self.host).await?),
+            Security::StartTls | Security::None => BufReader::new(Box::new(tcp)),
+        };
+        self.expect(&mut stream, "the greeting", 220).await?;
+
+        let hello = "EHLO localhost";
+        let mut extensions = self.command(&mut stream, hello, 250).await?;
+        if self.security == Security::StartTls {
+            let starttls =
+                |line: &String| line.get(4..).is_some_and(|ext| ext.starts_with("STARTTLS"));
+            if !extensions.iter().any(starttls) {
+                return Err(MailError::Tls(
+                    "the server doesn't offer STARTTLS".to_owned(),
+                ));
+            }
+            self.command(&mut stream, "STARTTLS", 220).await?;
+            // nothing else is sent before the handshake, so the buffer is empty
+            let tcp = stream.into_inner();
+            stream = BufReader::new(tls(tcp, &self.host).await?);
+            extensions = self.command(&mut stream, hello, 250).await?;
+        }
+        debug!(?extensions, "SMTP server extensions");
+
+        if let Some((username, password)) = &self.login {
+            let credentials = STANDARD.encode(format!("\0{username}\0{}", password.expose()));
+            self.send_line(&mut stream, &format!("AUTH PLAIN {credentials}"))
+                .await?;
+            self.expect(&mut stream, "AUTH PLAIN", 235).await?;
+        }
+
+        self.command(&mut stream, &format!("MAIL FROM:<{}>", mail.from), 250)
+            .await?;
+        for to in &mail.to {
+            self.command(&mut stream, &format!("RCPT TO:<{to}>"), 250)
+                .await?;
+        }
+        self.command(&mut stream, "DATA", 354).await?;
+        self.send_line(&mut stream, &format!("{}.", mail.message()))
+            .await?;
+        self.expect(&mut stream, "the message", 250).await?;
+        let _ = self.command(&mut stream, "QUIT", 221).await;
+        Ok(())
+    }
+
+    async fn send_line(&self, stream: &mut Stream, line: &str) -> Result<(), MailError> {
+        let connect = |source| MailError::Connect {
+            addr: self.to_string(),
+            source,
+        };
+        let inner = stream.get_mut();
+        inner
+            .write_all(format!("{line}\r\n").as_bytes())
+            .await
+            .map_err(connect)?;
+        inner.flush().await.map_err(connect)
+    }
+
+    /// Sends `line` and reads the reply, which has to have `code`
+    async fn command(
+        &self,
+        stream: &mut Stream,
+        line: &str,
+        code: u16,
+    ) -> Result<Vec<String>, MailError> {
+        self.send_line(stream, line).await?;
+        // `MAIL FROM`, not the address
+        let command = line.split_once(':').map_or(line, |(verb, _)| verb);
+        self.expect(stream, command, code).await
+    }
+
+    /// Reads a reply, up to the line that ends it, and checks its code
+    async fn expect(
+        &self,
+        stream: &mut Stream,
+        command: &str,
+        code: u16,
+    ) -> Result<Vec<String>, MailError> {
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            let read = stream
+                .read_line(&mut line)
+                .await
+                .map_err(|source| MailError::Connect {
+                    addr: self.to_string(),
+                    source,
+                })?;
+            if read == 0 {
+                return Err(MailError::Refused {
+                    command: command.to_owned(),
+                    reply: "the connection closed".to_owned(),
+                });
+            }
+            let line = line.trim_end().to_owned();
+            // `250-` goes on, `250 ` is the last line
+            let last = line.as_bytes().get(3) != Some(&b'-');
+            lines.push(line);
+            if last {
+                break;
+            }
+        }
+        let got = lines[0].get(..3).and_then(|code| code.parse::<u16>().ok());
+        if got != Some(code) {
+            return Err(MailError::Refused {
+                command: command.to_owned(),
+                reply: lines.join(" "),
+            });
+        }
+        Ok(lines)
+    }
+}
+
+#[cfg(feature = "rustls")]
+async fn tls(stream: Box<dyn Io>, host: &str) -> Result<Box<dyn Io>, MailError> {
+    use std::sync::Arc;
+
+    use tokio_rustls::rustls::pki_types::ServerName;
+    use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+    use tokio_rustls::TlsConnector;
+
+    let mut roots = RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    let config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let name =
+        ServerName::try_from(host.to_owned()).map_err(|err| MailError::Tls(err.to_string()))?;
+    let stream = TlsConnector::from(Arc::new(config))
+        .connect(name, stream)
+        .await
+        .map_err(|err| MailError::Tls(err.to_string()))?;
+    Ok(Box::new(stream))
+}
+
+#[cfg(all(feature = "native-tls", not(feature = "rustls")))]
+async fn tls(stream: Box<dyn Io>, host: &str) -> Result<Box<dyn Io>, MailError> {
+    use tokio_native_tls::{native_tls, TlsConnector};
+
+    let connector =
+        native_tls::TlsConnector::new().map_err(|err| MailError::Tls(err.to_string()))?;
+    let stream = TlsConnector::from(connector)
+        .connect(host, stream)
+        .await
+        .map_err(|err| MailError::Tls(err.to_string()))?;
+    Ok(Box::new(stream))
+}
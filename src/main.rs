@@ -1,191 +1,159 @@
-use std::collections::BTreeMap;
-use std::io::Write;
-use std::path::PathBuf;
-use std::sync::Arc;
-use std::time::Instant;
-
-use clap::{Parser, ValueEnum};
-use color_eyre::eyre::{self, bail, Context};
-use serde::Serialize;
-use serde_json::Value;
-use tokio::sync::Semaphore;
-use tokio::task::JoinSet;
-use tracing::instrument::Instrument;
-use tracing::{debug, error, info, info_span};
-use tracing_subscriber::filter::LevelFilter;
-use tracing_subscriber::layer::SubscriberExt;
-use tracing_subscriber::util::SubscriberInitExt;
-use tracing_subscriber::EnvFilter;
-
-#[derive(ValueEnum, Clone)]
-enum Format {
-    #[clap(help = "Simple output in human readable format")]
-    Plain,
-
-    #[clap(help = "Complete output in json format")]
-    Json,
-}
-
-#[derive(Parser)]
-#[command(about = "Check if any of the servers you are in is present in spy.pet's database")]
-struct Args {
-    #[arg(
-        short,
-        long,
-        default_value_t = 1,
-        help = "Max number of concurrent requests",
-        long_help = "Maximum number of concurrent requests. settings this higher than 1 may get you ratelimited"
-    )]
-    concurrency: usize,
-
-    #[arg(
-        short,
-        long,
-        default_value = "index.json",
-        help = "Path to index.json containing server names and IDs"
-    )]
-    index_path: PathBuf,
-
-    #[arg(short, long, default_value = "plain", help = "output format")]
-    format: Format,
-
-    #[arg(short, long, help = "Output to file instead of stdout")]
-    output: Option<PathBuf>,
-}
-
-#[derive(Serialize)]
-struct Response {
-    guild_id: String,
-    guild_name: String,
-    api_response: Value,
-}
-
-#[tokio::main]
-async fn process(args: &Args) -> eyre::Result<(Vec<Response>, i32)> {
-    let sema = Arc::new(Semaphore::new(args.concurrency));
-
-    let string = tokio::fs::read_to_string(&args.index_path)
-        .await
-        .with_context(|| format!("couldn't read file {}", args.index_path.display()))?;
-
-    let guilds: BTreeMap<String, String> =
-        serde_json::from_str(&string).context("couldn't parse index file")?;
-
-    let mut join_set = JoinSet::new();
-
-    for (id, name) in guilds {
-        let span = info_span!("check", %id, %name);
-        let sema = Arc::clone(&sema);
-        join_set.spawn(
-            async move {
-                let ticket = sema
-                    .acquire()
-                    .await
-                    .context("couldn't acquire ticket from sepahore")?;
-
-                let url = format!("https://api.spy.pet/servers/{id}");
-                info!(%url, "requesting");
-                let response = reqwest::get(url)
-                    .await
-                    .context("couldn't contact spy.pet api")?;
-
-                if response.status().is_success() {
-                    let text = response
-                        .text()
-                        .await
-                        .context("couldn't parse spy.pet api response")?;
-                    drop(ticket);
-                    debug!(size=%text.bytes().len(), "got response");
-
-                    if text == "false" {
-                        info!("not found");
-                    } else {
-                        info!("found");
-                    }
-                    Ok(Response {
-                        guild_id: id,
-                        guild_name: name,
-                        api_response: serde_json::from_str(&text)
-                            .context("couldn't parse spy.pet api response")?,
-                    })
-                } else {
-                    drop(ticket);
-                    error!(status=%response.status(), "api response");
-                    bail!("spy.pet api returned error: {}", response.status(),)
-                }
-            }
-            .instrument(span),
-        );
-    }
-
-    let mut total = Vec::new();
-    let mut errors = 0;
-
-    while let Some(handle) = join_set.join_next().await {
-        let result = handle.context("failed to join task")?;
-
-        match result {
-            Ok(v) => {
-                total.push(v);
-            }
-            Err(_) => {
-                errors += 1;
-            }
-        };
+use std::process::ExitCode;
+
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Args, CommandFactory, FromArgMatches};
+use color_eyre::eyre;
+
+use cli::{CheckArgs, Cli, Command, ProgressFormat};
+use logging::Terminal;
+
+mod cli;
+mod commands;
+mod config;
+mod credentials;
+mod dce;
+mod desktop;
+mod logging;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod package;
+#[cfg(feature = "pick")]
+mod pick;
+mod progress;
+#[cfg(feature = "otel")]
+mod telemetry;
+#[cfg(feature = "tui")]
+mod tui;
+#[cfg(feature = "self-update")]
+mod update;
+mod zip;
+
+/// `check`'s flags are accepted at the top level so that invocations without a
+/// subcommand keep working, but they mean nothing next to a subcommand.
+/// clap's `args_conflicts_with_subcommands` would also reject global flags, so
+/// this is checked by hand.
+fn reject_toplevel_check_args(matches: &ArgMatches) -> Result<(), clap::Error> {
+    let command = Cli::command();
+    for arg in CheckArgs::augment_args(clap::Command::new("check")).get_arguments() {
+        let id = arg.get_id().as_str();
+        if matches.value_source(id) == Some(ValueSource::CommandLine) {
+            let name = arg.get_long().unwrap_or(id);
+            return Err(command.clone().error(
+                ErrorKind::ArgumentConflict,
+                format!("--{name} must come after the subcommand"),
+            ));
+        }
     }
-
-    Ok((total, errors))
+    Ok(())
 }
 
-fn main() -> eyre::Result<()> {
+fn main() -> eyre::Result<ExitCode> {
     color_eyre::install()?;
-    tracing_subscriber::registry()
-        .with(
-            EnvFilter::builder()
-                .with_default_directive(LevelFilter::INFO.into())
-                .from_env_lossy(),
-        )
-        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
-        .init();
-
-    let args: &'static Args = Box::leak(Box::new(Args::parse()));
-
-    let start = Instant::now();
-    let (total, errors) = process(args)?;
-    info!("processing took {:?}", start.elapsed());
 
-    let mut writer: Box<dyn Write> = if let Some(path) = args.output.as_ref() {
-        Box::new(
-            std::fs::OpenOptions::new()
-                .write(true)
-                .truncate(true)
-                .open(path)
-                .with_context(|| format!("couldn't open {}", path.display()))?,
-        )
-    } else {
-        Box::new(std::io::stdout())
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches)?;
+    let check = match &cli.command {
+        Some(Command::Check(args) | Command::CheckUsers(args)) => Some(&**args),
+        Some(Command::Bot(args)) => Some(&args.check),
+        None => Some(&cli.check),
+        _ => None,
     };
-    match args.format {
-        Format::Plain => {
-            if total.is_empty() {
-                writeln!(writer, "No servers matched, you may not be in the dataset")?
-            } else {
-                for guild in total {
-                    if guild.api_response != serde_json::Value::Bool(false) {
-                        writeln!(
-                            writer,
-                            "{} (ID: {}) is compromised!",
-                            guild.guild_name, guild.guild_id
-                        )?
-                    }
+    let terminal = match check {
+        #[cfg(feature = "tui")]
+        Some(args) if args.tui => Terminal::Tui,
+        Some(args) if args.progress(&cli.global) == ProgressFormat::Json => Terminal::Progress,
+        _ => Terminal::Logs,
+    };
+    // flushes the log file and traces when main returns
+    let _log_guard = logging::init(&cli.global, terminal)?;
+
+    let result = match cli.command {
+        Some(command) => {
+            if let Err(err) = reject_toplevel_check_args(&matches) {
+                err.exit();
+            }
+            match command {
+                Command::Check(args) => {
+                    let matches = matches
+                        .subcommand_matches("check")
+                        .expect("subcommand matched");
+                    commands::check::run(cli.global, *args, matches)
                 }
+                Command::CheckUsers(args) => {
+                    let matches = matches
+                        .subcommand_matches("check-users")
+                        .expect("subcommand matched");
+                    commands::check::run_users(cli.global, *args, matches)
+                }
+                Command::FetchGuilds(args) => commands::fetch_guilds::run(cli.global, args),
+                Command::Bot(args) => {
+                    let matches = matches
+                        .subcommand_matches("bot")
+                        .expect("subcommand matched");
+                    commands::bot::run(cli.global, *args, matches)
+                }
+                Command::History(args) => commands::history::run(cli.global, args),
+                Command::Cache(args) => commands::cache::run(cli.global, args),
+                Command::Merge(args) => commands::merge::run(args),
+                Command::Stats(args) => commands::stats::run(args),
+                Command::Validate(args) => commands::validate::run(args),
+                Command::Diff(args) => commands::diff::run(args),
+                Command::Healthcheck(args) => {
+                    let matches = matches
+                        .subcommand_matches("healthcheck")
+                        .expect("subcommand matched");
+                    commands::healthcheck::run(cli.global, *args, matches)
+                }
+                Command::ScanBots(args) => {
+                    let matches = matches
+                        .subcommand_matches("scan-bots")
+                        .expect("subcommand matched");
+                    commands::scan_bots::run(cli.global, *args, matches)
+                }
+                Command::Dump(args) => {
+                    let matches = matches
+                        .subcommand_matches("dump")
+                        .expect("subcommand matched");
+                    commands::dump::run(cli.global, *args, matches)
+                }
+                Command::Report(args) => commands::report::run(cli.global, args),
+                #[cfg(feature = "sign")]
+                Command::Verify(args) => commands::verify::run(args),
+                #[cfg(feature = "serve")]
+                Command::Serve(args) => {
+                    let matches = matches
+                        .subcommand_matches("serve")
+                        .expect("subcommand matched");
+                    commands::serve::run(cli.global, *args, matches)
+                }
+                #[cfg(feature = "self-update")]
+                Command::SelfUpdate(args) => {
+                    let matches = matches
+                        .subcommand_matches("self-update")
+                        .expect("subcommand matched");
+                    commands::self_update::run(cli.global, *args, matches)
+                }
+                Command::Auth(args) => commands::auth::run(cli.global, args),
+                #[cfg(feature = "keyring")]
+                Command::Login(args) => commands::token::login(args),
+                #[cfg(feature = "keyring")]
+                Command::Logout(args) => commands::token::logout(args),
+                Command::Token(args) => commands::token::run(args),
+                Command::Completions(args) => commands::completions::run(args),
+                Command::Mangen => commands::mangen::run(),
             }
-            Ok::<(), std::io::Error>(())
         }
-        Format::Json => writeln!(writer, "{}", serde_json::to_string_pretty(&total)?),
+        None => commands::check::run(cli.global, cli.check, &matches),
+    };
+    match result {
+        Ok(()) => Ok(ExitCode::SUCCESS),
+        Err(err) => match err.downcast::<commands::Exit>() {
+            Ok(exit) => {
+                eprintln!("{}", exit.message);
+                Ok(ExitCode::from(exit.code))
+            }
+            Err(err) => Err(err),
+        },
     }
-    .context("couldn't write to output")?;
-    eprintln!("Errors: {errors}");
-
-    Ok(())
 }
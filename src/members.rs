@@ -0,0 +1,263 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+use crate::secret::Secret;
+use crate::CheckError;
+
+/// Where member lists are read from
+pub use crate::discord::DISCORD_API;
+
+/// The most members Discord returns in one page
+const PAGE_SIZE: usize = 1000;
+
+/// Scraper bot accounts known to this version, in the `--bot-list` format
+const BUNDLED: &str = include_str!("known_bots.txt");
+
+/// The latest version of the bundled list, for `scan-bots --update-list`
+pub const KNOWN_BOTS_URL: &str =
+    "https://raw.githubusercontent.com/slonkazoid/spy-pet-checker/main/src/known_bots.txt";
+
+/// A line of a bot list that isn't `<user id> [label]`
+#[derive(Error, Debug)]
+#[error("line {line}: {id:?} isn't a user ID")]
+pub struct BotListError {
+    pub line: usize,
+    pub id: String,
+}
+
+#[derive(Error, Debug)]
+pub enum BotListFetchError {
+    #[error("couldn't fetch the bot list: {0}")]
+    Fetch(#[from] CheckError),
+
+    #[error("the bot list downloaded is invalid: {0}")]
+    Invalid(#[from] BotListError),
+
+    #[error("couldn't save the bot list: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Known scraper bot accounts, user ID → label.
+///
+/// The text format has one bot per line, its user ID optionally followed by a
+/// label. Blank lines and lines starting with `#` are skipped.
+#[derive(Clone, Debug, Default)]
+pub struct BotList(BTreeMap<String, String>);
+
+impl BotList {
+    /// The list that ships with this version
+    pub fn bundled() -> Self {
+        Self::parse(BUNDLED).expect("the bundled bot list is valid")
+    }
+
+    pub fn parse(text: &str) -> Result<Self, BotListError> {
+        let mut bots = BTreeMap::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (id, label) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            if id.is_empty() || !id.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(BotListError {
+                    line: i + 1,
+                    id: id.to_owned(),
+                });
+            }
+            let label = match label.trim() {
+                "" => id,
+                label => label,
+            };
+            bots.insert(id.to_owned(), label.to_owned());
+        }
+        Ok(Self(bots))
+    }
+
+    /// Downloads a list in the text format and saves it to `path`, replacing
+    /// the file atomically. Nothing is saved unless the whole list is valid.
+    pub async fn update(
+        client: &Client,
+        url: &str,
+        path: &Path,
+    ) -> Result<Self, BotListFetchError> {
+        let (status, text) = crate::backend::fetch(client, url).await?;
+        if !status.is_success() {
+            return Err(CheckError::HttpStatus(status).into());
+        }
+        let bots = Self::parse(&text)?;
+        let tmp = path.with_extension("txt.tmp");
+        std::fs::write(&tmp, &text)?;
+        std::fs::rename(tmp, path)?;
+        debug!(bots = bots.len(), "updated the bot list");
+        Ok(bots)
+    }
+
+    /// Adds the bots from `other`; its labels win for IDs in both
+    pub fn extend(&mut self, other: BotList) {
+        self.0.extend(other.0);
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn get(&self, id: &str) -> Option<&str> {
+        self.0.get(id).map(String::as_str)
+    }
+}
+
+/// A known scraper bot found in a guild's member list
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct KnownBot {
+    pub id: String,
+    pub label: String,
+}
+
+/// What a guild's member list says, from `--scan-members`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "result", rename_all = "lowercase")]
+pub enum MemberScan {
+    /// The whole member list was read
+    Scanned {
+        members: u64,
+        /// Known scraper bots among the members; empty if there are none
+        bots: Vec<KnownBot>,
+    },
+    /// The token can't read the member list, e.g. because the bot isn't in
+    /// the guild or doesn't have the members intent
+    Skipped { reason: String },
+}
+
+#[derive(Deserialize)]
+struct Member {
+    user: MemberUser,
+}
+
+#[derive(Deserialize)]
+struct MemberUser {
+    id: String,
+}
+
+/// Looks for known scraper bots in guilds' member lists, through Discord's
+/// API with a bot token
+pub struct MemberScanner {
+    token: Secret<String>,
+    bots: BotList,
+    api: String,
+}
+
+impl MemberScanner {
+    pub fn new(token: Secret<String>, bots: BotList) -> Self {
+        Self {
+            token,
+            bots,
+            api: DISCORD_API.to_owned(),
+        }
+    }
+
+    /// Talks to another API base URL instead of Discord's
+    pub fn with_api(mut self, api: impl Into<String>) -> Self {
+        self.api = api.into();
+        self
+    }
+
+    pub fn bots(&self) -> &BotList {
+        &self.bots
+    }
+
+    /// Scans `id`'s member list, logging what came of it. Errors are left to
+    /// the caller.
+    pub(crate) async fn scan_logged(
+        &self,
+        client: &Client,
+        id: &str,
+    ) -> Result<MemberScan, CheckError> {
+        let scan = self.scan(client, id).await?;
+        match &scan {
+            MemberScan::Scanned { members, bots } if bots.is_empty() => {
+                debug!(members, "no known scraper bots in the member list");
+            }
+            MemberScan::Scanned { members, bots } => {
+                let labels: Vec<&str> = bots.iter().map(|b| b.label.as_str()).collect();
+                warn!(
+                    members,
+                    "known scraper bots in the member list: {}",
+                    labels.join(", ")
+                );
+            }
+            MemberScan::Skipped { reason } => info!("member list not scanned: {reason}"),
+        }
+        Ok(scan)
+    }
+
+    pub async fn scan(&self, client: &Client, id: &str) -> Result<MemberScan, CheckError> {
+        let url = format!(
+            "{}/guilds/{}/members",
+            self.api,
+            utf8_percent_encode(id, NON_ALPHANUMERIC)
+        );
+        let mut members = 0;
+        let mut bots = Vec::new();
+        let mut after = String::from("0");
+        loop {
+            let page = match self.page(client, &url, &after).await? {
+                Ok(page) => page,
+                Err(reason) => return Ok(MemberScan::Skipped { reason }),
+            };
+            members += page.len() as u64;
+            for member in &page {
+                if let Some(label) = self.bots.get(&member.user.id) {
+                    bots.push(KnownBot {
+                        id: member.user.id.clone(),
+                        label: label.to_owned(),
+                    });
+                }
+            }
+            match page.last() {
+                Some(last) if page.len() >= PAGE_SIZE => after.clone_from(&last.user.id),
+                _ => break,
+            }
+        }
+        Ok(MemberScan::Scanned { members, bots })
+    }
+
+    /// One page of members after `after`, waiting out rate limits. The inner
+    /// error is why the guild can't be scanned.
+    async fn page(
+        &self,
+        client: &Client,
+        url: &str,
+        after: &str,
+    ) -> Result<Result<Vec<Member>, String>, CheckError> {
+        let limit = PAGE_SIZE.to_string();
+        let query = [("limit", limit.as_str()), ("after", after)];
+        let authorization = format!("Bot {}", self.token.expose());
+        let (status, text) = crate::discord::get(client, url, &query, &authorization).await?;
+        debug!(%status, after, "got member page");
+
+        match status {
+            StatusCode::FORBIDDEN => {
+                return Ok(Err(
+                    "the bot can't see the member list (missing access, or the server members intent is off)".to_owned(),
+                ))
+            }
+            StatusCode::NOT_FOUND => return Ok(Err("the bot isn't in this server".to_owned())),
+            status if !status.is_success() => return Err(CheckError::HttpStatus(status)),
+            _ => {}
+        }
+
+        let page = serde_json::from_str(&text).map_err(|_| CheckError::bad_body(&text))?;
+        Ok(Ok(page))
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::{FailedCheck, Response};
+
+/// Two inputs disagreeing on whether a guild is compromised
+#[derive(Debug)]
+pub struct Conflict {
+    pub guild_id: String,
+    pub source: String,
+    /// Labels of the inputs that said compromised
+    pub compromised_in: Vec<String>,
+    /// Labels of the inputs that said clean
+    pub clean_in: Vec<String>,
+}
+
+/// The JSON output, of which only the results are read back
+#[derive(Deserialize)]
+struct Document {
+    results: Vec<Response>,
+}
+
+/// The errors of the JSON output, read apart from the results so a file
+/// with errors this version can't read still has its results merged
+#[derive(Deserialize)]
+struct Errors {
+    #[serde(default)]
+    errors: Vec<FailedCheck>,
+}
+
+/// Parses a result file written with `--format json` (an object with the
+/// results, or the array of them older versions wrote) or as
+/// newline-delimited JSON (one result per line)
+pub fn parse_results(text: &str) -> serde_json::Result<Vec<Response>> {
+    let start = text.trim_start();
+    if start.starts_with('[') {
+        return serde_json::from_str(text);
+    }
+    // a file with one result on one line is an object too
+    if start.starts_with('{') {
+        if let Ok(document) = serde_json::from_str::<Document>(text) {
+            return Ok(document.results);
+        }
+    }
+
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(serde_json::from_str)
+        .collect()
+}
+
+/// The checks that failed in a result file written with `--format json`.
+/// The other formats don't keep them, so they have none.
+pub fn parse_failed(text: &str) -> Vec<FailedCheck> {
+    serde_json::from_str::<Errors>(text)
+        .map(|document| document.errors)
+        .unwrap_or_default()
+}
+
+/// The checks in `failed` that none of `results` answers, once each, e.g.
+/// from shards of one run or a run and its retry
+pub fn still_failed(
+    failed: impl IntoIterator<Item = FailedCheck>,
+    results: &[Response],
+) -> Vec<FailedCheck> {
+    let mut seen: BTreeMap<(String, String), FailedCheck> = BTreeMap::new();
+    for failed in failed {
+        let key = (failed.guild_id.clone(), failed.source.clone());
+        seen.entry(key).or_insert(failed);
+    }
+    for result in results {
+        seen.remove(&(result.guild_id.clone(), result.source.clone()));
+    }
+    seen.into_values().collect()
+}
+
+/// Combines results from several inputs, one record per guild and backend.
+///
+/// Records without labels are attributed to their input's label. When inputs
+/// disagree, the most recently checked record wins; without timestamps, the
+/// compromised one does. Either way the disagreement is returned as a
+/// [`Conflict`].
+pub fn merge(
+    inputs: impl IntoIterator<Item = (String, Vec<Response>)>,
+) -> (Vec<Response>, Vec<Conflict>) {
+    let mut merged: BTreeMap<(String, String), Response> = BTreeMap::new();
+    let mut conflicts: BTreeMap<(String, String), Conflict> = BTreeMap::new();
+
+    for (label, results) in inputs {
+        for mut result in results {
+            if result.labels.is_empty() {
+                result.labels.push(label.clone());
+            }
+            let key = (result.guild_id.clone(), result.source.clone());
+
+            let existing = match merged.entry(key.clone()) {
+                Entry::Vacant(entry) => {
+                    entry.insert(result);
+                    continue;
+                }
+                Entry::Occupied(entry) => entry.into_mut(),
+            };
+
+            if existing.is_compromised() != result.is_compromised() {
+                let conflict = conflicts.entry(key).or_insert_with(|| Conflict {
+                    guild_id: result.guild_id.clone(),
+                    source: result.source.clone(),
+                    compromised_in: Vec::new(),
+                    clean_in: Vec::new(),
+                });
+                for side in [&*existing, &result] {
+                    let list = if side.is_compromised() {
+                        &mut conflict.compromised_in
+                    } else {
+                        &mut conflict.clean_in
+                    };
+                    for label in &side.labels {
+                        if !list.contains(label) {
+                            list.push(label.clone());
+                        }
+                    }
+                }
+            }
+
+            let newer = match (existing.checked_at, result.checked_at) {
+                (Some(old), Some(new)) => new > old,
+                (None, Some(_)) => true,
+                (_, None) => !existing.is_compromised() && result.is_compromised(),
+            };
+
+            let mut labels = std::mem::take(&mut existing.labels);
+            for label in &result.labels {
+                if !labels.contains(label) {
+                    labels.push(label.clone());
+                }
+            }
+            if newer {
+                *existing = result;
+            }
+            existing.labels = labels;
+        }
+    }
+
+    let conflicts: Vec<Conflict> = conflicts.into_values().collect();
+    for conflict in &conflicts {
+        warn!(
+            guild_id = %conflict.guild_id,
+            source = %conflict.source,
+            "conflicting results: compromised in {}, clean in {}",
+            conflict.compromised_in.join(", "),
+            conflict.clean_in.join(", "),
+        );
+    }
+
+    (merged.into_values().collect(), conflicts)
+}
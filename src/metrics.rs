@@ -0,0 +1,257 @@
+use std::net::SocketAddr;
+use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::extract::State;
+use axum::http::header::CONTENT_TYPE;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{self, Context};
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+use prometheus_client::registry::Registry;
+use reqwest::Client;
+use spy_pet_checker::backend::{Backend, BackendFuture};
+use spy_pet_checker::{classify, last_request, CancellationToken, CheckError, Schema};
+use tokio::net::TcpListener;
+use tracing::{info, warn};
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct RequestLabels {
+    backend: String,
+    outcome: &'static str,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct BackendLabels {
+    backend: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct StatusLabels {
+    backend: String,
+    /// The HTTP status code, or `none` when no response came
+    code: String,
+}
+
+/// Everything exported on `--metrics-listen`
+pub struct Metrics {
+    registry: Registry,
+    requests: Family<RequestLabels, Counter>,
+    responses: Family<StatusLabels, Counter>,
+    retries: Family<BackendLabels, Counter>,
+    latency: Family<BackendLabels, Histogram>,
+    compromised: Gauge,
+    found: Counter,
+    last_cycle_timestamp: Gauge,
+    last_cycle_duration: Gauge<f64, AtomicU64>,
+}
+
+impl Metrics {
+    pub fn new() -> Arc<Self> {
+        let mut registry = Registry::with_prefix("spy_pet");
+        let requests = Family::<RequestLabels, Counter>::default();
+        registry.register(
+            "requests",
+            "Backend requests by outcome (clean, compromised, indeterminate, unparseable, error, ratelimited)",
+            requests.clone(),
+        );
+        let responses = Family::<StatusLabels, Counter>::default();
+        registry.register(
+            "responses",
+            "Backend requests by HTTP status code, \"none\" when no response came",
+            responses.clone(),
+        );
+        let retries = Family::<BackendLabels, Counter>::default();
+        registry.register(
+            "retries",
+            "Backend requests that tried again after an earlier one failed",
+            retries.clone(),
+        );
+        let latency = Family::<BackendLabels, Histogram>::new_with_constructor(|| {
+            Histogram::new(exponential_buckets(0.05, 2.0, 10))
+        });
+        registry.register(
+            "request_duration_seconds",
+            "Time taken by backend requests",
+            latency.clone(),
+        );
+        let compromised = Gauge::default();
+        registry.register(
+            "compromised_guilds",
+            "Guilds currently reported by at least one backend",
+            compromised.clone(),
+        );
+        let found = Counter::default();
+        registry.register(
+            "compromised_found",
+            "Guilds a watch cycle found compromised that the one before hadn't",
+            found.clone(),
+        );
+        let last_cycle_timestamp = Gauge::default();
+        registry.register(
+            "last_cycle_timestamp_seconds",
+            "When the last watch cycle finished, as a Unix timestamp",
+            last_cycle_timestamp.clone(),
+        );
+        let last_cycle_duration = Gauge::<f64, AtomicU64>::default();
+        registry.register(
+            "last_cycle_duration_seconds",
+            "How long the last watch cycle took",
+            last_cycle_duration.clone(),
+        );
+
+        Arc::new(Self {
+            registry,
+            requests,
+            responses,
+            retries,
+            latency,
+            compromised,
+            found,
+            last_cycle_timestamp,
+            last_cycle_duration,
+        })
+    }
+
+    /// Wraps `backends` so that every request they make is counted
+    pub fn instrument(self: &Arc<Self>, backends: Vec<Arc<dyn Backend>>) -> Vec<Arc<dyn Backend>> {
+        backends
+            .into_iter()
+            .map(|inner| {
+                Arc::new(Instrumented {
+                    inner,
+                    metrics: Arc::clone(self),
+                }) as Arc<dyn Backend>
+            })
+            .collect()
+    }
+
+    pub fn set_compromised(&self, count: usize) {
+        self.compromised.set(count as i64);
+    }
+
+    /// Counts the guilds a cycle found newly compromised
+    pub fn record_found(&self, count: usize) {
+        self.found.inc_by(count as u64);
+    }
+
+    pub fn finish_cycle(&self, finished_at: DateTime<Utc>, duration: Duration) {
+        self.last_cycle_timestamp.set(finished_at.timestamp());
+        self.last_cycle_duration.set(duration.as_secs_f64());
+    }
+
+    fn encode(&self) -> String {
+        let mut text = String::new();
+        prometheus_client::encoding::text::encode(&mut text, &self.registry)
+            .expect("writing to a String can't fail");
+        text
+    }
+}
+
+struct Instrumented {
+    inner: Arc<dyn Backend>,
+    metrics: Arc<Metrics>,
+}
+
+impl Backend for Instrumented {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn url(&self, id: &str) -> String {
+        self.inner.url(id)
+    }
+
+    fn schema(&self) -> Option<Schema> {
+        self.inner.schema()
+    }
+
+    fn check<'a>(&'a self, client: &'a Client, id: &'a str) -> BackendFuture<'a> {
+        Box::pin(async move {
+            let start = Instant::now();
+            let result = self.inner.check(client, id).await;
+
+            let backend = self.inner.name().to_owned();
+            let outcome = match &result {
+                Ok(value) => classify(value).as_str(),
+                Err(CheckError::RateLimited { .. }) => "ratelimited",
+                Err(CheckError::BadBody { .. }) => "unparseable",
+                Err(_) => "error",
+            };
+            self.metrics
+                .latency
+                .get_or_create(&BackendLabels {
+                    backend: backend.clone(),
+                })
+                .observe(start.elapsed().as_secs_f64());
+            let last = last_request().unwrap_or_default();
+            let code = last
+                .status
+                .map_or_else(|| "none".to_owned(), |status| status.as_u16().to_string());
+            self.metrics
+                .responses
+                .get_or_create(&StatusLabels {
+                    backend: backend.clone(),
+                    code,
+                })
+                .inc();
+            if last.number > 1 {
+                self.metrics
+                    .retries
+                    .get_or_create(&BackendLabels {
+                        backend: backend.clone(),
+                    })
+                    .inc();
+            }
+            self.metrics
+                .requests
+                .get_or_create(&RequestLabels { backend, outcome })
+                .inc();
+
+            result
+        })
+    }
+}
+
+async fn scrape(State(metrics): State<Arc<Metrics>>) -> impl IntoResponse {
+    (
+        [(
+            CONTENT_TYPE,
+            "application/openmetrics-text; version=1.0.0; charset=utf-8",
+        )],
+        metrics.encode(),
+    )
+}
+
+/// Binds `addr` right away so the endpoint is up before any checks run, then
+/// serves `/metrics` in the background until `shutdown` fires
+pub async fn serve(
+    addr: SocketAddr,
+    metrics: Arc<Metrics>,
+    shutdown: CancellationToken,
+) -> eyre::Result<()> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("couldn't listen on {addr}"))?;
+    info!("serving metrics on {addr}");
+
+    let app = Router::new()
+        .route("/metrics", get(scrape))
+        .with_state(metrics);
+    tokio::spawn(async move {
+        let result = axum::serve(listener, app)
+            .with_graceful_shutdown(shutdown.cancelled_owned())
+            .await;
+        if let Err(err) = result {
+            warn!(%err, "metrics server failed");
+        }
+    });
+    Ok(())
+}
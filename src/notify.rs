@@ -0,0 +1,159 @@
+use reqwest::header::{HeaderMap, CONTENT_TYPE, RETRY_AFTER};
+use reqwest::{Client, Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::retry::RetryPolicy;
+use crate::secret::Secret;
+use crate::{CheckError, Response};
+
+/// When a finished run is worth telling someone about
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum NotifyOn {
+    /// After every run, as a heartbeat
+    Always,
+    /// When any guild is compromised
+    Compromised,
+    /// When a guild is compromised that wasn't in the previous run or watch
+    /// cycle
+    NewCompromised,
+    /// When any check failed
+    Errors,
+    Never,
+}
+
+/// The counts of a run that notification conditions look at
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RunSummary {
+    pub compromised: usize,
+    /// Compromised guilds the previous run didn't report. With no previous
+    /// run to compare against, all of them.
+    pub new_compromised: usize,
+    pub errors: usize,
+}
+
+impl NotifyOn {
+    pub fn fires(self, summary: &RunSummary) -> bool {
+        match self {
+            NotifyOn::Always => true,
+            NotifyOn::Compromised => summary.compromised > 0,
+            NotifyOn::NewCompromised => summary.new_compromised > 0,
+            NotifyOn::Errors => summary.errors > 0,
+            NotifyOn::Never => false,
+        }
+    }
+}
+
+/// A run's notification condition and whether it fired, as recorded with it
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Notification {
+    pub on: NotifyOn,
+    pub fired: bool,
+}
+
+impl Notification {
+    pub fn evaluate(on: NotifyOn, summary: &RunSummary) -> Self {
+        Self {
+            on,
+            fired: on.fires(summary),
+        }
+    }
+}
+
+/// What a run is told with: the JSON `--notify-url` gets, and what the
+/// Discord webhook's message is made of
+#[derive(Serialize, Clone, Debug)]
+pub struct Payload<'a> {
+    /// One line about the run, e.g. `2 compromised servers in a check of 120`
+    pub summary: &'a str,
+    pub checked: usize,
+    pub errors: usize,
+    /// The compromised guilds worth telling about: those a run found, or
+    /// those a watch cycle found that the one before hadn't
+    pub findings: &'a [&'a Response],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notification: Option<Notification>,
+}
+
+/// Sends the [`Payload`] as JSON to a URL of the user's choosing
+pub struct Callback {
+    url: Secret<String>,
+    method: Method,
+    headers: HeaderMap,
+    retry: RetryPolicy,
+}
+
+impl Callback {
+    /// POSTs to `url`, once
+    pub fn new(url: Secret<String>) -> Self {
+        Self {
+            url,
+            method: Method::POST,
+            headers: HeaderMap::new(),
+            retry: RetryPolicy::NONE,
+        }
+    }
+
+    pub fn with_method(mut self, method: Method) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Sends `headers` along, e.g. the endpoint's authorization
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Tries again after network errors, 5xx and 429 as `retry` allows
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub async fn send(&self, client: &Client, payload: &Payload<'_>) -> Result<(), CheckError> {
+        let body = serde_json::to_string(payload).expect("payload serializes");
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let err = match self.send_once(client, &body).await {
+                Ok(()) => return Ok(()),
+                Err(err) => err,
+            };
+            match self.retry.delay(attempts, &err) {
+                Some(wait) => {
+                    debug!(%err, ?wait, "notification failed, trying again");
+                    tokio::time::sleep(wait).await;
+                }
+                None => return Err(err),
+            }
+        }
+    }
+
+    async fn send_once(&self, client: &Client, body: &str) -> Result<(), CheckError> {
+        let response = client
+            .request(self.method.clone(), self.url.expose())
+            .headers(self.headers.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.to_owned())
+            .send()
+            .await
+            // the URL can hold a token, in its path or query
+            .map_err(|err| err.without_url())?;
+        let status = response.status();
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse().ok())
+                .map(std::time::Duration::from_secs);
+            return Err(CheckError::RateLimited { retry_after });
+        }
+        if !status.is_success() {
+            return Err(CheckError::HttpStatus(status));
+        }
+        Ok(())
+    }
+}
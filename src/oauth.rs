@@ -0,0 +1,497 @@
+//! Discord's OAuth2 authorization code flow with the `guilds` scope, which
+//! lists someone's servers without them handing over their raw token. The
+//! browser is sent back to a listener on 127.0.0.1 with the code.
+
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, TimeDelta, Utc};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::debug;
+
+use crate::discord::DISCORD_API;
+use crate::secret::Secret;
+use crate::CheckError;
+
+/// Where the browser is sent to authorize the application
+pub const AUTHORIZE_URL: &str = "https://discord.com/oauth2/authorize";
+
+/// All the flow asks for: the list of servers, and nothing else
+pub const SCOPE: &str = "guilds";
+
+/// The path of the redirect on the listener
+pub const CALLBACK_PATH: &str = "/callback";
+
+/// Tokens this close to expiring are refreshed before they're used
+const EXPIRY_MARGIN: TimeDelta = TimeDelta::minutes(5);
+
+/// The most of the browser's request that's read
+const MAX_REQUEST: usize = 16 * 1024;
+
+/// How long a connection from the browser has to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Error, Debug)]
+pub enum OAuthError {
+    #[error("couldn't listen for the redirect: {0}")]
+    Listen(#[source] io::Error),
+
+    #[error("couldn't read {path}: {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("couldn't save the token to {path}: {source}")]
+    Save {
+        path: String,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("the authorization was refused: {0}")]
+    Denied(String),
+
+    #[error("the redirect wasn't for this login, its state doesn't match")]
+    StateMismatch,
+
+    #[error("Discord refused the {grant} ({status}): {error}")]
+    Refused {
+        grant: &'static str,
+        status: StatusCode,
+        error: String,
+    },
+
+    #[error("the token wasn't granted the guilds scope, only {0:?}")]
+    MissingScope(String),
+
+    #[error(transparent)]
+    Request(#[from] CheckError),
+}
+
+/// An access token from the flow, and what refreshes it
+#[derive(Clone, Debug)]
+pub struct OAuthToken {
+    pub access_token: Secret<String>,
+    pub refresh_token: Option<Secret<String>>,
+    pub expires_at: DateTime<Utc>,
+    pub scope: String,
+    /// The application it was granted to, which refreshes it
+    pub client_id: String,
+    /// The application's secret, if it has one, since refreshing needs it too
+    pub client_secret: Option<Secret<String>>,
+}
+
+/// [`OAuthToken`] on disk
+#[derive(Serialize, Deserialize)]
+struct TokenFile {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_at: DateTime<Utc>,
+    scope: String,
+    client_id: String,
+    client_secret: Option<String>,
+}
+
+impl OAuthToken {
+    /// Whether it has to be refreshed before it's used
+    pub fn is_expired(&self) -> bool {
+        self.expires_at - EXPIRY_MARGIN <= Utc::now()
+    }
+
+    /// The token saved at `path`, or `None` if there's none
+    pub fn load(path: &Path) -> Result<Option<Self>, OAuthError> {
+        let io_error = |source| OAuthError::Io {
+            path: path.display().to_string(),
+            source,
+        };
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(io_error(err)),
+        };
+        let file: TokenFile = serde_json::from_slice(&bytes).map_err(|err| io_error(err.into()))?;
+        Ok(Some(Self {
+            access_token: Secret::new(file.access_token),
+            refresh_token: file.refresh_token.map(Secret::new),
+            expires_at: file.expires_at,
+            scope: file.scope,
+            client_id: file.client_id,
+            client_secret: file.client_secret.map(Secret::new),
+        }))
+    }
+
+    /// Writes the token to `path`, readable only by the current user, and
+    /// replaces it atomically
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let file = TokenFile {
+            access_token: self.access_token.expose().clone(),
+            refresh_token: self.refresh_token.as_ref().map(|t| t.expose().clone()),
+            expires_at: self.expires_at,
+            scope: self.scope.clone(),
+            client_id: self.client_id.clone(),
+            client_secret: self.client_secret.as_ref().map(|s| s.expose().clone()),
+        };
+        let tmp = path.with_extension("json.tmp");
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        io::Write::write_all(&mut options.open(&tmp)?, &serde_json::to_vec(&file)?)?;
+        std::fs::rename(tmp, path)
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+    /// Seconds
+    expires_in: i64,
+    #[serde(default)]
+    scope: String,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+    error_description: Option<String>,
+}
+
+/// A Discord application, which the user authorizes to list their servers.
+/// Its redirects must include the listener's
+/// [`redirect_uri`](RedirectListener::redirect_uri).
+pub struct OAuthApp {
+    client_id: String,
+    client_secret: Option<Secret<String>>,
+    api: String,
+    authorize_url: String,
+}
+
+impl OAuthApp {
+    /// `client_secret` can be left out for a public client
+    pub fn new(client_id: impl Into<String>, client_secret: Option<Secret<String>>) -> Self {
+        Self {
+            client_id: client_id.into(),
+            client_secret,
+            api: DISCORD_API.to_owned(),
+            authorize_url: AUTHORIZE_URL.to_owned(),
+        }
+    }
+
+    /// The application a saved token was granted to
+    pub fn of(token: &OAuthToken) -> Self {
+        Self::new(token.client_id.clone(), token.client_secret.clone())
+    }
+
+    /// Talks to another API base URL instead of Discord's
+    pub fn with_api(mut self, api: impl Into<String>) -> Self {
+        self.api = api.into();
+        self
+    }
+
+    /// The page to open in the browser. `state` comes back with the
+    /// redirect, to tell it's for this login.
+    pub fn authorize_url(&self, redirect_uri: &str, state: &str) -> String {
+        let query = [
+            ("client_id", self.client_id.as_str()),
+            ("response_type", "code"),
+            ("scope", SCOPE),
+            ("redirect_uri", redirect_uri),
+            ("state", state),
+            // the user already said yes once, don't keep asking
+            ("prompt", "none"),
+        ];
+        let query: Vec<String> = query
+            .iter()
+            .map(|(name, value)| format!("{name}={}", utf8_percent_encode(value, NON_ALPHANUMERIC)))
+            .collect();
+        format!("{}?{}", self.authorize_url, query.join("&"))
+    }
+
+    /// Trades the `code` from the redirect for a token
+    pub async fn exchange(
+        &self,
+        client: &Client,
+        code: &str,
+        redirect_uri: &str,
+    ) -> Result<OAuthToken, OAuthError> {
+        let form = [
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", redirect_uri),
+        ];
+        let token = self.token(client, "authorization code", &form).await?;
+        if !token.scope.split(' ').any(|scope| scope == SCOPE) {
+            return Err(OAuthError::MissingScope(token.scope));
+        }
+        Ok(token)
+    }
+
+    /// A new token in place of `token`, which has expired or is about to
+    pub async fn refresh(
+        &self,
+        client: &Client,
+        token: &OAuthToken,
+    ) -> Result<OAuthToken, OAuthError> {
+        let Some(refresh_token) = &token.refresh_token else {
+            return Err(OAuthError::Refused {
+                grant: "refresh",
+                status: StatusCode::UNAUTHORIZED,
+                error: "the token expired and can't be refreshed".to_owned(),
+            });
+        };
+        let form = [
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token.expose().as_str()),
+        ];
+        let mut refreshed = self.token(client, "refresh", &form).await?;
+        // Discord may not hand out a new refresh token every time
+        if refreshed.refresh_token.is_none() {
+            refreshed.refresh_token = token.refresh_token.clone();
+        }
+        Ok(refreshed)
+    }
+
+    /// Invalidates `token`, so that it's no use to anyone who has a copy
+    pub async fn revoke(&self, client: &Client, token: &OAuthToken) -> Result<(), OAuthError> {
+        let form = [
+            ("token", token.access_token.expose().as_str()),
+            ("token_type_hint", "access_token"),
+        ];
+        let url = format!("{}/oauth2/token/revoke", self.api);
+        let (status, text) = self.post(client, &url, &form).await?;
+        match status.is_success() {
+            true => Ok(()),
+            false => Err(refused("revocation", status, &text)),
+        }
+    }
+
+    async fn token(
+        &self,
+        client: &Client,
+        grant: &'static str,
+        form: &[(&str, &str)],
+    ) -> Result<OAuthToken, OAuthError> {
+        let url = format!("{}/oauth2/token", self.api);
+        let (status, text) = self.post(client, &url, form).await?;
+        if !status.is_success() {
+            return Err(refused(grant, status, &text));
+        }
+        let response: TokenResponse =
+            serde_json::from_str(&text).map_err(|_| CheckError::bad_body(&text))?;
+        debug!(
+            grant,
+            scope = response.scope,
+            expires_in = response.expires_in,
+            "got OAuth2 token"
+        );
+        Ok(OAuthToken {
+            access_token: Secret::new(response.access_token),
+            refresh_token: response.refresh_token.map(Secret::new),
+            expires_at: Utc::now() + TimeDelta::seconds(response.expires_in),
+            scope: response.scope,
+            client_id: self.client_id.clone(),
+            client_secret: self.client_secret.clone(),
+        })
+    }
+
+    /// Posts `form` with the application's credentials, which Discord takes
+    /// in the body as well as with basic auth
+    async fn post(
+        &self,
+        client: &Client,
+        url: &str,
+        form: &[(&str, &str)],
+    ) -> Result<(StatusCode, String), CheckError> {
+        let mut form = form.to_vec();
+        form.push(("client_id", self.client_id.as_str()));
+        if let Some(secret) = &self.client_secret {
+            form.push(("client_secret", secret.expose().as_str()));
+        }
+        let response = client.post(url).form(&form).send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        debug!(%status, size = text.len(), "got Discord OAuth2 response");
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            return Err(CheckError::RateLimited { retry_after: None });
+        }
+        Ok((status, text))
+    }
+}
+
+fn refused(grant: &'static str, status: StatusCode, text: &str) -> OAuthError {
+    let error = match serde_json::from_str::<ErrorResponse>(text) {
+        Ok(body) => body.error_description.unwrap_or(body.error),
+        Err(_) => CheckError::bad_body(text).to_string(),
+    };
+    OAuthError::Refused {
+        grant,
+        status,
+        error,
+    }
+}
+
+/// The token saved at `path`, refreshed first (and saved again) if it has
+/// expired, or `None` if `auth` was never run
+pub async fn stored_access_token(
+    client: &Client,
+    path: &Path,
+) -> Result<Option<Secret<String>>, OAuthError> {
+    let Some(token) = OAuthToken::load(path)? else {
+        return Ok(None);
+    };
+    if !token.is_expired() {
+        return Ok(Some(token.access_token));
+    }
+    debug!(expired = %token.expires_at, "refreshing the OAuth2 token");
+    let token = OAuthApp::of(&token).refresh(client, &token).await?;
+    token.save(path).map_err(|source| OAuthError::Save {
+        path: path.display().to_string(),
+        source,
+    })?;
+    Ok(Some(token.access_token))
+}
+
+/// A random `state` for [`OAuthApp::authorize_url`], from the system's secure
+/// randomness, since it's what keeps another site from completing the login
+pub fn random_state() -> String {
+    let mut state = [0u8; 32];
+    getrandom::getrandom(&mut state).expect("the system has no randomness");
+    URL_SAFE_NO_PAD.encode(state)
+}
+
+/// Waits on 127.0.0.1 for the browser to come back from Discord with the
+/// code
+pub struct RedirectListener {
+    listener: TcpListener,
+    port: u16,
+}
+
+impl RedirectListener {
+    /// Listens on `port`, or any free one with 0
+    pub async fn bind(port: u16) -> Result<Self, OAuthError> {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .await
+            .map_err(OAuthError::Listen)?;
+        let port = listener.local_addr().map_err(OAuthError::Listen)?.port();
+        Ok(Self { listener, port })
+    }
+
+    /// What the application has to have among its redirects
+    pub fn redirect_uri(&self) -> String {
+        format!("http://127.0.0.1:{}{CALLBACK_PATH}", self.port)
+    }
+
+    /// The code of the first redirect to [`CALLBACK_PATH`]. Other requests,
+    /// like the browser's for a favicon, get a 404 and are otherwise ignored.
+    pub async fn code(&self, state: &str) -> Result<String, OAuthError> {
+        loop {
+            let (mut stream, _) = self.listener.accept().await.map_err(OAuthError::Listen)?;
+            let Some(target) = read_target(&mut stream).await else {
+                continue;
+            };
+            let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+            if path != CALLBACK_PATH {
+                respond(&mut stream, "404 Not Found", "Not found.").await;
+                continue;
+            }
+            let param = |name: &str| {
+                query.split('&').find_map(|pair| {
+                    let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                    (key == name).then(|| {
+                        let value = value.replace('+', " ");
+                        percent_decode_str(&value).decode_utf8_lossy().into_owned()
+                    })
+                })
+            };
+            if param("state").as_deref() != Some(state) {
+                respond(
+                    &mut stream,
+                    "400 Bad Request",
+                    "This isn't the login that was started. Try again from the terminal.",
+                )
+                .await;
+                return Err(OAuthError::StateMismatch);
+            }
+            if let Some(error) = param("error") {
+                respond(
+                    &mut stream,
+                    "200 OK",
+                    "Not authorized. You can close this tab.",
+                )
+                .await;
+                return Err(OAuthError::Denied(
+                    param("error_description").unwrap_or(error),
+                ));
+            }
+            let Some(code) = param("code") else {
+                respond(
+                    &mut stream,
+                    "400 Bad Request",
+                    "Discord sent no code. Try again from the terminal.",
+                )
+                .await;
+                return Err(OAuthError::Denied("the redirect had no code".to_owned()));
+            };
+            respond(
+                &mut stream,
+                "200 OK",
+                "Authorized. You can close this tab and go back to the terminal.",
+            )
+            .await;
+            return Ok(code);
+        }
+    }
+}
+
+/// The target of an HTTP request, e.g. `/callback?code=...`. Browsers open
+/// connections ahead of time that may never carry a request, so those are
+/// given up on after [`READ_TIMEOUT`].
+async fn read_target(stream: &mut TcpStream) -> Option<String> {
+    let mut request = Vec::new();
+    let mut buf = [0; 1024];
+    while !request.windows(2).any(|w| w == b"\r\n") && request.len() < MAX_REQUEST {
+        let read = tokio::time::timeout(READ_TIMEOUT, stream.read(&mut buf));
+        let n = read.await.ok()?.ok()?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+    let line = String::from_utf8_lossy(&request);
+    let mut parts = line.lines().next()?.split(' ');
+    match (parts.next()?, parts.next()?) {
+        ("GET", target) => Some(target.to_owned()),
+        _ => None,
+    }
+}
+
+/// Answers the browser with a page saying `message`. The browser closing
+/// the connection first doesn't matter.
+async fn respond(stream: &mut TcpStream, status: &str, message: &str) {
+    let body = format!(
+        "<!DOCTYPE html><meta charset=utf-8><title>spy-pet-checker</title><p>{message}</p>\n"
+    );
+    let response = format!(
+        "HTTP/1.1 {status}\r\n\
+         Content-Type: text/html; charset=utf-8\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
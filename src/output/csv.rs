@@ -0,0 +1,175 @@
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+
+use chrono::SecondsFormat;
+use serde_json::{Map, Value};
+
+use super::Formatter;
+use crate::watch::Change;
+use crate::{Response, RunReport};
+
+/// One row per result, and per failed check, for spreadsheets. The fields of
+/// the answers are flattened into a column each, named by their path, like
+/// `api.guild.name`; arrays stay JSON.
+#[derive(Default)]
+pub struct Csv {
+    /// Whether [`Formatter::write_changes`] already wrote its header
+    changes_header: bool,
+}
+
+const COLUMNS: &[&str] = &[
+    "guild_id",
+    "guild_name",
+    "kind",
+    "source",
+    "status",
+    "compromised",
+    "checked_at",
+    "labels",
+    "error",
+];
+
+/// Writes one CSV record, quoting fields as RFC 4180 has it. Fields a
+/// spreadsheet would take for a formula, like a server named `=HYPERLINK(...)`,
+/// get a leading `'`.
+pub fn write_csv_row<S: AsRef<str>>(w: &mut dyn Write, fields: &[S]) -> io::Result<()> {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            w.write_all(b",")?;
+        }
+        let field = field.as_ref();
+        let formula =
+            field.starts_with(['=', '+', '-', '@', '\t', '\r']) && field.parse::<f64>().is_err();
+        let field = match formula {
+            true => format!("'{field}"),
+            false => field.to_owned(),
+        };
+        if field.contains([',', '"', '\n', '\r']) {
+            write!(w, "\"{}\"", field.replace('"', "\"\""))?;
+        } else {
+            w.write_all(field.as_bytes())?;
+        }
+    }
+    w.write_all(b"\r\n")
+}
+
+fn flatten(prefix: &str, fields: &Map<String, Value>, out: &mut Vec<(String, String)>) {
+    for (key, value) in fields {
+        let path = format!("{prefix}.{key}");
+        match value {
+            Value::Object(fields) => flatten(&path, fields, out),
+            Value::String(s) => out.push((path, s.clone())),
+            Value::Null => out.push((path, String::new())),
+            value => out.push((path, value.to_string())),
+        }
+    }
+}
+
+/// The answer's fields, by path
+pub(super) fn fields(response: &Response) -> Vec<(String, String)> {
+    let mut out = Vec::new();
+    if let Value::Object(fields) = &response.api_response {
+        flatten("api", fields, &mut out);
+    }
+    out
+}
+
+impl Formatter for Csv {
+    fn write_results(&mut self, w: &mut dyn Write, run: &RunReport) -> io::Result<()> {
+        let flattened: Vec<Vec<(String, String)>> = run.results.iter().map(fields).collect();
+        let extra: BTreeSet<&str> = flattened
+            .iter()
+            .flatten()
+            .map(|(path, _)| path.as_str())
+            .collect();
+        let mut header: Vec<&str> = COLUMNS.to_vec();
+        header.extend(&extra);
+        // added after the rest, so columns read by position stay put
+        header.extend(["severity", "severity_score"]);
+        write_csv_row(w, &header)?;
+
+        for (result, fields) in run.results.iter().zip(&flattened) {
+            let severity = result.severity();
+            let mut row = vec![
+                result.guild_id.clone(),
+                result.guild_name.clone(),
+                result.kind.as_str().to_owned(),
+                result.source.clone(),
+                result.status().as_str().to_owned(),
+                result.is_compromised().to_string(),
+                result
+                    .checked_at
+                    .map(|at| at.to_rfc3339_opts(SecondsFormat::Secs, true))
+                    .unwrap_or_default(),
+                result.labels.join(";"),
+                String::new(),
+            ];
+            row.extend(extra.iter().map(|column| {
+                fields
+                    .iter()
+                    .find(|(path, _)| path == column)
+                    .map(|(_, value)| value.clone())
+                    .unwrap_or_default()
+            }));
+            row.push(severity.map(|s| s.level.to_string()).unwrap_or_default());
+            row.push(severity.map(|s| s.score.to_string()).unwrap_or_default());
+            write_csv_row(w, &row)?;
+        }
+        for failed in &run.failed {
+            let mut row = vec![
+                failed.guild_id.clone(),
+                failed.guild_name.clone(),
+                String::new(),
+                failed.source.clone(),
+                "error".to_owned(),
+                String::new(),
+                String::new(),
+                String::new(),
+                failed.message.clone(),
+            ];
+            row.resize(header.len(), String::new());
+            write_csv_row(w, &row)?;
+        }
+        Ok(())
+    }
+
+    /// A header before the first cycle's changes, then a row per change
+    fn write_changes(&mut self, w: &mut dyn Write, changes: &[Change]) -> io::Result<()> {
+        if !std::mem::replace(&mut self.changes_header, true) {
+            write_csv_row(
+                w,
+                &["change", "guild_id", "guild_name", "source", "from", "to"],
+            )?;
+        }
+        for change in changes {
+            let row = match change {
+                Change::Compromised {
+                    guild_id,
+                    guild_name,
+                    source,
+                } => ["compromised", guild_id, guild_name, source, "", ""].map(str::to_owned),
+                Change::Removed {
+                    guild_id,
+                    guild_name,
+                    source,
+                } => ["removed", guild_id, guild_name, source, "", ""].map(str::to_owned),
+                Change::MessagesGrew {
+                    guild_id,
+                    guild_name,
+                    source,
+                    from,
+                    to,
+                } => [
+                    "messages_grew".to_owned(),
+                    guild_id.clone(),
+                    guild_name.clone(),
+                    source.clone(),
+                    from.to_string(),
+                    to.to_string(),
+                ],
+            };
+            write_csv_row(w, &row)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Kind, Response, Status};
+
+/// The group of results that have no label
+pub const UNLABELLED: &str = "unlabelled";
+
+/// What results can be grouped by in the output
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    /// The backend that answered
+    Source,
+    /// The labels of the result, e.g. the accounts whose indexes listed the
+    /// guild
+    Label,
+    /// `clean`, `compromised`, `indeterminate`, `unparseable` or `unlisted`
+    Status,
+    /// `servers`, `users` or `channels`
+    Kind,
+}
+
+impl GroupBy {
+    /// The groups `result` belongs to. A result with several labels is in
+    /// the group of each of them.
+    pub fn keys(self, result: &Response) -> Vec<String> {
+        match self {
+            GroupBy::Source => vec![result.source.clone()],
+            GroupBy::Label if result.labels.is_empty() => vec![UNLABELLED.to_owned()],
+            GroupBy::Label => result.labels.clone(),
+            GroupBy::Status => vec![result.status().to_string()],
+            GroupBy::Kind => match result.kind {
+                Kind::Guild => vec!["servers".to_owned()],
+                Kind::User => vec!["users".to_owned()],
+                Kind::Channel => vec!["channels".to_owned()],
+            },
+        }
+    }
+}
+
+/// Counts for one group
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct GroupSummary {
+    pub total: usize,
+    pub compromised: usize,
+    pub indeterminate: usize,
+    pub unparseable: usize,
+    #[serde(default)]
+    pub unlisted: usize,
+}
+
+impl GroupSummary {
+    /// Counts in one more result
+    pub fn add(&mut self, result: &Response) {
+        self.total += 1;
+        self.compromised += result.is_compromised() as usize;
+        match result.status() {
+            Status::Indeterminate => self.indeterminate += 1,
+            Status::Unparseable => self.unparseable += 1,
+            Status::Unlisted => self.unlisted += 1,
+            Status::Clean | Status::Compromised => {}
+        }
+    }
+
+    /// Counts for all of `results`, each counted once
+    pub fn of(results: &[Response]) -> Self {
+        let mut summary = Self::default();
+        for result in results {
+            summary.add(result);
+        }
+        summary
+    }
+}
+
+pub struct Group<'a> {
+    pub key: String,
+    pub summary: GroupSummary,
+    pub results: Vec<&'a Response>,
+}
+
+/// Sorts `results` into groups, ordered by key
+pub fn group(by: GroupBy, results: &[Response]) -> Vec<Group<'_>> {
+    let mut groups: BTreeMap<String, Group> = BTreeMap::new();
+    for result in results {
+        for key in by.keys(result) {
+            let group = groups.entry(key.clone()).or_insert_with(|| Group {
+                key,
+                summary: GroupSummary::default(),
+                results: Vec::new(),
+            });
+            group.summary.add(result);
+            group.results.push(result);
+        }
+    }
+    groups.into_values().collect()
+}
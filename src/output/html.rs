@@ -0,0 +1,287 @@
+use std::io::{self, Write};
+
+use chrono::{SecondsFormat, Utc};
+
+use super::csv::fields;
+use super::group::{group, GroupBy, GroupSummary};
+use super::Formatter;
+use crate::backend::SIMULATED;
+use crate::watch::Change;
+use crate::{Kind, Response, RunReport, Status};
+
+/// A standalone page to hand to people who won't read JSON: a summary, a
+/// table of every server that sorts by any column, and the details of the
+/// compromised ones. Styles and the sorting script are inline, so the file
+/// works on its own, offline.
+#[derive(Default)]
+pub struct Html {
+    /// Adds a summary table counting each group
+    pub group_by: Option<GroupBy>,
+}
+
+/// `text` safe to put in HTML, in text or in a quoted attribute
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+const STYLE: &str = "\
+body{font-family:system-ui,sans-serif;margin:2em auto;max-width:70em;padding:0 1em;color:#222}
+h1{font-size:1.6em}h2{font-size:1.3em;margin-top:2em}h3{font-size:1.1em}
+table{border-collapse:collapse;width:100%}
+th,td{border-bottom:1px solid #ddd;padding:.4em .6em;text-align:left;vertical-align:top}
+th{background:#f4f4f4}
+#results th{cursor:pointer;user-select:none}
+#results th::after{content:' \\2195';color:#aaa}
+tr.compromised td{background:#fde8e8}
+tr.compromised td.status{color:#b00;font-weight:bold}
+.cards{display:flex;gap:1em;flex-wrap:wrap}
+.card{border:1px solid #ddd;border-radius:6px;padding:.8em 1.2em}
+.card b{display:block;font-size:1.6em}
+.card.bad b{color:#b00}
+.note{background:#fff6d6;border-left:4px solid #e0b000;padding:.6em 1em}
+code{font-family:ui-monospace,monospace}
+dl{display:grid;grid-template-columns:max-content auto;gap:.2em 1em}dt{font-weight:bold}dd{margin:0}
+";
+
+/// Sorts `#results` by the clicked column, numbers (IDs too) as numbers,
+/// flipping the order on a second click
+const SCRIPT: &str = "\
+document.querySelectorAll('#results th').forEach(function(th,i){
+  var asc=true;
+  th.addEventListener('click',function(){
+    var body=th.closest('table').tBodies[0];
+    var rows=Array.prototype.slice.call(body.rows);
+    rows.sort(function(a,b){
+      var x=a.cells[i].dataset.sort||a.cells[i].textContent;
+      var y=b.cells[i].dataset.sort||b.cells[i].textContent;
+      var c=x.localeCompare(y,undefined,{numeric:true});
+      return asc?c:-c;
+    });
+    asc=!asc;
+    rows.forEach(function(r){body.appendChild(r);});
+  });
+});
+";
+
+/// The page around `body`, which is HTML already
+pub fn write_html_page(w: &mut dyn Write, title: &str, body: &str) -> io::Result<()> {
+    writeln!(w, "<!DOCTYPE html>")?;
+    writeln!(w, "<html lang=\"en\">")?;
+    writeln!(w, "<head>")?;
+    writeln!(w, "<meta charset=\"utf-8\">")?;
+    writeln!(
+        w,
+        "<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">"
+    )?;
+    writeln!(w, "<title>{}</title>", escape_html(title))?;
+    writeln!(w, "<style>\n{STYLE}</style>")?;
+    writeln!(w, "</head>")?;
+    writeln!(w, "<body>")?;
+    writeln!(w, "<h1>{}</h1>", escape_html(title))?;
+    w.write_all(body.as_bytes())?;
+    writeln!(w, "<script>\n{SCRIPT}</script>")?;
+    writeln!(w, "</body>")?;
+    writeln!(w, "</html>")
+}
+
+fn anchor(result: &Response) -> String {
+    format!("{}-{}", result.kind.as_str(), result.guild_id)
+}
+
+fn card(body: &mut String, label: &str, count: usize, bad: bool) {
+    let class = match bad && count > 0 {
+        true => "card bad",
+        false => "card",
+    };
+    body.push_str(&format!(
+        "<div class=\"{class}\"><b>{count}</b>{label}</div>\n"
+    ));
+}
+
+impl Formatter for Html {
+    fn write_results(&mut self, w: &mut dyn Write, run: &RunReport) -> io::Result<()> {
+        let mut body = String::new();
+        let generated = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+        body.push_str(&format!("<p>Generated {generated}</p>\n"));
+        let simulated = run.results.iter().any(|r| r.source == SIMULATED)
+            || run.failed.iter().any(|f| f.source == SIMULATED);
+        if simulated {
+            body.push_str("<p class=\"note\"><b>Simulated run:</b> these results were made up by <code>--simulate</code>, none of them are real.</p>\n");
+        }
+        if run.cancelled {
+            body.push_str(
+                "<p class=\"note\">The run stopped early, not every server was checked.</p>\n",
+            );
+        }
+
+        let total = GroupSummary::of(&run.results);
+        body.push_str("<div class=\"cards\">\n");
+        card(&mut body, "checked", total.total, false);
+        card(&mut body, "compromised", total.compromised, true);
+        card(
+            &mut body,
+            "couldn't be told",
+            total.indeterminate + total.unparseable,
+            false,
+        );
+        card(&mut body, "failed", run.failed.len(), true);
+        body.push_str("</div>\n");
+
+        if let Some(by) = self.group_by {
+            body.push_str("<h2>By group</h2>\n<table>\n<thead><tr><th>Group</th><th>Checked</th><th>Compromised</th></tr></thead>\n<tbody>\n");
+            for group in group(by, &run.results) {
+                body.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                    escape_html(&group.key),
+                    group.summary.total,
+                    group.summary.compromised
+                ));
+            }
+            body.push_str("</tbody>\n</table>\n");
+        }
+
+        body.push_str("<h2>Servers</h2>\n<table id=\"results\">\n<thead><tr><th>Name</th><th>ID</th><th>Status</th><th>Severity</th><th>Messages</th><th>Labels</th><th>Backend</th></tr></thead>\n<tbody>\n");
+        for result in &run.results {
+            let status = result.status();
+            let class = match status {
+                Status::Compromised => " class=\"compromised\"",
+                _ => "",
+            };
+            let name = match result.is_compromised() {
+                true => format!(
+                    "<a href=\"#{}\">{}</a>",
+                    escape_html(&anchor(result)),
+                    escape_html(&result.guild_name)
+                ),
+                false => escape_html(&result.guild_name),
+            };
+            let name = match result.kind {
+                Kind::Guild => name,
+                Kind::User => format!("User {name}"),
+                Kind::Channel => format!("Channel {name}"),
+            };
+            // compromised first when sorted by status
+            let rank = match status {
+                Status::Compromised => 0,
+                Status::Indeterminate | Status::Unparseable => 1,
+                Status::Clean | Status::Unlisted => 2,
+            };
+            // the score sorts, the level is what's shown
+            let severity = match result.severity() {
+                Some(severity) => format!(
+                    "<td data-sort=\"{}\">{}</td>",
+                    severity.score, severity.level
+                ),
+                None => "<td></td>".to_owned(),
+            };
+            let messages = result
+                .message_count()
+                .map(|n| n.to_string())
+                .unwrap_or_default();
+            body.push_str(&format!(
+                "<tr{class}><td>{name}</td><td><code>{}</code></td><td class=\"status\" data-sort=\"{rank}\">{}</td>{severity}<td>{messages}</td><td>{}</td><td>{}</td></tr>\n",
+                escape_html(&result.guild_id),
+                status.as_str(),
+                escape_html(&result.labels.join(", ")),
+                escape_html(&result.source),
+            ));
+        }
+        body.push_str("</tbody>\n</table>\n");
+
+        let compromised: Vec<&Response> = run.compromised().collect();
+        if !compromised.is_empty() {
+            body.push_str("<h2>Compromised</h2>\n");
+        }
+        for result in compromised {
+            body.push_str(&format!(
+                "<h3 id=\"{}\">{} (<code>{}</code>)</h3>\n<dl>\n",
+                escape_html(&anchor(result)),
+                escape_html(&result.guild_name),
+                escape_html(&result.guild_id)
+            ));
+            let mut row = |term: &str, value: &str| {
+                body.push_str(&format!(
+                    "<dt>{}</dt><dd>{}</dd>\n",
+                    escape_html(term),
+                    escape_html(value)
+                ));
+            };
+            row("Backend", &result.source);
+            if let Some(severity) = result.severity() {
+                row("Severity", &severity.to_string());
+            }
+            if let Some(at) = result.checked_at {
+                row("Checked", &at.to_rfc3339_opts(SecondsFormat::Secs, true));
+            }
+            if result.fallback.is_some() {
+                row("Confidence", "low, read off the web page");
+            }
+            if let Some(verification) = result.verification {
+                row("Verified", verification.as_str());
+            }
+            for (path, value) in fields(result) {
+                row(path.strip_prefix("api.").unwrap_or(&path), &value);
+            }
+            body.push_str("</dl>\n");
+        }
+
+        if !run.failed.is_empty() {
+            body.push_str("<h2>Failed checks</h2>\n<table>\n<thead><tr><th>Name</th><th>ID</th><th>Backend</th><th>Error</th></tr></thead>\n<tbody>\n");
+            for failed in &run.failed {
+                body.push_str(&format!(
+                    "<tr><td>{}</td><td><code>{}</code></td><td>{}</td><td>{}</td></tr>\n",
+                    escape_html(&failed.guild_name),
+                    escape_html(&failed.guild_id),
+                    escape_html(&failed.source),
+                    escape_html(&failed.message)
+                ));
+            }
+            body.push_str("</tbody>\n</table>\n");
+        }
+        write_html_page(w, "spy.pet check report", &body)
+    }
+
+    /// A list per watch cycle; the output isn't a page of its own then
+    fn write_changes(&mut self, w: &mut dyn Write, changes: &[Change]) -> io::Result<()> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        writeln!(w, "<ul>")?;
+        for change in changes {
+            let line = match change {
+                Change::Compromised {
+                    guild_id,
+                    guild_name,
+                    ..
+                } => format!("{guild_name} (ID: {guild_id}) is now compromised"),
+                Change::Removed {
+                    guild_id,
+                    guild_name,
+                    ..
+                } => format!("{guild_name} (ID: {guild_id}) is no longer in the dataset"),
+                Change::MessagesGrew {
+                    guild_id,
+                    guild_name,
+                    from,
+                    to,
+                    ..
+                } => format!(
+                    "{guild_name} (ID: {guild_id}) archived messages grew from {from} to {to}"
+                ),
+            };
+            writeln!(w, "<li>{}</li>", escape_html(&line))?;
+        }
+        writeln!(w, "</ul>")
+    }
+}
@@ -0,0 +1,317 @@
+use std::io::{self, Write};
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use super::group::{group, GroupBy, GroupSummary};
+use super::Formatter;
+use crate::shared::{is_multi_label, shared, SharedGuild};
+use crate::watch::Change;
+use crate::{FailedCheck, Performance, Response, RunReport};
+
+/// Version of the `--format json` document, raised whenever a field changes
+/// meaning or goes away. 1 was the bare array of results.
+pub const JSON_SCHEMA_VERSION: u32 = 2;
+
+/// What the JSON document says about itself and the run, before the results
+#[derive(Serialize)]
+struct Header {
+    schema_version: u32,
+    tool_version: &'static str,
+    started_at: Option<DateTime<Utc>>,
+}
+
+impl Header {
+    fn new(started_at: Option<DateTime<Utc>>) -> Self {
+        Self {
+            schema_version: JSON_SCHEMA_VERSION,
+            tool_version: env!("CARGO_PKG_VERSION"),
+            started_at,
+        }
+    }
+}
+
+/// The counts at the end of the JSON document, over the results in it
+#[derive(Serialize)]
+struct Summary {
+    #[serde(flatten)]
+    counts: GroupSummary,
+    errors: usize,
+}
+
+/// Writes the same document as [`Json`], one result at a time, so a run's
+/// results never have to be in memory all at once
+pub struct JsonDocument<W: Write> {
+    w: W,
+    started_at: Option<DateTime<Utc>>,
+    compact: bool,
+    /// Whether a field has been written yet
+    fields: bool,
+    empty: bool,
+    /// Of the results written so far
+    counts: GroupSummary,
+    /// The result being written, reused between results
+    buf: Vec<u8>,
+}
+
+/// Writes `value` pretty-printed, its lines after the first indented
+fn write_indented(
+    w: &mut dyn Write,
+    buf: &mut Vec<u8>,
+    value: &impl Serialize,
+    indent: &[u8],
+) -> io::Result<()> {
+    buf.clear();
+    serde_json::to_writer_pretty(&mut *buf, value)?;
+    // strings can't contain raw newlines, so every line break is formatting
+    for (i, line) in buf.split(|&b| b == b'\n').enumerate() {
+        if i > 0 {
+            w.write_all(b"\n")?;
+            w.write_all(indent)?;
+        }
+        w.write_all(line)?;
+    }
+    Ok(())
+}
+
+impl<W: Write> JsonDocument<W> {
+    pub fn new(w: W, started_at: Option<DateTime<Utc>>) -> Self {
+        Self {
+            w,
+            started_at,
+            compact: false,
+            fields: false,
+            empty: true,
+            counts: GroupSummary::default(),
+            buf: Vec::new(),
+        }
+    }
+
+    /// Writes the document on a single line, without any whitespace
+    pub fn compact(mut self, compact: bool) -> Self {
+        self.compact = compact;
+        self
+    }
+
+    /// Writes `value` as the document's top level field `name`
+    fn field(&mut self, name: &str, value: &impl Serialize) -> io::Result<()> {
+        self.key(name)?;
+        self.value(value, b"  ")
+    }
+
+    /// Starts the document's top level field `name`, after the ones before
+    fn key(&mut self, name: &str) -> io::Result<()> {
+        let comma = if self.fields { "," } else { "" };
+        self.fields = true;
+        match self.compact {
+            true => write!(self.w, "{comma}\"{name}\":"),
+            false => write!(self.w, "{comma}\n  \"{name}\": "),
+        }
+    }
+
+    fn value(&mut self, value: &impl Serialize, indent: &[u8]) -> io::Result<()> {
+        match self.compact {
+            true => Ok(serde_json::to_writer(&mut self.w, value)?),
+            false => write_indented(&mut self.w, &mut self.buf, value, indent),
+        }
+    }
+
+    /// Opens the document, up to where the results array starts
+    fn open(&mut self) -> io::Result<()> {
+        let header = Header::new(self.started_at);
+        self.w.write_all(b"{")?;
+        self.field("schema_version", &header.schema_version)?;
+        self.field("tool_version", &header.tool_version)?;
+        self.field("started_at", &header.started_at)?;
+        self.key("results")
+    }
+
+    pub fn push(&mut self, result: &Response) -> io::Result<()> {
+        if self.empty {
+            self.open()?;
+            self.w.write_all(b"[")?;
+        } else {
+            self.w.write_all(b",")?;
+        }
+        if !self.compact {
+            self.w.write_all(b"\n    ")?;
+        }
+        self.empty = false;
+        self.counts.add(result);
+        self.value(result, b"    ")
+    }
+
+    /// Closes the results, adds the checks that failed and the summary, and
+    /// flushes the writer, which it hands back. `run` is only read for what
+    /// isn't a result.
+    pub fn finish(mut self, run: &RunReport) -> io::Result<W> {
+        if self.empty {
+            self.open()?;
+            self.w.write_all(b"[]")?;
+        } else {
+            if !self.compact {
+                self.w.write_all(b"\n  ")?;
+            }
+            self.w.write_all(b"]")?;
+        }
+        self.field("errors", &run.failed)?;
+        let summary = Summary {
+            counts: std::mem::take(&mut self.counts),
+            errors: run.failed.len(),
+        };
+        self.field("summary", &summary)?;
+        if run.cancelled {
+            self.field("cancelled", &true)?;
+        }
+        let duration = run.performance.as_ref().map(|perf| perf.elapsed_ms);
+        self.field("duration_ms", &duration)?;
+        if let Some(performance) = &run.performance {
+            self.field("performance", performance)?;
+        }
+        match self.compact {
+            true => self.w.write_all(b"}\n")?,
+            false => self.w.write_all(b"\n}\n")?,
+        }
+        self.w.flush()?;
+        Ok(self.w)
+    }
+}
+
+/// Writes each result as a line of JSON, flushed right away, so tools like
+/// `jq` get them as they come in
+pub struct JsonLines<W: Write> {
+    w: W,
+}
+
+impl<W: Write> JsonLines<W> {
+    pub fn new(w: W) -> Self {
+        Self { w }
+    }
+
+    pub fn push(&mut self, result: &Response) -> io::Result<()> {
+        serde_json::to_writer(&mut self.w, result)?;
+        self.w.write_all(b"\n")?;
+        self.w.flush()
+    }
+
+    /// Flushes the writer and hands it back
+    pub fn finish(mut self) -> io::Result<W> {
+        self.w.flush()?;
+        Ok(self.w)
+    }
+}
+
+/// Newline-delimited JSON, a result per line
+#[derive(Default)]
+pub struct Ndjson;
+
+impl Formatter for Ndjson {
+    fn write_results(&mut self, w: &mut dyn Write, run: &RunReport) -> io::Result<()> {
+        let mut lines = JsonLines::new(w);
+        for result in &run.results {
+            lines.push(result)?;
+        }
+        lines.finish().map(drop)
+    }
+
+    fn write_changes(&mut self, w: &mut dyn Write, changes: &[Change]) -> io::Result<()> {
+        Json::default().write_changes(w, changes)
+    }
+}
+
+/// Complete output in json format: an object with the `results`, the
+/// `errors` of the checks that failed, a `summary` counting both and what
+/// version of the tool and of the format wrote it. `cancelled` is there,
+/// and true, when the run stopped early.
+#[derive(Default)]
+pub struct Json {
+    /// Wraps the results in an object with a `groups` summary, keyed by
+    /// group
+    pub group_by: Option<GroupBy>,
+    /// On a single line instead of pretty-printed
+    pub compact: bool,
+}
+
+impl Json {
+    pub fn grouped(group_by: GroupBy) -> Self {
+        Self {
+            group_by: Some(group_by),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Grouped<'a> {
+    #[serde(flatten)]
+    header: Header,
+    results: &'a [Response],
+    errors: &'a [FailedCheck],
+    groups: BTreeMap<String, GroupSummary>,
+    /// Every result counted once, which the groups don't add up to when a
+    /// result is in several
+    total: GroupSummary,
+    /// Compromised guilds by how many labels share them, when there are
+    /// several labels
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    shared: Vec<SharedGuild>,
+    summary: Summary,
+    /// Set when the run stopped early, so the results are partial
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    cancelled: bool,
+    duration_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    performance: Option<&'a Performance>,
+}
+
+impl Formatter for Json {
+    fn write_results(&mut self, w: &mut dyn Write, run: &RunReport) -> io::Result<()> {
+        if let Some(by) = self.group_by {
+            let groups = group(by, &run.results)
+                .into_iter()
+                .map(|group| (group.key, group.summary))
+                .collect();
+            let grouped = Grouped {
+                header: Header::new(run.started_at),
+                results: &run.results,
+                errors: &run.failed,
+                groups,
+                total: GroupSummary::of(&run.results),
+                shared: match is_multi_label(&run.results) {
+                    true => shared(&run.results),
+                    false => Vec::new(),
+                },
+                summary: Summary {
+                    counts: GroupSummary::of(&run.results),
+                    errors: run.failed.len(),
+                },
+                cancelled: run.cancelled,
+                duration_ms: run.performance.as_ref().map(|perf| perf.elapsed_ms),
+                performance: run.performance.as_ref(),
+            };
+            match self.compact {
+                true => serde_json::to_writer(&mut *w, &grouped)?,
+                false => serde_json::to_writer_pretty(&mut *w, &grouped)?,
+            }
+            return writeln!(w);
+        }
+
+        let mut document = JsonDocument::new(w, run.started_at).compact(self.compact);
+        for result in &run.results {
+            document.push(result)?;
+        }
+        document.finish(run).map(drop)
+    }
+
+    /// One change per line, so a long-running watch can be piped into
+    /// other tools
+    fn write_changes(&mut self, w: &mut dyn Write, changes: &[Change]) -> io::Result<()> {
+        for change in changes {
+            serde_json::to_writer(&mut *w, change)?;
+            writeln!(w)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use chrono::{SecondsFormat, Utc};
+
+use super::csv::fields;
+use super::html::escape_html;
+use super::Formatter;
+use crate::watch::Change;
+use crate::{Kind, Response, RunReport, Status};
+
+/// A JUnit XML report for CI systems: a test case per server, which passes
+/// when it's clean, fails when it's compromised and errors when the check
+/// failed. Servers the answer doesn't say either way about are skipped.
+/// There's a test suite per backend.
+#[derive(Default)]
+pub struct Junit;
+
+/// `text` safe to put in XML. Control characters, which XML 1.0 has no
+/// way to write, are left out.
+pub fn escape_xml(text: &str) -> String {
+    let text: String = text
+        .chars()
+        .filter(|&c| !c.is_control() || matches!(c, '\t' | '\n' | '\r'))
+        .collect();
+    escape_html(&text)
+}
+
+/// What became of a test case
+pub enum Outcome {
+    Passed,
+    Failed { message: String, details: String },
+    Errored { kind: String, message: String },
+    Skipped { message: String },
+}
+
+pub struct TestCase {
+    pub classname: String,
+    pub name: String,
+    pub outcome: Outcome,
+}
+
+pub struct TestSuite {
+    pub name: String,
+    pub cases: Vec<TestCase>,
+}
+
+fn count(cases: &[TestCase], which: fn(&Outcome) -> bool) -> usize {
+    cases.iter().filter(|case| which(&case.outcome)).count()
+}
+
+fn counts(cases: &[TestCase]) -> String {
+    format!(
+        "tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\"",
+        cases.len(),
+        count(cases, |o| matches!(o, Outcome::Failed { .. })),
+        count(cases, |o| matches!(o, Outcome::Errored { .. })),
+        count(cases, |o| matches!(o, Outcome::Skipped { .. })),
+    )
+}
+
+fn write_suite(w: &mut dyn Write, suite: &TestSuite, timestamp: &str) -> io::Result<()> {
+    writeln!(
+        w,
+        "  <testsuite name=\"{}\" {} timestamp=\"{timestamp}\">",
+        escape_xml(&suite.name),
+        counts(&suite.cases)
+    )?;
+    for case in &suite.cases {
+        let open = format!(
+            "    <testcase classname=\"{}\" name=\"{}\"",
+            escape_xml(&case.classname),
+            escape_xml(&case.name)
+        );
+        match &case.outcome {
+            Outcome::Passed => writeln!(w, "{open}/>")?,
+            Outcome::Failed { message, details } => {
+                writeln!(w, "{open}>")?;
+                writeln!(
+                    w,
+                    "      <failure message=\"{}\" type=\"compromised\">{}</failure>",
+                    escape_xml(message),
+                    escape_xml(details)
+                )?;
+                writeln!(w, "    </testcase>")?;
+            }
+            Outcome::Errored { kind, message } => {
+                writeln!(w, "{open}>")?;
+                writeln!(
+                    w,
+                    "      <error message=\"{}\" type=\"{}\"/>",
+                    escape_xml(message),
+                    escape_xml(kind)
+                )?;
+                writeln!(w, "    </testcase>")?;
+            }
+            Outcome::Skipped { message } => {
+                writeln!(w, "{open}>")?;
+                writeln!(w, "      <skipped message=\"{}\"/>", escape_xml(message))?;
+                writeln!(w, "    </testcase>")?;
+            }
+        }
+    }
+    writeln!(w, "  </testsuite>")
+}
+
+/// Writes a whole report, `suites` in a `<testsuites>` named `name`
+pub fn write_testsuites(
+    w: &mut dyn Write,
+    name: &str,
+    suites: &[TestSuite],
+    seconds: Option<f64>,
+) -> io::Result<()> {
+    let timestamp = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+    let all: Vec<&TestCase> = suites.iter().flat_map(|suite| &suite.cases).collect();
+    let failures = all
+        .iter()
+        .filter(|case| matches!(case.outcome, Outcome::Failed { .. }))
+        .count();
+    let errors = all
+        .iter()
+        .filter(|case| matches!(case.outcome, Outcome::Errored { .. }))
+        .count();
+    let time = match seconds {
+        Some(seconds) => format!(" time=\"{seconds:.3}\""),
+        None => String::new(),
+    };
+    writeln!(w, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(
+        w,
+        "<testsuites name=\"{}\" tests=\"{}\" failures=\"{failures}\" errors=\"{errors}\"{time}>",
+        escape_xml(name),
+        all.len()
+    )?;
+    for suite in suites {
+        write_suite(w, suite, &timestamp)?;
+    }
+    writeln!(w, "</testsuites>")
+}
+
+fn name(kind: Kind, guild_name: &str, guild_id: &str) -> String {
+    match kind {
+        Kind::Guild => format!("{guild_name} (ID: {guild_id})"),
+        Kind::User => format!("User {guild_name} (ID: {guild_id})"),
+        Kind::Channel => format!("Channel {guild_name} (ID: {guild_id})"),
+    }
+}
+
+fn case(result: &Response) -> TestCase {
+    let outcome = match result.status() {
+        Status::Clean | Status::Unlisted => Outcome::Passed,
+        Status::Compromised => {
+            let details: Vec<String> = fields(result)
+                .into_iter()
+                .map(|(path, value)| {
+                    format!("{}: {value}", path.strip_prefix("api.").unwrap_or(&path))
+                })
+                .collect();
+            let severity = result.severity().map(|s| s.level);
+            Outcome::Failed {
+                message: match severity {
+                    Some(level) => format!("in the dataset, {level} severity"),
+                    None => "in the dataset".to_owned(),
+                },
+                details: details.join("\n"),
+            }
+        }
+        status @ (Status::Indeterminate | Status::Unparseable) => Outcome::Skipped {
+            message: format!("the answer doesn't say either way ({status})"),
+        },
+    };
+    TestCase {
+        classname: result.source.clone(),
+        name: name(result.kind, &result.guild_name, &result.guild_id),
+        outcome,
+    }
+}
+
+impl Formatter for Junit {
+    fn write_results(&mut self, w: &mut dyn Write, run: &RunReport) -> io::Result<()> {
+        let mut suites: BTreeMap<&str, Vec<TestCase>> = BTreeMap::new();
+        for result in &run.results {
+            suites.entry(&result.source).or_default().push(case(result));
+        }
+        for failed in &run.failed {
+            suites.entry(&failed.source).or_default().push(TestCase {
+                classname: failed.source.clone(),
+                name: name(Kind::Guild, &failed.guild_name, &failed.guild_id),
+                outcome: Outcome::Errored {
+                    kind: failed.kind.as_str().to_owned(),
+                    message: failed.message.clone(),
+                },
+            });
+        }
+        let suites: Vec<TestSuite> = suites
+            .into_iter()
+            .map(|(name, cases)| TestSuite {
+                name: name.to_owned(),
+                cases,
+            })
+            .collect();
+        let seconds = run
+            .performance
+            .as_ref()
+            .map(|perf| perf.elapsed_ms / 1000.0);
+        write_testsuites(w, "spy-pet-checker", &suites, seconds)
+    }
+
+    /// A report per watch cycle, with a test case per change that fails
+    /// unless the server left the dataset
+    fn write_changes(&mut self, w: &mut dyn Write, changes: &[Change]) -> io::Result<()> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let cases = changes
+            .iter()
+            .map(|change| {
+                let (guild_id, guild_name, source, message) = match change {
+                    Change::Compromised {
+                        guild_id,
+                        guild_name,
+                        source,
+                    } => (guild_id, guild_name, source, "now compromised".to_owned()),
+                    Change::Removed {
+                        guild_id,
+                        guild_name,
+                        source,
+                    } => (
+                        guild_id,
+                        guild_name,
+                        source,
+                        "no longer in the dataset".to_owned(),
+                    ),
+                    Change::MessagesGrew {
+                        guild_id,
+                        guild_name,
+                        source,
+                        from,
+                        to,
+                    } => (
+                        guild_id,
+                        guild_name,
+                        source,
+                        format!("archived messages grew from {from} to {to}"),
+                    ),
+                };
+                let outcome = match change {
+                    Change::Removed { .. } => Outcome::Passed,
+                    _ => Outcome::Failed {
+                        message,
+                        details: String::new(),
+                    },
+                };
+                TestCase {
+                    classname: source.clone(),
+                    name: name(Kind::Guild, guild_name, guild_id),
+                    outcome,
+                }
+            })
+            .collect();
+        let suite = TestSuite {
+            name: "watch".to_owned(),
+            cases,
+        };
+        write_testsuites(w, "spy-pet-checker", &[suite], None)
+    }
+}
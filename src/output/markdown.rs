@@ -0,0 +1,198 @@
+use std::io::{self, Write};
+
+use chrono::SecondsFormat;
+
+use super::csv::fields;
+use super::group::{group, GroupBy, GroupSummary};
+use super::Formatter;
+use crate::backend::SIMULATED;
+use crate::watch::Change;
+use crate::{Kind, RunReport};
+
+/// A report to paste into a Discord message or a GitHub issue: a summary
+/// table, then a section per compromised server with what the answer said
+#[derive(Default)]
+pub struct Markdown {
+    /// Has the summary table count each group
+    pub group_by: Option<GroupBy>,
+}
+
+/// `text` with the characters Markdown would act on escaped
+pub fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '\\' | '`' | '*' | '_' | '~' | '|' | '[' | ']' | '<' | '>' | '#' | '@'
+        ) {
+            escaped.push('\\');
+        }
+        match c {
+            '\n' | '\r' => escaped.push(' '),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn summary_row(w: &mut dyn Write, key: &str, summary: &GroupSummary) -> io::Result<()> {
+    writeln!(
+        w,
+        "| {key} | {} | {} | {} |",
+        summary.total,
+        summary.compromised,
+        summary.indeterminate + summary.unparseable
+    )
+}
+
+impl Formatter for Markdown {
+    fn write_results(&mut self, w: &mut dyn Write, run: &RunReport) -> io::Result<()> {
+        writeln!(w, "# spy.pet check report")?;
+        writeln!(w)?;
+        let simulated = run.results.iter().any(|r| r.source == SIMULATED)
+            || run.failed.iter().any(|f| f.source == SIMULATED);
+        if simulated {
+            writeln!(
+                w,
+                "> **Simulated run:** these results were made up by `--simulate`, none of them are real"
+            )?;
+            writeln!(w)?;
+        }
+        if run.cancelled {
+            writeln!(w, "> The run stopped early, not every server was checked")?;
+            writeln!(w)?;
+        }
+
+        let first = match self.group_by {
+            Some(by) => by_name(by),
+            None => "",
+        };
+        writeln!(w, "| {first} | Checked | Compromised | Not checked |")?;
+        writeln!(w, "|---|---:|---:|---:|")?;
+        if let Some(by) = self.group_by {
+            for group in group(by, &run.results) {
+                summary_row(w, &escape_markdown(&group.key), &group.summary)?;
+            }
+        }
+        summary_row(w, "**Total**", &GroupSummary::of(&run.results))?;
+
+        let compromised: Vec<_> = run.compromised().collect();
+        if !compromised.is_empty() {
+            writeln!(w)?;
+            writeln!(w, "## Compromised")?;
+        }
+        for result in compromised {
+            writeln!(w)?;
+            let what = match result.kind {
+                Kind::Guild => "",
+                Kind::User => "User ",
+                Kind::Channel => "Channel ",
+            };
+            writeln!(
+                w,
+                "### {what}{} (`{}`)",
+                escape_markdown(&result.guild_name),
+                result.guild_id
+            )?;
+            writeln!(w)?;
+            writeln!(w, "- **Backend:** {}", escape_markdown(&result.source))?;
+            if let Some(severity) = result.severity() {
+                writeln!(w, "- **Severity:** {severity}")?;
+            }
+            if let Some(at) = result.checked_at {
+                writeln!(
+                    w,
+                    "- **Checked:** {}",
+                    at.to_rfc3339_opts(SecondsFormat::Secs, true)
+                )?;
+            }
+            if !result.labels.is_empty() {
+                writeln!(
+                    w,
+                    "- **Labels:** {}",
+                    escape_markdown(&result.labels.join(", "))
+                )?;
+            }
+            if result.fallback.is_some() {
+                writeln!(w, "- Read off the web page, low confidence")?;
+            }
+            if let Some(verification) = result.verification {
+                writeln!(w, "- **Verified:** {}", verification.as_str())?;
+            }
+            for (path, value) in fields(result) {
+                let path = path.strip_prefix("api.").unwrap_or(&path);
+                writeln!(
+                    w,
+                    "- **{}:** {}",
+                    escape_markdown(path),
+                    escape_markdown(&value)
+                )?;
+            }
+        }
+
+        if !run.failed.is_empty() {
+            writeln!(w)?;
+            writeln!(w, "## Failed checks")?;
+            writeln!(w)?;
+            writeln!(w, "| Server | ID | Backend | Error |")?;
+            writeln!(w, "|---|---|---|---|")?;
+            for failed in &run.failed {
+                writeln!(
+                    w,
+                    "| {} | `{}` | {} | {} |",
+                    escape_markdown(&failed.guild_name),
+                    failed.guild_id,
+                    escape_markdown(&failed.source),
+                    escape_markdown(&failed.message)
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_changes(&mut self, w: &mut dyn Write, changes: &[Change]) -> io::Result<()> {
+        for change in changes {
+            match change {
+                Change::Compromised {
+                    guild_id,
+                    guild_name,
+                    ..
+                } => writeln!(
+                    w,
+                    "- **{}** (`{guild_id}`) is now compromised",
+                    escape_markdown(guild_name)
+                )?,
+                Change::Removed {
+                    guild_id,
+                    guild_name,
+                    ..
+                } => writeln!(
+                    w,
+                    "- **{}** (`{guild_id}`) is no longer in the dataset",
+                    escape_markdown(guild_name)
+                )?,
+                Change::MessagesGrew {
+                    guild_id,
+                    guild_name,
+                    from,
+                    to,
+                    ..
+                } => writeln!(
+                    w,
+                    "- **{}** (`{guild_id}`) archived messages grew from {from} to {to}",
+                    escape_markdown(guild_name)
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn by_name(by: GroupBy) -> &'static str {
+    match by {
+        GroupBy::Source => "Backend",
+        GroupBy::Label => "Label",
+        GroupBy::Status => "Status",
+        GroupBy::Kind => "Kind",
+    }
+}
@@ -0,0 +1,51 @@
+use std::io::{self, Write};
+
+use serde_json::{json, Value};
+
+use crate::watch::Change;
+use crate::RunReport;
+
+mod csv;
+mod group;
+mod html;
+mod json;
+mod junit;
+mod markdown;
+mod plain;
+mod sort;
+mod template;
+mod toml;
+mod yaml;
+
+pub use csv::{write_csv_row, Csv};
+pub use group::{group, Group, GroupBy, GroupSummary, UNLABELLED};
+pub use html::{escape_html, write_html_page, Html};
+pub use json::{Json, JsonDocument, JsonLines, Ndjson, JSON_SCHEMA_VERSION};
+pub use junit::{escape_xml, write_testsuites, Junit, Outcome, TestCase, TestSuite};
+pub use markdown::{escape_markdown, Markdown};
+pub use plain::Plain;
+pub use sort::{sort_results, sort_run, SortBy};
+pub use template::{template_context, OutputTemplate, OutputTemplateError, Templated};
+pub use toml::{to_toml, Toml};
+pub use yaml::{to_yaml, Yaml};
+
+/// An output backend rendering a finished run
+pub trait Formatter {
+    fn write_results(&mut self, w: &mut dyn Write, run: &RunReport) -> io::Result<()>;
+
+    /// Reports what a watch cycle found that the previous ones hadn't
+    fn write_changes(&mut self, w: &mut dyn Write, changes: &[Change]) -> io::Result<()>;
+}
+
+/// A run as the mapping the YAML and TOML formats write: the results, and
+/// the failed checks if there are any
+fn document(run: &RunReport) -> io::Result<Value> {
+    let mut document = json!({ "results": run.results });
+    if !run.failed.is_empty() {
+        document["failed"] = serde_json::to_value(&run.failed)?;
+    }
+    if run.cancelled {
+        document["cancelled"] = Value::Bool(true);
+    }
+    Ok(document)
+}
@@ -0,0 +1,424 @@
+use std::io::{self, Write};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use fluent_bundle::FluentValue;
+
+use super::group::{group, GroupBy, GroupSummary};
+use super::Formatter;
+use crate::backend::SIMULATED;
+use crate::i18n::{Lang, Messages};
+use crate::members::MemberScan;
+use crate::shared::{is_multi_label, shared};
+use crate::watch::Change;
+use crate::window::InWindow;
+use crate::{Kind, Response, RunReport, Status, Verification};
+
+/// Simple output in human readable format
+#[derive(Default)]
+pub struct Plain {
+    /// Lists results under a heading per group
+    pub group_by: Option<GroupBy>,
+    /// Lists the clean servers too, not just the ones that need looking at
+    pub include_clean: bool,
+    /// Lists what the answers say about the compromised servers under them
+    pub details: bool,
+    /// Colors what's said about each server, for a terminal
+    pub color: bool,
+    pub lang: Lang,
+}
+
+impl Plain {
+    pub fn grouped(group_by: GroupBy) -> Self {
+        Self {
+            group_by: Some(group_by),
+            ..Default::default()
+        }
+    }
+}
+
+/// Samples and members listed under a server at most
+const SHOWN: usize = 3;
+
+/// What the answer says of a compromised guild, a line per field
+fn details(guild: &Response, m: &Messages) -> Vec<String> {
+    let Some(info) = guild.guild_info() else {
+        return Vec::new();
+    };
+    let day = |at: DateTime<Utc>| at.format("%Y-%m-%d").to_string();
+    let mut lines = Vec::new();
+    if let Some(name) = info.name.filter(|name| *name != guild.guild_name) {
+        lines.push(m.with("detail-name", [("name", name.into())]));
+    }
+    if let Some(messages) = info.message_count {
+        lines.push(m.with("detail-messages", [("count", messages.into())]));
+    }
+    if let Some(members) = info.member_count {
+        lines.push(m.with("detail-members", [("count", members.into())]));
+    }
+    if let Some(first) = info.first_seen {
+        lines.push(m.with("detail-first-seen", [("date", day(first).into())]));
+    }
+    if let Some(last) = info.last_seen {
+        lines.push(m.with("detail-last-seen", [("date", day(last).into())]));
+    }
+    if !info.users.is_empty() {
+        let mut users = info.users;
+        users.sort_by_key(|user| std::cmp::Reverse(user.message_count));
+        let top: Vec<String> = users
+            .iter()
+            .take(SHOWN)
+            .map(|user| {
+                let name = user.name.as_deref().unwrap_or(&user.id);
+                match user.message_count {
+                    Some(messages) => format!("{name} ({messages})"),
+                    None => name.to_owned(),
+                }
+            })
+            .collect();
+        let count = FluentValue::from(users.len());
+        lines.push(match top.is_empty() {
+            true => m.with("detail-archived-members", [("count", count)]),
+            false => m.with(
+                "detail-archived-members-top",
+                [("count", count), ("top", top.join(", ").into())],
+            ),
+        });
+    }
+    for sample in info.message_samples.iter().take(SHOWN) {
+        let author = match &sample.author_id {
+            Some(author) => author.as_str().into(),
+            None => m.get("detail-someone").into(),
+        };
+        let content = sample.content.as_deref().unwrap_or_default().into();
+        let line = match &sample.channel_id {
+            Some(channel) => m.with(
+                "detail-sample",
+                [
+                    ("author", author),
+                    ("channel", channel.as_str().into()),
+                    ("content", content),
+                ],
+            ),
+            None => m.with(
+                "detail-sample-no-channel",
+                [("author", author), ("content", content)],
+            ),
+        };
+        lines.push(line);
+    }
+    if info.message_samples.len() > SHOWN {
+        let more = info.message_samples.len() - SHOWN;
+        lines.push(m.with("detail-more-samples", [("count", more.into())]));
+    }
+    lines
+}
+
+/// ANSI color codes, for [`paint`]
+const RED: &str = "1;31";
+const GREEN: &str = "32";
+const YELLOW: &str = "33";
+
+/// `text` in the terminal color `code` when `color` is set
+fn paint(text: &str, code: &str, color: bool) -> String {
+    match color {
+        true => format!("\x1b[{code}m{text}\x1b[0m"),
+        false => text.to_owned(),
+    }
+}
+
+/// A line about a server, and those to write under it
+struct Line {
+    /// Which server it is, padded so what's said about it lines up
+    subject: String,
+    said: String,
+    below: Vec<String>,
+}
+
+/// Writes `lines`, their subjects padded to the same width
+fn write_aligned(w: &mut dyn Write, lines: &[Line], indent: &str) -> io::Result<()> {
+    let width = lines
+        .iter()
+        .map(|line| line.subject.chars().count())
+        .max()
+        .unwrap_or(0);
+    for line in lines {
+        writeln!(w, "{indent}{:<width$} {}", line.subject, line.said)?;
+        for below in &line.below {
+            writeln!(w, "{indent}  {below}")?;
+        }
+    }
+    Ok(())
+}
+
+/// `name (ID: id)`, in the order and with the spacing of the language
+fn named(m: &Messages, id: &str, name: &str, guild_id: &str) -> String {
+    m.with(id, [("name", name.into()), ("id", guild_id.into())])
+}
+
+fn subject(guild: &Response, m: &Messages) -> String {
+    let id = match guild.kind {
+        Kind::Guild => "subject-guild",
+        Kind::User => "subject-user",
+        Kind::Channel => "subject-channel",
+    };
+    named(m, id, &guild.guild_name, &guild.guild_id)
+}
+
+fn write_guilds<'a>(
+    w: &mut dyn Write,
+    results: impl Iterator<Item = &'a Response> + Clone,
+    indent: &str,
+    options: &Plain,
+    m: &Messages,
+) -> io::Result<()> {
+    let color = options.color;
+    let mut lines = Vec::new();
+    for guild in results.clone().filter(|r| r.is_compromised()) {
+        let mut note = match guild.window {
+            Some(InWindow::Outside) => format!(" {}", m.get("note-outside-window")),
+            Some(InWindow::NoTimestamps) => format!(" {}", m.get("note-no-timestamps")),
+            Some(InWindow::Inside) | None => String::new(),
+        };
+        if guild.fallback.is_some() {
+            note = format!("{note} {}", m.get("note-fallback"));
+        }
+        if let Some(verification) = guild.verification {
+            let id = match verification {
+                Verification::Confirmed => "note-confirmed",
+                Verification::Flaky => "note-flaky",
+                Verification::Unverified => "note-unverified",
+            };
+            note = format!("{note} {}", m.get(id));
+        }
+        if let Some(severity) = guild.severity() {
+            let level = m.get(&format!("severity-{}", severity.level));
+            let args = [
+                ("level", FluentValue::from(level)),
+                ("score", FluentValue::from(severity.score)),
+            ];
+            note = format!("{note} {}", m.with("note-severity", args));
+        }
+        if !guild.labels.is_empty() {
+            note = format!("{note} [{}]", guild.labels.join(", "));
+        }
+        let said = m.get(match guild.kind {
+            Kind::Guild => "guild-compromised",
+            Kind::User => "user-compromised",
+            Kind::Channel => "channel-compromised",
+        });
+        lines.push(Line {
+            subject: subject(guild, m),
+            said: format!("{}{note}", paint(&said, RED, color)),
+            below: match options.details {
+                true => details(guild, m),
+                false => Vec::new(),
+            },
+        });
+    }
+    let clean = results.clone().filter(|r| {
+        options.include_clean && matches!(r.status(), Status::Clean | Status::Unlisted)
+    });
+    for guild in clean {
+        let note = match guild.status() {
+            Status::Unlisted => format!(" {}", m.get("note-unlisted")),
+            _ => String::new(),
+        };
+        let said = m.get(match guild.kind {
+            Kind::Guild => "guild-clean",
+            Kind::User | Kind::Channel => "not-in-dataset",
+        });
+        lines.push(Line {
+            subject: subject(guild, m),
+            said: format!("{}{note}", paint(&said, GREEN, color)),
+            below: Vec::new(),
+        });
+    }
+    for guild in results.clone() {
+        let answer = match (&guild.unparseable, guild.status()) {
+            (Some(body), _) => format!("{:?}", body.snippet),
+            (None, Status::Indeterminate) => guild.api_response.to_string(),
+            _ => continue,
+        };
+        let answered = m.with(
+            "unchecked-answer",
+            [
+                ("source", guild.source.as_str().into()),
+                ("answer", answer.into()),
+            ],
+        );
+        lines.push(Line {
+            subject: subject(guild, m),
+            said: format!("{}, {answered}", paint(&m.get("unchecked"), YELLOW, color)),
+            below: Vec::new(),
+        });
+    }
+    write_aligned(w, &lines, indent)?;
+    let mut skipped = 0;
+    for guild in results {
+        match &guild.member_scan {
+            Some(MemberScan::Scanned { bots, .. }) if !bots.is_empty() => {
+                let bots: Vec<String> = bots
+                    .iter()
+                    .map(|bot| format!("{} ({})", bot.label, bot.id))
+                    .collect();
+                let said = m.with(
+                    "scraper-bots",
+                    [
+                        ("name", guild.guild_name.as_str().into()),
+                        ("id", guild.guild_id.as_str().into()),
+                        ("bots", bots.join(", ").into()),
+                    ],
+                );
+                writeln!(w, "{indent}{said}")?
+            }
+            Some(MemberScan::Skipped { .. }) => skipped += 1,
+            _ => {}
+        }
+    }
+    if skipped > 0 {
+        let said = m.with("members-not-scanned", [("count", skipped.into())]);
+        writeln!(w, "{indent}{said}")?
+    }
+    Ok(())
+}
+
+impl Formatter for Plain {
+    fn write_results(&mut self, w: &mut dyn Write, run: &RunReport) -> io::Result<()> {
+        let m = Messages::new(self.lang);
+        let simulated = run.results.iter().any(|r| r.source == SIMULATED)
+            || run.failed.iter().any(|f| f.source == SIMULATED);
+        if simulated {
+            writeln!(w, "{}", m.get("simulated-run"))?;
+        }
+        if run.cancelled {
+            writeln!(w, "{}", m.get("run-stopped"))?;
+        }
+        if run.results.is_empty() && run.failed.is_empty() {
+            return writeln!(w, "{}", m.get("nothing-matched"));
+        }
+
+        match self.group_by {
+            None => write_guilds(w, run.results.iter(), "", self, &m)?,
+            Some(by) => {
+                for group in group(by, &run.results) {
+                    let summary = m.with(
+                        "group-summary",
+                        [
+                            ("group", group.key.as_str().into()),
+                            ("total", group.summary.total.into()),
+                            ("compromised", group.summary.compromised.into()),
+                        ],
+                    );
+                    writeln!(w, "{summary}")?;
+                    write_guilds(w, group.results.iter().copied(), "  ", self, &m)?;
+                }
+                // a result with several labels is in each of their groups
+                if by == GroupBy::Label {
+                    let total = GroupSummary::of(&run.results);
+                    let summary = m.with(
+                        "overall-summary",
+                        [
+                            ("total", total.total.into()),
+                            ("compromised", total.compromised.into()),
+                        ],
+                    );
+                    writeln!(w, "{summary}")?;
+                }
+            }
+        }
+        let shared = match is_multi_label(&run.results) {
+            true => shared(&run.results),
+            false => Vec::new(),
+        };
+        if !shared.is_empty() {
+            let mut labels: Vec<&String> = run.results.iter().flat_map(|r| &r.labels).collect();
+            labels.sort();
+            labels.dedup();
+            writeln!(w, "{}", m.get("shared-heading"))?;
+            for guild in shared {
+                let line = m.with(
+                    "shared-guild",
+                    [
+                        ("name", guild.guild_name.as_str().into()),
+                        ("id", guild.guild_id.as_str().into()),
+                        ("count", guild.labels.len().into()),
+                        ("total", labels.len().into()),
+                        ("labels", guild.labels.join(", ").into()),
+                    ],
+                );
+                writeln!(w, "  {line}")?;
+            }
+        }
+        if let Some(prefilter) = run.prefilter.as_ref().filter(|p| p.unlisted > 0) {
+            // to the minute, seconds would only be noise
+            let age = (Utc::now() - prefilter.fetched_at).num_minutes().max(0) as u64;
+            let age = humantime::format_duration(Duration::from_secs(age * 60));
+            let line = m.with(
+                "prefilter-unlisted",
+                [
+                    ("count", prefilter.unlisted.into()),
+                    ("source", prefilter.source.as_str().into()),
+                    ("age", age.to_string().into()),
+                ],
+            );
+            writeln!(w, "{line}")?;
+        }
+        if !run.failed.is_empty() {
+            let header = m.with("failed-heading", [("count", run.failed.len().into())]);
+            writeln!(w, "{}", paint(&header, YELLOW, self.color))?;
+            let lines: Vec<Line> = run
+                .failed
+                .iter()
+                .map(|failed| Line {
+                    subject: named(&m, "subject-guild", &failed.guild_name, &failed.guild_id),
+                    said: m.with(
+                        "failed-on",
+                        [
+                            ("source", failed.source.as_str().into()),
+                            ("message", failed.message.as_str().into()),
+                        ],
+                    ),
+                    below: Vec::new(),
+                })
+                .collect();
+            write_aligned(w, &lines, "  ")?;
+        }
+        Ok(())
+    }
+
+    fn write_changes(&mut self, w: &mut dyn Write, changes: &[Change]) -> io::Result<()> {
+        let m = Messages::new(self.lang);
+        for change in changes {
+            let line = match change {
+                Change::Compromised {
+                    guild_id,
+                    guild_name,
+                    ..
+                } => named(&m, "change-compromised", guild_name, guild_id),
+                Change::Removed {
+                    guild_id,
+                    guild_name,
+                    ..
+                } => named(&m, "change-removed", guild_name, guild_id),
+                Change::MessagesGrew {
+                    guild_id,
+                    guild_name,
+                    from,
+                    to,
+                    ..
+                } => m.with(
+                    "change-messages-grew",
+                    [
+                        ("name", guild_name.as_str().into()),
+                        ("id", guild_id.as_str().into()),
+                        ("from", (*from).into()),
+                        ("to", (*to).into()),
+                    ],
+                ),
+            };
+            writeln!(w, "{line}")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+use std::cmp::{Ordering, Reverse};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Response, RunReport, Status};
+
+/// What results can be put in order by in the output, instead of the order
+/// the checks finished in
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    /// Server ID, oldest server first
+    Id,
+    /// Server name, ignoring case
+    Name,
+    /// Compromised first, then the ones the answer doesn't say either way
+    /// about, then clean
+    Status,
+    /// Most archived messages first
+    Messages,
+    /// Worst exposure first, see [`Severity`](crate::severity::Severity)
+    Severity,
+}
+
+/// Snowflakes by value, so `9` comes before `10`; IDs that aren't numbers
+/// go after them
+fn by_id(a: &str, b: &str) -> Ordering {
+    match (a.parse::<u64>(), b.parse::<u64>()) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        (Ok(_), Err(_)) => Ordering::Less,
+        (Err(_), Ok(_)) => Ordering::Greater,
+        (Err(_), Err(_)) => a.cmp(b),
+    }
+}
+
+fn rank(status: Status) -> u8 {
+    match status {
+        Status::Compromised => 0,
+        Status::Indeterminate => 1,
+        Status::Unparseable => 2,
+        Status::Clean => 3,
+        Status::Unlisted => 4,
+    }
+}
+
+impl SortBy {
+    /// Ties, and results of the same server from several backends, are put
+    /// in ID and then source order, so the same results always come out the
+    /// same way
+    pub fn compare(self, a: &Response, b: &Response) -> Ordering {
+        let first = match self {
+            SortBy::Id => Ordering::Equal,
+            SortBy::Name => a
+                .guild_name
+                .to_lowercase()
+                .cmp(&b.guild_name.to_lowercase()),
+            SortBy::Status => rank(a.status()).cmp(&rank(b.status())),
+            // results without a count go last
+            SortBy::Messages => {
+                let key = |result: &Response| {
+                    let count = result.message_count();
+                    (count.is_none(), Reverse(count))
+                };
+                key(a).cmp(&key(b))
+            }
+            // results that aren't compromised go last
+            SortBy::Severity => {
+                let key = |result: &Response| {
+                    let score = result.severity().map(|severity| severity.score);
+                    (score.is_none(), Reverse(score))
+                };
+                key(a).cmp(&key(b))
+            }
+        };
+        first
+            .then_with(|| by_id(&a.guild_id, &b.guild_id))
+            .then_with(|| a.source.cmp(&b.source))
+    }
+}
+
+/// Puts `results` in `by` order
+pub fn sort_results(results: &mut [Response], by: SortBy) {
+    results.sort_by(|a, b| by.compare(a, b));
+}
+
+/// Puts the results of `run` in `by` order, and the checks that failed in
+/// ID order
+pub fn sort_run(run: &mut RunReport, by: SortBy) {
+    sort_results(&mut run.results, by);
+    run.failed
+        .sort_by(|a, b| by_id(&a.guild_id, &b.guild_id).then_with(|| a.source.cmp(&b.source)));
+}
@@ -0,0 +1,651 @@
+use std::cmp::Ordering;
+use std::io::{self, Write};
+
+use chrono::{SecondsFormat, Utc};
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use super::group::GroupSummary;
+use super::Formatter;
+use crate::watch::Change;
+use crate::RunReport;
+
+/// A syntax or render error, at a line of the template
+#[derive(Error, Debug)]
+#[error("line {line}: {message}")]
+pub struct OutputTemplateError {
+    pub line: usize,
+    pub message: String,
+}
+
+fn error(line: usize, message: impl Into<String>) -> OutputTemplateError {
+    OutputTemplateError {
+        line,
+        message: message.into(),
+    }
+}
+
+/// A template for the results, in the part of the Tera/Jinja syntax reports
+/// need: `{{ guild.guild_name | upper }}` prints a value, `{% for %}` and
+/// `{% if %}`/`{% elif %}`/`{% else %}` control what's printed, `{# #}` is a
+/// comment, and a `-` just inside a tag's braces (`{%-`, `-}}`) trims the
+/// whitespace on that side of it.
+///
+/// Values that don't exist print as nothing, so one template works for
+/// answers with different fields.
+pub struct OutputTemplate {
+    nodes: Vec<Node>,
+}
+
+enum Token {
+    Text(String),
+    Print(String, usize),
+    Tag(String, usize),
+}
+
+enum Node {
+    Text(String),
+    Print(Expr),
+    For {
+        var: String,
+        list: Expr,
+        body: Vec<Node>,
+        line: usize,
+    },
+    If {
+        cond: Cond,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+}
+
+enum Operand {
+    Path(Vec<String>),
+    Literal(Value),
+}
+
+enum Filter {
+    Length,
+    Upper,
+    Lower,
+    Trim,
+    Json,
+    Join(String),
+    Default(Value),
+    Pad(usize),
+}
+
+struct Expr {
+    operand: Operand,
+    filters: Vec<Filter>,
+}
+
+#[derive(Clone, Copy)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+struct Cond {
+    negate: bool,
+    left: Expr,
+    compare: Option<(Op, Expr)>,
+}
+
+fn lex(source: &str) -> Result<Vec<Token>, OutputTemplateError> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+    let mut line = 1;
+    let mut trim_start = false;
+    loop {
+        let open = rest
+            .match_indices('{')
+            .map(|(i, _)| i)
+            .find(|&i| matches!(rest.as_bytes().get(i + 1), Some(b'{' | b'%' | b'#')));
+        let (text, tail) = rest.split_at(open.unwrap_or(rest.len()));
+        let mut trimmed = match trim_start {
+            true => text.trim_start(),
+            false => text,
+        };
+        if open.is_none() {
+            if !trimmed.is_empty() {
+                tokens.push(Token::Text(trimmed.to_owned()));
+            }
+            return Ok(tokens);
+        }
+        line += text.matches('\n').count();
+
+        let kind = tail.as_bytes()[1];
+        let close = match kind {
+            b'{' => "}}",
+            b'%' => "%}",
+            _ => "#}",
+        };
+        let end = tail[2..]
+            .find(close)
+            .ok_or_else(|| error(line, format!("`{}` is never closed", &tail[..2])))?
+            + 2;
+        let mut inner = &tail[2..end];
+        if let Some(after) = inner.strip_prefix('-') {
+            trimmed = trimmed.trim_end();
+            inner = after;
+        }
+        trim_start = false;
+        if let Some(before) = inner.strip_suffix('-') {
+            trim_start = true;
+            inner = before;
+        }
+        if !trimmed.is_empty() {
+            tokens.push(Token::Text(trimmed.to_owned()));
+        }
+        match kind {
+            b'{' => tokens.push(Token::Print(inner.trim().to_owned(), line)),
+            b'%' => tokens.push(Token::Tag(inner.trim().to_owned(), line)),
+            _ => {}
+        }
+        line += inner.matches('\n').count();
+        rest = &tail[end + 2..];
+    }
+}
+
+/// Splits `s` at `sep`s that aren't inside a quoted string
+fn split_unquoted<'a>(s: &'a str, sep: &str) -> Vec<&'a str> {
+    let mut parts = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    let mut i = 0;
+    while i < s.len() {
+        let c = s[i..].chars().next().expect("in bounds");
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if s[i..].starts_with(sep) => {
+                parts.push(&s[start..i]);
+                i += sep.len();
+                start = i;
+                continue;
+            }
+            None => {}
+        }
+        i += c.len_utf8();
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+fn literal(s: &str) -> Option<Value> {
+    let quoted = ['"', '\''].into_iter().find_map(|q| {
+        s.strip_prefix(q)
+            .and_then(|s| s.strip_suffix(q))
+            .filter(|inner| !inner.contains(q))
+    });
+    if let Some(inner) = quoted {
+        return Some(Value::String(inner.to_owned()));
+    }
+    match s {
+        "true" => Some(Value::Bool(true)),
+        "false" => Some(Value::Bool(false)),
+        "null" | "none" => Some(Value::Null),
+        s if s.starts_with(|c: char| c.is_ascii_digit() || c == '-') => {
+            serde_json::from_str::<serde_json::Number>(s)
+                .ok()
+                .map(Value::Number)
+        }
+        _ => None,
+    }
+}
+
+fn is_name(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl Operand {
+    fn parse(s: &str, line: usize) -> Result<Self, OutputTemplateError> {
+        let s = s.trim();
+        if let Some(value) = literal(s) {
+            return Ok(Self::Literal(value));
+        }
+        let path: Vec<String> = s.split('.').map(str::to_owned).collect();
+        let valid = is_name(&path[0])
+            && path[1..]
+                .iter()
+                .all(|part| is_name(part) || part.parse::<usize>().is_ok());
+        match valid {
+            true => Ok(Self::Path(path)),
+            false => Err(error(line, format!("`{s}` isn't a name or a value"))),
+        }
+    }
+}
+
+impl Filter {
+    fn parse(s: &str, line: usize) -> Result<Self, OutputTemplateError> {
+        let s = s.trim();
+        let (name, arg) = match s.split_once('(') {
+            Some((name, rest)) => {
+                let arg = rest
+                    .strip_suffix(')')
+                    .ok_or_else(|| error(line, format!("`{s}` is missing a `)`")))?;
+                let arg = literal(arg.trim())
+                    .ok_or_else(|| error(line, format!("`{arg}` isn't a value")))?;
+                (name.trim(), Some(arg))
+            }
+            None => (s, None),
+        };
+        let filter = match (name, arg) {
+            ("length", None) => Self::Length,
+            ("upper", None) => Self::Upper,
+            ("lower", None) => Self::Lower,
+            ("trim", None) => Self::Trim,
+            ("json", None) => Self::Json,
+            ("join", None) => Self::Join(", ".to_owned()),
+            ("join", Some(Value::String(sep))) => Self::Join(sep),
+            ("default", Some(value)) => Self::Default(value),
+            ("pad", Some(Value::Number(n))) if n.as_u64().is_some() => {
+                Self::Pad(n.as_u64().expect("checked") as usize)
+            }
+            ("length" | "upper" | "lower" | "trim" | "json" | "join" | "default" | "pad", _) => {
+                return Err(error(line, format!("wrong argument for `{name}`")))
+            }
+            _ => return Err(error(line, format!("unknown filter `{name}`"))),
+        };
+        Ok(filter)
+    }
+
+    fn apply(&self, value: Value) -> Value {
+        match self {
+            Self::Length => {
+                let n = match &value {
+                    Value::Array(items) => items.len(),
+                    Value::Object(fields) => fields.len(),
+                    Value::String(s) => s.chars().count(),
+                    _ => 0,
+                };
+                Value::from(n)
+            }
+            Self::Upper => Value::String(display(&value).to_uppercase()),
+            Self::Lower => Value::String(display(&value).to_lowercase()),
+            Self::Trim => Value::String(display(&value).trim().to_owned()),
+            Self::Json => Value::String(value.to_string()),
+            Self::Join(sep) => match &value {
+                Value::Array(items) => {
+                    let items: Vec<String> = items.iter().map(display).collect();
+                    Value::String(items.join(sep))
+                }
+                _ => value,
+            },
+            Self::Default(default) => match truthy(&value) {
+                true => value,
+                false => default.clone(),
+            },
+            Self::Pad(width) => Value::String(format!("{:width$}", display(&value))),
+        }
+    }
+}
+
+impl Expr {
+    fn parse(s: &str, line: usize) -> Result<Self, OutputTemplateError> {
+        let mut parts = split_unquoted(s, "|").into_iter();
+        let operand = Operand::parse(parts.next().unwrap_or_default(), line)?;
+        let filters = parts
+            .map(|filter| Filter::parse(filter, line))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { operand, filters })
+    }
+
+    fn eval(&self, scope: &Scope) -> Value {
+        let value = match &self.operand {
+            Operand::Literal(value) => value.clone(),
+            Operand::Path(path) => scope.lookup(path),
+        };
+        self.filters
+            .iter()
+            .fold(value, |value, filter| filter.apply(value))
+    }
+}
+
+impl Cond {
+    fn parse(s: &str, line: usize) -> Result<Self, OutputTemplateError> {
+        let s = s.trim();
+        let (negate, s) = match s.strip_prefix("not ") {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        // longest first, so `<=` isn't read as `<`
+        let ops = [
+            ("==", Op::Eq),
+            ("!=", Op::Ne),
+            ("<=", Op::Le),
+            (">=", Op::Ge),
+            ("<", Op::Lt),
+            (">", Op::Gt),
+        ];
+        for (token, op) in ops {
+            let parts = split_unquoted(s, token);
+            if parts.len() == 2 {
+                return Ok(Self {
+                    negate,
+                    left: Expr::parse(parts[0], line)?,
+                    compare: Some((op, Expr::parse(parts[1], line)?)),
+                });
+            }
+        }
+        Ok(Self {
+            negate,
+            left: Expr::parse(s, line)?,
+            compare: None,
+        })
+    }
+
+    fn eval(&self, scope: &Scope) -> bool {
+        let left = self.left.eval(scope);
+        let holds = match &self.compare {
+            None => truthy(&left),
+            Some((op, right)) => {
+                let order = compare(&left, &right.eval(scope));
+                match op {
+                    Op::Eq => order == Some(Ordering::Equal),
+                    Op::Ne => order != Some(Ordering::Equal),
+                    Op::Lt => order == Some(Ordering::Less),
+                    Op::Le => matches!(order, Some(Ordering::Less | Ordering::Equal)),
+                    Op::Gt => order == Some(Ordering::Greater),
+                    Op::Ge => matches!(order, Some(Ordering::Greater | Ordering::Equal)),
+                }
+            }
+        };
+        holds != self.negate
+    }
+}
+
+/// Numbers by value and strings by text; anything else is only ever equal
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (a, b) if a == b => Some(Ordering::Equal),
+        _ => None,
+    }
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64() != Some(0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(fields) => !fields.is_empty(),
+    }
+}
+
+/// How a value prints: strings as they are, nothing for null, JSON for
+/// arrays and objects
+fn display(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// The tag that ended a block: its first word, the rest of it, and its line
+struct End(String, String, usize);
+
+/// Parses the nodes up to one of the tags in `ends`, which is returned too
+fn parse_block(
+    tokens: &mut std::vec::IntoIter<Token>,
+    ends: &[&str],
+) -> Result<(Vec<Node>, Option<End>), OutputTemplateError> {
+    let mut nodes = Vec::new();
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Text(text) => nodes.push(Node::Text(text)),
+            Token::Print(expr, line) => nodes.push(Node::Print(Expr::parse(&expr, line)?)),
+            Token::Tag(tag, line) => {
+                let (word, rest) = tag
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((tag.as_str(), ""));
+                let rest = rest.trim();
+                if ends.contains(&word) {
+                    return Ok((nodes, Some(End(word.to_owned(), rest.to_owned(), line))));
+                }
+                match word {
+                    "for" => {
+                        let (var, list) = rest
+                            .split_once(" in ")
+                            .ok_or_else(|| error(line, "expected `for NAME in LIST`"))?;
+                        let var = var.trim();
+                        if !is_name(var) || var == "loop" {
+                            return Err(error(line, format!("`{var}` can't be a loop variable")));
+                        }
+                        let list = Expr::parse(list, line)?;
+                        let (body, end) = parse_block(tokens, &["endfor"])?;
+                        if end.is_none() {
+                            return Err(error(line, "`for` without an `endfor`"));
+                        }
+                        nodes.push(Node::For {
+                            var: var.to_owned(),
+                            list,
+                            body,
+                            line,
+                        });
+                    }
+                    "if" => nodes.push(parse_if(tokens, rest, line)?),
+                    "" => return Err(error(line, "empty tag")),
+                    word => return Err(error(line, format!("unexpected `{word}`"))),
+                }
+            }
+        }
+    }
+    Ok((nodes, None))
+}
+
+/// An `if` from its condition on, through its `endif`
+fn parse_if(
+    tokens: &mut std::vec::IntoIter<Token>,
+    cond: &str,
+    line: usize,
+) -> Result<Node, OutputTemplateError> {
+    let cond = Cond::parse(cond, line)?;
+    let (then, end) = parse_block(tokens, &["elif", "else", "endif"])?;
+    let otherwise = match end {
+        None => return Err(error(line, "`if` without an `endif`")),
+        Some(End(word, _, _)) if word == "endif" => Vec::new(),
+        Some(End(word, _, line)) if word == "else" => {
+            let (otherwise, end) = parse_block(tokens, &["endif"])?;
+            if end.is_none() {
+                return Err(error(line, "`else` without an `endif`"));
+            }
+            otherwise
+        }
+        // the nested `if` takes the `endif`
+        Some(End(_, cond, line)) => vec![parse_if(tokens, &cond, line)?],
+    };
+    Ok(Node::If {
+        cond,
+        then,
+        otherwise,
+    })
+}
+
+struct Scope<'a> {
+    root: &'a Value,
+    /// Loop variables, innermost last
+    vars: Vec<(String, Value)>,
+}
+
+impl Scope<'_> {
+    fn lookup(&self, path: &[String]) -> Value {
+        let start = self
+            .vars
+            .iter()
+            .rev()
+            .find(|(name, _)| *name == path[0])
+            .map(|(_, value)| value)
+            .or_else(|| self.root.get(&path[0]));
+        let mut current = match start {
+            Some(value) => value,
+            None => return Value::Null,
+        };
+        for part in &path[1..] {
+            let next = match (current, part.parse::<usize>()) {
+                (Value::Array(items), Ok(i)) => items.get(i),
+                (value, _) => value.get(part),
+            };
+            current = match next {
+                Some(value) => value,
+                None => return Value::Null,
+            };
+        }
+        current.clone()
+    }
+
+    fn render(&mut self, nodes: &[Node], out: &mut String) -> Result<(), OutputTemplateError> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => out.push_str(text),
+                Node::Print(expr) => out.push_str(&display(&expr.eval(self))),
+                Node::If {
+                    cond,
+                    then,
+                    otherwise,
+                } => match cond.eval(self) {
+                    true => self.render(then, out)?,
+                    false => self.render(otherwise, out)?,
+                },
+                Node::For {
+                    var,
+                    list,
+                    body,
+                    line,
+                } => {
+                    let items = match list.eval(self) {
+                        Value::Array(items) => items,
+                        Value::Null => Vec::new(),
+                        value => {
+                            return Err(error(
+                                *line,
+                                format!("can't loop over {}", display(&value)),
+                            ))
+                        }
+                    };
+                    let length = items.len();
+                    for (i, item) in items.into_iter().enumerate() {
+                        let info = json!({
+                            "index": i + 1,
+                            "index0": i,
+                            "first": i == 0,
+                            "last": i + 1 == length,
+                            "length": length,
+                        });
+                        self.vars.push(("loop".to_owned(), info));
+                        self.vars.push((var.clone(), item));
+                        let rendered = self.render(body, out);
+                        self.vars.truncate(self.vars.len() - 2);
+                        rendered?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl OutputTemplate {
+    pub fn parse(source: &str) -> Result<Self, OutputTemplateError> {
+        let mut tokens = lex(source)?.into_iter();
+        let (nodes, end) = parse_block(&mut tokens, &[])?;
+        debug_assert!(end.is_none());
+        Ok(Self { nodes })
+    }
+
+    /// The template filled in from `context`, an object
+    pub fn render(&self, context: &Value) -> Result<String, OutputTemplateError> {
+        let mut out = String::new();
+        Scope {
+            root: context,
+            vars: Vec::new(),
+        }
+        .render(&self.nodes, &mut out)?;
+        Ok(out)
+    }
+}
+
+/// What a template gets to see of a run:
+///
+/// - `results`: every result as in the JSON output, each with its `status`,
+///   a `compromised` boolean, the compromised ones' `severity` and, when
+///   the answer has fields, their [`GuildInfo`](crate::answer::GuildInfo)
+///   as `info`
+/// - `compromised`: the compromised ones only
+/// - `failed`: the checks that failed
+/// - `summary`: `total`, `compromised`, `clean`, `indeterminate`,
+///   `unparseable`, `unlisted` and `failed` counts
+/// - `cancelled`, whether the run stopped early, and `generated_at`
+pub fn template_context(run: &RunReport) -> io::Result<Value> {
+    let mut results = Vec::with_capacity(run.results.len());
+    for result in &run.results {
+        let mut value = serde_json::to_value(result)?;
+        value["status"] = Value::from(result.status().as_str());
+        value["compromised"] = Value::Bool(result.is_compromised());
+        if let Some(severity) = result.severity() {
+            value["severity"] = serde_json::to_value(severity)?;
+        }
+        if let Some(info) = result.guild_info() {
+            value["info"] = serde_json::to_value(info)?;
+        }
+        results.push(value);
+    }
+    let compromised: Vec<Value> = results
+        .iter()
+        .filter(|result| result["compromised"] == true)
+        .cloned()
+        .collect();
+    let summary = GroupSummary::of(&run.results);
+    let clean = summary.total
+        - summary.compromised
+        - summary.indeterminate
+        - summary.unparseable
+        - summary.unlisted;
+    Ok(json!({
+        "results": results,
+        "compromised": compromised,
+        "failed": run.failed,
+        "summary": {
+            "total": summary.total,
+            "compromised": summary.compromised,
+            "clean": clean,
+            "indeterminate": summary.indeterminate,
+            "unparseable": summary.unparseable,
+            "unlisted": summary.unlisted,
+            "failed": run.failed.len(),
+        },
+        "cancelled": run.cancelled,
+        "generated_at": Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+    }))
+}
+
+/// Writes the results the way `--template` has them
+pub struct Templated {
+    pub template: OutputTemplate,
+}
+
+impl Formatter for Templated {
+    fn write_results(&mut self, w: &mut dyn Write, run: &RunReport) -> io::Result<()> {
+        let text = self
+            .template
+            .render(&template_context(run)?)
+            .map_err(io::Error::other)?;
+        w.write_all(text.as_bytes())
+    }
+
+    /// Watch mode has its own output, the template is only for results
+    fn write_changes(&mut self, _: &mut dyn Write, _: &[Change]) -> io::Result<()> {
+        Ok(())
+    }
+}
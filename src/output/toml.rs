@@ -0,0 +1,53 @@
+use std::io::{self, Write};
+
+use serde_json::Value;
+
+use super::{document, Formatter};
+use crate::watch::Change;
+use crate::RunReport;
+
+/// The results as a TOML document, a `[[results]]` table each (and
+/// `[[failed]]` ones for the checks that failed)
+#[derive(Default)]
+pub struct Toml;
+
+/// Renders `value`, which has to be an object, as TOML. TOML has no null,
+/// so null fields are left out and null array items dropped.
+pub fn to_toml(value: &Value) -> io::Result<String> {
+    ::toml::to_string(&without_nulls(value.clone())).map_err(io::Error::other)
+}
+
+fn without_nulls(value: Value) -> Value {
+    match value {
+        Value::Object(fields) => Value::Object(
+            fields
+                .into_iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(name, value)| (name, without_nulls(value)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .filter(|value| !value.is_null())
+                .map(without_nulls)
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+impl Formatter for Toml {
+    fn write_results(&mut self, w: &mut dyn Write, run: &RunReport) -> io::Result<()> {
+        w.write_all(to_toml(&document(run)?)?.as_bytes())
+    }
+
+    /// A `[[changes]]` table per change, so the cycles add up to one array
+    fn write_changes(&mut self, w: &mut dyn Write, changes: &[Change]) -> io::Result<()> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let changes = serde_json::json!({ "changes": changes });
+        writeln!(w, "{}", to_toml(&changes)?)
+    }
+}
@@ -0,0 +1,110 @@
+use std::io::{self, Write};
+
+use serde_json::Value;
+
+use super::{document, Formatter};
+use crate::watch::Change;
+use crate::RunReport;
+
+/// The results as a YAML mapping, under `results` (and `failed`, for the
+/// checks that failed)
+#[derive(Default)]
+pub struct Yaml;
+
+/// Renders `value` as block-style YAML. Strings are always double-quoted,
+/// so none of them can be read back as a number, a boolean or a tag.
+pub fn to_yaml(value: &Value) -> String {
+    let mut out = String::new();
+    let lines = match inline(value) {
+        Some(scalar) => vec![scalar],
+        None => block(value),
+    };
+    for line in lines {
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+fn quote(s: &str) -> String {
+    serde_json::to_string(s).expect("strings serialize")
+}
+
+/// A mapping key, quoted unless it's a plain word YAML can't misread
+fn key(key: &str) -> String {
+    let plain = key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    let reserved = matches!(
+        key.to_ascii_lowercase().as_str(),
+        "true" | "false" | "null" | "yes" | "no" | "on" | "off" | "y" | "n"
+    );
+    match plain && !reserved {
+        true => key.to_owned(),
+        false => quote(key),
+    }
+}
+
+/// `value` on one line, if it fits there
+fn inline(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => Some("null".to_owned()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::String(s) => Some(quote(s)),
+        Value::Array(items) if items.is_empty() => Some("[]".to_owned()),
+        Value::Object(fields) if fields.is_empty() => Some("{}".to_owned()),
+        Value::Array(_) | Value::Object(_) => None,
+    }
+}
+
+/// The lines of a non-empty mapping or sequence, unindented
+fn block(value: &Value) -> Vec<String> {
+    let mut lines = Vec::new();
+    match value {
+        Value::Object(fields) => {
+            for (name, value) in fields {
+                match inline(value) {
+                    Some(scalar) => lines.push(format!("{}: {scalar}", key(name))),
+                    None => {
+                        lines.push(format!("{}:", key(name)));
+                        lines.extend(block(value).into_iter().map(|line| format!("  {line}")));
+                    }
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                match inline(item) {
+                    Some(scalar) => lines.push(format!("- {scalar}")),
+                    None => {
+                        for (i, line) in block(item).into_iter().enumerate() {
+                            match i {
+                                0 => lines.push(format!("- {line}")),
+                                _ => lines.push(format!("  {line}")),
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        scalar => lines.extend(inline(scalar)),
+    }
+    lines
+}
+
+impl Formatter for Yaml {
+    fn write_results(&mut self, w: &mut dyn Write, run: &RunReport) -> io::Result<()> {
+        w.write_all(to_yaml(&document(run)?).as_bytes())
+    }
+
+    /// A sequence item per change, so the cycles add up to one sequence
+    fn write_changes(&mut self, w: &mut dyn Write, changes: &[Change]) -> io::Result<()> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let changes = serde_json::to_value(changes)?;
+        w.write_all(to_yaml(&changes).as_bytes())
+    }
+}
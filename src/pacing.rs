@@ -0,0 +1,294 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tokio::time::Instant;
+use tracing::{debug, info};
+
+/// How often a cooldown logs the time it has left
+const LOG_EVERY: Duration = Duration::from_secs(60);
+
+#[derive(Default)]
+struct State {
+    /// Requests of the current batch that have finished
+    finished: usize,
+    /// Requests waiting for the next batch
+    waiting: usize,
+    cooling_until: Option<Instant>,
+    /// Time spent in finished cooldowns
+    cooled: Duration,
+}
+
+/// Sends requests in batches with rests in between: `size` requests, then
+/// nothing until `cooldown` after the last of them finished, and so on.
+///
+/// Every request counts toward its batch, so a check that's tried again takes
+/// another place in one. Checks answered from the cache don't count.
+pub struct Batches {
+    size: usize,
+    cooldown: Duration,
+    budget: Semaphore,
+    state: Mutex<State>,
+}
+
+impl Batches {
+    pub fn new(size: usize, cooldown: Duration) -> Arc<Self> {
+        let size = size.max(1);
+        Arc::new(Self {
+            size,
+            cooldown,
+            budget: Semaphore::new(size),
+            state: Mutex::default(),
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn cooldown(&self) -> Duration {
+        self.cooldown
+    }
+
+    /// Waits for a place in the current batch, before a request
+    pub(crate) async fn start(&self) {
+        self.state.lock().expect("batch lock poisoned").waiting += 1;
+        let permit = self.budget.acquire().await;
+        self.state.lock().expect("batch lock poisoned").waiting -= 1;
+        // given back for the next batch, all at once
+        permit.expect("budget is never closed").forget();
+    }
+
+    /// After a request, starts the cooldown if it was the batch's last one
+    pub(crate) fn finish(self: &Arc<Self>) {
+        let mut state = self.state.lock().expect("batch lock poisoned");
+        state.finished += 1;
+        if state.finished < self.size {
+            return;
+        }
+        state.finished = 0;
+        // nothing left to send, so nothing to rest for
+        if state.waiting == 0 {
+            drop(state);
+            self.budget.add_permits(self.size);
+            return;
+        }
+        let until = Instant::now() + self.cooldown;
+        state.cooling_until = Some(until);
+        drop(state);
+        info!(
+            "batch of {} done, cooling down for {}",
+            self.size,
+            humantime::format_duration(self.cooldown)
+        );
+        tokio::spawn(Arc::clone(self).cool_down(until));
+    }
+
+    async fn cool_down(self: Arc<Self>, until: Instant) {
+        loop {
+            let now = Instant::now();
+            if now >= until {
+                break;
+            }
+            tokio::time::sleep_until(until.min(now + LOG_EVERY)).await;
+            if let Some(left) = until
+                .checked_duration_since(Instant::now())
+                .filter(|d| !d.is_zero())
+            {
+                info!("cooling down, {} left", whole_seconds(left));
+            }
+        }
+        let mut state = self.state.lock().expect("batch lock poisoned");
+        state.cooling_until = None;
+        state.cooled += self.cooldown;
+        drop(state);
+        info!("cooldown over, starting the next batch");
+        self.budget.add_permits(self.size);
+    }
+
+    /// How long the current cooldown has left, if one is going on
+    pub fn cooling_left(&self) -> Option<Duration> {
+        let state = self.state.lock().expect("batch lock poisoned");
+        let until = state.cooling_until?;
+        Some(until.saturating_duration_since(Instant::now()))
+    }
+
+    /// Time spent cooling down so far, including the current cooldown
+    pub fn cooled(&self) -> Duration {
+        let state = self.state.lock().expect("batch lock poisoned");
+        let current = state.cooling_until.map_or(Duration::ZERO, |until| {
+            self.cooldown
+                .saturating_sub(until.saturating_duration_since(Instant::now()))
+        });
+        state.cooled + current
+    }
+
+    /// The cooldowns still to come before `requests` more have been sent,
+    /// including what's left of the current one
+    pub fn cooldowns_left(&self, requests: usize) -> Duration {
+        let state = self.state.lock().expect("batch lock poisoned");
+        let now = Instant::now();
+        let (current, later) = match state.cooling_until {
+            // the first of them goes out once this cooldown is over
+            Some(until) => (
+                until.saturating_duration_since(now),
+                requests.div_ceil(self.size).saturating_sub(1),
+            ),
+            None => {
+                let rest_of_batch = self.size - state.finished;
+                let after = requests.saturating_sub(rest_of_batch);
+                (Duration::ZERO, after.div_ceil(self.size))
+            }
+        };
+        current + self.cooldown * later as u32
+    }
+}
+
+/// Overload signals this close together count as one, since the requests in
+/// flight when the API started struggling all come back with one
+const CUT_SPACING: Duration = Duration::from_secs(1);
+
+struct Limit {
+    current: usize,
+    lowest: usize,
+    /// Permits to take out of circulation as they come back, from a cut made
+    /// while they were in use
+    debt: usize,
+    /// Healthy answers since the limit last changed
+    healthy: usize,
+    last_cut: Option<Instant>,
+}
+
+/// A concurrency limit that follows how the API copes: halved when it answers
+/// 429 or 5xx, down to one, and raised by one after as many healthy answers
+/// in a row as the limit, back up to the maximum it started at.
+pub struct AdaptiveConcurrency {
+    max: usize,
+    semaphore: Semaphore,
+    limit: Mutex<Limit>,
+}
+
+impl AdaptiveConcurrency {
+    pub fn new(max: usize) -> Arc<Self> {
+        let max = max.max(1);
+        Arc::new(Self {
+            max,
+            semaphore: Semaphore::new(max),
+            limit: Mutex::new(Limit {
+                current: max,
+                lowest: max,
+                debt: 0,
+                healthy: 0,
+                last_cut: None,
+            }),
+        })
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    /// The limit right now
+    pub fn current(&self) -> usize {
+        self.limit.lock().expect("limit lock poisoned").current
+    }
+
+    /// The lowest the limit has been
+    pub fn lowest(&self) -> usize {
+        self.limit.lock().expect("limit lock poisoned").lowest
+    }
+
+    /// Waits for a place under the limit, before a request. Places given
+    /// back after a cut are taken out of circulation on the way.
+    pub(crate) async fn acquire(&self) -> SemaphorePermit<'_> {
+        loop {
+            let permit = self.semaphore.acquire().await;
+            let permit = permit.expect("semaphore is never closed");
+            let mut limit = self.limit.lock().expect("limit lock poisoned");
+            if limit.debt == 0 {
+                return permit;
+            }
+            limit.debt -= 1;
+            permit.forget();
+        }
+    }
+
+    /// After a request: `overloaded` if the answer was a 429 or a 5xx
+    pub(crate) fn record(&self, overloaded: bool) {
+        let mut limit = self.limit.lock().expect("limit lock poisoned");
+        if overloaded {
+            limit.healthy = 0;
+            let recent = limit.last_cut.is_some_and(|at| at.elapsed() < CUT_SPACING);
+            if recent || limit.current == 1 {
+                return;
+            }
+            let cut = limit.current - limit.current / 2;
+            limit.current -= cut;
+            limit.lowest = limit.lowest.min(limit.current);
+            limit.last_cut = Some(Instant::now());
+            limit.debt += cut - self.semaphore.forget_permits(cut);
+            info!(
+                "API is struggling, lowering concurrency to {}",
+                limit.current
+            );
+            return;
+        }
+        limit.healthy += 1;
+        if limit.current == self.max || limit.healthy < limit.current {
+            return;
+        }
+        limit.healthy = 0;
+        limit.current += 1;
+        match limit.debt {
+            0 => self.semaphore.add_permits(1),
+            _ => limit.debt -= 1,
+        }
+        debug!("raising concurrency to {}", limit.current);
+    }
+}
+
+/// Keeps requests apart: each one starts at least `delay` after the one
+/// before it, plus a random part of `jitter`, however many are in flight.
+pub struct Spacing {
+    delay: Duration,
+    jitter: Duration,
+    /// When the next request may start
+    next: Mutex<Option<Instant>>,
+}
+
+impl Spacing {
+    pub fn new(delay: Duration, jitter: Duration) -> Arc<Self> {
+        Arc::new(Self {
+            delay,
+            jitter,
+            next: Mutex::default(),
+        })
+    }
+
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    pub fn jitter(&self) -> Duration {
+        self.jitter
+    }
+
+    /// Waits for this request's turn, before sending it. The turn is taken
+    /// right away, so the requests waiting go out in order.
+    pub(crate) async fn wait(&self) {
+        let at = {
+            let mut next = self.next.lock().expect("spacing lock poisoned");
+            let now = Instant::now();
+            let at = next.map_or(now, |next| next.max(now));
+            let jitter = self.jitter.mul_f64(fastrand::f64());
+            *next = Some(at + self.delay + jitter);
+            at
+        };
+        tokio::time::sleep_until(at).await;
+    }
+}
+
+/// `d` without the fractions of a second, for logs
+fn whole_seconds(d: Duration) -> humantime::FormattedDuration {
+    humantime::format_duration(Duration::from_secs(d.as_secs()))
+}
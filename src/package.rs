@@ -0,0 +1,304 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{self, bail, Context};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+use spy_pet_checker::warnings::{Condition, Warnings};
+use tracing::debug;
+
+use crate::zip::ZipArchive;
+
+/// Discord's relationship type for friends, as opposed to blocked users and
+/// pending requests
+const FRIEND: u8 = 1;
+
+/// The part of `account/user.json` we care about
+#[derive(Deserialize)]
+struct Account {
+    id: String,
+    #[serde(default)]
+    relationships: Vec<Relationship>,
+}
+
+#[derive(Deserialize)]
+struct Relationship {
+    id: String,
+    #[serde(rename = "type")]
+    kind: u8,
+    user: Option<RelationshipUser>,
+}
+
+#[derive(Deserialize)]
+struct RelationshipUser {
+    username: String,
+    global_name: Option<String>,
+}
+
+/// `messages/<channel>/channel.json`
+#[derive(Deserialize)]
+struct Channel {
+    id: String,
+    /// `1` or `"DM"` in direct messages, `3` or `"GROUP_DM"` in groups
+    #[serde(rename = "type")]
+    kind: Value,
+    #[serde(default)]
+    recipients: Vec<String>,
+}
+
+impl Channel {
+    fn is_dm(&self) -> bool {
+        match &self.kind {
+            Value::Number(n) => matches!(n.as_u64(), Some(1 | 3)),
+            Value::String(s) => s == "DM" || s == "GROUP_DM",
+            _ => false,
+        }
+    }
+}
+
+/// An extracted data package, or the zip file Discord sends it as
+enum Package {
+    Dir(PathBuf),
+    Zip {
+        path: PathBuf,
+        archive: ZipArchive,
+        /// Where the package starts in the archive: nowhere, or in the one
+        /// directory a re-zipped package was put in
+        prefix: String,
+    },
+}
+
+/// Newer packages capitalize their directories (`Servers`, `Messages`,
+/// `Account`)
+fn spellings(name: &str) -> [String; 2] {
+    let mut capitalized = name.to_owned();
+    capitalized[..1].make_ascii_uppercase();
+    [name.to_owned(), capitalized]
+}
+
+impl Package {
+    fn open(root: &Path) -> eyre::Result<Self> {
+        if root.is_dir() {
+            return Ok(Package::Dir(root.to_owned()));
+        }
+        if !root
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+        {
+            bail!("{} isn't a directory or a zip file", root.display());
+        }
+        let archive = ZipArchive::open(root)
+            .with_context(|| format!("couldn't read zip file {}", root.display()))?;
+        let prefix = archive
+            .names()
+            .find_map(|name| {
+                spellings("servers").into_iter().find_map(|servers| {
+                    let prefix = name.strip_suffix(&format!("{servers}/index.json"))?;
+                    // at the root, or in one directory
+                    let depth = prefix.matches('/').count();
+                    (depth == 0 && prefix.is_empty() || depth == 1 && prefix.ends_with('/'))
+                        .then(|| prefix.to_owned())
+                })
+            })
+            .unwrap_or_default();
+        debug!(prefix, "reading the data package from the zip file");
+        Ok(Package::Zip {
+            path: root.to_owned(),
+            archive,
+            prefix,
+        })
+    }
+
+    /// How section `name` is spelled in this package, if it has it
+    fn section(&self, name: &str) -> Option<String> {
+        spellings(name).into_iter().find(|dir| match self {
+            Package::Dir(root) => root.join(dir).is_dir(),
+            Package::Zip {
+                archive, prefix, ..
+            } => {
+                let dir = format!("{prefix}{dir}/");
+                archive.names().any(|name| name.starts_with(&dir))
+            }
+        })
+    }
+
+    /// Where `file` of section `name` would be, for messages
+    fn describe(&self, section: &str, file: &str) -> String {
+        match self {
+            Package::Dir(root) => root.join(section).join(file).display().to_string(),
+            Package::Zip { path, prefix, .. } => {
+                format!("{}:{prefix}{section}/{file}", path.display())
+            }
+        }
+    }
+
+    /// Parses `file` in section `name`. `None` if the package doesn't have
+    /// it.
+    fn read_json<T: DeserializeOwned>(
+        &mut self,
+        name: &str,
+        file: &str,
+    ) -> eyre::Result<Option<T>> {
+        let Some(section) = self.section(name) else {
+            return Ok(None);
+        };
+        let describe = self.describe(&section, file);
+        match self {
+            Package::Dir(root) => {
+                let path = root.join(&section).join(file);
+                if !path.is_file() {
+                    return Ok(None);
+                }
+                let file =
+                    File::open(&path).with_context(|| format!("couldn't open {describe}"))?;
+                serde_json::from_reader(BufReader::new(file))
+                    .with_context(|| format!("couldn't parse {describe}"))
+                    .map(Some)
+            }
+            Package::Zip {
+                archive, prefix, ..
+            } => {
+                let bytes = archive
+                    .read(&format!("{prefix}{section}/{file}"))
+                    .with_context(|| format!("couldn't read {describe}"))?;
+                match bytes {
+                    Some(bytes) => serde_json::from_slice(&bytes)
+                        .with_context(|| format!("couldn't parse {describe}"))
+                        .map(Some),
+                    None => Ok(None),
+                }
+            }
+        }
+    }
+
+    /// The `<channel>/channel.json` files of section `name`, relative to it
+    fn channel_files(&self, section: &str) -> eyre::Result<Vec<String>> {
+        match self {
+            Package::Dir(root) => {
+                let dir = root.join(section);
+                let entries = std::fs::read_dir(&dir)
+                    .with_context(|| format!("couldn't read directory {}", dir.display()))?;
+                let mut files = Vec::new();
+                for entry in entries {
+                    let entry = entry?;
+                    if entry.path().join("channel.json").is_file() {
+                        let channel = entry.file_name().to_string_lossy().into_owned();
+                        files.push(format!("{channel}/channel.json"));
+                    }
+                }
+                Ok(files)
+            }
+            Package::Zip {
+                archive, prefix, ..
+            } => {
+                let dir = format!("{prefix}{section}/");
+                Ok(archive
+                    .names()
+                    .filter_map(|name| name.strip_prefix(&dir))
+                    .filter(|file| {
+                        file.strip_suffix("/channel.json")
+                            .is_some_and(|channel| !channel.is_empty() && !channel.contains('/'))
+                    })
+                    .map(str::to_owned)
+                    .collect())
+            }
+        }
+    }
+}
+
+/// The servers listed in a data package's `servers/index.json`
+pub fn load_guilds(root: &Path) -> eyre::Result<BTreeMap<String, String>> {
+    let mut package = Package::open(root)?;
+    match package.read_json("servers", "index.json")? {
+        Some(guilds) => Ok(guilds),
+        None => bail!("{} has no servers/index.json", root.display()),
+    }
+}
+
+/// Friends and DM contacts in a data package (id → name), each once.
+/// Sections the package doesn't have are reported to `warnings` and skipped.
+pub fn load_users(root: &Path, warnings: &Warnings) -> eyre::Result<BTreeMap<String, String>> {
+    let mut package = Package::open(root)?;
+    let mut users = BTreeMap::new();
+
+    let account = match package.read_json::<Account>("account", "user.json")? {
+        Some(account) => Some(account),
+        None => {
+            warnings.warn(
+                Condition::MissingSection,
+                "the data package has no account/user.json, so no friends list, and your own ID can't be left out of the DM contacts",
+            );
+            None
+        }
+    };
+    let own_id = account.as_ref().map(|a| a.id.clone());
+    if let Some(account) = account {
+        let friends = account
+            .relationships
+            .into_iter()
+            .filter(|r| r.kind == FRIEND);
+        for friend in friends {
+            let name = match friend.user {
+                Some(user) => user.global_name.unwrap_or(user.username),
+                None => friend.id.clone(),
+            };
+            users.insert(friend.id, name);
+        }
+        debug!(friends = users.len(), "read friends list");
+    }
+
+    let Some(messages) = package.section("messages") else {
+        warnings.warn(
+            Condition::MissingSection,
+            "the data package has no messages directory, so no DM contacts",
+        );
+        return Ok(users);
+    };
+    // DM channels are named after the other person in the index
+    let names: BTreeMap<String, Option<String>> = match package.read_json("messages", "index.json")
+    {
+        Ok(names) => names.unwrap_or_default(),
+        Err(err) => {
+            debug!(%err, "no channel names");
+            BTreeMap::new()
+        }
+    };
+    let mut channels = 0;
+    for file in package.channel_files(&messages)? {
+        let channel: Channel = match package.read_json("messages", &file) {
+            Ok(Some(channel)) => channel,
+            Ok(None) => continue,
+            Err(err) => {
+                warnings.warn(
+                    Condition::SkippedInput,
+                    format!("skipping {}: {err:#}", package.describe(&messages, &file)),
+                );
+                continue;
+            }
+        };
+        if !channel.is_dm() {
+            continue;
+        }
+        channels += 1;
+        let others: Vec<String> = channel
+            .recipients
+            .into_iter()
+            .filter(|id| Some(id) != own_id.as_ref())
+            .collect();
+        let name = names
+            .get(&channel.id)
+            .and_then(Option::as_deref)
+            .and_then(|name| name.strip_prefix("Direct Message with "))
+            .filter(|_| others.len() == 1);
+        for id in others {
+            // friends keep the name from the friends list
+            let name = name.map_or_else(|| id.clone(), str::to_owned);
+            users.entry(id).or_insert(name);
+        }
+    }
+    debug!(channels, users = users.len(), "read DM contacts");
+    Ok(users)
+}
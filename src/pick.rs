@@ -0,0 +1,68 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::IsTerminal;
+use std::path::Path;
+
+use color_eyre::eyre::{self, Context};
+use inquire::{InquireError, MultiSelect};
+use tracing::info;
+
+struct Entry {
+    id: String,
+    name: String,
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.name, self.id)
+    }
+}
+
+/// Asks which of `guilds` to check, in a list searchable by name and ID.
+/// `what` is what they are, e.g. "servers". `None` if the user backed out.
+pub fn pick(
+    guilds: BTreeMap<String, String>,
+    what: &str,
+) -> eyre::Result<Option<BTreeMap<String, String>>> {
+    if !std::io::stdin().is_terminal() {
+        eyre::bail!("--pick needs a terminal to pick on; give the IDs with --only instead");
+    }
+    let mut entries: Vec<Entry> = guilds
+        .into_iter()
+        .map(|(id, name)| Entry { id, name })
+        .collect();
+    // in the order people know them by, rather than by ID
+    entries.sort_by_key(|entry| entry.name.to_lowercase());
+
+    let message = format!("Pick the {what} to check:");
+    let picked = MultiSelect::new(&message, entries)
+        .with_page_size(15)
+        .with_help_message("type to search, space to select, → all, ← none, enter to start")
+        .prompt();
+    let picked = match picked {
+        Ok(picked) => picked,
+        Err(InquireError::OperationCanceled | InquireError::OperationInterrupted) => {
+            return Ok(None)
+        }
+        Err(err) => return Err(err).context("couldn't show the picker"),
+    };
+    Ok(Some(
+        picked
+            .into_iter()
+            .map(|entry| (entry.id, entry.name))
+            .collect(),
+    ))
+}
+
+/// Writes the picked guilds as an index, ID → name
+pub fn save(path: &Path, guilds: &BTreeMap<String, String>) -> eyre::Result<()> {
+    let json = serde_json::to_string_pretty(guilds)?;
+    std::fs::write(path, json + "\n")
+        .with_context(|| format!("couldn't write {}", path.display()))?;
+    info!(
+        "saved {} picked to {path}, check them again with --index-path {path}",
+        guilds.len(),
+        path = path.display()
+    );
+    Ok(())
+}
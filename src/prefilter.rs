@@ -0,0 +1,200 @@
+use std::collections::{BTreeMap, HashSet};
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use tracing::debug;
+
+use crate::backend::fetch;
+use crate::{CheckError, Kind, Response, Status};
+
+/// Bumped whenever the cache file layout changes; older files are ignored
+const VERSION: u32 = 1;
+
+/// Recorded as the `source` of guilds the listing left out
+pub const SOURCE: &str = "prefilter";
+
+#[derive(Error, Debug)]
+pub enum ListingError {
+    #[error("couldn't fetch the listing: {0}")]
+    Fetch(#[from] CheckError),
+
+    #[error("couldn't read the listing: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("{0:?} isn't an ID")]
+    BadId(String),
+}
+
+/// A set of the IDs a dataset tracks, e.g. spy.pet's published server list or
+/// a community mirror of it.
+///
+/// Listings are JSON (an array of IDs, or of objects with an `id`, or an
+/// object keyed by ID) or plain text with one ID per line.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Listing {
+    /// The URL or path it came from
+    pub source: String,
+    /// When it was downloaded, or the file last changed
+    pub fetched_at: DateTime<Utc>,
+    ids: HashSet<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    listing: Listing,
+}
+
+fn id(value: &Value) -> Option<String> {
+    match value {
+        Value::String(id) => Some(id.clone()),
+        Value::Number(id) => Some(id.to_string()),
+        Value::Object(fields) => id(fields.get("id")?),
+        _ => None,
+    }
+}
+
+fn check_id(id: String) -> Result<String, ListingError> {
+    match !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()) {
+        true => Ok(id),
+        false => Err(ListingError::BadId(id)),
+    }
+}
+
+impl Listing {
+    pub fn parse(
+        source: impl Into<String>,
+        text: &str,
+        fetched_at: DateTime<Utc>,
+    ) -> Result<Self, ListingError> {
+        let ids = match serde_json::from_str::<Value>(text) {
+            Ok(Value::Array(items)) => items
+                .iter()
+                .map(|item| check_id(id(item).unwrap_or_else(|| item.to_string())))
+                .collect::<Result<_, _>>()?,
+            Ok(Value::Object(fields)) => fields
+                .into_iter()
+                .map(|(id, _)| check_id(id))
+                .collect::<Result<_, _>>()?,
+            // a bare number parses as JSON too
+            _ => text
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(|line| check_id(line.to_owned()))
+                .collect::<Result<_, _>>()?,
+        };
+        Ok(Self {
+            source: source.into(),
+            fetched_at,
+            ids,
+        })
+    }
+
+    /// Reads a listing from a file; its age is that of the file
+    pub fn read(path: &Path) -> Result<Self, ListingError> {
+        let text = std::fs::read_to_string(path)?;
+        let modified = std::fs::metadata(path)?.modified()?;
+        Self::parse(path.display().to_string(), &text, modified.into())
+    }
+
+    /// Downloads a listing
+    pub async fn fetch(client: &Client, url: &str) -> Result<Self, ListingError> {
+        let (status, text) = fetch(client, url).await?;
+        if !status.is_success() {
+            return Err(CheckError::HttpStatus(status).into());
+        }
+        let listing = Self::parse(url, &text, Utc::now())?;
+        debug!(ids = listing.len(), "fetched listing");
+        Ok(listing)
+    }
+
+    /// A listing saved with [`Listing::save`]. A missing or outdated file is
+    /// `None`.
+    pub fn load(path: &Path) -> io::Result<Option<Self>> {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let file: CacheFile = serde_json::from_slice(&bytes)?;
+        Ok((file.version == VERSION).then_some(file.listing))
+    }
+
+    /// Writes the listing to `path`, replacing the file atomically
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let file = CacheFile {
+            version: VERSION,
+            listing: self.clone(),
+        };
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(&file)?)?;
+        std::fs::rename(tmp, path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.ids.contains(id)
+    }
+
+    pub fn age(&self) -> Duration {
+        (Utc::now() - self.fetched_at).to_std().unwrap_or_default()
+    }
+
+    /// Splits `guilds` into those in the listing and those that aren't
+    pub fn split(
+        &self,
+        guilds: BTreeMap<String, String>,
+    ) -> (BTreeMap<String, String>, BTreeMap<String, String>) {
+        guilds.into_iter().partition(|(id, _)| self.contains(id))
+    }
+
+    /// The result recorded for a guild that isn't in the listing, and so
+    /// wasn't asked about
+    pub fn unlisted(&self, id: String, name: String) -> Response {
+        Response {
+            guild_id: id,
+            guild_name: name,
+            source: SOURCE.to_owned(),
+            kind: Kind::Guild,
+            checked_at: Some(self.fetched_at),
+            labels: Vec::new(),
+            api_response: Value::Null,
+            unparseable: None,
+            details: None,
+            member_scan: None,
+            headers: None,
+            classification: Some(Status::Unlisted),
+            window: None,
+            from_cache: false,
+            fallback: None,
+            request: None,
+            verification: None,
+            severity: None,
+        }
+    }
+}
+
+/// What `--prefilter` left out of a run
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PrefilterReport {
+    pub source: String,
+    pub fetched_at: DateTime<Utc>,
+    /// Guilds in the listing, which were checked
+    pub listed: usize,
+    /// Guilds that weren't, and were only marked unlisted
+    pub unlisted: usize,
+}
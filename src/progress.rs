@@ -0,0 +1,507 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use reqwest::Client;
+use serde::Serialize;
+use spy_pet_checker::backend::{Backend, BackendFuture};
+use spy_pet_checker::pacing::Batches;
+use spy_pet_checker::{
+    classify, last_request, CancellationToken, ErrorKind, FailedCheck, Response, RunReport, Schema,
+    Semaphore, Status,
+};
+use tokio::task::JoinHandle;
+use tracing::info;
+
+/// What a run sends as it goes, for views that follow it live. The channel
+/// closing means the run is over.
+// only the TUI reads them so far
+#[cfg_attr(not(feature = "tui"), allow(dead_code))]
+pub enum Update {
+    Started {
+        total: usize,
+        concurrency: usize,
+        /// Holding all its permits pauses the run
+        limiter: Arc<Semaphore>,
+        batches: Option<Arc<Batches>>,
+        cancel: CancellationToken,
+    },
+    Result(Box<Response>),
+    Failed {
+        guild_id: String,
+        guild_name: String,
+        source: String,
+        message: String,
+    },
+}
+
+/// Counters updated as results come in, for status reports from other tasks
+pub struct RunStatus {
+    total: usize,
+    done: AtomicUsize,
+    errors: AtomicUsize,
+    started: Instant,
+    concurrency: usize,
+    limiter: Arc<Semaphore>,
+    batches: Option<Arc<Batches>>,
+}
+
+impl RunStatus {
+    pub fn new(
+        total: usize,
+        concurrency: usize,
+        limiter: Arc<Semaphore>,
+        batches: Option<Arc<Batches>>,
+    ) -> Self {
+        Self {
+            total,
+            done: AtomicUsize::new(0),
+            errors: AtomicUsize::new(0),
+            started: Instant::now(),
+            concurrency,
+            limiter,
+            batches,
+        }
+    }
+
+    pub fn record(&self, ok: bool) {
+        self.done.fetch_add(1, Ordering::Relaxed);
+        if !ok {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Checks finished so far, successfully or not
+    pub fn done(&self) -> usize {
+        self.done.load(Ordering::Relaxed)
+    }
+
+    /// How long the rest of the run should take, going by how long the
+    /// checks so far took; `None` before the first one is done
+    pub fn eta(&self) -> Option<Duration> {
+        let done = self.done();
+        if done == 0 {
+            return None;
+        }
+        let left = self.total.saturating_sub(done);
+        // cooldowns are planned, so they're added up rather than extrapolated
+        let (cooled, cooldowns) = match &self.batches {
+            Some(batches) => (batches.cooled(), batches.cooldowns_left(left)),
+            None => (Duration::ZERO, Duration::ZERO),
+        };
+        let working = self.started.elapsed().saturating_sub(cooled);
+        Some(working.mul_f64(left as f64 / done as f64) + cooldowns)
+    }
+
+    pub fn log(&self) {
+        let done = self.done.load(Ordering::Relaxed);
+        let elapsed = self.started.elapsed();
+        let eta = match self.eta() {
+            Some(left) => seconds(left).to_string(),
+            None => "unknown".to_owned(),
+        };
+        let cooling_left = self
+            .batches
+            .as_ref()
+            .and_then(|batches| batches.cooling_left())
+            .map(seconds);
+        info!(
+            done,
+            total = self.total,
+            in_flight = self.concurrency - self.limiter.available_permits(),
+            errors = self.errors.load(Ordering::Relaxed),
+            elapsed = %seconds(elapsed),
+            %eta,
+            cooling_left = cooling_left.map(tracing::field::display),
+            "status"
+        );
+    }
+}
+
+/// `duration` to the second, for people to read
+fn seconds(duration: Duration) -> humantime::FormattedDuration {
+    humantime::format_duration(Duration::from_secs(duration.as_secs()))
+}
+
+/// Logs the run's status every time the process gets SIGUSR1
+#[cfg(unix)]
+pub async fn dump_on_sigusr1(status: Arc<RunStatus>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut signals = match signal(SignalKind::user_defined1()) {
+        Ok(signals) => signals,
+        Err(err) => {
+            tracing::warn!(%err, "couldn't listen for SIGUSR1");
+            return;
+        }
+    };
+    while signals.recv().await.is_some() {
+        status.log();
+    }
+}
+
+/// One line of `--progress-format json` or `--progress-events` output
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Event<'a> {
+    Start {
+        total: usize,
+    },
+    /// Only with `--progress-events`, like the request events
+    Queued {
+        id: &'a str,
+        name: &'a str,
+    },
+    RequestStarted {
+        id: &'a str,
+        source: &'a str,
+        attempt: u32,
+    },
+    RequestFinished {
+        id: &'a str,
+        source: &'a str,
+        attempt: u32,
+        duration_ms: f64,
+        /// `None` if no response came
+        http_status: Option<u16>,
+        /// The answer's status, or `error`
+        outcome: &'a str,
+        error: Option<String>,
+    },
+    Result {
+        id: &'a str,
+        name: &'a str,
+        source: &'a str,
+        status: Status,
+        done: usize,
+        total: usize,
+    },
+    Error {
+        id: &'a str,
+        name: &'a str,
+        source: &'a str,
+        kind: ErrorKind,
+        message: &'a str,
+        done: usize,
+        total: usize,
+    },
+    Finished {
+        done: usize,
+        total: usize,
+        compromised: usize,
+        errors: usize,
+        cancelled: bool,
+    },
+}
+
+/// Where progress events are written: stderr, or a file or named pipe for
+/// `--progress-events`
+pub struct EventSink {
+    writer: Mutex<Box<dyn Write + Send>>,
+    /// Also writes the queued and request events
+    detailed: bool,
+}
+
+impl EventSink {
+    pub fn stderr(detailed: bool) -> Arc<Self> {
+        Arc::new(Self {
+            writer: Mutex::new(Box::new(std::io::stderr())),
+            detailed,
+        })
+    }
+
+    /// Opens `path` to write the detailed events to, creating it if it
+    /// doesn't exist. A named pipe blocks here until something reads it.
+    pub fn open(path: &Path) -> io::Result<Arc<Self>> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Arc::new(Self {
+            writer: Mutex::new(Box::new(file)),
+            detailed: true,
+        }))
+    }
+
+    fn emit(&self, event: &Event) {
+        let mut line = serde_json::to_vec(event).expect("events serialize");
+        line.push(b'\n');
+        let mut writer = self.writer.lock().expect("event sink lock poisoned");
+        // progress is best effort; a closed stderr or pipe shouldn't fail
+        // the run
+        let _ = writer.write_all(&line);
+        let _ = writer.flush();
+    }
+
+    /// Wraps `backends` so that every request they make is written as it
+    /// starts and finishes, if the events are detailed
+    pub fn report_requests(
+        self: &Arc<Self>,
+        backends: Vec<Arc<dyn Backend>>,
+    ) -> Vec<Arc<dyn Backend>> {
+        if !self.detailed {
+            return backends;
+        }
+        backends
+            .into_iter()
+            .map(|inner| {
+                Arc::new(Instrumented {
+                    inner,
+                    sink: Arc::clone(self),
+                }) as Arc<dyn Backend>
+            })
+            .collect()
+    }
+}
+
+struct Instrumented {
+    inner: Arc<dyn Backend>,
+    sink: Arc<EventSink>,
+}
+
+impl Backend for Instrumented {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn url(&self, id: &str) -> String {
+        self.inner.url(id)
+    }
+
+    fn schema(&self) -> Option<Schema> {
+        self.inner.schema()
+    }
+
+    fn check<'a>(&'a self, client: &'a Client, id: &'a str) -> BackendFuture<'a> {
+        Box::pin(async move {
+            let source = self.inner.name();
+            // counted once the request goes out, so this one is the next
+            let attempt = last_request().map_or(0, |last| last.number) + 1;
+            self.sink.emit(&Event::RequestStarted {
+                id,
+                source,
+                attempt,
+            });
+            let start = Instant::now();
+            let result = self.inner.check(client, id).await;
+
+            let (outcome, error) = match &result {
+                Ok(value) => (classify(value).as_str(), None),
+                Err(err) => ("error", Some(err.to_string())),
+            };
+            self.sink.emit(&Event::RequestFinished {
+                id,
+                source,
+                attempt,
+                duration_ms: start.elapsed().as_secs_f64() * 1000.0,
+                http_status: last_request()
+                    .and_then(|last| last.status)
+                    .map(|status| status.as_u16()),
+                outcome,
+                error,
+            });
+            result
+        })
+    }
+}
+
+/// Writes the events of one run as newline-delimited JSON
+pub struct JsonProgress {
+    sink: Arc<EventSink>,
+    done: usize,
+    total: usize,
+}
+
+impl JsonProgress {
+    pub fn start(sink: Arc<EventSink>, total: usize) -> Self {
+        sink.emit(&Event::Start { total });
+        Self {
+            sink,
+            done: 0,
+            total,
+        }
+    }
+
+    /// Says `id` is waiting to be checked, if the events are detailed
+    pub fn queued(&self, id: &str, name: &str) {
+        if self.sink.detailed {
+            self.sink.emit(&Event::Queued { id, name });
+        }
+    }
+
+    pub fn result(&mut self, response: &Response) {
+        self.done += 1;
+        self.sink.emit(&Event::Result {
+            id: &response.guild_id,
+            name: &response.guild_name,
+            source: &response.source,
+            status: response.status(),
+            done: self.done,
+            total: self.total,
+        });
+    }
+
+    pub fn error(&mut self, failed: &FailedCheck) {
+        self.done += 1;
+        self.sink.emit(&Event::Error {
+            id: &failed.guild_id,
+            name: &failed.guild_name,
+            source: &failed.source,
+            kind: failed.kind,
+            message: &failed.message,
+            done: self.done,
+            total: self.total,
+        });
+    }
+
+    pub fn finish(self, report: &RunReport) {
+        self.sink.emit(&Event::Finished {
+            done: self.done,
+            total: self.total,
+            compromised: report.compromised().count(),
+            errors: report.failed.len(),
+            cancelled: report.cancelled,
+        });
+    }
+}
+
+/// The progress bar, while a run draws one. Logs written through
+/// [`StderrWriter`] go above it.
+static BAR: Mutex<Option<Bar>> = Mutex::new(None);
+
+const BAR_WIDTH: usize = 30;
+
+/// How often the bar is redrawn at most, and at least while nothing comes in
+const REDRAW_EVERY: Duration = Duration::from_millis(100);
+const TICK_EVERY: Duration = Duration::from_secs(1);
+
+struct Bar {
+    status: Arc<RunStatus>,
+    compromised: usize,
+    drawn_at: Option<Instant>,
+}
+
+impl Bar {
+    fn line(&self) -> String {
+        let status = &self.status;
+        let done = status.done();
+        let filled = match status.total {
+            0 => BAR_WIDTH,
+            total => BAR_WIDTH * done.min(total) / total,
+        };
+        let elapsed = status.started.elapsed().as_secs_f64();
+        let rate = match elapsed > 0.0 {
+            true => done as f64 / elapsed,
+            false => 0.0,
+        };
+        let eta = match status.eta() {
+            Some(left) => seconds(left).to_string(),
+            None => "?".to_owned(),
+        };
+        format!(
+            "[{}{}] {done}/{} checked, {rate:.1}/s, {eta} left, {} compromised, {} errors",
+            "#".repeat(filled),
+            "-".repeat(BAR_WIDTH - filled),
+            status.total,
+            self.compromised,
+            status.errors.load(Ordering::Relaxed),
+        )
+    }
+
+    fn draw(&mut self, w: &mut dyn Write) {
+        // progress is best effort, like the JSON events
+        let _ = write!(w, "\r\x1b[2K{}", self.line());
+        let _ = w.flush();
+        self.drawn_at = Some(Instant::now());
+    }
+
+    /// Draws the bar unless it was just drawn, and always once it's full
+    fn redraw(&mut self) {
+        let finished = self.status.done() >= self.status.total;
+        if finished || self.drawn_at.is_none_or(|at| at.elapsed() >= REDRAW_EVERY) {
+            self.draw(&mut std::io::stderr().lock());
+        }
+    }
+}
+
+fn lock() -> MutexGuard<'static, Option<Bar>> {
+    BAR.lock().expect("progress bar lock poisoned")
+}
+
+/// Draws a progress bar on stderr while it's alive, under the logs
+pub struct ProgressBar {
+    ticker: JoinHandle<()>,
+}
+
+impl ProgressBar {
+    pub fn start(status: Arc<RunStatus>) -> Self {
+        let mut bar = Bar {
+            status,
+            compromised: 0,
+            drawn_at: None,
+        };
+        bar.draw(&mut std::io::stderr().lock());
+        *lock() = Some(bar);
+        // the rate and time left change while a slow check is waited on
+        let ticker = tokio::spawn(async {
+            loop {
+                tokio::time::sleep(TICK_EVERY).await;
+                if let Some(bar) = lock().as_mut() {
+                    bar.draw(&mut std::io::stderr().lock());
+                }
+            }
+        });
+        Self { ticker }
+    }
+
+    /// Counts `response` in; the run's status has the rest
+    pub fn result(&self, response: &Response) {
+        let mut bar = lock();
+        let Some(bar) = bar.as_mut() else {
+            return;
+        };
+        if response.is_compromised() {
+            bar.compromised += 1;
+        }
+        bar.redraw();
+    }
+
+    pub fn error(&self) {
+        if let Some(bar) = lock().as_mut() {
+            bar.redraw();
+        }
+    }
+}
+
+impl Drop for ProgressBar {
+    fn drop(&mut self) {
+        self.ticker.abort();
+        if lock().take().is_some() {
+            let _ = write!(std::io::stderr(), "\r\x1b[2K");
+        }
+    }
+}
+
+/// Writes to stderr, clearing the progress bar first and drawing it again
+/// after, if there is one
+pub struct StderrWriter;
+
+impl Write for StderrWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut bar = lock();
+        let mut stderr = std::io::stderr().lock();
+        if bar.is_some() {
+            stderr.write_all(b"\r\x1b[2K")?;
+        }
+        stderr.write_all(buf)?;
+        // a log line is written in one go, so the bar goes back under it
+        if let (Some(bar), true) = (bar.as_mut(), buf.ends_with(b"\n")) {
+            bar.draw(&mut stderr);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        std::io::stderr().flush()
+    }
+}
@@ -0,0 +1,421 @@
+//! Sending the requests through a proxy. HTTP and HTTPS proxies are
+//! reqwest's own; SOCKS5 ones go through a bridge on localhost that reqwest
+//! talks to as an HTTP proxy, since this build of reqwest can't speak SOCKS.
+
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+
+use percent_encoding::percent_decode_str;
+use reqwest::header::HeaderValue;
+use reqwest::{NoProxy, Url};
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, warn};
+
+use crate::secret::Secret;
+
+/// The port of a SOCKS proxy given without one
+const SOCKS_PORT: u16 = 1080;
+
+/// The environment variables a SOCKS proxy is taken from, in order. reqwest
+/// reads them too, but skips SOCKS URLs.
+const PROXY_ENV: [&str; 6] = [
+    "ALL_PROXY",
+    "all_proxy",
+    "HTTPS_PROXY",
+    "https_proxy",
+    "HTTP_PROXY",
+    "http_proxy",
+];
+
+/// Requests to the bridge longer than this are refused
+const MAX_HEAD: usize = 64 * 1024;
+
+#[derive(Error, Debug)]
+pub enum ProxyError {
+    #[error("{0:?} isn't a proxy URL")]
+    BadUrl(String),
+
+    #[error("{0}:// proxies aren't supported, only http, https, socks5 and socks5h")]
+    Scheme(String),
+
+    #[error("couldn't start the SOCKS bridge: {0}")]
+    Bridge(#[source] io::Error),
+}
+
+/// A proxy to send every request through
+#[derive(Clone)]
+pub struct Proxy {
+    /// The URL without its password, for messages
+    shown: String,
+    inner: reqwest::Proxy,
+}
+
+impl Proxy {
+    /// A proxy at `url`: `http://`, `https://`, `socks5://` (host names
+    /// looked up here) or `socks5h://` (looked up by the proxy). The user
+    /// name comes from the URL, the password from `password` or else the URL.
+    /// `NO_PROXY` is honoured.
+    pub fn new(url: &str, password: Option<&Secret<String>>) -> Result<Self, ProxyError> {
+        let mut parsed = Url::parse(url).map_err(|_| ProxyError::BadUrl(url.to_owned()))?;
+        let host = match parsed.host_str() {
+            Some(host) if !host.is_empty() => host.trim_matches(['[', ']']).to_owned(),
+            _ => return Err(ProxyError::BadUrl(url.to_owned())),
+        };
+        let username = percent_decode_str(parsed.username())
+            .decode_utf8_lossy()
+            .into_owned();
+        let password = match password {
+            Some(password) => Some(password.clone()),
+            None => parsed
+                .password()
+                .map(|p| Secret::new(percent_decode_str(p).decode_utf8_lossy().into_owned())),
+        };
+        let _ = parsed.set_password(None);
+        let shown = parsed.to_string().trim_end_matches('/').to_owned();
+
+        let inner = match parsed.scheme() {
+            "http" | "https" => {
+                let proxy = reqwest::Proxy::all(parsed.as_str())
+                    .map_err(|_| ProxyError::BadUrl(shown.clone()))?;
+                match username.is_empty() {
+                    true => proxy,
+                    false => {
+                        let password = password.as_ref().map_or("", |p| p.expose());
+                        proxy.basic_auth(&username, password)
+                    }
+                }
+            }
+            scheme @ ("socks5" | "socks5h") => {
+                let upstream = Socks5 {
+                    host,
+                    port: parsed.port().unwrap_or(SOCKS_PORT),
+                    auth: (!username.is_empty()).then(|| {
+                        (
+                            username,
+                            password.unwrap_or_else(|| Secret::new(String::new())),
+                        )
+                    }),
+                    remote_dns: scheme == "socks5h",
+                };
+                let (addr, token) = bridge(upstream).map_err(ProxyError::Bridge)?;
+                let mut auth =
+                    HeaderValue::try_from(format!("Bearer {token}")).expect("the token is hex");
+                auth.set_sensitive(true);
+                reqwest::Proxy::all(format!("http://{addr}"))
+                    .expect("the bridge's URL is valid")
+                    .custom_http_auth(auth)
+            }
+            scheme => return Err(ProxyError::Scheme(scheme.to_owned())),
+        };
+        Ok(Self {
+            shown,
+            inner: inner.no_proxy(NoProxy::from_env()),
+        })
+    }
+
+    /// A SOCKS proxy from `ALL_PROXY`, `HTTPS_PROXY` or `HTTP_PROXY`, which
+    /// reqwest would skip. HTTP proxies in them are left to reqwest.
+    pub fn socks_from_env() -> Result<Option<Self>, ProxyError> {
+        let found = PROXY_ENV.into_iter().find_map(|var| {
+            let url = std::env::var(var).ok()?;
+            url.starts_with("socks5").then_some(url)
+        });
+        found.map(|url| Self::new(&url, None)).transpose()
+    }
+
+    pub(crate) fn reqwest(&self) -> reqwest::Proxy {
+        self.inner.clone()
+    }
+}
+
+impl fmt::Display for Proxy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.shown)
+    }
+}
+
+impl fmt::Debug for Proxy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Proxy").field(&self.shown).finish()
+    }
+}
+
+/// A SOCKS5 proxy, RFC 1928, with username and password auth from RFC 1929
+#[derive(Clone, PartialEq, Eq)]
+struct Socks5 {
+    host: String,
+    port: u16,
+    auth: Option<(String, Secret<String>)>,
+    /// Leave looking up host names to the proxy
+    remote_dns: bool,
+}
+
+impl Socks5 {
+    /// Opens a connection to `host`:`port` through the proxy
+    async fn connect(&self, host: &str, port: u16) -> io::Result<TcpStream> {
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port)).await?;
+        match &self.auth {
+            Some(_) => stream.write_all(&[5, 2, 0, 2]).await?,
+            None => stream.write_all(&[5, 1, 0]).await?,
+        }
+        let mut choice = [0; 2];
+        stream.read_exact(&mut choice).await?;
+        match (choice, &self.auth) {
+            ([5, 0], _) => {}
+            ([5, 2], Some((username, password))) => {
+                let password = password.expose();
+                let (Ok(ulen), Ok(plen)) =
+                    (u8::try_from(username.len()), u8::try_from(password.len()))
+                else {
+                    return Err(socks_error(
+                        "the username or password is too long for SOCKS",
+                    ));
+                };
+                let mut request = vec![1, ulen];
+                request.extend_from_slice(username.as_bytes());
+                request.push(plen);
+                request.extend_from_slice(password.as_bytes());
+                stream.write_all(&request).await?;
+                let mut status = [0; 2];
+                stream.read_exact(&mut status).await?;
+                if status[1] != 0 {
+                    return Err(socks_error("the proxy refused the username or password"));
+                }
+            }
+            ([5, 2], None) => return Err(socks_error("the proxy wants a username and password")),
+            ([5, _], _) => return Err(socks_error("the proxy accepts none of our auth methods")),
+            _ => return Err(socks_error("not a SOCKS5 proxy")),
+        }
+
+        let mut request = vec![5, 1, 0];
+        let ip = match host.parse::<IpAddr>() {
+            Ok(ip) => Some(ip),
+            Err(_) if self.remote_dns => None,
+            Err(_) => {
+                let addr = tokio::net::lookup_host((host, port)).await?.next();
+                Some(
+                    addr.ok_or_else(|| socks_error("host name has no addresses"))?
+                        .ip(),
+                )
+            }
+        };
+        match ip {
+            Some(IpAddr::V4(ip)) => {
+                request.push(1);
+                request.extend_from_slice(&ip.octets());
+            }
+            Some(IpAddr::V6(ip)) => {
+                request.push(4);
+                request.extend_from_slice(&ip.octets());
+            }
+            None => {
+                let len = u8::try_from(host.len())
+                    .map_err(|_| socks_error("host name too long for SOCKS"))?;
+                request.push(3);
+                request.push(len);
+                request.extend_from_slice(host.as_bytes());
+            }
+        }
+        request.extend_from_slice(&port.to_be_bytes());
+        stream.write_all(&request).await?;
+
+        let mut reply = [0; 4];
+        stream.read_exact(&mut reply).await?;
+        if reply[1] != 0 {
+            return Err(socks_error(reply_message(reply[1])));
+        }
+        // the address the proxy connected from, of no use here
+        let skip = match reply[3] {
+            1 => 4,
+            4 => 16,
+            3 => stream.read_u8().await? as usize,
+            _ => return Err(socks_error("not a SOCKS5 reply")),
+        };
+        let mut bound = vec![0; skip + 2];
+        stream.read_exact(&mut bound).await?;
+        Ok(stream)
+    }
+}
+
+fn socks_error(message: &str) -> io::Error {
+    io::Error::other(format!("SOCKS: {message}"))
+}
+
+fn reply_message(code: u8) -> &'static str {
+    match code {
+        1 => "the proxy failed",
+        2 => "the proxy's rules forbid the connection",
+        3 => "network unreachable",
+        4 => "host unreachable",
+        5 => "connection refused",
+        6 => "TTL expired",
+        7 => "the proxy doesn't support CONNECT",
+        8 => "the proxy doesn't support the address type",
+        _ => "the proxy refused the connection",
+    }
+}
+
+/// Bridges already running, shared by every client sent through the same
+/// proxy
+static BRIDGES: Mutex<Vec<(Socks5, SocketAddr, String)>> = Mutex::new(Vec::new());
+
+/// The address of a bridge to `upstream` and the token it wants, starting
+/// one if none is running. The bridge runs on a thread of its own, for
+/// clients built outside a runtime or used from several.
+fn bridge(upstream: Socks5) -> io::Result<(SocketAddr, String)> {
+    let mut bridges = BRIDGES.lock().expect("bridge lock poisoned");
+    if let Some((_, addr, token)) = bridges.iter().find(|(running, ..)| *running == upstream) {
+        return Ok((*addr, token.clone()));
+    }
+    // without it, anything on this machine could use the proxy through the
+    // bridge
+    let token = format!("{:016x}{:016x}", fastrand::u64(..), fastrand::u64(..));
+    let listener = std::net::TcpListener::bind(("127.0.0.1", 0))?;
+    listener.set_nonblocking(true)?;
+    let addr = listener.local_addr()?;
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let (target, secret) = (upstream.clone(), token.clone());
+    std::thread::Builder::new()
+        .name("socks-bridge".to_owned())
+        .spawn(move || runtime.block_on(serve(listener, target, secret)))?;
+    debug!(%addr, proxy = upstream.host, "started SOCKS bridge");
+    bridges.push((upstream, addr, token.clone()));
+    Ok((addr, token))
+}
+
+async fn serve(listener: std::net::TcpListener, upstream: Socks5, token: String) {
+    let listener = match TcpListener::from_std(listener) {
+        Ok(listener) => listener,
+        Err(err) => return warn!(%err, "SOCKS bridge failed"),
+    };
+    let expected = format!("Bearer {token}");
+    loop {
+        let Ok((client, _)) = listener.accept().await else {
+            continue;
+        };
+        let (upstream, expected) = (upstream.clone(), expected.clone());
+        tokio::spawn(async move {
+            if let Err(err) = handle(client, &upstream, &expected).await {
+                debug!(%err, "SOCKS bridge connection failed");
+            }
+        });
+    }
+}
+
+/// One connection from reqwest: a `CONNECT` for an HTTPS request, or a
+/// plain request with an absolute URL, which is passed on with the
+/// connection closed after it, since the next one may be for another host
+async fn handle(mut client: TcpStream, upstream: &Socks5, expected: &str) -> io::Result<()> {
+    let (head, rest) = read_head(&mut client).await?;
+    let head = String::from_utf8_lossy(&head);
+    let mut lines = head.split("\r\n");
+    let request_line = lines.next().unwrap_or_default();
+    let mut parts = request_line.split(' ');
+    let (Some(method), Some(target), Some(version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return respond(&mut client, "400 Bad Request").await;
+    };
+    let headers: Vec<(&str, &str)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim(), value.trim()))
+        .collect();
+    let authorized = headers.iter().any(|(name, value)| {
+        name.eq_ignore_ascii_case("proxy-authorization") && *value == expected
+    });
+    if !authorized {
+        return respond(&mut client, "407 Proxy Authentication Required").await;
+    }
+
+    if method == "CONNECT" {
+        let Some((host, port)) = authority(target) else {
+            return respond(&mut client, "400 Bad Request").await;
+        };
+        let mut server = match upstream.connect(&host, port).await {
+            Ok(server) => server,
+            Err(err) => {
+                debug!(%err, host, "SOCKS connect failed");
+                return respond(&mut client, "502 Bad Gateway").await;
+            }
+        };
+        client
+            .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+            .await?;
+        server.write_all(&rest).await?;
+        tokio::io::copy_bidirectional(&mut client, &mut server).await?;
+        return Ok(());
+    }
+
+    let Some(url) = Url::parse(target).ok().filter(|url| url.scheme() == "http") else {
+        return respond(&mut client, "400 Bad Request").await;
+    };
+    let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+        return respond(&mut client, "400 Bad Request").await;
+    };
+    let host = host.trim_matches(['[', ']']);
+    let mut server = match upstream.connect(host, port).await {
+        Ok(server) => server,
+        Err(err) => {
+            debug!(%err, host, "SOCKS connect failed");
+            return respond(&mut client, "502 Bad Gateway").await;
+        }
+    };
+    let path = match url.query() {
+        Some(query) => format!("{}?{query}", url.path()),
+        None => url.path().to_owned(),
+    };
+    let mut forwarded = format!("{method} {path} {version}\r\n");
+    for (name, value) in headers {
+        let hop = ["proxy-authorization", "proxy-connection", "connection"];
+        if !hop.iter().any(|hop| name.eq_ignore_ascii_case(hop)) {
+            forwarded.push_str(&format!("{name}: {value}\r\n"));
+        }
+    }
+    forwarded.push_str("Connection: close\r\n\r\n");
+    server.write_all(forwarded.as_bytes()).await?;
+    server.write_all(&rest).await?;
+    tokio::io::copy_bidirectional(&mut client, &mut server).await?;
+    Ok(())
+}
+
+/// Reads up to the end of the request head, returning it and whatever came
+/// after it
+async fn read_head(stream: &mut (impl AsyncRead + Unpin)) -> io::Result<(Vec<u8>, Vec<u8>)> {
+    let mut buf = Vec::new();
+    let mut chunk = [0; 4096];
+    loop {
+        if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            let rest = buf.split_off(end + 4);
+            buf.truncate(end);
+            return Ok((buf, rest));
+        }
+        if buf.len() > MAX_HEAD {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "request head too long",
+            ));
+        }
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        buf.extend_from_slice(&chunk[..read]);
+    }
+}
+
+async fn respond(stream: &mut (impl AsyncWrite + Unpin), status: &str) -> io::Result<()> {
+    let response = format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+    stream.write_all(response.as_bytes()).await
+}
+
+/// `host:port` of a `CONNECT`, IPv6 hosts in brackets
+fn authority(target: &str) -> Option<(String, u16)> {
+    let (host, port) = target.rsplit_once(':')?;
+    let host = host.trim_matches(['[', ']']);
+    Some((host.to_owned(), port.parse().ok()?))
+}
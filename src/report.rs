@@ -0,0 +1,125 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::notify::Notification;
+use crate::prefilter::PrefilterReport;
+use crate::window::WindowReport;
+use crate::{FailedCheck, Response, SchemaDrift, Timing};
+
+/// Everything a run produced, as handed to the formatters
+#[derive(Serialize, Deserialize, Default)]
+pub struct RunReport {
+    /// When the run started, for runs made by this process
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<DateTime<Utc>>,
+    pub results: Vec<Response>,
+    /// Checks that failed, in completion order
+    #[serde(default)]
+    pub failed: Vec<FailedCheck>,
+    /// The run was stopped before every guild was checked
+    pub cancelled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub performance: Option<Performance>,
+    /// Backends whose answers didn't look like they used to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schema_drift: Vec<SchemaDrift>,
+    /// The `--since`/`--until` window results were checked against
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window: Option<WindowReport>,
+    /// The `--prefilter` listing, and how many guilds it left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefilter: Option<PrefilterReport>,
+    /// The `--notify-on` condition and whether this run met it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notification: Option<Notification>,
+    /// Whether the checks were sent with an API key; never the key itself
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<ApiKeyStatus>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiKeyStatus {
+    Set,
+}
+
+impl RunReport {
+    pub fn compromised(&self) -> impl Iterator<Item = &Response> {
+        self.results.iter().filter(|r| r.is_compromised())
+    }
+}
+
+/// Request latency percentiles, in milliseconds
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Latency {
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+}
+
+/// How a run performed, to tell whether `concurrency` can be raised
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Performance {
+    /// Checks that finished, successfully or not
+    pub requests: usize,
+    pub elapsed_ms: f64,
+    pub requests_per_second: f64,
+    pub bytes_received: u64,
+    /// `None` if no request finished
+    pub latency: Option<Latency>,
+    /// Time checks spent waiting for a slot under the concurrency limit,
+    /// summed over all checks
+    pub queued_ms: f64,
+    /// Time checks spent on the network, summed over all checks
+    pub requesting_ms: f64,
+    /// The lowest remaining rate limit quota any response reported, if the
+    /// API sends rate limit headers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_rate_limit_remaining: Option<u64>,
+    /// Requests sent again after failing, over all checks
+    #[serde(default)]
+    pub retries: u64,
+}
+
+fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+impl Performance {
+    pub fn compute(timings: &[Timing], elapsed: Duration) -> Self {
+        let mut latencies: Vec<Duration> = timings.iter().map(|t| t.request).collect();
+        latencies.sort();
+        // nearest-rank percentile
+        let percentile = |p: f64| {
+            let rank = (p * latencies.len() as f64).ceil() as usize;
+            ms(latencies[rank.saturating_sub(1)])
+        };
+        let latency = (!latencies.is_empty()).then(|| Latency {
+            p50_ms: percentile(0.50),
+            p95_ms: percentile(0.95),
+            max_ms: ms(latencies[latencies.len() - 1]),
+        });
+
+        let seconds = elapsed.as_secs_f64();
+        Self {
+            requests: timings.len(),
+            elapsed_ms: ms(elapsed),
+            requests_per_second: if seconds > 0.0 {
+                timings.len() as f64 / seconds
+            } else {
+                0.0
+            },
+            bytes_received: timings.iter().map(|t| t.bytes).sum(),
+            latency,
+            queued_ms: ms(timings.iter().map(|t| t.queued).sum()),
+            requesting_ms: ms(timings.iter().map(|t| t.request).sum()),
+            min_rate_limit_remaining: timings.iter().filter_map(|t| t.rate_limit_remaining).min(),
+            retries: timings
+                .iter()
+                .map(|t| u64::from(t.attempts.saturating_sub(1)))
+                .sum(),
+        }
+    }
+}
@@ -0,0 +1,274 @@
+//! What checks find, apart from the machinery that runs them, so that it
+//! builds wherever the checks can run
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::deep::Details;
+use crate::error::{CheckError, ErrorKind};
+use crate::members::MemberScan;
+use crate::severity::Severity;
+use crate::web::Fallback;
+use crate::window::InWindow;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Response {
+    pub guild_id: String,
+    pub guild_name: String,
+    /// Name of the backend that produced this result
+    #[serde(default)]
+    pub source: String,
+    /// Whether `guild_id` and `guild_name` are a server's or a user's.
+    /// Records written by older versions are all servers.
+    #[serde(default)]
+    pub kind: Kind,
+    /// When the backend answered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checked_at: Option<DateTime<Utc>>,
+    /// Who this result belongs to, e.g. the accounts whose indexes listed the
+    /// guild or the result files it was merged from
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+    /// Null when the body couldn't be parsed
+    pub api_response: Value,
+    /// Set when the backend answered with something that isn't JSON
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unparseable: Option<Unparseable>,
+    /// From `--deep-scan`, for compromised guilds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<Details>,
+    /// From `--scan-members`: known scraper bots in the guild right now
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub member_scan: Option<MemberScan>,
+    /// Rate limit, tracing and content type headers the answer came with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub headers: Option<BTreeMap<String, String>>,
+    /// What the answer means, see [`classify`]. Records written by older
+    /// versions don't have it; [`Response::status`] works it out for them.
+    #[serde(default, rename = "status", skip_serializing_if = "Option::is_none")]
+    pub classification: Option<Status>,
+    /// For compromised guilds, whether the backend saw them during
+    /// `--since`/`--until`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window: Option<InWindow>,
+    /// Answered from the result cache instead of asking the backend
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub from_cache: bool,
+    /// Read off the website with `--fallback-web`, because the API failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<Fallback>,
+    /// How asking the backend went; `None` for answers that weren't asked
+    /// for, such as cached ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<RequestStats>,
+    /// From `--verify`, for compromised guilds: whether asking again said the
+    /// same
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification: Option<Verification>,
+    /// For compromised guilds, how bad their exposure is, see
+    /// [`Response::severity`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+}
+
+/// The tries behind a result
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct RequestStats {
+    /// From sending the first try to having read the last answer, retries
+    /// included
+    pub duration_ms: f64,
+    /// What the last try was answered with, if it got an answer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_status: Option<u16>,
+    pub attempts: u32,
+}
+
+impl Response {
+    /// Whether the backend reported the guild in its dataset
+    pub fn is_compromised(&self) -> bool {
+        self.status() == Status::Compromised
+    }
+
+    pub fn status(&self) -> Status {
+        if self.unparseable.is_some() {
+            Status::Unparseable
+        } else {
+            self.classification
+                .unwrap_or_else(|| classify(&self.api_response))
+        }
+    }
+}
+
+/// What a check looks up
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    /// A server
+    #[default]
+    Guild,
+    User,
+    /// A channel, which is in the dataset if its messages were scraped
+    Channel,
+}
+
+impl Kind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Kind::Guild => "guild",
+            Kind::User => "user",
+            Kind::Channel => "channel",
+        }
+    }
+}
+
+/// What a result says about a guild
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    /// Not in the dataset
+    Clean,
+    /// In the dataset
+    Compromised,
+    /// Valid JSON that doesn't say either way, e.g. `null` or `"False"`
+    Indeterminate,
+    /// The body wasn't JSON
+    Unparseable,
+    /// Not in the `--prefilter` listing, so not asked about
+    Unlisted,
+}
+
+impl Status {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Status::Clean => "clean",
+            Status::Compromised => "compromised",
+            Status::Indeterminate => "indeterminate",
+            Status::Unparseable => "unparseable",
+            Status::Unlisted => "unlisted",
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What asking again about a compromised guild said
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Verification {
+    /// Compromised again
+    Confirmed,
+    /// Anything else the second time, so the first answer may have been a
+    /// fluke
+    Flaky,
+    /// The second request failed, or the run ended before it was sent
+    Unverified,
+}
+
+impl Verification {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Verification::Confirmed => "confirmed",
+            Verification::Flaky => "flaky",
+            Verification::Unverified => "unverified",
+        }
+    }
+}
+
+/// Reads a backend's answer. `false` means the guild isn't in the dataset,
+/// and `true` or an object with at least one field that it is. Anything else
+/// (`null`, numbers, strings, arrays, `{}`) is indeterminate rather than
+/// guessed at.
+pub fn classify(api_response: &Value) -> Status {
+    match api_response {
+        Value::Bool(false) => Status::Clean,
+        Value::Bool(true) => Status::Compromised,
+        Value::Object(fields) if !fields.is_empty() => Status::Compromised,
+        _ => Status::Indeterminate,
+    }
+}
+
+/// What a body that wasn't JSON looked like
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyKind {
+    Empty,
+    Html,
+    Other,
+}
+
+/// A response body that couldn't be parsed, kept so the guild still shows
+/// up in the output
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Unparseable {
+    pub body: BodyKind,
+    /// The start of the body, at most [`SNIPPET_LEN`](crate::error::SNIPPET_LEN)
+    /// bytes
+    pub snippet: String,
+}
+
+impl Unparseable {
+    pub(crate) fn new(snippet: String) -> Self {
+        let start = snippet.trim_start();
+        let body = if start.is_empty() {
+            BodyKind::Empty
+        } else if start.starts_with('<') {
+            BodyKind::Html
+        } else {
+            BodyKind::Other
+        };
+        Self { body, snippet }
+    }
+}
+
+/// A check that didn't produce a result
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FailedCheck {
+    pub guild_id: String,
+    pub guild_name: String,
+    /// Name of the backend that was asked
+    pub source: String,
+    pub kind: ErrorKind,
+    pub message: String,
+    /// What the API answered with, when the error was an HTTP status
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_status: Option<u16>,
+    /// Requests made before giving up
+    pub attempts: u32,
+    /// The error itself, for checks run by this process; records read back
+    /// from disk only have `kind` and `message`
+    #[serde(skip)]
+    pub error: Option<CheckError>,
+}
+
+impl FailedCheck {
+    pub fn new(
+        guild_id: String,
+        guild_name: String,
+        source: String,
+        attempts: u32,
+        error: CheckError,
+    ) -> Self {
+        Self {
+            guild_id,
+            guild_name,
+            source,
+            kind: error.kind(),
+            message: error.to_string(),
+            http_status: error.http_status().map(|status| status.as_u16()),
+            attempts,
+            error: Some(error),
+        }
+    }
+}
+
+pub type CheckResult = Result<Response, FailedCheck>;
@@ -0,0 +1,55 @@
+use std::time::Duration;
+
+use crate::CheckError;
+
+/// How failed requests are tried again
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Tries after the first one
+    pub retries: u32,
+    /// Wait before the first retry, doubled for each one after it
+    pub backoff: Duration,
+    /// The longest wait. A 429 asking for more than this isn't tried again.
+    pub max_wait: Duration,
+}
+
+impl RetryPolicy {
+    /// One try only
+    pub const NONE: Self = Self {
+        retries: 0,
+        backoff: Duration::from_secs(1),
+        max_wait: Duration::from_secs(60),
+    };
+
+    pub fn new(retries: u32, backoff: Duration) -> Self {
+        Self {
+            retries,
+            backoff,
+            ..Self::NONE
+        }
+    }
+
+    /// How long to wait before trying again after `attempts` tries ended in
+    /// `err`, or `None` to give up. A 429's `Retry-After` is waited out
+    /// instead of the backoff.
+    pub fn delay(&self, attempts: u32, err: &CheckError) -> Option<Duration> {
+        if attempts > self.retries || !err.is_retryable() {
+            return None;
+        }
+        match err {
+            CheckError::RateLimited {
+                retry_after: Some(wait),
+            } => (*wait <= self.max_wait).then_some(*wait),
+            _ => {
+                let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+                Some(self.backoff.saturating_mul(factor).min(self.max_wait))
+            }
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
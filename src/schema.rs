@@ -0,0 +1,89 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{Kind, Response};
+
+/// The fields a backend is known to send for guilds in its dataset
+#[derive(Clone, Copy, Debug)]
+pub struct Schema {
+    /// Fields every answer has
+    pub required: &'static [&'static str],
+    /// Fields some answers have
+    pub optional: &'static [&'static str],
+}
+
+impl Schema {
+    fn knows(&self, field: &str) -> bool {
+        self.required.contains(&field) || self.optional.contains(&field)
+    }
+}
+
+/// How a backend's answers differed from its [`Schema`] over a run. A sign
+/// the API changed and this version may misread it.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SchemaDrift {
+    pub source: String,
+    /// Fields that showed up without being in the schema
+    pub unknown_fields: BTreeSet<String>,
+    /// Required fields that some answer lacked
+    pub missing_fields: BTreeSet<String>,
+}
+
+/// Collects drift from each result as it comes in
+#[derive(Default)]
+pub(crate) struct DriftCheck {
+    schemas: BTreeMap<String, Schema>,
+    drift: BTreeMap<String, SchemaDrift>,
+}
+
+impl DriftCheck {
+    pub fn new(schemas: impl IntoIterator<Item = (String, Schema)>) -> Self {
+        Self {
+            schemas: schemas.into_iter().collect(),
+            drift: BTreeMap::new(),
+        }
+    }
+
+    pub fn extend(&mut self, schemas: impl IntoIterator<Item = (String, Schema)>) {
+        self.schemas.extend(schemas);
+    }
+
+    /// The schemas describe server answers, anything else is left alone
+    pub fn record(&mut self, response: &Response) {
+        if response.kind != Kind::Guild {
+            return;
+        }
+        let (Some(schema), Value::Object(fields)) =
+            (self.schemas.get(&response.source), &response.api_response)
+        else {
+            return;
+        };
+
+        let schema = *schema;
+        for field in fields.keys().filter(|field| !schema.knows(field)) {
+            self.entry(&response.source)
+                .unknown_fields
+                .insert(field.clone());
+        }
+        for field in schema.required.iter().filter(|f| !fields.contains_key(**f)) {
+            self.entry(&response.source)
+                .missing_fields
+                .insert((*field).to_owned());
+        }
+    }
+
+    fn entry(&mut self, source: &str) -> &mut SchemaDrift {
+        self.drift
+            .entry(source.to_owned())
+            .or_insert_with(|| SchemaDrift {
+                source: source.to_owned(),
+                ..Default::default()
+            })
+    }
+
+    pub fn drift(&self) -> Vec<SchemaDrift> {
+        self.drift.values().cloned().collect()
+    }
+}
@@ -0,0 +1,161 @@
+//! Finding servers in a dataset by name, for when the ID isn't known
+
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use reqwest::{Client, StatusCode};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::backend::{fetch, parse_json};
+use crate::CheckError;
+
+/// spy.pet's search, with `{query}` for the name
+pub const DEFAULT_SEARCH_URL: &str = "https://api.spy.pet/search?q={query}";
+
+/// Candidates scoring lower than this aren't reported
+pub const MIN_SCORE: f64 = 0.3;
+
+/// Candidates reported per search at most
+pub const MAX_CANDIDATES: usize = 10;
+
+/// A server the search found, and how close its name is to the one asked
+/// for, from 0 to 1
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Candidate {
+    pub id: String,
+    pub name: String,
+    pub score: f64,
+}
+
+/// Lowercase words of letters and digits, so case and punctuation don't
+/// count against a match
+fn normalize(name: &str) -> String {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn bigrams(s: &str) -> Vec<(char, char)> {
+    let chars: Vec<char> = s.chars().collect();
+    chars.windows(2).map(|pair| (pair[0], pair[1])).collect()
+}
+
+/// How alike two names are, from 0 to 1: 1 for the same name give or take
+/// case and punctuation, and otherwise the share of character pairs they
+/// have in common, or more if one has the other in it
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (normalize(a), normalize(b));
+    if a == b {
+        return 1.0;
+    }
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let (left, mut right) = (bigrams(&a), bigrams(&b));
+    let total = left.len() + right.len();
+    let mut shared = 0;
+    for pair in &left {
+        if let Some(at) = right.iter().position(|other| other == pair) {
+            right.swap_remove(at);
+            shared += 1;
+        }
+    }
+    let dice = match total {
+        0 => 0.0,
+        total => 2.0 * shared as f64 / total as f64,
+    };
+    let (short, long) = match a.len() <= b.len() {
+        true => (&a, &b),
+        false => (&b, &a),
+    };
+    match long.contains(short.as_str()) {
+        true => dice.max(0.7 + 0.3 * short.len() as f64 / long.len() as f64),
+        false => dice,
+    }
+}
+
+/// `found` as candidates for `query`, best first, without the ones too far
+/// off
+pub fn rank(query: &str, found: impl IntoIterator<Item = (String, String)>) -> Vec<Candidate> {
+    let mut candidates: Vec<Candidate> = found
+        .into_iter()
+        .map(|(id, name)| Candidate {
+            score: similarity(query, &name),
+            id,
+            name,
+        })
+        .filter(|candidate| candidate.score >= MIN_SCORE)
+        .collect();
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.id.cmp(&b.id)));
+    candidates.dedup_by(|a, b| a.id == b.id);
+    candidates.truncate(MAX_CANDIDATES);
+    candidates
+}
+
+fn id(value: &Value) -> Option<String> {
+    match value {
+        Value::String(id) => Some(id.clone()),
+        Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+fn name(value: &Value) -> Option<String> {
+    match value {
+        Value::String(name) => Some(name.clone()),
+        Value::Object(fields) => ["name", "guild_name"]
+            .iter()
+            .find_map(|key| fields.get(*key)?.as_str().map(str::to_owned)),
+        _ => None,
+    }
+}
+
+/// The servers a search answer lists: an array of objects with an `id` and
+/// a `name`, or an object of them keyed by ID, bare or under `results`
+fn servers(answer: Value) -> Vec<(String, String)> {
+    match answer {
+        Value::Array(items) => items
+            .iter()
+            .filter_map(|item| Some((id(item.get("id")?)?, name(item)?)))
+            .collect(),
+        Value::Object(mut fields) => match fields.remove("results") {
+            Some(results) => servers(results),
+            None => fields
+                .into_iter()
+                .filter_map(|(id, value)| Some((id, name(&value)?)))
+                .collect(),
+        },
+        _ => Vec::new(),
+    }
+}
+
+/// Searches a dataset by name through a URL template with `{query}`
+pub struct Search {
+    template: String,
+}
+
+impl Search {
+    /// `None` if `template` has no `{query}` to put the name in
+    pub fn new(template: impl Into<String>) -> Option<Self> {
+        let template = template.into();
+        template.contains("{query}").then_some(Self { template })
+    }
+
+    pub fn url(&self, query: &str) -> String {
+        let query = utf8_percent_encode(query, NON_ALPHANUMERIC).to_string();
+        self.template.replace("{query}", &query)
+    }
+
+    /// The servers whose name is close to `query`, best first
+    pub async fn find(&self, client: &Client, query: &str) -> Result<Vec<Candidate>, CheckError> {
+        let (status, text) = fetch(client, &self.url(query)).await?;
+        if status == StatusCode::NOT_FOUND {
+            return Ok(Vec::new());
+        }
+        if !status.is_success() {
+            return Err(CheckError::HttpStatus(status));
+        }
+        Ok(rank(query, servers(parse_json(&text)?)))
+    }
+}
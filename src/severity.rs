@@ -0,0 +1,140 @@
+//! How bad it is that a guild is in the dataset, worked out from what the
+//! answer says about it, so the worst of many compromised guilds can be
+//! looked at first.
+//!
+//! The score goes from 0 to 100 and adds up three parts:
+//!
+//! - up to 50 for the archived messages, 10 for every tenfold, so 100 000
+//!   messages get all of it
+//! - up to 30 for how recently the guild was seen: 30 within a month of
+//!   the check, 20 within three, 10 within a year, and 15 when the answer
+//!   doesn't say
+//! - up to 20 for the member count, 4 for every tenfold
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::answer::GuildInfo;
+use crate::Response;
+
+/// What a [`Severity`] score comes out as, in order
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    /// Below 25
+    Low,
+    /// 25 to 49
+    Medium,
+    /// 50 to 74
+    High,
+    /// 75 and up
+    Critical,
+}
+
+impl Level {
+    pub const ALL: [Level; 4] = [Level::Low, Level::Medium, Level::High, Level::Critical];
+
+    pub fn of(score: u8) -> Self {
+        match score {
+            75.. => Level::Critical,
+            50.. => Level::High,
+            25.. => Level::Medium,
+            _ => Level::Low,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Low => "low",
+            Level::Medium => "medium",
+            Level::High => "high",
+            Level::Critical => "critical",
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Level::ALL
+            .into_iter()
+            .find(|level| level.as_str().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("{s:?} isn't low, medium, high or critical"))
+    }
+}
+
+/// How bad a compromised guild's exposure is
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Severity {
+    /// From 0 to 100
+    pub score: u8,
+    pub level: Level,
+}
+
+/// Points for `count`, `per_tenfold` for every power of ten, up to `most`
+fn tenfolds(count: Option<u64>, per_tenfold: f64, most: f64) -> f64 {
+    count.map_or(0.0, |count| {
+        (per_tenfold * (count as f64 + 1.0).log10()).min(most)
+    })
+}
+
+fn recency(info: &GuildInfo, at: DateTime<Utc>) -> f64 {
+    let Some(seen) = info.last_seen.or(info.first_seen) else {
+        return 15.0;
+    };
+    let age = at - seen;
+    if age <= TimeDelta::days(30) {
+        30.0
+    } else if age <= TimeDelta::days(90) {
+        20.0
+    } else if age <= TimeDelta::days(365) {
+        10.0
+    } else {
+        0.0
+    }
+}
+
+impl Severity {
+    /// Scores what the answer says, with recency counted back from `at`
+    pub fn of(info: &GuildInfo, at: DateTime<Utc>) -> Self {
+        let score = tenfolds(info.message_count, 10.0, 50.0)
+            + recency(info, at)
+            + tenfolds(info.member_count, 4.0, 20.0);
+        let score = score.round().clamp(0.0, 100.0) as u8;
+        Self {
+            score,
+            level: Level::of(score),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}/100)", self.level, self.score)
+    }
+}
+
+impl Response {
+    /// How bad it is that the guild is in the dataset; `None` unless it's
+    /// compromised. Records written by older versions don't have it, so
+    /// it's worked out for them as of when they were checked.
+    pub fn severity(&self) -> Option<Severity> {
+        if !self.is_compromised() {
+            return None;
+        }
+        self.severity.or_else(|| {
+            let at = self.checked_at.unwrap_or_else(Utc::now);
+            Some(Severity::of(&self.guild_info().unwrap_or_default(), at))
+        })
+    }
+}
@@ -0,0 +1,52 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::Response;
+
+/// A compromised guild and the labels (e.g. accounts) whose inputs listed it
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct SharedGuild {
+    pub guild_id: String,
+    pub guild_name: String,
+    pub labels: Vec<String>,
+}
+
+/// The compromised guilds of `results`, most widely shared first. A guild
+/// compromised according to several backends is listed once, with the
+/// labels of all its results.
+pub fn shared(results: &[Response]) -> Vec<SharedGuild> {
+    let mut guilds: BTreeMap<&str, SharedGuild> = BTreeMap::new();
+    for result in results.iter().filter(|r| r.is_compromised()) {
+        let guild = guilds
+            .entry(&result.guild_id)
+            .or_insert_with(|| SharedGuild {
+                guild_id: result.guild_id.clone(),
+                guild_name: result.guild_name.clone(),
+                labels: Vec::new(),
+            });
+        for label in &result.labels {
+            if !guild.labels.contains(label) {
+                guild.labels.push(label.clone());
+            }
+        }
+    }
+    let mut guilds: Vec<SharedGuild> = guilds.into_values().collect();
+    guilds.sort_by(|a, b| {
+        b.labels
+            .len()
+            .cmp(&a.labels.len())
+            .then_with(|| a.guild_name.cmp(&b.guild_name))
+    });
+    guilds
+}
+
+/// Whether `results` come from more than one label, so that sharing means
+/// something
+pub fn is_multi_label(results: &[Response]) -> bool {
+    let mut labels = results.iter().flat_map(|r| &r.labels);
+    match labels.next() {
+        Some(first) => labels.any(|label| label != first),
+        None => false,
+    }
+}
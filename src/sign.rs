@@ -0,0 +1,233 @@
+//! Signing reports with Ed25519, as JWS (RFC 7515), so that whoever a
+//! report is shared with can check it wasn't changed since: detached for any
+//! format, or with the report inside for JSON.
+
+use std::path::Path;
+
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use ring::digest::{digest, SHA256};
+use ring::signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// What a DER Ed25519 public key (SubjectPublicKeyInfo) has before the key
+const PUBLIC_KEY_PREFIX: [u8; 12] = [
+    0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00,
+];
+
+#[derive(Error, Debug)]
+pub enum SignError {
+    #[error("couldn't read {path}: {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("not an Ed25519 key in PEM, e.g. from openssl genpkey -algorithm ed25519")]
+    NotAKey,
+
+    #[error("not a signature from spy-pet-checker")]
+    Malformed,
+
+    #[error("signed with key {signed_with}, not with {expected}")]
+    OtherKey {
+        signed_with: String,
+        expected: String,
+    },
+
+    #[error("the signature doesn't match, the report was changed after it was signed")]
+    Mismatch,
+}
+
+/// The protected header of the signatures
+#[derive(Serialize, Deserialize)]
+struct Header {
+    alg: String,
+    kid: String,
+    /// `false` when the report isn't in the signing input as base64
+    /// (RFC 7797), which is how detached signatures are made
+    #[serde(skip_serializing_if = "Option::is_none")]
+    b64: Option<bool>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    crit: Vec<String>,
+}
+
+/// A JWS in the flattened JSON serialization, the report in `payload`
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    payload: String,
+    protected: String,
+    signature: String,
+}
+
+fn read(path: &Path) -> Result<String, SignError> {
+    std::fs::read_to_string(path).map_err(|source| SignError::Io {
+        path: path.display().to_string(),
+        source,
+    })
+}
+
+/// The DER in the first PEM block labelled `label`
+fn pem(text: &str, label: &str) -> Option<Vec<u8>> {
+    let begin = format!("-----BEGIN {label}-----");
+    let end = format!("-----END {label}-----");
+    let (_, rest) = text.split_once(&begin)?;
+    let (body, _) = rest.split_once(&end)?;
+    let body: String = body.split_whitespace().collect();
+    STANDARD.decode(body).ok()
+}
+
+/// A short name for a public key: the start of its SHA-256, in hex
+fn key_id(public: &[u8]) -> String {
+    digest(&SHA256, public).as_ref()[..8]
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// The key reports are signed with
+pub struct SigningKey {
+    pair: Ed25519KeyPair,
+}
+
+impl SigningKey {
+    /// A PKCS#8 private key in PEM
+    pub fn from_pem(text: &str) -> Result<Self, SignError> {
+        let der = pem(text, "PRIVATE KEY").ok_or(SignError::NotAKey)?;
+        let pair =
+            Ed25519KeyPair::from_pkcs8_maybe_unchecked(&der).map_err(|_| SignError::NotAKey)?;
+        Ok(Self { pair })
+    }
+
+    pub fn read(path: &Path) -> Result<Self, SignError> {
+        Self::from_pem(&read(path)?)
+    }
+
+    pub fn key_id(&self) -> String {
+        key_id(self.pair.public_key().as_ref())
+    }
+
+    fn protected(&self, detached: bool) -> String {
+        let header = Header {
+            alg: "EdDSA".to_owned(),
+            kid: self.key_id(),
+            b64: detached.then_some(false),
+            crit: match detached {
+                true => vec!["b64".to_owned()],
+                false => Vec::new(),
+            },
+        };
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header).expect("a header serializes"))
+    }
+
+    /// A detached signature of `report`, as a compact JWS with nothing
+    /// between its two dots
+    pub fn detached(&self, report: &[u8]) -> String {
+        let protected = self.protected(true);
+        let input = [protected.as_bytes(), b".", report].concat();
+        let signature = URL_SAFE_NO_PAD.encode(self.pair.sign(&input));
+        format!("{protected}..{signature}")
+    }
+
+    /// `report` signed, inside a JSON JWS
+    pub fn embedded(&self, report: &[u8]) -> String {
+        let protected = self.protected(false);
+        let payload = URL_SAFE_NO_PAD.encode(report);
+        let input = format!("{protected}.{payload}");
+        let signature = URL_SAFE_NO_PAD.encode(self.pair.sign(input.as_bytes()));
+        let envelope = Envelope {
+            payload,
+            protected,
+            signature,
+        };
+        serde_json::to_string_pretty(&envelope).expect("an envelope serializes")
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey {
+        let public = self.pair.public_key().as_ref();
+        VerifyingKey(public.try_into().expect("Ed25519 public keys are 32 bytes"))
+    }
+}
+
+/// The public half, to check signatures with
+pub struct VerifyingKey([u8; 32]);
+
+impl VerifyingKey {
+    /// A public key in PEM, or the private key it's the public half of
+    pub fn from_pem(text: &str) -> Result<Self, SignError> {
+        if let Some(der) = pem(text, "PUBLIC KEY") {
+            let key = der
+                .strip_prefix(PUBLIC_KEY_PREFIX.as_slice())
+                .and_then(|key| key.try_into().ok())
+                .ok_or(SignError::NotAKey)?;
+            return Ok(Self(key));
+        }
+        SigningKey::from_pem(text).map(|key| key.verifying_key())
+    }
+
+    pub fn read(path: &Path) -> Result<Self, SignError> {
+        Self::from_pem(&read(path)?)
+    }
+
+    pub fn key_id(&self) -> String {
+        key_id(&self.0)
+    }
+
+    fn check(
+        &self,
+        protected: &str,
+        detached: bool,
+        input: &[u8],
+        signature: &str,
+    ) -> Result<(), SignError> {
+        let header: Header = URL_SAFE_NO_PAD
+            .decode(protected)
+            .ok()
+            .and_then(|header| serde_json::from_slice(&header).ok())
+            .ok_or(SignError::Malformed)?;
+        // a detached signature is over the report as is, not as base64
+        if header.alg != "EdDSA" || header.b64.unwrap_or(true) == detached {
+            return Err(SignError::Malformed);
+        }
+        if header.kid != self.key_id() {
+            return Err(SignError::OtherKey {
+                signed_with: header.kid,
+                expected: self.key_id(),
+            });
+        }
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| SignError::Malformed)?;
+        UnparsedPublicKey::new(&ED25519, &self.0)
+            .verify(input, &signature)
+            .map_err(|_| SignError::Mismatch)
+    }
+
+    /// Checks a signature from [`SigningKey::detached`] against `report`
+    pub fn verify_detached(&self, report: &[u8], jws: &str) -> Result<(), SignError> {
+        let parts: Vec<&str> = jws.trim().split('.').collect();
+        let [protected, "", signature] = parts[..] else {
+            return Err(SignError::Malformed);
+        };
+        let input = [protected.as_bytes(), b".", report].concat();
+        self.check(protected, true, &input, signature)
+    }
+
+    /// Checks a report from [`SigningKey::embedded`], and gives back the
+    /// report in it
+    pub fn verify_embedded(&self, text: &str) -> Result<Vec<u8>, SignError> {
+        let envelope: Envelope = serde_json::from_str(text).map_err(|_| SignError::Malformed)?;
+        let input = format!("{}.{}", envelope.protected, envelope.payload);
+        self.check(
+            &envelope.protected,
+            false,
+            input.as_bytes(),
+            &envelope.signature,
+        )?;
+        URL_SAFE_NO_PAD
+            .decode(&envelope.payload)
+            .map_err(|_| SignError::Malformed)
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+use std::fs::DirBuilder;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use directories::ProjectDirs;
+
+/// Where the cache, checkpoints and run history live on disk.
+///
+/// Directories are created on first use, readable only by the current user.
+#[derive(Clone, Debug)]
+pub struct StateDir {
+    root: PathBuf,
+}
+
+impl StateDir {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// The platform's conventional location: `$XDG_STATE_HOME/spy-pet-checker`
+    /// on Linux, the local application data directory elsewhere
+    pub fn default_location() -> Option<Self> {
+        let dirs = ProjectDirs::from("", "", "spy-pet-checker")?;
+        let root = dirs.state_dir().unwrap_or_else(|| dirs.data_local_dir());
+        Some(Self::new(root))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn subdir(&self, name: &str) -> io::Result<PathBuf> {
+        let path = self.root.join(name);
+        create_private_dir(&path)?;
+        Ok(path)
+    }
+
+    pub fn cache_dir(&self) -> io::Result<PathBuf> {
+        self.subdir("cache")
+    }
+
+    /// Backend answers kept for `--cache-ttl`
+    pub fn result_cache_path(&self) -> io::Result<PathBuf> {
+        Ok(self.cache_dir()?.join("results.json"))
+    }
+
+    /// The last `--prefilter` listing downloaded
+    pub fn prefilter_path(&self) -> io::Result<PathBuf> {
+        Ok(self.cache_dir()?.join("prefilter.json"))
+    }
+
+    pub fn checkpoint_dir(&self) -> io::Result<PathBuf> {
+        self.subdir("checkpoints")
+    }
+
+    /// The checkpoint of runs with this [fingerprint](crate::checkpoint::fingerprint)
+    pub fn checkpoint_path(&self, fingerprint: &str) -> io::Result<PathBuf> {
+        Ok(self.checkpoint_dir()?.join(format!("{fingerprint}.jsonl")))
+    }
+
+    pub fn history_dir(&self) -> io::Result<PathBuf> {
+        self.subdir("history")
+    }
+
+    pub fn run_path(&self, run_id: &str) -> io::Result<PathBuf> {
+        Ok(self.history_dir()?.join(format!("{run_id}.json")))
+    }
+
+    /// The last watch cycle's results
+    pub fn watch_state_path(&self) -> io::Result<PathBuf> {
+        create_private_dir(&self.root)?;
+        Ok(self.root.join("watch.json"))
+    }
+
+    /// When the release feed was last checked for updates
+    pub fn update_check_path(&self) -> io::Result<PathBuf> {
+        create_private_dir(&self.root)?;
+        Ok(self.root.join("update-check.json"))
+    }
+
+    /// The bot list `scan-bots --update-list` downloaded
+    pub fn known_bots_path(&self) -> io::Result<PathBuf> {
+        create_private_dir(&self.root)?;
+        Ok(self.root.join("known_bots.txt"))
+    }
+
+    pub fn cookie_jar_path(&self) -> io::Result<PathBuf> {
+        create_private_dir(&self.root)?;
+        Ok(self.root.join("cookies.json"))
+    }
+
+    /// The Discord token `auth` got, and what refreshes it
+    pub fn oauth_token_path(&self) -> io::Result<PathBuf> {
+        create_private_dir(&self.root)?;
+        Ok(self.root.join("discord-oauth.json"))
+    }
+
+    pub fn audit_log_path(&self) -> io::Result<PathBuf> {
+        create_private_dir(&self.root)?;
+        Ok(self.root.join("audit.log"))
+    }
+
+    /// Bytes on disk under each top-level entry of the directory, by name.
+    /// A directory that doesn't exist yet uses none.
+    pub fn disk_usage(&self) -> io::Result<BTreeMap<String, u64>> {
+        let mut usage = BTreeMap::new();
+        let entries = match std::fs::read_dir(&self.root) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(usage),
+            Err(err) => return Err(err),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            usage.insert(name, size_of(&entry.path())?);
+        }
+        Ok(usage)
+    }
+}
+
+/// Total size of the files at `path`, recursively. Files removed while
+/// counting don't count.
+fn size_of(path: &Path) -> io::Result<u64> {
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(err) => return Err(err),
+    };
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    let mut total = 0;
+    for entry in std::fs::read_dir(path)? {
+        total += size_of(&entry?.path())?;
+    }
+    Ok(total)
+}
+
+fn create_private_dir(path: &Path) -> io::Result<()> {
+    let mut builder = DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(path)
+}
@@ -0,0 +1,111 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::Response;
+
+#[derive(Serialize)]
+pub struct Exposure {
+    pub guild_id: String,
+    pub guild_name: String,
+    pub messages: u64,
+}
+
+#[derive(Serialize)]
+pub struct MessageDistribution {
+    pub min: u64,
+    pub median: u64,
+    pub max: u64,
+    pub total: u64,
+    /// Compromised guilds per order of magnitude, keyed by lower bound
+    pub buckets: BTreeMap<u64, usize>,
+}
+
+/// Aggregate numbers over a set of results
+#[derive(Serialize)]
+pub struct Stats {
+    pub total: usize,
+    pub compromised: usize,
+    pub clean: usize,
+    pub by_source: BTreeMap<String, usize>,
+    pub messages: Option<MessageDistribution>,
+    /// The compromised guilds with the most archived messages
+    pub most_exposed: Vec<Exposure>,
+    /// What couldn't be computed from the input, and why
+    pub notes: Vec<String>,
+}
+
+impl Stats {
+    pub fn compute(results: &[Response], top: usize) -> Self {
+        let compromised: Vec<&Response> = results.iter().filter(|r| r.is_compromised()).collect();
+
+        let mut by_source = BTreeMap::new();
+        for result in results {
+            let source = if result.source.is_empty() {
+                "unknown"
+            } else {
+                &result.source
+            };
+            *by_source.entry(source.to_owned()).or_default() += 1;
+        }
+
+        let mut exposures: Vec<Exposure> = compromised
+            .iter()
+            .filter_map(|r| {
+                Some(Exposure {
+                    guild_id: r.guild_id.clone(),
+                    guild_name: r.guild_name.clone(),
+                    messages: r.message_count()?,
+                })
+            })
+            .collect();
+        exposures.sort_by_key(|e| std::cmp::Reverse(e.messages));
+
+        let mut notes = Vec::new();
+        let messages = if exposures.is_empty() {
+            if !compromised.is_empty() {
+                notes.push("no message counts in the compromised results".to_owned());
+            }
+            None
+        } else {
+            if exposures.len() < compromised.len() {
+                notes.push(format!(
+                    "{} compromised results have no message count",
+                    compromised.len() - exposures.len()
+                ));
+            }
+            let mut counts: Vec<u64> = exposures.iter().map(|e| e.messages).collect();
+            counts.sort_unstable();
+            let mut buckets = BTreeMap::new();
+            for &count in &counts {
+                let bucket = if count == 0 {
+                    0
+                } else {
+                    10u64.pow(count.ilog10())
+                };
+                *buckets.entry(bucket).or_default() += 1;
+            }
+            Some(MessageDistribution {
+                min: counts[0],
+                median: counts[counts.len() / 2],
+                max: counts[counts.len() - 1],
+                total: counts.iter().sum(),
+                buckets,
+            })
+        };
+
+        notes.push("input has no per-guild errors".to_owned());
+        notes.push("input has no per-guild timing".to_owned());
+
+        exposures.truncate(top);
+        Self {
+            total: results.len(),
+            compromised: compromised.len(),
+            clean: results.len() - compromised.len(),
+            by_source,
+            messages,
+            most_exposed: exposures,
+            notes,
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use color_eyre::eyre::{self, Context};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::trace::{SdkTracerProvider, SpanData, SpanExporter};
+use opentelemetry_sdk::Resource;
+use tracing::warn;
+use tracing_subscriber::{EnvFilter, Layer, Registry};
+
+/// Keeps a dead collector from holding up the exit for long
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Warns the first time an export fails and stays quiet after that, so an
+/// unreachable collector costs one line of output
+#[derive(Debug)]
+struct WarnOnce {
+    inner: opentelemetry_otlp::SpanExporter,
+    warned: AtomicBool,
+}
+
+impl SpanExporter for WarnOnce {
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        let result = self.inner.export(batch).await;
+        if let Err(err) = &result {
+            if !self.warned.swap(true, Ordering::Relaxed) {
+                warn!(%err, "couldn't export traces, continuing without them");
+            }
+        }
+        result
+    }
+
+    fn shutdown_with_timeout(&mut self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn force_flush(&mut self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource);
+    }
+}
+
+/// Collectors are usually given as a base URL; OTLP/HTTP wants the traces
+/// path on it
+fn traces_url(endpoint: &str) -> String {
+    let base = endpoint.trim_end_matches('/');
+    if base.ends_with("/v1/traces") {
+        base.to_owned()
+    } else {
+        format!("{base}/v1/traces")
+    }
+}
+
+/// The collector to export to: `--otlp-endpoint`, or else the standard
+/// OpenTelemetry variables, so a deployment that sets those for everything
+/// doesn't need the flag too
+pub fn endpoint(flag: Option<&str>) -> Option<String> {
+    flag.map(str::to_owned)
+        .or_else(|| std::env::var("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT").ok())
+        .or_else(|| std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").ok())
+        .filter(|endpoint| !endpoint.is_empty())
+}
+
+/// What the spans say they come from. `OTEL_SERVICE_NAME` and
+/// `OTEL_RESOURCE_ATTRIBUTES` are read by the SDK, and the name is only
+/// defaulted to the crate's when the first isn't set.
+fn resource() -> Resource {
+    let mut resource = Resource::builder()
+        .with_attribute(KeyValue::new("service.version", env!("CARGO_PKG_VERSION")));
+    if std::env::var_os("OTEL_SERVICE_NAME").is_none() {
+        resource = resource.with_service_name(env!("CARGO_PKG_NAME"));
+    }
+    resource.build()
+}
+
+/// Exports this crate's spans to the OTLP/HTTP collector at `endpoint`.
+/// Spans are sent in batches from a background thread; shut the provider
+/// down before exiting to send the last ones.
+pub fn layer(
+    endpoint: &str,
+) -> eyre::Result<(Box<dyn Layer<Registry> + Send + Sync>, SdkTracerProvider)> {
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(traces_url(endpoint))
+        .with_timeout(EXPORT_TIMEOUT)
+        .build()
+        .context("couldn't set up trace export")?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(WarnOnce {
+            inner: exporter,
+            warned: AtomicBool::new(false),
+        })
+        .with_resource(resource())
+        .build();
+
+    let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+    // only our spans; the exporter's own HTTP client would trace itself
+    let layer = tracing_opentelemetry::layer()
+        .with_tracer(tracer)
+        .with_filter(EnvFilter::new("spy_pet_checker=info"))
+        .boxed();
+    Ok((layer, provider))
+}
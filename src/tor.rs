@@ -0,0 +1,202 @@
+//! Asking Tor for new circuits through its control port, so requests after
+//! a rotation leave from another exit, with another IP address.
+
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tracing::{debug, info, warn};
+
+use crate::secret::Secret;
+
+/// Where Tor listens for SOCKS connections by default
+pub const TOR_SOCKS: &str = "127.0.0.1:9050";
+/// Where Tor's control port is by default
+pub const TOR_CONTROL: &str = "127.0.0.1:9051";
+
+#[derive(Error, Debug)]
+pub enum TorError {
+    #[error("couldn't talk to the Tor control port at {addr}: {source}")]
+    Connect {
+        addr: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("the Tor control port answered {0:?}")]
+    Refused(String),
+
+    #[error("couldn't authenticate to the Tor control port: {0}")]
+    Auth(String),
+}
+
+/// Tor's control port, to ask it for new circuits
+pub struct TorControl {
+    addr: String,
+    password: Option<Secret<String>>,
+}
+
+impl TorControl {
+    /// The control port at `addr`. Authenticates with the cookie Tor names
+    /// if it allows that, or else `password` for a `HashedControlPassword`.
+    pub fn new(addr: impl Into<String>, password: Option<Secret<String>>) -> Self {
+        Self {
+            addr: addr.into(),
+            password,
+        }
+    }
+
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+
+    /// Sends `SIGNAL NEWNYM`: connections opened after it get new circuits.
+    /// Tor may hold it back if the last one was less than 10 seconds ago.
+    pub async fn new_circuit(&self) -> Result<(), TorError> {
+        let connect = |source| TorError::Connect {
+            addr: self.addr.clone(),
+            source,
+        };
+        let stream = TcpStream::connect(&self.addr).await.map_err(connect)?;
+        let mut stream = BufReader::new(stream);
+        let info = command(&mut stream, "PROTOCOLINFO 1")
+            .await
+            .map_err(connect)?;
+        let auth = self.authenticate(&info).await?;
+        let reply = command(&mut stream, &auth).await.map_err(connect)?;
+        if !reply.iter().any(|line| line.starts_with("250")) {
+            return Err(TorError::Auth(reply.join(" ")));
+        }
+        let reply = command(&mut stream, "SIGNAL NEWNYM")
+            .await
+            .map_err(connect)?;
+        if !reply.iter().any(|line| line.starts_with("250")) {
+            return Err(TorError::Refused(reply.join(" ")));
+        }
+        let _ = command(&mut stream, "QUIT").await;
+        Ok(())
+    }
+
+    /// The `AUTHENTICATE` command for the methods `PROTOCOLINFO` listed
+    async fn authenticate(&self, info: &[String]) -> Result<String, TorError> {
+        let line = info
+            .iter()
+            .find_map(|line| line.strip_prefix("250-AUTH "))
+            .ok_or_else(|| TorError::Auth("no auth methods in PROTOCOLINFO".to_owned()))?;
+        let methods = field(line, "METHODS").unwrap_or_default();
+        let methods: Vec<&str> = methods.split(',').collect();
+        if methods.contains(&"NULL") {
+            return Ok("AUTHENTICATE".to_owned());
+        }
+        if let Some(password) = &self.password {
+            if methods.contains(&"HASHEDPASSWORD") {
+                let escaped = password.expose().replace('\\', "\\\\").replace('"', "\\\"");
+                return Ok(format!("AUTHENTICATE \"{escaped}\""));
+            }
+        }
+        if methods.contains(&"COOKIE") {
+            let path = field(line, "COOKIEFILE")
+                .map(PathBuf::from)
+                .ok_or_else(|| TorError::Auth("Tor named no cookie file".to_owned()))?;
+            let cookie = tokio::fs::read(&path).await.map_err(|err| {
+                TorError::Auth(format!("couldn't read {}: {err}", path.display()))
+            })?;
+            let mut hex = String::with_capacity(cookie.len() * 2);
+            for byte in cookie {
+                let _ = write!(hex, "{byte:02x}");
+            }
+            return Ok(format!("AUTHENTICATE {hex}"));
+        }
+        Err(TorError::Auth(format!(
+            "no usable method among {}; set a control password",
+            methods.join(", ")
+        )))
+    }
+}
+
+/// A `KEY=value` or `KEY="value"` of a reply line
+fn field(line: &str, key: &str) -> Option<String> {
+    let start = line.find(&format!("{key}="))? + key.len() + 1;
+    let rest = &line[start..];
+    match rest.strip_prefix('"') {
+        Some(quoted) => {
+            let end = quoted.find('"')?;
+            Some(quoted[..end].replace("\\\\", "\\"))
+        }
+        None => Some(rest.split(' ').next()?.to_owned()),
+    }
+}
+
+/// Sends one command and reads its reply, up to the line ending it
+async fn command(stream: &mut BufReader<TcpStream>, command: &str) -> std::io::Result<Vec<String>> {
+    stream
+        .get_mut()
+        .write_all(format!("{command}\r\n").as_bytes())
+        .await?;
+    let mut lines = Vec::new();
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line).await? == 0 {
+            return Ok(lines);
+        }
+        let line = line.trim_end().to_owned();
+        // `250 OK` ends a reply, `250-...` continues it
+        let done = line.as_bytes().get(3) == Some(&b' ');
+        lines.push(line);
+        if done {
+            return Ok(lines);
+        }
+    }
+}
+
+/// When to ask for a new circuit, through `control`
+pub struct CircuitRotation {
+    control: TorControl,
+    /// After this many requests
+    every: Option<u32>,
+    /// After a 429
+    on_rate_limit: bool,
+    requests: AtomicU32,
+    /// Held while asking, so a burst of 429s asks once
+    rotating: tokio::sync::Mutex<()>,
+}
+
+impl CircuitRotation {
+    pub fn new(control: TorControl, every: Option<u32>, on_rate_limit: bool) -> Arc<Self> {
+        Arc::new(Self {
+            control,
+            every: every.filter(|&n| n > 0),
+            on_rate_limit,
+            requests: AtomicU32::new(0),
+            rotating: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    pub fn control(&self) -> &TorControl {
+        &self.control
+    }
+
+    /// After a request: asks for a new circuit if it's time to. A failed
+    /// rotation is logged, and the run carries on over the old circuit.
+    pub(crate) async fn record(&self, rate_limited: bool) {
+        let requests = self.requests.fetch_add(1, Ordering::Relaxed) + 1;
+        let due = self
+            .every
+            .is_some_and(|every| requests.is_multiple_of(every));
+        if !(due || rate_limited && self.on_rate_limit) {
+            return;
+        }
+        let Ok(_rotating) = self.rotating.try_lock() else {
+            return;
+        };
+        match self.control.new_circuit().await {
+            Ok(()) if rate_limited => info!("rate limited, switched to a new Tor circuit"),
+            Ok(()) => debug!(requests, "switched to a new Tor circuit"),
+            Err(err) => warn!(%err, "couldn't switch to a new Tor circuit"),
+        }
+    }
+}
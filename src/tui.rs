@@ -0,0 +1,566 @@
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Cell, Gauge, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use serde_json::Value;
+use spy_pet_checker::pacing::Batches;
+use spy_pet_checker::window::InWindow;
+use spy_pet_checker::{CancellationToken, Semaphore, Status};
+use tokio::runtime::Handle;
+use tokio::sync::mpsc::error::TryRecvError;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::progress::Update;
+
+/// How often the screen is redrawn when nothing happens
+const TICK: Duration = Duration::from_millis(100);
+
+/// Log lines kept for the log pane
+const LOG_LINES: usize = 500;
+
+/// Columns from which the selected result's answer is shown next to the
+/// table
+const DETAIL_WIDTH: u16 = 120;
+
+/// `None` while the screen isn't up
+static LOG_PANE: Mutex<Option<VecDeque<String>>> = Mutex::new(None);
+
+/// Log writer for `--tui`: lines go to the log pane while the screen is up,
+/// and to stderr before and after
+pub struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut pane = LOG_PANE.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(lines) = pane.as_mut() else {
+            return io::stderr().write(buf);
+        };
+        for line in String::from_utf8_lossy(buf).lines() {
+            if lines.len() == LOG_LINES {
+                lines.pop_front();
+            }
+            lines.push_back(line.to_owned());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+fn log_pane() -> std::sync::MutexGuard<'static, Option<VecDeque<String>>> {
+    LOG_PANE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Sends logs to the log pane from now on, until the screen is closed
+pub fn capture_logs() {
+    *log_pane() = Some(VecDeque::new());
+}
+
+/// Shows the run coming through `updates` until the user quits. Quitting
+/// before the run is over cancels it.
+pub fn run(mut updates: mpsc::UnboundedReceiver<Update>, runtime: Handle) -> io::Result<()> {
+    // also restores the terminal if anything panics while it's up
+    let mut terminal = ratatui::init();
+    let mut app = App::new(runtime);
+    let result = app.run(&mut terminal, &mut updates);
+    ratatui::restore();
+    app.quit();
+
+    // what scrolled by is worth keeping, warnings especially
+    let lines = log_pane().take();
+    if let Some(lines) = lines {
+        let mut stderr = io::stderr().lock();
+        for line in lines {
+            let _ = writeln!(stderr, "{line}");
+        }
+    }
+    result
+}
+
+/// One result as shown in the table
+struct Entry {
+    id: String,
+    name: String,
+    source: String,
+    /// `None` if the check failed
+    status: Option<Status>,
+    note: String,
+    api_response: Option<Value>,
+    /// Read out of the answer once, for sorting
+    messages: Option<u64>,
+}
+
+impl Entry {
+    /// Where sorting by status puts it: what needs looking at first
+    fn rank(&self) -> u8 {
+        match self.status {
+            Some(Status::Compromised) => 0,
+            Some(Status::Indeterminate) => 1,
+            Some(Status::Unparseable) => 2,
+            None => 3,
+            Some(Status::Clean) => 4,
+            Some(Status::Unlisted) => 5,
+        }
+    }
+
+    fn status_cell(&self) -> Cell<'static> {
+        let (text, style) = match self.status {
+            Some(Status::Compromised) => (
+                "compromised",
+                Style::new().fg(Color::Red).add_modifier(Modifier::BOLD),
+            ),
+            Some(status @ (Status::Indeterminate | Status::Unparseable)) => {
+                (status.as_str(), Style::new().fg(Color::Yellow))
+            }
+            Some(Status::Clean) => ("clean", Style::new().fg(Color::Green)),
+            Some(Status::Unlisted) => ("unlisted", Style::new().fg(Color::DarkGray)),
+            None => ("error", Style::new().fg(Color::Magenta)),
+        };
+        Cell::from(text).style(style)
+    }
+}
+
+/// What the table is sorted by, `s` going from one to the next
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Sort {
+    /// As the results came in
+    Arrival,
+    Status,
+    Name,
+    Id,
+    /// Most archived messages first
+    Messages,
+}
+
+impl Sort {
+    fn next(self) -> Self {
+        match self {
+            Sort::Arrival => Sort::Status,
+            Sort::Status => Sort::Name,
+            Sort::Name => Sort::Id,
+            Sort::Id => Sort::Messages,
+            Sort::Messages => Sort::Arrival,
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            Sort::Arrival => "",
+            Sort::Status => " (by status)",
+            Sort::Name => " (by name)",
+            Sort::Id => " (by ID)",
+            Sort::Messages => " (by messages)",
+        }
+    }
+}
+
+/// The run being shown
+struct Run {
+    total: usize,
+    concurrency: usize,
+    limiter: Arc<Semaphore>,
+    /// Shows when the run is resting between batches
+    batches: Option<Arc<Batches>>,
+    cancel: CancellationToken,
+}
+
+struct App {
+    runtime: Handle,
+    run: Option<Run>,
+    entries: Vec<Entry>,
+    compromised: usize,
+    errors: usize,
+    /// Resumes the run when dropped
+    pause: Option<oneshot::Sender<()>>,
+    finished: bool,
+    compromised_only: bool,
+    /// Only rows whose name or ID has this in it, ignoring case
+    search: String,
+    /// Whether keys go to `search`
+    searching: bool,
+    sort: Sort,
+    table: TableState,
+    /// Index into `entries` of the result whose answer is open, and how far
+    /// it's scrolled
+    raw: Option<(usize, u16)>,
+}
+
+impl App {
+    fn new(runtime: Handle) -> Self {
+        Self {
+            runtime,
+            run: None,
+            entries: Vec::new(),
+            compromised: 0,
+            errors: 0,
+            pause: None,
+            finished: false,
+            compromised_only: false,
+            search: String::new(),
+            searching: false,
+            sort: Sort::Arrival,
+            table: TableState::default(),
+            raw: None,
+        }
+    }
+
+    fn run(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        updates: &mut mpsc::UnboundedReceiver<Update>,
+    ) -> io::Result<()> {
+        loop {
+            while !self.finished {
+                match updates.try_recv() {
+                    Ok(update) => self.update(update),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => self.finished = true,
+                }
+            }
+            terminal.draw(|frame| self.draw(frame))?;
+            if !event::poll(TICK)? {
+                continue;
+            }
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                // raw mode swallows the signal
+                let ctrl_c =
+                    key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c');
+                if ctrl_c || (key.code == KeyCode::Char('q') && !self.searching) {
+                    return Ok(());
+                }
+                self.key(key.code);
+            }
+        }
+    }
+
+    /// Cancels the run if it's still going
+    fn quit(&mut self) {
+        self.pause = None;
+        if let (Some(run), false) = (&self.run, self.finished) {
+            tracing::warn!("TUI closed, stopping");
+            run.cancel.cancel();
+        }
+    }
+
+    fn update(&mut self, update: Update) {
+        let entry = match update {
+            Update::Started {
+                total,
+                concurrency,
+                limiter,
+                batches,
+                cancel,
+            } => {
+                self.run = Some(Run {
+                    total,
+                    concurrency,
+                    limiter,
+                    batches,
+                    cancel,
+                });
+                return;
+            }
+            Update::Result(response) => {
+                let status = response.status();
+                if status == Status::Compromised {
+                    self.compromised += 1;
+                }
+                let note = match response.window {
+                    Some(InWindow::Outside) => "outside window",
+                    Some(InWindow::NoTimestamps) => "no dataset timestamps",
+                    _ if response.from_cache => "cached",
+                    _ => "",
+                };
+                Entry {
+                    messages: response.message_count(),
+                    id: response.guild_id,
+                    name: response.guild_name,
+                    source: response.source,
+                    status: Some(status),
+                    note: note.to_owned(),
+                    api_response: Some(response.api_response),
+                }
+            }
+            Update::Failed {
+                guild_id,
+                guild_name,
+                source,
+                message,
+            } => {
+                self.errors += 1;
+                Entry {
+                    id: guild_id,
+                    name: guild_name,
+                    source,
+                    status: None,
+                    note: message,
+                    api_response: None,
+                    messages: None,
+                }
+            }
+        };
+        self.entries.push(entry);
+        if self.table.selected().is_none() {
+            self.table.select_first();
+        }
+    }
+
+    /// Indices into `entries` of the rows shown, in order
+    fn visible(&self) -> Vec<usize> {
+        let search = self.search.to_lowercase();
+        let mut rows: Vec<usize> = (0..self.entries.len())
+            .filter(|&i| {
+                let entry = &self.entries[i];
+                (!self.compromised_only || entry.status == Some(Status::Compromised))
+                    && (entry.name.to_lowercase().contains(&search) || entry.id.contains(&search))
+            })
+            .collect();
+        let entries = &self.entries;
+        // sorts are stable, so ties stay in the order they came in
+        match self.sort {
+            Sort::Arrival => {}
+            Sort::Status => rows.sort_by_key(|&i| entries[i].rank()),
+            Sort::Name => rows.sort_by_cached_key(|&i| entries[i].name.to_lowercase()),
+            Sort::Id => rows.sort_by_key(|&i| entries[i].id.parse::<u64>().unwrap_or(u64::MAX)),
+            Sort::Messages => rows.sort_by_key(|&i| std::cmp::Reverse(entries[i].messages)),
+        }
+        rows
+    }
+
+    fn key(&mut self, code: KeyCode) {
+        if self.searching {
+            match code {
+                KeyCode::Char(c) => self.search.push(c),
+                KeyCode::Backspace => {
+                    self.search.pop();
+                }
+                KeyCode::Enter => self.searching = false,
+                KeyCode::Esc => {
+                    self.search.clear();
+                    self.searching = false;
+                }
+                _ => return,
+            }
+            self.table.select_first();
+            return;
+        }
+        if let Some((_, scroll)) = &mut self.raw {
+            match code {
+                KeyCode::Esc | KeyCode::Enter | KeyCode::Backspace => self.raw = None,
+                KeyCode::Up | KeyCode::Char('k') => *scroll = scroll.saturating_sub(1),
+                KeyCode::Down | KeyCode::Char('j') => *scroll = scroll.saturating_add(1),
+                KeyCode::PageUp => *scroll = scroll.saturating_sub(20),
+                KeyCode::PageDown => *scroll = scroll.saturating_add(20),
+                KeyCode::Home => *scroll = 0,
+                _ => {}
+            }
+            return;
+        }
+        match code {
+            KeyCode::Up | KeyCode::Char('k') => self.table.select_previous(),
+            KeyCode::Down | KeyCode::Char('j') => self.table.select_next(),
+            KeyCode::PageUp => self.table.scroll_up_by(20),
+            KeyCode::PageDown => self.table.scroll_down_by(20),
+            KeyCode::Home => self.table.select_first(),
+            KeyCode::End => self.table.select_last(),
+            KeyCode::Enter => {
+                let selected = self.table.selected();
+                let entry = selected.and_then(|row| self.visible().get(row).copied());
+                if let Some(entry) = entry {
+                    self.raw = Some((entry, 0));
+                }
+            }
+            KeyCode::Char('p') => self.toggle_pause(),
+            KeyCode::Char('c') => {
+                self.compromised_only = !self.compromised_only;
+                self.table.select_first();
+            }
+            KeyCode::Char('s') => self.sort = self.sort.next(),
+            KeyCode::Char('/') => self.searching = true,
+            KeyCode::Esc => {
+                self.search.clear();
+                self.table.select_first();
+            }
+            _ => {}
+        }
+    }
+
+    /// Pausing takes every permit of the limiter, so requests in flight
+    /// finish but no new ones start
+    fn toggle_pause(&mut self) {
+        if self.pause.take().is_some() || self.finished {
+            return;
+        }
+        let Some(run) = &self.run else {
+            return;
+        };
+        let (resume, mut resumed) = oneshot::channel::<()>();
+        let limiter = Arc::clone(&run.limiter);
+        let permits = run.concurrency as u32;
+        self.runtime.spawn(async move {
+            tokio::select! {
+                taken = limiter.acquire_many_owned(permits) => {
+                    let _taken = taken;
+                    let _ = resumed.await;
+                }
+                _ = &mut resumed => {}
+            }
+        });
+        self.pause = Some(resume);
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [gauge, main, logs, help] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(5),
+            Constraint::Length(8),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        self.draw_gauge(frame, gauge);
+        match self.raw {
+            Some((entry, scroll)) => self.draw_raw(frame, main, entry, scroll),
+            // wide enough for the selected answer next to the table
+            None if main.width >= DETAIL_WIDTH => {
+                let [table, detail] =
+                    Layout::horizontal([Constraint::Fill(3), Constraint::Fill(2)]).areas(main);
+                self.draw_table(frame, table);
+                let selected = self.table.selected();
+                match selected.and_then(|row| self.visible().get(row).copied()) {
+                    Some(entry) => self.draw_raw(frame, detail, entry, 0),
+                    None => frame.render_widget(Block::bordered().title("Answer"), detail),
+                }
+            }
+            None => self.draw_table(frame, main),
+        }
+        self.draw_logs(frame, logs);
+
+        let keys = match (self.raw, self.searching) {
+            (Some(_), _) => "↑/↓ scroll  esc back  q quit".to_owned(),
+            (None, true) => format!("search: {}▏  enter done  esc clear", self.search),
+            (None, false) => {
+                "↑/↓ select  enter answer  / search  p pause  c compromised only  s sort  q quit"
+                    .to_owned()
+            }
+        };
+        frame.render_widget(
+            Line::from(keys).style(Style::new().fg(Color::DarkGray)),
+            help,
+        );
+    }
+
+    fn draw_gauge(&self, frame: &mut Frame, area: Rect) {
+        let done = self.entries.len();
+        let total = self.run.as_ref().map_or(0, |run| run.total);
+        let cooling_left = self
+            .run
+            .as_ref()
+            .and_then(|run| run.batches.as_ref()?.cooling_left());
+        let state = match (self.finished, self.pause.is_some(), cooling_left) {
+            (true, _, _) => " · finished".to_owned(),
+            (false, true, _) => " · paused".to_owned(),
+            (false, false, Some(left)) => format!(
+                " · cooling down, {} left",
+                humantime::format_duration(Duration::from_secs(left.as_secs()))
+            ),
+            (false, false, None) => String::new(),
+        };
+        let label = format!(
+            "{done}/{total} · {} compromised · {} errors{state}",
+            self.compromised, self.errors
+        );
+        let ratio = match total {
+            0 => 0.0,
+            total => (done as f64 / total as f64).min(1.0),
+        };
+        let gauge = Gauge::default()
+            .block(Block::bordered().title("spy-pet-checker"))
+            .gauge_style(Style::new().fg(Color::Cyan))
+            .ratio(ratio)
+            .label(label);
+        frame.render_widget(gauge, area);
+    }
+
+    fn draw_table(&mut self, frame: &mut Frame, area: Rect) {
+        let rows: Vec<Row> = self
+            .visible()
+            .into_iter()
+            .map(|i| {
+                let entry = &self.entries[i];
+                Row::new(vec![
+                    entry.status_cell(),
+                    Cell::from(entry.name.clone()),
+                    Cell::from(entry.id.clone()),
+                    Cell::from(entry.source.clone()),
+                    Cell::from(entry.note.clone()),
+                ])
+            })
+            .collect();
+        let mut title = String::from("Results");
+        if self.compromised_only {
+            title.push_str(" (compromised only)");
+        }
+        if !self.search.is_empty() {
+            title.push_str(&format!(" matching {:?}", self.search));
+        }
+        title.push_str(self.sort.title());
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(13),
+                Constraint::Fill(2),
+                Constraint::Length(20),
+                Constraint::Length(12),
+                Constraint::Fill(1),
+            ],
+        )
+        .header(
+            Row::new(["Status", "Server", "ID", "Source", ""])
+                .style(Style::new().add_modifier(Modifier::BOLD)),
+        )
+        .block(Block::bordered().title(title))
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, area, &mut self.table);
+    }
+
+    fn draw_raw(&self, frame: &mut Frame, area: Rect, entry: usize, scroll: u16) {
+        let entry = &self.entries[entry];
+        let text = match &entry.api_response {
+            Some(answer) => serde_json::to_string_pretty(answer).unwrap_or_default(),
+            None => format!("The check failed: {}", entry.note),
+        };
+        let title = format!("{} (ID: {}) on {}", entry.name, entry.id, entry.source);
+        let raw = Paragraph::new(text)
+            .block(Block::bordered().title(title))
+            .scroll((scroll, 0));
+        frame.render_widget(raw, area);
+    }
+
+    fn draw_logs(&self, frame: &mut Frame, area: Rect) {
+        let height = area.height.saturating_sub(2) as usize;
+        let lines: Vec<Line> = match &*log_pane() {
+            Some(pane) => pane
+                .iter()
+                .skip(pane.len().saturating_sub(height))
+                .map(|line| Line::from(Span::raw(line.clone())))
+                .collect(),
+            None => Vec::new(),
+        };
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("Log")),
+            area,
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{self, bail, eyre, Context};
+use reqwest::Client;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use spy_pet_checker::state::StateDir;
+use spy_pet_checker::{build_keyless_client, CheckOptions};
+use tracing::{debug, info};
+
+const RELEASES_URL: &str =
+    "https://api.github.com/repos/slonkazoid/spy-pet-checker/releases/latest";
+const CHECKSUMS_ASSET: &str = "SHA256SUMS";
+
+/// How often the automatic notice may query the release feed
+const NOTICE_INTERVAL: chrono::TimeDelta = chrono::TimeDelta::days(1);
+
+/// Name of the release artifact built for this platform
+fn asset_name() -> String {
+    let platform = if cfg!(all(target_os = "linux", target_env = "musl")) {
+        "linux-musl"
+    } else if cfg!(target_os = "linux") {
+        "linux-gnu"
+    } else if cfg!(target_os = "macos") {
+        "macos"
+    } else if cfg!(target_os = "windows") {
+        "windows.exe"
+    } else {
+        std::env::consts::OS
+    };
+    format!("spy-pet-checker-{}-{platform}", std::env::consts::ARCH)
+}
+
+pub fn current_version() -> Version {
+    Version::parse(env!("CARGO_PKG_VERSION")).expect("package version is semver")
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+#[derive(Deserialize)]
+pub struct Release {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+impl Release {
+    pub fn version(&self) -> eyre::Result<Version> {
+        Version::parse(self.tag_name.trim_start_matches('v'))
+            .with_context(|| format!("release tag {} isn't a version", self.tag_name))
+    }
+
+    fn asset_url(&self, name: &str) -> eyre::Result<&str> {
+        self.assets
+            .iter()
+            .find(|asset| asset.name == name)
+            .map(|asset| asset.browser_download_url.as_str())
+            .ok_or_else(|| eyre!("release {} has no {name}", self.tag_name))
+    }
+}
+
+/// A client that reaches GitHub the way checks reach the backends, through
+/// the same proxy, resolver and certificates, but without the API key
+pub fn client(options: &CheckOptions) -> eyre::Result<Client> {
+    build_keyless_client(options).context("couldn't build HTTP client")
+}
+
+async fn get(client: &Client, url: &str) -> eyre::Result<reqwest::Response> {
+    client
+        .get(url)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .with_context(|| format!("couldn't fetch {url}"))
+}
+
+pub async fn latest_release(client: &Client) -> eyre::Result<Release> {
+    let text = get(client, RELEASES_URL).await?.text().await?;
+    serde_json::from_str(&text).context("couldn't parse the release feed")
+}
+
+/// Finds `asset`'s digest in a `sha256sum`-style listing
+fn expected_digest<'a>(checksums: &'a str, asset: &str) -> Option<&'a str> {
+    checksums.lines().find_map(|line| {
+        let (digest, name) = line.split_once(char::is_whitespace)?;
+        // sha256sum marks binary mode with a leading '*'
+        (name.trim_start().trim_start_matches('*') == asset).then_some(digest)
+    })
+}
+
+/// Downloads this platform's artifact from `release`, checks it against the
+/// release's checksums and swaps it in for the running executable
+pub async fn install(client: &Client, release: &Release) -> eyre::Result<()> {
+    let asset = asset_name();
+    let checksums = get(client, release.asset_url(CHECKSUMS_ASSET)?)
+        .await?
+        .text()
+        .await?;
+    let Some(expected) = expected_digest(&checksums, &asset) else {
+        bail!("{CHECKSUMS_ASSET} has no entry for {asset}");
+    };
+
+    info!("downloading {asset}");
+    let binary = get(client, release.asset_url(&asset)?)
+        .await?
+        .bytes()
+        .await
+        .context("download interrupted")?;
+    let actual = format!("{:x}", Sha256::digest(&binary));
+    if !actual.eq_ignore_ascii_case(expected) {
+        bail!("checksum mismatch for {asset}: expected {expected}, got {actual}");
+    }
+
+    let exe = std::env::current_exe().context("couldn't locate the running executable")?;
+    let tmp = exe.with_extension("update");
+    std::fs::write(&tmp, &binary).with_context(|| format!("couldn't write {}", tmp.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o755))?;
+    }
+    // self_replace takes care of Windows not letting a running .exe be
+    // overwritten
+    let result = self_replace::self_replace(&tmp).context("couldn't replace the executable");
+    let _ = std::fs::remove_file(&tmp);
+    result
+}
+
+#[derive(Serialize, Deserialize)]
+struct NoticeStamp {
+    checked_at: DateTime<Utc>,
+}
+
+/// Queries the release feed if it hasn't been in the last day and returns
+/// the newer version if there is one. Every failure is silent; this runs
+/// alongside real work.
+pub async fn newer_version(state: &StateDir, options: CheckOptions) -> Option<Version> {
+    let path = state.update_check_path().ok()?;
+    if let Ok(bytes) = std::fs::read(&path) {
+        if let Ok(stamp) = serde_json::from_slice::<NoticeStamp>(&bytes) {
+            if Utc::now() - stamp.checked_at < NOTICE_INTERVAL {
+                return None;
+            }
+        }
+    }
+    let stamp = NoticeStamp {
+        checked_at: Utc::now(),
+    };
+    let _ = std::fs::write(&path, serde_json::to_vec(&stamp).ok()?);
+
+    let client = client(&CheckOptions {
+        timeout: Some(Duration::from_secs(5)),
+        ..options
+    })
+    .ok()?;
+    let latest = latest_release(&client)
+        .await
+        .and_then(|release| release.version())
+        .inspect_err(|err| debug!("update check failed: {err:#}"))
+        .ok()?;
+    (latest > current_version()).then_some(latest)
+}
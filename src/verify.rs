@@ -0,0 +1,37 @@
+use std::time::Duration;
+
+use reqwest::Client;
+
+/// Asks again about every guild found compromised, a while after the first
+/// answer, to tell hits from a backend's passing hiccups. The result is
+/// marked with what the second answer said, see
+/// [`Verification`](crate::Verification).
+#[derive(Clone, Debug)]
+pub struct Verifier {
+    delay: Duration,
+    client: Option<Client>,
+}
+
+impl Verifier {
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            client: None,
+        }
+    }
+
+    /// Asks again with `client` instead of the one the first answer came
+    /// through, e.g. one going through another proxy
+    pub fn via(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    pub(crate) fn client(&self) -> Option<&Client> {
+        self.client.as_ref()
+    }
+}
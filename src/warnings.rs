@@ -0,0 +1,144 @@
+use std::fmt;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use thiserror::Error;
+use tracing::{error, warn};
+
+/// Something normally warned about and worked around, which `--strict` makes
+/// an error instead
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    /// An index written name → id, read the other way round
+    FlippedIndex,
+    /// An index with the same key more than once; the last one is used
+    DuplicateKey,
+    /// An index entry without a name, which goes by its ID
+    EmptyName,
+    /// `--only` names an ID that isn't in the index
+    MissingOnly,
+    /// An input, a file or an invite, that couldn't be read and was skipped
+    SkippedInput,
+    /// A data package without a section it usually has
+    MissingSection,
+    /// A config file key this version doesn't know
+    UnknownConfigKey,
+    /// An option that has no effect as given
+    IgnoredOption,
+    /// A lookup or listing that isn't as safe or fresh as asked for
+    Degraded,
+    /// An answer that doesn't say whether the guild is in the dataset
+    Indeterminate,
+    /// An answer that isn't JSON
+    UnparseableBody,
+    /// A deep scan or member scan that failed, leaving the shallow result
+    ScanFailed,
+    /// A backend whose answers no longer look like they used to
+    SchemaDrift,
+}
+
+impl Condition {
+    /// Short, stable label used in error reports
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Condition::FlippedIndex => "flipped_index",
+            Condition::DuplicateKey => "duplicate_key",
+            Condition::EmptyName => "empty_name",
+            Condition::MissingOnly => "missing_only",
+            Condition::SkippedInput => "skipped_input",
+            Condition::MissingSection => "missing_section",
+            Condition::UnknownConfigKey => "unknown_config_key",
+            Condition::IgnoredOption => "ignored_option",
+            Condition::Degraded => "degraded",
+            Condition::Indeterminate => "indeterminate",
+            Condition::UnparseableBody => "unparseable_body",
+            Condition::ScanFailed => "scan_failed",
+            Condition::SchemaDrift => "schema_drift",
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A condition met under `--strict`
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    pub condition: Condition,
+    pub message: String,
+}
+
+/// The violations of a strict run, as its error
+#[derive(Error, Debug)]
+pub struct Violations(pub Vec<Violation>);
+
+impl fmt::Display for Violations {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let n = self.0.len();
+        write!(
+            f,
+            "{n} {} not allowed with --strict:",
+            if n == 1 { "warning" } else { "warnings" }
+        )?;
+        for violation in &self.0 {
+            write!(f, "\n  [{}] {}", violation.condition, violation.message)?;
+        }
+        Ok(())
+    }
+}
+
+/// Where the warn-and-continue conditions go, so `--strict` applies to all
+/// of them alike. Lenient ones are logged as warnings; strict ones are
+/// logged as errors and kept for [`Warnings::check`].
+#[derive(Default, Debug)]
+pub struct Warnings {
+    strict: bool,
+    violations: Mutex<Vec<Violation>>,
+}
+
+impl Warnings {
+    pub fn new(strict: bool) -> Self {
+        Self {
+            strict,
+            violations: Mutex::default(),
+        }
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Reports `condition`. Returns whether to carry on with the workaround,
+    /// which is only the case when not strict.
+    pub fn warn(&self, condition: Condition, message: impl Into<String>) -> bool {
+        let message = message.into();
+        if !self.strict {
+            warn!("{message}");
+            return true;
+        }
+        error!(%condition, "{message}");
+        self.violations
+            .lock()
+            .expect("warnings poisoned")
+            .push(Violation { condition, message });
+        false
+    }
+
+    /// The violations so far, in the order they were met
+    pub fn violations(&self) -> Vec<Violation> {
+        self.violations.lock().expect("warnings poisoned").clone()
+    }
+
+    /// Fails with every violation so far, if there were any
+    pub fn check(&self) -> Result<(), Violations> {
+        let violations = self.violations();
+        match violations.is_empty() {
+            true => Ok(()),
+            false => Err(Violations(violations)),
+        }
+    }
+}
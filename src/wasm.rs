@@ -0,0 +1,161 @@
+//! The checks for a web page, built for `wasm32-unknown-unknown` with the
+//! `wasm` feature. Requests go through the browser's fetch and run side by
+//! side on the page's event loop instead of tokio's, so the index never
+//! leaves the browser except as the lookups themselves.
+//!
+//! From JavaScript, `await check(index, options)` takes the text of an index
+//! in any format the CLI reads and options as JSON, and resolves to
+//! `{"results": [...], "errors": [...]}` with the same records as
+//! `--format json`.
+
+use std::path::Path;
+
+use chrono::Utc;
+use futures_util::{stream, StreamExt};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+use crate::backend::{Backend, KickTheSpy, SpyPet, UrlTemplate};
+use crate::index::{self, IndexFormat};
+use crate::{classify, CheckError, FailedCheck, Kind, Response, Status, Unparseable};
+
+/// Requests in flight at once unless the options say otherwise, like the
+/// CLI's `--concurrency`
+const DEFAULT_CONCURRENCY: usize = 1;
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Options {
+    /// `spypet`, `kickthespy`, or a URL template with `{id}`
+    backend: String,
+    /// Another API root for `spypet` or `kickthespy`, e.g. a proxy that
+    /// allows the page's origin
+    base_url: Option<String>,
+    /// With a URL template, the JSON path of the field saying a guild is in
+    /// the dataset
+    matcher: Option<String>,
+    concurrency: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            backend: "spypet".to_owned(),
+            base_url: None,
+            matcher: None,
+            concurrency: DEFAULT_CONCURRENCY,
+        }
+    }
+}
+
+impl Options {
+    fn backend(&self) -> Result<Box<dyn Backend>, JsError> {
+        let base_url = self.base_url.as_deref();
+        Ok(match self.backend.as_str() {
+            "spypet" => Box::new(base_url.map_or_else(SpyPet::default, SpyPet::new)),
+            "kickthespy" => Box::new(base_url.map_or_else(KickTheSpy::default, KickTheSpy::new)),
+            template => Box::new(
+                UrlTemplate::new(template, self.matcher.as_deref())
+                    .map_err(|err| JsError::new(&err.to_string()))?,
+            ),
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct Checked {
+    results: Vec<Response>,
+    errors: Vec<FailedCheck>,
+}
+
+/// Checks every guild in `index` and resolves to the results as JSON.
+/// `options` is a JSON object, or empty for the defaults.
+#[wasm_bindgen]
+pub async fn check(index: String, options: String) -> Result<String, JsError> {
+    let options: Options = match options.trim() {
+        "" => Options::default(),
+        options => serde_json::from_str(options).map_err(|err| JsError::new(&err.to_string()))?,
+    };
+    let guilds = parse_index(&index)?;
+    let backend = options.backend()?;
+    let client = Client::new();
+
+    let mut checked = Checked {
+        results: Vec::new(),
+        errors: Vec::new(),
+    };
+    let mut outcomes = stream::iter(guilds)
+        .map(|(id, name)| check_guild(&client, &*backend, id, name))
+        .buffer_unordered(options.concurrency.max(1));
+    while let Some(outcome) = outcomes.next().await {
+        match outcome {
+            Ok(result) => checked.results.push(result),
+            Err(failed) => checked.errors.push(failed),
+        }
+    }
+    serde_json::to_string(&checked).map_err(|err| JsError::new(&err.to_string()))
+}
+
+/// The index as id → name, whichever way round and in whichever format it
+/// was written
+fn parse_index(text: &str) -> Result<Vec<(String, String)>, JsError> {
+    let format = IndexFormat::detect(Path::new(""), text);
+    let (guilds, _) =
+        index::parse_as(format, text).map_err(|err| JsError::new(&err.to_string()))?;
+    let guilds = match format.is_map() {
+        true => {
+            index::orient(guilds)
+                .map_err(|err| JsError::new(&err.to_string()))?
+                .0
+        }
+        false => guilds,
+    };
+    Ok(guilds.into_iter().collect())
+}
+
+/// Asks `backend` about one guild, once; the page decides whether to try
+/// again
+async fn check_guild(
+    client: &Client,
+    backend: &dyn Backend,
+    id: String,
+    name: String,
+) -> Result<Response, FailedCheck> {
+    let source = backend.name().to_owned();
+    let (api_response, unparseable, status) = match backend.check(client, &id).await {
+        Ok(api_response) => {
+            let status = classify(&api_response);
+            (api_response, None, status)
+        }
+        Err(CheckError::BadBody { snippet }) => (
+            Value::Null,
+            Some(Unparseable::new(snippet)),
+            Status::Unparseable,
+        ),
+        Err(err) => return Err(FailedCheck::new(id, name, source, 1, err)),
+    };
+    let mut response = Response {
+        guild_id: id,
+        guild_name: name,
+        source,
+        kind: Kind::Guild,
+        checked_at: Some(Utc::now()),
+        labels: Vec::new(),
+        api_response,
+        unparseable,
+        details: None,
+        member_scan: None,
+        headers: None,
+        classification: Some(status),
+        window: None,
+        from_cache: false,
+        fallback: None,
+        request: None,
+        verification: None,
+        severity: None,
+    };
+    response.severity = response.severity();
+    Ok(response)
+}
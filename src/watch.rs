@@ -0,0 +1,149 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Response, Status};
+
+/// Archived message counts must grow by at least this fraction between
+/// cycles to be reported
+pub const SIGNIFICANT_GROWTH: f64 = 0.10;
+
+/// Something that differs between two watch cycles
+#[derive(Serialize, Debug)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum Change {
+    /// Compromised now, clean or unknown before
+    Compromised {
+        guild_id: String,
+        guild_name: String,
+        source: String,
+    },
+    /// Compromised before, clean now
+    Removed {
+        guild_id: String,
+        guild_name: String,
+        source: String,
+    },
+    /// Still compromised, with significantly more archived messages
+    MessagesGrew {
+        guild_id: String,
+        guild_name: String,
+        source: String,
+        from: u64,
+        to: u64,
+    },
+}
+
+type Key = (String, String);
+
+fn key(response: &Response) -> Key {
+    (response.guild_id.clone(), response.source.clone())
+}
+
+/// What the previous cycles found, one result per guild and backend.
+///
+/// Guilds that errored in a cycle keep their last known result, so a
+/// flaky request doesn't show up as a change.
+#[derive(Serialize, Deserialize, Default)]
+pub struct WatchState {
+    pub results: Vec<Response>,
+}
+
+impl WatchState {
+    /// Reads the state saved at `path`; a missing file is an empty state
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match std::fs::read(path) {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Writes the state to `path`, replacing it atomically
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut tmp = PathBuf::from(path);
+        tmp.set_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(self)?)?;
+        std::fs::rename(tmp, path)
+    }
+
+    /// Number of distinct guilds compromised according to any backend
+    pub fn compromised_guilds(&self) -> usize {
+        let ids: BTreeSet<&str> = self
+            .results
+            .iter()
+            .filter(|r| r.is_compromised())
+            .map(|r| r.guild_id.as_str())
+            .collect();
+        ids.len()
+    }
+
+    /// Folds a cycle's results into the state and returns what changed.
+    /// Guilds no longer in `index` are forgotten.
+    pub fn update(
+        &mut self,
+        results: Vec<Response>,
+        index: &BTreeMap<String, String>,
+    ) -> Vec<Change> {
+        let mut known: BTreeMap<Key, Response> = std::mem::take(&mut self.results)
+            .into_iter()
+            .filter(|r| index.contains_key(&r.guild_id))
+            .map(|r| (key(&r), r))
+            .collect();
+
+        let mut changes = Vec::new();
+        for current in results {
+            // like an error, says nothing about the guild
+            if matches!(
+                current.status(),
+                Status::Indeterminate | Status::Unparseable | Status::Unlisted
+            ) {
+                continue;
+            }
+            if let Some(change) = compare(known.get(&key(&current)), &current) {
+                changes.push(change);
+            }
+            known.insert(key(&current), current);
+        }
+
+        self.results = known.into_values().collect();
+        changes
+    }
+}
+
+fn compare(previous: Option<&Response>, current: &Response) -> Option<Change> {
+    let was_compromised = previous.is_some_and(Response::is_compromised);
+    let (guild_id, guild_name, source) = (
+        current.guild_id.clone(),
+        current.guild_name.clone(),
+        current.source.clone(),
+    );
+
+    match (was_compromised, current.is_compromised()) {
+        (false, true) => Some(Change::Compromised {
+            guild_id,
+            guild_name,
+            source,
+        }),
+        (true, false) => Some(Change::Removed {
+            guild_id,
+            guild_name,
+            source,
+        }),
+        (true, true) => {
+            let from = previous?.message_count()?;
+            let to = current.message_count()?;
+            let grew = to > from && (to - from) as f64 >= from as f64 * SIGNIFICANT_GROWTH;
+            grew.then_some(Change::MessagesGrew {
+                guild_id,
+                guild_name,
+                source,
+                from,
+                to,
+            })
+        }
+        (false, false) => None,
+    }
+}
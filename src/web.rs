@@ -0,0 +1,234 @@
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::backend::{fetch, TemplateError, UrlTemplate};
+use crate::{CheckError, Kind};
+
+/// The `source` of results read off the website
+pub const WEB_FALLBACK: &str = "web-fallback";
+
+/// spy.pet's page about a server
+pub const DEFAULT_WEB_URL: &str = "https://spy.pet/servers/{id}";
+
+/// How far a result can be trusted
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Confidence {
+    /// Scraped from a page meant for people, which may have changed since
+    /// the scraper was written
+    Low,
+}
+
+/// Why a result came from the website instead of the API
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Fallback {
+    pub confidence: Confidence,
+    /// The page that was read
+    pub url: String,
+    /// What went wrong with the API
+    pub api_error: String,
+}
+
+/// Reads spy.pet's web pages for guilds whose API request failed with a
+/// server error or a challenge. Only ever asked when the API gave no answer,
+/// never to second-guess one.
+pub struct WebFallback {
+    template: String,
+}
+
+impl WebFallback {
+    /// `template` is the page's URL, with `{id}`
+    pub fn new(template: impl Into<String>) -> Result<Self, TemplateError> {
+        let template = template.into();
+        UrlTemplate::validate(&template)?;
+        Ok(Self { template })
+    }
+
+    pub fn url(&self, id: &str) -> String {
+        let id = utf8_percent_encode(id, NON_ALPHANUMERIC).to_string();
+        self.template.replace("{id}", &id)
+    }
+
+    /// Whether `backend` failing to look up a `kind` with `err` is a reason
+    /// to read the website. Only spy.pet has pages, and only for servers.
+    pub fn applies(&self, backend: &str, kind: Kind, err: &CheckError) -> bool {
+        if backend != "spy.pet" || kind != Kind::Guild {
+            return false;
+        }
+        match err {
+            CheckError::HttpStatus(status) => status.is_server_error(),
+            CheckError::Challenge(_) => true,
+            // a challenge page served with a 200
+            CheckError::BadBody { snippet } => snippet.trim_start().starts_with('<'),
+            _ => false,
+        }
+    }
+
+    /// The page about `id`, read like an API answer: `false` if it says the
+    /// server isn't tracked, an object if it is, and `null` if it can't be
+    /// made out
+    pub(crate) async fn check(&self, client: &Client, id: &str) -> Result<Value, CheckError> {
+        let (status, text) = fetch(client, &self.url(id)).await?;
+        if !status.is_success() {
+            return Err(CheckError::HttpStatus(status));
+        }
+        Ok(scrape(&text))
+    }
+}
+
+/// Reads a server page. `data-tracked` and `data-messages` attributes are
+/// preferred, wherever they are; without them the page's text is searched
+/// for a message count or a "not tracked". Anything else, including a
+/// challenge page, is `null`.
+pub fn scrape(html: &str) -> Value {
+    let tags = tags(html);
+    let tracked = tags.iter().find_map(|tag| attr(tag, "data-tracked"));
+    let messages = tags
+        .iter()
+        .find_map(|tag| attr(tag, "data-messages").or_else(|| attr(tag, "data-message-count")))
+        .and_then(|count| number(&count));
+    let text = text(html).to_lowercase();
+
+    let tracked = match tracked.as_deref().map(str::trim) {
+        Some("true") => true,
+        Some("false") => false,
+        _ if messages.is_some() => true,
+        _ if NOT_TRACKED.iter().any(|phrase| text.contains(phrase)) => false,
+        _ => match count_before(&text, "messages") {
+            Some(count) => return answer(html, &tags, Some(count)),
+            None => return Value::Null,
+        },
+    };
+    match tracked {
+        true => answer(html, &tags, messages),
+        false => Value::Bool(false),
+    }
+}
+
+/// What the pages say about servers that aren't in the dataset
+const NOT_TRACKED: &[&str] = &[
+    "not tracked",
+    "isn't tracked",
+    "is not being tracked",
+    "no data for this server",
+];
+
+fn answer(html: &str, tags: &[&str], messages: Option<u64>) -> Value {
+    let mut answer = Map::new();
+    answer.insert("tracked".to_owned(), Value::Bool(true));
+    let name = tags
+        .iter()
+        .filter(|tag| attr(tag, "property").as_deref() == Some("og:title"))
+        .find_map(|tag| attr(tag, "content"))
+        .or_else(|| title(html));
+    if let Some(name) = name.filter(|name| !name.is_empty()) {
+        answer.insert("name".to_owned(), json!(name));
+    }
+    if let Some(messages) = messages {
+        answer.insert("messages".to_owned(), json!(messages));
+    }
+    Value::Object(answer)
+}
+
+/// The insides of every tag, from after `<` to before `>`
+fn tags(html: &str) -> Vec<&str> {
+    html.split('<')
+        .skip(1)
+        .filter_map(|rest| rest.split_once('>').map(|(tag, _)| tag))
+        .collect()
+}
+
+/// The value of attribute `name` in the insides of a tag, quoted or not,
+/// whatever its case
+fn attr(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let mut from = 0;
+    while let Some(at) = lower[from..].find(name) {
+        let start = from + at;
+        from = start + name.len();
+        let before = lower[..start].chars().next_back();
+        if !before.is_some_and(char::is_whitespace) {
+            continue;
+        }
+        let rest = tag[from..].trim_start();
+        let Some(value) = rest.strip_prefix('=') else {
+            continue;
+        };
+        let value = value.trim_start();
+        let value = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => value[1..].split(quote).next().unwrap_or_default(),
+            _ => value
+                .split(|c: char| c.is_whitespace() || c == '/')
+                .next()
+                .unwrap_or_default(),
+        };
+        return Some(entities(value));
+    }
+    None
+}
+
+fn title(html: &str) -> Option<String> {
+    let lower = html.to_ascii_lowercase();
+    let start = lower.find("<title")?;
+    let start = start + lower[start..].find('>')? + 1;
+    let end = start + lower[start..].find("</title")?;
+    Some(entities(html[start..end].trim()))
+}
+
+/// The text of the page, without tags, scripts and styles
+fn text(html: &str) -> String {
+    let lower = html.to_ascii_lowercase();
+    let mut text = String::new();
+    let mut rest = 0;
+    while let Some(at) = lower[rest..].find('<') {
+        let start = rest + at;
+        text.push_str(&html[rest..start]);
+        text.push(' ');
+        let skip_to = ["script", "style"]
+            .iter()
+            .find(|name| lower[start + 1..].starts_with(*name))
+            .and_then(|name| lower[start..].find(&format!("</{name}")))
+            .map_or(start, |end| start + end);
+        match lower[skip_to..].find('>') {
+            Some(end) => rest = skip_to + end + 1,
+            None => return entities(&text),
+        }
+    }
+    text.push_str(&html[rest..]);
+    entities(&text)
+}
+
+fn entities(text: &str) -> String {
+    text.replace("&nbsp;", " ")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&quot;", "\"")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// The number right before `word` in `text`, like `1,234 messages`. `None`
+/// if there's none, or several different ones to choose from.
+fn count_before(text: &str, word: &str) -> Option<u64> {
+    let mut counts = text.match_indices(word).filter_map(|(at, _)| {
+        let before = text[..at].trim_end();
+        let start = before
+            .char_indices()
+            .rev()
+            .take_while(|&(_, c)| c.is_ascii_digit() || c == ',' || c == '.')
+            .last()?
+            .0;
+        number(&before[start..])
+    });
+    let count = counts.next()?;
+    counts.all(|other| other == count).then_some(count)
+}
+
+/// A count, with or without thousands separators
+fn number(text: &str) -> Option<u64> {
+    let digits: String = text.chars().filter(char::is_ascii_digit).collect();
+    digits.parse().ok()
+}
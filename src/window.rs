@@ -0,0 +1,79 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::answer::GuildInfo;
+
+/// Parses an RFC 3339 timestamp or a `YYYY-MM-DD` date, which is taken as
+/// midnight UTC
+pub fn parse_date(s: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(s) {
+        return Ok(date.to_utc());
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map(|date| {
+            date.and_hms_opt(0, 0, 0)
+                .expect("midnight exists")
+                .and_utc()
+        })
+        .map_err(|_| format!("{s:?} isn't an RFC 3339 timestamp or a YYYY-MM-DD date"))
+}
+
+/// A period of dataset activity that matters, for `--since` and `--until`
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Window {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<DateTime<Utc>>,
+}
+
+/// Where a guild's dataset activity falls relative to a [`Window`]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum InWindow {
+    Inside,
+    /// Seen only before `since` or after `until`
+    Outside,
+    /// The answer doesn't say when the guild was seen
+    NoTimestamps,
+}
+
+/// What's done with compromised guilds outside the window
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowMode {
+    /// They're left out of the results
+    Filter,
+    /// They're kept and marked
+    #[default]
+    Annotate,
+}
+
+/// The window a run used, as recorded with it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct WindowReport {
+    #[serde(flatten)]
+    pub window: Window,
+    pub mode: WindowMode,
+    /// Compromised guilds that were outside the window
+    pub outside: usize,
+}
+
+impl Window {
+    pub fn check(&self, api_response: &Value) -> InWindow {
+        let info = GuildInfo::of(api_response).unwrap_or_default();
+        let (first, last) = (info.first_seen, info.last_seen);
+        // with only one of them, activity is taken to be that instant
+        let (Some(first), Some(last)) = (first.or(last), last.or(first)) else {
+            return InWindow::NoTimestamps;
+        };
+        let before = self.since.is_some_and(|since| last < since);
+        let after = self.until.is_some_and(|until| first > until);
+        if before || after {
+            InWindow::Outside
+        } else {
+            InWindow::Inside
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+const ZIP64_END_OF_CENTRAL_DIRECTORY: u32 = 0x0606_4b50;
+const ZIP64_LOCATOR: u32 = 0x0706_4b50;
+const CENTRAL_DIRECTORY_ENTRY: u32 = 0x0201_4b50;
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+
+/// The extra field holding the 64-bit sizes and offset
+const ZIP64_EXTRA: u16 = 0x0001;
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+/// Entries bigger than this aren't read, so a tiny archive can't fill the
+/// memory
+const MAX_ENTRY_SIZE: u64 = 512 * 1024 * 1024;
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+struct Entry {
+    name: String,
+    method: u16,
+    encrypted: bool,
+    compressed_size: u64,
+    size: u64,
+    offset: u64,
+}
+
+/// A zip archive on disk, read one entry at a time. Supports just enough of
+/// the format for Discord's data packages: stored and deflated entries, with
+/// or without zip64.
+pub struct ZipArchive {
+    file: File,
+    entries: Vec<Entry>,
+}
+
+impl ZipArchive {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let entries = central_directory(&mut file)?;
+        Ok(Self { file, entries })
+    }
+
+    /// Paths of the files in the archive, `/`-separated
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries
+            .iter()
+            .map(|e| e.name.as_str())
+            .filter(|name| !name.ends_with('/'))
+    }
+
+    /// The contents of `name`, if the archive has it
+    pub fn read(&mut self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        let Some(entry) = self.entries.iter().find(|e| e.name == name) else {
+            return Ok(None);
+        };
+        if entry.encrypted {
+            return Err(invalid(format!("{name} is encrypted")));
+        }
+        if entry.size > MAX_ENTRY_SIZE || entry.compressed_size > MAX_ENTRY_SIZE {
+            return Err(invalid(format!("{name} is too big to read")));
+        }
+
+        self.file.seek(SeekFrom::Start(entry.offset))?;
+        let mut header = [0; 30];
+        self.file.read_exact(&mut header)?;
+        if u32le(&header, 0) != LOCAL_HEADER {
+            return Err(invalid(format!("{name} has no local header")));
+        }
+        // the local header's name and extra field can differ from the
+        // central directory's
+        let skip = u16le(&header, 26) as i64 + u16le(&header, 28) as i64;
+        self.file.seek(SeekFrom::Current(skip))?;
+        let mut data = vec![0; entry.compressed_size as usize];
+        self.file.read_exact(&mut data)?;
+
+        let data = match entry.method {
+            STORED => data,
+            DEFLATED => {
+                miniz_oxide::inflate::decompress_to_vec_with_limit(&data, entry.size as usize)
+                    .map_err(|err| invalid(format!("{name} is damaged: {err}")))?
+            }
+            method => {
+                return Err(invalid(format!(
+                    "{name} is compressed with an unsupported method ({method})"
+                )))
+            }
+        };
+        if data.len() as u64 != entry.size {
+            return Err(invalid(format!("{name} is damaged: wrong size")));
+        }
+        Ok(Some(data))
+    }
+}
+
+fn u16le(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn u32le(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+fn u64le(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+/// Reads the list of entries at the end of the archive
+fn central_directory(file: &mut File) -> io::Result<Vec<Entry>> {
+    let len = file.seek(SeekFrom::End(0))?;
+    // the end record is 22 bytes, followed by a comment of up to 64 KiB
+    let tail_len = len.min(22 + u16::MAX as u64);
+    file.seek(SeekFrom::Start(len - tail_len))?;
+    let mut tail = vec![0; tail_len as usize];
+    file.read_exact(&mut tail)?;
+    let end = (0..tail.len().saturating_sub(21))
+        .rev()
+        .find(|&at| u32le(&tail, at) == END_OF_CENTRAL_DIRECTORY)
+        .ok_or_else(|| invalid("not a zip file"))?;
+
+    let mut count = u16le(&tail, end + 10) as u64;
+    let mut size = u32le(&tail, end + 12) as u64;
+    let mut offset = u32le(&tail, end + 16) as u64;
+    if count == u16::MAX as u64 || size == u32::MAX as u64 || offset == u32::MAX as u64 {
+        let locator = end
+            .checked_sub(20)
+            .filter(|&at| u32le(&tail, at) == ZIP64_LOCATOR)
+            .ok_or_else(|| invalid("zip64 archive without its locator"))?;
+        file.seek(SeekFrom::Start(u64le(&tail, locator + 8)))?;
+        let mut record = [0; 56];
+        file.read_exact(&mut record)?;
+        if u32le(&record, 0) != ZIP64_END_OF_CENTRAL_DIRECTORY {
+            return Err(invalid("zip64 end record missing"));
+        }
+        count = u64le(&record, 32);
+        size = u64le(&record, 40);
+        offset = u64le(&record, 48);
+    }
+    if size > MAX_ENTRY_SIZE {
+        return Err(invalid("central directory too big"));
+    }
+
+    file.seek(SeekFrom::Start(offset))?;
+    let mut directory = vec![0; size as usize];
+    file.read_exact(&mut directory)?;
+    let mut entries = Vec::new();
+    let mut at = 0;
+    for _ in 0..count {
+        if directory.len() < at + 46 || u32le(&directory, at) != CENTRAL_DIRECTORY_ENTRY {
+            return Err(invalid("damaged central directory"));
+        }
+        let flags = u16le(&directory, at + 8);
+        let method = u16le(&directory, at + 10);
+        let mut compressed_size = u32le(&directory, at + 20) as u64;
+        let mut size = u32le(&directory, at + 24) as u64;
+        let name_len = u16le(&directory, at + 28) as usize;
+        let extra_len = u16le(&directory, at + 30) as usize;
+        let comment_len = u16le(&directory, at + 32) as usize;
+        let mut offset = u32le(&directory, at + 42) as u64;
+        let name_at = at + 46;
+        let extra_at = name_at + name_len;
+        let next = extra_at + extra_len + comment_len;
+        if directory.len() < next {
+            return Err(invalid("damaged central directory"));
+        }
+        let name = String::from_utf8_lossy(&directory[name_at..extra_at]).into_owned();
+
+        // only the fields that didn't fit are in the zip64 extra field, in
+        // this order
+        let mut extra = &directory[extra_at..extra_at + extra_len];
+        while extra.len() >= 4 {
+            let (id, len) = (u16le(extra, 0), u16le(extra, 2) as usize);
+            let Some(data) = extra.get(4..4 + len) else {
+                break;
+            };
+            if id == ZIP64_EXTRA {
+                let mut fields = data.chunks_exact(8).map(|field| u64le(field, 0));
+                for value in [&mut size, &mut compressed_size, &mut offset] {
+                    if *value == u32::MAX as u64 {
+                        *value = fields
+                            .next()
+                            .ok_or_else(|| invalid("damaged zip64 extra field"))?;
+                    }
+                }
+            }
+            extra = &extra[4 + len..];
+        }
+
+        entries.push(Entry {
+            name,
+            method,
+            encrypted: flags & 1 != 0,
+            compressed_size,
+            size,
+            offset,
+        });
+        at = next;
+    }
+    Ok(entries)
+}
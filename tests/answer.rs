@@ -0,0 +1,80 @@
+use serde_json::json;
+use spy_pet_checker::answer::GuildInfo;
+use spy_pet_checker::Response;
+
+#[test]
+fn guild_info() {
+    let info = GuildInfo::of(&json!({
+        "guild_name": "Leaky",
+        "memberCount": 530,
+        "messages": "lots",
+        "scraped_messages": 1204,
+        "first_seen": "2023-05-01",
+        "lastSeen": 1712016000,
+        "tracked_by": ["a", "b"],
+    }))
+    .unwrap();
+    assert_eq!(info.name.as_deref(), Some("Leaky"));
+    assert_eq!(info.member_count, Some(530));
+    // the first key whose value makes sense wins
+    assert_eq!(info.message_count, Some(1204));
+    assert_eq!(
+        info.first_seen.unwrap().to_rfc3339(),
+        "2023-05-01T00:00:00+00:00"
+    );
+    assert_eq!(
+        info.last_seen.unwrap().to_rfc3339(),
+        "2024-04-02T00:00:00+00:00"
+    );
+    assert_eq!(info.icon, None);
+    // what isn't read stays
+    assert_eq!(info.extra["messages"], "lots");
+    assert_eq!(info.extra["tracked_by"], json!(["a", "b"]));
+    assert!(!info.extra.contains_key("scraped_messages"));
+
+    let written = serde_json::to_value(&info).unwrap();
+    assert_eq!(written["message_count"], 1204);
+    assert_eq!(written["tracked_by"], json!(["a", "b"]));
+    assert_eq!(serde_json::from_value::<GuildInfo>(written).unwrap(), info);
+
+    assert!(GuildInfo::of(&json!(false)).is_none());
+    let response: Response = serde_json::from_value(json!({
+        "guild_id": "1",
+        "guild_name": "Leaky",
+        "source": "spy.pet",
+        "api_response": { "message_count": 7 },
+    }))
+    .unwrap();
+    assert_eq!(response.message_count(), Some(7));
+}
+
+#[test]
+fn authenticated_fields() {
+    let info = GuildInfo::of(&json!({
+        "name": "Leaky",
+        "messageSamples": [
+            { "channelId": "200", "author": "300", "content": "hi", "timestamp": "2024-04-02T10:00:00Z" },
+        ],
+        "users": [
+            { "user_id": "300", "username": "someone", "messages": 41 },
+            { "id": "301" },
+        ],
+    }))
+    .unwrap();
+    assert_eq!(info.message_samples.len(), 1);
+    assert_eq!(info.message_samples[0].channel_id.as_deref(), Some("200"));
+    assert_eq!(info.message_samples[0].author_id.as_deref(), Some("300"));
+    assert_eq!(info.users[0].name.as_deref(), Some("someone"));
+    assert_eq!(info.users[0].message_count, Some(41));
+    assert_eq!(info.users[1].message_count, None);
+    assert!(info.extra.is_empty());
+
+    let written = serde_json::to_value(&info).unwrap();
+    assert_eq!(written["users"][0]["id"], "300");
+    assert_eq!(serde_json::from_value::<GuildInfo>(written).unwrap(), info);
+
+    // users that aren't objects aren't member stats
+    let info = GuildInfo::of(&json!({ "users": 12 })).unwrap();
+    assert!(info.users.is_empty());
+    assert_eq!(info.extra["users"], 12);
+}
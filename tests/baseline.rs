@@ -0,0 +1,61 @@
+use serde_json::{json, Value};
+use tokio::process::Command;
+use wiremock::matchers::path;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[tokio::test]
+async fn only_new_findings() {
+    let server = MockServer::start().await;
+    for (id, answer) in [
+        ("100000000000000001", json!({ "name": "Known" })),
+        ("100000000000000002", json!({ "name": "New" })),
+        ("100000000000000003", json!(false)),
+    ] {
+        Mock::given(path(format!("/servers/{id}")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(answer))
+            .mount(&server)
+            .await;
+    }
+
+    let dir = std::env::temp_dir().join(format!("spy-pet-baseline-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let index = dir.join("index.json");
+    std::fs::write(&index, r#"{"100000000000000001": "Known", "100000000000000002": "New", "100000000000000003": "Clean"}"#).unwrap();
+    let baseline = dir.join("last-week.json");
+    std::fs::write(&baseline, r#"[{"guild_id": "100000000000000001", "guild_name": "Known", "source": "spy.pet", "api_response": {"name": "Known"}}]"#).unwrap();
+
+    let run = |baseline: &std::path::Path| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"));
+        command
+            .arg("--state-dir")
+            .arg(dir.join("state"))
+            .arg("--no-update-check")
+            .arg("--index-path")
+            .arg(&index)
+            .arg("--url-template")
+            .arg(format!("{}/servers/{{id}}", server.uri()))
+            .args(["--format", "json", "--baseline"])
+            .arg(baseline);
+        async move {
+            let output = command.output().await.expect("binary runs");
+            assert!(output.status.success());
+            let output: Value = serde_json::from_slice(&output.stdout).unwrap();
+            let mut ids: Vec<String> = output["results"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|r| r["guild_id"].as_str().unwrap().to_owned())
+                .collect();
+            ids.sort();
+            ids
+        }
+    };
+
+    assert_eq!(
+        run(&baseline).await,
+        ["100000000000000002", "100000000000000003"]
+    );
+    // the run was recorded in full, so next time both are known
+    assert_eq!(run("previous".as_ref()).await, ["100000000000000003"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
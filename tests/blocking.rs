@@ -0,0 +1,42 @@
+#![cfg(feature = "blocking")]
+
+use std::sync::Arc;
+
+use serde_json::json;
+use spy_pet_checker::backend::SpyPet;
+use spy_pet_checker::{blocking, CheckOptions};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[test]
+fn check_without_async() {
+    // the mock server needs a runtime of its own to keep serving while the
+    // blocking call runs on this thread
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let server = runtime.block_on(async {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/servers/1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "name": "Leaky" })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("false"))
+            .mount(&server)
+            .await;
+        server
+    });
+
+    let options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(server.uri()))],
+        ..Default::default()
+    };
+    let guilds =
+        [("1", "leaky"), ("2", "clean")].map(|(id, name)| (id.to_owned(), name.to_owned()));
+    let mut report = blocking::check_guilds(guilds, &options).unwrap();
+    report.results.sort_by(|a, b| a.guild_id.cmp(&b.guild_id));
+
+    assert!(report.failed.is_empty());
+    assert_eq!(report.results[0].api_response, json!({ "name": "Leaky" }));
+    assert_eq!(report.results[1].api_response, json!(false));
+}
@@ -0,0 +1,2379 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::future::BoxFuture;
+use futures_util::{Stream, StreamExt};
+use reqwest::header::HeaderMap;
+use reqwest::{Client, StatusCode};
+use serde_json::{json, Value};
+use spy_pet_checker::answer::GuildInfo;
+use spy_pet_checker::backend::{
+    Backend, Fixtures, KickTheSpy, Simulated, SimulationProfile, SpyPet, UrlTemplate, FIXTURES,
+    SIMULATED,
+};
+use spy_pet_checker::cache::{CacheEntry, ResultCache};
+use spy_pet_checker::deep::DeepScan;
+use spy_pet_checker::discord::{GuildLister, TokenType};
+use spy_pet_checker::dns::DohResolver;
+use spy_pet_checker::dump::{DumpMeta, ResponseDump};
+use spy_pet_checker::i18n::Lang;
+use spy_pet_checker::members::{BotList, KnownBot, MemberScan, MemberScanner};
+use spy_pet_checker::merge::parse_results;
+use spy_pet_checker::output::{
+    group, sort_results, to_toml, to_yaml, Csv, Formatter, GroupBy, GroupSummary, Html, Json,
+    JsonDocument, JsonLines, Junit, Markdown, Ndjson, OutputTemplate, Plain, SortBy, Templated,
+    JSON_SCHEMA_VERSION,
+};
+use spy_pet_checker::pacing::{AdaptiveConcurrency, Batches, Spacing};
+use spy_pet_checker::retry::RetryPolicy;
+use spy_pet_checker::secret::Secret;
+use spy_pet_checker::severity::{Level, Severity};
+use spy_pet_checker::shared::{is_multi_label, shared};
+use spy_pet_checker::verify::Verifier;
+use spy_pet_checker::warnings::{Condition, Warnings};
+use spy_pet_checker::web::{Confidence, WebFallback, WEB_FALLBACK};
+use spy_pet_checker::{
+    check_guilds, check_stream, classify, parse_header, ApiKey, ApiKeyError, ApiKeyStatus,
+    BodyKind, CheckError, CheckOptions, CheckResult, Checker, ErrorKind, FailedCheck, HeaderError,
+    Kind, Response, RunReport, SchemaDrift, Status, Unparseable, Verification, USER_AGENT,
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use wiremock::matchers::{header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const CLEAN: &str = "100000000000000001";
+const COMPROMISED: &str = "100000000000000002";
+const RATE_LIMITED: &str = "100000000000000003";
+const SERVER_ERROR: &str = "100000000000000004";
+const SLOW: &str = "100000000000000005";
+const HTML: &str = "100000000000000006";
+const EMPTY: &str = "100000000000000007";
+const TRUNCATED: &str = "100000000000000008";
+const NEW_FIELDS: &str = "100000000000000010";
+
+async fn mock_api() -> MockServer {
+    let server = MockServer::start().await;
+    let respond = |id: &str, response: ResponseTemplate| {
+        Mock::given(method("GET"))
+            .and(path(format!("/servers/{id}")))
+            .respond_with(response)
+    };
+
+    respond(CLEAN, ResponseTemplate::new(200).set_body_string("false"))
+        .mount(&server)
+        .await;
+    respond(
+        COMPROMISED,
+        ResponseTemplate::new(200).set_body_json(json!({ "name": "Leaky" })),
+    )
+    .mount(&server)
+    .await;
+    respond(
+        RATE_LIMITED,
+        ResponseTemplate::new(429).insert_header("Retry-After", "7"),
+    )
+    .mount(&server)
+    .await;
+    respond(SERVER_ERROR, ResponseTemplate::new(500))
+        .mount(&server)
+        .await;
+    respond(
+        SLOW,
+        ResponseTemplate::new(200)
+            .set_body_string("false")
+            .set_delay(Duration::from_secs(5)),
+    )
+    .mount(&server)
+    .await;
+    respond(
+        HTML,
+        ResponseTemplate::new(200).set_body_string("<html><body>Bad gateway</body></html>"),
+    )
+    .mount(&server)
+    .await;
+    respond(
+        NEW_FIELDS,
+        ResponseTemplate::new(200)
+            .set_body_json(json!({ "name": "Leaky", "messages": 12, "first_seen": "2024-04-01" })),
+    )
+    .mount(&server)
+    .await;
+    respond(EMPTY, ResponseTemplate::new(200))
+        .mount(&server)
+        .await;
+    respond(
+        TRUNCATED,
+        ResponseTemplate::new(200).set_body_string(r#"{"name": "Lea"#),
+    )
+    .mount(&server)
+    .await;
+
+    server
+}
+
+async fn check_one(server: &MockServer, id: &str) -> CheckResult {
+    let options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(server.uri()))],
+        timeout: Some(Duration::from_millis(500)),
+        ..Default::default()
+    };
+    let mut stream = check_stream([(id.to_owned(), format!("guild {id}"))], &options);
+    let result = stream.next().await.expect("one result");
+    assert!(stream.next().await.is_none());
+    result
+}
+
+#[tokio::test]
+async fn clean_guild() {
+    let server = mock_api().await;
+    let response = check_one(&server, CLEAN).await.unwrap();
+    assert_eq!(response.guild_id, CLEAN);
+    assert_eq!(response.guild_name, format!("guild {CLEAN}"));
+    assert_eq!(response.api_response, json!(false));
+}
+
+#[tokio::test]
+async fn compromised_guild() {
+    let server = mock_api().await;
+    let response = check_one(&server, COMPROMISED).await.unwrap();
+    assert_eq!(response.api_response, json!({ "name": "Leaky" }));
+}
+
+#[tokio::test]
+async fn rate_limited() {
+    let server = mock_api().await;
+    let failed = check_one(&server, RATE_LIMITED).await.unwrap_err();
+    assert_eq!(failed.guild_id, RATE_LIMITED);
+    assert_eq!(failed.kind, ErrorKind::RateLimited);
+    assert_eq!(failed.attempts, 1);
+    let err = failed.error.unwrap();
+    assert!(matches!(
+        err,
+        CheckError::RateLimited { retry_after: Some(d) } if d == Duration::from_secs(7)
+    ));
+    assert!(err.is_retryable());
+}
+
+#[tokio::test]
+async fn server_error() {
+    let server = mock_api().await;
+    let err = check_one(&server, SERVER_ERROR)
+        .await
+        .unwrap_err()
+        .error
+        .unwrap();
+    assert!(matches!(
+        err,
+        CheckError::HttpStatus(StatusCode::INTERNAL_SERVER_ERROR)
+    ));
+    assert!(err.is_retryable());
+}
+
+#[tokio::test]
+async fn retries() {
+    let server = MockServer::start().await;
+    // fails twice, then answers
+    Mock::given(path(format!("/servers/{CLEAN}")))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(2)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(path(format!("/servers/{CLEAN}")))
+        .respond_with(ResponseTemplate::new(200).set_body_string("false"))
+        .mount(&server)
+        .await;
+    Mock::given(path(format!("/servers/{SERVER_ERROR}")))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&server)
+        .await;
+    let options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(server.uri()))],
+        retry: RetryPolicy::new(3, Duration::from_millis(10)),
+        ..Default::default()
+    };
+    let guilds = [CLEAN, SERVER_ERROR].map(|id| (id.to_owned(), id.to_owned()));
+    let report = check_guilds(guilds, &options).await;
+    assert_eq!(report.results.len(), 1);
+    assert_eq!(report.failed[0].guild_id, SERVER_ERROR);
+    assert_eq!(report.failed[0].attempts, 4);
+    assert_eq!(server.received_requests().await.unwrap().len(), 3 + 4);
+    let request = report.results[0].request.unwrap();
+    assert_eq!((request.attempts, request.http_status), (3, Some(200)));
+    assert!(request.duration_ms >= 20.0, "{request:?}");
+    assert_eq!(report.performance.unwrap().retries, 2 + 3);
+
+    let policy = RetryPolicy::new(3, Duration::from_secs(1));
+    let server_error = CheckError::HttpStatus(StatusCode::BAD_GATEWAY);
+    let delays: Vec<_> = (1..=4).map(|n| policy.delay(n, &server_error)).collect();
+    let secs = |s| Some(Duration::from_secs(s));
+    assert_eq!(delays, [secs(1), secs(2), secs(4), None]);
+    let rate_limited = |s| CheckError::RateLimited {
+        retry_after: Some(Duration::from_secs(s)),
+    };
+    assert_eq!(policy.delay(1, &rate_limited(7)), secs(7));
+    assert_eq!(policy.delay(1, &rate_limited(3600)), None);
+    let unauthorized = CheckError::Unauthorized(StatusCode::UNAUTHORIZED);
+    assert_eq!(policy.delay(1, &unauthorized), None);
+    assert_eq!(RetryPolicy::NONE.delay(1, &server_error), None);
+}
+
+#[tokio::test]
+async fn retry_passes() {
+    let server = MockServer::start().await;
+    // rate limited the first time through, answers in the second pass
+    Mock::given(path(format!("/servers/{CLEAN}")))
+        .respond_with(ResponseTemplate::new(429))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(path(format!("/servers/{CLEAN}")))
+        .respond_with(ResponseTemplate::new(200).set_body_string("false"))
+        .mount(&server)
+        .await;
+    Mock::given(path(format!("/servers/{SERVER_ERROR}")))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&server)
+        .await;
+    Mock::given(path(format!("/servers/{COMPROMISED}")))
+        .respond_with(ResponseTemplate::new(401))
+        .mount(&server)
+        .await;
+    let options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(server.uri()))],
+        concurrency: 4,
+        retry_passes: 2,
+        ..Default::default()
+    };
+    let guilds = [CLEAN, SERVER_ERROR, COMPROMISED].map(|id| (id.to_owned(), id.to_owned()));
+    let report = check_guilds(guilds, &options).await;
+    assert_eq!(report.results.len(), 1);
+    assert_eq!(report.results[0].guild_id, CLEAN);
+    let mut failed: Vec<_> = report.failed.iter().map(|f| f.guild_id.as_str()).collect();
+    failed.sort();
+    assert_eq!(failed, [COMPROMISED, SERVER_ERROR]);
+    // a 401 isn't worth another pass, a 500 gets both
+    let requests = server.received_requests().await.unwrap();
+    let asked = |id| {
+        requests
+            .iter()
+            .filter(|r| r.url.path().ends_with(id))
+            .count()
+    };
+    assert_eq!(
+        (asked(CLEAN), asked(SERVER_ERROR), asked(COMPROMISED)),
+        (2, 3, 1)
+    );
+}
+
+#[tokio::test]
+async fn adaptive_concurrency() {
+    let server = MockServer::start().await;
+    // struggles at first, then recovers
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(8)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_string("false")
+                .set_delay(Duration::from_millis(10)),
+        )
+        .mount(&server)
+        .await;
+    let adaptive = AdaptiveConcurrency::new(8);
+    let options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(server.uri()))],
+        concurrency: 8,
+        adaptive: Some(Arc::clone(&adaptive)),
+        retry: RetryPolicy::new(3, Duration::from_millis(5)),
+        ..Default::default()
+    };
+    let guilds = (0..60).map(|i| (format!("1000000000000000{i:02}"), format!("guild {i}")));
+    let report = check_guilds(guilds, &options).await;
+    assert_eq!(report.results.len(), 60, "{:?}", report.failed);
+    // the first 503s come back together and count as one
+    assert_eq!(adaptive.lowest(), 4);
+    assert_eq!(adaptive.current(), 8);
+}
+
+/// An API answering `false` to everything over keep-alive connections,
+/// counting the connections opened to it
+async fn counting_api() -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let connections = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&connections);
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            counter.fetch_add(1, Ordering::SeqCst);
+            tokio::spawn(async move {
+                let mut buf = vec![0; 4096];
+                // requests have no body, so each read is one of them
+                while matches!(stream.read(&mut buf).await, Ok(n) if n > 0) {
+                    let response = "HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\nfalse";
+                    if stream.write_all(response.as_bytes()).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    (url, connections)
+}
+
+#[tokio::test]
+async fn connection_reuse() {
+    let guilds = || (0..20).map(|i| (format!("1000000000000000{i:02}"), format!("guild {i}")));
+    let (url, connections) = counting_api().await;
+    let options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(&url))],
+        ..Default::default()
+    };
+    let report = check_guilds(guilds(), &options).await;
+    assert_eq!(report.results.len(), 20, "{:?}", report.failed);
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+    let (url, connections) = counting_api().await;
+    let options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(&url))],
+        pool_size: Some(0),
+        ..Default::default()
+    };
+    let report = check_guilds(guilds(), &options).await;
+    assert_eq!(report.results.len(), 20, "{:?}", report.failed);
+    assert_eq!(connections.load(Ordering::SeqCst), 20);
+}
+
+#[tokio::test]
+async fn spawned_as_needed() {
+    let (url, _) = counting_api().await;
+    let options = CheckOptions {
+        concurrency: 4,
+        backends: vec![Arc::new(SpyPet::new(&url))],
+        ..Default::default()
+    };
+    let guilds = (0..2000).map(|i| (format!("10000000000000{i:04}"), format!("guild {i}")));
+    let mut stream = check_stream(guilds, &options);
+    let (spawned, total) = stream.size_hint();
+    assert!(spawned < 2000, "{spawned} checks spawned up front");
+    assert_eq!(total, Some(2000));
+
+    let mut checked = 0;
+    while let Some(result) = stream.next().await {
+        assert!(result.is_ok(), "{result:?}");
+        checked += 1;
+    }
+    assert_eq!(checked, 2000);
+}
+
+#[tokio::test]
+async fn timeout() {
+    let server = mock_api().await;
+    let err = check_one(&server, SLOW).await.unwrap_err().error.unwrap();
+    assert!(matches!(err, CheckError::Timeout), "{err:?}");
+}
+
+#[test]
+fn oddball_bodies() {
+    let cases = [
+        ("false", Status::Clean),
+        ("true", Status::Compromised),
+        (r#"{"name": "Leaky"}"#, Status::Compromised),
+        ("null", Status::Indeterminate),
+        ("0", Status::Indeterminate),
+        ("1", Status::Indeterminate),
+        (r#""false""#, Status::Indeterminate),
+        (r#""False""#, Status::Indeterminate),
+        ("[]", Status::Indeterminate),
+        ("{}", Status::Indeterminate),
+    ];
+    for (body, status) in cases {
+        let value: Value = serde_json::from_str(body).unwrap();
+        assert_eq!(classify(&value), status, "{body}");
+    }
+}
+
+#[tokio::test]
+async fn indeterminate_body() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("null"))
+        .mount(&server)
+        .await;
+    let response = check_one(&server, CLEAN).await.unwrap();
+    assert_eq!(response.status(), Status::Indeterminate);
+    assert!(!response.is_compromised());
+    let json = serde_json::to_value(&response).unwrap();
+    assert_eq!(json["status"], "indeterminate");
+}
+
+async fn unparseable(id: &str) -> Unparseable {
+    let server = mock_api().await;
+    let response = check_one(&server, id).await.unwrap();
+    assert_eq!(response.guild_id, id);
+    assert_eq!(response.status(), Status::Unparseable);
+    assert!(!response.is_compromised());
+    response.unparseable.unwrap()
+}
+
+#[tokio::test]
+async fn html_body() {
+    let body = unparseable(HTML).await;
+    assert_eq!(body.body, BodyKind::Html);
+    assert!(body.snippet.starts_with("<html>"));
+}
+
+#[tokio::test]
+async fn strict() {
+    let server = mock_api().await;
+    let warnings = Arc::new(Warnings::new(true));
+    let options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(server.uri()))],
+        warnings: Arc::clone(&warnings),
+        ..Default::default()
+    };
+    let guilds = [CLEAN, HTML].map(|id| (id.to_owned(), id.to_owned()));
+    let report = check_guilds(guilds, &options).await;
+    assert_eq!(report.results.len(), 1);
+    assert_eq!(report.results[0].guild_id, CLEAN);
+    let failed = &report.failed[0];
+    assert_eq!(
+        (failed.guild_id.as_str(), failed.kind),
+        (HTML, ErrorKind::Strict)
+    );
+
+    let violations = warnings.violations();
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].condition, Condition::UnparseableBody);
+    let err = warnings.check().unwrap_err().to_string();
+    assert!(
+        err.starts_with("1 warning not allowed with --strict:\n  [unparseable_body] "),
+        "{err}"
+    );
+
+    let lenient = Warnings::new(false);
+    assert!(lenient.warn(Condition::FlippedIndex, "read the other way round"));
+    assert!(lenient.violations().is_empty());
+    assert!(lenient.check().is_ok());
+}
+
+#[tokio::test]
+async fn empty_body() {
+    let body = unparseable(EMPTY).await;
+    assert_eq!(body.body, BodyKind::Empty);
+    assert_eq!(body.snippet, "");
+}
+
+#[tokio::test]
+async fn truncated_json() {
+    let body = unparseable(TRUNCATED).await;
+    assert_eq!(body.body, BodyKind::Other);
+    assert_eq!(body.snippet, r#"{"name": "Lea"#);
+}
+
+#[tokio::test]
+async fn too_large() {
+    let server = MockServer::start().await;
+    let padding = "x".repeat(64 * 1024);
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "name": padding })))
+        .mount(&server)
+        .await;
+    let check = |max_response_size| {
+        let options = CheckOptions {
+            backends: vec![Arc::new(SpyPet::new(server.uri()))],
+            max_response_size,
+            ..Default::default()
+        };
+        async move {
+            let guilds = [(COMPROMISED.to_owned(), "Leaky".to_owned())];
+            check_stream(guilds, &options).next().await.unwrap()
+        }
+    };
+
+    let failed = check(Some(1024)).await.unwrap_err();
+    assert_eq!(failed.kind, ErrorKind::TooLarge);
+    assert_eq!(failed.attempts, 1);
+    assert!(matches!(
+        failed.error,
+        Some(CheckError::TooLarge { limit: 1024 })
+    ));
+    assert!(check(Some(1 << 20)).await.unwrap().is_compromised());
+    assert!(check(None).await.unwrap().is_compromised());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn full_index_current_thread() {
+    full_index().await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn full_index_multi_thread() {
+    full_index().await
+}
+
+async fn full_index() {
+    let server = mock_api().await;
+    let options = CheckOptions {
+        concurrency: 3,
+        backends: vec![Arc::new(SpyPet::new(server.uri()))],
+        timeout: Some(Duration::from_millis(500)),
+        ..Default::default()
+    };
+    let guilds = [CLEAN, COMPROMISED, RATE_LIMITED, SERVER_ERROR, SLOW, HTML]
+        .map(|id| (id.to_owned(), id.to_owned()));
+
+    let results: Vec<CheckResult> = check_stream(guilds, &options).collect().await;
+    let (ok, err): (Vec<_>, Vec<_>) = results.into_iter().partition(Result::is_ok);
+
+    let mut ok: Vec<String> = ok.into_iter().map(|r| r.unwrap().guild_id).collect();
+    ok.sort();
+    assert_eq!(ok, [CLEAN, COMPROMISED, HTML]);
+
+    let mut kinds: Vec<&str> = err
+        .iter()
+        .map(|r| r.as_ref().unwrap_err().kind.as_str())
+        .collect();
+    kinds.sort();
+    assert_eq!(kinds, ["http_status", "rate_limited", "timeout"]);
+}
+
+#[tokio::test]
+async fn performance() {
+    let server = mock_api().await;
+    let options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(server.uri()))],
+        ..Default::default()
+    };
+    let guilds = [CLEAN, COMPROMISED, SERVER_ERROR].map(|id| (id.to_owned(), id.to_owned()));
+
+    let report = check_guilds(guilds, &options).await;
+    let perf = report.performance.unwrap();
+    assert_eq!(perf.requests, 3);
+    // "false", the compromised guild's JSON and the error body
+    assert!(perf.bytes_received >= "false".len() as u64 + 2);
+    let latency = perf.latency.unwrap();
+    assert!(latency.p50_ms <= latency.p95_ms && latency.p95_ms <= latency.max_ms);
+    assert!(perf.requests_per_second > 0.0);
+}
+
+/// spy.pet, except that checking `PANICS` panics
+struct Buggy(SpyPet);
+
+const PANICS: &str = "100000000000000009";
+
+impl Backend for Buggy {
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    fn url(&self, id: &str) -> String {
+        self.0.url(id)
+    }
+
+    fn check<'a>(
+        &'a self,
+        client: &'a Client,
+        id: &'a str,
+    ) -> BoxFuture<'a, Result<Value, CheckError>> {
+        assert_ne!(id, PANICS, "bug in body parsing");
+        self.0.check(client, id)
+    }
+}
+
+#[tokio::test]
+async fn panicking_check() {
+    let server = mock_api().await;
+    let options = CheckOptions {
+        concurrency: 3,
+        backends: vec![Arc::new(Buggy(SpyPet::new(server.uri())))],
+        ..Default::default()
+    };
+    let guilds = [CLEAN, PANICS, COMPROMISED].map(|id| (id.to_owned(), id.to_owned()));
+
+    let results: Vec<CheckResult> = check_stream(guilds, &options).collect().await;
+    let (ok, err): (Vec<_>, Vec<_>) = results.into_iter().partition(Result::is_ok);
+    let mut ok: Vec<String> = ok.into_iter().map(|r| r.unwrap().guild_id).collect();
+    ok.sort();
+    assert_eq!(ok, [CLEAN, COMPROMISED]);
+    let failed = match &err[..] {
+        [Err(failed)] => failed,
+        err => panic!("unexpected errors {err:?}"),
+    };
+    assert_eq!(failed.guild_id, PANICS);
+    assert_eq!(failed.kind, ErrorKind::Panic);
+    assert!(failed.message.contains("bug in body parsing"));
+}
+
+#[tokio::test]
+async fn schema_drift() {
+    let server = mock_api().await;
+    let options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(server.uri()))],
+        ..Default::default()
+    };
+    let guilds = [CLEAN, COMPROMISED, NEW_FIELDS].map(|id| (id.to_owned(), id.to_owned()));
+
+    let report = check_guilds(guilds, &options).await;
+    // drift is advisory, every guild still has its result
+    assert_eq!(report.results.len(), 3);
+    assert_eq!(
+        report.schema_drift,
+        [SchemaDrift {
+            source: "spy.pet".to_owned(),
+            unknown_fields: ["first_seen".to_owned()].into(),
+            missing_fields: ["messages".to_owned()].into(),
+        }]
+    );
+}
+
+#[tokio::test]
+async fn result_cache() {
+    let server = mock_api().await;
+    let path = std::env::temp_dir().join(format!("spy-pet-cache-{}.json", std::process::id()));
+    // a truncated file is a miss, not an error
+    std::fs::write(&path, r#"{"version": 1, "entr"#).unwrap();
+    let guilds = || [CLEAN, COMPROMISED, HTML].map(|id| (id.to_owned(), id.to_owned()));
+    let run = |cache: ResultCache| {
+        let options = CheckOptions {
+            backends: vec![Arc::new(SpyPet::new(server.uri()))],
+            cache: Some(Arc::new(cache)),
+            ..Default::default()
+        };
+        async move {
+            let report = check_guilds(guilds(), &options).await;
+            options.cache.unwrap().save().unwrap();
+            report
+        }
+    };
+
+    let first = run(ResultCache::load(&path, Duration::from_secs(3600))).await;
+    assert!(first.results.iter().all(|r| !r.from_cache));
+
+    let second = run(ResultCache::load(&path, Duration::from_secs(3600))).await;
+    let mut cached: Vec<&str> = second
+        .results
+        .iter()
+        .filter(|r| r.from_cache)
+        .map(|r| r.guild_id.as_str())
+        .collect();
+    cached.sort();
+    // unparseable answers aren't worth keeping
+    assert_eq!(cached, [CLEAN, COMPROMISED]);
+    let compromised = second.results.iter().find(|r| r.guild_id == COMPROMISED);
+    assert_eq!(
+        compromised.unwrap().api_response,
+        json!({ "name": "Leaky" })
+    );
+
+    let expired = run(ResultCache::load(&path, Duration::ZERO)).await;
+    assert!(expired.results.iter().all(|r| !r.from_cache));
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn fixtures() {
+    let dir = std::env::temp_dir().join(format!("spy-pet-fixtures-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join(format!("{COMPROMISED}.json")),
+        r#"{"name": "Leaky"}"#,
+    )
+    .unwrap();
+    std::fs::write(dir.join(format!("{HTML}.json")), "<html></html>").unwrap();
+    let options = CheckOptions {
+        backends: vec![Arc::new(Fixtures::new(&dir))],
+        ..Default::default()
+    };
+    let guilds = [CLEAN, COMPROMISED, HTML].map(|id| (id.to_owned(), id.to_owned()));
+    let mut report = check_guilds(guilds, &options).await;
+    report.results.sort_by(|a, b| a.guild_id.cmp(&b.guild_id));
+
+    let statuses: Vec<_> = report.results.iter().map(|r| r.status()).collect();
+    assert_eq!(
+        statuses,
+        [Status::Clean, Status::Compromised, Status::Unparseable]
+    );
+    assert!(report.results.iter().all(|r| r.source == FIXTURES));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn conditional_requests() {
+    let server = MockServer::start().await;
+    let etag = r#""v1""#;
+    Mock::given(header("if-none-match", etag))
+        .respond_with(ResponseTemplate::new(304).insert_header("etag", etag))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("etag", etag)
+                .set_body_json(json!({ "name": "Leaky" })),
+        )
+        .mount(&server)
+        .await;
+
+    let path = std::env::temp_dir().join(format!("spy-pet-etag-{}.json", std::process::id()));
+    let run = || {
+        let options = CheckOptions {
+            backends: vec![Arc::new(SpyPet::new(server.uri()))],
+            cache: Some(Arc::new(ResultCache::load(&path, Duration::ZERO))),
+            ..Default::default()
+        };
+        async move {
+            let guilds = [(COMPROMISED.to_owned(), "Leaky".to_owned())];
+            let mut report = check_guilds(guilds, &options).await;
+            options.cache.unwrap().save().unwrap();
+            report.results.pop().expect("a result")
+        }
+    };
+
+    let first = run().await;
+    let second = run().await;
+    // answered by the server both times, but without a body the second
+    assert!(!second.from_cache);
+    assert_eq!(second.api_response, first.api_response);
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 2);
+    assert!(!requests[0].headers.contains_key("if-none-match"));
+    assert_eq!(requests[1].headers["if-none-match"], etag);
+    let entries = ResultCache::entries(&path).unwrap();
+    assert_eq!(entries[0].entry.validators.etag.as_deref(), Some(etag));
+
+    std::fs::remove_file(&path).unwrap();
+    let _ = std::fs::remove_file(path.with_extension("json.lock"));
+}
+
+#[test]
+fn cache_management() {
+    let path = std::env::temp_dir().join(format!("spy-pet-cache-mgmt-{}.json", std::process::id()));
+    let entry = |days: i64| CacheEntry {
+        api_response: json!(false),
+        checked_at: chrono::Utc::now() - chrono::Duration::days(days),
+        validators: Default::default(),
+    };
+    let ttl = Duration::from_secs(3600);
+    let cache = ResultCache::load(&path, ttl);
+    cache.insert("spy.pet", CLEAN, entry(0));
+    cache.insert("spy.pet", COMPROMISED, entry(10));
+    cache.insert("kickthespy", COMPROMISED, entry(1));
+    cache.save().unwrap();
+
+    // loaded before the removal, saved after it
+    let running = ResultCache::load(&path, ttl);
+    assert_eq!(ResultCache::remove(&path, COMPROMISED).unwrap(), 2);
+    running.insert("spy.pet", HTML, entry(0));
+    running.save().unwrap();
+    let ids = |path| -> Vec<String> {
+        let entries = ResultCache::entries(path).unwrap();
+        entries.into_iter().map(|a| a.guild_id).collect()
+    };
+    assert_eq!(ids(&path), [CLEAN, HTML]);
+
+    let old = ResultCache::load(&path, ttl);
+    old.insert("spy.pet", SLOW, entry(10));
+    old.save().unwrap();
+    let week = Duration::from_secs(7 * 86400);
+    assert_eq!(ResultCache::clear(&path, Some(week)).unwrap(), 1);
+    assert_eq!(ids(&path), [CLEAN, HTML]);
+    assert_eq!(ResultCache::clear(&path, None).unwrap(), 2);
+    assert!(ids(&path).is_empty());
+
+    std::fs::remove_file(&path).unwrap();
+    let _ = std::fs::remove_file(path.with_extension("json.lock"));
+}
+
+#[tokio::test]
+async fn response_dump() {
+    let server = mock_api().await;
+    let dir = std::env::temp_dir().join(format!("spy-pet-dump-{}", std::process::id()));
+    let run = |overwrite| {
+        let options = CheckOptions {
+            backends: vec![Arc::new(SpyPet::new(server.uri()))],
+            dump: Some(Arc::new(ResponseDump::new(&dir, 1, overwrite).unwrap())),
+            ..Default::default()
+        };
+        async move {
+            let guilds = [COMPROMISED, HTML].map(|id| (id.to_owned(), id.to_owned()));
+            check_guilds(guilds, &options).await;
+            options.dump.unwrap().kept()
+        }
+    };
+
+    assert_eq!(run(false).await, 0);
+    let body = std::fs::read_to_string(dir.join(format!("{COMPROMISED}.json"))).unwrap();
+    assert_eq!(
+        serde_json::from_str::<Value>(&body).unwrap(),
+        json!({ "name": "Leaky" })
+    );
+    let meta = std::fs::read(dir.join(format!("{HTML}.meta.json"))).unwrap();
+    let meta: DumpMeta = serde_json::from_slice(&meta).unwrap();
+    assert_eq!(meta.status, 200);
+    assert_eq!(meta.body_file, format!("{HTML}.txt"));
+    assert!(dir.join(&meta.body_file).exists());
+
+    assert_eq!(run(false).await, 2);
+    assert_eq!(run(true).await, 0);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+async fn deep_scan(endpoints: &[&str]) -> RunReport {
+    let server = mock_api().await;
+    for (page, body) in [
+        ("1", json!([{ "id": 1 }, { "id": 2 }])),
+        ("2", json!([{ "id": 3 }])),
+    ] {
+        Mock::given(method("GET"))
+            .and(path(format!("/servers/{COMPROMISED}/channels")))
+            .and(query_param("page", page))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .mount(&server)
+            .await;
+    }
+    Mock::given(method("GET"))
+        .and(path(format!("/servers/{COMPROMISED}/channels")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(format!("/servers/{COMPROMISED}/stats")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "messages": 12 })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(format!("/servers/{COMPROMISED}/broken")))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&server)
+        .await;
+
+    let endpoints = endpoints
+        .iter()
+        .map(|e| format!("{}{e}", server.uri()))
+        .collect();
+    let options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(server.uri()))],
+        deep_scan: Some(Arc::new(DeepScan::new(endpoints).unwrap())),
+        ..Default::default()
+    };
+    let guilds = [CLEAN, COMPROMISED].map(|id| (id.to_owned(), id.to_owned()));
+    check_guilds(guilds, &options).await
+}
+
+#[tokio::test]
+async fn deep_scan_pages() {
+    let report = deep_scan(&["/servers/{id}/channels?page={page}", "/servers/{id}/stats"]).await;
+    assert!(report.failed.is_empty());
+    let clean = report.results.iter().find(|r| r.guild_id == CLEAN).unwrap();
+    assert!(clean.details.is_none());
+
+    let compromised = report.compromised().next().unwrap();
+    let deep = &compromised.details.as_ref().unwrap().deep;
+    assert_eq!(deep.len(), 2);
+    assert_eq!(
+        deep.values().next().unwrap(),
+        &json!([{ "id": 1 }, { "id": 2 }, { "id": 3 }])
+    );
+    assert_eq!(deep.values().nth(1).unwrap(), &json!({ "messages": 12 }));
+}
+
+#[tokio::test]
+async fn deep_scan_failure_keeps_result() {
+    // nothing is mounted there, so wiremock answers 404, which is empty
+    let report = deep_scan(&["/nowhere/{id}"]).await;
+    let compromised = report.compromised().next().unwrap();
+    let deep = &compromised.details.as_ref().unwrap().deep;
+    assert_eq!(deep.values().next().unwrap(), &Value::Null);
+
+    let server_error = deep_scan(&["/servers/{id}/stats", "/servers/{id}/broken"]).await;
+    let compromised = server_error.compromised().next().unwrap();
+    assert!(compromised.details.is_none());
+    assert!(server_error.failed.is_empty());
+}
+
+#[tokio::test]
+async fn cancel_midway() {
+    let server = mock_api().await;
+    let options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(server.uri()))],
+        ..Default::default()
+    };
+    // with one ticket, the clean guild finishes, the slow one is cancelled
+    // while reading its response and the compromised one while waiting for a
+    // ticket
+    let guilds = [CLEAN, SLOW, COMPROMISED].map(|id| (id.to_owned(), id.to_owned()));
+
+    let cancel = options.cancel.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        cancel.cancel();
+    });
+
+    let started = std::time::Instant::now();
+    let report = check_guilds(guilds, &options).await;
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert!(report.cancelled);
+    assert!(report.failed.is_empty());
+    assert!(report.results.len() <= 1);
+}
+
+#[tokio::test]
+async fn drain_midway() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_string("false")
+                .set_delay(Duration::from_millis(600)),
+        )
+        .mount(&server)
+        .await;
+    let options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(server.uri()))],
+        ..Default::default()
+    };
+    // with one ticket, the first guild's request is in flight when the run
+    // winds down, so it finishes; the others are never sent
+    let guilds = ["1", "2", "3"].map(|id| (id.to_owned(), id.to_owned()));
+
+    let drain = options.drain.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        drain.cancel();
+    });
+
+    let report = check_guilds(guilds, &options).await;
+    assert!(report.cancelled);
+    assert_eq!(report.results.len(), 1);
+    assert_eq!(report.results[0].guild_id, "1");
+    assert_eq!(server.received_requests().await.unwrap().len(), 1);
+
+    let mut out = Vec::new();
+    Json::default().write_results(&mut out, &report).unwrap();
+    let document: Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(document["cancelled"], true);
+    assert_eq!(document["results"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn kickthespy() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/getBot"))
+        .and(query_param("id", COMPROMISED))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "username": "spy" })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/getBot"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&server)
+        .await;
+
+    let options = CheckOptions {
+        backends: vec![Arc::new(KickTheSpy::new(server.uri()))],
+        ..Default::default()
+    };
+    let guilds = [CLEAN, COMPROMISED].map(|id| (id.to_owned(), id.to_owned()));
+    let mut report = check_guilds(guilds, &options).await;
+    report.results.sort_by(|a, b| a.guild_id.cmp(&b.guild_id));
+
+    assert!(report.failed.is_empty());
+    assert_eq!(report.results[0].api_response, json!(false));
+    assert_eq!(report.results[1].api_response, json!({ "username": "spy" }));
+    assert!(report.results.iter().all(|r| r.source == "kickthespy.pet"));
+}
+
+#[tokio::test]
+async fn users() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/users/{COMPROMISED}")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "guilds": 3 })))
+        .mount(&server)
+        .await;
+
+    let options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(server.uri()).with_kind(Kind::User))],
+        kind: Kind::User,
+        ..Default::default()
+    };
+    let users = [(COMPROMISED.to_owned(), "alice".to_owned())];
+    let report = check_guilds(users, &options).await;
+
+    assert!(report.failed.is_empty());
+    let result = &report.results[0];
+    assert!(result.is_compromised());
+    assert_eq!(result.kind, Kind::User);
+    let written = serde_json::to_value(result).unwrap();
+    assert_eq!(written["kind"], "user");
+
+    // records from before there were users are servers
+    let mut old = written;
+    old.as_object_mut().unwrap().remove("kind");
+    let old: spy_pet_checker::Response = serde_json::from_value(old).unwrap();
+    assert_eq!(old.kind, Kind::Guild);
+}
+
+#[tokio::test]
+async fn channels() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/channels/{COMPROMISED}")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "messages": 120 })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(format!("/channels/{CLEAN}")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!(false)))
+        .mount(&server)
+        .await;
+
+    let options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(server.uri()).with_kind(Kind::Channel))],
+        kind: Kind::Channel,
+        ..Default::default()
+    };
+    let channels = [COMPROMISED, CLEAN].map(|id| (id.to_owned(), id.to_owned()));
+    let mut report = check_guilds(channels, &options).await;
+    report.results.sort_by(|a, b| a.guild_id.cmp(&b.guild_id));
+
+    assert!(report.failed.is_empty());
+    assert!(!report.results[0].is_compromised());
+    assert!(report.results[1].is_compromised());
+    assert_eq!(
+        serde_json::to_value(&report.results[1]).unwrap()["kind"],
+        "channel"
+    );
+    let mut out = Vec::new();
+    Plain {
+        include_clean: true,
+        ..Default::default()
+    }
+    .write_results(&mut out, &report)
+    .unwrap();
+    let plain = String::from_utf8(out).unwrap();
+    assert!(plain.contains(&format!(
+        "Channel {COMPROMISED} (ID: {COMPROMISED}) had its messages scraped!"
+    )));
+    assert!(plain.contains(&format!(
+        "Channel {CLEAN} (ID: {CLEAN}) isn't in the dataset"
+    )));
+}
+
+#[tokio::test]
+async fn users_next_to_servers() {
+    let server = mock_api().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/users/{COMPROMISED}")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "guilds": 3 })))
+        .mount(&server)
+        .await;
+
+    let options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(server.uri()))],
+        ..Default::default()
+    };
+    let user_options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(server.uri()).with_kind(Kind::User))],
+        kind: Kind::User,
+        ..options.clone()
+    };
+    let mut results = check_stream([(CLEAN.to_owned(), "guild".to_owned())], &options);
+    results.add(
+        [(COMPROMISED.to_owned(), "alice".to_owned())],
+        &user_options,
+    );
+    let results: Vec<_> = results.map(Result::unwrap).collect().await;
+
+    let groups = group(GroupBy::Kind, &results);
+    let keys: Vec<&str> = groups.iter().map(|g| g.key.as_str()).collect();
+    assert_eq!(keys, ["servers", "users"]);
+    assert_eq!(groups[0].summary.compromised, 0);
+    assert_eq!(groups[1].results[0].guild_name, "alice");
+    assert!(groups[1].results[0].is_compromised());
+}
+
+#[tokio::test]
+async fn labelled_results() {
+    let server = mock_api().await;
+    let options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(server.uri()))],
+        ..Default::default()
+    };
+    let guilds = [CLEAN, COMPROMISED].map(|id| (id.to_owned(), id.to_owned()));
+    let mut report = check_guilds(guilds, &options).await;
+    for result in &mut report.results {
+        result.labels = match result.guild_id.as_str() {
+            COMPROMISED => vec!["alice".to_owned(), "bob".to_owned()],
+            _ => vec!["alice".to_owned()],
+        };
+    }
+
+    let groups = group(GroupBy::Label, &report.results);
+    let counts: Vec<(&str, usize, usize)> = groups
+        .iter()
+        .map(|g| (g.key.as_str(), g.summary.total, g.summary.compromised))
+        .collect();
+    assert_eq!(counts, [("alice", 2, 1), ("bob", 1, 1)]);
+    let total = GroupSummary::of(&report.results);
+    assert_eq!((total.total, total.compromised), (2, 1));
+
+    let mut out = Vec::new();
+    Plain::grouped(GroupBy::Label)
+        .write_results(&mut out, &report)
+        .unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(
+        out.contains("is compromised! (severity: low, 15/100) [alice, bob]"),
+        "{out}"
+    );
+    assert!(out.contains("Overall: 2 checked, 1 compromised\n"), "{out}");
+
+    assert!(is_multi_label(&report.results));
+    let shared = shared(&report.results);
+    assert_eq!(shared.len(), 1);
+    assert_eq!(shared[0].guild_id, COMPROMISED);
+    assert_eq!(shared[0].labels, ["alice", "bob"]);
+    assert!(out.ends_with("2 of 2 labels (alice, bob)\n"), "{out}");
+}
+
+#[tokio::test]
+async fn checker() {
+    let server = mock_api().await;
+    let checker = Checker::new().endpoint(server.uri()).concurrency(2);
+    let response = checker.check(COMPROMISED, "Leaky").await.unwrap().unwrap();
+    assert!(response.is_compromised());
+    let failed = checker
+        .check(SERVER_ERROR, "Down")
+        .await
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(failed.kind, ErrorKind::HttpStatus);
+
+    let report = checker
+        .check_all([
+            (CLEAN.to_owned(), "Clean".to_owned()),
+            (COMPROMISED.to_owned(), "Leaky".to_owned()),
+        ])
+        .await;
+    assert_eq!(report.results.len(), 2, "{:?}", report.failed);
+
+    // a client of the caller's own is used as it is
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(header("x-bot", "modbot"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("false"))
+        .mount(&server)
+        .await;
+    let client = Client::builder()
+        .default_headers(
+            [("x-bot".parse().unwrap(), "modbot".parse().unwrap())]
+                .into_iter()
+                .collect(),
+        )
+        .build()
+        .unwrap();
+    let checker = Checker::new().endpoint(server.uri()).client(client);
+    let response = checker.check(CLEAN, "Clean").await.unwrap().unwrap();
+    assert_eq!(response.status(), Status::Clean);
+
+    checker.options().cancel.cancel();
+    assert!(checker.check(CLEAN, "Clean").await.is_none());
+}
+
+#[tokio::test]
+async fn api_key() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/servers/{CLEAN}")))
+        .and(header("authorization", "Bearer hunter2"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!(false)))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(401))
+        .mount(&server)
+        .await;
+    let options = |api_key| CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(server.uri()))],
+        api_key,
+        ..Default::default()
+    };
+    let key = |header| ApiKey::new(header, &Secret::new("hunter2".to_owned())).unwrap();
+
+    let report = check_guilds(
+        [(CLEAN.to_owned(), CLEAN.to_owned())],
+        &options(Some(key("Authorization"))),
+    )
+    .await;
+    assert_eq!(report.results.len(), 1, "{:?}", report.failed);
+    assert_eq!(report.api_key, Some(ApiKeyStatus::Set));
+
+    for api_key in [None, Some(key("X-Api-Key"))] {
+        let report = check_guilds([(CLEAN.to_owned(), CLEAN.to_owned())], &options(api_key)).await;
+        let failed = &report.failed[0];
+        assert_eq!(failed.kind, ErrorKind::Unauthorized);
+        assert!(!failed.error.as_ref().unwrap().is_retryable());
+    }
+
+    assert!(matches!(
+        ApiKey::new("bad header", &Secret::new("key".to_owned())),
+        Err(ApiKeyError::BadHeader(_))
+    ));
+    assert!(matches!(
+        ApiKey::new("x-api-key", &Secret::new("new\nline".to_owned())),
+        Err(ApiKeyError::BadKey)
+    ));
+}
+
+#[tokio::test]
+async fn custom_headers() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(header("user-agent", "mirror-bot/1.0"))
+        .and(header("x-mirror-token", "abc"))
+        .and(header("cookie", "session=1; theme=dark"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!(false)))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(header("user-agent", USER_AGENT))
+        .respond_with(ResponseTemplate::new(418))
+        .mount(&server)
+        .await;
+    let mut headers = HeaderMap::new();
+    for line in ["X-Mirror-Token: abc", "Cookie:session=1; theme=dark"] {
+        let (name, value) = parse_header(line).unwrap();
+        headers.append(name, value);
+    }
+    let options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(server.uri()))],
+        headers,
+        user_agent: Some("mirror-bot/1.0".to_owned()),
+        ..Default::default()
+    };
+    let guilds = [(CLEAN.to_owned(), CLEAN.to_owned())];
+    let report = check_guilds(guilds.clone(), &options).await;
+    assert_eq!(report.results.len(), 1, "{:?}", report.failed);
+
+    // the default agent, and no extra headers
+    let options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(server.uri()))],
+        ..Default::default()
+    };
+    let report = check_guilds(guilds, &options).await;
+    assert_eq!(report.failed[0].kind, ErrorKind::HttpStatus);
+
+    assert!(matches!(
+        parse_header("no colon"),
+        Err(HeaderError::Format(_))
+    ));
+    assert!(matches!(
+        parse_header("bad name: x"),
+        Err(HeaderError::Name(_))
+    ));
+}
+
+#[tokio::test]
+async fn member_scan() {
+    let server = mock_api().await;
+    let discord = MockServer::start().await;
+    let members = |ids: &[&str]| {
+        let members: Vec<Value> = ids
+            .iter()
+            .map(|id| json!({ "user": { "id": id } }))
+            .collect();
+        ResponseTemplate::new(200).set_body_json(members)
+    };
+    // rate limited once, then answered
+    Mock::given(method("GET"))
+        .and(path(format!("/guilds/{CLEAN}/members")))
+        .respond_with(
+            ResponseTemplate::new(429)
+                .set_body_json(json!({ "retry_after": 0.01, "global": false })),
+        )
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&discord)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(format!("/guilds/{CLEAN}/members")))
+        .and(query_param("after", "0"))
+        .and(header("authorization", "Bot sekrit"))
+        .respond_with(members(&["1", "42", "3"]))
+        .mount(&discord)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(format!("/guilds/{COMPROMISED}/members")))
+        .respond_with(ResponseTemplate::new(403).set_body_json(json!({ "code": 50001 })))
+        .mount(&discord)
+        .await;
+
+    let bots = BotList::parse("# scrapers\n42 watcher\n43\n").unwrap();
+    assert_eq!(bots.len(), 2);
+    assert!(BotList::parse("bot 42").is_err());
+    let scanner =
+        MemberScanner::new(Secret::new("sekrit".to_owned()), bots).with_api(discord.uri());
+    let options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(server.uri()))],
+        member_scan: Some(Arc::new(scanner)),
+        ..Default::default()
+    };
+    let guilds = [CLEAN, COMPROMISED].map(|id| (id.to_owned(), id.to_owned()));
+    let report = check_guilds(guilds, &options).await;
+    assert!(report.failed.is_empty());
+
+    let clean = report.results.iter().find(|r| r.guild_id == CLEAN).unwrap();
+    assert_eq!(
+        clean.member_scan,
+        Some(MemberScan::Scanned {
+            members: 3,
+            bots: vec![KnownBot {
+                id: "42".to_owned(),
+                label: "watcher".to_owned()
+            }],
+        })
+    );
+    let compromised = report.compromised().next().unwrap();
+    assert!(matches!(
+        compromised.member_scan,
+        Some(MemberScan::Skipped { .. })
+    ));
+    let written = serde_json::to_value(compromised).unwrap();
+    assert_eq!(written["member_scan"]["result"], "skipped");
+}
+
+#[tokio::test]
+async fn guild_list() {
+    let discord = MockServer::start().await;
+    let guilds = |ids: std::ops::Range<u64>| {
+        let guilds: Vec<Value> = ids
+            .map(|i| json!({ "id": (FIRST + i).to_string(), "name": format!("Server {i}"), "owner": false }))
+            .collect();
+        ResponseTemplate::new(200).set_body_json(guilds)
+    };
+    const FIRST: u64 = 200000000000000000;
+    Mock::given(method("GET"))
+        .and(path("/users/@me/guilds"))
+        .and(query_param("after", "0"))
+        .and(header("authorization", "sekrit"))
+        .respond_with(guilds(0..200))
+        .mount(&discord)
+        .await;
+    let last = (FIRST + 199).to_string();
+    // the second page is rate limited once, then answered
+    Mock::given(method("GET"))
+        .and(path("/users/@me/guilds"))
+        .and(query_param("after", last.as_str()))
+        .respond_with(ResponseTemplate::new(429).set_body_json(json!({ "retry_after": 0.01 })))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&discord)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/users/@me/guilds"))
+        .and(query_param("after", last.as_str()))
+        .respond_with(guilds(200..203))
+        .mount(&discord)
+        .await;
+
+    let lister =
+        GuildLister::new(Secret::new("sekrit".to_owned()), TokenType::User).with_api(discord.uri());
+    let listed = lister.guilds(&Client::new()).await.unwrap();
+    assert_eq!(listed.len(), 203);
+    assert_eq!(listed[&(FIRST + 202).to_string()], "Server 202");
+
+    // a bot token is sent as one, and refused here
+    let lister =
+        GuildLister::new(Secret::new("sekrit".to_owned()), TokenType::Bot).with_api(discord.uri());
+    let err = lister.guilds(&Client::new()).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::HttpStatus);
+}
+
+#[tokio::test]
+async fn rate_limit_headers() {
+    let server = MockServer::start().await;
+    for (id, remaining) in [(CLEAN, "7"), (COMPROMISED, "3")] {
+        Mock::given(method("GET"))
+            .and(path(format!("/servers/{id}")))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string("false")
+                    .insert_header("x-ratelimit-remaining", remaining)
+                    .insert_header("x-ratelimit-limit", "10")
+                    .insert_header("cf-ray", "8a1b2c3d4e5f-AMS")
+                    .insert_header("x-unrelated", "noise"),
+            )
+            .mount(&server)
+            .await;
+    }
+    let guilds = [CLEAN, COMPROMISED].map(|id| (id.to_owned(), id.to_owned()));
+    let mut options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(server.uri()))],
+        ..Default::default()
+    };
+
+    let report = check_guilds(guilds.clone(), &options).await;
+    let performance = report.performance.unwrap();
+    assert_eq!(performance.min_rate_limit_remaining, Some(3));
+    assert!(report.results.iter().all(|r| r.headers.is_none()));
+
+    options.keep_headers = true;
+    let report = check_guilds(guilds, &options).await;
+    let clean = report.results.iter().find(|r| r.guild_id == CLEAN).unwrap();
+    let headers = clean.headers.as_ref().unwrap();
+    assert_eq!(headers["x-ratelimit-remaining"], "7");
+    assert_eq!(headers["cf-ray"], "8a1b2c3d4e5f-AMS");
+    assert!(!headers.contains_key("x-unrelated"));
+}
+
+#[tokio::test]
+async fn batches() {
+    let server = mock_api().await;
+    let batches = Batches::new(2, Duration::from_millis(200));
+    assert_eq!(batches.cooldowns_left(5), Duration::from_millis(400));
+    let options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(server.uri()))],
+        concurrency: 4,
+        batches: Some(Arc::clone(&batches)),
+        ..Default::default()
+    };
+    let guilds =
+        [CLEAN, COMPROMISED, SERVER_ERROR, HTML, EMPTY].map(|id| (id.to_owned(), id.to_owned()));
+
+    let started = std::time::Instant::now();
+    let mut results = check_stream(guilds, &options);
+    let mut done = 0;
+    while results.next().await.is_some() {
+        done += 1;
+        if done == 2 {
+            assert!(batches.cooling_left().is_some());
+        }
+    }
+    assert_eq!(done, 5);
+    // three batches, two cooldowns, and none after the last one
+    assert!(started.elapsed() >= Duration::from_millis(400));
+    assert!(batches.cooling_left().is_none());
+    assert_eq!(batches.cooled(), Duration::from_millis(400));
+}
+
+#[tokio::test]
+async fn spacing() {
+    let server = mock_api().await;
+    let options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(server.uri()))],
+        concurrency: 4,
+        spacing: Some(Spacing::new(
+            Duration::from_millis(100),
+            Duration::from_millis(50),
+        )),
+        ..Default::default()
+    };
+    let guilds = [CLEAN, COMPROMISED, HTML, EMPTY].map(|id| (id.to_owned(), id.to_owned()));
+
+    let started = std::time::Instant::now();
+    let report = check_guilds(guilds, &options).await;
+    assert_eq!(report.results.len(), 4);
+    // the first goes out right away, the other three a delay apart however
+    // many are allowed in flight
+    let elapsed = started.elapsed();
+    assert!(elapsed >= Duration::from_millis(300), "{elapsed:?}");
+    assert!(elapsed < Duration::from_millis(600), "{elapsed:?}");
+}
+
+#[tokio::test]
+async fn simulated() {
+    let profile = SimulationProfile {
+        latency: (Duration::ZERO, Duration::ZERO),
+        compromised: 1.0,
+        rate_limited: 0.0,
+        retry_after: None,
+        server_errors: 0.0,
+    };
+    let guilds = Simulated::guilds(3);
+    assert_eq!(guilds.len(), 3);
+    assert!(guilds
+        .values()
+        .all(|name| name.starts_with("Simulated server")));
+    let options = CheckOptions {
+        backends: vec![Arc::new(Simulated::new(profile))],
+        ..Default::default()
+    };
+    let report = check_guilds(guilds.clone(), &options).await;
+    assert_eq!(report.compromised().count(), 3);
+    assert!(report.results.iter().all(|r| r.source == SIMULATED));
+
+    let mut out = Vec::new();
+    Plain::default().write_results(&mut out, &report).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("SIMULATED RUN"), "{out}");
+
+    let options = CheckOptions {
+        backends: vec![Arc::new(Simulated::new(SimulationProfile {
+            rate_limited: 1.0,
+            retry_after: Some(Duration::from_secs(5)),
+            ..profile
+        }))],
+        ..Default::default()
+    };
+    let report = check_guilds(guilds, &options).await;
+    assert!(report.results.is_empty());
+    assert_eq!(report.failed.len(), 3);
+    assert!(report
+        .failed
+        .iter()
+        .all(|f| f.kind == ErrorKind::RateLimited));
+}
+
+#[tokio::test]
+async fn web_fallback() {
+    const CHALLENGE: &str = "100000000000000011";
+    const NO_PAGE: &str = "100000000000000012";
+    let server = mock_api().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/servers/{CHALLENGE}")))
+        .respond_with(ResponseTemplate::new(403).insert_header("cf-mitigated", "challenge"))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(format!("/servers/{NO_PAGE}")))
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&server)
+        .await;
+    let page = std::fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/web/tracked.html"
+    ))
+    .unwrap();
+    for id in [CLEAN, SERVER_ERROR, HTML, RATE_LIMITED, CHALLENGE] {
+        Mock::given(method("GET"))
+            .and(path(format!("/web/{id}")))
+            .respond_with(ResponseTemplate::new(200).set_body_string(page.clone()))
+            .mount(&server)
+            .await;
+    }
+
+    let options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(server.uri()))],
+        web_fallback: Some(Arc::new(
+            WebFallback::new(format!("{}/web/{{id}}", server.uri())).unwrap(),
+        )),
+        ..Default::default()
+    };
+    let guilds = [CLEAN, SERVER_ERROR, HTML, RATE_LIMITED, CHALLENGE, NO_PAGE]
+        .map(|id| (id.to_owned(), id.to_owned()));
+    let report = check_guilds(guilds, &options).await;
+
+    let result = |id| report.results.iter().find(|r| r.guild_id == id);
+    // a definitive answer is never second-guessed
+    let clean = result(CLEAN).unwrap();
+    assert_eq!(
+        (clean.source.as_str(), clean.status()),
+        ("spy.pet", Status::Clean)
+    );
+    assert!(clean.fallback.is_none());
+    for id in [SERVER_ERROR, HTML, CHALLENGE] {
+        let scraped = result(id).unwrap();
+        assert_eq!(scraped.source, WEB_FALLBACK);
+        assert!(scraped.is_compromised());
+        assert_eq!(scraped.api_response["messages"], 48213);
+        let fallback = scraped.fallback.as_ref().unwrap();
+        assert_eq!(fallback.confidence, Confidence::Low);
+        assert!(fallback.url.ends_with(&format!("/web/{id}")));
+    }
+
+    let failed = |id| report.failed.iter().find(|f| f.guild_id == id).unwrap();
+    assert_eq!(failed(RATE_LIMITED).kind, ErrorKind::RateLimited);
+    // without a page to read, the API's error stands
+    assert_eq!(failed(NO_PAGE).kind, ErrorKind::HttpStatus);
+    assert_eq!(failed(NO_PAGE).source, "spy.pet");
+
+    let mut out = Vec::new();
+    Plain::default().write_results(&mut out, &report).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(
+        out.contains("(read off the web page, low confidence)"),
+        "{out}"
+    );
+}
+
+#[tokio::test]
+async fn verify() {
+    const FLAKY: &str = "100000000000000011";
+    let server = mock_api().await;
+    // compromised the first time, gone when asked again
+    Mock::given(method("GET"))
+        .and(path(format!("/servers/{FLAKY}")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "name": "Fluke" })))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(format!("/servers/{FLAKY}")))
+        .respond_with(ResponseTemplate::new(200).set_body_string("false"))
+        .mount(&server)
+        .await;
+
+    let options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(server.uri()))],
+        verify: Some(Arc::new(Verifier::new(Duration::ZERO))),
+        ..Default::default()
+    };
+    let guilds = [CLEAN, COMPROMISED, FLAKY].map(|id| (id.to_owned(), id.to_owned()));
+    let report = check_guilds(guilds, &options).await;
+
+    let result = |id| report.results.iter().find(|r| r.guild_id == id).unwrap();
+    assert_eq!(result(CLEAN).verification, None);
+    assert_eq!(
+        result(COMPROMISED).verification,
+        Some(Verification::Confirmed)
+    );
+    // the first answer stands, marked as such
+    assert!(result(FLAKY).is_compromised());
+    assert_eq!(result(FLAKY).verification, Some(Verification::Flaky));
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 5);
+
+    let mut out = Vec::new();
+    Plain::default().write_results(&mut out, &report).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("(confirmed when asked again)"), "{out}");
+    assert!(
+        out.contains("(flaky, not compromised when asked again)"),
+        "{out}"
+    );
+}
+
+#[tokio::test]
+async fn url_template() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/guild/{COMPROMISED}")))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({ "guild": { "tracked": true } })),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({ "guild": { "tracked": false } })),
+        )
+        .mount(&server)
+        .await;
+
+    let template = format!("{}/guild/{{id}}", server.uri());
+    let backend = UrlTemplate::new(&template, Some(".guild.tracked")).unwrap();
+    let options = CheckOptions {
+        backends: vec![Arc::new(backend)],
+        ..Default::default()
+    };
+    let guilds = [CLEAN, COMPROMISED, "a b/c"].map(|id| (id.to_owned(), id.to_owned()));
+    let mut report = check_guilds(guilds, &options).await;
+    report.results.sort_by(|a, b| a.guild_id.cmp(&b.guild_id));
+
+    assert!(report.failed.is_empty());
+    assert_eq!(report.results[0].api_response, json!(false));
+    assert_eq!(
+        report.results[1].api_response["guild"]["tracked"],
+        json!(true)
+    );
+    assert!(report.results.iter().all(|r| r.source == template));
+
+    let requested = server.received_requests().await.unwrap();
+    assert!(requested.iter().any(|r| r.url.path() == "/guild/a%20b%2Fc"));
+    assert!(UrlTemplate::new("https://example.com/", None).is_err());
+}
+
+/// A DNS answer in wire format with one A record, or none and `rcode`
+fn dns_answer(ip: Option<[u8; 4]>, rcode: u8) -> Vec<u8> {
+    let answers = u8::from(ip.is_some());
+    let mut message = vec![0, 0, 0x81, 0x80 | rcode, 0, 0, 0, answers, 0, 0, 0, 0];
+    if let Some(ip) = ip {
+        // root name, A, IN, TTL 300, 4 bytes
+        message.extend_from_slice(&[0, 0, 1, 0, 1, 0, 0, 1, 0x2c, 0, 4]);
+        message.extend_from_slice(&ip);
+    }
+    message
+}
+
+#[tokio::test]
+async fn custom_resolution() {
+    let api = mock_api().await;
+    let port = api.address().port();
+    let doh = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/dns-query"))
+        .and(header("content-type", "application/dns-message"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            dns_answer(Some([127, 0, 0, 1]), 0),
+            "application/dns-message",
+        ))
+        .mount(&doh)
+        .await;
+    let nxdomain = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_raw(dns_answer(None, 3), "application/dns-message"),
+        )
+        .mount(&nxdomain)
+        .await;
+    let doh_at = |server: &MockServer| {
+        let url = format!("{}/dns-query", server.uri()).parse().unwrap();
+        Some(DohResolver::new(url, Client::new()))
+    };
+    let check = |options: CheckOptions| async move {
+        let mut stream = check_stream([(CLEAN.to_owned(), CLEAN.to_owned())], &options);
+        stream.next().await.expect("one result")
+    };
+    let backends = vec![Arc::new(SpyPet::new(format!("http://spy.test:{port}"))) as _];
+
+    let pinned = CheckOptions {
+        backends: backends.clone(),
+        resolve: vec![("spy.test".to_owned(), [127, 0, 0, 1].into())],
+        ..Default::default()
+    };
+    assert_eq!(check(pinned).await.unwrap().api_response, json!(false));
+
+    let resolved = CheckOptions {
+        backends: backends.clone(),
+        doh: doh_at(&doh),
+        ..Default::default()
+    };
+    assert_eq!(check(resolved).await.unwrap().api_response, json!(false));
+
+    let unknown = CheckOptions {
+        backends,
+        doh: doh_at(&nxdomain),
+        ..Default::default()
+    };
+    let failed = check(unknown).await.unwrap_err();
+    assert_eq!(failed.kind, ErrorKind::Dns);
+    let message = failed.error.unwrap().to_string();
+    assert!(message.contains("spy.test"), "{message}");
+    assert!(message.contains("DNS-over-HTTPS server"), "{message}");
+    assert!(message.contains("no such domain"), "{message}");
+}
+
+#[test]
+fn csv_output() {
+    let results = [
+        json!({ "guild_id": "1", "guild_name": "=cmd|' /C calc'!A0", "source": "spy.pet", "api_response": false }),
+        json!({ "guild_id": "2", "guild_name": "Leaky, Inc.", "source": "spy.pet", "api_response": { "messages": 3, "guild": { "name": "Leaky \"the\" server" } } }),
+    ];
+    let report = RunReport {
+        results: results
+            .into_iter()
+            .map(|r| serde_json::from_value(r).unwrap())
+            .collect(),
+        ..Default::default()
+    };
+    let mut out = Vec::new();
+    Csv::default().write_results(&mut out, &report).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = out.split("\r\n").collect();
+    assert_eq!(
+        lines[0],
+        "guild_id,guild_name,kind,source,status,compromised,checked_at,labels,error,api.guild.name,api.messages,severity,severity_score"
+    );
+    assert_eq!(
+        lines[1],
+        "1,'=cmd|' /C calc'!A0,guild,spy.pet,clean,false,,,,,,,"
+    );
+    assert_eq!(
+        lines[2],
+        r#"2,"Leaky, Inc.",guild,spy.pet,compromised,true,,,,"Leaky ""the"" server",3,low,21"#
+    );
+}
+
+#[test]
+fn markdown_output() {
+    let report = RunReport {
+        results: vec![serde_json::from_value(json!({
+            "guild_id": "2",
+            "guild_name": "**Leaky** | @everyone",
+            "source": "spy.pet",
+            "labels": ["alice"],
+            "api_response": { "messages": 3 },
+        }))
+        .unwrap()],
+        ..Default::default()
+    };
+    let mut out = Vec::new();
+    Markdown::default()
+        .write_results(&mut out, &report)
+        .unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("| **Total** | 1 | 1 | 0 |\n"), "{out}");
+    assert!(
+        out.contains("### \\*\\*Leaky\\*\\* \\| \\@everyone (`2`)\n"),
+        "{out}"
+    );
+    assert!(
+        out.contains("- **Labels:** alice\n- **messages:** 3\n"),
+        "{out}"
+    );
+    assert!(!out.contains("Failed checks"), "{out}");
+}
+
+#[test]
+fn html_output() {
+    let report = RunReport {
+        results: vec![
+            serde_json::from_value(json!({
+                "guild_id": "2",
+                "guild_name": "<script>alert(1)</script>",
+                "source": "spy.pet",
+                "api_response": { "messages": 3 },
+            }))
+            .unwrap(),
+            serde_json::from_value(json!({
+                "guild_id": "3",
+                "guild_name": "Fine & Dandy",
+                "source": "spy.pet",
+                "api_response": false,
+            }))
+            .unwrap(),
+        ],
+        ..Default::default()
+    };
+    let mut out = Vec::new();
+    Html::default().write_results(&mut out, &report).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with("<!DOCTYPE html>"), "{out}");
+    assert!(!out.contains("<script>alert"), "{out}");
+    assert!(
+        out.contains("&lt;script&gt;alert(1)&lt;/script&gt;"),
+        "{out}"
+    );
+    assert!(out.contains("<td>Fine &amp; Dandy</td>"), "{out}");
+    assert!(
+        out.contains("<tr class=\"compromised\"><td><a href=\"#guild-2\">"),
+        "{out}"
+    );
+    assert!(out.contains("<h3 id=\"guild-2\">"), "{out}");
+    assert_eq!(out.matches("<tr class=\"compromised\">").count(), 1);
+}
+
+#[test]
+fn ndjson_output() {
+    let results: Vec<Response> = ["1", "2"]
+        .iter()
+        .map(|id| {
+            serde_json::from_value(json!({
+                "guild_id": id,
+                "guild_name": "line\nbreak",
+                "source": "spy.pet",
+                "api_response": false,
+            }))
+            .unwrap()
+        })
+        .collect();
+    let mut out = Vec::new();
+    let mut lines = JsonLines::new(&mut out);
+    lines.push(&results[0]).unwrap();
+    lines.push(&results[1]).unwrap();
+    lines.finish().unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let ids: Vec<String> = out
+        .lines()
+        .map(|line| serde_json::from_str::<Response>(line).unwrap().guild_id)
+        .collect();
+    assert_eq!(ids, ["1", "2"]);
+
+    let report = RunReport {
+        results,
+        ..Default::default()
+    };
+    let mut written = Vec::new();
+    Ndjson.write_results(&mut written, &report).unwrap();
+    assert_eq!(String::from_utf8(written).unwrap(), out);
+}
+
+#[test]
+fn yaml_and_toml() {
+    let value = json!({
+        "on": "yes",
+        "list": [[1, 2], { "a": null, "b": [] }, "x"],
+        "empty": {},
+        // `n` would be read as a boolean by YAML 1.1
+        "nested": { "deeper": { "n": 1.5 } },
+    });
+    assert_eq!(
+        to_yaml(&value),
+        "\
+empty: {}
+list:
+  - - 1
+    - 2
+  - a: null
+    b: []
+  - \"x\"
+nested:
+  deeper:
+    \"n\": 1.5
+\"on\": \"yes\"
+"
+    );
+
+    let toml = to_toml(&value).unwrap();
+    let parsed: toml::Value = toml::from_str(&toml).unwrap();
+    assert_eq!(parsed["on"].as_str(), Some("yes"));
+    assert_eq!(parsed["nested"]["deeper"]["n"].as_float(), Some(1.5));
+    // TOML has no null
+    assert!(parsed["list"][1].get("a").is_none());
+}
+
+#[test]
+fn template_output() {
+    let results: Vec<Response> = [("1", json!(false)), ("2", json!({ "messages": 1204 }))]
+        .into_iter()
+        .map(|(id, answer)| {
+            serde_json::from_value(json!({
+                "guild_id": id,
+                "guild_name": format!("Server {id}"),
+                "source": "spy.pet",
+                "labels": ["alt", "main"],
+                "api_response": answer,
+            }))
+            .unwrap()
+        })
+        .collect();
+    let report = RunReport {
+        results,
+        ..Default::default()
+    };
+    let template = OutputTemplate::parse(
+        "\
+{{ summary.compromised }}/{{ summary.total }} {# not shown #}
+{% for guild in results -%}
+{{ loop.index }}. {{ guild.guild_name | upper | pad(9) }}|
+{%- if guild.compromised %} {{ guild.api_response.messages }} messages
+{%- elif guild.status == \"clean\" %} clean
+{%- else %} ?{% endif %} [{{ guild.labels | join(\"; \") }}] {{ guild.missing.field | default(\"-\") }}
+{% endfor -%}
+{% if not failed %}nothing failed{% endif %}",
+    )
+    .unwrap();
+    let mut out = Vec::new();
+    Templated { template }
+        .write_results(&mut out, &report)
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "\
+1/2 
+1. SERVER 1 | clean [alt; main] -
+2. SERVER 2 | 1204 messages [alt; main] -
+nothing failed"
+    );
+
+    for broken in [
+        "{% if x %}",
+        "{{ x | nope }}",
+        "{% for in x %}{% endfor %}",
+        "a\n{{ x",
+    ] {
+        assert!(OutputTemplate::parse(broken).is_err(), "{broken}");
+    }
+    match OutputTemplate::parse("a\nb\n{% endif %}") {
+        Err(err) => assert_eq!(err.line, 3),
+        Ok(_) => panic!("a stray endif parsed"),
+    }
+}
+
+#[test]
+fn junit_output() {
+    let results: Vec<Response> = [
+        ("1", json!(false)),
+        ("2", json!({ "messages": 1204 })),
+        ("3", json!(null)),
+    ]
+    .into_iter()
+    .map(|(id, answer)| {
+        serde_json::from_value(json!({
+            "guild_id": id,
+            "guild_name": format!("<Server {id}>\u{7}"),
+            "source": "spy.pet",
+            "api_response": answer,
+        }))
+        .unwrap()
+    })
+    .collect();
+    let failed = FailedCheck::new(
+        "4".to_owned(),
+        "Down".to_owned(),
+        "spy.pet".to_owned(),
+        1,
+        CheckError::HttpStatus(StatusCode::INTERNAL_SERVER_ERROR),
+    );
+    let report = RunReport {
+        results,
+        failed: vec![failed],
+        ..Default::default()
+    };
+    let mut out = Vec::new();
+    Formatter::write_results(&mut Junit, &mut out, &report).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with("<?xml"));
+    assert!(out
+        .contains("<testsuites name=\"spy-pet-checker\" tests=\"4\" failures=\"1\" errors=\"1\">"));
+    assert!(out.contains("tests=\"4\" failures=\"1\" errors=\"1\" skipped=\"1\""));
+    assert!(out.contains("<testcase classname=\"spy.pet\" name=\"&lt;Server 1&gt; (ID: 1)\"/>"));
+    assert!(out.contains("type=\"compromised\">messages: 1204</failure>"));
+    assert!(out.contains("<error message=\""));
+    assert!(out.contains("type=\"http_status\"/>"));
+    assert!(!out.contains('\u{7}'));
+}
+
+#[test]
+fn errors_in_output() {
+    let result: Response = serde_json::from_value(json!({
+        "guild_id": "1",
+        "guild_name": "Fine",
+        "source": "spy.pet",
+        "api_response": false,
+    }))
+    .unwrap();
+    let failed = FailedCheck::new(
+        "2".to_owned(),
+        "Down".to_owned(),
+        "spy.pet".to_owned(),
+        3,
+        CheckError::HttpStatus(StatusCode::BAD_GATEWAY),
+    );
+    let report = RunReport {
+        results: vec![result],
+        failed: vec![failed],
+        ..Default::default()
+    };
+
+    let mut out = Vec::new();
+    Json::default().write_results(&mut out, &report).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let output: Value = serde_json::from_str(&out).unwrap();
+    assert_eq!(output["results"][0]["guild_id"], "1");
+    assert_eq!(output["errors"][0]["guild_id"], "2");
+    assert_eq!(output["errors"][0]["http_status"], 502);
+    assert_eq!(output["errors"][0]["attempts"], 3);
+    // the output reads back in, and so do the arrays older versions wrote
+    assert_eq!(parse_results(&out).unwrap().len(), 1);
+    let array = serde_json::to_string(&report.results).unwrap();
+    assert_eq!(parse_results(&array).unwrap().len(), 1);
+
+    let mut out = Vec::new();
+    Plain::default().write_results(&mut out, &report).unwrap();
+    assert!(String::from_utf8(out).unwrap().ends_with(
+        "1 server couldn't be checked:\n  Down (ID: 2) on spy.pet: api returned error: 502 Bad Gateway\n"
+    ));
+    let mut report = report;
+    report.failed.push(FailedCheck::new(
+        "3".to_owned(),
+        "Gone".to_owned(),
+        "spy.pet".to_owned(),
+        1,
+        CheckError::HttpStatus(StatusCode::BAD_GATEWAY),
+    ));
+    let mut out = Vec::new();
+    Plain::default().write_results(&mut out, &report).unwrap();
+    assert!(String::from_utf8(out)
+        .unwrap()
+        .contains("2 servers couldn't be checked:\n"));
+}
+
+#[test]
+fn json_envelope() {
+    let results: Vec<Response> = [("1", json!(false)), ("2", json!({ "name": "Leaky" }))]
+        .into_iter()
+        .map(|(id, answer)| {
+            serde_json::from_value(json!({
+                "guild_id": id,
+                "guild_name": "Server",
+                "source": "spy.pet",
+                "api_response": answer,
+            }))
+            .unwrap()
+        })
+        .collect();
+    let started_at = "2024-05-01T12:00:00Z".parse().unwrap();
+    let report = RunReport {
+        started_at: Some(started_at),
+        results,
+        ..Default::default()
+    };
+
+    let mut streamed = Vec::new();
+    let mut document = JsonDocument::new(&mut streamed, Some(started_at));
+    for result in &report.results {
+        document.push(result).unwrap();
+    }
+    document.finish(&report).unwrap();
+    let mut written = Vec::new();
+    Json::default()
+        .write_results(&mut written, &report)
+        .unwrap();
+    assert_eq!(streamed, written);
+
+    let output: Value = serde_json::from_slice(&written).unwrap();
+    assert_eq!(output["schema_version"], JSON_SCHEMA_VERSION);
+    assert_eq!(output["tool_version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(output["started_at"], "2024-05-01T12:00:00Z");
+    assert_eq!(output["summary"]["total"], 2);
+    assert_eq!(output["summary"]["compromised"], 1);
+    assert_eq!(output["summary"]["errors"], 0);
+    assert_eq!(output["duration_ms"], Value::Null);
+
+    let mut grouped = Vec::new();
+    Json::grouped(GroupBy::Status)
+        .write_results(&mut grouped, &report)
+        .unwrap();
+    let grouped: Value = serde_json::from_slice(&grouped).unwrap();
+    assert_eq!(grouped["schema_version"], JSON_SCHEMA_VERSION);
+    assert_eq!(grouped["summary"], output["summary"]);
+}
+
+#[test]
+fn sorted_compact_json() {
+    let mut results: Vec<Response> = [
+        ("10", "beta", json!({ "messages": 5 })),
+        ("9", "Alpha", json!(false)),
+        ("11", "gamma", json!({ "messages": 700 })),
+        ("8", "delta", json!({ "name": "Leaky" })),
+    ]
+    .into_iter()
+    .map(|(id, name, answer)| {
+        serde_json::from_value(json!({
+            "guild_id": id,
+            "guild_name": name,
+            "source": "spy.pet",
+            "api_response": answer,
+        }))
+        .unwrap()
+    })
+    .collect();
+    let order = |results: &[Response]| -> Vec<String> {
+        results.iter().map(|r| r.guild_id.clone()).collect()
+    };
+
+    sort_results(&mut results, SortBy::Id);
+    assert_eq!(order(&results), ["8", "9", "10", "11"]);
+    sort_results(&mut results, SortBy::Name);
+    assert_eq!(order(&results), ["9", "10", "8", "11"]);
+    sort_results(&mut results, SortBy::Status);
+    assert_eq!(order(&results), ["8", "10", "11", "9"]);
+    // no count goes last
+    sort_results(&mut results, SortBy::Messages);
+    assert_eq!(order(&results), ["11", "10", "8", "9"]);
+
+    let report = RunReport {
+        results,
+        ..Default::default()
+    };
+    let mut compact = Vec::new();
+    Json {
+        compact: true,
+        ..Default::default()
+    }
+    .write_results(&mut compact, &report)
+    .unwrap();
+    assert_eq!(compact.iter().filter(|&&b| b == b'\n').count(), 1);
+    let mut pretty = Vec::new();
+    Json::default().write_results(&mut pretty, &report).unwrap();
+    assert_eq!(
+        serde_json::from_slice::<Value>(&compact).unwrap(),
+        serde_json::from_slice::<Value>(&pretty).unwrap()
+    );
+}
+
+#[test]
+fn plain_details() {
+    let result: Response = serde_json::from_value(json!({
+        "guild_id": "1",
+        "guild_name": "Art Club",
+        "source": "spy.pet",
+        "api_response": {
+            "name": "art club (old)",
+            "messageCount": 1204,
+            "members": 52,
+            "first_seen": "2023-02-01T00:00:00Z",
+            "last_seen": 1714564800,
+        },
+    }))
+    .unwrap();
+    let report = RunReport {
+        results: vec![result],
+        ..Default::default()
+    };
+
+    let mut out = Vec::new();
+    Plain::default().write_results(&mut out, &report).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "Art Club (ID: 1) is compromised! (severity: medium, 38/100)\n"
+    );
+
+    let mut out = Vec::new();
+    Plain {
+        details: true,
+        ..Default::default()
+    }
+    .write_results(&mut out, &report)
+    .unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "Art Club (ID: 1) is compromised! (severity: medium, 38/100)\n  name in the dataset: art club (old)\n  messages archived: 1204\n  members: 52\n  first seen: 2023-02-01\n  last seen: 2024-05-01\n"
+    );
+}
+
+#[test]
+fn severity() {
+    let at = "2024-06-01T00:00:00Z".parse().unwrap();
+    let score = |answer: Value| {
+        let info = GuildInfo::of(&answer).unwrap();
+        let severity = Severity::of(&info, at);
+        (severity.score, severity.level)
+    };
+    // nothing known but that it's in the dataset
+    assert_eq!(score(json!({ "name": "Art" })), (15, Level::Low));
+    assert_eq!(
+        score(json!({ "messages": 1204, "members": 52, "last_seen": "2024-05-20" })),
+        (68, Level::High)
+    );
+    assert_eq!(
+        score(json!({ "messages": 5000000, "members": 200000, "last_seen": "2024-05-31" })),
+        (100, Level::Critical)
+    );
+    // seen long enough ago, the same counts matter less
+    assert_eq!(
+        score(json!({ "messages": 1204, "members": 52, "last_seen": "2022-01-01" })),
+        (38, Level::Medium)
+    );
+
+    let clean: Response = serde_json::from_value(json!({
+        "guild_id": "1",
+        "guild_name": "Art Club",
+        "api_response": false,
+    }))
+    .unwrap();
+    assert_eq!(clean.severity(), None);
+    assert!("HIGH".parse::<Level>().unwrap() > Level::Medium);
+}
+
+#[test]
+fn plain_alignment_and_color() {
+    let results: Vec<Response> = [
+        ("1", "Art", json!({ "name": "Art" })),
+        ("22", "Gaming Hub", json!(false)),
+    ]
+    .into_iter()
+    .map(|(id, name, answer)| {
+        serde_json::from_value(json!({
+            "guild_id": id,
+            "guild_name": name,
+            "source": "spy.pet",
+            "api_response": answer,
+        }))
+        .unwrap()
+    })
+    .collect();
+    let report = RunReport {
+        results,
+        ..Default::default()
+    };
+
+    let mut out = Vec::new();
+    Plain {
+        include_clean: true,
+        ..Default::default()
+    }
+    .write_results(&mut out, &report)
+    .unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "Art (ID: 1)         is compromised! (severity: low, 15/100)\nGaming Hub (ID: 22) is clean\n"
+    );
+
+    let mut out = Vec::new();
+    Plain {
+        include_clean: true,
+        color: true,
+        ..Default::default()
+    }
+    .write_results(&mut out, &report)
+    .unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "Art (ID: 1)         \x1b[1;31mis compromised!\x1b[0m (severity: low, 15/100)\nGaming Hub (ID: 22) \x1b[32mis clean\x1b[0m\n"
+    );
+}
+
+#[test]
+fn plain_in_other_languages() {
+    assert_eq!("de".parse::<Lang>(), Ok(Lang::De));
+    assert_eq!("pt-BR".parse::<Lang>(), Ok(Lang::Pt));
+    assert_eq!("fr_FR.UTF-8".parse::<Lang>(), Ok(Lang::Fr));
+    assert!("tlh".parse::<Lang>().is_err());
+
+    let results: Vec<Response> = [
+        ("1", "Art", json!({ "name": "Art", "messageCount": 12 })),
+        ("22", "Gaming Hub", json!(false)),
+    ]
+    .into_iter()
+    .map(|(id, name, answer)| {
+        serde_json::from_value(json!({
+            "guild_id": id,
+            "guild_name": name,
+            "source": "spy.pet",
+            "api_response": answer,
+        }))
+        .unwrap()
+    })
+    .collect();
+    let report = RunReport {
+        results,
+        cancelled: true,
+        ..Default::default()
+    };
+    let write = |lang| {
+        let mut out = Vec::new();
+        Plain {
+            include_clean: true,
+            details: true,
+            lang,
+            ..Default::default()
+        }
+        .write_results(&mut out, &report)
+        .unwrap();
+        String::from_utf8(out).unwrap()
+    };
+
+    assert_eq!(
+        write(Lang::De),
+        "Lauf vorzeitig beendet, nicht jeder Server wurde geprüft\n\
+         Art (ID: 1)         ist betroffen: seine Nachrichten wurden archiviert! (Schweregrad: mittel, 26/100)\n  \
+         archivierte Nachrichten: 12\n\
+         Gaming Hub (ID: 22) ist nicht betroffen\n"
+    );
+    let english = write(Lang::En);
+    for lang in Lang::ALL {
+        let written = write(lang);
+        // the names and IDs are kept as they are, and nothing's left out
+        assert!(written.contains("Gaming Hub"), "{lang}: {written}");
+        assert_eq!(written.lines().count(), english.lines().count(), "{lang}");
+        if lang != Lang::En {
+            assert_ne!(written, english, "{lang}");
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use std::io::Write;
+
+use serde_json::json;
+use spy_pet_checker::checkpoint::{self, Checkpoint};
+use spy_pet_checker::{Kind, Response};
+
+fn response(id: &str, source: &str) -> Response {
+    serde_json::from_value(json!({
+        "guild_id": id,
+        "guild_name": "guild",
+        "source": source,
+        "api_response": false,
+    }))
+    .unwrap()
+}
+
+#[test]
+fn save_and_load() {
+    let path =
+        std::env::temp_dir().join(format!("spy-pet-checkpoint-{}.jsonl", std::process::id()));
+    let mut saved = Checkpoint::create(&path, false).unwrap();
+    saved.record(&response("1", "spy.pet")).unwrap();
+    drop(saved);
+
+    // picking up keeps what's there, and a line cut short is skipped
+    let mut saved = Checkpoint::create(&path, true).unwrap();
+    saved.record(&response("2", "spy.pet")).unwrap();
+    drop(saved);
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .unwrap()
+        .write_all(b"{\"guild_id\":\"3\",\"gui")
+        .unwrap();
+    let loaded = Checkpoint::load(&path).unwrap();
+    let ids: Vec<&str> = loaded.iter().map(|r| r.guild_id.as_str()).collect();
+    assert_eq!(ids, ["1", "2"]);
+
+    // and picking up after it starts a line of its own
+    let mut saved = Checkpoint::create(&path, true).unwrap();
+    saved.record(&response("4", "spy.pet")).unwrap();
+    drop(saved);
+    let loaded = Checkpoint::load(&path).unwrap();
+    let ids: Vec<&str> = loaded.iter().map(|r| r.guild_id.as_str()).collect();
+    assert_eq!(ids, ["1", "2", "4"]);
+
+    // starting over doesn't
+    let saved = Checkpoint::create(&path, false).unwrap();
+    assert!(Checkpoint::load(&path).unwrap().is_empty());
+    saved.remove().unwrap();
+    assert!(!path.exists());
+    assert!(Checkpoint::load(&path).unwrap().is_empty());
+}
+
+#[test]
+fn finished() {
+    let saved = vec![
+        response("1", "a"),
+        response("1", "b"),
+        response("2", "a"),
+        response("2", "a"),
+    ];
+    let done = checkpoint::finished(saved, |kind| match kind {
+        Kind::Guild => 2,
+        Kind::User | Kind::Channel => 1,
+    });
+    let ids: Vec<&str> = done.iter().map(|r| r.guild_id.as_str()).collect();
+    assert_eq!(ids, ["1", "1"]);
+}
+
+#[test]
+fn fingerprint() {
+    let one = checkpoint::fingerprint("guild", ["a", "b"], ["1", "2"]);
+    assert_eq!(
+        one,
+        checkpoint::fingerprint("guild", ["b", "a"], ["2", "1"])
+    );
+    assert_ne!(one, checkpoint::fingerprint("user", ["a", "b"], ["1", "2"]));
+    assert_ne!(one, checkpoint::fingerprint("guild", ["a"], ["1", "2"]));
+    assert_ne!(
+        one,
+        checkpoint::fingerprint("guild", ["a", "b"], ["1", "3"])
+    );
+    assert_eq!(one.len(), 16);
+}
@@ -0,0 +1,30 @@
+use std::process::Command;
+
+#[test]
+fn every_shell() {
+    for shell in ["bash", "zsh", "fish", "powershell"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"))
+            .args(["completions", shell])
+            .output()
+            .expect("binary runs");
+        assert!(output.status.success(), "{shell}: {output:?}");
+        let script = String::from_utf8(output.stdout).unwrap();
+        // a subcommand, a flag of it, and the values of a flag
+        for word in ["fetch-guilds", "sort-by", "ndjson"] {
+            assert!(script.contains(word), "{shell} script is missing {word}");
+        }
+    }
+}
+
+#[test]
+fn man_page() {
+    let output = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"))
+        .arg("mangen")
+        .output()
+        .expect("binary runs");
+    assert!(output.status.success(), "{output:?}");
+    let page = String::from_utf8(output.stdout).unwrap();
+    assert!(page.starts_with(".TH SPY\\-PET\\-CHECKER 1"));
+    assert!(page.contains(".SS \"history show\""));
+    assert!(page.contains("\\fB\\-\\-sort\\-by\\fR=\\fISORT_BY\\fR"));
+}
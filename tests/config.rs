@@ -0,0 +1,214 @@
+use serde_json::{json, Value};
+use tokio::process::Command;
+use wiremock::matchers::{header, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[tokio::test]
+async fn local_config_file() {
+    let server = MockServer::start().await;
+    Mock::given(path("/servers/100000000000000001"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "name": "Leaky" })))
+        .mount(&server)
+        .await;
+
+    let dir = std::env::temp_dir().join(format!("spy-pet-config-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("index.json"), r#"{"100000000000000001": "Leaky"}"#).unwrap();
+    std::fs::write(
+        dir.join("spy-pet-checker.toml"),
+        format!(
+            "index_path = \"index.json\"\nformat = \"json\"\nurl_template = \"{}/servers/{{id}}\"\n",
+            server.uri()
+        ),
+    )
+    .unwrap();
+
+    let run = |args: &[&str]| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"));
+        command
+            .current_dir(&dir)
+            .env_remove("SPY_PET_CONFIG")
+            .args(["--no-state", "--no-update-check"])
+            .args(args);
+        async move { command.output().await.expect("binary runs") }
+    };
+
+    let output = run(&[]).await;
+    assert!(output.status.success(), "{output:?}");
+    let document: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(document["results"][0]["status"], "compromised");
+
+    // flags take precedence over the file
+    let output = run(&["check", "--format", "ndjson"]).await;
+    assert!(output.status.success(), "{output:?}");
+    let line: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(line["guild_id"], "100000000000000001");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn environment() {
+    let server = MockServer::start().await;
+    Mock::given(path("/servers/100000000000000001"))
+        .and(header("x-team", "blue"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("false"))
+        .mount(&server)
+        .await;
+
+    let dir = std::env::temp_dir().join(format!("spy-pet-env-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let index = dir.join("index.json");
+    std::fs::write(&index, r#"{"100000000000000001": "Quiet"}"#).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"))
+        .current_dir(&dir)
+        .env("SPY_PET_INDEX_PATH", &index)
+        .env(
+            "SPY_PET_URL_TEMPLATE",
+            format!("{}/servers/{{id}}", server.uri()),
+        )
+        .env("SPY_PET_HEADER", "X-Team: blue")
+        .env("SPY_PET_FORMAT", "ndjson")
+        .env("SPY_PET_RETRIES", "0")
+        .env("SPY_PET_NO_CACHE", "true")
+        .args(["--no-state", "--no-update-check"])
+        .output()
+        .await
+        .expect("binary runs");
+    assert!(output.status.success(), "{output:?}");
+    let line: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(line["status"], "clean");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn cache_ttl_while_simulating() {
+    let output = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"))
+        .env("SPY_PET_CACHE_TTL", "1h")
+        .args(["--no-update-check", "--simulate", "2", "--format", "json"])
+        .output()
+        .await
+        .expect("binary runs");
+    assert!(output.status.success(), "{output:?}");
+    // the warning blames what actually turned the state off
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("--cache-ttl has no effect with --simulate"),
+        "{stderr}"
+    );
+    assert!(!stderr.contains("--no-state"), "{stderr}");
+}
+
+#[tokio::test]
+async fn simulate_from_environment() {
+    let output = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"))
+        .env("SPY_PET_SIMULATE", "3")
+        .env("SPY_PET_SIMULATE_LATENCY", "1ms")
+        .env("SPY_PET_SIMULATE_COMPROMISED", "100")
+        .env("SPY_PET_FORMAT", "json")
+        .args(["--no-update-check"])
+        .output()
+        .await
+        .expect("binary runs");
+    assert!(output.status.success(), "{output:?}");
+    let document: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let results = document["results"].as_array().unwrap();
+    assert_eq!(results.len(), 3);
+    assert!(results.iter().all(|r| r["status"] == "compromised"));
+}
+
+#[tokio::test]
+async fn verbosity() {
+    let server = MockServer::start().await;
+    Mock::given(path("/servers/100000000000000001"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("false"))
+        .mount(&server)
+        .await;
+    let dir = std::env::temp_dir().join(format!("spy-pet-verbosity-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let index = dir.join("index.json");
+    std::fs::write(&index, r#"{"100000000000000001": "Quiet"}"#).unwrap();
+
+    let logs = |flag: &'static str| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"));
+        command
+            .env_remove("RUST_LOG")
+            .args(["--no-state", "--no-update-check", flag, "--index-path"])
+            .arg(&index)
+            .arg("--url-template")
+            .arg(format!("{}/servers/{{id}}", server.uri()))
+            .args(["--progress-format", "logs"]);
+        async move {
+            let output = command.output().await.expect("binary runs");
+            assert!(output.status.success(), "{output:?}");
+            String::from_utf8(output.stderr).unwrap()
+        }
+    };
+
+    let verbose = logs("-v").await;
+    assert!(verbose.contains("DEBUG"), "{verbose}");
+    let quiet = logs("-q").await;
+    assert!(!quiet.contains("INFO"), "{quiet}");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn rotated_log_file() {
+    let dir = std::env::temp_dir().join(format!("spy-pet-log-rotate-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"))
+        .args(["--no-state", "--no-update-check", "--log-file"])
+        .arg(dir.join("run.log"))
+        .args([
+            "--log-rotate",
+            "daily",
+            "--log-keep",
+            "2",
+            "completions",
+            "bash",
+        ])
+        .output()
+        .expect("binary runs");
+    assert!(output.status.success(), "{output:?}");
+    let names: Vec<String> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(names.len(), 1, "{names:?}");
+    assert!(names[0].starts_with("run.log."), "{names:?}");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn several_backends() {
+    let dir = std::env::temp_dir().join(format!("spy-pet-backends-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("spy-pet-checker.toml"),
+        "backend = [\"kickthespy\", \"spypet\"]\n",
+    )
+    .unwrap();
+    let run = |args: &[&str]| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"));
+        command
+            .current_dir(&dir)
+            .env_remove("SPY_PET_CONFIG")
+            .env_remove("SPY_PET_BACKEND")
+            .args(["--no-state", "--no-update-check", "--dry-run"])
+            .args(["--ids", "100000000000000001"])
+            .args(args);
+        async move {
+            let output = command.output().await.expect("binary runs");
+            assert!(output.status.success(), "{output:?}");
+            let stdout = String::from_utf8(output.stdout).unwrap();
+            let line = stdout.lines().find(|l| l.starts_with("Backends: "));
+            line.unwrap().to_owned()
+        }
+    };
+
+    assert_eq!(run(&[]).await, "Backends: kickthespy.pet, spy.pet");
+    // each once, however they're given
+    let line = run(&["--backend", "spypet", "--backend", "all"]).await;
+    assert_eq!(line, "Backends: spy.pet, kickthespy.pet");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,134 @@
+use serde_json::{json, Value};
+use spy_pet_checker::diff::{diff_runs, diff_values, Change, FieldChange, Ignore};
+use spy_pet_checker::{Response, RunReport, Status};
+
+fn changes(old: Value, new: Value, ignore: &[&str]) -> Vec<FieldChange> {
+    let ignore = Ignore::new(ignore.iter().map(|f| (*f).to_owned()));
+    diff_values(&old, &new, &ignore)
+}
+
+#[test]
+fn fields() {
+    let old = json!({ "name": "Leaky", "messages": 1204, "icon": "a" });
+    let new = json!({ "name": "Leaky", "messages": 88391, "first_seen": "2024-04-01" });
+    assert_eq!(
+        changes(old, new, &[]),
+        [
+            FieldChange::Added {
+                path: "/first_seen".to_owned(),
+                value: json!("2024-04-01"),
+            },
+            FieldChange::Removed {
+                path: "/icon".to_owned(),
+                value: json!("a"),
+            },
+            FieldChange::Changed {
+                path: "/messages".to_owned(),
+                from: json!(1204),
+                to: json!(88391),
+            },
+        ]
+    );
+}
+
+#[test]
+fn nested_arrays() {
+    let old = json!({ "channels": [{ "id": 1, "messages": 5 }] });
+    let new = json!({ "channels": [{ "id": 1, "messages": 7 }, { "id": 2, "messages": 1 }] });
+    assert_eq!(
+        changes(old, new, &[]),
+        [
+            FieldChange::Length {
+                path: "/channels".to_owned(),
+                from: 1,
+                to: 2,
+            },
+            FieldChange::Changed {
+                path: "/channels/0/messages".to_owned(),
+                from: json!(5),
+                to: json!(7),
+            },
+            FieldChange::Added {
+                path: "/channels/1".to_owned(),
+                value: json!({ "id": 2, "messages": 1 }),
+            },
+        ]
+    );
+}
+
+#[test]
+fn ignored_fields() {
+    let old = json!({ "last_seen": 1, "guild": { "last_seen": 1, "seen": 1 } });
+    let new = json!({ "last_seen": 2, "guild": { "last_seen": 2, "seen": 2 } });
+    assert!(changes(old.clone(), new.clone(), &["last_seen", "seen"]).is_empty());
+    // a pointer only matches that one field
+    assert_eq!(changes(old, new, &["/last_seen", "seen"]).len(), 1);
+}
+
+#[test]
+fn deep_nesting() {
+    let mut old = json!(1);
+    let mut new = json!(2);
+    for _ in 0..100 {
+        old = json!({ "a": [old] });
+        new = json!({ "a": [new] });
+    }
+    let changes = changes(old, new, &[]);
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].path(), "/a/0".repeat(100));
+}
+
+fn report(results: &[(&str, Value)]) -> RunReport {
+    let results = results
+        .iter()
+        .map(|(id, api_response)| {
+            serde_json::from_value::<Response>(json!({
+                "guild_id": id,
+                "guild_name": format!("guild {id}"),
+                "source": "spy.pet",
+                "api_response": api_response,
+            }))
+            .unwrap()
+        })
+        .collect();
+    RunReport {
+        results,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn runs() {
+    let old = report(&[
+        ("1", json!(false)),
+        ("2", json!({ "messages": 1 })),
+        ("3", json!(false)),
+    ]);
+    let new = report(&[
+        ("1", json!({ "messages": 5 })),
+        ("2", json!({ "messages": 2 })),
+        ("3", json!(false)),
+        ("4", json!(false)),
+    ]);
+    let diffs = diff_runs(&old, &new, &Ignore::default());
+    let ids: Vec<&str> = diffs.iter().map(|d| d.guild_id.as_str()).collect();
+    assert_eq!(ids, ["1", "2", "4"]);
+    assert_eq!(diffs[0].from, Some(Status::Clean));
+    assert_eq!(diffs[0].to, Some(Status::Compromised));
+    assert_eq!(diffs[1].from, diffs[1].to);
+    assert_eq!(diffs[1].changes.len(), 1);
+    assert_eq!(diffs[2].from, None);
+    let kinds: Vec<Change> = diffs.iter().map(|d| d.change).collect();
+    assert_eq!(
+        kinds,
+        [Change::NewlyCompromised, Change::Changed, Change::Changed]
+    );
+    assert_eq!(
+        Change::of(Some(Status::Compromised), Some(Status::Clean)),
+        Change::NewlyClean
+    );
+    assert_eq!(
+        Change::of(None, Some(Status::Compromised)),
+        Change::NewlyCompromised
+    );
+}
@@ -0,0 +1,71 @@
+use serde_json::json;
+use tokio::process::Command;
+use wiremock::matchers::{path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[tokio::test]
+async fn dump_and_check_locally() {
+    let server = MockServer::start().await;
+    Mock::given(path("/servers"))
+        .and(query_param("page", "1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            { "id": "100000000000000001", "name": "Leaky", "messages": 12 },
+            "100000000000000002",
+        ])))
+        .mount(&server)
+        .await;
+    Mock::given(path("/servers"))
+        .and(query_param("page", "2"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .mount(&server)
+        .await;
+
+    let dir = std::env::temp_dir().join(format!("spy-pet-dump-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let dataset = dir.join("dataset.json");
+    let output = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"))
+        .args(["--no-state", "--no-update-check", "dump"])
+        .arg(&dataset)
+        .arg("--list-url")
+        .arg(format!("{}/servers?page={{page}}", server.uri()))
+        .output()
+        .await
+        .expect("binary runs");
+    assert!(output.status.success(), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("2 servers"));
+    assert_eq!(server.received_requests().await.unwrap().len(), 2);
+
+    let index = dir.join("index.json");
+    std::fs::write(
+        &index,
+        r#"{"100000000000000001": "Leaky", "100000000000000002": "Listed", "100000000000000003": "Clean"}"#,
+    )
+    .unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"))
+        .args([
+            "--no-state",
+            "--no-update-check",
+            "--offline",
+            "--index-path",
+        ])
+        .arg(&index)
+        .arg("--local-dataset")
+        .arg(&dataset)
+        .args(["--format", "json"])
+        .output()
+        .await
+        .expect("binary runs");
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(server.received_requests().await.unwrap().len(), 2);
+    let document: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let status = |id: &str| {
+        let results = document["results"].as_array().unwrap();
+        let result = results.iter().find(|r| r["guild_id"] == id).unwrap();
+        assert_eq!(result["source"], "local");
+        result["status"].as_str().unwrap().to_owned()
+    };
+    assert_eq!(status("100000000000000001"), "compromised");
+    assert_eq!(status("100000000000000002"), "compromised");
+    assert_eq!(status("100000000000000003"), "clean");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,203 @@
+#![cfg(feature = "encrypt")]
+
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305};
+use ring::{hkdf, hmac};
+use spy_pet_checker::encrypt::{bech32_decode, scrypt, EncryptError, Recipient, Sealer};
+use spy_pet_checker::secret::Secret;
+use tokio::process::Command;
+
+const RECIPIENT: &str = "age1vqtknucmglyqqnwjwh085pd6u0ma4k0nra78zjh27r8ec5frl52qaj2rnx";
+
+#[test]
+fn recipients() {
+    assert!(Recipient::parse(RECIPIENT).is_ok());
+    assert!(Recipient::parse(&RECIPIENT.to_uppercase()).is_ok());
+    // one character off breaks the checksum
+    let typo = RECIPIENT.replace("rnx", "rny");
+    assert!(matches!(
+        Recipient::parse(&typo),
+        Err(EncryptError::BadRecipient(_))
+    ));
+    assert!(Recipient::parse("ssh-ed25519 AAAA").is_err());
+
+    let passphrase = || Recipient::Passphrase {
+        passphrase: Secret::new("correct horse".to_owned()),
+        work_factor: 2,
+    };
+    let mixed = [passphrase(), Recipient::parse(RECIPIENT).unwrap()];
+    assert!(matches!(
+        Sealer::new(&mixed),
+        Err(EncryptError::PassphraseNotAlone)
+    ));
+    assert!(matches!(Sealer::new(&[]), Err(EncryptError::NoRecipients)));
+
+    let (mut sealer, header) = Sealer::new(&[passphrase()]).unwrap();
+    let header = String::from_utf8_lossy(&header);
+    let lines: Vec<&str> = header.lines().collect();
+    assert_eq!(lines[0], "age-encryption.org/v1");
+    assert!(lines[1].starts_with("-> scrypt ") && lines[1].ends_with(" 2"));
+    assert!(lines[3].starts_with("--- "));
+
+    // chunks are held back until the next one starts, the last is marked as such
+    assert!(sealer.update(&[b'x'; 64 * 1024]).unwrap().is_empty());
+    assert_eq!(sealer.update(b"more").unwrap().len(), 64 * 1024 + 16);
+    assert_eq!(sealer.finish().unwrap().len(), 4 + 16);
+}
+
+#[test]
+fn known_answers() {
+    // RFC 7914, section 12: N = 16384, r = 8, p = 1, the first 32 bytes
+    let key = scrypt(b"pleaseletmein", b"SodiumChloride", 14);
+    assert_eq!(
+        key,
+        *b"\x70\x23\xbd\xcb\x3a\xfd\x73\x48\x46\x1c\x06\xcd\x81\xfd\x38\xeb\
+           \xfd\xa8\xfb\xba\x90\x4f\x8e\x3e\xa9\xb5\x43\xf6\x54\x5d\xa1\xf2"
+    );
+
+    // BIP 173's valid and invalid strings
+    assert_eq!(bech32_decode("A12UEL5L"), Some(("a".to_owned(), vec![])));
+    let (hrp, data) = bech32_decode("abcdef1qpzry9x8gf2tvdw0s3jn54khce6mua7lmqqqxw").unwrap();
+    assert_eq!(hrp, "abcdef");
+    assert_eq!(
+        data,
+        b"\x00\x44\x32\x14\xc7\x42\x54\xb6\x35\xcf\x84\x65\x3a\x56\xd7\xc6\x75\xbe\x77\xdf"
+    );
+    assert!(bech32_decode("?1ezyfcl").is_some());
+    assert!(
+        bech32_decode("split1checkupstagehandshakeupstreamerranterredcaperred2y9e3w").is_some()
+    );
+    for invalid in [
+        "1nwldj5",
+        "pzry9x0s0muk",
+        "x1b4n0q5v",
+        "li1dgmt3",
+        "A1G7SGD8",
+        "1qzzfhee",
+    ] {
+        assert_eq!(bech32_decode(invalid), None, "{invalid}");
+    }
+}
+
+/// Decrypts a passphrase-encrypted age file the way the spec has it, from
+/// the header's scrypt stanza to the last payload chunk
+fn open_with_passphrase(file: &[u8], passphrase: &str) -> Vec<u8> {
+    struct Len;
+    impl hkdf::KeyType for Len {
+        fn len(&self) -> usize {
+            32
+        }
+    }
+    let hkdf = |salt: &[u8], ikm: &[u8], info: &[u8]| {
+        let mut key = [0; 32];
+        hkdf::Salt::new(hkdf::HKDF_SHA256, salt)
+            .extract(ikm)
+            .expand(&[info], Len)
+            .unwrap()
+            .fill(&mut key)
+            .unwrap();
+        key
+    };
+    let open = |key: &[u8], nonce: [u8; 12], sealed: &[u8]| {
+        let key = LessSafeKey::new(UnboundKey::new(&CHACHA20_POLY1305, key).unwrap());
+        let mut sealed = sealed.to_vec();
+        let plain = key
+            .open_in_place(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::empty(),
+                &mut sealed,
+            )
+            .expect("authentic");
+        plain.to_vec()
+    };
+
+    let end = file.windows(4).position(|w| w == b"\n---").unwrap() + 4;
+    let mac_end = end + file[end..].iter().position(|&b| b == b'\n').unwrap();
+    let header = std::str::from_utf8(&file[..end]).unwrap();
+    let lines: Vec<&str> = header.lines().collect();
+    assert_eq!(lines[0], "age-encryption.org/v1");
+    let args: Vec<&str> = lines[1].split(' ').collect();
+    assert_eq!(args[..2], ["->", "scrypt"]);
+    let salt = STANDARD_NO_PAD.decode(args[2]).unwrap();
+    let label = [b"age-encryption.org/v1/scrypt".as_slice(), &salt].concat();
+    let key = scrypt(passphrase.as_bytes(), &label, args[3].parse().unwrap());
+    let body = STANDARD_NO_PAD.decode(lines[2]).unwrap();
+    let file_key = open(&key, [0; 12], &body);
+
+    let mac = std::str::from_utf8(&file[end + 1..mac_end]).unwrap();
+    let mac_key = hmac::Key::new(hmac::HMAC_SHA256, &hkdf(&[], &file_key, b"header"));
+    hmac::verify(
+        &mac_key,
+        header.as_bytes(),
+        &STANDARD_NO_PAD.decode(mac).unwrap(),
+    )
+    .expect("header MAC");
+
+    let nonce = &file[mac_end + 1..mac_end + 17];
+    let payload_key = hkdf(nonce, &file_key, b"payload");
+    let chunks: Vec<&[u8]> = file[mac_end + 17..].chunks(64 * 1024 + 16).collect();
+    let mut plain = Vec::new();
+    for (counter, chunk) in chunks.iter().enumerate() {
+        let mut nonce = [0u8; 12];
+        nonce[3..11].copy_from_slice(&(counter as u64).to_be_bytes());
+        nonce[11] = u8::from(counter == chunks.len() - 1);
+        plain.extend(open(&payload_key, nonce, chunk));
+    }
+    plain
+}
+
+#[test]
+fn round_trip() {
+    let report: Vec<u8> = (0..100_000u32).flat_map(|i| i.to_le_bytes()).collect();
+    for split in [0, 1, 64 * 1024, report.len()] {
+        let recipient = Recipient::Passphrase {
+            passphrase: Secret::new("correct horse".to_owned()),
+            work_factor: 2,
+        };
+        let (mut sealer, mut file) = Sealer::new(&[recipient]).unwrap();
+        file.extend(sealer.update(&report[..split]).unwrap());
+        file.extend(sealer.update(&report[split..]).unwrap());
+        file.extend(sealer.finish().unwrap());
+        assert_eq!(open_with_passphrase(&file, "correct horse"), report);
+    }
+}
+
+#[tokio::test]
+async fn encrypted_report() {
+    let dir = std::env::temp_dir().join(format!("spy-pet-encrypt-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let report = dir.join("report.json");
+    std::fs::write(&report, "the last report").unwrap();
+    let run = |recipient: &str| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"));
+        command
+            .args(["--no-state", "--no-update-check", "--simulate", "2"])
+            .args(["--format", "json", "--encrypt", recipient, "-o"])
+            .arg(&report)
+            .env_remove("SPY_PET_PASSPHRASE");
+        command
+    };
+
+    // a bad recipient fails before the run, leaving the old report alone
+    let output = run("age1nope").output().await.expect("binary runs");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("isn't an age recipient"));
+    assert_eq!(std::fs::read_to_string(&report).unwrap(), "the last report");
+
+    let output = run("passphrase").output().await.expect("binary runs");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("SPY_PET_PASSPHRASE"));
+    assert_eq!(std::fs::read_to_string(&report).unwrap(), "the last report");
+
+    let output = run(RECIPIENT).output().await.expect("binary runs");
+    assert!(output.status.success(), "{output:?}");
+    let written = std::fs::read(&report).unwrap();
+    let text = String::from_utf8_lossy(&written);
+    assert!(
+        text.starts_with("age-encryption.org/v1\n-> X25519 "),
+        "{text}"
+    );
+    assert!(!text.contains("Simulated server"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,112 @@
+use serde_json::json;
+use tokio::process::Command;
+use wiremock::matchers::path;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[tokio::test]
+async fn exit_codes() {
+    let server = MockServer::start().await;
+    Mock::given(path("/servers/100000000000000001"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "name": "Leaky" })))
+        .mount(&server)
+        .await;
+    Mock::given(path("/servers/100000000000000002"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&server)
+        .await;
+
+    let dir = std::env::temp_dir().join(format!("spy-pet-fail-on-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let index = dir.join("index.json");
+    std::fs::write(
+        &index,
+        r#"{"100000000000000001": "Leaky", "100000000000000002": "Down"}"#,
+    )
+    .unwrap();
+    let baseline = dir.join("baseline.json");
+    std::fs::write(&baseline, r#"[{"guild_id": "100000000000000001", "guild_name": "Leaky", "source": "spy.pet", "api_response": {"name": "Leaky"}}]"#).unwrap();
+
+    let run = |args: &[&str]| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"));
+        command
+            .args(["--no-state", "--no-update-check", "--index-path"])
+            .arg(&index)
+            .arg("--url-template")
+            .arg(format!("{}/servers/{{id}}", server.uri()))
+            .args(["--retries", "0"])
+            .args(args);
+        async move {
+            let output = command.output().await.expect("binary runs");
+            let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+            (output.status.code(), stderr)
+        }
+    };
+
+    assert_eq!(run(&[]).await.0, Some(0));
+    let (code, stderr) = run(&["--fail-on", "found"]).await;
+    assert_eq!(code, Some(2));
+    assert!(stderr.contains("found 1 compromised server\n"), "{stderr}");
+    let (code, stderr) = run(&["--fail-on", "error"]).await;
+    assert_eq!(code, Some(1));
+    assert!(stderr.contains("1 check failed\n"), "{stderr}");
+    assert_eq!(run(&["--fail-on", "any"]).await.0, Some(2));
+    // what the baseline already had isn't a finding
+    let baseline = baseline.to_str().unwrap();
+    assert_eq!(
+        run(&["--fail-on", "found", "--baseline", baseline]).await.0,
+        Some(0)
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn stopping_early() {
+    let server = MockServer::start().await;
+    Mock::given(path("/servers/100000000000000001"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!(false)))
+        .mount(&server)
+        .await;
+    // every other server is down
+    Mock::given(wiremock::matchers::any())
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&server)
+        .await;
+
+    let dir = std::env::temp_dir().join(format!("spy-pet-stop-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let output = dir.join("results.json");
+    let ids: Vec<String> = (1..=9).map(|i| format!("10000000000000000{i}")).collect();
+    let run = |args: &[&str]| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"));
+        command
+            .args(["--no-state", "--no-update-check", "--url-template"])
+            .arg(format!("{}/servers/{{id}}", server.uri()))
+            .args([
+                "--ids",
+                &ids.join(","),
+                "--concurrency",
+                "1",
+                "--retries",
+                "0",
+                "--retry-passes",
+                "0",
+            ])
+            .args(["--format", "json", "-o"])
+            .arg(&output)
+            .args(args);
+        async move { command.output().await.expect("binary runs").status.code() }
+    };
+    let written = || -> serde_json::Value {
+        serde_json::from_str(&std::fs::read_to_string(&output).unwrap()).unwrap()
+    };
+
+    for args in [&["--max-errors", "2"][..], &["--fail-fast=error"]] {
+        assert_eq!(run(args).await, Some(1), "{args:?}");
+        // what came in before stopping is still written
+        let document = written();
+        assert_eq!(document["cancelled"], true);
+        assert_eq!(document["results"][0]["guild_id"], "100000000000000001");
+        assert!(document["errors"].as_array().unwrap().len() < 8, "{args:?}");
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
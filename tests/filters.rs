@@ -0,0 +1,230 @@
+use serde_json::{json, Value};
+use tokio::process::Command;
+use wiremock::matchers::path;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[tokio::test]
+async fn output_filters() {
+    let server = MockServer::start().await;
+    Mock::given(path("/servers/100000000000000001"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "name": "Leaky" })))
+        .mount(&server)
+        .await;
+    Mock::given(path("/servers/100000000000000002"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!(false)))
+        .mount(&server)
+        .await;
+
+    let dir = std::env::temp_dir().join(format!("spy-pet-filters-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let index = dir.join("index.json");
+    std::fs::write(
+        &index,
+        r#"{"100000000000000001": "Leaky", "100000000000000002": "Fine"}"#,
+    )
+    .unwrap();
+
+    let run = |args: &[&str]| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"));
+        command
+            .args(["--no-state", "--no-update-check", "--index-path"])
+            .arg(&index)
+            .arg("--url-template")
+            .arg(format!("{}/servers/{{id}}", server.uri()))
+            .args(["--retries", "0"])
+            .env("SPY_PET_LANG", "en")
+            .args(args);
+        async move {
+            let output = command.output().await.expect("binary runs");
+            assert!(output.status.success());
+            String::from_utf8(output.stdout).unwrap()
+        }
+    };
+
+    let json: Value =
+        serde_json::from_str(&run(&["-f", "json", "--only-compromised"]).await).unwrap();
+    let results = json["results"].as_array().unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0]["guild_id"], "100000000000000001");
+
+    let plain = run(&[]).await;
+    assert!(!plain.contains("Fine"));
+    let plain = run(&["--include-clean"]).await;
+    assert!(plain.lines().any(
+        |line| line.starts_with("Fine (ID: 100000000000000002)") && line.ends_with(" is clean")
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn severity_filter() {
+    let server = MockServer::start().await;
+    let last_seen = chrono::Utc::now().to_rfc3339();
+    let answers = [
+        ("100000000000000001", json!({ "name": "Leaky" })),
+        (
+            "100000000000000002",
+            json!({ "name": "Busy", "messages": 1000 }),
+        ),
+        (
+            "100000000000000003",
+            json!({ "name": "Huge", "messages": 200000, "members": 5000, "last_seen": last_seen }),
+        ),
+    ];
+    for (id, answer) in answers {
+        Mock::given(path(format!("/servers/{id}")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(answer))
+            .mount(&server)
+            .await;
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"))
+        .args(["--no-state", "--no-update-check", "--ids"])
+        .arg("100000000000000001,100000000000000002,100000000000000003")
+        .arg("--url-template")
+        .arg(format!("{}/servers/{{id}}", server.uri()))
+        .args([
+            "--min-severity",
+            "medium",
+            "--sort-by",
+            "severity",
+            "-f",
+            "json",
+        ])
+        .output()
+        .await
+        .expect("binary runs");
+    assert!(output.status.success());
+    let json: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let results = json["results"].as_array().unwrap();
+    let severities: Vec<(&str, &str)> = results
+        .iter()
+        .map(|r| {
+            (
+                r["guild_id"].as_str().unwrap(),
+                r["severity"]["level"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        severities,
+        [
+            ("100000000000000003", "critical"),
+            ("100000000000000002", "medium")
+        ]
+    );
+}
+
+#[tokio::test]
+async fn input_filters() {
+    let server = MockServer::start().await;
+    Mock::given(wiremock::matchers::any())
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!(false)))
+        .mount(&server)
+        .await;
+
+    let dir = std::env::temp_dir().join(format!("spy-pet-input-filters-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let index = dir.join("index.json");
+    std::fs::write(
+        &index,
+        r#"{"100000000000000001": "Art Club", "100000000000000002": "art dump", "100000000000000003": "Gaming"}"#,
+    )
+    .unwrap();
+
+    let checked = |args: &[&str]| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"));
+        command
+            .args(["--no-state", "--no-update-check", "--index-path"])
+            .arg(&index)
+            .arg("--url-template")
+            .arg(format!("{}/servers/{{id}}", server.uri()))
+            .args(["-f", "json"])
+            .args(args);
+        async move {
+            let output = command.output().await.expect("binary runs");
+            assert!(output.status.success());
+            let json: Value = serde_json::from_slice(&output.stdout).unwrap();
+            let mut ids: Vec<String> = json["results"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|r| r["guild_id"].as_str().unwrap().to_owned())
+                .collect();
+            ids.sort();
+            ids
+        }
+    };
+
+    assert_eq!(
+        checked(&["--include-id", "100000000000000003"]).await,
+        ["100000000000000003"]
+    );
+    assert_eq!(
+        checked(&["--exclude-id", "100000000000000001,100000000000000003"]).await,
+        ["100000000000000002"]
+    );
+    assert_eq!(
+        checked(&["--name-filter", "(?i)^art"]).await,
+        ["100000000000000001", "100000000000000002"]
+    );
+    assert_eq!(
+        checked(&["--name-filter", "Art", "--exclude-id", "100000000000000001"]).await,
+        Vec::<String>::new()
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn shards() {
+    let server = MockServer::start().await;
+    Mock::given(path("/servers/100000000000000013"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&server)
+        .await;
+    Mock::given(wiremock::matchers::any())
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!(false)))
+        .mount(&server)
+        .await;
+
+    let dir = std::env::temp_dir().join(format!("spy-pet-shards-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let ids: Vec<String> = (10..30).map(|i| format!("1000000000000000{i}")).collect();
+    let mut files = Vec::new();
+    for shard in ["1/3", "2/3", "3/3"] {
+        let file = dir.join(format!("shard-{}.json", &shard[..1]));
+        let output = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"))
+            .args(["--no-state", "--no-update-check", "--url-template"])
+            .arg(format!("{}/servers/{{id}}", server.uri()))
+            .args(["--ids", &ids.join(","), "--shard", shard])
+            .args(["--retries", "0", "--retry-passes", "0", "-f", "json", "-o"])
+            .arg(&file)
+            .output()
+            .await
+            .expect("binary runs");
+        assert!(output.status.success(), "{shard}");
+        files.push(file);
+    }
+    // every server was asked by exactly one shard
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), ids.len());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"))
+        .arg("merge")
+        .args(&files)
+        .output()
+        .await
+        .expect("binary runs");
+    assert!(output.status.success());
+    let merged: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(merged["results"].as_array().unwrap().len(), ids.len() - 1);
+    assert_eq!(merged["errors"][0]["guild_id"], "100000000000000013");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"))
+        .args(["--no-state", "--no-update-check", "--shard", "4/3"])
+        .output()
+        .await
+        .expect("binary runs");
+    assert_eq!(output.status.code(), Some(2));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,61 @@
+use serde_json::{json, Value};
+use tokio::process::Command;
+use wiremock::matchers::path;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[tokio::test]
+async fn exit_codes() {
+    let server = MockServer::start().await;
+    Mock::given(path("/up/613425648685547541"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!(false)))
+        .mount(&server)
+        .await;
+    Mock::given(path("/limited/613425648685547541"))
+        .respond_with(
+            ResponseTemplate::new(429)
+                .insert_header("retry-after", "30")
+                .insert_header("x-ratelimit-remaining", "0"),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(path("/down/613425648685547541"))
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&server)
+        .await;
+    Mock::given(path("/locked/613425648685547541"))
+        .respond_with(ResponseTemplate::new(401))
+        .mount(&server)
+        .await;
+
+    let probe = |endpoint: &str| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"));
+        command
+            .args(["healthcheck", "--json", "--no-state", "--url-template"])
+            .arg(format!("{}/{endpoint}/{{id}}", server.uri()));
+        async move {
+            let output = command.output().await.expect("binary runs");
+            let probe: Value = serde_json::from_slice(&output.stdout).unwrap();
+            (output.status.code(), probe)
+        }
+    };
+
+    let (code, up) = probe("up").await;
+    assert_eq!(code, Some(0));
+    assert_eq!(
+        (up["health"].as_str(), up["status"].as_u64()),
+        (Some("up"), Some(200))
+    );
+    let (code, limited) = probe("limited").await;
+    assert_eq!(code, Some(4));
+    assert_eq!(limited["headers"]["x-ratelimit-remaining"], "0");
+    assert_eq!(probe("down").await.0, Some(3));
+    assert_eq!(probe("locked").await.0, Some(5));
+    // nothing listening
+    let closed = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"))
+        .args(["healthcheck", "--no-state", "--url-template"])
+        .arg("http://127.0.0.1:1/servers/{id}")
+        .output()
+        .await
+        .unwrap();
+    assert_eq!(closed.status.code(), Some(3));
+}
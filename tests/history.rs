@@ -0,0 +1,48 @@
+use chrono::{TimeZone, Utc};
+use serde_json::{json, Value};
+use spy_pet_checker::history::{History, RunRecord};
+use spy_pet_checker::state::StateDir;
+use spy_pet_checker::{Response, RunReport, Status};
+
+fn report(answer: Value) -> RunReport {
+    let response = serde_json::from_value::<Response>(json!({
+        "guild_id": "1",
+        "guild_name": "guild",
+        "source": "spy.pet",
+        "api_response": answer,
+    }))
+    .unwrap();
+    RunReport {
+        results: vec![response],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn sightings() {
+    let dir = std::env::temp_dir().join(format!("spy-pet-history-{}", std::process::id()));
+    let history = History::new(StateDir::new(&dir));
+    let answers = [json!(false), json!({ "messages": 1 }), json!(false)];
+    for (month, answer) in (2..).zip(answers) {
+        let started_at = Utc.with_ymd_and_hms(2024, month, 10, 12, 0, 0).unwrap();
+        let mut record = RunRecord::new(started_at, 1, report(answer));
+        history.save(&mut record).unwrap();
+    }
+
+    let march = |day| Utc.with_ymd_and_hms(2024, 3, day, 0, 0, 0).unwrap();
+    let runs = history.runs(Some(march(1)), Some(march(31))).unwrap();
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0].run_id, "20240310T120000Z");
+
+    let sightings = history.sightings("1").unwrap();
+    let statuses: Vec<Status> = sightings
+        .iter()
+        .map(|s| s.outcome.clone().unwrap())
+        .collect();
+    assert_eq!(
+        statuses,
+        [Status::Clean, Status::Compromised, Status::Clean]
+    );
+    assert!(history.sightings("2").unwrap().is_empty());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
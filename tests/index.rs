@@ -0,0 +1,378 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use chrono::Utc;
+use serde_json::json;
+use spy_pet_checker::discord::{invite_code, InviteResolver};
+use spy_pet_checker::index::{
+    is_json, is_snowflake, mostly_ids, normalize, orient, parse, parse_as, parse_csv, parse_from,
+    parse_lines, parse_reader, parse_yaml, take_invites, validate, Finding, IndexFormat, LineError,
+    OrientError, Orientation, Problem,
+};
+use spy_pet_checker::prefilter::{Listing, SOURCE};
+use spy_pet_checker::Status;
+
+fn index(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+    entries
+        .iter()
+        .map(|(k, v)| ((*k).to_owned(), (*v).to_owned()))
+        .collect()
+}
+
+#[test]
+fn snowflakes() {
+    assert!(is_snowflake("1234567890123456789"));
+    assert!(is_snowflake("12345678901234567"));
+    assert!(!is_snowflake("1234567890123456"));
+    assert!(!is_snowflake("123456789012345678901"));
+    assert!(!is_snowflake("My Cool Server"));
+    assert!(!is_snowflake("12345678901234567a"));
+}
+
+#[test]
+fn invite_links() {
+    assert_eq!(invite_code("https://discord.gg/spy-pet"), Some("spy-pet"));
+    assert_eq!(invite_code("discord.gg/abc123?event=1"), Some("abc123"));
+    assert_eq!(
+        invite_code("https://discord.com/invite/abc123"),
+        Some("abc123")
+    );
+    assert_eq!(
+        invite_code("www.discordapp.com/invite/abc123/"),
+        Some("abc123")
+    );
+    assert_eq!(invite_code("https://discord.gg/"), None);
+    assert_eq!(invite_code("https://example.com/invite/abc123"), None);
+    assert_eq!(invite_code("My Cool Server"), None);
+
+    // on either side, since an index can be written either way round
+    let mut listed = index(&[
+        ("12345678901234567", "a"),
+        ("https://discord.gg/b", "b"),
+        ("c", "discord.gg/c"),
+    ]);
+    let invites = take_invites(&mut listed);
+    assert_eq!(listed, index(&[("12345678901234567", "a")]));
+    assert_eq!(
+        invites,
+        [
+            ("discord.gg/c".to_owned(), "c".to_owned()),
+            ("https://discord.gg/b".to_owned(), "b".to_owned()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn resolve_invites() {
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    let invite = json!({ "code": "abc", "guild": { "id": "12345678901234567", "name": "Lounge" } });
+    Mock::given(method("GET"))
+        .and(path("/invites/abc"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(invite))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/invites/gone"))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({ "code": 10006 })))
+        .mount(&server)
+        .await;
+
+    let client = reqwest::Client::new();
+    let resolver = InviteResolver::default().with_api(server.uri());
+    let guild = resolver.resolve(&client, "abc").await.unwrap();
+    assert_eq!(
+        guild,
+        Some(("12345678901234567".to_owned(), "Lounge".to_owned()))
+    );
+    assert_eq!(resolver.resolve(&client, "gone").await.unwrap(), None);
+    let requests = server.received_requests().await.unwrap();
+    assert!(requests[0].headers.get("authorization").is_none());
+}
+
+#[test]
+fn plain_text() {
+    let text = "# from cut\n12345678901234561\tLounge\n\n12345678901234562 Two words\r\n\
+                12345678901234563\n12345678901234561\tLounge again\n";
+    assert!(!is_json(text));
+    let (parsed, duplicates) = parse_lines(text).unwrap();
+    let expected = index(&[
+        ("12345678901234561", "Lounge again"),
+        ("12345678901234562", "Two words"),
+        ("12345678901234563", "12345678901234563"),
+    ]);
+    assert_eq!(parsed, expected);
+    assert_eq!(duplicates, ["12345678901234561"]);
+
+    let error = parse_lines("12345678901234561\nLounge\t12345678901234562\n").unwrap_err();
+    assert_eq!(
+        error,
+        LineError {
+            line: 2,
+            reason: "expected a server ID or invite link first",
+            text: "Lounge\t12345678901234562".to_owned()
+        }
+    );
+    assert!(is_json("\u{feff}\n  {\"12345678901234561\": \"Lounge\"}"));
+}
+
+#[test]
+fn other_formats() {
+    let expected = index(&[
+        ("12345678901234561", "Lounge, the \"good\" one"),
+        ("12345678901234562", "12345678901234562"),
+    ]);
+
+    let csv = "server_id,member_count,name\r\n\
+               12345678901234561,40,\"Lounge, the \"\"good\"\" one\"\n\
+               \n\
+               12345678901234562,3,\n";
+    assert_eq!(parse_csv(csv).unwrap().0, expected);
+    let headless = "12345678901234561,\"Lounge, the \"\"good\"\" one\"\n12345678901234562\n";
+    assert_eq!(parse_csv(headless).unwrap().0, expected);
+    assert_eq!(parse_csv("name,members\nLounge,40\n").unwrap_err().line, 1);
+
+    let yaml = "---\n# exported\n12345678901234561: 'Lounge, the \"good\" one' # comment\n\
+                \"12345678901234562\": \"12345678901234562\"\n";
+    assert_eq!(parse_yaml(yaml).unwrap().0, expected);
+    let nested = "servers:\n  12345678901234561: Lounge\n";
+    assert_eq!(parse_yaml(nested).unwrap_err().line, 1);
+
+    let toml = "12345678901234561 = 'Lounge, the \"good\" one'\n\"12345678901234562\" = \"12345678901234562\"\n";
+    assert_eq!(parse_as(IndexFormat::Toml, toml).unwrap().0, expected);
+
+    // without an extension to go by
+    let detect = |text| IndexFormat::detect(Path::new("-"), text);
+    assert_eq!(detect(csv), IndexFormat::Csv);
+    assert_eq!(detect(headless), IndexFormat::Csv);
+    assert_eq!(detect(yaml), IndexFormat::Yaml);
+    assert_eq!(detect(toml), IndexFormat::Toml);
+    assert_eq!(
+        detect("12345678901234561 Lounge, the good one\n"),
+        IndexFormat::Text
+    );
+    assert_eq!(detect("{}"), IndexFormat::Json);
+    assert_eq!(
+        IndexFormat::detect(Path::new("a.yml"), "{}"),
+        IndexFormat::Yaml
+    );
+}
+
+#[test]
+fn duplicate_keys() {
+    let (parsed, duplicates) = parse(r#"{"1": "a", "2": "b", "1": "c", "1": "d"}"#).unwrap();
+    assert_eq!(parsed, index(&[("1", "d"), ("2", "b")]));
+    assert_eq!(duplicates, ["1"]);
+
+    let (_, duplicates) = parse(r#"{"1": "a", "2": "b"}"#).unwrap();
+    assert!(duplicates.is_empty());
+    assert!(parse(r#"["1", "2"]"#).is_err());
+}
+
+#[test]
+fn id_to_name() {
+    let expected = index(&[("100000000000000001", "My Cool Server")]);
+    let (oriented, orientation) = orient(expected.clone()).unwrap();
+    assert_eq!(orientation, Orientation::IdToName);
+    assert_eq!(oriented, expected);
+}
+
+#[test]
+fn name_to_id() {
+    let flipped = index(&[
+        ("My Cool Server", "100000000000000001"),
+        ("Other", "100000000000000002"),
+    ]);
+    let (oriented, orientation) = orient(flipped).unwrap();
+    assert_eq!(orientation, Orientation::NameToId);
+    assert_eq!(
+        oriented,
+        index(&[
+            ("100000000000000001", "My Cool Server"),
+            ("100000000000000002", "Other"),
+        ])
+    );
+}
+
+#[test]
+fn ambiguous() {
+    let both = index(&[("100000000000000001", "100000000000000002")]);
+    assert_eq!(orient(both).unwrap_err(), OrientError::Ambiguous);
+
+    let neither = index(&[("My Cool Server", "Other")]);
+    assert_eq!(orient(neither).unwrap_err(), OrientError::NoIds);
+
+    // one bad entry is enough to leave it unclear
+    let mixed = index(&[
+        ("100000000000000001", "My Cool Server"),
+        ("Other", "100000000000000002"),
+    ]);
+    assert_eq!(orient(mixed).unwrap_err(), OrientError::NoIds);
+}
+
+#[test]
+fn prefilter_listing() {
+    let now = Utc::now();
+    let formats = [
+        r#"["1", 2, {"id": "3"}]"#,
+        r#"{"1": "a", "2": "b", "3": "c"}"#,
+        "# tracked\n1\n2\n\n3\n",
+    ];
+    for text in formats {
+        let listing = Listing::parse("test", text, now).unwrap();
+        assert_eq!(listing.len(), 3, "{text}");
+        assert!(listing.contains("2"));
+    }
+    assert!(Listing::parse("test", r#"["1", "two"]"#, now).is_err());
+
+    let listing = Listing::parse("test", "1\n3\n", now).unwrap();
+    let (listed, unlisted) = listing.split(index(&[("1", "a"), ("2", "b"), ("3", "c")]));
+    assert_eq!(listed, index(&[("1", "a"), ("3", "c")]));
+    assert_eq!(unlisted, index(&[("2", "b")]));
+
+    let response = listing.unlisted("2".to_owned(), "b".to_owned());
+    assert_eq!(response.status(), Status::Unlisted);
+    assert!(!response.is_compromised());
+    assert_eq!(response.source, SOURCE);
+}
+
+#[tokio::test]
+async fn index_directory() {
+    use wiremock::matchers::path;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    Mock::given(path("/servers/100000000000000001"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "name": "Lounge" })))
+        .mount(&server)
+        .await;
+    Mock::given(path("/servers/100000000000000002"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!(false)))
+        .mount(&server)
+        .await;
+
+    let dir = std::env::temp_dir().join(format!("spy-pet-index-dir-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("alice.json"),
+        r#"{"100000000000000001": "Lounge"}"#,
+    )
+    .unwrap();
+    let bob = "100000000000000001\tThe Lounge\n100000000000000002\tQuiet\n";
+    std::fs::write(dir.join("bob.txt"), bob).unwrap();
+    std::fs::write(dir.join("notes.md"), "not an index").unwrap();
+
+    let output = tokio::process::Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"))
+        .args(["--no-state", "--no-update-check", "--index-path"])
+        .arg(&dir)
+        .arg("--url-template")
+        .arg(format!("{}/servers/{{id}}", server.uri()))
+        .args(["--format", "json"])
+        .output()
+        .await
+        .expect("binary runs");
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(r#"100000000000000001 is "Lounge" in"#),
+        "{stderr}"
+    );
+
+    let output: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let mut names: Vec<&str> = output["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|result| result["guild_name"].as_str().unwrap())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["Lounge", "Quiet"]);
+}
+
+#[test]
+fn validation() {
+    let text = "{\n  \" 100000000000000001\": \" Lounge \",\n  \"lounge\": \"Two\",\n  \"100000000000000002\": \"\",\n  \"100000000000000002\": \"\"\n}\n";
+    let (index, duplicates) = parse(text).unwrap();
+    let index = normalize(index);
+    assert_eq!(index["100000000000000001"], "Lounge");
+    let finding = |line, key: &str, problem| Finding {
+        line: Some(line),
+        key: key.to_owned(),
+        problem,
+    };
+    assert_eq!(
+        validate(text, &index, &duplicates),
+        [
+            finding(3, "lounge", Problem::NotAnId),
+            finding(4, "100000000000000002", Problem::EmptyName),
+            finding(4, "100000000000000002", Problem::Duplicate),
+        ]
+    );
+    assert_eq!(
+        finding(3, "lounge", Problem::NotAnId).to_string(),
+        r#"line 3: "lounge" isn't a Discord ID"#
+    );
+
+    // flipped, with one broken entry
+    let index = BTreeMap::from([
+        ("Lounge".to_owned(), "100000000000000001".to_owned()),
+        ("Quiet".to_owned(), "100000000000000002".to_owned()),
+        ("Broken".to_owned(), "nope".to_owned()),
+    ]);
+    assert_eq!(orient(index.clone()).unwrap_err(), OrientError::NoIds);
+    assert_eq!(mostly_ids(index)["nope"], "Broken");
+}
+
+#[tokio::test]
+async fn validate_command() {
+    let dir = std::env::temp_dir().join(format!("spy-pet-validate-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let index = dir.join("index.json");
+    std::fs::write(
+        &index,
+        "{\n  \"100000000000000001\": \"Lounge\",\n  \"100000000000000002\": \"\"\n}\n",
+    )
+    .unwrap();
+    let validate = |normalize: bool| {
+        let mut command = tokio::process::Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"));
+        command
+            .args(["--no-state", "--no-update-check", "validate", "-i"])
+            .arg(&index);
+        if normalize {
+            command.arg("--normalize");
+        }
+        command.output()
+    };
+
+    let output = validate(false).await.expect("binary runs");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains(r#":3: warning: "100000000000000002" has no name"#),
+        "{stdout}"
+    );
+    let output = validate(true).await.expect("binary runs");
+    let normalized: BTreeMap<String, String> = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(normalized["100000000000000002"], "100000000000000002");
+
+    std::fs::write(&index, r#"{"100000000000000001": "Lounge", "lounge": "x"}"#).unwrap();
+    let output = validate(false).await.expect("binary runs");
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn streamed() {
+    let json = r#"{"100000000000000001": "Lounge", "lounge": "x", "100000000000000001": "Again"}"#;
+    let mut entries = Vec::new();
+    parse_reader(json.as_bytes(), |key, value| entries.push((key, value))).unwrap();
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[1], ("lounge".to_owned(), "x".to_owned()));
+    assert_eq!(parse_from(json.as_bytes()).unwrap(), parse(json).unwrap());
+
+    assert!(parse_reader(r#"{"a": "b"} trailing"#.as_bytes(), |_, _| {}).is_err());
+    assert!(parse_reader(r#"["a", "b"]"#.as_bytes(), |_, _| {}).is_err());
+}
@@ -0,0 +1,103 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use spy_pet_checker::mail::{check_address, Mail, Security, Smtp};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+/// Answers an SMTP session the way a relay would, and hands back every line
+/// it was sent
+async fn relay(listener: TcpListener) -> Vec<String> {
+    let (stream, _) = listener.accept().await.unwrap();
+    let (read, mut write) = stream.into_split();
+    let mut read = BufReader::new(read);
+    write.write_all(b"220 relay ready\r\n").await.unwrap();
+    let mut lines = Vec::new();
+    let mut data = false;
+    loop {
+        let mut line = String::new();
+        if read.read_line(&mut line).await.unwrap() == 0 {
+            break;
+        }
+        let line = line.trim_end().to_owned();
+        let reply: &[u8] = match line.as_str() {
+            "." if data => {
+                data = false;
+                b"250 queued\r\n"
+            }
+            _ if data => b"",
+            "EHLO localhost" => b"250-relay\r\n250 AUTH PLAIN\r\n",
+            "DATA" => {
+                data = true;
+                b"354 go ahead\r\n"
+            }
+            "QUIT" => b"221 bye\r\n",
+            _ if line.starts_with("AUTH PLAIN ") => b"235 welcome\r\n",
+            _ => b"250 ok\r\n",
+        };
+        lines.push(line);
+        write.write_all(reply).await.unwrap();
+    }
+    lines
+}
+
+#[tokio::test]
+async fn send() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let relay = tokio::spawn(relay(listener));
+
+    let mail = Mail {
+        from: "checker@example.com".to_owned(),
+        to: vec!["admin@example.com".to_owned(), "mod@example.com".to_owned()],
+        subject: "spy-pet-checker: 1 compromised server in a check of 7".to_owned(),
+        body: "g2 (ID: 2) is compromised!\n.\n".to_owned(),
+        html: false,
+    };
+    Smtp::new("127.0.0.1", Some(port), Security::None)
+        .with_login("checker".to_owned(), "hunter2".to_owned().into())
+        .send(&mail)
+        .await
+        .unwrap();
+    let lines = relay.await.unwrap();
+
+    let login = STANDARD.encode("\0checker\0hunter2");
+    assert_eq!(lines[0], "EHLO localhost");
+    assert_eq!(lines[1], format!("AUTH PLAIN {login}"));
+    assert_eq!(lines[2], "MAIL FROM:<checker@example.com>");
+    assert_eq!(lines[3], "RCPT TO:<admin@example.com>");
+    assert_eq!(lines[4], "RCPT TO:<mod@example.com>");
+    assert_eq!(lines[5], "DATA");
+    assert_eq!(lines.last().unwrap(), "QUIT");
+    let message = &lines[6..lines.len() - 2];
+    assert!(message.contains(&"To: admin@example.com, mod@example.com".to_owned()));
+    assert!(message.contains(&"Content-Type: text/plain; charset=utf-8".to_owned()));
+
+    // the body is base64, so its lone dot doesn't end the message early
+    let blank = message.iter().position(String::is_empty).unwrap();
+    let body = STANDARD.decode(message[blank + 1..].concat()).unwrap();
+    assert_eq!(String::from_utf8(body).unwrap(), mail.body);
+}
+
+#[test]
+fn addresses() {
+    assert!(check_address("admin@example.com").is_ok());
+    assert!(check_address("admin").is_err());
+    assert!(check_address("admin@example.com>\r\nRCPT TO:<x@y").is_err());
+}
+
+#[test]
+fn plaintext_login_refused() {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"))
+        .args(["--no-state", "--no-update-check", "--simulate", "1"])
+        .args(["--email-to", "admin@example.com"])
+        .args(["--email-from", "checker@example.com"])
+        .args(["--smtp-server", "mail.example.com:25"])
+        .args(["--smtp-security", "none", "--smtp-username", "checker"])
+        .env("SPY_PET_SMTP_PASSWORD", "hunter2")
+        .output()
+        .expect("binary runs");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success(), "{stderr}");
+    assert!(stderr.contains("unencrypted"), "{stderr}");
+}
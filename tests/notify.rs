@@ -0,0 +1,218 @@
+use spy_pet_checker::discord::{result_message, Bot, Webhook, SLASH_COMMAND};
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use reqwest::Method;
+use serde_json::{json, Value};
+use spy_pet_checker::notify::{Callback, NotifyOn, Payload, RunSummary};
+use spy_pet_checker::retry::RetryPolicy;
+use spy_pet_checker::Response;
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[test]
+fn conditions() {
+    let quiet = RunSummary::default();
+    let known = RunSummary {
+        compromised: 2,
+        ..Default::default()
+    };
+    let new = RunSummary {
+        compromised: 2,
+        new_compromised: 1,
+        ..Default::default()
+    };
+    let failing = RunSummary {
+        errors: 3,
+        ..Default::default()
+    };
+
+    let cases = [
+        (NotifyOn::Always, [true, true, true, true]),
+        (NotifyOn::Compromised, [false, true, true, false]),
+        (NotifyOn::NewCompromised, [false, false, true, false]),
+        (NotifyOn::Errors, [false, false, false, true]),
+        (NotifyOn::Never, [false, false, false, false]),
+    ];
+    for (on, expected) in cases {
+        let fired = [quiet, known, new, failing].map(|summary| on.fires(&summary));
+        assert_eq!(fired, expected, "{on:?}");
+    }
+}
+
+fn compromised(id: u64) -> Response {
+    serde_json::from_value(json!({
+        "guild_id": id.to_string(),
+        "guild_name": format!("guild {id}"),
+        "source": "spy.pet",
+        "api_response": { "messages": id * 100 },
+    }))
+    .unwrap()
+}
+
+#[tokio::test]
+async fn discord_webhook() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/webhooks/1/token"))
+        .respond_with(ResponseTemplate::new(204))
+        .mount(&server)
+        .await;
+
+    let guilds: Vec<Response> = (1..=12).map(compromised).collect();
+    let guilds: Vec<&Response> = guilds.iter().collect();
+    let webhook = Webhook::new(format!("{}/api/webhooks/1/token", server.uri()).into());
+    let payload = Payload {
+        summary: "12 compromised servers",
+        checked: 12,
+        errors: 0,
+        findings: &guilds,
+        notification: None,
+    };
+    webhook
+        .post(&reqwest::Client::new(), &payload)
+        .await
+        .unwrap();
+
+    // Discord takes ten embeds a message
+    let messages: Vec<Value> = server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|request| serde_json::from_slice(&request.body).unwrap())
+        .collect();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0]["content"], "12 compromised servers");
+    assert!(messages[1].get("content").is_none());
+    assert_eq!(messages[0]["embeds"].as_array().unwrap().len(), 10);
+    assert_eq!(messages[1]["embeds"].as_array().unwrap().len(), 2);
+
+    let embed = &messages[0]["embeds"][0];
+    assert_eq!(embed["title"], "guild 1");
+    assert_eq!(embed["fields"][0]["value"], "1");
+    assert_eq!(embed["fields"][1]["name"], "Archived messages");
+    assert_eq!(embed["fields"][1]["value"], "100");
+}
+
+#[tokio::test]
+async fn callback() {
+    let server = MockServer::start().await;
+    // the first try fails, the retry gets through
+    Mock::given(method("PUT"))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("PUT"))
+        .and(path("/alerts"))
+        .and(header("authorization", "Bearer secret"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&server)
+        .await;
+
+    let guilds = [compromised(2)];
+    let guilds: Vec<&Response> = guilds.iter().collect();
+    let payload = Payload {
+        summary: "1 compromised server in a check of 7",
+        checked: 7,
+        errors: 1,
+        findings: &guilds,
+        notification: None,
+    };
+    let mut headers = HeaderMap::new();
+    headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer secret"));
+    Callback::new(format!("{}/alerts", server.uri()).into())
+        .with_method(Method::PUT)
+        .with_headers(headers)
+        .with_retry(RetryPolicy::new(1, Duration::from_millis(10)))
+        .send(&reqwest::Client::new(), &payload)
+        .await
+        .unwrap();
+
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 2);
+    let sent: Value = serde_json::from_slice(&requests[1].body).unwrap();
+    assert_eq!(sent["summary"], "1 compromised server in a check of 7");
+    assert_eq!(sent["checked"], 7);
+    assert_eq!(sent["errors"], 1);
+    assert_eq!(sent["findings"][0]["guild_id"], "2");
+}
+
+#[tokio::test]
+async fn bot() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/guilds/2"))
+        .and(header("authorization", "Bot token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "owner_id": "80" })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/users/@me/channels"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": "90" })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/channels/90/messages"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": "100" })))
+        .mount(&server)
+        .await;
+
+    let client = reqwest::Client::new();
+    let bot = Bot::new("token".to_owned().into()).with_api(server.uri());
+    let channel = bot.owner_dm(&client, "2").await.unwrap();
+    assert_eq!(channel, "90");
+    bot.post(&client, &channel, &compromised(2)).await.unwrap();
+
+    let requests = server.received_requests().await.unwrap();
+    let dm: Value = serde_json::from_slice(&requests[1].body).unwrap();
+    assert_eq!(dm["recipient_id"], "80");
+    let message: Value = serde_json::from_slice(&requests[2].body).unwrap();
+    assert_eq!(message["content"], "guild 2 is in the spy.pet dataset.");
+    assert_eq!(message["embeds"][0]["title"], "guild 2");
+}
+
+#[tokio::test]
+async fn slash_command() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/applications/@me"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": "70" })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/applications/70/commands"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": "71" })))
+        .mount(&server)
+        .await;
+    Mock::given(method("PATCH"))
+        .and(path("/webhooks/70/interaction/messages/@original"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": "72" })))
+        .mount(&server)
+        .await;
+
+    let client = reqwest::Client::new();
+    let bot = Bot::new("token".to_owned().into()).with_api(server.uri());
+    let application = bot.application_id(&client).await.unwrap();
+    bot.register_command(&client, &application).await.unwrap();
+    let mut result = compromised(2);
+    result.api_response = json!({ "messages": 200, "first_seen": "2023-09-01T00:00:00Z" });
+    bot.answer(
+        &client,
+        &application,
+        "interaction",
+        &result_message(&result),
+    )
+    .await
+    .unwrap();
+
+    let requests = server.received_requests().await.unwrap();
+    let command: Value = serde_json::from_slice(&requests[1].body).unwrap();
+    assert_eq!(command["name"], SLASH_COMMAND);
+    let answer: Value = serde_json::from_slice(&requests[2].body).unwrap();
+    let fields = &answer["embeds"][0]["fields"];
+    assert_eq!(fields[1]["value"], "200");
+    assert_eq!(fields[2]["name"], "First seen");
+    assert_eq!(fields[2]["value"], "<t:1693526400:D>");
+}
@@ -0,0 +1,160 @@
+use chrono::{TimeDelta, Utc};
+use reqwest::{Client, StatusCode};
+use serde_json::json;
+use spy_pet_checker::oauth::{self, OAuthApp, OAuthError, OAuthToken, RedirectListener};
+use spy_pet_checker::secret::Secret;
+use wiremock::matchers::{body_string_contains, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn token_response(access: &str, refresh: Option<&str>) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({
+        "access_token": access,
+        "token_type": "Bearer",
+        "expires_in": 604800,
+        "refresh_token": refresh,
+        "scope": "guilds",
+    }))
+}
+
+#[tokio::test]
+async fn redirect_and_exchange() {
+    let discord = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/oauth2/token"))
+        .and(body_string_contains("grant_type=authorization_code"))
+        .and(body_string_contains("code=the-code"))
+        .and(body_string_contains("client_id=1234"))
+        .respond_with(token_response("access", Some("refresh")))
+        .mount(&discord)
+        .await;
+
+    let app = OAuthApp::new("1234", None).with_api(discord.uri());
+    let listener = RedirectListener::bind(0).await.unwrap();
+    let redirect_uri = listener.redirect_uri();
+    let url = app.authorize_url(&redirect_uri, "xyz");
+    assert!(url.contains("scope=guilds"), "{url}");
+    assert!(url.contains("state=xyz"), "{url}");
+
+    let browser = tokio::spawn({
+        let redirect_uri = redirect_uri.clone();
+        async move {
+            let client = Client::new();
+            let base = redirect_uri.trim_end_matches(oauth::CALLBACK_PATH);
+            // asked for along the way, and ignored
+            let favicon = client.get(format!("{base}/favicon.ico")).send().await;
+            assert_eq!(favicon.unwrap().status(), StatusCode::NOT_FOUND);
+            let page = client
+                .get(format!("{redirect_uri}?code=the-code&state=xyz"))
+                .send()
+                .await
+                .unwrap();
+            assert!(page.status().is_success());
+        }
+    });
+    let code = listener.code("xyz").await.unwrap();
+    browser.await.unwrap();
+    assert_eq!(code, "the-code");
+
+    let token = app
+        .exchange(&Client::new(), &code, &redirect_uri)
+        .await
+        .unwrap();
+    assert_eq!(token.access_token.expose(), "access");
+    assert!(!token.is_expired());
+}
+
+#[tokio::test]
+async fn redirect_for_another_login() {
+    let listener = RedirectListener::bind(0).await.unwrap();
+    let redirect_uri = listener.redirect_uri();
+    tokio::spawn(async move {
+        let _ = reqwest::get(format!("{redirect_uri}?code=the-code&state=other")).await;
+    });
+    let err = listener.code("xyz").await.unwrap_err();
+    assert!(matches!(err, OAuthError::StateMismatch), "{err}");
+}
+
+#[test]
+fn random_states() {
+    let state = oauth::random_state();
+    // 256 bits, base64url without padding
+    assert_eq!(state.len(), 43);
+    assert!(state
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_'));
+    assert_ne!(state, oauth::random_state());
+}
+
+#[tokio::test]
+async fn stored_token_is_refreshed() {
+    let discord = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/oauth2/token"))
+        .and(body_string_contains("grant_type=refresh_token"))
+        .and(body_string_contains("refresh_token=old-refresh"))
+        .respond_with(token_response("new-access", None))
+        .expect(1)
+        .mount(&discord)
+        .await;
+
+    let dir = std::env::temp_dir().join(format!("spy-pet-oauth-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("discord-oauth.json");
+    assert!(oauth::stored_access_token(&Client::new(), &path)
+        .await
+        .unwrap()
+        .is_none());
+
+    let expired = OAuthToken {
+        access_token: Secret::new("old-access".to_owned()),
+        refresh_token: Some(Secret::new("old-refresh".to_owned())),
+        expires_at: Utc::now() - TimeDelta::hours(1),
+        scope: "guilds".to_owned(),
+        client_id: "1234".to_owned(),
+        client_secret: None,
+    };
+    let token = OAuthApp::of(&expired)
+        .with_api(discord.uri())
+        .refresh(&Client::new(), &expired)
+        .await
+        .unwrap();
+    assert_eq!(token.access_token.expose(), "new-access");
+    // Discord gave no new refresh token, so the old one stays
+    assert_eq!(
+        token.refresh_token.as_ref().unwrap().expose(),
+        "old-refresh"
+    );
+
+    token.save(&path).unwrap();
+    let loaded = oauth::stored_access_token(&Client::new(), &path)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(loaded.expose(), "new-access");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn refused_grant() {
+    let discord = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/oauth2/token"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "error": "invalid_grant",
+            "error_description": "Invalid \"code\" in request.",
+        })))
+        .mount(&discord)
+        .await;
+    let app = OAuthApp::new("1234", Some(Secret::new("s".to_owned()))).with_api(discord.uri());
+    let err = app
+        .exchange(&Client::new(), "stale", "http://127.0.0.1:1/callback")
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("Invalid \"code\""), "{err}");
+}
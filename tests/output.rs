@@ -0,0 +1,211 @@
+use serde_json::json;
+use tokio::process::Command;
+use wiremock::matchers::path;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[tokio::test]
+async fn output_modes() {
+    let server = MockServer::start().await;
+    Mock::given(path("/servers/100000000000000001"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "name": "Leaky" })))
+        .mount(&server)
+        .await;
+
+    let dir = std::env::temp_dir().join(format!("spy-pet-output-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let index = dir.join("index.json");
+    std::fs::write(&index, r#"{"100000000000000001": "Leaky"}"#).unwrap();
+    let output = dir.join("results.ndjson");
+    let run = |args: &[&str]| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"));
+        command
+            .args(["--no-state", "--no-update-check", "--index-path"])
+            .arg(&index)
+            .arg("--url-template")
+            .arg(format!("{}/servers/{{id}}", server.uri()))
+            .args(["--retries", "0", "--format", "ndjson", "--output"])
+            .arg(&output)
+            .args(args);
+        async move {
+            let output = command.output().await.expect("binary runs");
+            assert!(
+                output.status.success(),
+                "{}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+    };
+    let lines = || std::fs::read_to_string(&output).unwrap().lines().count();
+
+    // created when it isn't there yet
+    run(&[]).await;
+    assert_eq!(lines(), 1);
+    run(&["--append"]).await;
+    assert_eq!(lines(), 2);
+    run(&[]).await;
+    assert_eq!(lines(), 1);
+
+    std::fs::write(&output, "the previous report\n".repeat(3)).unwrap();
+    run(&["--atomic"]).await;
+    assert_eq!(lines(), 1);
+    let files: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+    assert_eq!(files.len(), 2, "a temporary file was left behind");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn several_outputs() {
+    let server = MockServer::start().await;
+    Mock::given(path("/servers/100000000000000001"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "name": "Leaky" })))
+        .mount(&server)
+        .await;
+    Mock::given(path("/servers/100000000000000002"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!(false)))
+        .mount(&server)
+        .await;
+
+    let dir = std::env::temp_dir().join(format!("spy-pet-outputs-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let index = dir.join("index.json");
+    std::fs::write(
+        &index,
+        r#"{"100000000000000001": "Leaky", "100000000000000002": "Quiet"}"#,
+    )
+    .unwrap();
+    let (json, ndjson) = (dir.join("results.json"), dir.join("results.ndjson"));
+    let output = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"))
+        .args(["--no-state", "--no-update-check", "--index-path"])
+        .arg(&index)
+        .arg("--url-template")
+        .arg(format!("{}/servers/{{id}}", server.uri()))
+        .args(["--format", "json", "-o"])
+        .arg(&json)
+        .args(["--format", "ndjson", "-o"])
+        .arg(&ndjson)
+        .args(["--format", "plain"])
+        .env("SPY_PET_LANG", "en")
+        .output()
+        .await
+        .expect("binary runs");
+    assert!(output.status.success());
+
+    // one pass, every format
+    assert_eq!(server.received_requests().await.unwrap().len(), 2);
+    let document: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&json).unwrap()).unwrap();
+    assert_eq!(document["results"].as_array().unwrap().len(), 2);
+    let lines = std::fs::read_to_string(&ndjson).unwrap();
+    assert_eq!(lines.lines().count(), 2);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Leaky (ID: 100000000000000001) is compromised!"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn dry_run() {
+    let server = MockServer::start().await;
+    let ids: Vec<String> = (1..=7).map(|i| format!("10000000000000000{i}")).collect();
+    let output = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"))
+        .args(["--no-state", "--no-update-check", "--dry-run", "--all-urls"])
+        .arg("--url-template")
+        .arg(format!("{}/servers/{{id}}", server.uri()))
+        .args(["--ids", &ids.join(","), "--format", "json", "-o"])
+        .arg(std::env::temp_dir().join("spy-pet-dry-run.json"))
+        .args(["--format", "plain"])
+        .output()
+        .await
+        .expect("binary runs");
+    assert!(output.status.success());
+
+    assert!(server.received_requests().await.unwrap().is_empty());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let urls = stdout.lines().filter(|line| line.starts_with("  GET "));
+    assert_eq!(urls.count(), 7, "{stdout}");
+    assert!(stdout.contains("Proxy: none"));
+    assert!(stdout.contains("Output: plain to stdout"));
+    assert!(!std::env::temp_dir().join("spy-pet-dry-run.json").exists());
+}
+
+#[tokio::test]
+async fn unauthorized_midway() {
+    let server = MockServer::start().await;
+    Mock::given(path("/servers/100000000000000001"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "name": "Leaky" })))
+        .mount(&server)
+        .await;
+    Mock::given(path("/servers/100000000000000002"))
+        .respond_with(ResponseTemplate::new(401))
+        .mount(&server)
+        .await;
+
+    let dir = std::env::temp_dir().join(format!("spy-pet-unauthorized-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let json = dir.join("results.json");
+    std::fs::write(&json, "the previous report\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"))
+        .args(["--no-state", "--no-update-check", "--concurrency", "1"])
+        .args(["--ids", "100000000000000001,100000000000000002"])
+        .arg("--url-template")
+        .arg(format!("{}/servers/{{id}}", server.uri()))
+        .args(["--retries", "0", "--format", "json", "--output"])
+        .arg(&json)
+        .output()
+        .await
+        .expect("binary runs");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("it needs an API key"), "{stderr}");
+
+    // the report is still whole JSON, saying it's partial
+    let document: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&json).unwrap()).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(document["cancelled"], true);
+    assert_eq!(document["results"][0]["guild_id"], "100000000000000001");
+    assert_eq!(document["errors"][0]["guild_id"], "100000000000000002");
+}
+
+#[tokio::test]
+async fn failures_listed_once() {
+    let server = MockServer::start().await;
+    Mock::given(path("/servers/100000000000000001"))
+        .respond_with(ResponseTemplate::new(502))
+        .mount(&server)
+        .await;
+
+    let dir = std::env::temp_dir().join(format!("spy-pet-failures-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let json = dir.join("results.json");
+    let run = |args: &[&str]| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"));
+        command
+            .args(["--no-state", "--no-update-check"])
+            .args(["--ids", "100000000000000001", "--url-template"])
+            .arg(format!("{}/servers/{{id}}", server.uri()))
+            .args(["--retries", "0", "--retry-passes", "0"])
+            .args(args);
+        async move { command.output().await.expect("binary runs") }
+    };
+    let line = "100000000000000001 (ID: 100000000000000001) on";
+
+    // the plain output lists it, so the summary only counts it
+    let output = run(&["--format", "plain"]).await;
+    let (stdout, stderr) = (
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr),
+    );
+    assert!(
+        stdout.contains("1 server couldn't be checked:\n"),
+        "{stdout}"
+    );
+    assert_eq!(stdout.matches(line).count(), 1, "{stdout}");
+    assert!(stderr.contains("Errors: 1 "), "{stderr}");
+    assert!(!stderr.contains(line), "{stderr}");
+
+    // with the results in a file, the summary is all there is on the terminal
+    let output = run(&["--format", "json", "-o", json.to_str().unwrap()]).await;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.matches(line).count(), 1, "{stderr}");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
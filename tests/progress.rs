@@ -0,0 +1,206 @@
+use serde_json::{json, Value};
+use tokio::process::Command;
+use wiremock::matchers::path;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[tokio::test]
+async fn json_progress_events() {
+    let server = MockServer::start().await;
+    Mock::given(path("/servers/100000000000000001"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("false"))
+        .mount(&server)
+        .await;
+    Mock::given(path("/servers/100000000000000002"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "name": "Leaky" })))
+        .mount(&server)
+        .await;
+    Mock::given(path("/servers/100000000000000003"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&server)
+        .await;
+
+    let dir = std::env::temp_dir().join(format!("spy-pet-progress-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let index = dir.join("index.json");
+    std::fs::write(&index, r#"{"100000000000000001": "Clean", "100000000000000002": "Leaky", "100000000000000003": "Broken"}"#).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"))
+        .arg("--no-state")
+        .arg("--index-path")
+        .arg(&index)
+        .arg("--url-template")
+        .arg(format!("{}/servers/{{id}}", server.uri()))
+        .args(["--progress-format", "json"])
+        .env("RUST_LOG", "debug")
+        .output()
+        .await
+        .expect("binary runs");
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(output.status.success());
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let events: Vec<Value> = stderr
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap_or_else(|_| panic!("not JSON: {line}")))
+        .collect();
+
+    assert_eq!(events.len(), 5, "{stderr}");
+    assert_eq!(events[0], json!({ "event": "start", "total": 3 }));
+    for (n, event) in events[1..4].iter().enumerate() {
+        assert_eq!(event["done"], n + 1);
+        assert_eq!(event["total"], 3);
+    }
+
+    let status = |id: &str| {
+        events
+            .iter()
+            .find(|e| e["event"] == "result" && e["id"] == id)
+            .map(|e| e["status"].clone())
+    };
+    assert_eq!(status("100000000000000001"), Some(json!("clean")));
+    assert_eq!(status("100000000000000002"), Some(json!("compromised")));
+
+    let error = events.iter().find(|e| e["event"] == "error").unwrap();
+    assert_eq!(error["kind"], "http_status");
+
+    assert_eq!(
+        events[4],
+        json!({
+            "event": "finished",
+            "done": 3,
+            "total": 3,
+            "compromised": 1,
+            "errors": 1,
+            "cancelled": false,
+        })
+    );
+}
+
+#[tokio::test]
+async fn detailed_progress_events() {
+    let server = MockServer::start().await;
+    Mock::given(path("/servers/100000000000000001"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("false"))
+        .mount(&server)
+        .await;
+    Mock::given(path("/servers/100000000000000002"))
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&server)
+        .await;
+
+    let dir = std::env::temp_dir().join(format!("spy-pet-events-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let index = dir.join("index.json");
+    std::fs::write(
+        &index,
+        r#"{"100000000000000001": "Clean", "100000000000000002": "Down"}"#,
+    )
+    .unwrap();
+    let events_path = dir.join("events.ndjson");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"))
+        .arg("--no-state")
+        .arg("--index-path")
+        .arg(&index)
+        .arg("--url-template")
+        .arg(format!("{}/servers/{{id}}", server.uri()))
+        .args(["--retries", "1", "--backoff", "10ms", "--progress-events"])
+        .arg(&events_path)
+        .output()
+        .await
+        .expect("binary runs");
+    assert!(output.status.success());
+    let written = std::fs::read_to_string(&events_path).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    // the logs stay on stderr
+    assert!(!output.stderr.is_empty());
+
+    let events: Vec<Value> = written
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap_or_else(|_| panic!("not JSON: {line}")))
+        .collect();
+    let of = |kind: &str| -> Vec<&Value> { events.iter().filter(|e| e["event"] == kind).collect() };
+    assert_eq!(events[0], json!({ "event": "start", "total": 2 }));
+    assert_eq!(
+        events[1],
+        json!({ "event": "queued", "id": "100000000000000001", "name": "Clean" })
+    );
+    assert_eq!(of("queued").len(), 2);
+    assert_eq!(events.last().unwrap()["event"], "finished");
+
+    // a request for the clean server, and two for the one that's down, then
+    // two more for it in the retry pass at the end
+    assert_eq!(of("request_started").len(), 5);
+    let finished = of("request_finished");
+    assert_eq!(finished.len(), 5);
+    let clean = finished
+        .iter()
+        .find(|e| e["id"] == "100000000000000001")
+        .unwrap();
+    assert_eq!(clean["outcome"], "clean");
+    assert_eq!(clean["http_status"], 200);
+    assert_eq!(clean["attempt"], 1);
+    let down: Vec<&&Value> = finished
+        .iter()
+        .filter(|e| e["id"] == "100000000000000002")
+        .collect();
+    let attempts: Vec<&Value> = down.iter().map(|e| &e["attempt"]).collect();
+    assert_eq!(attempts, [1, 2, 1, 2]);
+    for request in &down {
+        assert_eq!(request["outcome"], "error");
+        assert_eq!(request["http_status"], 503);
+    }
+    // the failure is only reported once the retry pass has given up too
+    let position = |event: &Value| events.iter().position(|e| e == event).unwrap();
+    let error = of("error")[0];
+    assert_eq!(error["id"], "100000000000000002");
+    assert!(position(error) > position(down[3]));
+    assert_eq!(of("result").len(), 1);
+    assert_eq!(of("error").len(), 1);
+}
+
+#[tokio::test]
+async fn progress_bar() {
+    let server = MockServer::start().await;
+    Mock::given(path("/servers/100000000000000001"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "name": "Leaky" })))
+        .mount(&server)
+        .await;
+    Mock::given(path("/servers/100000000000000002"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&server)
+        .await;
+
+    let dir = std::env::temp_dir().join(format!("spy-pet-progress-bar-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let index = dir.join("index.json");
+    std::fs::write(
+        &index,
+        r#"{"100000000000000001": "Leaky", "100000000000000002": "Broken"}"#,
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"))
+        .arg("--no-state")
+        .arg("--index-path")
+        .arg(&index)
+        .arg("--url-template")
+        .arg(format!("{}/servers/{{id}}", server.uri()))
+        .args(["--retries", "0", "--progress-format", "bar"])
+        .output()
+        .await
+        .expect("binary runs");
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(output.status.success());
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("2/2 checked") && stderr.contains("1 compromised, 1 errors"),
+        "{stderr}"
+    );
+    // the logs are written over the bar, not after it on the same line
+    for line in stderr.lines().filter(|line| line.contains("INFO")) {
+        let logged = line.rsplit("\r\x1b[2K").next().unwrap();
+        assert!(!logged.contains("checked,"), "{line:?}");
+    }
+}
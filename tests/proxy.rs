@@ -0,0 +1,195 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use spy_pet_checker::backend::SpyPet;
+use spy_pet_checker::proxy::Proxy;
+use spy_pet_checker::secret::Secret;
+use spy_pet_checker::tor::{CircuitRotation, TorControl, TorError};
+use spy_pet_checker::{check_guilds, CheckOptions};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const GUILD: &str = "100000000000000001";
+
+async fn api() -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/servers/{GUILD}")))
+        .respond_with(ResponseTemplate::new(200).set_body_string("false"))
+        .mount(&server)
+        .await;
+    server
+}
+
+/// A SOCKS5 proxy wanting `user` and `pass`, sending every connection to
+/// `target` whatever host it was for, counting them
+async fn socks_proxy(target: std::net::SocketAddr) -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let connections = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&connections);
+    tokio::spawn(async move {
+        loop {
+            let (mut client, _) = listener.accept().await.unwrap();
+            counter.fetch_add(1, Ordering::SeqCst);
+            tokio::spawn(async move {
+                let mut greeting = [0; 2];
+                client.read_exact(&mut greeting).await.unwrap();
+                let mut methods = vec![0; greeting[1] as usize];
+                client.read_exact(&mut methods).await.unwrap();
+                assert!(methods.contains(&2));
+                client.write_all(&[5, 2]).await.unwrap();
+
+                let mut auth = [0; 2];
+                client.read_exact(&mut auth).await.unwrap();
+                let mut user = vec![0; auth[1] as usize];
+                client.read_exact(&mut user).await.unwrap();
+                let mut pass = vec![0; client.read_u8().await.unwrap() as usize];
+                client.read_exact(&mut pass).await.unwrap();
+                let ok = user == b"user" && pass == b"pass";
+                client.write_all(&[1, u8::from(!ok)]).await.unwrap();
+                if !ok {
+                    return;
+                }
+
+                let mut request = [0; 4];
+                client.read_exact(&mut request).await.unwrap();
+                // a host name, left to the proxy to look up
+                assert_eq!(request[3], 3);
+                let mut host = vec![0; client.read_u8().await.unwrap() as usize];
+                client.read_exact(&mut host).await.unwrap();
+                assert_eq!(host, b"api.invalid");
+                client.read_u16().await.unwrap();
+                client
+                    .write_all(&[5, 0, 0, 1, 127, 0, 0, 1, 0, 0])
+                    .await
+                    .unwrap();
+                let mut server = TcpStream::connect(target).await.unwrap();
+                let _ = tokio::io::copy_bidirectional(&mut client, &mut server).await;
+            });
+        }
+    });
+    (format!("socks5h://user@{addr}"), connections)
+}
+
+#[tokio::test]
+async fn socks5() {
+    let server = api().await;
+    let (url, connections) = socks_proxy(*server.address()).await;
+    let password = Secret::new("pass".to_owned());
+    let proxy = Proxy::new(&url, Some(&password)).unwrap();
+    assert!(!proxy.to_string().contains("pass"));
+    let options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(format!(
+            "http://api.invalid:{}",
+            server.address().port()
+        )))],
+        proxy: Some(proxy),
+        ..Default::default()
+    };
+    let report = check_guilds([(GUILD.to_owned(), "guild".to_owned())], &options).await;
+    assert!(report.failed.is_empty(), "{:?}", report.failed);
+    assert_eq!(report.results.len(), 1);
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+    let wrong = Secret::new("wrong".to_owned());
+    let options = CheckOptions {
+        proxy: Some(Proxy::new(&url, Some(&wrong)).unwrap()),
+        ..options
+    };
+    let report = check_guilds([(GUILD.to_owned(), "guild".to_owned())], &options).await;
+    assert_eq!(report.failed.len(), 1);
+}
+
+#[tokio::test]
+async fn http_proxy() {
+    // answers as the proxy, for a host that doesn't exist
+    let proxy = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/servers/{GUILD}")))
+        .and(header("proxy-authorization", "Basic dXNlcjpwYXNz"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("false"))
+        .mount(&proxy)
+        .await;
+    let password = Secret::new("pass".to_owned());
+    let url = format!("http://user@{}", proxy.address());
+    let options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new("http://api.invalid"))],
+        proxy: Some(Proxy::new(&url, Some(&password)).unwrap()),
+        ..Default::default()
+    };
+    let report = check_guilds([(GUILD.to_owned(), "guild".to_owned())], &options).await;
+    assert!(report.failed.is_empty(), "{:?}", report.failed);
+}
+
+#[test]
+fn bad_proxies() {
+    assert!(Proxy::new("ftp://proxy.example", None).is_err());
+    assert!(Proxy::new("not a url", None).is_err());
+}
+
+/// A Tor control port wanting `password`, counting the NEWNYMs it gets
+async fn control_port(password: &'static str) -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let newnyms = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&newnyms);
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let counter = Arc::clone(&counter);
+            tokio::spawn(async move {
+                let (read, mut write) = stream.into_split();
+                let mut lines = tokio::io::BufReader::new(read).lines();
+                let mut authenticated = false;
+                while let Some(line) = lines.next_line().await.unwrap() {
+                    let reply = match line.as_str() {
+                        "PROTOCOLINFO 1" => "250-PROTOCOLINFO 1\r\n250-AUTH METHODS=HASHEDPASSWORD\r\n250-VERSION Tor=\"0.4.8.10\"\r\n250 OK\r\n".to_owned(),
+                        line if line.starts_with("AUTHENTICATE") => {
+                            authenticated = line == format!("AUTHENTICATE \"{password}\"");
+                            match authenticated {
+                                true => "250 OK\r\n".to_owned(),
+                                false => "515 Authentication failed\r\n".to_owned(),
+                            }
+                        }
+                        "SIGNAL NEWNYM" if authenticated => {
+                            counter.fetch_add(1, Ordering::SeqCst);
+                            "250 OK\r\n".to_owned()
+                        }
+                        "QUIT" => return,
+                        _ => "514 Authentication required\r\n".to_owned(),
+                    };
+                    write.write_all(reply.as_bytes()).await.unwrap();
+                }
+            });
+        }
+    });
+    (addr, newnyms)
+}
+
+#[tokio::test]
+async fn tor_circuit_rotation() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("false"))
+        .mount(&server)
+        .await;
+    let (addr, newnyms) = control_port("hunter2").await;
+
+    let wrong = TorControl::new(&addr, Some(Secret::new("wrong".to_owned())));
+    assert!(matches!(wrong.new_circuit().await, Err(TorError::Auth(_))));
+    assert_eq!(newnyms.load(Ordering::SeqCst), 0);
+
+    let control = TorControl::new(&addr, Some(Secret::new("hunter2".to_owned())));
+    let options = CheckOptions {
+        backends: vec![Arc::new(SpyPet::new(server.uri()))],
+        tor: Some(CircuitRotation::new(control, Some(2), false)),
+        ..Default::default()
+    };
+    let guilds = (1..=5).map(|i| (format!("10000000000000000{i}"), format!("guild {i}")));
+    let report = check_guilds(guilds, &options).await;
+    assert_eq!(report.results.len(), 5);
+    assert_eq!(newnyms.load(Ordering::SeqCst), 2);
+}
@@ -0,0 +1,149 @@
+use std::process::{Command, Output};
+
+use spy_pet_checker::secret::Secret;
+use wiremock::matchers::{header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const TOKEN: &str = "MTAxMDEwMTAx.hunter2.c2VjcmV0";
+
+fn run(args: &[&str], token: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"))
+        .arg("--no-state")
+        .args(args)
+        .env("SPY_PET_DISCORD_TOKEN", token)
+        .env("RUST_LOG", "trace")
+        .env("RUST_BACKTRACE", "1")
+        .output()
+        .expect("binary runs")
+}
+
+fn captured(output: &Output) -> String {
+    format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    )
+}
+
+#[test]
+fn secret_formatting() {
+    let secret = Secret::new(TOKEN.to_owned());
+    assert_eq!(format!("{secret}"), "[redacted]");
+    assert_eq!(format!("{secret:?}"), "[redacted]");
+    assert_eq!(secret.expose(), TOKEN);
+}
+
+#[test]
+fn token_not_logged() {
+    let output = run(&["token", "status"], TOKEN);
+    let text = captured(&output);
+
+    assert!(output.status.success(), "{text}");
+    assert!(text.contains("[redacted]"), "{text}");
+    assert!(!text.contains("hunter2"), "{text}");
+}
+
+#[test]
+fn token_not_in_error_chain() {
+    let output = run(&["token", "status"], &format!("{TOKEN} trailing"));
+    let text = captured(&output);
+
+    assert!(!output.status.success(), "{text}");
+    assert!(text.contains("contains whitespace"), "{text}");
+    assert!(!text.contains("hunter2"), "{text}");
+}
+
+#[test]
+fn webhook_token_not_logged() {
+    // nothing listens on the discard port, so the post fails
+    let webhook = "http://127.0.0.1:9/api/webhooks/123/SECRETTOKEN";
+    let output = run(
+        &[
+            "--no-update-check",
+            "--simulate",
+            "1",
+            "--simulate-compromised",
+            "100",
+            "--simulate-latency",
+            "1ms",
+            "--discord-webhook",
+            webhook,
+        ],
+        TOKEN,
+    );
+    let text = captured(&output);
+
+    assert!(
+        text.contains("couldn't reach the Discord webhook"),
+        "{text}"
+    );
+    assert!(!text.contains("SECRETTOKEN"), "{text}");
+}
+
+#[test]
+fn notify_url_token_not_logged() {
+    let output = run(
+        &[
+            "--no-update-check",
+            "--simulate",
+            "1",
+            "--simulate-compromised",
+            "100",
+            "--simulate-latency",
+            "1ms",
+            "--notify-url",
+            "http://127.0.0.1:9/topic?auth=SECRETTOKEN",
+            "--notify-retries",
+            "0",
+        ],
+        TOKEN,
+    );
+    let text = captured(&output);
+
+    assert!(text.contains("couldn't send to --notify-url"), "{text}");
+    assert!(!text.contains("SECRETTOKEN"), "{text}");
+}
+
+#[tokio::test]
+async fn notify_secrets_from_files() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/topic"))
+        .and(query_param("auth", "SECRETTOKEN"))
+        .and(header("authorization", "Bearer SECRETHEADER"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let dir = std::env::temp_dir().join(format!("spy-pet-notify-files-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let url_file = dir.join("url");
+    let header_file = dir.join("headers");
+    let url = format!("{}/topic?auth=SECRETTOKEN\n", server.uri());
+    std::fs::write(&url_file, url).unwrap();
+    std::fs::write(&header_file, "Authorization: Bearer SECRETHEADER\n").unwrap();
+
+    let output = run(
+        &[
+            "--no-update-check",
+            "--simulate",
+            "1",
+            "--simulate-compromised",
+            "100",
+            "--simulate-latency",
+            "1ms",
+            "--notify-url-file",
+            url_file.to_str().unwrap(),
+            "--notify-header-file",
+            header_file.to_str().unwrap(),
+        ],
+        TOKEN,
+    );
+    let text = captured(&output);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(text.contains("sent to --notify-url"), "{text}");
+    assert!(!text.contains("SECRETTOKEN"), "{text}");
+    assert!(!text.contains("SECRETHEADER"), "{text}");
+}
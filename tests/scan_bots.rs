@@ -0,0 +1,50 @@
+use tokio::process::Command;
+use wiremock::matchers::path;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[tokio::test]
+async fn update_list() {
+    let server = MockServer::start().await;
+    Mock::given(path("/known_bots.txt"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_string("# a comment\n100000000000000001 Scraper\n100000000000000002\n"),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(path("/broken.txt"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("<html>not a list</html>\n"))
+        .mount(&server)
+        .await;
+
+    let dir = std::env::temp_dir().join(format!("spy-pet-scan-bots-{}", std::process::id()));
+    let run = |list: &str| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"));
+        command
+            .arg("--state-dir")
+            .arg(&dir)
+            .args([
+                "--no-update-check",
+                "scan-bots",
+                "--update-list",
+                "--list-url",
+            ])
+            .arg(format!("{}/{list}", server.uri()));
+        async move { command.output().await.expect("binary runs") }
+    };
+
+    let output = run("known_bots.txt").await;
+    assert!(output.status.success(), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("2 known scraper bots"));
+    let saved = std::fs::read_to_string(dir.join("known_bots.txt")).unwrap();
+    assert!(saved.contains("100000000000000001 Scraper"));
+
+    // the list that's there is kept
+    let output = run("broken.txt").await;
+    assert!(!output.status.success());
+    assert_eq!(
+        std::fs::read_to_string(dir.join("known_bots.txt")).unwrap(),
+        saved
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,79 @@
+use serde_json::json;
+use spy_pet_checker::search::{rank, similarity};
+use tokio::process::Command;
+use wiremock::matchers::{path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[test]
+fn ranking() {
+    assert_eq!(similarity("Art Club!", "art club"), 1.0);
+    assert!(similarity("Art Club", "The Art Club") > similarity("Art Club", "Art Clan"));
+    assert!(similarity("Art Club", "Gaming Hub") < 0.3);
+
+    let found = [
+        ("3", "Gaming Hub"),
+        ("2", "The Art Club"),
+        ("1", "art club"),
+        ("1", "art club"),
+    ]
+    .map(|(id, name)| (id.to_owned(), name.to_owned()));
+    let candidates = rank("Art Club", found);
+    let ids: Vec<&str> = candidates.iter().map(|c| c.id.as_str()).collect();
+    assert_eq!(ids, ["1", "2"]);
+}
+
+#[tokio::test]
+async fn search_by_name() {
+    let server = MockServer::start().await;
+    Mock::given(path("/search"))
+        .and(query_param("q", "Art Club"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            { "id": "100000000000000002", "name": "The Art Club" },
+            { "id": "100000000000000001", "name": "Art Club" },
+            { "id": "100000000000000003", "name": "Gaming Hub" },
+        ])))
+        .mount(&server)
+        .await;
+    let search_url = format!("{}/search?q={{query}}", server.uri());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"))
+        .args(["--no-state", "--no-update-check", "--search", "Art Club"])
+        .args(["--search-url", &search_url])
+        .output()
+        .await
+        .expect("binary runs");
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines[0], "\"Art Club\": 2 candidates");
+    assert!(lines[1].contains("Art Club (ID: 100000000000000001, 100% alike)"));
+    assert!(lines[2].contains("The Art Club (ID: 100000000000000002"));
+
+    // a name-only entry is searched for and reported, not checked
+    let dir = std::env::temp_dir().join(format!("spy-pet-search-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let index = dir.join("index.txt");
+    std::fs::write(&index, "100000000000000009 Known\n? Art Club\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_spy-pet-checker"))
+        .args(["--no-state", "--no-update-check", "--index-path"])
+        .arg(&index)
+        .args(["--search-url", &search_url])
+        .arg("--url-template")
+        .arg(format!("{}/servers/{{id}}", server.uri()))
+        .output()
+        .await
+        .expect("binary runs");
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stdout.contains("Art Club"), "{stdout}");
+    assert!(
+        stderr.contains("\"Art Club\" has no ID, skipping it; the search found Art Club (ID: 100000000000000001"),
+        "{stderr}"
+    );
+    let checked: Vec<_> = server.received_requests().await.unwrap();
+    assert!(checked
+        .iter()
+        .any(|request| request.url.path() == "/servers/100000000000000009"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
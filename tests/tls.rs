@@ -0,0 +1,28 @@
+use spy_pet_checker::{build_client, CheckOptions, TLS_BACKEND};
+
+fn options_with_ca() -> CheckOptions {
+    let pem = std::fs::read(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/ca.pem"
+    ))
+    .unwrap();
+    CheckOptions {
+        ca_certs: vec![reqwest::Certificate::from_pem(&pem).unwrap()],
+        insecure: true,
+        ..Default::default()
+    }
+}
+
+#[cfg(feature = "rustls")]
+#[test]
+fn rustls_client() {
+    assert_eq!(TLS_BACKEND, "rustls");
+    build_client(&options_with_ca()).unwrap();
+}
+
+#[cfg(feature = "native-tls")]
+#[test]
+fn native_tls_client() {
+    assert_eq!(TLS_BACKEND, "native-tls");
+    build_client(&options_with_ca()).unwrap();
+}
@@ -0,0 +1,42 @@
+use serde_json::{json, Value};
+use spy_pet_checker::web::scrape;
+
+fn fixture(name: &str) -> Value {
+    let path = format!("{}/tests/fixtures/web/{name}", env!("CARGO_MANIFEST_DIR"));
+    scrape(&std::fs::read_to_string(path).unwrap())
+}
+
+#[test]
+fn tracked() {
+    let expected = json!({ "tracked": true, "name": "Leaky Lounge", "messages": 48213 });
+    assert_eq!(fixture("tracked.html"), expected);
+}
+
+#[test]
+fn tracked_without_attributes() {
+    // the title is the fallback for the name
+    let expected = json!({ "tracked": true, "name": "Leaky Lounge - spy.pet", "messages": 48213 });
+    assert_eq!(fixture("tracked_text.html"), expected);
+}
+
+#[test]
+fn untracked() {
+    assert_eq!(fixture("untracked.html"), json!(false));
+}
+
+#[test]
+fn unrecognised_pages() {
+    assert_eq!(fixture("challenge.html"), Value::Null);
+    assert_eq!(fixture("redesigned.html"), Value::Null);
+}
+
+#[test]
+fn attributes_in_any_form() {
+    let page = r#"<DIV class=card DATA-TRACKED=false data-messages-total="3">"#;
+    assert_eq!(scrape(page), json!(false));
+    let page = "<span data-message-count='1,024'></span>";
+    assert_eq!(scrape(page), json!({ "tracked": true, "messages": 1024 }));
+    // two different counts, so neither is trusted
+    let page = "<p>12 messages here</p><footer>5,000,000 messages archived</footer>";
+    assert_eq!(scrape(page), Value::Null);
+}
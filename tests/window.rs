@@ -0,0 +1,43 @@
+use serde_json::json;
+use spy_pet_checker::window::{parse_date, InWindow, Window};
+
+#[test]
+fn dates() {
+    let midnight = parse_date("2024-04-01").unwrap();
+    assert_eq!(midnight, parse_date("2024-04-01T00:00:00Z").unwrap());
+    assert_eq!(parse_date("2024-04-01T02:00:00+02:00").unwrap(), midnight);
+    assert!(parse_date("April 1st").is_err());
+}
+
+#[test]
+fn activity_against_window() {
+    let window = Window {
+        since: Some(parse_date("2024-04-01").unwrap()),
+        until: Some(parse_date("2024-05-01").unwrap()),
+    };
+    let check = |answer| window.check(&answer);
+
+    let during = json!({ "first_seen": "2024-03-01T00:00:00Z", "last_seen": "2024-04-02" });
+    assert_eq!(check(during), InWindow::Inside);
+    let before = json!({ "firstSeen": "2024-01-01", "lastSeen": "2024-03-31" });
+    assert_eq!(check(before), InWindow::Outside);
+    let after = json!({ "first_seen": "2024-05-02", "last_seen": "2024-06-01" });
+    assert_eq!(check(after), InWindow::Outside);
+    // Unix timestamps are read too, and one of the two is enough
+    assert_eq!(check(json!({ "last_seen": 1712102400 })), InWindow::Inside);
+    assert_eq!(check(json!({ "name": "Leaky" })), InWindow::NoTimestamps);
+    assert_eq!(
+        check(json!({ "last_seen": "yesterday" })),
+        InWindow::NoTimestamps
+    );
+}
+
+#[test]
+fn open_ended() {
+    let window = Window {
+        since: Some(parse_date("2024-04-01").unwrap()),
+        until: None,
+    };
+    let answer = json!({ "first_seen": "2030-01-01", "last_seen": "2030-01-02" });
+    assert_eq!(window.check(&answer), InWindow::Inside);
+}